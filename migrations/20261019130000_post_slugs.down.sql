@@ -0,0 +1,6 @@
+DROP INDEX blog_posts_slug;
+
+ALTER TABLE blog_posts
+    DROP COLUMN slug,
+    DROP COLUMN draft,
+    DROP COLUMN published_at;
@@ -0,0 +1,7 @@
+-- Slugs identify posts authored outside the API, e.g. Markdown files in git.
+ALTER TABLE blog_posts
+    ADD COLUMN slug TEXT,
+    ADD COLUMN draft BOOLEAN NOT NULL DEFAULT FALSE,
+    ADD COLUMN published_at TIMESTAMPTZ;
+
+CREATE UNIQUE INDEX blog_posts_slug ON blog_posts (slug);
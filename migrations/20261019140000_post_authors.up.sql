@@ -0,0 +1,2 @@
+-- Authors are free text, e.g. the display names of posts imported from WordPress.
+ALTER TABLE blog_posts ADD COLUMN author TEXT;
@@ -0,0 +1,24 @@
+-- Media files are stored by content hash, so identical uploads share one stored blob.
+CREATE TABLE media (
+    id SERIAL PRIMARY KEY,
+    owner TEXT,
+    filename TEXT NOT NULL,
+    content_type TEXT NOT NULL,
+    size BIGINT NOT NULL,
+    content_hash TEXT NOT NULL,
+    width INTEGER,
+    height INTEGER,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX media_content_hash ON media (content_hash);
+CREATE INDEX media_owner ON media (owner);
+
+CREATE TABLE post_media (
+    post_id INTEGER NOT NULL REFERENCES blog_posts (id) ON DELETE CASCADE,
+    media_id INTEGER NOT NULL REFERENCES media (id) ON DELETE CASCADE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (post_id, media_id)
+);
+
+CREATE INDEX post_media_media_id ON post_media (media_id);
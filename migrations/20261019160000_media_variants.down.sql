@@ -0,0 +1,2 @@
+DROP TABLE media_variants;
+ALTER TABLE media DROP COLUMN variants_status;
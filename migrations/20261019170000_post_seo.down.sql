@@ -0,0 +1,6 @@
+ALTER TABLE blog_posts
+    DROP COLUMN featured_image_id,
+    DROP COLUMN meta_title,
+    DROP COLUMN meta_description,
+    DROP COLUMN canonical_url,
+    DROP COLUMN noindex;
@@ -0,0 +1,10 @@
+-- Featured images and the fields shared links and search engines are shown. A deleted
+-- media file leaves the posts featuring it without a featured image.
+ALTER TABLE blog_posts
+    ADD COLUMN featured_image_id INTEGER REFERENCES media (id) ON DELETE SET NULL,
+    ADD COLUMN meta_title TEXT,
+    ADD COLUMN meta_description TEXT,
+    ADD COLUMN canonical_url TEXT,
+    ADD COLUMN noindex BOOLEAN NOT NULL DEFAULT FALSE;
+
+CREATE INDEX blog_posts_featured_image_id ON blog_posts (featured_image_id);
@@ -0,0 +1,18 @@
+-- Outbox of domain events, written in the same transaction as the changes to
+-- `blog_posts` they describe. `aggregate_id` is the ID of the post, whose events are
+-- dispatched in `id` order. Undispatched events are due at `next_attempt_at`, which is
+-- pushed back while the event is being dispatched and after each failed attempt.
+CREATE TABLE domain_events (
+    id BIGSERIAL PRIMARY KEY,
+    aggregate_id INTEGER NOT NULL,
+    event_type TEXT NOT NULL,
+    payload JSONB NOT NULL,
+    occurred_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    attempts INTEGER NOT NULL DEFAULT 0,
+    next_attempt_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    last_error TEXT,
+    dispatched_at TIMESTAMPTZ
+);
+
+CREATE INDEX domain_events_pending ON domain_events (aggregate_id, id)
+    WHERE dispatched_at IS NULL;
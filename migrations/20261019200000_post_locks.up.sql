@@ -0,0 +1,10 @@
+-- Advisory edit locks on blog posts, at most one per post. A lock is held until
+-- `expires_at`, which its holder pushes back with heartbeats; an expired lock may be
+-- taken over. Updates of a locked post must carry the lock's `token`.
+CREATE TABLE post_locks (
+    post_id INTEGER PRIMARY KEY REFERENCES blog_posts (id) ON DELETE CASCADE,
+    holder TEXT NOT NULL,
+    token TEXT NOT NULL,
+    acquired_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    expires_at TIMESTAMPTZ NOT NULL
+);
@@ -0,0 +1 @@
+DROP INDEX domain_events_publications;
@@ -0,0 +1,4 @@
+-- Finds the last `post.published` or `post.unpublished` event of a post, which tells
+-- whether a post whose scheduled publication date has passed was announced.
+CREATE INDEX domain_events_publications ON domain_events (aggregate_id, id)
+    WHERE event_type IN ('post.published', 'post.unpublished');
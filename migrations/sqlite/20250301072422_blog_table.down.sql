@@ -0,0 +1,5 @@
+DROP TRIGGER blog_posts_fts_delete;
+DROP TRIGGER blog_posts_fts_update;
+DROP TRIGGER blog_posts_fts_insert;
+DROP TABLE blog_posts_fts;
+DROP TABLE blog_posts;
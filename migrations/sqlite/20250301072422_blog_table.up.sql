@@ -0,0 +1,31 @@
+-- Tags are stored as a JSON array of strings, e.g. '["rust","async"]'.
+CREATE TABLE blog_posts (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    title TEXT NOT NULL,
+    content TEXT NOT NULL,
+    category TEXT NOT NULL,
+    tags TEXT NOT NULL DEFAULT '[]' CHECK (json_valid(tags) AND json_type(tags) = 'array'),
+    created_at TEXT DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now')),
+    updated_at TEXT DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now'))
+);
+
+-- Full-text index used by search. The trigram tokenizer matches substrings case-insensitively,
+-- like the ILIKE search of the Postgres backend. Tags are indexed comma-separated.
+CREATE VIRTUAL TABLE blog_posts_fts USING fts5(title, content, tags, tokenize = 'trigram');
+
+CREATE TRIGGER blog_posts_fts_insert AFTER INSERT ON blog_posts BEGIN
+    INSERT INTO blog_posts_fts (rowid, title, content, tags)
+    VALUES (new.id, new.title, new.content, (SELECT group_concat(value, ',') FROM json_each(new.tags)));
+END;
+
+CREATE TRIGGER blog_posts_fts_update AFTER UPDATE ON blog_posts BEGIN
+    UPDATE blog_posts_fts
+    SET title = new.title,
+        content = new.content,
+        tags = (SELECT group_concat(value, ',') FROM json_each(new.tags))
+    WHERE rowid = old.id;
+END;
+
+CREATE TRIGGER blog_posts_fts_delete AFTER DELETE ON blog_posts BEGIN
+    DELETE FROM blog_posts_fts WHERE rowid = old.id;
+END;
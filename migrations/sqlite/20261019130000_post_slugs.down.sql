@@ -0,0 +1,5 @@
+DROP INDEX blog_posts_slug;
+
+ALTER TABLE blog_posts DROP COLUMN slug;
+ALTER TABLE blog_posts DROP COLUMN draft;
+ALTER TABLE blog_posts DROP COLUMN published_at;
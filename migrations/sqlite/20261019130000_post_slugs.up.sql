@@ -0,0 +1,6 @@
+-- Slugs identify posts authored outside the API, e.g. Markdown files in git.
+ALTER TABLE blog_posts ADD COLUMN slug TEXT;
+ALTER TABLE blog_posts ADD COLUMN draft INTEGER NOT NULL DEFAULT 0 CHECK (draft IN (0, 1));
+ALTER TABLE blog_posts ADD COLUMN published_at TEXT;
+
+CREATE UNIQUE INDEX blog_posts_slug ON blog_posts (slug);
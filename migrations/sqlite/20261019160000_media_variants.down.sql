@@ -0,0 +1,3 @@
+DROP INDEX media_variants_status;
+DROP TABLE media_variants;
+ALTER TABLE media DROP COLUMN variants_status;
@@ -0,0 +1,19 @@
+-- Resized and re-encoded copies of uploaded images, generated in the background. Files
+-- uploaded before variants existed have nothing to wait for, hence `ready`.
+ALTER TABLE media ADD COLUMN variants_status TEXT NOT NULL DEFAULT 'ready';
+
+CREATE TABLE media_variants (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    media_id INTEGER NOT NULL REFERENCES media (id) ON DELETE CASCADE,
+    name TEXT NOT NULL,
+    content_type TEXT NOT NULL,
+    size INTEGER NOT NULL,
+    content_hash TEXT NOT NULL,
+    width INTEGER NOT NULL,
+    height INTEGER NOT NULL,
+    created_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now')),
+    UNIQUE (media_id, name)
+);
+
+CREATE INDEX media_variants_content_hash ON media_variants (content_hash);
+CREATE INDEX media_variants_status ON media (variants_status);
@@ -0,0 +1,7 @@
+DROP TRIGGER media_featured_image_delete;
+DROP INDEX blog_posts_featured_image_id;
+ALTER TABLE blog_posts DROP COLUMN featured_image_id;
+ALTER TABLE blog_posts DROP COLUMN meta_title;
+ALTER TABLE blog_posts DROP COLUMN meta_description;
+ALTER TABLE blog_posts DROP COLUMN canonical_url;
+ALTER TABLE blog_posts DROP COLUMN noindex;
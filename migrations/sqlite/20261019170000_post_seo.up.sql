@@ -0,0 +1,14 @@
+-- Featured images and the fields shared links and search engines are shown.
+-- SQLite cannot drop a column used by a foreign key, so instead of one a trigger leaves
+-- the posts featuring a deleted media file without a featured image.
+ALTER TABLE blog_posts ADD COLUMN featured_image_id INTEGER;
+ALTER TABLE blog_posts ADD COLUMN meta_title TEXT;
+ALTER TABLE blog_posts ADD COLUMN meta_description TEXT;
+ALTER TABLE blog_posts ADD COLUMN canonical_url TEXT;
+ALTER TABLE blog_posts ADD COLUMN noindex INTEGER NOT NULL DEFAULT 0 CHECK (noindex IN (0, 1));
+
+CREATE INDEX blog_posts_featured_image_id ON blog_posts (featured_image_id);
+
+CREATE TRIGGER media_featured_image_delete AFTER DELETE ON media BEGIN
+    UPDATE blog_posts SET featured_image_id = NULL WHERE featured_image_id = old.id;
+END;
@@ -0,0 +1,19 @@
+-- Outbox of domain events, written in the same transaction as the changes to
+-- `blog_posts` they describe. `aggregate_id` is the ID of the post, whose events are
+-- dispatched in `id` order. Undispatched events are due at `next_attempt_at`, in Unix
+-- milliseconds, which is pushed back while the event is being dispatched and after
+-- each failed attempt.
+CREATE TABLE domain_events (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    aggregate_id INTEGER NOT NULL,
+    event_type TEXT NOT NULL,
+    payload TEXT NOT NULL,
+    occurred_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now')),
+    attempts INTEGER NOT NULL DEFAULT 0,
+    next_attempt_at INTEGER NOT NULL,
+    last_error TEXT,
+    dispatched_at TEXT
+);
+
+CREATE INDEX domain_events_pending ON domain_events (aggregate_id, id)
+    WHERE dispatched_at IS NULL;
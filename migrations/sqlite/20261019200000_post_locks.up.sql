@@ -0,0 +1,11 @@
+-- Advisory edit locks on blog posts, at most one per post. A lock is held until
+-- `expires_at`, in Unix milliseconds, which its holder pushes back with heartbeats; an
+-- expired lock may be taken over. Updates of a locked post must carry the lock's
+-- `token`.
+CREATE TABLE post_locks (
+    post_id INTEGER PRIMARY KEY REFERENCES blog_posts (id) ON DELETE CASCADE,
+    holder TEXT NOT NULL,
+    token TEXT NOT NULL,
+    acquired_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now')),
+    expires_at INTEGER NOT NULL
+);
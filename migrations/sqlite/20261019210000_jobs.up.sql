@@ -0,0 +1,26 @@
+-- Background jobs, run by the workers of their `queue`. Queued jobs are due at
+-- `run_at`, in Unix milliseconds, which is pushed back after each failed attempt. A
+-- running job is reserved for its worker until `locked_until`, in Unix milliseconds,
+-- which the worker extends while the job runs; past it, the job is claimed again.
+-- `attempts` counts the claims and tells apart the attempts of a job. Jobs that failed
+-- every attempt are kept as `dead` until they are retried or purged. A `unique_key`
+-- enqueues a job at most once, such as for each run of a recurring job.
+CREATE TABLE jobs (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    queue TEXT NOT NULL,
+    kind TEXT NOT NULL,
+    payload TEXT NOT NULL,
+    status TEXT NOT NULL DEFAULT 'queued',
+    attempts INTEGER NOT NULL DEFAULT 0,
+    max_attempts INTEGER NOT NULL,
+    run_at INTEGER NOT NULL,
+    locked_until INTEGER,
+    last_error TEXT,
+    unique_key TEXT UNIQUE,
+    created_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now')),
+    finished_at TEXT
+);
+
+CREATE INDEX jobs_queued ON jobs (queue, run_at) WHERE status = 'queued';
+CREATE INDEX jobs_running ON jobs (queue, locked_until) WHERE status = 'running';
+CREATE INDEX jobs_finished ON jobs (finished_at) WHERE finished_at IS NOT NULL;
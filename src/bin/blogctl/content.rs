@@ -0,0 +1,263 @@
+use anyhow::{Context, Result, bail};
+use blog_api::{
+    content,
+    media::DynMediaRepository,
+    model::{blog::BlogPostBody, export::ExportFormat, import::WordPressItemStatus},
+    repository::{DynPostRepository, PostFilter},
+};
+use clap::{Args, ValueEnum};
+use futures_util::TryStreamExt;
+use std::{
+    fs::{self, File},
+    io::{self, BufRead, BufReader, BufWriter, Read, Write},
+    path::{Path, PathBuf},
+};
+use validator::Validate;
+
+/// File format of `import`.
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum ImportFormat {
+    /// One JSON post per line, as written by `export`.
+    Ndjson,
+    /// A Markdown file with YAML front matter, or a tar, tar.gz or zip archive of them.
+    Markdown,
+    /// A WordPress eXtended RSS (WXR) export.
+    Wordpress,
+}
+
+/// Arguments of `import`.
+#[derive(Debug, Args)]
+pub struct ImportArgs {
+    /// File to import, or `-` for stdin.
+    ///
+    /// NDJSON posts with a slug update the post with that slug, if there is one; other
+    /// posts are created with new IDs. Markdown and WordPress posts are always matched
+    /// by slug.
+    file: PathBuf,
+    /// File format.
+    #[arg(long, value_enum, default_value_t = ImportFormat::Ndjson)]
+    format: ImportFormat,
+    /// Only report what a WordPress import would do, without storing anything.
+    #[arg(long)]
+    dry_run: bool,
+}
+
+/// File format of `export`.
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum Format {
+    /// One JSON object per line, readable by `import`.
+    Ndjson,
+    /// Comma-separated values with a header row and `|`-separated tags.
+    Csv,
+    /// A tar archive of Markdown files with YAML front matter, readable by
+    /// `import --format markdown`.
+    Markdown,
+}
+
+impl From<Format> for ExportFormat {
+    fn from(format: Format) -> Self {
+        match format {
+            Format::Ndjson => Self::Ndjson,
+            Format::Csv => Self::Csv,
+            Format::Markdown => Self::Markdown,
+        }
+    }
+}
+
+/// Arguments of `export`.
+#[derive(Debug, Args)]
+pub struct ExportArgs {
+    /// File to write, or `-` for stdout.
+    #[arg(long, short, default_value = "-")]
+    output: PathBuf,
+    /// File format.
+    #[arg(long, value_enum, default_value_t = Format::Ndjson)]
+    format: Format,
+    /// Only export posts in this category.
+    #[arg(long)]
+    category: Option<String>,
+    /// Only export posts carrying this tag.
+    #[arg(long)]
+    tag: Option<String>,
+}
+
+/// Returns whether a path means stdin or stdout.
+fn is_stdio(path: &Path) -> bool {
+    path.as_os_str() == "-"
+}
+
+/// Imports posts, reporting the lines or files that could not be imported.
+pub async fn import(
+    posts: &DynPostRepository,
+    media: &DynMediaRepository,
+    args: ImportArgs,
+) -> Result<()> {
+    match args.format {
+        ImportFormat::Wordpress => import_wordpress(posts, &args.file, args.dry_run).await,
+        _ if args.dry_run => bail!("--dry-run is only supported with --format wordpress"),
+        ImportFormat::Ndjson => import_ndjson(posts, &args.file).await,
+        ImportFormat::Markdown => import_markdown(posts, media, &args.file).await,
+    }
+}
+
+/// Reads a whole file, or stdin.
+fn read_input(path: &Path) -> Result<Vec<u8>> {
+    if is_stdio(path) {
+        let mut data = Vec::new();
+        io::stdin()
+            .read_to_end(&mut data)
+            .context("Failed to read input")?;
+        Ok(data)
+    } else {
+        fs::read(path).with_context(|| format!("Failed to read {}", path.display()))
+    }
+}
+
+/// Imports posts from NDJSON, one post per line.
+async fn import_ndjson(posts: &DynPostRepository, path: &Path) -> Result<()> {
+    let reader: Box<dyn BufRead> = if is_stdio(path) {
+        Box::new(io::stdin().lock())
+    } else {
+        let file =
+            File::open(path).with_context(|| format!("Failed to open {}", path.display()))?;
+        Box::new(BufReader::new(file))
+    };
+
+    let (mut imported, mut failed) = (0_usize, 0_usize);
+    for (index, line) in reader.lines().enumerate() {
+        let line = line.context("Failed to read input")?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let result = serde_json::from_str::<BlogPostBody>(&line)
+            .map_err(anyhow::Error::from)
+            .and_then(|body| body.validate().map(|()| body).map_err(Into::into));
+        let body = match result {
+            Ok(body) => body,
+            Err(err) => {
+                eprintln!("line {}: {err}", index + 1);
+                failed += 1;
+                continue;
+            }
+        };
+        let post = if body.slug.is_some() {
+            posts.upsert_by_slug(&body).await?.post().clone()
+        } else {
+            posts.create(&body).await?
+        };
+        tracing::debug!(id = post.id, "imported post");
+        imported += 1;
+    }
+
+    println!("Imported {imported} post(s)");
+    if failed > 0 {
+        bail!("{failed} line(s) could not be imported");
+    }
+    Ok(())
+}
+
+/// Imports Markdown files with YAML front matter, upserting each post by its slug.
+async fn import_markdown(
+    posts: &DynPostRepository,
+    media: &DynMediaRepository,
+    path: &Path,
+) -> Result<()> {
+    let data = read_input(path)?;
+    let file_name = path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .filter(|_| !is_stdio(path))
+        .unwrap_or(content::DEFAULT_FILE_NAME);
+
+    let report = content::import_markdown(posts, media, &data, file_name).await?;
+    for result in &report.results {
+        match (&result.error, result.id) {
+            (Some(error), _) => eprintln!("{}: {error}", result.file),
+            (None, Some(id)) => tracing::debug!(id, file = result.file, "imported post"),
+            (None, None) => {}
+        }
+    }
+    println!(
+        "Created {} and updated {} post(s)",
+        report.created, report.updated
+    );
+    if report.failed > 0 {
+        bail!("{} file(s) could not be imported", report.failed);
+    }
+    Ok(())
+}
+
+/// Imports the posts of a WordPress export, printing the status, slug and original
+/// permalink of each post so that redirects can be set up.
+async fn import_wordpress(posts: &DynPostRepository, path: &Path, dry_run: bool) -> Result<()> {
+    let data = read_input(path)?;
+    let report = content::import_wordpress(posts, &data, dry_run).await?;
+    for result in &report.results {
+        let status = match result.status {
+            WordPressItemStatus::Created => "created",
+            WordPressItemStatus::Updated => "updated",
+            WordPressItemStatus::Skipped => {
+                tracing::debug!(title = result.title, reason = result.reason, "skipped item");
+                continue;
+            }
+            WordPressItemStatus::Invalid => {
+                let reason = result.reason.as_deref().unwrap_or_default();
+                eprintln!("{}: {reason}", result.title);
+                continue;
+            }
+        };
+        println!(
+            "{status:<8}  {:<40}  {}",
+            result.slug.as_deref().unwrap_or_default(),
+            result.link.as_deref().unwrap_or_default()
+        );
+    }
+    let (created, updated) = if dry_run {
+        ("Would create", "update")
+    } else {
+        ("Created", "updated")
+    };
+    println!(
+        "{created} {} and {updated} {} post(s); skipped {} item(s)",
+        report.created, report.updated, report.skipped
+    );
+    if report.failed > 0 {
+        bail!("{} post(s) could not be imported", report.failed);
+    }
+    Ok(())
+}
+
+/// Exports posts as NDJSON, CSV or Markdown, streaming them from the storage.
+pub async fn export(posts: &DynPostRepository, args: ExportArgs) -> Result<()> {
+    let format = ExportFormat::from(args.format);
+    let mut stream = posts.stream(&PostFilter {
+        category: args.category,
+        tag: args.tag,
+    });
+
+    let writer: Box<dyn Write> = if is_stdio(&args.output) {
+        Box::new(io::stdout().lock())
+    } else {
+        let file = File::create(&args.output)
+            .with_context(|| format!("Failed to create {}", args.output.display()))?;
+        Box::new(file)
+    };
+    let mut writer = BufWriter::new(writer);
+    if let Some(header) = format.header() {
+        writer.write_all(&header)?;
+    }
+    let mut exported = 0_usize;
+    while let Some(post) = stream.try_next().await? {
+        writer.write_all(&format.encode(&post)?)?;
+        exported += 1;
+    }
+    if let Some(footer) = format.footer() {
+        writer.write_all(&footer)?;
+    }
+    writer.flush()?;
+
+    if !is_stdio(&args.output) {
+        println!("Exported {exported} post(s) to {}", args.output.display());
+    }
+    Ok(())
+}
@@ -0,0 +1,103 @@
+//! `blogctl`, the command-line tool for operating a blog.
+//!
+//! It reads the same configuration as the server (`DATABASE_URL` and the other variables,
+//! optionally from `.env`) and works on the same database.
+
+use anyhow::{Context, Result};
+use blog_api::{
+    database::connection::{Database, db_open},
+    media::{DynMediaRepository, postgres::PgMediaRepository, sqlite::SqliteMediaRepository},
+    repository::{DynPostRepository, postgres::PgPostRepository, sqlite::SqlitePostRepository},
+};
+use clap::{Parser, Subcommand};
+use std::sync::Arc;
+use tracing_subscriber::EnvFilter;
+
+/// Content import and export.
+mod content;
+/// Migration commands.
+mod migrate;
+/// Post management commands.
+mod posts;
+/// Static site generation.
+mod site;
+
+/// Operate a blog: migrations, posts, search indexes, content and static sites.
+#[derive(Debug, Parser)]
+#[command(name = "blogctl", version)]
+struct Cli {
+    /// The command to run.
+    #[command(subcommand)]
+    command: Command,
+}
+
+/// Top-level commands.
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Run, revert or inspect database migrations.
+    #[command(subcommand)]
+    Migrate(migrate::MigrateCommand),
+    /// List, show, create, publish or delete posts.
+    #[command(subcommand)]
+    Posts(posts::PostsCommand),
+    /// Rebuild the search index from the stored posts.
+    Reindex,
+    /// Import posts from a file.
+    Import(content::ImportArgs),
+    /// Export posts to a file.
+    Export(content::ExportArgs),
+    /// Render the published posts as a static website.
+    Site(site::SiteArgs),
+}
+
+/// Builds the post repository for a database.
+fn repository(database: &Database) -> DynPostRepository {
+    match database {
+        Database::Postgres(pool) => Arc::new(PgPostRepository::new(pool.clone())),
+        Database::Sqlite(pool) => Arc::new(SqlitePostRepository::new(pool.clone())),
+    }
+}
+
+/// Builds the media file repository for a database.
+fn media_repository(database: &Database) -> DynMediaRepository {
+    match database {
+        Database::Postgres(pool) => Arc::new(PgMediaRepository::new(pool.clone())),
+        Database::Sqlite(pool) => Arc::new(SqliteMediaRepository::new(pool.clone())),
+    }
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    if let Err(err) = dotenv::dotenv()
+        && !err.not_found()
+    {
+        return Err(err).context("Failed to load .env file");
+    }
+    tracing_subscriber::fmt()
+        .with_env_filter(EnvFilter::try_from_default_env().or_else(|_| EnvFilter::try_new("warn"))?)
+        .with_writer(std::io::stderr)
+        .init();
+
+    let cli = Cli::parse();
+    let database = db_open().await?;
+
+    // Migration commands manage the schema themselves; everything else needs it current.
+    if let Command::Migrate(command) = cli.command {
+        return migrate::run(&database, command).await;
+    }
+    database.migrate().await?;
+    let posts = repository(&database);
+
+    match cli.command {
+        Command::Migrate(_) => Ok(()),
+        Command::Posts(command) => posts::run(&posts, command).await,
+        Command::Reindex => {
+            posts.rebuild_search_index().await?;
+            println!("Search index rebuilt");
+            Ok(())
+        }
+        Command::Import(args) => content::import(&posts, &media_repository(&database), args).await,
+        Command::Export(args) => content::export(&posts, args).await,
+        Command::Site(args) => site::build(&posts, args).await,
+    }
+}
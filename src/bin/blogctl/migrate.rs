@@ -0,0 +1,106 @@
+use anyhow::{Context, Result, bail};
+use blog_api::database::connection::Database;
+use clap::Subcommand;
+use sqlx::{
+    Pool,
+    migrate::{AppliedMigration, Migrate},
+};
+
+/// Migration commands.
+#[derive(Debug, Subcommand)]
+pub enum MigrateCommand {
+    /// Apply every pending migration.
+    Run,
+    /// Revert applied migrations, newest first.
+    Revert {
+        /// Revert every migration newer than this version, instead of only the newest.
+        #[arg(long)]
+        to: Option<i64>,
+    },
+    /// List migrations and whether they are applied.
+    Info,
+}
+
+/// Lists the migrations applied to a database, oldest first.
+async fn applied<DB>(pool: &Pool<DB>) -> Result<Vec<AppliedMigration>>
+where
+    DB: sqlx::Database,
+    DB::Connection: Migrate,
+{
+    let mut conn = pool.acquire().await?;
+    conn.ensure_migrations_table().await?;
+    let mut applied = conn.list_applied_migrations().await?;
+    applied.sort_by_key(|migration| migration.version);
+    Ok(applied)
+}
+
+/// Runs a migration command.
+pub async fn run(database: &Database, command: MigrateCommand) -> Result<()> {
+    let migrator = database.migrator();
+    let applied = match database {
+        Database::Postgres(pool) => applied(pool).await?,
+        Database::Sqlite(pool) => applied(pool).await?,
+    };
+
+    match command {
+        MigrateCommand::Run => {
+            let pending = migrator
+                .iter()
+                .filter(|migration| !migration.migration_type.is_down_migration())
+                .filter(|migration| !applied.iter().any(|a| a.version == migration.version))
+                .count();
+            database.migrate().await?;
+            println!("Applied {pending} migration(s)");
+        }
+        MigrateCommand::Revert { to } => {
+            let Some(newest) = applied.last() else {
+                bail!("No migrations are applied");
+            };
+            let target = to.unwrap_or_else(|| {
+                applied
+                    .iter()
+                    .rev()
+                    .nth(1)
+                    .map_or(0, |migration| migration.version)
+            });
+            let reverted: Vec<_> = applied
+                .iter()
+                .filter(|migration| migration.version > target)
+                .collect();
+            for migration in &reverted {
+                let reversible = migrator.iter().any(|m| {
+                    m.version == migration.version && m.migration_type.is_down_migration()
+                });
+                if !reversible {
+                    bail!(
+                        "Migration {} has no down migration and cannot be reverted",
+                        migration.version
+                    );
+                }
+            }
+            match database {
+                Database::Postgres(pool) => migrator.undo(pool, target).await,
+                Database::Sqlite(pool) => migrator.undo(pool, target).await,
+            }
+            .with_context(|| format!("Failed to revert migration {}", newest.version))?;
+            println!("Reverted {} migration(s)", reverted.len());
+        }
+        MigrateCommand::Info => {
+            for migration in migrator
+                .iter()
+                .filter(|migration| !migration.migration_type.is_down_migration())
+            {
+                let status = match applied.iter().find(|a| a.version == migration.version) {
+                    Some(a) if a.checksum == migration.checksum => "applied",
+                    Some(_) => "applied (changed since)",
+                    None => "pending",
+                };
+                println!(
+                    "{:<16} {:<24} {}",
+                    migration.version, status, migration.description
+                );
+            }
+        }
+    }
+    Ok(())
+}
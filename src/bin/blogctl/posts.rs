@@ -0,0 +1,182 @@
+use anyhow::{Context, Result, bail};
+use blog_api::{
+    model::blog::{BlogPost, BlogPostBody},
+    repository::{DynPostRepository, PostFilter},
+};
+use chrono::Utc;
+use clap::{Args, Subcommand};
+use std::{fs, path::PathBuf};
+use validator::Validate;
+
+/// Post management commands.
+#[derive(Debug, Subcommand)]
+pub enum PostsCommand {
+    /// List posts, optionally filtered by category or tag.
+    List {
+        /// Only list posts in this category.
+        #[arg(long)]
+        category: Option<String>,
+        /// Only list posts carrying this tag.
+        #[arg(long)]
+        tag: Option<String>,
+        /// Print one JSON object per line instead of a table.
+        #[arg(long)]
+        json: bool,
+    },
+    /// Print a post as JSON.
+    Show {
+        /// ID of the post.
+        id: i32,
+    },
+    /// Create a post.
+    Create(CreateArgs),
+    /// Publish drafts and scheduled posts now.
+    Publish {
+        /// IDs of the posts.
+        #[arg(required = true)]
+        ids: Vec<i32>,
+    },
+    /// Delete posts.
+    Delete {
+        /// IDs of the posts.
+        #[arg(required = true)]
+        ids: Vec<i32>,
+    },
+}
+
+/// Arguments of `posts create`.
+#[derive(Debug, Args)]
+pub struct CreateArgs {
+    /// Title of the post.
+    #[arg(long)]
+    title: String,
+    /// Category of the post.
+    #[arg(long)]
+    category: String,
+    /// Tag of the post; repeat for several tags.
+    #[arg(long = "tag", required = true)]
+    tags: Vec<String>,
+    /// Content of the post.
+    #[arg(
+        long,
+        conflicts_with = "content_file",
+        required_unless_present = "content_file"
+    )]
+    content: Option<String>,
+    /// File to read the content of the post from.
+    #[arg(long)]
+    content_file: Option<PathBuf>,
+    /// Unique, URL-friendly name of the post.
+    #[arg(long)]
+    slug: Option<String>,
+    /// Author of the post.
+    #[arg(long)]
+    author: Option<String>,
+    /// Create the post as a draft.
+    #[arg(long)]
+    draft: bool,
+}
+
+/// Prints posts as an aligned table.
+fn print_table(posts: &[BlogPost]) {
+    println!("{:>6}  {:<16}  {:<40}  TAGS", "ID", "CATEGORY", "TITLE");
+    for post in posts {
+        println!(
+            "{:>6}  {:<16}  {:<40}  {}",
+            post.id,
+            post.category,
+            post.title,
+            post.tags.join(",")
+        );
+    }
+}
+
+/// Runs a post management command.
+pub async fn run(posts: &DynPostRepository, command: PostsCommand) -> Result<()> {
+    match command {
+        PostsCommand::List {
+            category,
+            tag,
+            json,
+        } => {
+            let listed = posts.list(&PostFilter { category, tag }).await?;
+            if json {
+                for post in &listed {
+                    println!("{}", serde_json::to_string(post)?);
+                }
+            } else {
+                print_table(&listed);
+            }
+        }
+        PostsCommand::Show { id } => {
+            let Some(post) = posts.get(id).await? else {
+                bail!("Post {id} not found");
+            };
+            println!("{}", serde_json::to_string_pretty(&post)?);
+        }
+        PostsCommand::Create(args) => {
+            let content = match (args.content, args.content_file) {
+                (Some(content), _) => content,
+                (None, Some(path)) => fs::read_to_string(&path)
+                    .with_context(|| format!("Failed to read {}", path.display()))?,
+                (None, None) => bail!("Either --content or --content-file is required"),
+            };
+            let body = BlogPostBody {
+                title: args.title,
+                content,
+                category: args.category,
+                tags: args.tags,
+                slug: args.slug.map(Some),
+                author: args.author.map(Some),
+                draft: Some(args.draft),
+                ..BlogPostBody::default()
+            };
+            body.validate()?;
+            let post = posts.create(&body).await?;
+            println!("Created post {}", post.id);
+        }
+        PostsCommand::Publish { ids } => {
+            let mut missing = Vec::new();
+            for id in ids {
+                let Some(post) = posts.get(id).await? else {
+                    missing.push(id.to_string());
+                    continue;
+                };
+                // Posts published in the past keep their date; the others are dated now.
+                let now = Utc::now();
+                let published_at = post.published_at.filter(|date| *date <= now).unwrap_or(now);
+                let body = BlogPostBody {
+                    title: post.title,
+                    content: post.content,
+                    category: post.category,
+                    tags: post.tags,
+                    draft: Some(false),
+                    published_at: Some(Some(published_at)),
+                    ..BlogPostBody::default()
+                };
+                if posts.update(id, &body).await?.is_some() {
+                    println!("Published post {id}");
+                } else {
+                    missing.push(id.to_string());
+                }
+            }
+            if !missing.is_empty() {
+                bail!("Posts not found: {}", missing.join(", "));
+            }
+        }
+        PostsCommand::Delete { ids } => {
+            let mut missing = Vec::new();
+            for id in ids {
+                if posts.delete(id).await? {
+                    println!("Deleted post {id}");
+                } else {
+                    missing.push(id.to_string());
+                }
+            }
+            if !missing.is_empty() {
+                bail!("Posts not found: {}", missing.join(", "));
+            }
+        }
+    }
+    Ok(())
+}
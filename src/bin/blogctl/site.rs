@@ -0,0 +1,55 @@
+use anyhow::Result;
+use blog_api::{
+    repository::{DynPostRepository, PostFilter},
+    site::{self, SiteConfig},
+};
+use chrono::Utc;
+use clap::Args;
+use std::path::PathBuf;
+
+/// Arguments of `site`.
+#[derive(Debug, Args)]
+pub struct SiteArgs {
+    /// Directory to write the site to.
+    #[arg(long, short, default_value = "public")]
+    output: PathBuf,
+    /// Directory of templates replacing the built-in ones, overriding
+    /// `SITE_TEMPLATES_DIR`.
+    #[arg(long)]
+    templates: Option<PathBuf>,
+    /// Absolute URL the site is served from, overriding `SITE_BASE_URL`.
+    #[arg(long)]
+    base_url: Option<String>,
+    /// Title of the site, overriding `SITE_TITLE`.
+    #[arg(long)]
+    title: Option<String>,
+    /// Rewrite every file, even those whose sources did not change.
+    #[arg(long)]
+    force: bool,
+}
+
+/// Renders the published posts as a static website.
+pub async fn build(posts: &DynPostRepository, args: SiteArgs) -> Result<()> {
+    let mut config = SiteConfig::from_env()?;
+    if let Some(templates) = args.templates {
+        config.templates_dir = Some(templates);
+    }
+    if let Some(base_url) = args.base_url {
+        config.base_url = base_url;
+    }
+    if let Some(title) = args.title {
+        config.title = title;
+    }
+    let config = config.validated()?;
+
+    let listed = posts.list(&PostFilter::default()).await?;
+    let report = site::build(&listed, &config, &args.output, Utc::now(), args.force)?;
+    println!(
+        "Wrote {} files to {}, {} unchanged, {} removed",
+        report.written,
+        args.output.display(),
+        report.unchanged,
+        report.removed
+    );
+    Ok(())
+}
@@ -0,0 +1,78 @@
+use super::EditLockRepository;
+use crate::{error::AppError, model::collab::EditLock};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use std::{
+    collections::HashMap,
+    sync::{Mutex, MutexGuard, PoisonError},
+};
+
+/// [`EditLockRepository`] keeping locks in memory. They are lost when the repository is
+/// dropped, and the posts they lock are not checked.
+#[derive(Debug, Default)]
+pub struct InMemoryEditLockRepository {
+    /// Locks by post ID, expired or not.
+    locks: Mutex<HashMap<i32, EditLock>>,
+}
+
+impl InMemoryEditLockRepository {
+    /// Creates an empty repository.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Locks the store, recovering it if a previous holder panicked.
+    fn locks(&self) -> MutexGuard<'_, HashMap<i32, EditLock>> {
+        self.locks.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+#[async_trait]
+impl EditLockRepository for InMemoryEditLockRepository {
+    async fn acquire(
+        &self,
+        post_id: i32,
+        holder: &str,
+        token: &str,
+        expires_at: DateTime<Utc>,
+    ) -> Result<EditLock, AppError> {
+        let now = Utc::now();
+        let mut locks = self.locks();
+        let lock = match locks.get(&post_id) {
+            Some(lock) if lock.is_held_at(now) && lock.token != token => lock.clone(),
+            current => {
+                let acquired_at = current
+                    .filter(|lock| lock.token == token)
+                    .map_or(now, |lock| lock.acquired_at);
+                let lock = EditLock {
+                    post_id,
+                    holder: holder.to_string(),
+                    token: token.to_string(),
+                    acquired_at,
+                    expires_at,
+                };
+                locks.insert(post_id, lock.clone());
+                lock
+            }
+        };
+        Ok(lock)
+    }
+
+    async fn release(&self, post_id: i32, token: &str) -> Result<bool, AppError> {
+        let mut locks = self.locks();
+        if locks.get(&post_id).is_some_and(|lock| lock.token == token) {
+            locks.remove(&post_id);
+            return Ok(true);
+        }
+        Ok(false)
+    }
+
+    async fn current(&self, post_id: i32) -> Result<Option<EditLock>, AppError> {
+        let now = Utc::now();
+        Ok(self
+            .locks()
+            .get(&post_id)
+            .filter(|lock| lock.is_held_at(now))
+            .cloned())
+    }
+}
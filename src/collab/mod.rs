@@ -0,0 +1,385 @@
+use crate::{
+    config,
+    error::AppError,
+    model::collab::{ClientMessage, EditLock, Participant, ServerMessage},
+};
+use anyhow::{Result, bail};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::{
+        Arc, Mutex, MutexGuard, PoisonError,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::Duration,
+};
+use tokio::sync::broadcast;
+use uuid::Uuid;
+
+/// In-memory storage, for tests and local development without a database.
+pub mod memory;
+
+/// PostgreSQL storage.
+pub mod postgres;
+
+/// SQLite storage.
+pub mod sqlite;
+
+/// Capacity of the channel telling the sessions of a post that its room changed. A
+/// session that falls behind only misses intermediate states, as it always sends the
+/// latest one.
+const ROOM_CHANNEL_CAPACITY: usize = 16;
+
+/// Collaborative editing configuration read from the environment.
+///
+/// | Variable               | Default | Description                                             |
+/// |------------------------|---------|---------------------------------------------------------|
+/// | `COLLAB_LOCK_TTL_SECS` | `30`    | How long an edit lock is held after its last heartbeat. |
+#[derive(Debug, Clone)]
+pub struct CollabConfig {
+    /// How long an edit lock is held after it is acquired or renewed.
+    pub lock_ttl: Duration,
+}
+
+impl CollabConfig {
+    /// Builds the configuration from environment variables.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the variable holds an invalid value or zero.
+    pub fn from_env() -> Result<Self> {
+        let config = Self {
+            lock_ttl: Duration::from_secs(config::parse_or("COLLAB_LOCK_TTL_SECS", 30)?),
+        };
+        if config.lock_ttl.is_zero() {
+            bail!("Invalid value for COLLAB_LOCK_TTL_SECS: must be at least 1");
+        }
+        Ok(config)
+    }
+}
+
+/// Header carrying the token of the edit lock of a post, required to update or delete
+/// the post on its own while the lock is held. The bulk endpoints do not check locks.
+pub const EDIT_LOCK_HEADER: &str = "Edit-Lock";
+
+/// Storage of edit locks, shared by every server so that locks hold across them.
+#[async_trait]
+pub trait EditLockRepository: Send + Sync {
+    /// Grants the lock of a post to the holder of `token` until `expires_at`, if the
+    /// post is not locked, its lock expired or the lock is already theirs, and returns
+    /// the lock in place afterwards, which belongs to someone else if it was not
+    /// granted.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `AppError` if the storage fails or the post does not exist.
+    async fn acquire(
+        &self,
+        post_id: i32,
+        holder: &str,
+        token: &str,
+        expires_at: DateTime<Utc>,
+    ) -> Result<EditLock, AppError>;
+
+    /// Releases the lock of a post if it belongs to the holder of `token`, and returns
+    /// whether it did.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `AppError` if the storage fails.
+    async fn release(&self, post_id: i32, token: &str) -> Result<bool, AppError>;
+
+    /// Returns the lock of a post, if it is held.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `AppError` if the storage fails.
+    async fn current(&self, post_id: i32) -> Result<Option<EditLock>, AppError>;
+}
+
+/// A shared, type-erased [`EditLockRepository`].
+pub type DynEditLockRepository = Arc<dyn EditLockRepository>;
+
+/// The participants of a post on this server, and its edit lock as last seen.
+struct Room {
+    /// Participants by session ID.
+    participants: BTreeMap<u64, Participant>,
+    /// The edit lock as last seen, and the session holding it if it is on this server.
+    lock: Option<(EditLock, Option<u64>)>,
+    /// Tells the sessions in the room that it changed.
+    changed: broadcast::Sender<()>,
+}
+
+impl Room {
+    /// Returns the presence message describing the room at `now`.
+    fn presence(&self, now: DateTime<Utc>) -> ServerMessage {
+        let lock = self.lock.as_ref().filter(|(lock, _)| lock.is_held_at(now));
+        let editor = lock.and_then(|(_, session)| *session);
+        ServerMessage::Presence {
+            participants: self
+                .participants
+                .values()
+                .map(|participant| Participant {
+                    editing: Some(participant.id) == editor,
+                    ..participant.clone()
+                })
+                .collect(),
+            lock: lock.map(|(lock, _)| lock.clone()),
+        }
+    }
+}
+
+/// Presence and edit locks of the posts being viewed or edited.
+///
+/// Participants connected to a post are grouped in a room per post, which is kept in
+/// memory and therefore only lists the participants of this server. Edit locks are
+/// stored through an [`EditLockRepository`], so they hold across servers.
+#[derive(Clone)]
+pub struct Collaboration {
+    /// Storage of the edit locks.
+    locks: DynEditLockRepository,
+    /// Rooms of the posts with participants, by post ID.
+    rooms: Arc<Mutex<HashMap<i32, Room>>>,
+    /// ID of the most recent session.
+    last_session: Arc<AtomicU64>,
+    /// Lock expiry.
+    config: Arc<CollabConfig>,
+}
+
+impl Collaboration {
+    /// Creates a hub storing its locks in `locks`, without participants.
+    pub fn new(locks: DynEditLockRepository, config: CollabConfig) -> Self {
+        Self {
+            locks,
+            rooms: Arc::new(Mutex::new(HashMap::new())),
+            last_session: Arc::new(AtomicU64::new(0)),
+            config: Arc::new(config),
+        }
+    }
+
+    /// Locks the rooms, recovering them if a holder panicked.
+    fn rooms(&self) -> MutexGuard<'_, HashMap<i32, Room>> {
+        self.rooms.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Calls `change` on the room of a post, if it has participants, and tells its
+    /// sessions.
+    fn change_room(&self, post_id: i32, change: impl FnOnce(&mut Room)) {
+        if let Some(room) = self.rooms().get_mut(&post_id) {
+            change(room);
+            // Sending only fails when nobody is listening.
+            let _ = room.changed.send(());
+        }
+    }
+
+    /// Checks that an update or deletion of a post may proceed: the post is not locked,
+    /// or `token` is the token of its lock.
+    ///
+    /// # Errors
+    ///
+    /// Returns `AppError::Locked` if someone else holds the lock of the post, or an
+    /// `AppError` if the storage fails.
+    pub async fn check_update(&self, post_id: i32, token: Option<&str>) -> Result<(), AppError> {
+        match self.locks.current(post_id).await? {
+            Some(lock) if Some(lock.token.as_str()) != token => Err(AppError::Locked(format!(
+                "Blog post {post_id} is being edited by {} until {}; updates must carry the \
+                 token of its edit lock in the `{EDIT_LOCK_HEADER}` header",
+                lock.holder,
+                lock.expires_at.to_rfc3339(),
+            ))),
+            _ => Ok(()),
+        }
+    }
+
+    /// Joins the room of a post as `name`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `AppError` if the lock of the post cannot be read.
+    pub async fn join(&self, post_id: i32, name: &str) -> Result<Session, AppError> {
+        let lock = self.locks.current(post_id).await?;
+        let id = self.last_session.fetch_add(1, Ordering::Relaxed) + 1;
+        let participant = Participant {
+            id,
+            name: name.to_string(),
+            editing: false,
+            joined_at: Utc::now(),
+        };
+        let changed = {
+            let mut rooms = self.rooms();
+            let room = rooms.entry(post_id).or_insert_with(|| Room {
+                participants: BTreeMap::new(),
+                lock: None,
+                changed: broadcast::channel(ROOM_CHANNEL_CAPACITY).0,
+            });
+            room.participants.insert(id, participant);
+            // Keep track of the session holding the lock, if it is on this server.
+            let holder = room.lock.as_ref().and_then(|(known, session)| {
+                lock.as_ref()
+                    .filter(|lock| lock.token == known.token)
+                    .and(*session)
+            });
+            room.lock = lock.map(|lock| (lock, holder));
+            let changed = room.changed.subscribe();
+            let _ = room.changed.send(());
+            changed
+        };
+        Ok(Session {
+            hub: self.clone(),
+            post_id,
+            id,
+            name: name.to_string(),
+            token: Uuid::new_v4().simple().to_string(),
+            changed,
+        })
+    }
+}
+
+/// A participant connected to the room of a post, from [`Collaboration::join`].
+///
+/// [`leave`](Self::leave) must be called when the participant disconnects, to release
+/// its lock and tell the others.
+pub struct Session {
+    /// The hub the session belongs to.
+    hub: Collaboration,
+    /// ID of the post.
+    post_id: i32,
+    /// ID of the session.
+    id: u64,
+    /// Name of the participant.
+    name: String,
+    /// Token of the edit lock, if the participant acquires it.
+    token: String,
+    /// Tells the session that its room changed.
+    changed: broadcast::Receiver<()>,
+}
+
+impl Session {
+    /// Returns the ID of the session.
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// Returns the presence message describing the room now.
+    pub fn presence(&self) -> ServerMessage {
+        self.hub.rooms().get(&self.post_id).map_or(
+            ServerMessage::Presence {
+                participants: Vec::new(),
+                lock: None,
+            },
+            |room| room.presence(Utc::now()),
+        )
+    }
+
+    /// Returns when the edit lock of the post, as last seen, expires.
+    pub fn lock_expiry(&self) -> Option<DateTime<Utc>> {
+        let now = Utc::now();
+        self.hub.rooms().get(&self.post_id).and_then(|room| {
+            room.lock
+                .as_ref()
+                .map(|(lock, _)| lock.expires_at)
+                .filter(|expires_at| *expires_at > now)
+        })
+    }
+
+    /// Waits until the room changes.
+    ///
+    /// Returns `false` if the room can no longer change, which does not happen while
+    /// the session has not left.
+    pub async fn changed(&mut self) -> bool {
+        match self.changed.recv().await {
+            Ok(()) | Err(broadcast::error::RecvError::Lagged(_)) => true,
+            Err(broadcast::error::RecvError::Closed) => false,
+        }
+    }
+
+    /// Handles a message of the participant, and returns the reply to send it, if any.
+    /// Changes to the lock are also announced to the whole room.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `AppError` if the storage fails.
+    pub async fn handle(&self, message: ClientMessage) -> Result<Option<ServerMessage>, AppError> {
+        match message {
+            ClientMessage::Lock => self.lock().await.map(Some),
+            ClientMessage::Heartbeat if self.holds_lock() => Ok(Some(match self.lock().await? {
+                ServerMessage::LockDenied { .. } => ServerMessage::LockLost,
+                reply => reply,
+            })),
+            ClientMessage::Heartbeat => Ok(Some(ServerMessage::LockLost)),
+            ClientMessage::Unlock => {
+                self.unlock().await?;
+                Ok(None)
+            }
+        }
+    }
+
+    /// Returns whether the session holds the lock, as last seen.
+    fn holds_lock(&self) -> bool {
+        self.hub.rooms().get(&self.post_id).is_some_and(|room| {
+            room.lock.as_ref().is_some_and(|(lock, session)| {
+                *session == Some(self.id) && lock.is_held_at(Utc::now())
+            })
+        })
+    }
+
+    /// Acquires or renews the edit lock.
+    async fn lock(&self) -> Result<ServerMessage, AppError> {
+        let ttl = chrono::Duration::from_std(self.hub.config.lock_ttl)
+            .map_err(|_| AppError::InternalServerError)?;
+        let lock = self
+            .hub
+            .locks
+            .acquire(self.post_id, &self.name, &self.token, Utc::now() + ttl)
+            .await?;
+        let granted = lock.token == self.token;
+        let session = granted.then_some(self.id);
+        self.hub.change_room(self.post_id, |room| {
+            room.lock = Some((lock.clone(), session))
+        });
+        Ok(if granted {
+            ServerMessage::LockGranted {
+                token: self.token.clone(),
+                lock,
+            }
+        } else {
+            ServerMessage::LockDenied { lock }
+        })
+    }
+
+    /// Releases the edit lock, if the session holds it.
+    async fn unlock(&self) -> Result<(), AppError> {
+        if self.hub.locks.release(self.post_id, &self.token).await? {
+            self.hub.change_room(self.post_id, |room| {
+                if room
+                    .lock
+                    .as_ref()
+                    .is_some_and(|(lock, _)| lock.token == self.token)
+                {
+                    room.lock = None;
+                }
+            });
+        }
+        Ok(())
+    }
+
+    /// Leaves the room, releasing the edit lock if the session holds it.
+    pub async fn leave(self) {
+        if let Err(err) = self.unlock().await {
+            tracing::warn!(
+                post_id = self.post_id,
+                "failed to release an edit lock: {err}"
+            );
+        }
+        let mut rooms = self.hub.rooms();
+        if let Some(room) = rooms.get_mut(&self.post_id) {
+            room.participants.remove(&self.id);
+            if room.participants.is_empty() {
+                rooms.remove(&self.post_id);
+            } else {
+                let _ = room.changed.send(());
+            }
+        }
+    }
+}
@@ -0,0 +1,102 @@
+use super::EditLockRepository;
+use crate::{error::AppError, model::collab::EditLock, telemetry::span};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use tracing::Instrument;
+
+/// Table of edit locks, reported on query spans.
+const TABLE: &str = "post_locks";
+
+/// Database system reported on query spans.
+const SYSTEM: &str = "postgresql";
+
+/// [`EditLockRepository`] backed by the `post_locks` table.
+#[derive(Debug, Clone)]
+pub struct PgEditLockRepository {
+    /// Pool of the primary database.
+    pool: PgPool,
+}
+
+impl PgEditLockRepository {
+    /// Creates a repository running its queries on `pool`.
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl EditLockRepository for PgEditLockRepository {
+    async fn acquire(
+        &self,
+        post_id: i32,
+        holder: &str,
+        token: &str,
+        expires_at: DateTime<Utc>,
+    ) -> Result<EditLock, AppError> {
+        // The lock in place is read from the snapshot taken before the insert, so it is
+        // returned when the insert leaves it alone.
+        let lock = sqlx::query_as!(
+            EditLock,
+            r#"
+            WITH acquired AS (
+                INSERT INTO post_locks AS existing (post_id, holder, token, expires_at)
+                VALUES ($1, $2, $3, $4)
+                ON CONFLICT (post_id) DO UPDATE
+                SET holder = EXCLUDED.holder,
+                    token = EXCLUDED.token,
+                    acquired_at = CASE
+                        WHEN existing.token = EXCLUDED.token THEN existing.acquired_at
+                        ELSE NOW()
+                    END,
+                    expires_at = EXCLUDED.expires_at
+                WHERE existing.token = EXCLUDED.token OR existing.expires_at <= NOW()
+                RETURNING post_id, holder, token, acquired_at, expires_at
+            )
+            SELECT post_id AS "post_id!", holder AS "holder!", token AS "token!",
+                   acquired_at AS "acquired_at!", expires_at AS "expires_at!"
+            FROM acquired
+            UNION ALL
+            SELECT post_id, holder, token, acquired_at, expires_at
+            FROM post_locks
+            WHERE post_id = $1 AND NOT EXISTS (SELECT 1 FROM acquired);
+            "#,
+            post_id,
+            holder,
+            token,
+            expires_at
+        )
+        .fetch_one(&self.pool)
+        .instrument(span::db_query_on(SYSTEM, "INSERT", TABLE))
+        .await?;
+        Ok(lock)
+    }
+
+    async fn release(&self, post_id: i32, token: &str) -> Result<bool, AppError> {
+        let result = sqlx::query!(
+            "DELETE FROM post_locks WHERE post_id = $1 AND token = $2;",
+            post_id,
+            token
+        )
+        .execute(&self.pool)
+        .instrument(span::db_query_on(SYSTEM, "DELETE", TABLE))
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn current(&self, post_id: i32) -> Result<Option<EditLock>, AppError> {
+        let lock = sqlx::query_as!(
+            EditLock,
+            r#"
+            SELECT post_id, holder, token, acquired_at, expires_at
+            FROM post_locks
+            WHERE post_id = $1 AND expires_at > NOW();
+            "#,
+            post_id
+        )
+        .fetch_optional(&self.pool)
+        .instrument(span::db_query_on(SYSTEM, "SELECT", TABLE))
+        .await?;
+        Ok(lock)
+    }
+}
@@ -0,0 +1,137 @@
+use super::EditLockRepository;
+use crate::{error::AppError, model::collab::EditLock, telemetry::span};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use sqlx::{FromRow, SqlitePool};
+use tracing::Instrument;
+
+/// Table of edit locks, reported on query spans.
+const TABLE: &str = "post_locks";
+
+/// Database system reported on query spans.
+const SYSTEM: &str = "sqlite";
+
+/// A row of the `post_locks` table.
+#[derive(Debug, FromRow)]
+struct LockRow {
+    /// ID of the locked blog post.
+    post_id: i32,
+    /// Name of the holder.
+    holder: String,
+    /// Secret identifying the holder.
+    token: String,
+    /// When the holder acquired the lock.
+    acquired_at: DateTime<Utc>,
+    /// When the lock expires, in Unix milliseconds.
+    expires_at: i64,
+}
+
+impl From<LockRow> for EditLock {
+    fn from(row: LockRow) -> Self {
+        Self {
+            post_id: row.post_id,
+            holder: row.holder,
+            token: row.token,
+            acquired_at: row.acquired_at,
+            expires_at: DateTime::from_timestamp_millis(row.expires_at).unwrap_or_default(),
+        }
+    }
+}
+
+/// [`EditLockRepository`] backed by a SQLite database migrated from `migrations/sqlite`.
+///
+/// The times locks expire are stored as Unix milliseconds.
+#[derive(Debug, Clone)]
+pub struct SqliteEditLockRepository {
+    /// Pool the queries run on.
+    pool: SqlitePool,
+}
+
+impl SqliteEditLockRepository {
+    /// Creates a repository running its queries on `pool`.
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl EditLockRepository for SqliteEditLockRepository {
+    async fn acquire(
+        &self,
+        post_id: i32,
+        holder: &str,
+        token: &str,
+        expires_at: DateTime<Utc>,
+    ) -> Result<EditLock, AppError> {
+        let now = Utc::now().timestamp_millis();
+        let mut tx = self.pool.begin().await?;
+        let acquired: Option<LockRow> = sqlx::query_as(
+            r#"
+            INSERT INTO post_locks AS existing (post_id, holder, token, expires_at)
+            VALUES (?1, ?2, ?3, ?4)
+            ON CONFLICT (post_id) DO UPDATE
+            SET holder = excluded.holder,
+                token = excluded.token,
+                acquired_at = CASE
+                    WHEN existing.token = excluded.token THEN existing.acquired_at
+                    ELSE strftime('%Y-%m-%dT%H:%M:%fZ', 'now')
+                END,
+                expires_at = excluded.expires_at
+            WHERE existing.token = excluded.token OR existing.expires_at <= ?5
+            RETURNING post_id, holder, token, acquired_at, expires_at;
+            "#,
+        )
+        .bind(post_id)
+        .bind(holder)
+        .bind(token)
+        .bind(expires_at.timestamp_millis())
+        .bind(now)
+        .fetch_optional(&mut *tx)
+        .instrument(span::db_query_on(SYSTEM, "INSERT", TABLE))
+        .await?;
+        let lock = match acquired {
+            Some(lock) => lock,
+            None => {
+                sqlx::query_as(
+                    r#"
+                    SELECT post_id, holder, token, acquired_at, expires_at
+                    FROM post_locks
+                    WHERE post_id = ?1;
+                    "#,
+                )
+                .bind(post_id)
+                .fetch_one(&mut *tx)
+                .instrument(span::db_query_on(SYSTEM, "SELECT", TABLE))
+                .await?
+            }
+        };
+        tx.commit().await?;
+        Ok(lock.into())
+    }
+
+    async fn release(&self, post_id: i32, token: &str) -> Result<bool, AppError> {
+        let result = sqlx::query("DELETE FROM post_locks WHERE post_id = ?1 AND token = ?2;")
+            .bind(post_id)
+            .bind(token)
+            .execute(&self.pool)
+            .instrument(span::db_query_on(SYSTEM, "DELETE", TABLE))
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn current(&self, post_id: i32) -> Result<Option<EditLock>, AppError> {
+        let lock: Option<LockRow> = sqlx::query_as(
+            r#"
+            SELECT post_id, holder, token, acquired_at, expires_at
+            FROM post_locks
+            WHERE post_id = ?1 AND expires_at > ?2;
+            "#,
+        )
+        .bind(post_id)
+        .bind(Utc::now().timestamp_millis())
+        .fetch_optional(&self.pool)
+        .instrument(span::db_query_on(SYSTEM, "SELECT", TABLE))
+        .await?;
+        Ok(lock.map(EditLock::from))
+    }
+}
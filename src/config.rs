@@ -0,0 +1,31 @@
+use anyhow::{Context, Result};
+use std::{env, fmt::Display, str::FromStr};
+
+/// Reads an environment variable, falling back to `default` when it is unset.
+pub fn var_or(key: &str, default: &str) -> String {
+    env::var(key).unwrap_or_else(|_| default.to_string())
+}
+
+/// Reads an optional environment variable, treating empty values as unset.
+pub fn var_opt(key: &str) -> Option<String> {
+    env::var(key).ok().filter(|value| !value.trim().is_empty())
+}
+
+/// Reads and parses an environment variable, falling back to `default` when it is unset.
+///
+/// # Errors
+///
+/// Returns an error if the variable is set but cannot be parsed into `T`.
+pub fn parse_or<T>(key: &str, default: T) -> Result<T>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    match var_opt(key) {
+        Some(value) => value
+            .parse()
+            .map_err(|err| anyhow::anyhow!("{err}"))
+            .with_context(|| format!("Invalid value for {key}: {value:?}")),
+        None => Ok(default),
+    }
+}
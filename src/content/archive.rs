@@ -0,0 +1,188 @@
+use crate::error::AppError;
+use flate2::read::GzDecoder;
+use std::{
+    io::{Cursor, Read},
+    mem,
+    path::Path,
+};
+
+/// Most files read from one upload.
+pub const MAX_FILES: usize = 1000;
+
+/// Largest file read from an upload, in bytes.
+pub const MAX_FILE_BYTES: u64 = 1024 * 1024;
+
+/// Most bytes read out of a compressed archive, so that a small upload cannot expand
+/// without bound.
+pub const MAX_UNCOMPRESSED_BYTES: u64 = 256 * 1024 * 1024;
+
+/// The two zero blocks ending a tar archive.
+pub const TAR_END: [u8; 1024] = [0; 1024];
+
+/// A file read from an upload.
+#[derive(Debug)]
+pub struct UploadedFile {
+    /// Path of the file within the upload.
+    pub name: String,
+    /// Contents of the file, or why they could not be read.
+    pub text: Result<String, String>,
+}
+
+/// Kinds of uploads, told apart by their leading bytes.
+enum Kind {
+    /// A zip archive.
+    Zip,
+    /// A gzip-compressed tar archive.
+    TarGz,
+    /// An uncompressed tar archive.
+    Tar,
+    /// A single file.
+    Plain,
+}
+
+impl Kind {
+    /// Sniffs the kind of an upload.
+    fn of(data: &[u8]) -> Self {
+        if data.starts_with(b"PK\x03\x04") || data.starts_with(b"PK\x05\x06") {
+            Self::Zip
+        } else if data.starts_with(&[0x1f, 0x8b]) {
+            Self::TarGz
+        } else if data.get(257..262) == Some(b"ustar") {
+            Self::Tar
+        } else {
+            Self::Plain
+        }
+    }
+}
+
+/// Returns whether an archive member should be skipped: hidden files and directories,
+/// including the `__MACOSX` metadata of archives made on macOS.
+fn is_hidden(name: &str) -> bool {
+    Path::new(name).components().any(|component| {
+        component.as_os_str().to_str().is_some_and(|part| {
+            (part.starts_with('.') && part != "." && part != "..") || part == "__MACOSX"
+        })
+    })
+}
+
+/// Reads the text of a file of an upload, returning how many bytes were read along with
+/// it, whether or not they are text.
+///
+/// A file declared or found to be larger than [`MAX_FILE_BYTES`] is an error rather
+/// than cut short; at most one byte more than that is read from it.
+fn read_text(reader: impl Read, size: u64) -> (u64, Result<String, String>) {
+    let too_large = || format!("the file is larger than {MAX_FILE_BYTES} bytes");
+    if size > MAX_FILE_BYTES {
+        return (0, Err(too_large()));
+    }
+    let mut bytes = Vec::new();
+    let result = reader.take(MAX_FILE_BYTES + 1).read_to_end(&mut bytes);
+    let read = u64::try_from(bytes.len()).unwrap_or(u64::MAX);
+    let text = match result {
+        Err(err) => Err(format!("the file cannot be read: {err}")),
+        Ok(_) if read > MAX_FILE_BYTES => Err(too_large()),
+        Ok(_) => String::from_utf8(bytes).map_err(|_| "the file is not valid UTF-8".to_string()),
+    };
+    (read, text)
+}
+
+/// Reads the files an upload consists of, keeping those `wanted` accepts by name.
+///
+/// The upload is a zip archive, a tar archive (optionally gzip-compressed), or a
+/// single file named `file_name`, which is returned whether `wanted` accepts it or not.
+/// Hidden files in archives are skipped. A file that is too large or not UTF-8 is
+/// returned with an error rather than failing the whole upload.
+///
+/// # Errors
+///
+/// Returns an `AppError::BadRequest` if the archive is corrupt, contains more than
+/// [`MAX_FILES`] wanted files, or its wanted files add up to more than
+/// [`MAX_UNCOMPRESSED_BYTES`].
+pub fn read_files(
+    data: &[u8],
+    file_name: &str,
+    wanted: impl Fn(&str) -> bool,
+) -> Result<Vec<UploadedFile>, AppError> {
+    let corrupt = |err: &dyn std::fmt::Display| {
+        AppError::BadRequest(format!("The archive cannot be read: {err}"))
+    };
+    let mut files = Vec::new();
+    let mut push = |name: String, text: Result<String, String>| {
+        if files.len() == MAX_FILES {
+            return Err(AppError::BadRequest(format!(
+                "An upload may contain at most {MAX_FILES} files"
+            )));
+        }
+        files.push(UploadedFile { name, text });
+        Ok(())
+    };
+
+    match Kind::of(data) {
+        Kind::Zip => {
+            let mut archive =
+                zip::ZipArchive::new(Cursor::new(data)).map_err(|err| corrupt(&err))?;
+            // Bytes still allowed to be read, text or not, so that highly compressed
+            // entries cannot fill memory.
+            let mut remaining = MAX_UNCOMPRESSED_BYTES;
+            for index in 0..archive.len() {
+                let entry = archive.by_index(index).map_err(|err| corrupt(&err))?;
+                let name = entry.name().to_string();
+                if !entry.is_file() || is_hidden(&name) || !wanted(&name) {
+                    continue;
+                }
+                let size = entry.size();
+                let (read, text) = read_text(entry, size);
+                remaining = remaining.checked_sub(read).ok_or_else(|| {
+                    AppError::BadRequest(format!(
+                        "The files of an archive may add up to at most \
+                         {MAX_UNCOMPRESSED_BYTES} bytes"
+                    ))
+                })?;
+                push(name, text)?;
+            }
+        }
+        kind @ (Kind::Tar | Kind::TarGz) => {
+            let reader: Box<dyn Read> = match kind {
+                Kind::TarGz => Box::new(GzDecoder::new(data).take(MAX_UNCOMPRESSED_BYTES)),
+                _ => Box::new(data),
+            };
+            let mut archive = tar::Archive::new(reader);
+            for entry in archive.entries().map_err(|err| corrupt(&err))? {
+                let entry = entry.map_err(|err| corrupt(&err))?;
+                let name = entry
+                    .path()
+                    .map_err(|err| corrupt(&err))?
+                    .to_string_lossy()
+                    .into_owned();
+                if !entry.header().entry_type().is_file() || is_hidden(&name) || !wanted(&name) {
+                    continue;
+                }
+                let size = entry.size();
+                push(name, read_text(entry, size).1)?;
+            }
+        }
+        Kind::Plain => {
+            let size = u64::try_from(data.len()).unwrap_or(u64::MAX);
+            push(file_name.to_string(), read_text(data, size).1)?;
+        }
+    }
+    Ok(files)
+}
+
+/// Encodes one file as a tar archive member, without the end-of-archive marker, so that
+/// members can be streamed one at a time and followed by [`TAR_END`].
+///
+/// # Errors
+///
+/// Returns an error if the header cannot be built.
+pub fn tar_entry(path: &str, contents: &[u8], mtime: u64) -> std::io::Result<Vec<u8>> {
+    let mut header = tar::Header::new_gnu();
+    header.set_size(u64::try_from(contents.len()).unwrap_or(u64::MAX));
+    header.set_mode(0o644);
+    header.set_mtime(mtime);
+    let mut builder = tar::Builder::new(Vec::new());
+    // `append_data` writes GNU long-name records for long paths; the member is taken
+    // out of the builder before it is dropped, which would write the end marker.
+    builder.append_data(&mut header, path, contents)?;
+    Ok(mem::take(builder.get_mut()))
+}
@@ -0,0 +1,238 @@
+use crate::model::blog::{BlogPost, BlogPostBody, slugify};
+use chrono::{DateTime, NaiveDate, NaiveDateTime, SecondsFormat, Utc};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::path::Path;
+
+/// Line opening and closing the front matter.
+const DELIMITER: &str = "---";
+
+/// Front matter of a post, as written at the top of its Markdown file.
+///
+/// Keys other than these are ignored on import, so files written for static site
+/// generators can be imported as they are.
+#[derive(Debug, Serialize, Deserialize)]
+struct FrontMatter {
+    /// Title of the post.
+    title: String,
+    /// Slug of the post; derived from the file name when missing.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    slug: Option<String>,
+    /// Author of the post.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    author: Option<String>,
+    /// Category of the post.
+    category: String,
+    /// Tags of the post, as a list or a comma-separated string.
+    #[serde(default, deserialize_with = "tags")]
+    tags: Vec<String>,
+    /// Publication date of the post.
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "serialize_date",
+        deserialize_with = "deserialize_date"
+    )]
+    date: Option<DateTime<Utc>>,
+    /// Whether the post is a draft.
+    #[serde(default)]
+    draft: bool,
+    /// ID of the media file shown as the cover of the post.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    featured_image_id: Option<i32>,
+    /// Title of the post for search engines and shared links.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    meta_title: Option<String>,
+    /// Summary of the post for search engines and shared links.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    description: Option<String>,
+    /// Preferred URL of the post.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    canonical_url: Option<String>,
+    /// Whether search engines are asked not to index the post.
+    #[serde(default, skip_serializing_if = "is_false")]
+    noindex: bool,
+}
+
+/// Returns whether a flag is unset, so it is left out of the front matter.
+fn is_false(flag: &bool) -> bool {
+    !flag
+}
+
+/// Reads tags written either as a list or as a comma-separated string.
+fn tags<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<String>, D::Error> {
+    /// The accepted ways of writing tags.
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Tags {
+        /// `tags: [rust, async]`
+        List(Vec<String>),
+        /// `tags: rust, async`
+        Joined(String),
+    }
+    Ok(match Tags::deserialize(deserializer)? {
+        Tags::List(tags) => tags,
+        Tags::Joined(tags) => tags
+            .split(',')
+            .map(str::trim)
+            .filter(|tag| !tag.is_empty())
+            .map(str::to_string)
+            .collect(),
+    })
+}
+
+/// Parses a date as written by common static site generators: RFC 3339, a date and
+/// time with or without an offset, or a bare date. Dates without an offset are UTC.
+fn parse_date(date: &str) -> Option<DateTime<Utc>> {
+    let date = date.trim();
+    DateTime::parse_from_rfc3339(date)
+        .or_else(|_| DateTime::parse_from_str(date, "%Y-%m-%d %H:%M:%S %z"))
+        .map(|date| date.to_utc())
+        .ok()
+        .or_else(|| {
+            ["%Y-%m-%dT%H:%M:%S", "%Y-%m-%d %H:%M:%S", "%Y-%m-%d %H:%M"]
+                .into_iter()
+                .find_map(|format| NaiveDateTime::parse_from_str(date, format).ok())
+                .or_else(|| {
+                    NaiveDate::parse_from_str(date, "%Y-%m-%d")
+                        .ok()
+                        .and_then(|date| date.and_hms_opt(0, 0, 0))
+                })
+                .map(|date| date.and_utc())
+        })
+}
+
+/// Reads the `date` key, see [`parse_date`].
+fn deserialize_date<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<DateTime<Utc>>, D::Error> {
+    Option::<String>::deserialize(deserializer)?
+        .map(|date| {
+            parse_date(&date)
+                .ok_or_else(|| serde::de::Error::custom(format!("invalid date `{date}`")))
+        })
+        .transpose()
+}
+
+/// Writes the `date` key as RFC 3339 in UTC.
+fn serialize_date<S: Serializer>(
+    date: &Option<DateTime<Utc>>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    match date {
+        Some(date) => serializer.serialize_str(&date.to_rfc3339_opts(SecondsFormat::AutoSi, true)),
+        None => serializer.serialize_none(),
+    }
+}
+
+/// Derives a slug from a file name, dropping the extension and a Jekyll-style
+/// `YYYY-MM-DD-` date prefix.
+fn slug_from_file_name(file_name: &str) -> Option<String> {
+    let stem = Path::new(file_name).file_stem()?.to_str()?;
+    let undated = stem
+        .get(..11)
+        .filter(|prefix| {
+            prefix.ends_with('-') && NaiveDate::parse_from_str(&prefix[..10], "%Y-%m-%d").is_ok()
+        })
+        .map_or(stem, |_| &stem[11..]);
+    slugify(undated)
+}
+
+/// Returns whether a file is a Markdown file, judging by its extension.
+pub fn is_markdown(file_name: &str) -> bool {
+    Path::new(file_name)
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| {
+            extension.eq_ignore_ascii_case("md") || extension.eq_ignore_ascii_case("markdown")
+        })
+}
+
+/// Parses a Markdown file with YAML front matter into a post.
+///
+/// The front matter maps onto the post as follows: `title`, `author`, `category`, `tags`,
+/// `slug`, `draft`, `date` (the publication date), `featured_image_id`, `meta_title`,
+/// `description` (the meta description), `canonical_url` and `noindex`. A missing slug
+/// is derived from the file name, and everything after the front matter is the content.
+/// The post is not validated.
+///
+/// # Errors
+///
+/// Returns a description of the problem if the file has no front matter, the front
+/// matter is not valid YAML or lacks a required key, or no slug can be derived.
+pub fn parse(file_name: &str, text: &str) -> Result<BlogPostBody, String> {
+    let text = text.strip_prefix('\u{feff}').unwrap_or(text);
+    let mut lines = text.split_inclusive('\n');
+    if lines.next().map(str::trim_end) != Some(DELIMITER) {
+        return Err(format!(
+            "front matter: the file must start with a `{DELIMITER}` line"
+        ));
+    }
+    let mut yaml_len = 0;
+    let mut closed = false;
+    for line in lines.by_ref() {
+        if matches!(line.trim_end(), DELIMITER | "...") {
+            closed = true;
+            break;
+        }
+        yaml_len += line.len();
+    }
+    if !closed {
+        return Err(format!("front matter: no closing `{DELIMITER}` line"));
+    }
+    let yaml_start = text.find('\n').map_or(text.len(), |end| end + 1);
+    let yaml = &text[yaml_start..yaml_start + yaml_len];
+    let content: String = lines.collect();
+
+    let front: FrontMatter =
+        serde_yaml::from_str(yaml).map_err(|err| format!("front matter: {err}"))?;
+    let slug = front
+        .slug
+        .or_else(|| slug_from_file_name(file_name))
+        .ok_or("slug: no slug in the front matter, and none can be derived from the file name")?;
+    Ok(BlogPostBody {
+        title: front.title,
+        content,
+        category: front.category,
+        tags: front.tags,
+        slug: Some(Some(slug)),
+        author: front.author.map(Some),
+        draft: Some(front.draft),
+        published_at: front.date.map(Some),
+        featured_image_id: front.featured_image_id.map(Some),
+        meta_title: front.meta_title.map(Some),
+        meta_description: front.description.map(Some),
+        canonical_url: front.canonical_url.map(Some),
+        noindex: Some(front.noindex),
+    })
+}
+
+/// Returns the file name a post is exported as: its slug, or `post-<id>` without one.
+pub fn file_name(post: &BlogPost) -> String {
+    match &post.slug {
+        Some(slug) => format!("{slug}.md"),
+        None => format!("post-{}.md", post.id),
+    }
+}
+
+/// Renders a post as Markdown with YAML front matter, the inverse of [`parse`].
+///
+/// # Errors
+///
+/// Returns an error if the front matter cannot be serialized.
+pub fn render(post: &BlogPost) -> Result<String, serde_yaml::Error> {
+    let front = serde_yaml::to_string(&FrontMatter {
+        title: post.title.clone(),
+        slug: post.slug.clone(),
+        author: post.author.clone(),
+        category: post.category.clone(),
+        tags: post.tags.clone(),
+        date: post.published_at,
+        draft: post.draft,
+        featured_image_id: post.featured_image_id,
+        meta_title: post.meta_title.clone(),
+        description: post.meta_description.clone(),
+        canonical_url: post.canonical_url.clone(),
+        noindex: post.noindex,
+    })?;
+    Ok(format!("{DELIMITER}\n{front}{DELIMITER}\n{}", post.content))
+}
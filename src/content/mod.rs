@@ -0,0 +1,233 @@
+use crate::{
+    error::AppError,
+    media::{self, DynMediaRepository},
+    model::{
+        blog::BlogPostBody,
+        import::{
+            ImportItemResult, ImportItemStatus, ImportResponse, WordPressImportResponse,
+            WordPressItemResult, WordPressItemStatus,
+        },
+    },
+    repository::{DynPostRepository, Upserted},
+};
+use std::collections::HashSet;
+use validator::Validate;
+
+/// Reading uploads that may be archives, and writing tar archives.
+pub mod archive;
+
+/// Markdown files with YAML front matter.
+pub mod markdown;
+
+/// WordPress eXtended RSS (WXR) exports.
+pub mod wordpress;
+
+/// File name assumed for a single uploaded Markdown file that comes without one.
+pub const DEFAULT_FILE_NAME: &str = "post.md";
+
+/// Returns the message of an error the storage raised for one post only, or the error
+/// itself if the storage failed and the import cannot go on.
+fn rejection(err: AppError) -> Result<String, AppError> {
+    match err {
+        AppError::BadRequest(message)
+        | AppError::Conflict(message)
+        | AppError::UnprocessableEntity(message) => Ok(message),
+        err => Err(err),
+    }
+}
+
+/// Upserts an imported post by its slug, after checking the image it features.
+///
+/// # Errors
+///
+/// Returns an `AppError::UnprocessableEntity` if the featured image is missing or not
+/// an image, or an `AppError` if the storage refuses the post or fails.
+async fn upsert(
+    posts: &DynPostRepository,
+    media: &DynMediaRepository,
+    post: &BlogPostBody,
+) -> Result<Upserted, AppError> {
+    if let Some(id) = post.featured_image_id() {
+        media::check_featured_image(media.as_ref(), id).await?;
+    }
+    posts.upsert_by_slug(post).await
+}
+
+/// Imports Markdown files with YAML front matter, upserting each post by its slug.
+///
+/// `data` is a single Markdown file named `file_name`, or a zip or (gzip-compressed)
+/// tar archive whose `.md` and `.markdown` files are imported in archive order. Files
+/// that cannot be read, parsed or validated, whose slug an earlier file of the upload
+/// already took, or that the storage refuses (such as one featuring a missing image),
+/// are reported and skipped; the others are imported one at a time, so a storage failure
+/// leaves the files before it imported.
+///
+/// # Errors
+///
+/// Returns an `AppError` if the archive cannot be read, holds no Markdown file or too
+/// many, or if the storage fails.
+pub async fn import_markdown(
+    posts: &DynPostRepository,
+    media: &DynMediaRepository,
+    data: &[u8],
+    file_name: &str,
+) -> Result<ImportResponse, AppError> {
+    let files = archive::read_files(data, file_name, markdown::is_markdown)?;
+    if files.is_empty() {
+        return Err(AppError::BadRequest(
+            "The upload contains no Markdown files".to_string(),
+        ));
+    }
+
+    let mut response = ImportResponse::default();
+    let mut slugs = HashSet::new();
+    for file in files {
+        let parsed = file.text.and_then(|text| {
+            let post = markdown::parse(&file.name, &text)?;
+            post.validate().map_err(|err| err.to_string())?;
+            match post.slug() {
+                Some(slug) if !slugs.insert(slug.to_string()) => Err(format!(
+                    "slug: `{slug}` is already taken by an earlier file of the upload"
+                )),
+                _ => Ok(post),
+            }
+        });
+        let upserted = match parsed {
+            Ok(post) => match upsert(posts, media, &post).await {
+                Ok(upserted) => Ok(upserted),
+                Err(err) => Err(rejection(err)?),
+            },
+            Err(error) => Err(error),
+        };
+        let result = match upserted {
+            Ok(upserted) => {
+                let status = match upserted {
+                    Upserted::Created(_) => {
+                        response.created += 1;
+                        ImportItemStatus::Created
+                    }
+                    Upserted::Updated(_) => {
+                        response.updated += 1;
+                        ImportItemStatus::Updated
+                    }
+                };
+                ImportItemResult {
+                    file: file.name,
+                    status,
+                    id: Some(upserted.post().id),
+                    slug: upserted.post().slug.clone(),
+                    error: None,
+                }
+            }
+            Err(error) => {
+                response.failed += 1;
+                ImportItemResult {
+                    file: file.name,
+                    status: ImportItemStatus::Invalid,
+                    id: None,
+                    slug: None,
+                    error: Some(error),
+                }
+            }
+        };
+        response.results.push(result);
+    }
+    Ok(response)
+}
+
+/// Imports the posts of a WordPress eXtended RSS (WXR) export, upserting each post by
+/// its slug.
+///
+/// Items other than posts, and trashed posts, are skipped. Posts that fail validation,
+/// whose slug an earlier post of the export already took, or that the storage refuses,
+/// are reported and skipped; the others are imported one at a time in export order, so
+/// a storage failure leaves the posts before it imported. With `dry_run`, nothing is stored and the report tells
+/// which posts would be created and which updated.
+///
+/// # Errors
+///
+/// Returns an `AppError::BadRequest` if the file is not a WordPress export, or an
+/// `AppError` if the storage fails.
+pub async fn import_wordpress(
+    posts: &DynPostRepository,
+    data: &[u8],
+    dry_run: bool,
+) -> Result<WordPressImportResponse, AppError> {
+    let export = wordpress::parse(data).map_err(|err| {
+        AppError::BadRequest(format!("The WordPress export cannot be read: {err}"))
+    })?;
+
+    let mut response = WordPressImportResponse {
+        dry_run,
+        ..WordPressImportResponse::default()
+    };
+    let mut slugs = HashSet::new();
+    for item in &export.items {
+        let mut result = WordPressItemResult {
+            title: item.title.clone(),
+            link: item.link.clone(),
+            original_slug: item.post_name.clone(),
+            slug: None,
+            status: WordPressItemStatus::Skipped,
+            id: None,
+            reason: item.skip_reason(),
+        };
+        if result.reason.is_some() {
+            response.skipped += 1;
+            response.results.push(result);
+            continue;
+        }
+
+        let mapped = export.to_post(item).and_then(|post| {
+            post.validate().map_err(|err| err.to_string())?;
+            match post.slug() {
+                Some(slug) if !slugs.insert(slug.to_string()) => Err(format!(
+                    "slug: `{slug}` is already taken by an earlier post of the export"
+                )),
+                _ => Ok(post),
+            }
+        });
+        let post = match mapped {
+            Ok(post) => post,
+            Err(reason) => {
+                response.failed += 1;
+                result.status = WordPressItemStatus::Invalid;
+                result.reason = Some(reason);
+                response.results.push(result);
+                continue;
+            }
+        };
+
+        let (status, id) = if dry_run {
+            let existing = match post.slug() {
+                Some(slug) => posts.get_by_slug(slug).await?,
+                None => None,
+            };
+            match existing {
+                Some(existing) => (WordPressItemStatus::Updated, Some(existing.id)),
+                None => (WordPressItemStatus::Created, None),
+            }
+        } else {
+            match posts.upsert_by_slug(&post).await {
+                Ok(Upserted::Created(created)) => (WordPressItemStatus::Created, Some(created.id)),
+                Ok(Upserted::Updated(updated)) => (WordPressItemStatus::Updated, Some(updated.id)),
+                Err(err) => {
+                    response.failed += 1;
+                    result.status = WordPressItemStatus::Invalid;
+                    result.reason = Some(rejection(err)?);
+                    response.results.push(result);
+                    continue;
+                }
+            }
+        };
+        match status {
+            WordPressItemStatus::Created => response.created += 1,
+            _ => response.updated += 1,
+        }
+        result.status = status;
+        result.id = id;
+        result.slug = post.slug().map(str::to_string);
+        response.results.push(result);
+    }
+    Ok(response)
+}
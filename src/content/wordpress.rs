@@ -0,0 +1,351 @@
+use crate::model::blog::{BlogPostBody, is_valid_slug, slugify};
+use chrono::{DateTime, NaiveDateTime, Utc};
+use htmd::{
+    HtmlToMarkdown,
+    options::{BrStyle, BulletListMarker, HrStyle, Options},
+};
+use quick_xml::{Reader, events::Event};
+use std::{collections::HashMap, mem};
+
+/// Elements of WordPress HTML that start a block of their own; text between blank lines
+/// that starts with anything else is a paragraph.
+const BLOCK_TAGS: [&str; 23] = [
+    "address",
+    "article",
+    "aside",
+    "blockquote",
+    "details",
+    "div",
+    "dl",
+    "figure",
+    "footer",
+    "form",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "header",
+    "hr",
+    "ol",
+    "p",
+    "pre",
+    "table",
+    "ul",
+];
+
+/// An item of a WordPress export: a post, page, attachment or other content.
+#[derive(Debug, Default)]
+pub struct Item {
+    /// Title of the item (`title`).
+    pub title: String,
+    /// Permalink of the item (`link`).
+    pub link: Option<String>,
+    /// Login of the author of the item (`dc:creator`).
+    creator: Option<String>,
+    /// HTML body of the item (`content:encoded`).
+    content: String,
+    /// ID of the item in WordPress (`wp:post_id`).
+    post_id: Option<String>,
+    /// Publication date in UTC, as `YYYY-MM-DD HH:MM:SS` (`wp:post_date_gmt`).
+    post_date_gmt: Option<String>,
+    /// Slug of the item, percent-encoded (`wp:post_name`).
+    pub post_name: Option<String>,
+    /// Status of the item, e.g. `publish` or `draft` (`wp:status`).
+    status: String,
+    /// Type of the item, e.g. `post` or `page` (`wp:post_type`).
+    post_type: String,
+    /// Categories of the item, in order.
+    categories: Vec<String>,
+    /// Tags of the item, in order.
+    tags: Vec<String>,
+}
+
+/// The contents of a WordPress eXtended RSS (WXR) export that map onto posts.
+#[derive(Debug, Default)]
+pub struct Export {
+    /// Display names of the authors, by login.
+    authors: HashMap<String, String>,
+    /// Items of the export, in order.
+    pub items: Vec<Item>,
+}
+
+/// Returns the text of an optional element, treating an empty one as missing.
+fn non_empty(text: String) -> Option<String> {
+    let text = text.trim();
+    (!text.is_empty()).then(|| text.to_string())
+}
+
+/// Parses a WordPress eXtended RSS (WXR) export, as written by Tools → Export.
+///
+/// # Errors
+///
+/// Returns a description of the problem if the file is not well-formed XML or not a
+/// WordPress export.
+pub fn parse(xml: &[u8]) -> Result<Export, String> {
+    let mut reader = Reader::from_reader(xml);
+    reader.config_mut().expand_empty_elements = true;
+    let mut buf = Vec::new();
+    let mut export = Export::default();
+    let mut is_wxr = false;
+    // Names of the open elements, and the text of the innermost one.
+    let mut path: Vec<String> = Vec::new();
+    let mut text = String::new();
+    let mut item: Option<Item> = None;
+    let mut author: (Option<String>, Option<String>) = (None, None);
+    let mut category_domain = None;
+
+    loop {
+        let event = reader
+            .read_event_into(&mut buf)
+            .map_err(|err| format!("invalid XML at byte {}: {err}", reader.error_position()))?;
+        match event {
+            Event::Start(start) => {
+                let name = String::from_utf8_lossy(start.name().as_ref()).into_owned();
+                match (path.last().map(String::as_str), name.as_str()) {
+                    (Some("channel"), "item") => item = Some(Item::default()),
+                    (Some("item"), "category") => {
+                        category_domain = start
+                            .try_get_attribute("domain")
+                            .ok()
+                            .flatten()
+                            .map(|domain| String::from_utf8_lossy(&domain.value).into_owned());
+                    }
+                    _ => {}
+                }
+                path.push(name);
+                text.clear();
+            }
+            Event::Text(escaped) => match escaped.unescape() {
+                Ok(unescaped) => text.push_str(&unescaped),
+                // HTML entities such as `&nbsp;` are not XML entities; keep them as is.
+                Err(_) => text.push_str(&String::from_utf8_lossy(&escaped)),
+            },
+            Event::CData(data) => text.push_str(&String::from_utf8_lossy(&data)),
+            Event::End(_) => {
+                let name = path.pop().unwrap_or_default();
+                let value = mem::take(&mut text);
+                match (path.last().map(String::as_str), name.as_str()) {
+                    (_, "wp:wxr_version") => is_wxr = true,
+                    (Some("channel"), "item") => export.items.extend(item.take()),
+                    (Some("channel"), "wp:author") => {
+                        if let (Some(login), Some(name)) = mem::take(&mut author) {
+                            export.authors.insert(login, name);
+                        }
+                    }
+                    (Some("wp:author"), "wp:author_login") => author.0 = non_empty(value),
+                    (Some("wp:author"), "wp:author_display_name") => author.1 = non_empty(value),
+                    (Some("item"), field) => {
+                        if let Some(item) = item.as_mut() {
+                            item.set(field, value, category_domain.take());
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+        buf.clear();
+    }
+
+    if is_wxr {
+        Ok(export)
+    } else {
+        Err("not a WordPress export: there is no `wp:wxr_version` element".to_string())
+    }
+}
+
+impl Item {
+    /// Sets the field read from an element directly inside `<item>`.
+    fn set(&mut self, element: &str, value: String, category_domain: Option<String>) {
+        match element {
+            "title" => self.title = value.trim().to_string(),
+            "link" => self.link = non_empty(value),
+            "dc:creator" => self.creator = non_empty(value),
+            "content:encoded" => self.content = value,
+            "wp:post_id" => self.post_id = non_empty(value),
+            "wp:post_date_gmt" => self.post_date_gmt = non_empty(value),
+            "wp:post_name" => self.post_name = non_empty(value),
+            "wp:status" => self.status = value.trim().to_string(),
+            "wp:post_type" => self.post_type = value.trim().to_string(),
+            "category" => {
+                let Some(value) = non_empty(value) else {
+                    return;
+                };
+                match category_domain.as_deref() {
+                    Some("category") => self.categories.push(value),
+                    Some("post_tag") => self.tags.push(value),
+                    _ => {}
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Returns why the item is not imported, if it is not: only posts are, and trashed
+    /// and auto-saved ones are not.
+    pub fn skip_reason(&self) -> Option<String> {
+        if self.post_type != "post" {
+            Some(format!("post type `{}` is not imported", self.post_type))
+        } else if matches!(self.status.as_str(), "trash" | "auto-draft") {
+            Some(format!("status `{}` is not imported", self.status))
+        } else {
+            None
+        }
+    }
+
+    /// Returns the slug of the item: its WordPress slug if it is valid here, or else one
+    /// derived from its WordPress slug, its title or its ID, in that order.
+    fn slug(&self) -> Option<String> {
+        let original = self.post_name.as_deref();
+        original
+            .filter(|slug| is_valid_slug(slug))
+            .map(str::to_string)
+            .or_else(|| original.and_then(|slug| slugify(&percent_decode(slug))))
+            .or_else(|| slugify(&self.title))
+            .or_else(|| self.post_id.as_ref().map(|id| format!("wp-{id}")))
+    }
+
+    /// Returns the publication date, which WordPress leaves as zeros for drafts.
+    fn published_at(&self) -> Option<DateTime<Utc>> {
+        self.post_date_gmt
+            .as_deref()
+            .and_then(|date| NaiveDateTime::parse_from_str(date, "%Y-%m-%d %H:%M:%S").ok())
+            .map(|date| date.and_utc())
+    }
+}
+
+/// Decodes a percent-encoded slug, as WordPress stores slugs with non-ASCII characters.
+fn percent_decode(slug: &str) -> String {
+    let bytes = slug.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+    while let Some(&byte) = bytes.get(index) {
+        let escaped = (byte == b'%')
+            .then(|| bytes.get(index + 1..index + 3))
+            .flatten()
+            .and_then(|hex| u8::from_str_radix(&String::from_utf8_lossy(hex), 16).ok());
+        match escaped {
+            Some(escaped) => {
+                decoded.push(escaped);
+                index += 3;
+            }
+            None => {
+                decoded.push(byte);
+                index += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// Returns whether a chunk of WordPress HTML starts with a block element or a comment,
+/// such as the `<!-- wp:paragraph -->` markers of the block editor.
+fn starts_with_block(chunk: &str) -> bool {
+    let Some(tag) = chunk.strip_prefix('<') else {
+        return false;
+    };
+    let name: String = tag
+        .chars()
+        .take_while(char::is_ascii_alphanumeric)
+        .collect::<String>()
+        .to_ascii_lowercase();
+    tag.starts_with("!--") || BLOCK_TAGS.contains(&name.as_str())
+}
+
+/// Adds the paragraphs WordPress leaves implicit, like its `wpautop` filter: text
+/// separated by blank lines becomes paragraphs, and single line breaks become `<br>`.
+/// Preformatted blocks are left alone.
+fn add_paragraphs(html: &str) -> String {
+    let html = html
+        .lines()
+        .map(|line| if line.trim().is_empty() { "" } else { line })
+        .collect::<Vec<_>>()
+        .join("\n");
+    let mut chunks = Vec::new();
+    let mut in_pre = false;
+    for chunk in html.split("\n\n") {
+        if in_pre || starts_with_block(chunk.trim_start()) {
+            chunks.push(chunk.to_string());
+        } else if !chunk.trim().is_empty() {
+            chunks.push(format!("<p>{}</p>", chunk.trim().replace('\n', "<br>")));
+        }
+        if let Some(open) = chunk.rfind("<pre") {
+            in_pre = chunk.rfind("</pre").is_none_or(|close| close < open);
+        } else if chunk.contains("</pre") {
+            in_pre = false;
+        }
+    }
+    chunks.join("\n\n")
+}
+
+/// Converts the HTML body of a WordPress post to Markdown.
+///
+/// # Errors
+///
+/// Returns a description of the problem if the HTML cannot be converted.
+pub fn html_to_markdown(html: &str) -> Result<String, String> {
+    let converter = HtmlToMarkdown::builder()
+        .options(Options {
+            br_style: BrStyle::Backslash,
+            bullet_list_marker: BulletListMarker::Dash,
+            ul_bullet_spacing: 1,
+            ol_number_spacing: 1,
+            hr_style: HrStyle::Dashes,
+            ..Options::default()
+        })
+        .skip_tags(vec!["script", "style"])
+        .build();
+    converter
+        .convert(&add_paragraphs(html))
+        .map_err(|err| format!("content: {err}"))
+}
+
+impl Export {
+    /// Maps an item onto a post; the post is not validated.
+    ///
+    /// The first category becomes the category of the post, and its tags are the tags of
+    /// the item followed by its other categories, or the category alone if there are
+    /// none, since a post needs a tag. Published items are published posts, and items
+    /// in any other status are drafts. The author is the display name of the item's
+    /// author.
+    ///
+    /// # Errors
+    ///
+    /// Returns a description of the problem if the body cannot be converted or no slug
+    /// can be found for the item.
+    pub fn to_post(&self, item: &Item) -> Result<BlogPostBody, String> {
+        let slug = item
+            .slug()
+            .ok_or("slug: the item has no slug, title or ID to derive one from")?;
+        let category = item.categories.first().cloned().unwrap_or_default();
+        let mut tags: Vec<String> = Vec::new();
+        for tag in item.tags.iter().chain(item.categories.iter().skip(1)) {
+            if !tags.contains(tag) {
+                tags.push(tag.clone());
+            }
+        }
+        if tags.is_empty() && !category.is_empty() {
+            tags.push(category.clone());
+        }
+        let author = item.creator.as_ref().map(|login| {
+            self.authors
+                .get(login)
+                .cloned()
+                .unwrap_or_else(|| login.clone())
+        });
+        Ok(BlogPostBody {
+            title: item.title.clone(),
+            content: html_to_markdown(&item.content)?,
+            category,
+            tags,
+            slug: Some(Some(slug)),
+            author: author.map(Some),
+            draft: Some(item.status != "publish"),
+            published_at: item.published_at().map(Some),
+            ..BlogPostBody::default()
+        })
+    }
+}
@@ -1,35 +1,181 @@
+use crate::config;
 use anyhow::{Context, Result};
-use sqlx::{Pool, Postgres};
-use std::env;
+use log::LevelFilter;
+use sqlx::{
+    ConnectOptions, Pool, Postgres, Sqlite,
+    migrate::Migrator,
+    postgres::PgConnectOptions,
+    sqlite::{SqliteConnectOptions, SqliteJournalMode, SqlitePoolOptions},
+};
+use std::{env, str::FromStr, time::Duration};
+
+/// Migrations for PostgreSQL databases, from `migrations`.
+pub static PG_MIGRATOR: Migrator = sqlx::migrate!();
+
+/// Migrations for SQLite databases, from `migrations/sqlite`.
+pub static SQLITE_MIGRATOR: Migrator = sqlx::migrate!("./migrations/sqlite");
+
+/// A connection pool to one of the supported databases.
+#[derive(Debug, Clone)]
+pub enum Database {
+    /// A PostgreSQL pool, selected by a `postgres://` or `postgresql://` URL.
+    Postgres(Pool<Postgres>),
+    /// A SQLite pool, selected by a `sqlite:` URL.
+    Sqlite(Pool<Sqlite>),
+}
+
+impl Database {
+    /// Returns the migrations for this kind of database.
+    pub fn migrator(&self) -> &'static Migrator {
+        match self {
+            Self::Postgres(_) => &PG_MIGRATOR,
+            Self::Sqlite(_) => &SQLITE_MIGRATOR,
+        }
+    }
+
+    /// Runs any pending migrations.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a migration fails, or if an applied migration was changed.
+    pub async fn migrate(&self) -> Result<()> {
+        match self {
+            Self::Postgres(pool) => self.migrator().run(pool).await,
+            Self::Sqlite(pool) => self.migrator().run(pool).await,
+        }
+        .context("Failed to run migrations")
+    }
+}
+
+/// Connects to the database named by `DATABASE_URL` and runs any pending migrations.
+///
+/// The backend is chosen from the URL scheme: `sqlite:` URLs (e.g. `sqlite://blog.db` or
+/// `sqlite::memory:`) select SQLite, anything else is treated as a PostgreSQL URL.
+///
+/// # Errors
+///
+/// Returns an error if `DATABASE_URL` is not set, or if connecting or migrating fails;
+/// see [`pg_connect`] and [`sqlite_connect`].
+pub async fn db_connect() -> Result<Database> {
+    let database = db_open().await?;
+    database.migrate().await?;
+    Ok(database)
+}
+
+/// Connects to the database named by `DATABASE_URL` without running migrations.
+///
+/// # Errors
+///
+/// Returns an error if `DATABASE_URL` is not set or invalid, or if connecting fails.
+pub async fn db_open() -> Result<Database> {
+    // Retrieve the database URL from the environment variable
+    let database_url = env::var("DATABASE_URL").context("DATABASE_URL is not set")?;
+
+    if database_url.starts_with("sqlite:") {
+        sqlite_pool(&database_url).await.map(Database::Sqlite)
+    } else {
+        pg_pool(&database_url).await.map(Database::Postgres)
+    }
+}
+
+/// Reads the slow statement threshold from `DB_SLOW_QUERY_MS` (default `500`).
+fn slow_query_threshold() -> Result<Duration> {
+    Ok(Duration::from_millis(config::parse_or(
+        "DB_SLOW_QUERY_MS",
+        500,
+    )?))
+}
 
 /// Establishes a connection pool to the PostgreSQL database and runs any pending migrations.
 ///
+/// Every statement is logged at `DEBUG` together with its elapsed time, and statements
+/// slower than `DB_SLOW_QUERY_MS` (default `500`) are logged at `WARN`.
+///
 /// # Errors
 ///
 /// Returns an error if:
-/// - The `DATABASE_URL` environment variable is not set.
+/// - `database_url` is not a valid Postgres URL.
+/// - `DB_SLOW_QUERY_MS` is not a valid number.
 /// - The connection to the database cannot be established.
 /// - Migrations fail to run.
+pub async fn pg_connect(database_url: &str) -> Result<Pool<Postgres>> {
+    let pool = pg_pool(database_url).await?;
+    Database::Postgres(pool.clone()).migrate().await?;
+    Ok(pool)
+}
+
+/// Establishes a connection pool to the PostgreSQL database, without running migrations.
 ///
-/// # Panics
+/// # Errors
 ///
-/// This function will panic if the migrations directory is not found.
-pub async fn db_connect() -> Result<Pool<Postgres>> {
-    // Retrieve the database URL from the environment variable
-    let database_url = env::var("DATABASE_URL").context("DATABASE_URL is not set")?;
+/// Returns an error if the URL or `DB_SLOW_QUERY_MS` is invalid, or if the connection
+/// cannot be established.
+pub async fn pg_pool(database_url: &str) -> Result<Pool<Postgres>> {
+    let options = database_url
+        .parse::<PgConnectOptions>()
+        .context("DATABASE_URL is not a valid Postgres URL")?
+        .log_statements(LevelFilter::Debug)
+        .log_slow_statements(LevelFilter::Warn, slow_query_threshold()?);
 
     // Create a connection pool with a maximum of 5 connections
     let pool = sqlx::postgres::PgPoolOptions::new()
         .max_connections(5)
-        .connect(&database_url)
+        .connect_with(options)
         .await
         .context("Failed to connect to Postgres")?;
 
-    // Run pending migrations
-    sqlx::migrate!()
-        .run(&pool)
+    Ok(pool)
+}
+
+/// Opens a SQLite database, creating the file if needed, and runs the SQLite migrations
+/// from `migrations/sqlite`.
+///
+/// The database uses write-ahead logging and enforces foreign keys. Statements are logged
+/// like Postgres statements; see [`pg_connect`]. An in-memory database (`sqlite::memory:`)
+/// lives as long as the returned pool and is served over a single connection, so every
+/// query sees the same data.
+///
+/// # Errors
+///
+/// Returns an error if:
+/// - `database_url` is not a valid SQLite URL.
+/// - `DB_SLOW_QUERY_MS` is not a valid number.
+/// - The database cannot be opened.
+/// - Migrations fail to run.
+pub async fn sqlite_connect(database_url: &str) -> Result<Pool<Sqlite>> {
+    let pool = sqlite_pool(database_url).await?;
+    Database::Sqlite(pool.clone()).migrate().await?;
+    Ok(pool)
+}
+
+/// Opens a SQLite database, creating the file if needed, without running migrations.
+///
+/// # Errors
+///
+/// Returns an error if the URL or `DB_SLOW_QUERY_MS` is invalid, or if the database
+/// cannot be opened.
+pub async fn sqlite_pool(database_url: &str) -> Result<Pool<Sqlite>> {
+    let options = SqliteConnectOptions::from_str(database_url)
+        .context("DATABASE_URL is not a valid SQLite URL")?
+        .create_if_missing(true)
+        .journal_mode(SqliteJournalMode::Wal)
+        .foreign_keys(true)
+        .log_statements(LevelFilter::Debug)
+        .log_slow_statements(LevelFilter::Warn, slow_query_threshold()?);
+
+    let in_memory = database_url.contains(":memory:") || database_url.contains("mode=memory");
+    let pool_options = if in_memory {
+        SqlitePoolOptions::new()
+            .max_connections(1)
+            .idle_timeout(None)
+            .max_lifetime(None)
+    } else {
+        SqlitePoolOptions::new().max_connections(5)
+    };
+    let pool = pool_options
+        .connect_with(options)
         .await
-        .context("Failed to run migrations")?;
+        .context("Failed to open SQLite database")?;
 
     Ok(pool)
 }
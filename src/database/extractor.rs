@@ -1,11 +1,21 @@
 use axum::{
-    extract::{FromRef, FromRequestParts},
+    extract::{FromRef, FromRequestParts, OptionalFromRequestParts, Request},
     http::request::Parts,
+    middleware::Next,
+    response::{IntoResponse, Response},
 };
-use sqlx::PgPool;
+use sqlx::{Connection, PgConnection, PgPool, Postgres, Transaction};
+use std::{
+    ops::{Deref, DerefMut},
+    sync::Arc,
+};
+use tokio::sync::{Mutex, OwnedMappedMutexGuard, OwnedMutexGuard};
 
 use crate::error::AppError;
 
+/// A PostgreSQL transaction that outlives the extractor that began it.
+type PgTransaction = Transaction<'static, Postgres>;
+
 /// A wrapper around a SQLx PostgreSQL connection.
 ///
 /// This struct encapsulates a `sqlx::pool::PoolConnection<sqlx::Postgres>`,
@@ -18,7 +28,7 @@ pub struct DatabaseConnection(pub sqlx::pool::PoolConnection<sqlx::Postgres>);
 /// facilitating seamless database interactions within request processing.
 impl<S> FromRequestParts<S> for DatabaseConnection
 where
-    PgPool: FromRef<S>,
+    Option<PgPool>: FromRef<S>,
     S: Send + Sync,
 {
     type Rejection = AppError;
@@ -30,13 +40,174 @@ where
     ///
     /// # Errors
     ///
-    /// Returns an `AppError` if acquiring a connection from the pool fails.
+    /// Returns an `AppError` if the application is not backed by PostgreSQL, or if
+    /// acquiring a connection from the pool fails.
     async fn from_request_parts(_parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
         // Retrieve the connection pool from the application state
-        let pool = PgPool::from_ref(state);
+        let pool = Option::<PgPool>::from_ref(state).ok_or_else(|| {
+            AppError::ServiceUnavailable("This endpoint requires PostgreSQL".to_string())
+        })?;
         // Acquire a connection from the pool
         let conn = pool.acquire().await?;
         // Return the connection wrapped in DatabaseConnection
         Ok(Self(conn))
     }
 }
+
+/// Request extension holding the transaction of a request, if one was begun.
+///
+/// Inserted by [`finish_transaction`]; [`DatabaseTransaction`] begins the transaction on
+/// first use and the middleware commits or rolls it back once the response is ready.
+#[derive(Clone, Default)]
+struct TransactionSlot(Arc<Mutex<Option<PgTransaction>>>);
+
+/// A PostgreSQL transaction spanning the whole request.
+///
+/// The transaction is begun when the extractor runs and finished by the
+/// [`finish_transaction`] middleware, which must wrap every route using this extractor:
+///
+/// - it is committed if the handler produces a successful (`1xx`-`3xx`) response,
+///   e.g. by returning `Ok`;
+/// - it is rolled back if the response is an error, e.g. because the handler returned
+///   `Err(AppError)`, or if the handler panics or is cancelled by a timeout.
+///
+/// The extractor dereferences to a [`PgConnection`], so queries run on it directly
+/// (`.execute(&mut *tx)`). Nested units of work use [`savepoint`](Self::savepoint).
+///
+/// # Example
+///
+/// ```rust,ignore
+/// async fn publish(mut tx: DatabaseTransaction, Path(id): Path<i32>) -> Result<StatusCode, AppError> {
+///     sqlx::query!("UPDATE blog_posts SET category = 'published' WHERE id = $1", id)
+///         .execute(&mut *tx)
+///         .await?;
+///     Ok(StatusCode::NO_CONTENT)
+/// }
+/// ```
+pub struct DatabaseTransaction(OwnedMappedMutexGuard<Option<PgTransaction>, PgTransaction>);
+
+impl DatabaseTransaction {
+    /// Begins a nested unit of work inside the request's transaction, backed by a savepoint.
+    ///
+    /// Committing the returned transaction releases the savepoint and keeps its changes
+    /// in the request's transaction; dropping it or calling `rollback` undoes only the
+    /// changes made since the savepoint. Savepoints can themselves be nested.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `AppError` if the savepoint cannot be created.
+    pub async fn savepoint(&mut self) -> Result<Transaction<'_, Postgres>, AppError> {
+        Ok(self.0.begin().await?)
+    }
+}
+
+impl Deref for DatabaseTransaction {
+    type Target = PgConnection;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl DerefMut for DatabaseTransaction {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+/// Extractor implementation for `DatabaseTransaction`.
+impl<S> FromRequestParts<S> for DatabaseTransaction
+where
+    Option<PgPool>: FromRef<S>,
+    S: Send + Sync,
+{
+    type Rejection = AppError;
+
+    /// Begins a transaction on a connection from the application's pool.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `AppError` if:
+    /// - The application is not backed by PostgreSQL.
+    /// - The route is not wrapped by [`finish_transaction`].
+    /// - The extractor is used twice for the same request.
+    /// - Beginning the transaction fails.
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let pool = Option::<PgPool>::from_ref(state).ok_or_else(|| {
+            AppError::ServiceUnavailable("This endpoint requires PostgreSQL".to_string())
+        })?;
+        let Some(TransactionSlot(slot)) = parts.extensions.get::<TransactionSlot>().cloned() else {
+            tracing::error!("DatabaseTransaction used on a route without finish_transaction");
+            return Err(AppError::InternalServerError);
+        };
+        let Ok(mut guard) = slot.try_lock_owned() else {
+            tracing::error!("DatabaseTransaction extracted twice for the same request");
+            return Err(AppError::InternalServerError);
+        };
+        if guard.is_none() {
+            *guard = Some(pool.begin().await?);
+        }
+
+        OwnedMutexGuard::try_map(guard, Option::as_mut)
+            .map(Self)
+            .map_err(|_| AppError::InternalServerError)
+    }
+}
+
+/// Extractor implementation for `Option<DatabaseTransaction>`, for handlers that also
+/// serve applications not backed by PostgreSQL.
+impl<S> OptionalFromRequestParts<S> for DatabaseTransaction
+where
+    Option<PgPool>: FromRef<S>,
+    S: Send + Sync,
+{
+    type Rejection = AppError;
+
+    /// Begins a transaction like the [`DatabaseTransaction`] extractor, or returns `None`
+    /// if the application is not backed by PostgreSQL.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `AppError` if the application is backed by PostgreSQL and the
+    /// transaction cannot be begun.
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &S,
+    ) -> Result<Option<Self>, Self::Rejection> {
+        if Option::<PgPool>::from_ref(state).is_none() {
+            return Ok(None);
+        }
+        <Self as FromRequestParts<S>>::from_request_parts(parts, state)
+            .await
+            .map(Some)
+    }
+}
+
+/// Middleware finishing the transaction begun by a [`DatabaseTransaction`] extractor.
+///
+/// Commits the transaction when the response is not a client or server error and
+/// rolls it back otherwise. If the commit fails, the response is replaced by the
+/// database error. Requests whose handler did not use the extractor are unaffected.
+pub async fn finish_transaction(mut request: Request, next: Next) -> Response {
+    let TransactionSlot(slot) = request
+        .extensions_mut()
+        .get_or_insert_default::<TransactionSlot>()
+        .clone();
+    let response = next.run(request).await;
+
+    let Some(transaction) = slot.lock().await.take() else {
+        return response;
+    };
+    let status = response.status();
+    if status.is_client_error() || status.is_server_error() {
+        if let Err(err) = transaction.rollback().await {
+            tracing::warn!("Failed to roll back transaction: {err}");
+        }
+        response
+    } else {
+        match transaction.commit().await {
+            Ok(()) => response,
+            Err(err) => AppError::from(err).into_response(),
+        }
+    }
+}
@@ -4,9 +4,15 @@
 /// It provides connection pooling and request extractors to simplify database access.
 pub mod connection;
 
-/// Request extractors for database connections.
+/// Request extractors for database connections and transactions.
 ///
 /// This module defines custom extractors that allow handlers to easily access database
-/// connections from the application state. These extractors integrate seamlessly with
+/// connections, or a transaction spanning the request, from the application state. These extractors integrate seamlessly with
 /// Axum's request handling system.
 pub mod extractor;
+
+/// Read replica routing.
+///
+/// This module sends read-only queries to a streaming replica while it is healthy, and
+/// keeps clients reading from the primary for a short while after they write.
+pub mod replica;
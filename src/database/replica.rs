@@ -0,0 +1,331 @@
+use crate::{
+    config,
+    server::middleware::rate_limit::{self, RateLimiter, RouteClass},
+};
+use anyhow::{Context, Result};
+use axum::{
+    extract::{Request, State},
+    middleware::Next,
+    response::Response,
+};
+use log::LevelFilter;
+use sqlx::{ConnectOptions, PgPool, postgres::PgConnectOptions, postgres::PgPoolOptions};
+use std::{
+    collections::HashMap,
+    sync::{
+        Arc, Mutex, PoisonError,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::{Duration, Instant},
+};
+
+/// Number of recent writers tracked before stale entries are swept.
+const SWEEP_ABOVE: usize = 1024;
+
+tokio::task_local! {
+    /// Whether reads made by the current request must go to the primary.
+    static READ_PRIMARY: bool;
+}
+
+/// Read replica configuration read from the environment.
+///
+/// | Variable                        | Default | Description                                                |
+/// |---------------------------------|---------|------------------------------------------------------------|
+/// | `DATABASE_REPLICA_URL`          | unset   | Postgres URL of a streaming replica used for reads.        |
+/// | `DB_REPLICA_MAX_LAG_MS`         | `5000`  | Replay lag above which reads fall back to the primary.     |
+/// | `DB_REPLICA_STICKY_SECS`        | `5`     | How long a client's reads go to the primary after a write. |
+/// | `DB_REPLICA_CHECK_INTERVAL_SECS`| `5`     | Interval between replica health checks.                    |
+#[derive(Debug, Clone)]
+pub struct ReplicaConfig {
+    /// Connection URL of the replica, or `None` to read from the primary.
+    pub url: Option<String>,
+    /// Largest tolerated replay lag.
+    pub max_lag: Duration,
+    /// How long reads stick to the primary after a client writes.
+    pub sticky: Duration,
+    /// Interval between health checks.
+    pub check_interval: Duration,
+}
+
+impl ReplicaConfig {
+    /// Builds the configuration from environment variables.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if any of the variables holds an invalid value.
+    pub fn from_env() -> Result<Self> {
+        Ok(Self {
+            url: config::var_opt("DATABASE_REPLICA_URL"),
+            max_lag: Duration::from_millis(config::parse_or("DB_REPLICA_MAX_LAG_MS", 5000)?),
+            sticky: Duration::from_secs(config::parse_or("DB_REPLICA_STICKY_SECS", 5)?),
+            check_interval: Duration::from_secs(config::parse_or(
+                "DB_REPLICA_CHECK_INTERVAL_SECS",
+                5,
+            )?),
+        })
+    }
+}
+
+/// A streaming read replica of the primary database.
+///
+/// Reads are served by the replica only while it is healthy: reachable and replaying
+/// within [`ReplicaConfig::max_lag`] of the primary. Clients that wrote recently read
+/// from the primary instead, so they see their own writes; see [`read_your_writes`].
+///
+/// Cloning is cheap; all clones share the same pool and health state.
+#[derive(Debug, Clone)]
+pub struct Replica {
+    /// Connections to the replica.
+    pool: PgPool,
+    /// Lag threshold, stickiness and check interval.
+    config: Arc<ReplicaConfig>,
+    /// Result of the latest health check.
+    healthy: Arc<AtomicBool>,
+    /// When each client last wrote, keyed like the rate limiter keys clients.
+    recent_writers: Arc<Mutex<HashMap<String, Instant>>>,
+}
+
+impl Replica {
+    /// Creates the replica described by the configuration, if any.
+    ///
+    /// The pool connects lazily and the replica counts as unhealthy until its first
+    /// health check passes, so an unreachable replica never prevents startup. Call
+    /// [`spawn_health_checks`](Self::spawn_health_checks) to start checking it.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `DATABASE_REPLICA_URL` is not a valid Postgres URL.
+    pub fn from_config(config: ReplicaConfig) -> Result<Option<Self>> {
+        let Some(url) = &config.url else {
+            return Ok(None);
+        };
+        let options = url
+            .parse::<PgConnectOptions>()
+            .context("DATABASE_REPLICA_URL is not a valid Postgres URL")?
+            .log_statements(LevelFilter::Debug);
+        let pool = PgPoolOptions::new()
+            .max_connections(5)
+            .acquire_timeout(Duration::from_secs(2))
+            .connect_lazy_with(options);
+
+        Ok(Some(Self {
+            pool,
+            config: Arc::new(config),
+            healthy: Arc::default(),
+            recent_writers: Arc::default(),
+        }))
+    }
+
+    /// Returns the pool reads should use: the replica's, unless it is unhealthy or the
+    /// current request must read from the primary.
+    pub fn reader<'a>(&'a self, primary: &'a PgPool) -> &'a PgPool {
+        let read_primary = READ_PRIMARY
+            .try_with(|read_primary| *read_primary)
+            .unwrap_or(false);
+        if read_primary || !self.healthy.load(Ordering::Relaxed) {
+            primary
+        } else {
+            &self.pool
+        }
+    }
+
+    /// Measures the replica's replay lag.
+    ///
+    /// A replica that has replayed everything it received has no lag, even if the
+    /// primary has been idle for a while.
+    async fn lag(&self) -> Result<Duration, sqlx::Error> {
+        let lag_ms: i64 = sqlx::query_scalar(
+            r#"
+            SELECT CASE
+                WHEN NOT pg_is_in_recovery()
+                  OR pg_last_wal_receive_lsn() = pg_last_wal_replay_lsn() THEN 0
+                ELSE COALESCE(
+                    EXTRACT(EPOCH FROM now() - pg_last_xact_replay_timestamp()) * 1000, 0
+                )
+            END::BIGINT
+            "#,
+        )
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(Duration::from_millis(u64::try_from(lag_ms).unwrap_or(0)))
+    }
+
+    /// Checks the replica once and records whether it may serve reads.
+    pub async fn check_health(&self) -> bool {
+        let lag = self.lag().await;
+        self.record_lag(lag)
+    }
+
+    /// Records whether the replica may serve reads given the outcome of measuring its
+    /// lag, and returns it.
+    fn record_lag(&self, lag: Result<Duration, sqlx::Error>) -> bool {
+        let healthy = match lag {
+            Ok(lag) if lag <= self.config.max_lag => true,
+            Ok(lag) => {
+                tracing::warn!(lag_ms = lag.as_millis(), "read replica is lagging");
+                false
+            }
+            Err(err) => {
+                tracing::warn!("read replica health check failed: {err}");
+                false
+            }
+        };
+        if self.healthy.swap(healthy, Ordering::Relaxed) != healthy {
+            tracing::info!(healthy, "read replica health changed");
+        }
+        healthy
+    }
+
+    /// Checks the replica's health at the configured interval, for as long as the
+    /// process runs.
+    pub fn spawn_health_checks(&self) {
+        let replica = self.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(replica.config.check_interval);
+            loop {
+                interval.tick().await;
+                replica.check_health().await;
+            }
+        });
+    }
+
+    /// Returns whether the client wrote within the sticky window.
+    fn wrote_recently(&self, client: &str, now: Instant) -> bool {
+        self.recent_writers
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(client)
+            .is_some_and(|written| now.saturating_duration_since(*written) < self.config.sticky)
+    }
+
+    /// Records that the client just wrote.
+    fn record_write(&self, client: String, now: Instant) {
+        let mut writers = self
+            .recent_writers
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        if writers.len() >= SWEEP_ABOVE {
+            writers
+                .retain(|_, written| now.saturating_duration_since(*written) < self.config.sticky);
+        }
+        writers.insert(client, now);
+    }
+}
+
+/// Middleware giving clients read-your-writes consistency when reads go to a replica.
+///
+/// Reads by a client that wrote within [`ReplicaConfig::sticky`] are sent to the
+/// primary, as are the reads made while handling a write. Clients are identified like
+/// the rate limiter identifies them. Without a replica the middleware does nothing.
+pub async fn read_your_writes(
+    State(replica): State<Option<Replica>>,
+    State(limiter): State<RateLimiter>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let Some(replica) = replica else {
+        return next.run(request).await;
+    };
+
+    let is_write = RouteClass::of(request.method(), request.uri().path()) == RouteClass::Write;
+    let client = rate_limit::client_key(&request, limiter.config());
+    let read_primary = is_write || replica.wrote_recently(&client, Instant::now());
+
+    let response = READ_PRIMARY.scope(read_primary, next.run(request)).await;
+    if is_write && !response.status().is_client_error() {
+        replica.record_write(client, Instant::now());
+    }
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns a replica of an unreachable database, with the given stickiness and
+    /// largest tolerated lag.
+    fn replica(sticky: Duration, max_lag: Duration) -> Result<Replica> {
+        Replica::from_config(ReplicaConfig {
+            url: Some("postgres://blog@127.0.0.1:1/blog".to_string()),
+            max_lag,
+            sticky,
+            check_interval: Duration::from_secs(5),
+        })?
+        .context("a replica is configured")
+    }
+
+    /// Returns a pool standing in for the primary, which never connects.
+    fn primary() -> Result<PgPool> {
+        Ok(PgPoolOptions::new().connect_lazy("postgres://blog@127.0.0.1:1/primary")?)
+    }
+
+    #[tokio::test]
+    async fn writers_read_from_the_primary_within_the_sticky_window() -> Result<()> {
+        let replica = replica(Duration::from_secs(5), Duration::from_secs(1))?;
+        let now = Instant::now();
+        assert!(!replica.wrote_recently("ann", now));
+
+        replica.record_write("ann".to_string(), now);
+        assert!(replica.wrote_recently("ann", now));
+        assert!(replica.wrote_recently("ann", now + Duration::from_millis(4999)));
+        assert!(!replica.wrote_recently("bob", now));
+
+        // The window expires, and a new write opens it again.
+        let later = now + Duration::from_secs(5);
+        assert!(!replica.wrote_recently("ann", later));
+        replica.record_write("ann".to_string(), later);
+        assert!(replica.wrote_recently("ann", later + Duration::from_secs(1)));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn expired_writers_are_swept() -> Result<()> {
+        let replica = replica(Duration::from_secs(5), Duration::from_secs(1))?;
+        let now = Instant::now();
+        for client in 0..SWEEP_ABOVE {
+            replica.record_write(client.to_string(), now);
+        }
+        let later = now + Duration::from_secs(6);
+        replica.record_write("ann".to_string(), later);
+        let writers = replica
+            .recent_writers
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        assert_eq!(writers.keys().collect::<Vec<_>>(), ["ann"]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn reads_go_to_the_replica_only_while_it_keeps_up() -> Result<()> {
+        let replica = replica(Duration::from_secs(5), Duration::from_secs(1))?;
+        let primary = primary()?;
+        // Until its first check, the replica is assumed unhealthy.
+        assert!(std::ptr::eq(replica.reader(&primary), &primary));
+
+        assert!(replica.record_lag(Ok(Duration::from_millis(1000))));
+        assert!(std::ptr::eq(replica.reader(&primary), &replica.pool));
+        // Requests that must see their writes read from the primary anyway.
+        READ_PRIMARY
+            .scope(true, async {
+                assert!(std::ptr::eq(replica.reader(&primary), &primary));
+            })
+            .await;
+
+        assert!(!replica.record_lag(Ok(Duration::from_millis(1001))));
+        assert!(std::ptr::eq(replica.reader(&primary), &primary));
+        assert!(replica.record_lag(Ok(Duration::ZERO)));
+        assert!(!replica.record_lag(Err(sqlx::Error::PoolTimedOut)));
+        assert!(std::ptr::eq(replica.reader(&primary), &primary));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn unreachable_replicas_are_unhealthy() -> Result<()> {
+        let replica = replica(Duration::from_secs(5), Duration::from_secs(1))?;
+        replica.healthy.store(true, Ordering::Relaxed);
+        assert!(!replica.check_health().await);
+        let primary = primary()?;
+        assert!(std::ptr::eq(replica.reader(&primary), &primary));
+        Ok(())
+    }
+}
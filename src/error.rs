@@ -1,11 +1,14 @@
 use axum::{
-    http::StatusCode,
-    response::{IntoResponse, Response},
     Json,
+    http::{StatusCode, header},
+    response::{IntoResponse, Response},
 };
-use serde_json::json;
+use serde::Serialize;
 use thiserror::Error;
 use tracing::error;
+use utoipa::ToSchema;
+
+use crate::telemetry::request_id;
 
 /// Represents different types of application errors.
 ///
@@ -18,12 +21,21 @@ use tracing::error;
 /// * `DatabaseError` - Represents an error occurring in database operations.
 /// * `NotFound` - Indicates that the requested resource was not found.
 /// * `BadRequest` - Represents a client-side request error.
+/// * `Unauthorized` - Indicates a request without valid credentials.
+/// * `NotAcceptable` - Indicates that the requested API version or representation is unavailable.
+/// * `Conflict` - Indicates that the request conflicts with another request in progress.
+/// * `PayloadTooLarge` - Indicates that the request body exceeds the size limit.
+/// * `UnsupportedMediaType` - Indicates an uploaded file of a type that is not accepted.
+/// * `UnprocessableEntity` - Indicates a well-formed request that cannot be processed.
+/// * `RequestTimeout` - Indicates that the client did not send its request in time.
+/// * `TooManyRequests` - Indicates that the client exceeded its rate limit.
+/// * `ServiceUnavailable` - Indicates that the server could not complete the request in time.
 /// * `InternalServerError` - Covers unexpected server-side errors.
 ///
 /// # Example
 ///
 /// ```rust
-/// use crate::error::AppError;
+/// use blog_api::error::AppError;
 ///
 /// let error = AppError::NotFound("User not found".into());
 /// println!("{}", error); // Outputs: "Not Found: User not found"
@@ -42,31 +54,100 @@ pub enum AppError {
     #[error("Bad Request: {0}")]
     BadRequest(String),
 
+    /// Represents a request without valid credentials for the endpoint.
+    #[error("Unauthorized: {0}")]
+    Unauthorized(String),
+
+    /// Represents a request for an API version or representation that is not served.
+    #[error("Not Acceptable: {0}")]
+    NotAcceptable(String),
+
+    /// Represents a request that conflicts with another request in progress.
+    #[error("Conflict: {0}")]
+    Conflict(String),
+
+    /// Represents a request whose body exceeds the configured size limit.
+    #[error("Payload Too Large: {0}")]
+    PayloadTooLarge(String),
+
+    /// Represents an uploaded file whose type is not recognized or not accepted.
+    #[error("Unsupported Media Type: {0}")]
+    UnsupportedMediaType(String),
+
+    /// Represents a change to a resource that someone else holds the lock of.
+    #[error("Locked: {0}")]
+    Locked(String),
+
+    /// Represents a well-formed request that cannot be processed, e.g. because it reuses
+    /// an idempotency key with a different body.
+    #[error("Unprocessable Entity: {0}")]
+    UnprocessableEntity(String),
+
+    /// Represents a client that did not finish sending its request in time.
+    #[error("Request Timeout")]
+    RequestTimeout,
+
+    /// Represents a rejected request from a client that exceeded its rate limit.
+    #[error("Too Many Requests: retry after {retry_after}s")]
+    TooManyRequests {
+        /// Seconds until the client may retry.
+        retry_after: u64,
+    },
+
+    /// Represents a request the server could not complete, e.g. because it timed out.
+    #[error("Service Unavailable: {0}")]
+    ServiceUnavailable(String),
+
     /// Represents an internal server error.
     #[error("Internal Server Error")]
     InternalServerError,
 }
 
+/// The JSON body of every error response produced by [`AppError`].
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ErrorBody {
+    /// Human-readable description of the error.
+    #[schema(example = "Blog post not found")]
+    pub error: String,
+
+    /// ID of the request that failed, matching the `X-Request-Id` response header.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(example = "0b6f7f64-4d9c-4c8e-9b55-0f5b8d2b1c3a")]
+    pub request_id: Option<String>,
+}
+
 impl IntoResponse for AppError {
     /// Converts the `AppError` into an HTTP response.
     ///
     /// Logs the error and maps it to an appropriate HTTP status code and JSON body.
     ///
     /// # Response Format
-    /// The response body is a JSON object with an `"error"` field describing the issue.
+    /// The response body is an [`ErrorBody`]: a JSON object with an `"error"` field describing the issue
+    /// and, when the error occurs while handling a request, a `"request_id"` field
+    /// matching the `X-Request-Id` response header.
     ///
     /// | Error Variant         | HTTP Status Code        | Message                         |
     /// |-----------------------|------------------------|---------------------------------|
     /// | `DatabaseError`       | `500 Internal Server Error` | "A database error occurred."  |
     /// | `NotFound`            | `404 Not Found`        | Custom message                 |
     /// | `BadRequest`          | `400 Bad Request`      | Custom message                 |
+    /// | `Unauthorized`        | `401 Unauthorized`     | Custom message                 |
+    /// | `NotAcceptable`       | `406 Not Acceptable`   | Custom message                 |
+    /// | `Conflict`            | `409 Conflict`         | Custom message                 |
+    /// | `PayloadTooLarge`     | `413 Payload Too Large` | Custom message                 |
+    /// | `UnsupportedMediaType` | `415 Unsupported Media Type` | Custom message           |
+    /// | `UnprocessableEntity` | `422 Unprocessable Entity` | Custom message              |
+    /// | `Locked`              | `423 Locked`           | Custom message                 |
+    /// | `RequestTimeout`      | `408 Request Timeout`  | "The request was not received in time." |
+    /// | `TooManyRequests`     | `429 Too Many Requests` | "Rate limit exceeded. Try again later." |
+    /// | `ServiceUnavailable`  | `503 Service Unavailable` | Custom message               |
     /// | `InternalServerError` | `500 Internal Server Error` | "An internal server error occurred." |
     ///
     /// # Example Usage
     ///
     /// ```rust
     /// use axum::response::IntoResponse;
-    /// use crate::error::AppError;
+    /// use blog_api::error::AppError;
     ///
     /// let error = AppError::NotFound("User not found".into());
     /// let response = error.into_response();
@@ -81,14 +162,54 @@ impl IntoResponse for AppError {
             ),
             AppError::NotFound(message) => (StatusCode::NOT_FOUND, message.as_str()),
             AppError::BadRequest(message) => (StatusCode::BAD_REQUEST, message.as_str()),
+            AppError::Unauthorized(message) => (StatusCode::UNAUTHORIZED, message.as_str()),
+            AppError::NotAcceptable(message) => (StatusCode::NOT_ACCEPTABLE, message.as_str()),
+            AppError::Conflict(message) => (StatusCode::CONFLICT, message.as_str()),
+            AppError::PayloadTooLarge(message) => (StatusCode::PAYLOAD_TOO_LARGE, message.as_str()),
+            AppError::UnsupportedMediaType(message) => {
+                (StatusCode::UNSUPPORTED_MEDIA_TYPE, message.as_str())
+            }
+            AppError::UnprocessableEntity(message) => {
+                (StatusCode::UNPROCESSABLE_ENTITY, message.as_str())
+            }
+            AppError::Locked(message) => (StatusCode::LOCKED, message.as_str()),
+            AppError::RequestTimeout => (
+                StatusCode::REQUEST_TIMEOUT,
+                "The request was not received in time.",
+            ),
+            AppError::ServiceUnavailable(message) => {
+                (StatusCode::SERVICE_UNAVAILABLE, message.as_str())
+            }
+            AppError::TooManyRequests { .. } => (
+                StatusCode::TOO_MANY_REQUESTS,
+                "Rate limit exceeded. Try again later.",
+            ),
             AppError::InternalServerError => (
                 StatusCode::INTERNAL_SERVER_ERROR,
                 "An internal server error occurred.",
             ),
         };
 
-        let body = Json(json!({ "error": error_message }));
+        let body = Json(ErrorBody {
+            error: error_message.to_string(),
+            request_id: request_id::current(),
+        });
 
-        (status, body).into_response()
+        let mut response = (status, body).into_response();
+        match self {
+            AppError::TooManyRequests { retry_after } => {
+                response
+                    .headers_mut()
+                    .insert(header::RETRY_AFTER, retry_after.into());
+            }
+            AppError::Unauthorized(_) => {
+                response.headers_mut().insert(
+                    header::WWW_AUTHENTICATE,
+                    header::HeaderValue::from_static("Bearer"),
+                );
+            }
+            _ => {}
+        }
+        response
     }
 }
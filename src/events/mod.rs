@@ -0,0 +1,246 @@
+use crate::{
+    config,
+    error::AppError,
+    model::webhook::WebhookPayload,
+    outbox::{EventConsumer, OutboxEvent},
+    repository::PostFilter,
+};
+use anyhow::{Result, bail};
+use async_trait::async_trait;
+use futures_util::{Stream, stream};
+use serde::Serialize;
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
+    time::Duration,
+};
+use tokio::sync::broadcast::{self, error::RecvError};
+use utoipa::ToSchema;
+
+/// PostgreSQL `LISTEN`/`NOTIFY` relay between servers.
+pub mod postgres;
+
+/// Live event stream configuration read from the environment.
+///
+/// | Variable                  | Default | Description                                                      |
+/// |---------------------------|---------|------------------------------------------------------------------|
+/// | `EVENTS_REPLAY_CAPACITY`  | `1000`  | Recent events kept to resume streams from their `Last-Event-ID`. |
+/// | `EVENTS_CHANNEL_CAPACITY` | `256`   | Events a slow subscriber may fall behind before it is reset.     |
+/// | `EVENTS_KEEP_ALIVE_SECS`  | `15`    | Interval between keep-alive comments on idle streams.            |
+#[derive(Debug, Clone)]
+pub struct EventsConfig {
+    /// Number of recent events kept for resuming streams.
+    pub replay_capacity: usize,
+    /// Events a subscriber may fall behind before it is reset.
+    pub channel_capacity: usize,
+    /// Interval between keep-alive comments.
+    pub keep_alive: Duration,
+}
+
+impl EventsConfig {
+    /// Builds the configuration from environment variables.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if any of the variables holds an invalid value, or the channel
+    /// capacity or keep-alive interval is zero.
+    pub fn from_env() -> Result<Self> {
+        let config = Self {
+            replay_capacity: config::parse_or("EVENTS_REPLAY_CAPACITY", 1000)?,
+            channel_capacity: config::parse_or("EVENTS_CHANNEL_CAPACITY", 256)?,
+            keep_alive: Duration::from_secs(config::parse_or("EVENTS_KEEP_ALIVE_SECS", 15)?),
+        };
+        if config.channel_capacity == 0 {
+            bail!("Invalid value for EVENTS_CHANNEL_CAPACITY: must be at least 1");
+        }
+        if config.keep_alive.is_zero() {
+            bail!("Invalid value for EVENTS_KEEP_ALIVE_SECS: must be at least 1");
+        }
+        Ok(config)
+    }
+}
+
+/// A post lifecycle event pushed to live subscribers.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PostEvent {
+    /// ID of the event in the outbox, sent as the SSE event ID.
+    pub id: i64,
+    /// What happened, shaped like the body of a webhook request.
+    pub payload: WebhookPayload,
+}
+
+impl From<&OutboxEvent> for PostEvent {
+    fn from(event: &OutboxEvent) -> Self {
+        Self {
+            id: event.id,
+            payload: WebhookPayload::from(event),
+        }
+    }
+}
+
+/// Why a subscriber may have missed events and should fetch the posts again.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ResetReason {
+    /// The event the stream was resumed from is no longer in the replay buffer.
+    Expired,
+    /// The subscriber fell too far behind the events.
+    Lagged,
+    /// This server stopped receiving events for a while.
+    Interrupted,
+}
+
+/// The body of a `reset` event.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+pub struct Reset {
+    /// Why events may have been missed.
+    pub reason: ResetReason,
+}
+
+/// Something sent to the subscribers of [`PostEvents`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum Notice {
+    /// A post changed.
+    Event(Arc<PostEvent>),
+    /// Events may have been missed.
+    Reset(ResetReason),
+}
+
+/// Recent events and the channel new ones are sent on, guarded together so that a
+/// subscriber neither misses nor repeats an event between its replay and the channel.
+struct Buffer {
+    /// Most recent events, in the order they were published.
+    recent: VecDeque<Arc<PostEvent>>,
+    /// Channel of the events published from now on.
+    sender: broadcast::Sender<Notice>,
+}
+
+/// Hub of the live stream of post changes on this server.
+///
+/// Events reach the hub from the outbox [`Dispatcher`](crate::outbox::Dispatcher),
+/// directly when the hub is registered as a consumer, or through PostgreSQL
+/// `LISTEN`/`NOTIFY` so that every server sees the events of the others; see
+/// [`postgres`]. The most recent events are kept so that a subscriber reconnecting
+/// with the ID of the last event it received is sent the events it missed.
+#[derive(Clone)]
+pub struct PostEvents {
+    /// Recent events and the channel to subscribers.
+    buffer: Arc<Mutex<Buffer>>,
+    /// Replay capacity and keep-alive interval.
+    config: Arc<EventsConfig>,
+}
+
+impl PostEvents {
+    /// Creates a hub without events or subscribers.
+    pub fn new(config: EventsConfig) -> Self {
+        let (sender, _) = broadcast::channel(config.channel_capacity.max(1));
+        Self {
+            buffer: Arc::new(Mutex::new(Buffer {
+                recent: VecDeque::with_capacity(config.replay_capacity),
+                sender,
+            })),
+            config: Arc::new(config),
+        }
+    }
+
+    /// Locks the buffer, recovering it if a holder panicked.
+    fn lock(&self) -> MutexGuard<'_, Buffer> {
+        self.buffer.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Returns the interval between keep-alive comments on idle streams.
+    pub fn keep_alive(&self) -> Duration {
+        self.config.keep_alive
+    }
+
+    /// Sends an event to the subscribers and keeps it for replay, unless it was
+    /// published already, as happens when the outbox dispatches an event again.
+    pub fn publish(&self, event: PostEvent) {
+        let mut buffer = self.lock();
+        if buffer.recent.iter().any(|recent| recent.id == event.id) {
+            return;
+        }
+        let event = Arc::new(event);
+        if self.config.replay_capacity > 0 {
+            if buffer.recent.len() == self.config.replay_capacity {
+                buffer.recent.pop_front();
+            }
+            buffer.recent.push_back(event.clone());
+        }
+        // Sending only fails when nobody is subscribed.
+        let _ = buffer.sender.send(Notice::Event(event));
+    }
+
+    /// Forgets the recent events and tells every subscriber that it may have missed
+    /// some, after events could not be received for a while.
+    pub fn reset(&self) {
+        let mut buffer = self.lock();
+        buffer.recent.clear();
+        let _ = buffer.sender.send(Notice::Reset(ResetReason::Interrupted));
+    }
+
+    /// Subscribes to the events passing `filter`.
+    ///
+    /// With the ID of the last event a subscriber received, the stream starts with the
+    /// events published after it, or with a [`ResetReason::Expired`] reset if that
+    /// event is no longer kept. Resets are sent whatever the filter.
+    pub fn subscribe(
+        &self,
+        last_event_id: Option<i64>,
+        filter: PostFilter,
+    ) -> impl Stream<Item = Notice> + Send + use<> {
+        let buffer = self.lock();
+        let replay: VecDeque<Notice> = match last_event_id {
+            None => VecDeque::new(),
+            Some(id) => match buffer.recent.iter().position(|event| event.id == id) {
+                Some(position) => buffer
+                    .recent
+                    .iter()
+                    .skip(position + 1)
+                    .cloned()
+                    .map(Notice::Event)
+                    .collect(),
+                None => VecDeque::from([Notice::Reset(ResetReason::Expired)]),
+            },
+        };
+        let receiver = buffer.sender.subscribe();
+        drop(buffer);
+
+        let passes = move |notice: &Notice| match notice {
+            Notice::Event(event) => filter.matches(&event.payload.post),
+            Notice::Reset(_) => true,
+        };
+        stream::unfold(
+            (replay, receiver, passes),
+            |(mut replay, mut receiver, passes)| async move {
+                loop {
+                    let notice = match replay.pop_front() {
+                        Some(notice) => notice,
+                        None => match receiver.recv().await {
+                            Ok(notice) => notice,
+                            Err(RecvError::Lagged(_)) => Notice::Reset(ResetReason::Lagged),
+                            Err(RecvError::Closed) => return None,
+                        },
+                    };
+                    if passes(&notice) {
+                        return Some((notice, (replay, receiver, passes)));
+                    }
+                }
+            },
+        )
+    }
+}
+
+/// The hub consumes the domain events of the outbox directly when there is a single
+/// server, with SQLite or in-memory storage.
+#[async_trait]
+impl EventConsumer for PostEvents {
+    fn name(&self) -> &'static str {
+        "events"
+    }
+
+    async fn consume(&self, event: &OutboxEvent) -> Result<(), AppError> {
+        self.publish(PostEvent::from(event));
+        Ok(())
+    }
+}
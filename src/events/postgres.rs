@@ -0,0 +1,109 @@
+use super::{PostEvent, PostEvents};
+use crate::{
+    error::AppError,
+    outbox::{EventConsumer, OutboxEvent, postgres::PgOutboxRepository},
+    telemetry::span,
+};
+use async_trait::async_trait;
+use sqlx::{
+    PgPool,
+    postgres::{PgListener, PgNotification},
+};
+use std::time::Duration;
+use tracing::Instrument;
+
+/// Channel the IDs of post events are notified on.
+pub const CHANNEL: &str = "post_events";
+
+/// Delay before listening again after the connection could not be restored.
+const RECONNECT_DELAY: Duration = Duration::from_secs(1);
+
+/// Database system reported on query spans.
+const SYSTEM: &str = "postgresql";
+
+/// Consumer of the outbox notifying every server of each post event with
+/// `pg_notify`, for their [`listen`] task to publish it.
+///
+/// Only the ID of the event is sent, as notification payloads are limited to 8000
+/// bytes; the listeners read the event from the outbox.
+#[derive(Debug, Clone)]
+pub struct PgEventNotifier {
+    /// Pool of the primary database.
+    pool: PgPool,
+}
+
+impl PgEventNotifier {
+    /// Creates a notifier sending its notifications on `pool`.
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl EventConsumer for PgEventNotifier {
+    fn name(&self) -> &'static str {
+        "events"
+    }
+
+    async fn consume(&self, event: &OutboxEvent) -> Result<(), AppError> {
+        // pg_notify returns void, which the query macros cannot describe.
+        sqlx::query("SELECT pg_notify($1, $2);")
+            .bind(CHANNEL)
+            .bind(event.id.to_string())
+            .execute(&self.pool)
+            .instrument(span::db_query_on(SYSTEM, "SELECT", "pg_notify"))
+            .await?;
+        Ok(())
+    }
+}
+
+/// Listens for the events notified by the [`PgEventNotifier`] of any server and
+/// publishes them to `events`, in the background for as long as the process runs.
+///
+/// When the connection is lost, the listener reconnects and resets the hub, since
+/// notifications sent meanwhile are missed.
+///
+/// # Errors
+///
+/// Returns an `AppError` if listening cannot start.
+pub async fn listen(pool: PgPool, events: PostEvents) -> Result<(), AppError> {
+    let outbox = PgOutboxRepository::new(pool.clone());
+    let mut listener = PgListener::connect_with(&pool).await?;
+    listener.listen(CHANNEL).await?;
+    tokio::spawn(async move {
+        loop {
+            match listener.try_recv().await {
+                Ok(Some(notification)) => relay(&outbox, &events, &notification).await,
+                Ok(None) => {
+                    tracing::warn!("lost the connection listening for post events");
+                    events.reset();
+                }
+                Err(err) => {
+                    tracing::warn!("failed to listen for post events: {err}");
+                    tokio::time::sleep(RECONNECT_DELAY).await;
+                }
+            }
+        }
+    });
+    Ok(())
+}
+
+/// Reads the event a notification names from the outbox and publishes it, resetting
+/// the hub if it cannot be read.
+async fn relay(outbox: &PgOutboxRepository, events: &PostEvents, notification: &PgNotification) {
+    let Ok(id) = notification.payload().parse::<i64>() else {
+        tracing::warn!(
+            payload = notification.payload(),
+            "ignored an invalid post event notification"
+        );
+        return;
+    };
+    match outbox.find(id).await {
+        Ok(Some(event)) => events.publish(PostEvent::from(&event)),
+        Ok(None) => tracing::warn!(event_id = id, "notified of a missing post event"),
+        Err(err) => {
+            tracing::warn!(event_id = id, "failed to read a notified post event: {err}");
+            events.reset();
+        }
+    }
+}
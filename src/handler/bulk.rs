@@ -0,0 +1,398 @@
+use crate::{
+    collab::Collaboration,
+    error::{AppError, ErrorBody},
+    media::Media,
+    model::{
+        blog::{BlogPost, BlogPostBody},
+        bulk::{
+            BulkCreateParams, BulkCreateResponse, BulkDeleteBody, BulkDeleteResponse,
+            BulkItemResult, BulkItemStatus, BulkMode, BulkRejection, BulkUpdateBody,
+            BulkUpdateResponse,
+        },
+    },
+    repository::DynPostRepository,
+};
+use axum::{
+    Json,
+    extract::{Query, State},
+    http::StatusCode,
+};
+use validator::Validate;
+
+/// Largest number of posts or IDs accepted by one bulk request.
+pub const MAX_BULK_ITEMS: usize = 1000;
+
+/// Rejects empty and oversized bulk requests.
+fn check_item_count(count: usize) -> Result<(), AppError> {
+    if count == 0 {
+        return Err(AppError::BadRequest(
+            "The request contains no items".to_string(),
+        ));
+    }
+    if count > MAX_BULK_ITEMS {
+        return Err(AppError::BadRequest(format!(
+            "A bulk request may contain at most {MAX_BULK_ITEMS} items"
+        )));
+    }
+    Ok(())
+}
+
+/// Creates several blog posts in one request.
+///
+/// # Arguments
+/// * `State(posts)`: The blog post repository.
+/// * `State(media)`: The media library holding the featured images of the posts.
+/// * `Query(params)`: The bulk mode, `all_or_nothing` (default) or `best_effort`.
+/// * `Json(payload)`: The blog posts to create.
+///
+/// # Returns
+/// Returns the HTTP status code and one result per post, in the order of the request:
+/// - `201 Created` if every post was created.
+/// - `207 Multi-Status` in `best_effort` mode if some posts failed validation or were
+///   refused by the storage, e.g. because another post has their slug; the others were
+///   created.
+/// - `422 Unprocessable Entity` in `all_or_nothing` mode if any post failed validation;
+///   no post was created.
+///
+/// A post whose featured image is not an uploaded image fails validation.
+///
+/// In `all_or_nothing` mode the posts are stored in a single transaction, in batches.
+/// In `best_effort` mode each post is stored on its own, so one post refused by the
+/// storage does not keep the others from being created.
+///
+/// # Errors
+/// This function will return an `AppError` if:
+/// - The request contains no posts or more than [`MAX_BULK_ITEMS`].
+/// - In `all_or_nothing` mode, a post has the slug of another post, in which case no
+///   post is created.
+/// - The repository fails. No post is created in `all_or_nothing` mode; in
+///   `best_effort` mode the posts stored before the failure are kept.
+///
+/// # Example
+/// ```text
+/// POST /api/v1/posts/bulk?mode=best_effort
+/// [
+///     { "title": "First", "content": "...", "category": "Rust", "tags": ["rust"] },
+///     { "title": "Second", "content": "...", "category": "Rust", "tags": ["async"] }
+/// ]
+/// ```
+#[utoipa::path(
+    post,
+    path = "/posts/bulk",
+    tag = "posts",
+    description = "Creates several blog posts, in one transaction unless in best effort mode, and reports the outcome of each.",
+    params(BulkCreateParams),
+    request_body = Vec<BlogPostBody>,
+    responses(
+        (status = 201, description = "Every blog post was created", body = BulkCreateResponse),
+        (status = 207, description = "Some blog posts failed validation or were refused by the storage; the others were created", body = BulkCreateResponse),
+        (status = 400, description = "The request contains no posts or too many", body = ErrorBody),
+        (status = 409, description = "A blog post has the slug of another; none was created", body = ErrorBody),
+        (status = 413, description = "The request body is too large", body = ErrorBody),
+        (status = 422, description = "Some blog posts failed validation or feature no uploaded image; none was created", body = BulkCreateResponse),
+    )
+)]
+pub async fn create_posts(
+    State(posts): State<DynPostRepository>,
+    State(media): State<Media>,
+    Query(params): Query<BulkCreateParams>,
+    Json(payload): Json<Vec<BlogPostBody>>,
+) -> Result<(StatusCode, Json<BulkCreateResponse>), AppError> {
+    check_item_count(payload.len())?;
+
+    let mut errors = Vec::with_capacity(payload.len());
+    for post in &payload {
+        errors.push(check_post(&media, post).await?);
+    }
+    let results = match params.mode {
+        BulkMode::AllOrNothing => create_all(&posts, &payload, errors).await?,
+        BulkMode::BestEffort => create_each(&posts, &payload, errors).await?,
+    };
+
+    let created = results
+        .iter()
+        .filter(|result| result.status == BulkItemStatus::Created)
+        .count();
+    let failed = results
+        .iter()
+        .filter(|result| result.error.is_some())
+        .count();
+    let status = match (failed, params.mode) {
+        (0, _) => StatusCode::CREATED,
+        (_, BulkMode::BestEffort) => StatusCode::MULTI_STATUS,
+        (_, BulkMode::AllOrNothing) => StatusCode::UNPROCESSABLE_ENTITY,
+    };
+    let response = BulkCreateResponse {
+        created,
+        failed,
+        results,
+    };
+    Ok((status, Json(response)))
+}
+
+/// Returns why a post cannot be created, if it cannot: it fails validation or its
+/// featured image is not an uploaded image.
+async fn check_post(media: &Media, post: &BlogPostBody) -> Result<Option<String>, AppError> {
+    if let Err(err) = post.validate() {
+        return Ok(Some(err.to_string()));
+    }
+    let Some(id) = post.featured_image_id() else {
+        return Ok(None);
+    };
+    match media.check_featured_image(id).await {
+        Ok(()) => Ok(None),
+        Err(err) => rejection(err).map(Some),
+    }
+}
+
+/// What happened to one post of a bulk create: its status, the stored post and why it
+/// failed, as reported in its [`BulkItemResult`].
+type Outcome = (BulkItemStatus, Option<BlogPost>, Option<String>);
+
+/// Stores every post in one transaction if none failed validation, and none otherwise.
+async fn create_all(
+    posts: &DynPostRepository,
+    payload: &[BlogPostBody],
+    errors: Vec<Option<String>>,
+) -> Result<Vec<BulkItemResult>, AppError> {
+    let outcomes: Vec<Outcome> = if errors.iter().any(Option::is_some) {
+        errors
+            .into_iter()
+            .map(|error| match error {
+                Some(error) => (BulkItemStatus::Invalid, None, Some(error)),
+                None => (BulkItemStatus::Skipped, None, None),
+            })
+            .collect()
+    } else {
+        posts
+            .create_many(payload)
+            .await?
+            .into_iter()
+            .map(|post| (BulkItemStatus::Created, Some(post), None))
+            .collect()
+    };
+    Ok(item_results(outcomes))
+}
+
+/// Stores the posts that passed validation one by one, reporting those the storage
+/// refuses.
+async fn create_each(
+    posts: &DynPostRepository,
+    payload: &[BlogPostBody],
+    errors: Vec<Option<String>>,
+) -> Result<Vec<BulkItemResult>, AppError> {
+    let mut outcomes = Vec::with_capacity(payload.len());
+    for (post, error) in payload.iter().zip(errors) {
+        let outcome = match error {
+            Some(error) => (BulkItemStatus::Invalid, None, Some(error)),
+            None => match posts.create(post).await {
+                Ok(post) => (BulkItemStatus::Created, Some(post), None),
+                Err(err) => (BulkItemStatus::Rejected, None, Some(rejection(err)?)),
+            },
+        };
+        outcomes.push(outcome);
+    }
+    Ok(item_results(outcomes))
+}
+
+/// Returns why a post was refused, or the error if the storage failed.
+fn rejection(err: AppError) -> Result<String, AppError> {
+    match err {
+        AppError::BadRequest(message)
+        | AppError::Conflict(message)
+        | AppError::UnprocessableEntity(message) => Ok(message),
+        err => Err(err),
+    }
+}
+
+/// Numbers the outcomes of the posts of a bulk create in the order of the request.
+fn item_results(outcomes: Vec<Outcome>) -> Vec<BulkItemResult> {
+    outcomes
+        .into_iter()
+        .enumerate()
+        .map(|(index, (status, post, error))| BulkItemResult {
+            index,
+            status,
+            post,
+            error,
+        })
+        .collect()
+}
+
+/// Splits `ids` into the posts nobody has locked for editing and the locked ones.
+///
+/// # Errors
+///
+/// Returns an `AppError` if the locks cannot be read.
+async fn unlocked(
+    collab: &Collaboration,
+    ids: Vec<i32>,
+) -> Result<(Vec<i32>, Vec<BulkRejection>), AppError> {
+    let mut unlocked = Vec::new();
+    let mut rejected = Vec::new();
+    for id in ids {
+        match collab.check_update(id, None).await {
+            Ok(()) => unlocked.push(id),
+            Err(AppError::Locked(error)) => rejected.push(BulkRejection {
+                id,
+                status: BulkItemStatus::Rejected,
+                error,
+            }),
+            Err(err) => return Err(err),
+        }
+    }
+    Ok((unlocked, rejected))
+}
+
+/// Returns `207 Multi-Status` if some posts were rejected, or `200 OK` otherwise.
+fn bulk_status(rejected: &[BulkRejection]) -> StatusCode {
+    if rejected.is_empty() {
+        StatusCode::OK
+    } else {
+        StatusCode::MULTI_STATUS
+    }
+}
+
+/// Reassigns the category or tags of every blog post matching a filter.
+///
+/// # Arguments
+/// * `State(posts)`: The blog post repository.
+/// * `State(collab)`: The edit locks of the posts.
+/// * `Json(payload)`: The filter selecting the posts and the changes to apply.
+///
+/// # Returns
+/// Returns the number and IDs of the updated posts:
+/// - `200 OK` if every matching post was updated.
+/// - `207 Multi-Status` if some matching posts are locked for editing; they are
+///   reported as rejected and left unchanged, and the others were updated.
+///
+/// Posts the changes would leave without tags are not updated.
+///
+/// # Errors
+/// This function will return an `AppError` if:
+/// - The filter is empty, the request changes nothing, or the new category is empty.
+/// - The repository fails, in which case no post is updated.
+///
+/// # Example
+/// ```text
+/// PATCH /api/v1/posts/bulk
+/// {
+///     "filter": { "category": "Rust" },
+///     "category": "Programming",
+///     "add_tags": ["rust"],
+///     "remove_tags": ["draft"]
+/// }
+/// ```
+#[utoipa::path(
+    patch,
+    path = "/posts/bulk",
+    tag = "posts",
+    description = "Moves every blog post matching a filter to another category, or adds and removes tags.",
+    request_body = BulkUpdateBody,
+    responses(
+        (status = 200, description = "The matching blog posts were updated", body = BulkUpdateResponse),
+        (status = 207, description = "Some matching blog posts are locked for editing and were left unchanged; the others were updated", body = BulkUpdateResponse),
+        (status = 400, description = "The filter is empty or the request changes nothing", body = ErrorBody),
+        (status = 422, description = "The request body is not a valid bulk update", body = String),
+    )
+)]
+pub async fn update_posts(
+    State(posts): State<DynPostRepository>,
+    State(collab): State<Collaboration>,
+    Json(payload): Json<BulkUpdateBody>,
+) -> Result<(StatusCode, Json<BulkUpdateResponse>), AppError> {
+    if payload.filter.is_empty() {
+        return Err(AppError::BadRequest(
+            "A category or tag filter is required".to_string(),
+        ));
+    }
+    if payload.changes.is_empty() {
+        return Err(AppError::BadRequest(
+            "The request changes nothing".to_string(),
+        ));
+    }
+    let changes = &payload.changes;
+    if changes.category.as_ref().is_some_and(String::is_empty)
+        || changes.add_tags.iter().any(String::is_empty)
+    {
+        return Err(AppError::BadRequest(
+            "Categories and tags cannot be empty".to_string(),
+        ));
+    }
+
+    let matching = posts.list(&payload.filter).await?;
+    let (_, rejected) =
+        unlocked(&collab, matching.into_iter().map(|post| post.id).collect()).await?;
+    let locked: Vec<i32> = rejected.iter().map(|rejection| rejection.id).collect();
+    let ids = posts.reassign(&payload.filter, changes, &locked).await?;
+    Ok((
+        bulk_status(&rejected),
+        Json(BulkUpdateResponse {
+            updated: ids.len(),
+            ids,
+            rejected,
+        }),
+    ))
+}
+
+/// Deletes several blog posts by their IDs.
+///
+/// # Arguments
+/// * `State(posts)`: The blog post repository.
+/// * `State(collab)`: The edit locks of the posts.
+/// * `Json(payload)`: The IDs of the posts to delete.
+///
+/// # Returns
+/// Returns the IDs of the deleted posts and of the requested IDs no post has:
+/// - `200 OK` if no requested post is locked for editing.
+/// - `207 Multi-Status` if some requested posts are locked; they are reported as
+///   rejected and left in place, and the others were deleted.
+///
+/// # Errors
+/// This function will return an `AppError` if:
+/// - The request contains no IDs or more than [`MAX_BULK_ITEMS`].
+/// - The repository fails, in which case no post is deleted.
+///
+/// # Example
+/// ```text
+/// DELETE /api/v1/posts/bulk
+/// { "ids": [1, 2, 3] }
+/// ```
+#[utoipa::path(
+    delete,
+    path = "/posts/bulk",
+    tag = "posts",
+    description = "Permanently deletes several blog posts in one statement.",
+    request_body = BulkDeleteBody,
+    responses(
+        (status = 200, description = "The existing blog posts were deleted", body = BulkDeleteResponse),
+        (status = 207, description = "Some blog posts are locked for editing and were left in place; the others were deleted", body = BulkDeleteResponse),
+        (status = 400, description = "The request contains no IDs or too many", body = ErrorBody),
+        (status = 422, description = "The request body is not a valid bulk delete", body = String),
+    )
+)]
+pub async fn delete_posts(
+    State(posts): State<DynPostRepository>,
+    State(collab): State<Collaboration>,
+    Json(payload): Json<BulkDeleteBody>,
+) -> Result<(StatusCode, Json<BulkDeleteResponse>), AppError> {
+    check_item_count(payload.ids.len())?;
+
+    let mut ids = payload.ids;
+    ids.sort_unstable();
+    ids.dedup();
+    let (ids, rejected) = unlocked(&collab, ids).await?;
+    let deleted = posts.delete_many(&ids).await?;
+    let not_found: Vec<i32> = ids
+        .into_iter()
+        .filter(|id| deleted.binary_search(id).is_err())
+        .collect();
+    Ok((
+        bulk_status(&rejected),
+        Json(BulkDeleteResponse {
+            deleted,
+            not_found,
+            rejected,
+        }),
+    ))
+}
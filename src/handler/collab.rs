@@ -0,0 +1,157 @@
+use axum::{
+    extract::{
+        ws::{Message, WebSocket, WebSocketUpgrade},
+        Path, Query, State,
+    },
+    response::Response,
+};
+use std::future;
+
+use crate::{
+    collab::{Collaboration, Session},
+    error::{AppError, ErrorBody},
+    model::collab::{ClientMessage, CollaborateParams, ServerMessage, MAX_NAME_LENGTH},
+    repository::DynPostRepository,
+};
+
+/// Joins the room of a blog post over a WebSocket, to see who is viewing or editing it
+/// and to take its edit lock.
+///
+/// # Arguments
+///
+/// * `State(posts)`: The blog post repository, to check that the post exists.
+/// * `State(collab)`: The rooms and edit locks of the posts.
+/// * `Path(id)`: The ID of the blog post.
+/// * `Query(params)`: The `name` shown to the other participants.
+/// * `upgrade`: The WebSocket handshake.
+///
+/// # Returns
+///
+/// Returns `101 Switching Protocols` and exchanges JSON text messages tagged by `type`:
+/// - The server sends a `presence` message, listing the participants and the edit lock
+///   of the post, on joining and whenever either changes or the lock expires.
+/// - `{"type": "lock"}` asks for the edit lock, answered by `lock_granted` with the token
+///   to send in the `Edit-Lock` header of updates, or by `lock_denied` with the lock of
+///   the current holder.
+/// - `{"type": "heartbeat"}` renews the lock, answered by `lock_granted`, or by
+///   `lock_lost` if it expired and was taken over. The lock expires
+///   `COLLAB_LOCK_TTL_SECS` after it was last granted.
+/// - `{"type": "unlock"}` releases the lock, as does closing the connection.
+/// - An invalid message is answered by an `error` message.
+///
+/// # Errors
+///
+/// This function will return an `AppError` if:
+/// - The name is empty or too long.
+/// - The repository fails.
+/// - The specified blog post does not exist.
+///
+/// # Example
+///
+/// ```text
+/// GET /api/v1/posts/1/collaborate?name=alice
+/// Connection: Upgrade
+/// Upgrade: websocket
+/// ```
+#[utoipa::path(
+    get,
+    path = "/posts/{id}/collaborate",
+    tag = "posts",
+    description = "Opens a WebSocket listing who is viewing or editing a blog post, and granting its advisory edit lock.",
+    params(("id" = i32, Path, description = "ID of the blog post"), CollaborateParams),
+    responses(
+        (status = 101, description = "The connection was upgraded to a WebSocket exchanging `ClientMessage` and `ServerMessage` JSON messages"),
+        (status = 400, description = "The name is missing, empty or too long", body = ErrorBody),
+        (status = 404, description = "No blog post has this ID", body = ErrorBody),
+    )
+)]
+pub async fn collaborate(
+    State(posts): State<DynPostRepository>,
+    State(collab): State<Collaboration>,
+    Path(id): Path<i32>,
+    Query(params): Query<CollaborateParams>,
+    upgrade: WebSocketUpgrade,
+) -> Result<Response, AppError> {
+    let name = params.name.trim().to_string();
+    if name.is_empty() || name.chars().count() > MAX_NAME_LENGTH {
+        return Err(AppError::BadRequest(format!(
+            "Name must be between 1 and {MAX_NAME_LENGTH} characters long"
+        )));
+    }
+    posts
+        .get(id)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Blog post not found".to_string()))?;
+
+    Ok(upgrade.on_upgrade(move |mut socket| async move {
+        match collab.join(id, &name).await {
+            Ok(session) => run(&mut socket, session).await,
+            Err(err) => {
+                tracing::warn!(post_id = id, "failed to join a collaboration room: {err}");
+                let message = ServerMessage::Error {
+                    message: "The edit lock of the post could not be read".to_string(),
+                };
+                let _ = send(&mut socket, &message).await;
+            }
+        }
+    }))
+}
+
+/// Sends a message to a participant.
+async fn send(socket: &mut WebSocket, message: &ServerMessage) -> Result<(), axum::Error> {
+    let text = serde_json::to_string(message).map_err(axum::Error::new)?;
+    socket.send(Message::Text(text.into())).await
+}
+
+/// Relays the messages of a participant and the changes to its room until it
+/// disconnects, then leaves the room.
+async fn run(socket: &mut WebSocket, mut session: Session) {
+    loop {
+        // The room is sent again when the lock expires, as nothing else changes then.
+        let expiry = session.lock_expiry().map(|expires_at| {
+            (expires_at - chrono::Utc::now())
+                .to_std()
+                .unwrap_or_default()
+        });
+        let expired = async {
+            match expiry {
+                Some(delay) => tokio::time::sleep(delay).await,
+                None => future::pending().await,
+            }
+        };
+        let reply = tokio::select! {
+            message = socket.recv() => match message {
+                Some(Ok(Message::Text(text))) => match serde_json::from_str::<ClientMessage>(&text) {
+                    Ok(message) => session.handle(message).await.unwrap_or_else(|err| {
+                        tracing::warn!(session = session.id(), "failed to handle a collaboration message: {err}");
+                        Some(ServerMessage::Error {
+                            message: "The edit lock of the post could not be updated".to_string(),
+                        })
+                    }),
+                    Err(err) => Some(ServerMessage::Error {
+                        message: format!("Invalid message: {err}"),
+                    }),
+                },
+                Some(Ok(Message::Binary(_))) => Some(ServerMessage::Error {
+                    message: "Messages must be JSON text".to_string(),
+                }),
+                // Pings are answered by the WebSocket implementation.
+                Some(Ok(Message::Ping(_) | Message::Pong(_))) => None,
+                Some(Ok(Message::Close(_)) | Err(_)) | None => break,
+            },
+            changed = session.changed() => {
+                if !changed {
+                    break;
+                }
+                Some(session.presence())
+            }
+            () = expired => Some(session.presence()),
+        };
+        if let Some(reply) = reply
+            && send(socket, &reply).await.is_err()
+        {
+            break;
+        }
+    }
+    session.leave().await;
+}
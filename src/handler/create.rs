@@ -1,33 +1,20 @@
-use crate::{database::extractor::DatabaseConnection, error::AppError, model::blog::BlogPost};
-use axum::{http::StatusCode, Json};
+use crate::{
+    database::extractor::DatabaseTransaction,
+    error::{AppError, ErrorBody},
+    media::Media,
+    model::blog::{BlogPost, BlogPostBody},
+    repository::{DynPostRepository, postgres},
+};
+use axum::{Json, extract::State, http::StatusCode};
 use axum_valid::Valid;
-use serde::{Deserialize, Serialize};
-use validator::Validate;
 
-/// Represents the request body for creating a new blog post.
-#[derive(Debug, Serialize, Deserialize, Validate)]
-pub struct BlogPostBody {
-    /// Title of the blog post.
-    #[validate(length(min = 1, message = "Title cannot be empty"))]
-    pub title: String,
-
-    /// Content of the blog post.
-    #[validate(length(min = 1, message = "Content cannot be empty"))]
-    pub content: String,
-
-    /// Category to which the blog post belongs.
-    #[validate(length(min = 1, message = "Category cannot be empty"))]
-    pub category: String,
-
-    /// List of tags associated with the blog post.
-    #[validate(length(min = 1, message = "At least one tag is required"))]
-    pub tags: Vec<String>,
-}
-
-/// Creates a new blog post in the database.
+/// Creates a new blog post.
 ///
 /// # Arguments
-/// * `DatabaseConnection(mut conn)`: A database connection wrapper.
+/// * `State(posts)`: The blog post repository.
+/// * `State(media)`: The media file repository, to check the featured image.
+/// * `tx`: The transaction of the request, if the application is backed by PostgreSQL;
+///   the post is stored in it, and it is committed once the response is ready.
 /// * `Valid(payload)`: A validated JSON payload containing the blog post data.
 ///
 /// # Returns
@@ -36,12 +23,13 @@ pub struct BlogPostBody {
 ///
 /// # Errors
 /// This function will return an `AppError` if:
-/// - The database query fails (e.g., due to connection issues).
+/// - The repository fails (e.g., due to database connection issues).
 /// - The insertion violates a constraint (e.g., unique title or missing fields).
+/// - The featured image does not exist or is not an image.
 ///
 /// # Example
-/// ```
-/// POST /posts
+/// ```text
+/// POST /api/v1/posts
 /// {
 ///     "title": "My First Post",
 ///     "content": "This is the content of my first post.",
@@ -49,24 +37,31 @@ pub struct BlogPostBody {
 ///     "tags": ["rust", "async", "sqlx"]
 /// }
 /// ```
+#[utoipa::path(
+    post,
+    path = "/posts",
+    tag = "posts",
+    description = "Creates a blog post and returns it with its assigned ID and timestamps.",
+    request_body = BlogPostBody,
+    responses(
+        (status = 201, description = "The blog post was created", body = BlogPost),
+        (status = 400, description = "The request body failed validation", body = String),
+        (status = 413, description = "The request body is too large", body = ErrorBody),
+        (status = 422, description = "The request body is not a valid blog post, or its featured image is not an uploaded image", body = String),
+    )
+)]
 pub async fn create_post(
-    DatabaseConnection(mut conn): DatabaseConnection,
-    Valid(payload): Valid<Json<BlogPostBody>>,
+    State(posts): State<DynPostRepository>,
+    State(media): State<Media>,
+    tx: Option<DatabaseTransaction>,
+    Valid(Json(payload)): Valid<Json<BlogPostBody>>,
 ) -> Result<(StatusCode, Json<BlogPost>), AppError> {
-    let value = sqlx::query_as!(
-        BlogPost,
-        r#"
-        INSERT INTO blog_posts (title, content, category, tags)
-        VALUES ($1, $2, $3, $4)
-        RETURNING *;
-        "#,
-        payload.title,
-        payload.content,
-        payload.category,
-        &payload.tags
-    )
-    .fetch_one(&mut *conn)
-    .await?;
-
-    Ok((StatusCode::CREATED, Json(value)))
+    if let Some(id) = payload.featured_image_id() {
+        media.check_featured_image(id).await?;
+    }
+    let post = match tx {
+        Some(mut tx) => postgres::create_in(&mut tx, &payload).await?,
+        None => posts.create(&payload).await?,
+    };
+    Ok((StatusCode::CREATED, Json(post)))
 }
@@ -1,40 +1,68 @@
-use crate::{database::extractor::DatabaseConnection, error::AppError};
-use axum::{extract::Path, http::StatusCode};
+use crate::{
+    collab::{Collaboration, EDIT_LOCK_HEADER},
+    error::{AppError, ErrorBody},
+    repository::DynPostRepository,
+};
+use axum::{
+    extract::{Path, State},
+    http::{HeaderMap, StatusCode},
+};
 
-/// Deletes a blog post by its ID from the database.
+/// Deletes a blog post by its ID.
 ///
 /// # Arguments
 ///
-/// * `DatabaseConnection(mut conn)`: A wrapper for the database connection.
+/// * `State(posts)`: The blog post repository.
+/// * `State(collab)`: The edit locks of the posts.
 /// * `Path(id)`: The ID of the blog post to delete.
+/// * `headers`: The request headers, carrying the `Edit-Lock` token of the holder of the
+///   post's edit lock, if any.
 ///
 /// # Returns
 ///
 /// Returns a `Result` containing:
 /// - `StatusCode::NO_CONTENT` if the deletion is successful.
 /// - `AppError::NotFound` if no blog post with the given ID exists.
-/// - `AppError` if an error occurs during the database query.
+/// - `AppError` if an error occurs in the repository.
 ///
 /// # Errors
 ///
 /// This function will return an `AppError` if:
-/// - The database query fails.
+/// - The repository fails.
 /// - The specified blog post does not exist.
+/// - Someone else holds the edit lock of the blog post (`423`).
 ///
 /// # Example
 ///
+/// ```text
+/// DELETE /api/v1/posts/1
 /// ```
-/// DELETE /posts/1
-/// ```
+#[utoipa::path(
+    delete,
+    path = "/posts/{id}",
+    tag = "posts",
+    description = "Permanently deletes a blog post.",
+    params(
+        ("id" = i32, Path, description = "ID of the blog post to delete"),
+        ("Edit-Lock" = Option<String>, Header, description = "Token of the edit lock of the blog post, required while the lock is held"),
+    ),
+    responses(
+        (status = 204, description = "The blog post was deleted"),
+        (status = 404, description = "No blog post has this ID", body = ErrorBody),
+        (status = 423, description = "Someone else holds the edit lock of the blog post", body = ErrorBody),
+    )
+)]
 pub async fn delete_by_id(
-    DatabaseConnection(mut conn): DatabaseConnection,
+    State(posts): State<DynPostRepository>,
+    State(collab): State<Collaboration>,
     Path(id): Path<i32>,
+    headers: HeaderMap,
 ) -> Result<StatusCode, AppError> {
-    let rows_affected = sqlx::query!("DELETE FROM blog_posts WHERE id = $1", id)
-        .execute(&mut *conn)
-        .await?;
-
-    if rows_affected.rows_affected() == 0 {
+    let token = headers
+        .get(EDIT_LOCK_HEADER)
+        .and_then(|value| value.to_str().ok());
+    collab.check_update(id, token).await?;
+    if !posts.delete(id).await? {
         return Err(AppError::NotFound("Blog post not found".to_string()));
     }
     Ok(StatusCode::NO_CONTENT)
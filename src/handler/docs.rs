@@ -0,0 +1,53 @@
+use axum::{
+    Json,
+    http::header,
+    response::{Html, IntoResponse},
+};
+use utoipa::OpenApi;
+
+use crate::openapi::ApiDoc;
+
+/// Content security policy for the documentation page, which loads Redoc from its CDN.
+const DOCS_CSP: &str = "default-src 'none'; script-src https://cdn.redoc.ly; \
+    style-src 'unsafe-inline' https://fonts.googleapis.com; font-src https://fonts.gstatic.com; \
+    img-src 'self' data: https://cdn.redoc.ly; connect-src 'self'; worker-src blob:";
+
+/// HTML page rendering the OpenAPI document with Redoc.
+const REDOC_HTML: &str = r#"<!DOCTYPE html>
+<html>
+  <head>
+    <title>Blog Platform API</title>
+    <meta charset="utf-8" />
+    <meta name="viewport" content="width=device-width, initial-scale=1" />
+  </head>
+  <body>
+    <redoc spec-url="/openapi.json"></redoc>
+    <script src="https://cdn.redoc.ly/redoc/v2.4.0/bundles/redoc.standalone.js"></script>
+  </body>
+</html>
+"#;
+
+/// Returns the OpenAPI 3.1 document describing the API.
+///
+/// # Example
+///
+/// ```text
+/// GET /openapi.json
+/// ```
+pub async fn openapi_json() -> Json<utoipa::openapi::OpenApi> {
+    Json(ApiDoc::openapi())
+}
+
+/// Serves interactive API documentation rendered by Redoc.
+///
+/// # Example
+///
+/// ```text
+/// GET /docs
+/// ```
+pub async fn redoc() -> impl IntoResponse {
+    (
+        [(header::CONTENT_SECURITY_POLICY, DOCS_CSP)],
+        Html(REDOC_HTML),
+    )
+}
@@ -0,0 +1,88 @@
+use crate::{
+    error::{AppError, ErrorBody},
+    events::{Notice, PostEvents, Reset},
+    repository::PostFilter,
+};
+use axum::{
+    extract::{Query, State},
+    http::HeaderMap,
+    response::sse::{Event, KeepAlive, Sse},
+};
+use futures_util::{Stream, StreamExt};
+
+/// Header a reconnecting client sends with the ID of the last event it received.
+const LAST_EVENT_ID: &str = "last-event-id";
+
+/// Streams changes to blog posts as Server-Sent Events.
+///
+/// # Arguments
+///
+/// * `State(events)`: The live stream of post changes.
+/// * `Query(filter)`: Optional `category` and `tag` filters, matched against the post as
+///   written by each change.
+/// * `headers`: The request headers, possibly carrying a `Last-Event-ID`.
+///
+/// # Returns
+///
+/// Returns `200 OK` with a `text/event-stream` that stays open. Each change is sent as
+/// an event named after it, e.g. `post.published`, whose ID is the ID of the domain event
+/// and whose data is shaped like a webhook payload. Clients reconnecting with the
+/// `Last-Event-ID` header are sent the changes they missed, if the server still holds
+/// them. A `reset` event tells a client that it may have missed changes and should fetch
+/// the posts again; its data holds the reason, `expired`, `lagged` or `interrupted`.
+/// Comments are sent on idle streams to keep the connection open.
+///
+/// # Errors
+///
+/// This function will return an `AppError` if:
+/// - The `Last-Event-ID` header is not an event ID.
+///
+/// # Example
+///
+/// ```text
+/// GET /api/v1/events?category=Rust
+/// Last-Event-ID: 42
+/// ```
+#[utoipa::path(
+    get,
+    path = "/events",
+    tag = "posts",
+    description = "Streams the creation, update, deletion, publication and unpublication of blog posts as Server-Sent Events.",
+    params(
+        PostFilter,
+        ("Last-Event-ID" = Option<i64>, Header, description = "ID of the last event received, to resume the stream after it"),
+    ),
+    responses(
+        (status = 200, description = "A stream of `post.*` events carrying a `WebhookPayload`, and `reset` events carrying a `Reset`", content_type = "text/event-stream", body = String),
+        (status = 400, description = "The `Last-Event-ID` header is invalid", body = ErrorBody),
+    )
+)]
+pub async fn stream_events(
+    State(events): State<PostEvents>,
+    Query(filter): Query<PostFilter>,
+    headers: HeaderMap,
+) -> Result<Sse<impl Stream<Item = Result<Event, axum::Error>>>, AppError> {
+    let last_event_id = headers
+        .get(LAST_EVENT_ID)
+        .map(|value| {
+            value
+                .to_str()
+                .ok()
+                .and_then(|value| value.trim().parse::<i64>().ok())
+                .ok_or_else(|| {
+                    AppError::BadRequest("Last-Event-ID must be an event ID".to_string())
+                })
+        })
+        .transpose()?;
+
+    let stream = events
+        .subscribe(last_event_id, filter)
+        .map(|notice| match notice {
+            Notice::Event(event) => Event::default()
+                .id(event.id.to_string())
+                .event(event.payload.event.as_str())
+                .json_data(&event.payload),
+            Notice::Reset(reason) => Event::default().event("reset").json_data(Reset { reason }),
+        });
+    Ok(Sse::new(stream).keep_alive(KeepAlive::new().interval(events.keep_alive())))
+}
@@ -0,0 +1,87 @@
+use crate::{
+    error::{AppError, ErrorBody},
+    model::export::ExportParams,
+    repository::{DynPostRepository, PostFilter},
+};
+use axum::{
+    body::Body,
+    extract::{Query, State},
+    http::{HeaderValue, header},
+    response::{IntoResponse, Response},
+};
+use futures_util::{StreamExt, stream};
+
+/// Exports the blog posts passing the filters as NDJSON, CSV or Markdown.
+///
+/// # Arguments
+/// * `State(posts)`: The blog post repository.
+/// * `Query(params)`: The export format, `ndjson` (default), `csv` or `markdown`.
+/// * `Query(filter)`: The optional `category` and `tag` filters, as when listing posts.
+///
+/// # Returns
+/// Returns `200 OK` with the posts ordered by ID, as an attachment named `posts.ndjson`,
+/// `posts.csv` or `posts.tar`:
+/// - NDJSON: one JSON object per line, shaped like the posts of the listing.
+/// - CSV: a header row followed by one row per post; the tags of a post are joined by
+///   [`CSV_TAG_SEPARATOR`](crate::model::export::CSV_TAG_SEPARATOR), which a backslash
+///   escapes within tags.
+/// - Markdown: a tar archive of `<slug>.md` files with YAML front matter, which
+///   `POST /posts/import` reads back.
+///
+/// The posts are streamed from a database cursor as they are read, so the export is
+/// never held in memory. A storage error during the export aborts the response, which
+/// the client sees as a truncated body.
+///
+/// # Example
+/// ```text
+/// GET /api/v1/posts/export?format=csv&category=Rust
+/// ```
+#[utoipa::path(
+    get,
+    path = "/posts/export",
+    tag = "posts",
+    description = "Streams the blog posts passing the filters as NDJSON, CSV or a tar archive of Markdown files.",
+    params(ExportParams, PostFilter),
+    responses(
+        (status = 200, description = "Blog posts passing the filters, ordered by ID", content(
+            (String = "application/x-ndjson"),
+            (String = "text/csv"),
+            (String = "application/x-tar"),
+        )),
+        (status = 400, description = "The query string is invalid", body = ErrorBody),
+    )
+)]
+pub async fn export_posts(
+    State(posts): State<DynPostRepository>,
+    Query(ExportParams { format }): Query<ExportParams>,
+    Query(filter): Query<PostFilter>,
+) -> Response {
+    let lines = posts.stream(&filter).map(move |post| {
+        post.and_then(|post| {
+            format
+                .encode(&post)
+                .map_err(|_| AppError::InternalServerError)
+        })
+        .inspect_err(|err| tracing::error!("post export aborted: {err}"))
+    });
+    let body = stream::iter(format.header().map(Ok))
+        .chain(lines)
+        .chain(stream::iter(format.footer().map(Ok)));
+
+    let disposition = format!("attachment; filename=\"posts.{}\"", format.extension());
+    (
+        [
+            (
+                header::CONTENT_TYPE,
+                HeaderValue::from_static(format.content_type()),
+            ),
+            (
+                header::CONTENT_DISPOSITION,
+                HeaderValue::try_from(disposition)
+                    .unwrap_or(HeaderValue::from_static("attachment")),
+            ),
+        ],
+        Body::from_stream(body),
+    )
+        .into_response()
+}
@@ -0,0 +1,155 @@
+use crate::{
+    content,
+    error::{AppError, ErrorBody},
+    media::Media,
+    model::import::{ImportParams, ImportResponse, WordPressImportParams, WordPressImportResponse},
+    repository::DynPostRepository,
+};
+use axum::{
+    Json,
+    body::Bytes,
+    extract::{Query, State},
+    http::StatusCode,
+};
+
+/// Imports blog posts from Markdown files with YAML front matter.
+///
+/// # Arguments
+/// * `State(posts)`: The blog post repository.
+/// * `State(media)`: The media files that imported posts may feature.
+/// * `Query(params)`: The name of the file, when a single Markdown file is uploaded.
+/// * `body`: A Markdown file, or a zip or tar archive (optionally gzip-compressed) of
+///   `.md` files.
+///
+/// Each file is upserted by its slug: the post with the slug is updated, or a new post
+/// is created. The front matter keys `title`, `category`, `tags`, `slug`, `author`,
+/// `date`, `draft`, `featured_image_id`, `meta_title`, `description`, `canonical_url`
+/// and `noindex` map onto the post, and the rest of the file is its content. A file
+/// without a `slug` key takes its slug from its file name.
+///
+/// # Returns
+/// Returns one result per Markdown file, in the order of the upload:
+/// - `200 OK` if every file was imported.
+/// - `207 Multi-Status` if some files could not be read, parsed or validated, or were
+///   refused by the storage (such as a file featuring a missing image); the other files
+///   were imported.
+///
+/// # Errors
+/// This function will return an `AppError` if:
+/// - The archive is corrupt, or holds no Markdown files or too many.
+/// - The storage fails, in which case the files before the failure are imported.
+///
+/// # Example
+/// ```text
+/// POST /api/v1/posts/import?filename=hello-world.md
+/// ---
+/// title: Hello, world
+/// category: Rust
+/// tags: [rust, intro]
+/// date: 2025-03-01
+/// ---
+/// My first post.
+/// ```
+#[utoipa::path(
+    post,
+    path = "/posts/import",
+    tag = "posts",
+    description = "Imports blog posts from a Markdown file with YAML front matter, or a tar or zip archive of them, upserting each post by its slug.",
+    params(ImportParams),
+    request_body(
+        description = "A Markdown file, or a tar, tar.gz or zip archive of Markdown files",
+        content(
+            (String = "text/markdown"),
+            (String = "application/x-tar"),
+            (String = "application/gzip"),
+            (String = "application/zip"),
+        ),
+    ),
+    responses(
+        (status = 200, description = "Every file was imported", body = ImportResponse),
+        (status = 207, description = "Some files could not be imported; the others were", body = ImportResponse),
+        (status = 400, description = "The archive is corrupt, or holds no Markdown files or too many", body = ErrorBody),
+        (status = 413, description = "The request body is too large", body = ErrorBody),
+    )
+)]
+pub async fn import_posts(
+    State(posts): State<DynPostRepository>,
+    State(media): State<Media>,
+    Query(params): Query<ImportParams>,
+    body: Bytes,
+) -> Result<(StatusCode, Json<ImportResponse>), AppError> {
+    let file_name = params
+        .filename
+        .as_deref()
+        .unwrap_or(content::DEFAULT_FILE_NAME);
+    let response = content::import_markdown(&posts, &media.repository, &body, file_name).await?;
+    let status = if response.failed == 0 {
+        StatusCode::OK
+    } else {
+        StatusCode::MULTI_STATUS
+    };
+    Ok((status, Json(response)))
+}
+
+/// Imports blog posts from a WordPress eXtended RSS (WXR) export.
+///
+/// # Arguments
+/// * `State(posts)`: The blog post repository.
+/// * `Query(params)`: Whether to only report what the import would do.
+/// * `body`: The XML file written by WordPress under Tools → Export.
+///
+/// Each post is upserted by its WordPress slug, so importing an export again updates
+/// the posts it created. The first category of a post becomes its category, its tags
+/// and other categories its tags, its author's display name its author, and its
+/// publication date in UTC its `published_at`; posts that are not published are
+/// imported as drafts. HTML bodies are converted to Markdown. Pages, attachments and
+/// trashed posts are skipped.
+///
+/// # Returns
+/// Returns one result per item of the export, in order, with the original permalink
+/// and slug of the item so that redirects can be set up:
+/// - `200 OK` if every post was imported, or with `dry_run=true` would be.
+/// - `207 Multi-Status` if some posts failed validation or were refused by the storage;
+///   the other posts were imported.
+///
+/// # Errors
+/// This function will return an `AppError` if:
+/// - The body is not well-formed XML or not a WordPress export.
+/// - The repository fails, in which case the posts before the failure are imported.
+///
+/// # Example
+/// ```text
+/// POST /api/v1/posts/import/wordpress?dry_run=true
+/// <?xml version="1.0" encoding="UTF-8"?>
+/// <rss version="2.0" xmlns:wp="http://wordpress.org/export/1.2/" ...>
+/// ```
+#[utoipa::path(
+    post,
+    path = "/posts/import/wordpress",
+    tag = "posts",
+    description = "Imports the posts of a WordPress eXtended RSS (WXR) export, upserting each post by its slug. With `dry_run=true`, only reports what the import would do.",
+    params(WordPressImportParams),
+    request_body(
+        description = "A WordPress eXtended RSS (WXR) export",
+        content((String = "application/xml"), (String = "application/rss+xml")),
+    ),
+    responses(
+        (status = 200, description = "Every post was, or would be, imported", body = WordPressImportResponse),
+        (status = 207, description = "Some posts failed validation or were refused by the storage; the others were, or would be, imported", body = WordPressImportResponse),
+        (status = 400, description = "The body is not a WordPress export", body = ErrorBody),
+        (status = 413, description = "The request body is too large", body = ErrorBody),
+    )
+)]
+pub async fn import_wordpress(
+    State(posts): State<DynPostRepository>,
+    Query(params): Query<WordPressImportParams>,
+    body: Bytes,
+) -> Result<(StatusCode, Json<WordPressImportResponse>), AppError> {
+    let response = content::import_wordpress(&posts, &body, params.dry_run).await?;
+    let status = if response.failed == 0 {
+        StatusCode::OK
+    } else {
+        StatusCode::MULTI_STATUS
+    };
+    Ok((status, Json(response)))
+}
@@ -0,0 +1,149 @@
+use crate::{
+    error::{AppError, ErrorBody},
+    jobs::Jobs,
+    model::job::{Job, JobFilter, JobStatus},
+};
+use axum::{
+    Json,
+    extract::{Path, Query, State},
+};
+
+/// Builds the error for a missing job.
+fn job_not_found() -> AppError {
+    AppError::NotFound("Job not found".to_string())
+}
+
+/// Lists background jobs.
+///
+/// # Arguments
+/// * `State(jobs)`: The job queue.
+/// * `Query(filter)`: The optional `queue`, `kind`, `status` and `limit` filters.
+///
+/// # Returns
+/// Returns the jobs passing the filter with the outcome of their last attempt, newest
+/// first, up to the limit.
+///
+/// # Errors
+/// This function will return an `AppError` if the repository fails.
+///
+/// # Example
+/// ```text
+/// GET /api/v1/jobs?status=dead&limit=20
+/// ```
+#[utoipa::path(
+    get,
+    path = "/jobs",
+    tag = "jobs",
+    description = "Lists background jobs, newest first.",
+    params(JobFilter),
+    responses(
+        (status = 200, description = "Jobs passing the filter", body = Vec<Job>),
+        (status = 400, description = "The query string is invalid", body = String),
+    )
+)]
+pub async fn list_jobs(
+    State(jobs): State<Jobs>,
+    Query(filter): Query<JobFilter>,
+) -> Result<Json<Vec<Job>>, AppError> {
+    Ok(Json(jobs.repository.list(&filter).await?))
+}
+
+/// Retrieves a background job by its ID.
+///
+/// # Arguments
+/// * `State(jobs)`: The job queue.
+/// * `Path(id)`: The ID of the job.
+///
+/// # Returns
+/// Returns the job with its payload and the outcome of its last attempt.
+///
+/// # Errors
+/// This function will return an `AppError` if:
+/// - The repository fails.
+/// - The specified job does not exist.
+///
+/// # Example
+/// ```text
+/// GET /api/v1/jobs/42
+/// ```
+#[utoipa::path(
+    get,
+    path = "/jobs/{id}",
+    tag = "jobs",
+    description = "Returns a background job.",
+    params(("id" = i64, Path, description = "ID of the job")),
+    responses(
+        (status = 200, description = "The job", body = Job),
+        (status = 404, description = "No job has this ID", body = ErrorBody),
+    )
+)]
+pub async fn get_job(State(jobs): State<Jobs>, Path(id): Path<i64>) -> Result<Json<Job>, AppError> {
+    jobs.repository
+        .get(id)
+        .await?
+        .map(Json)
+        .ok_or_else(job_not_found)
+}
+
+/// Runs a dead or queued background job again, right away.
+///
+/// # Arguments
+/// * `State(jobs)`: The job queue.
+/// * `Path(id)`: The ID of the job.
+///
+/// The job is queued with its attempts reset, so it is retried as many times as a new
+/// job. Its last error is kept until the next attempt.
+///
+/// # Returns
+/// Returns the queued job.
+///
+/// # Errors
+/// This function will return an `AppError` if:
+/// - The repository fails.
+/// - The specified job does not exist.
+/// - The job is running or succeeded (`409`).
+///
+/// # Example
+/// ```text
+/// POST /api/v1/jobs/42/retry
+/// ```
+#[utoipa::path(
+    post,
+    path = "/jobs/{id}/retry",
+    tag = "jobs",
+    description = "Queues a dead or waiting background job to run again right away.",
+    params(("id" = i64, Path, description = "ID of the job to retry")),
+    responses(
+        (status = 200, description = "The queued job", body = Job),
+        (status = 404, description = "No job has this ID", body = ErrorBody),
+        (status = 409, description = "The job is running or succeeded", body = ErrorBody),
+    )
+)]
+pub async fn retry_job(
+    State(jobs): State<Jobs>,
+    Path(id): Path<i64>,
+) -> Result<Json<Job>, AppError> {
+    let status = jobs
+        .repository
+        .get(id)
+        .await?
+        .ok_or_else(job_not_found)?
+        .status;
+    let conflict = |status: JobStatus| {
+        AppError::Conflict(format!(
+            "Job {id} is {}; only dead or queued jobs can be retried",
+            status.as_str()
+        ))
+    };
+    if matches!(status, JobStatus::Running | JobStatus::Succeeded) {
+        return Err(conflict(status));
+    }
+    // The job may have been claimed since it was read.
+    let job = jobs
+        .repository
+        .retry(id)
+        .await?
+        .ok_or_else(|| conflict(JobStatus::Running))?;
+    jobs.wake(&job.queue);
+    Ok(Json(job))
+}
@@ -1,34 +1,48 @@
-use axum::Json;
+use axum::{
+    extract::{Query, State},
+    Json,
+};
 
-use crate::{database::extractor::DatabaseConnection, error::AppError, model::blog::BlogPost};
+use crate::{
+    error::AppError,
+    model::blog::BlogPost,
+    repository::{DynPostRepository, PostFilter},
+};
 
-/// Retrieves all blog posts from the database.
+/// Retrieves all blog posts, optionally filtered by category or tag.
 ///
 /// # Arguments
 ///
-/// * `DatabaseConnection(mut conn)`: A wrapper for the database connection.
+/// * `State(posts)`: The blog post repository.
+/// * `Query(filter)`: Optional `category` and `tag` filters.
 ///
 /// # Returns
 ///
 /// Returns a `Result` containing:
 /// - `Json(Vec<BlogPost>)` if the query is successful.
-/// - `AppError` if an error occurs during the database query.
+/// - `AppError` if an error occurs in the repository.
 ///
 /// # Errors
 ///
 /// This function will return an `AppError` if:
-/// - The database query fails.
+/// - The repository fails.
 ///
 /// # Example
 ///
+/// ```text
+/// GET /api/v1/posts?category=Rust&tag=async
 /// ```
-/// GET /posts
-/// ```
+#[utoipa::path(
+    get,
+    path = "/posts",
+    tag = "posts",
+    description = "Returns every blog post, ordered by ID, optionally filtered by category or tag.",
+    params(PostFilter),
+    responses((status = 200, description = "All blog posts", body = [BlogPost]))
+)]
 pub async fn find_all(
-    DatabaseConnection(mut conn): DatabaseConnection,
+    State(posts): State<DynPostRepository>,
+    Query(filter): Query<PostFilter>,
 ) -> Result<Json<Vec<BlogPost>>, AppError> {
-    let posts = sqlx::query_as!(BlogPost, "SELECT * FROM blog_posts")
-        .fetch_all(&mut *conn)
-        .await?;
-    Ok(Json(posts))
+    Ok(Json(posts.list(&filter).await?))
 }
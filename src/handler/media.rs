@@ -0,0 +1,670 @@
+use crate::{
+    error::{AppError, ErrorBody},
+    jobs::Jobs,
+    media::{Media, blob::storage_error, range::ByteRange},
+    model::media::{MediaFile, MediaFilter},
+    repository::DynPostRepository,
+};
+use axum::{
+    Json,
+    body::Body,
+    extract::{Multipart, Path, Query, State, multipart::MultipartError},
+    http::{HeaderMap, HeaderValue, StatusCode, header},
+    response::{IntoResponse, Response},
+};
+use chrono::{DateTime, Utc};
+use std::fmt::Write;
+
+/// Builds the error for a missing media file.
+fn media_not_found() -> AppError {
+    AppError::NotFound("Media file not found".to_string())
+}
+
+/// Converts a malformed multipart body into `AppError::BadRequest`.
+fn multipart_error(err: MultipartError) -> AppError {
+    AppError::BadRequest(format!("Invalid multipart body: {}", err.body_text()))
+}
+
+/// Uploads a media file.
+///
+/// # Arguments
+/// * `State(media)`: The media file repository, blob store and configuration.
+/// * `State(jobs)`: The job queue generating the variants of images.
+/// * `multipart`: A `multipart/form-data` body with the file in a `file` field and,
+///   optionally, the name of its owner in an `owner` field.
+///
+/// The type of the file is recognized from its first bytes, whatever its name or the
+/// `Content-Type` of its part; a part type other than `application/octet-stream` must
+/// match. The dimensions of images are read from their headers. PNG, JPEG and WebP images
+/// are stored without EXIF, XMP and other identifying metadata, rotated upright if their
+/// EXIF orientation asks for it. The content is stored once per SHA-256 hash, so
+/// uploading the same file again only adds a record.
+///
+/// The variants of images are generated by a background job: their `variants_status` is
+/// `pending` until they are all stored.
+///
+/// # Returns
+/// Returns `201 Created` with the record of the stored file.
+///
+/// # Errors
+/// This function will return an `AppError` if:
+/// - The body is not valid multipart, lacks a `file` field, or has other fields.
+/// - The file is empty or larger than `HTTP_MAX_UPLOAD_BYTES` (`413`).
+/// - The file type is not recognized, not accepted or not the declared one (`415`).
+/// - The repository or blob store fails.
+///
+/// # Example
+/// ```text
+/// POST /api/v1/media
+/// Content-Type: multipart/form-data; boundary=x
+///
+/// --x
+/// Content-Disposition: form-data; name="file"; filename="diagram.png"
+/// Content-Type: image/png
+///
+/// <PNG bytes>
+/// --x--
+/// ```
+#[utoipa::path(
+    post,
+    path = "/media",
+    tag = "media",
+    description = "Uploads a media file; its type is recognized from its content.",
+    request_body(
+        description = "The file in a `file` field and, optionally, its owner in an `owner` field",
+        content_type = "multipart/form-data",
+        content = String,
+    ),
+    responses(
+        (status = 201, description = "The stored media file", body = MediaFile),
+        (status = 400, description = "The body is not valid multipart, or the file is missing or empty", body = ErrorBody),
+        (status = 413, description = "The file is too large", body = ErrorBody),
+        (status = 415, description = "The file type is not recognized, not accepted or not the declared one", body = ErrorBody),
+    )
+)]
+pub async fn upload_media(
+    State(media): State<Media>,
+    State(jobs): State<Jobs>,
+    mut multipart: Multipart,
+) -> Result<(StatusCode, Json<MediaFile>), AppError> {
+    let mut owner = None;
+    let mut upload = None;
+    while let Some(field) = multipart.next_field().await.map_err(multipart_error)? {
+        let name = field.name().unwrap_or_default().to_string();
+        match name.as_str() {
+            "owner" if owner.is_none() => {
+                owner = Some(field.text().await.map_err(multipart_error)?);
+            }
+            "file" if upload.is_none() => {
+                let filename = field.file_name().map(str::to_string);
+                let content_type = field.content_type().map(str::to_string);
+                let data = field.bytes().await.map_err(multipart_error)?;
+                upload = Some((filename, content_type, data));
+            }
+            "owner" | "file" => {
+                return Err(AppError::BadRequest(format!(
+                    "The field `{name}` is given more than once"
+                )));
+            }
+            _ => {
+                return Err(AppError::BadRequest(format!(
+                    "Unknown field `{name}`; expected `file` and optionally `owner`"
+                )));
+            }
+        }
+    }
+    let Some((filename, content_type, data)) = upload else {
+        return Err(AppError::BadRequest(
+            "The field `file` is missing".to_string(),
+        ));
+    };
+
+    let file = media
+        .upload(
+            &jobs,
+            owner,
+            filename.as_deref(),
+            content_type.as_deref(),
+            data,
+        )
+        .await?;
+    Ok((StatusCode::CREATED, Json(file)))
+}
+
+/// Lists the media files passing the filter.
+///
+/// # Arguments
+/// * `State(media)`: The media file repository.
+/// * `Query(filter)`: The optional `owner` and `variants_status` filters.
+///
+/// # Returns
+/// Returns the records of the files, ordered by ID.
+///
+/// # Errors
+/// This function will return an `AppError` if the repository fails.
+///
+/// # Example
+/// ```text
+/// GET /api/v1/media?owner=ann
+/// ```
+#[utoipa::path(
+    get,
+    path = "/media",
+    tag = "media",
+    description = "Lists the uploaded media files, ordered by ID.",
+    params(MediaFilter),
+    responses(
+        (status = 200, description = "Media files passing the filter", body = Vec<MediaFile>),
+        (status = 400, description = "The query string is invalid", body = String),
+    )
+)]
+pub async fn list_media(
+    State(media): State<Media>,
+    Query(filter): Query<MediaFilter>,
+) -> Result<Json<Vec<MediaFile>>, AppError> {
+    Ok(Json(media.repository.list(&filter).await?))
+}
+
+/// Retrieves the record of a media file by its ID.
+///
+/// # Arguments
+/// * `State(media)`: The media file repository.
+/// * `Path(id)`: The ID of the media file.
+///
+/// # Returns
+/// Returns the record of the file with its variants and the `srcset` lists built from
+/// them; its content is served by [`download_media`], and that of its variants by
+/// [`download_media_variant`].
+///
+/// # Errors
+/// This function will return an `AppError` if:
+/// - The repository fails.
+/// - The specified media file does not exist.
+///
+/// # Example
+/// ```text
+/// GET /api/v1/media/1
+/// ```
+#[utoipa::path(
+    get,
+    path = "/media/{id}",
+    tag = "media",
+    description = "Returns the record of a media file.",
+    params(("id" = i32, Path, description = "ID of the media file")),
+    responses(
+        (status = 200, description = "The media file", body = MediaFile),
+        (status = 404, description = "No media file has this ID", body = ErrorBody),
+    )
+)]
+pub async fn get_media(
+    State(media): State<Media>,
+    Path(id): Path<i32>,
+) -> Result<Json<MediaFile>, AppError> {
+    media
+        .repository
+        .get(id)
+        .await?
+        .map(Json)
+        .ok_or_else(media_not_found)
+}
+
+/// Downloads the content of a media file.
+///
+/// # Arguments
+/// * `State(media)`: The media file repository, blob store and configuration.
+/// * `Path(id)`: The ID of the media file.
+/// * `headers`: The request headers, for conditional and range requests.
+///
+/// The content never changes, so its hash serves as a strong `ETag` and responses may be
+/// cached for `MEDIA_CACHE_MAX_AGE_SECS`. A single byte range can be requested with a
+/// `Range` header, and is only served if the `If-Range` header, when present, matches the
+/// `ETag`. Images are served inline and may be embedded by other origins; other files
+/// are served as attachments.
+///
+/// If the blob store offers temporary download URLs, such as presigned S3 URLs, the
+/// client is redirected there instead and the store serves the content and ranges.
+///
+/// # Returns
+/// - `200 OK` with the content.
+/// - `206 Partial Content` with the requested range and a `Content-Range` header.
+/// - `304 Not Modified` if the `If-None-Match` header matches the `ETag`.
+/// - `307 Temporary Redirect` to a download URL of the blob store, if it offers one.
+/// - `416 Range Not Satisfiable` if the range starts past the end of the content.
+///
+/// # Errors
+/// This function will return an `AppError` if:
+/// - The repository or blob store fails, or the content is missing from the blob store.
+/// - The specified media file does not exist.
+///
+/// # Example
+/// ```text
+/// GET /api/v1/media/1/content
+/// Range: bytes=0-1023
+/// ```
+#[utoipa::path(
+    get,
+    path = "/media/{id}/content",
+    tag = "media",
+    description = "Downloads the content of a media file, in full or a single byte range.",
+    params(
+        ("id" = i32, Path, description = "ID of the media file"),
+        ("Range" = Option<String>, Header, description = "A single byte range, e.g. `bytes=0-1023`"),
+        ("If-Range" = Option<String>, Header, description = "Serve the range only if the `ETag` still matches"),
+        ("If-None-Match" = Option<String>, Header, description = "`ETag` of a cached copy"),
+    ),
+    responses(
+        (status = 200, description = "The content of the file", content_type = "application/octet-stream", body = Vec<u8>),
+        (status = 206, description = "The requested range of the content", content_type = "application/octet-stream", body = Vec<u8>),
+        (status = 304, description = "The cached copy is current"),
+        (status = 307, description = "The content is served by the storage at the `Location` URL, which expires"),
+        (status = 404, description = "No media file has this ID", body = ErrorBody),
+        (status = 416, description = "The range starts past the end of the content"),
+    )
+)]
+pub async fn download_media(
+    State(media): State<Media>,
+    Path(id): Path<i32>,
+    headers: HeaderMap,
+) -> Result<Response, AppError> {
+    let file = media
+        .repository
+        .get(id)
+        .await?
+        .ok_or_else(media_not_found)?;
+    let content = Content {
+        content_hash: &file.content_hash,
+        content_type: &file.content_type,
+        size: file.size,
+        created_at: file.created_at,
+        disposition: content_disposition(&file.filename, &file.content_type),
+    };
+    serve(&media, &headers, content).await
+}
+
+/// Downloads the content of a variant of a media file, such as a WebP thumbnail.
+///
+/// # Arguments
+/// * `State(media)`: The media file repository, blob store and configuration.
+/// * `Path((id, name))`: The ID of the media file and the name of the variant.
+/// * `headers`: The request headers, for conditional and range requests.
+///
+/// Variants are served like the content of files by [`download_media`], under the name
+/// of the file with that of the variant appended.
+///
+/// # Returns
+/// The same responses as [`download_media`].
+///
+/// # Errors
+/// This function will return an `AppError` if:
+/// - The repository or blob store fails, or the content is missing from the blob store.
+/// - The specified media file or variant does not exist.
+///
+/// # Example
+/// ```text
+/// GET /api/v1/media/1/variants/640w.webp
+/// ```
+#[utoipa::path(
+    get,
+    path = "/media/{id}/variants/{name}",
+    tag = "media",
+    description = "Downloads a resized or re-encoded variant of an image, in full or a single byte range.",
+    params(
+        ("id" = i32, Path, description = "ID of the media file"),
+        ("name" = String, Path, description = "Name of the variant, e.g. `640w.webp`"),
+        ("Range" = Option<String>, Header, description = "A single byte range, e.g. `bytes=0-1023`"),
+        ("If-Range" = Option<String>, Header, description = "Serve the range only if the `ETag` still matches"),
+        ("If-None-Match" = Option<String>, Header, description = "`ETag` of a cached copy"),
+    ),
+    responses(
+        (status = 200, description = "The content of the variant", content_type = "application/octet-stream", body = Vec<u8>),
+        (status = 206, description = "The requested range of the content", content_type = "application/octet-stream", body = Vec<u8>),
+        (status = 304, description = "The cached copy is current"),
+        (status = 307, description = "The content is served by the storage at the `Location` URL, which expires"),
+        (status = 404, description = "No media file has this ID, or it has no variant of this name", body = ErrorBody),
+        (status = 416, description = "The range starts past the end of the content"),
+    )
+)]
+pub async fn download_media_variant(
+    State(media): State<Media>,
+    Path((id, name)): Path<(i32, String)>,
+    headers: HeaderMap,
+) -> Result<Response, AppError> {
+    let file = media
+        .repository
+        .get(id)
+        .await?
+        .ok_or_else(media_not_found)?;
+    let variant = file
+        .variants
+        .iter()
+        .find(|variant| variant.name == name)
+        .ok_or_else(|| AppError::NotFound("Media variant not found".to_string()))?;
+    let stem = file
+        .filename
+        .rsplit_once('.')
+        .map_or(file.filename.as_str(), |(stem, _)| stem);
+    let content = Content {
+        content_hash: &variant.content_hash,
+        content_type: &variant.content_type,
+        size: variant.size,
+        created_at: variant.created_at,
+        disposition: content_disposition(&format!("{stem}-{name}"), &variant.content_type),
+    };
+    serve(&media, &headers, content).await
+}
+
+/// Stored content to download, of a media file or one of its variants.
+struct Content<'a> {
+    /// Hex-encoded SHA-256 hash of the content, its key in the blob store.
+    content_hash: &'a str,
+    /// MIME type of the content.
+    content_type: &'a str,
+    /// Size of the content in bytes.
+    size: i64,
+    /// When the content was stored.
+    created_at: DateTime<Utc>,
+    /// `Content-Disposition` header of the download.
+    disposition: String,
+}
+
+/// Serves stored content as described for [`download_media`].
+async fn serve(
+    media: &Media,
+    headers: &HeaderMap,
+    content: Content<'_>,
+) -> Result<Response, AppError> {
+    let size = u64::try_from(content.size).map_err(|_| AppError::InternalServerError)?;
+    let etag = format!("\"{}\"", content.content_hash);
+    let matches_etag = |name| {
+        headers
+            .get_all(name)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .map(|tag| tag.trim().trim_start_matches("W/"))
+            .any(|tag| tag == etag || tag == "*")
+    };
+
+    if let Some(url) = media.blobs.download_url(
+        content.content_hash,
+        content.content_type,
+        &content.disposition,
+    )? {
+        // The URL expires, so the redirect must not be cached like the content.
+        let location = HeaderValue::try_from(url).map_err(|_| AppError::InternalServerError)?;
+        return Ok((
+            StatusCode::TEMPORARY_REDIRECT,
+            [
+                (header::LOCATION, location),
+                (header::CACHE_CONTROL, HeaderValue::from_static("no-store")),
+            ],
+        )
+            .into_response());
+    }
+
+    let mut response_headers = HeaderMap::new();
+    let mut set = |name, value: String| {
+        if let Ok(value) = HeaderValue::try_from(value) {
+            response_headers.insert(name, value);
+        }
+    };
+    set(header::ETAG, etag.clone());
+    set(
+        header::CACHE_CONTROL,
+        format!(
+            "public, max-age={}, immutable",
+            media.config.cache_max_age.as_secs()
+        ),
+    );
+    set(
+        header::LAST_MODIFIED,
+        content
+            .created_at
+            .format("%a, %d %b %Y %H:%M:%S GMT")
+            .to_string(),
+    );
+    if matches_etag(header::IF_NONE_MATCH) {
+        return Ok((StatusCode::NOT_MODIFIED, response_headers).into_response());
+    }
+    set(header::ACCEPT_RANGES, "bytes".to_string());
+    set(header::CONTENT_TYPE, content.content_type.to_string());
+    set(header::CONTENT_DISPOSITION, content.disposition);
+    set(
+        header::HeaderName::from_static("cross-origin-resource-policy"),
+        "cross-origin".to_string(),
+    );
+
+    let range = headers
+        .get(header::RANGE)
+        .and_then(|value| value.to_str().ok())
+        .filter(|_| !headers.contains_key(header::IF_RANGE) || matches_etag(header::IF_RANGE))
+        .map_or(ByteRange::Full, |value| ByteRange::parse(value, size));
+    let (status, range) = match range {
+        ByteRange::Full => (StatusCode::OK, 0..size),
+        ByteRange::Partial(range) => {
+            set(
+                header::CONTENT_RANGE,
+                format!(
+                    "bytes {}-{}/{size}",
+                    range.start,
+                    range.end.saturating_sub(1)
+                ),
+            );
+            (StatusCode::PARTIAL_CONTENT, range)
+        }
+        ByteRange::Unsatisfiable => {
+            set(header::CONTENT_RANGE, format!("bytes */{size}"));
+            return Ok((StatusCode::RANGE_NOT_SATISFIABLE, response_headers).into_response());
+        }
+    };
+    set(
+        header::CONTENT_LENGTH,
+        (range.end - range.start).to_string(),
+    );
+
+    let stream = media
+        .blobs
+        .get(content.content_hash, range)
+        .await?
+        .ok_or_else(|| {
+            storage_error(format!("media content {} is missing", content.content_hash))
+        })?;
+    Ok((status, response_headers, Body::from_stream(stream)).into_response())
+}
+
+/// Builds the `Content-Disposition` header of a download: `inline` for images and
+/// `attachment` for other files, with the file name as ASCII and percent-encoded UTF-8.
+fn content_disposition(filename: &str, content_type: &str) -> String {
+    let disposition = if content_type.starts_with("image/") {
+        "inline"
+    } else {
+        "attachment"
+    };
+    let ascii: String = filename
+        .chars()
+        .map(|c| match c {
+            ' '..='~' if c != '"' && c != '\\' => c,
+            _ => '_',
+        })
+        .collect();
+    let mut encoded = String::new();
+    for byte in filename.bytes() {
+        if byte.is_ascii_alphanumeric() || b"!#$&+-.^_`|~".contains(&byte) {
+            encoded.push(char::from(byte));
+        } else {
+            let _ = write!(encoded, "%{byte:02X}");
+        }
+    }
+    format!("{disposition}; filename=\"{ascii}\"; filename*=UTF-8''{encoded}")
+}
+
+/// Deletes a media file and its variants by its ID, detaching it from every post.
+///
+/// # Arguments
+/// * `State(media)`: The media file repository and blob store.
+/// * `Path(id)`: The ID of the media file to delete.
+///
+/// # Returns
+/// Returns `204 No Content`. The content of the file and its variants is deleted too,
+/// unless another media file or variant has the same content.
+///
+/// # Errors
+/// This function will return an `AppError` if:
+/// - The repository fails.
+/// - The specified media file does not exist.
+///
+/// # Example
+/// ```text
+/// DELETE /api/v1/media/1
+/// ```
+#[utoipa::path(
+    delete,
+    path = "/media/{id}",
+    tag = "media",
+    description = "Permanently deletes a media file and detaches it from every post.",
+    params(("id" = i32, Path, description = "ID of the media file to delete")),
+    responses(
+        (status = 204, description = "The media file was deleted"),
+        (status = 404, description = "No media file has this ID", body = ErrorBody),
+    )
+)]
+pub async fn delete_media(
+    State(media): State<Media>,
+    Path(id): Path<i32>,
+) -> Result<StatusCode, AppError> {
+    if !media.delete(id).await? {
+        return Err(media_not_found());
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Lists the media files attached to a blog post.
+///
+/// # Arguments
+/// * `State(posts)`: The blog post repository.
+/// * `State(media)`: The media file repository.
+/// * `Path(id)`: The ID of the blog post.
+///
+/// # Returns
+/// Returns the records of the attached files with their variants, in the order they were
+/// attached.
+///
+/// # Errors
+/// This function will return an `AppError` if:
+/// - The repository fails.
+/// - The specified blog post does not exist.
+///
+/// # Example
+/// ```text
+/// GET /api/v1/posts/1/media
+/// ```
+#[utoipa::path(
+    get,
+    path = "/posts/{id}/media",
+    tag = "media",
+    description = "Lists the media files attached to a blog post, in the order they were attached.",
+    params(("id" = i32, Path, description = "ID of the blog post")),
+    responses(
+        (status = 200, description = "The attached media files", body = Vec<MediaFile>),
+        (status = 404, description = "No blog post has this ID", body = ErrorBody),
+    )
+)]
+pub async fn list_post_media(
+    State(posts): State<DynPostRepository>,
+    State(media): State<Media>,
+    Path(id): Path<i32>,
+) -> Result<Json<Vec<MediaFile>>, AppError> {
+    if posts.get(id).await?.is_none() {
+        return Err(AppError::NotFound("Blog post not found".to_string()));
+    }
+    Ok(Json(media.repository.list_for_post(id).await?))
+}
+
+/// Attaches a media file to a blog post.
+///
+/// # Arguments
+/// * `State(posts)`: The blog post repository.
+/// * `State(media)`: The media file repository.
+/// * `Path((id, media_id))`: The IDs of the blog post and the media file.
+///
+/// # Returns
+/// Returns `204 No Content`, also when the file was attached already.
+///
+/// # Errors
+/// This function will return an `AppError` if:
+/// - The repository fails.
+/// - The specified blog post or media file does not exist.
+///
+/// # Example
+/// ```text
+/// PUT /api/v1/posts/1/media/3
+/// ```
+#[utoipa::path(
+    put,
+    path = "/posts/{id}/media/{media_id}",
+    tag = "media",
+    description = "Attaches a media file to a blog post; attaching it again changes nothing.",
+    params(
+        ("id" = i32, Path, description = "ID of the blog post"),
+        ("media_id" = i32, Path, description = "ID of the media file to attach"),
+    ),
+    responses(
+        (status = 204, description = "The media file is attached"),
+        (status = 404, description = "No blog post or media file has this ID", body = ErrorBody),
+    )
+)]
+pub async fn attach_media(
+    State(posts): State<DynPostRepository>,
+    State(media): State<Media>,
+    Path((id, media_id)): Path<(i32, i32)>,
+) -> Result<StatusCode, AppError> {
+    if posts.get(id).await?.is_none() {
+        return Err(AppError::NotFound("Blog post not found".to_string()));
+    }
+    media.repository.attach(id, media_id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Detaches a media file from a blog post, leaving the file in place.
+///
+/// # Arguments
+/// * `State(media)`: The media file repository.
+/// * `Path((id, media_id))`: The IDs of the blog post and the media file.
+///
+/// # Returns
+/// Returns `204 No Content` if the file was attached to the post.
+///
+/// # Errors
+/// This function will return an `AppError` if:
+/// - The repository fails.
+/// - The media file is not attached to the blog post.
+///
+/// # Example
+/// ```text
+/// DELETE /api/v1/posts/1/media/3
+/// ```
+#[utoipa::path(
+    delete,
+    path = "/posts/{id}/media/{media_id}",
+    tag = "media",
+    description = "Detaches a media file from a blog post without deleting the file.",
+    params(
+        ("id" = i32, Path, description = "ID of the blog post"),
+        ("media_id" = i32, Path, description = "ID of the media file to detach"),
+    ),
+    responses(
+        (status = 204, description = "The media file was detached"),
+        (status = 404, description = "The media file is not attached to this blog post", body = ErrorBody),
+    )
+)]
+pub async fn detach_media(
+    State(media): State<Media>,
+    Path((id, media_id)): Path<(i32, i32)>,
+) -> Result<StatusCode, AppError> {
+    if !media.repository.detach(id, media_id).await? {
+        return Err(AppError::NotFound(
+            "Media file not attached to this blog post".to_string(),
+        ));
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -0,0 +1,67 @@
+use crate::{
+    error::{AppError, ErrorBody},
+    media::Media,
+    model::meta::PostMeta,
+    repository::DynPostRepository,
+    site::{meta::post_meta, SiteConfig},
+};
+use axum::{
+    extract::{Path, State},
+    Json,
+};
+use std::sync::Arc;
+
+/// Returns the metadata of a blog post for search engines and link previews.
+///
+/// # Arguments
+///
+/// * `State(posts)`: The blog post repository.
+/// * `State(media)`: The media file repository, to read the featured image.
+/// * `State(site)`: The site configuration, for the title and URLs of the site.
+/// * `Path(id)`: The ID of the blog post.
+///
+/// # Returns
+///
+/// Returns the Open Graph and Twitter Card tags, the JSON-LD `BlogPosting` and the same
+/// as HTML for the page head. The meta title and description of the post are used if
+/// set, and its title and the start of its content otherwise. A featured image that was
+/// deleted is left out.
+///
+/// # Errors
+///
+/// This function will return an `AppError` if:
+/// - The repository fails.
+/// - The specified blog post does not exist.
+///
+/// # Example
+///
+/// ```text
+/// GET /api/v1/posts/1/meta
+/// ```
+#[utoipa::path(
+    get,
+    path = "/posts/{id}/meta",
+    tag = "posts",
+    description = "Returns the Open Graph, Twitter Card and JSON-LD metadata of a blog post.",
+    params(("id" = i32, Path, description = "ID of the blog post")),
+    responses(
+        (status = 200, description = "The metadata of the blog post", body = PostMeta),
+        (status = 404, description = "No blog post has this ID", body = ErrorBody),
+    )
+)]
+pub async fn find_meta_by_id(
+    State(posts): State<DynPostRepository>,
+    State(media): State<Media>,
+    State(site): State<Arc<SiteConfig>>,
+    Path(id): Path<i32>,
+) -> Result<Json<PostMeta>, AppError> {
+    let post = posts
+        .get(id)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Blog post not found".to_string()))?;
+    let featured_image = match post.featured_image_id {
+        Some(image_id) => media.repository.get(image_id).await?,
+        None => None,
+    };
+    Ok(Json(post_meta(&post, featured_image, &site)))
+}
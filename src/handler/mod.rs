@@ -1,12 +1,34 @@
+/// It have post, patch and delete methods for creating, updating and deleting blog posts in bulk.
+pub mod bulk;
+/// It have get method for joining the room of a blog post over a WebSocket, to see who is viewing or editing it and take its edit lock.
+pub mod collab;
 /// It have post method for creating a new blog post.
 pub mod create;
 /// It have delete method for deleting a blog post by id.
 pub mod delete;
+/// It have get methods for the OpenAPI document and the documentation page.
+pub mod docs;
+/// It have get method for streaming changes to blog posts as Server-Sent Events.
+pub mod events;
+/// It have get method for exporting blog posts as NDJSON, CSV or Markdown.
+pub mod export;
+/// It have post methods for importing blog posts from Markdown files and WordPress exports.
+pub mod import;
+/// It have get methods for listing and reading background jobs, and post method for retrying them.
+pub mod job;
 /// It have get method for reading all blog posts.
 pub mod list;
+/// It have post, get and delete methods for uploading, downloading and deleting media files, and put and delete methods for attaching them to blog posts.
+pub mod media;
+/// It have get method for reading the search engine and link preview metadata of a blog post by id.
+pub mod meta;
 /// It have get method for reading a blog post by id.
 pub mod read;
 /// It have get method for searching blog posts.
 pub mod search;
 /// It have put method for updating a blog post by id.
 pub mod update;
+/// Handlers specific to version 2 of the API.
+pub mod v2;
+/// It have post, get, put and delete methods for managing webhooks, and get and post methods for listing and repeating their deliveries.
+pub mod webhook;
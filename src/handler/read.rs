@@ -1,39 +1,56 @@
-use crate::model::blog::BlogPost;
-use crate::{database::extractor::DatabaseConnection, error::AppError};
-use axum::{extract::Path, Json};
+use crate::{
+    error::{AppError, ErrorBody},
+    model::blog::BlogPost,
+    repository::DynPostRepository,
+};
+use axum::{
+    extract::{Path, State},
+    Json,
+};
 
-/// Retrieves a blog post by its ID from the database.
+/// Retrieves a blog post by its ID.
 ///
 /// # Arguments
 ///
-/// * `DatabaseConnection(mut conn)`: A wrapper for the database connection.
+/// * `State(posts)`: The blog post repository.
 /// * `Path(id)`: The ID of the blog post to retrieve.
 ///
 /// # Returns
 ///
 /// Returns a `Result` containing:
-/// - `Json(Some(BlogPost))` if the blog post is found.
-/// - `Json(None)` if no blog post with the given ID exists.
-/// - `AppError` if an error occurs during the database query.
+/// - `Json(BlogPost)` if the blog post is found.
+/// - `AppError::NotFound` if no blog post with the given ID exists.
+/// - `AppError` if an error occurs in the repository.
 ///
 /// # Errors
 ///
 /// This function will return an `AppError` if:
-/// - The database query fails.
+/// - The repository fails.
+/// - The specified blog post does not exist.
 ///
 /// # Example
 ///
+/// ```text
+/// GET /api/v1/posts/1
 /// ```
-/// GET /posts/1
+#[utoipa::path(
+    get,
+    path = "/posts/{id}",
+    tag = "posts",
+    description = "Returns a single blog post.",
+    params(("id" = i32, Path, description = "ID of the blog post to retrieve")),
+    responses(
+        (status = 200, description = "The blog post", body = BlogPost),
+        (status = 404, description = "No blog post has this ID", body = ErrorBody),
+    )
+)]
 pub async fn find_by_id(
-    DatabaseConnection(mut conn): DatabaseConnection,
+    State(posts): State<DynPostRepository>,
     Path(id): Path<i32>,
 ) -> Result<Json<BlogPost>, AppError> {
-    let value = sqlx::query_as!(BlogPost, "SELECT * FROM blog_posts WHERE id = $1", id)
-        .fetch_optional(&mut *conn)
-        .await?;
-
-    value
+    posts
+        .get(id)
+        .await?
         .map(Json)
         .ok_or_else(|| AppError::NotFound("Blog post not found".to_string()))
 }
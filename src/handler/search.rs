@@ -1,6 +1,13 @@
-use axum::{Json, extract::Query};
+use axum::{
+    Json,
+    extract::{Query, State},
+};
 
-use crate::{database::extractor::DatabaseConnection, error::AppError, model::blog::BlogPost};
+use crate::{
+    error::{AppError, ErrorBody},
+    model::blog::BlogPost,
+    repository::DynPostRepository,
+};
 
 /// Represents the query parameters for searching blog posts.
 ///
@@ -10,19 +17,21 @@ use crate::{database::extractor::DatabaseConnection, error::AppError, model::blo
 ///
 /// # Example
 ///
+/// ```text
+/// GET /api/v1/posts/search?term=rust
 /// ```
-/// GET /posts?term=rust
-/// ```
-#[derive(serde::Deserialize)]
+#[derive(serde::Deserialize, utoipa::IntoParams)]
+#[into_params(parameter_in = Query)]
 pub struct SearchQuery {
+    /// Text matched case-insensitively against titles, content and tags.
     pub term: String,
 }
 
-/// Searches for blog posts in the database based on a search term.
+/// Searches for blog posts based on a search term.
 ///
 /// # Arguments
 ///
-/// * `DatabaseConnection(mut conn)`: A wrapper for the database connection.
+/// * `State(posts)`: The blog post repository.
 /// * `Query(SearchQuery { term })`: The search query containing the term to filter blog posts.
 ///
 /// # Returns
@@ -30,36 +39,35 @@ pub struct SearchQuery {
 /// Returns a `Result` containing:
 /// - `Json(Vec<BlogPost>)` if matching blog posts are found.
 /// - `AppError::NotFound` if no blog posts match the search criteria.
-/// - `AppError` if an error occurs during the database query.
+/// - `AppError` if an error occurs in the repository.
 ///
 /// # Errors
 ///
 /// This function will return an `AppError` if:
-/// - The database query fails.
+/// - The repository fails.
 /// - No matching blog posts are found.
 ///
 /// # Example
 ///
+/// ```text
+/// GET /api/v1/posts/search?term=rust
 /// ```
-/// GET /posts?term=rust
-/// ```
+#[utoipa::path(
+    get,
+    path = "/posts/search",
+    tag = "posts",
+    description = "Returns blog posts whose title, content or tags contain the search term.",
+    params(SearchQuery),
+    responses(
+        (status = 200, description = "Blog posts matching the search term", body = [BlogPost]),
+        (status = 404, description = "No blog post matches the search term", body = ErrorBody),
+    )
+)]
 pub async fn search_posts(
-    DatabaseConnection(mut conn): DatabaseConnection,
+    State(posts): State<DynPostRepository>,
     Query(SearchQuery { term }): Query<SearchQuery>,
 ) -> Result<Json<Vec<BlogPost>>, AppError> {
-    let blog_posts = sqlx::query_as!(
-        BlogPost,
-        r#"
-        SELECT * FROM blog_posts
-        WHERE 
-            title ILIKE '%' || $1 || '%' OR
-            content ILIKE '%' || $1 || '%' OR
-            array_to_string(tags, ',') ILIKE '%' || $1 || '%';
-        "#,
-        term
-    )
-    .fetch_all(&mut *conn)
-    .await?;
+    let blog_posts = posts.search(&term).await?;
 
     if blog_posts.is_empty() {
         return Err(AppError::NotFound("No blog posts found".to_string()));
@@ -1,16 +1,31 @@
-use axum::{extract::Path, Json};
+use axum::{
+    Json,
+    extract::{Path, State},
+    http::HeaderMap,
+};
 use axum_valid::Valid;
-use sqlx::query_as;
 
-use crate::{database::extractor::DatabaseConnection, error::AppError, model::blog::BlogPost};
+use crate::{
+    collab::{Collaboration, EDIT_LOCK_HEADER},
+    database::extractor::DatabaseTransaction,
+    error::{AppError, ErrorBody},
+    media::Media,
+    model::blog::{BlogPost, BlogPostBody},
+    repository::{DynPostRepository, postgres},
+};
 
-use super::create::BlogPostBody;
-/// Updates a blog post by its ID in the database.
+/// Updates a blog post by its ID.
 ///
 /// # Arguments
 ///
-/// * `DatabaseConnection(mut conn)`: A wrapper for the database connection.
+/// * `State(posts)`: The blog post repository.
+/// * `State(media)`: The media file repository, to check the featured image.
+/// * `State(collab)`: The edit locks of the posts.
 /// * `Path(id)`: The ID of the blog post to update.
+/// * `headers`: The request headers, carrying the `Edit-Lock` token of the holder of the
+///   post's edit lock, if any.
+/// * `tx`: The transaction of the request, if the application is backed by PostgreSQL;
+///   the post is updated in it, and it is committed once the response is ready.
 /// * `Valid(Json(payload))`: The validated JSON payload containing the updated blog post data.
 ///
 /// # Returns
@@ -18,18 +33,21 @@ use super::create::BlogPostBody;
 /// Returns a `Result` containing:
 /// - `Json(BlogPost)` if the update is successful.
 /// - `AppError::NotFound` if no blog post with the given ID exists.
-/// - `AppError` if an error occurs during the database query.
+/// - `AppError::Locked` if someone else holds the edit lock of the post.
+/// - `AppError` if an error occurs in the repository.
 ///
 /// # Errors
 ///
 /// This function will return an `AppError` if:
-/// - The database query fails.
+/// - The repository fails.
 /// - The specified blog post does not exist.
+/// - The featured image does not exist or is not an image.
+/// - The post is locked for editing and the request lacks the token of the lock.
 ///
 /// # Example
 ///
-/// ```
-/// PUT /posts/1
+/// ```text
+/// PUT /api/v1/posts/1
 /// {
 ///   "title": "Updated Title",
 ///   "content": "Updated content",
@@ -37,32 +55,46 @@ use super::create::BlogPostBody;
 ///   "tags": ["updated", "tags"]
 /// }
 /// ```
+#[utoipa::path(
+    put,
+    path = "/posts/{id}",
+    tag = "posts",
+    description = "Replaces the title, content, category and tags of a blog post, and the other fields that are set.",
+    params(
+        ("id" = i32, Path, description = "ID of the blog post to update"),
+        ("Edit-Lock" = Option<String>, Header, description = "Token of the edit lock of the blog post, required while the lock is held"),
+    ),
+    request_body = BlogPostBody,
+    responses(
+        (status = 200, description = "The updated blog post", body = BlogPost),
+        (status = 400, description = "The request body failed validation", body = String),
+        (status = 404, description = "No blog post has this ID", body = ErrorBody),
+        (status = 413, description = "The request body is too large", body = ErrorBody),
+        (status = 423, description = "Someone else holds the edit lock of the blog post", body = ErrorBody),
+        (status = 422, description = "The request body is not a valid blog post, or its featured image is not an uploaded image", body = String),
+    )
+)]
 pub async fn update_by_id(
-    DatabaseConnection(mut conn): DatabaseConnection,
+    State(posts): State<DynPostRepository>,
+    State(media): State<Media>,
+    State(collab): State<Collaboration>,
     Path(id): Path<i32>,
+    headers: HeaderMap,
+    tx: Option<DatabaseTransaction>,
     Valid(Json(payload)): Valid<Json<BlogPostBody>>,
 ) -> Result<Json<BlogPost>, AppError> {
-    let updated_post = query_as!(
-        BlogPost,
-        r#"
-        UPDATE blog_posts 
-        SET title = $1,
-            content = $2,
-            category = $3,
-            tags = $4
-        WHERE id = $5
-        RETURNING *;
-        "#,
-        payload.title,
-        payload.content,
-        payload.category,
-        &payload.tags,
-        id
-    )
-    .fetch_optional(&mut *conn)
-    .await?;
-
-    updated_post
+    let token = headers
+        .get(EDIT_LOCK_HEADER)
+        .and_then(|value| value.to_str().ok());
+    collab.check_update(id, token).await?;
+    if let Some(image_id) = payload.featured_image_id() {
+        media.check_featured_image(image_id).await?;
+    }
+    let updated = match tx {
+        Some(mut tx) => postgres::update_in(&mut tx, id, &payload).await?,
+        None => posts.update(id, &payload).await?,
+    };
+    updated
         .map(Json)
         .ok_or_else(|| AppError::NotFound("Blog post not found".to_string()))
 }
@@ -0,0 +1,50 @@
+use axum::{
+    Json,
+    extract::{Query, State},
+};
+
+use crate::{
+    error::AppError,
+    handler::list,
+    model::{blog::BlogPost, collection::Collection},
+    repository::{DynPostRepository, PostFilter},
+};
+
+/// Retrieves all blog posts, optionally filtered, wrapped in a [`Collection`].
+///
+/// # Arguments
+///
+/// * `posts`: The blog post repository.
+/// * `filter`: Optional `category` and `tag` filters.
+///
+/// # Returns
+///
+/// Returns a `Result` containing:
+/// - `Json(Collection<BlogPost>)` if the query is successful.
+/// - `AppError` if an error occurs in the repository.
+///
+/// # Errors
+///
+/// This function will return an `AppError` if:
+/// - The repository fails.
+///
+/// # Example
+///
+/// ```text
+/// GET /api/v2/posts
+/// ```
+#[utoipa::path(
+    get,
+    path = "/posts",
+    description = "Returns every blog post, ordered by ID, optionally filtered by category or tag, \
+        in a `data` envelope with a `count`.",
+    params(PostFilter),
+    responses((status = 200, description = "All blog posts", body = Collection<BlogPost>))
+)]
+pub async fn find_all(
+    posts: State<DynPostRepository>,
+    filter: Query<PostFilter>,
+) -> Result<Json<Collection<BlogPost>>, AppError> {
+    let Json(posts) = list::find_all(posts, filter).await?;
+    Ok(Json(posts.into()))
+}
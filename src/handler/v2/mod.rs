@@ -0,0 +1,9 @@
+//! Handlers whose response shape differs in version 2 of the API.
+//!
+//! Routes that behave identically in both versions reuse the version 1 handlers;
+//! only the handlers in this module are specific to `/api/v2`.
+
+/// It have get method for reading all blog posts wrapped in a collection.
+pub mod list;
+/// It have get method for searching blog posts wrapped in a collection.
+pub mod search;
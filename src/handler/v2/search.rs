@@ -0,0 +1,58 @@
+use axum::{
+    Json,
+    extract::{Query, State},
+};
+
+use crate::{
+    error::AppError,
+    handler::search::{self, SearchQuery},
+    model::{blog::BlogPost, collection::Collection},
+    repository::DynPostRepository,
+};
+
+/// Searches for blog posts, wrapped in a [`Collection`].
+///
+/// Unlike version 1, a search without matches is not an error: it returns an
+/// empty collection with `200 OK`.
+///
+/// # Arguments
+///
+/// * `posts`: The blog post repository.
+/// * `query`: The search query containing the term to filter blog posts.
+///
+/// # Returns
+///
+/// Returns a `Result` containing:
+/// - `Json(Collection<BlogPost>)` with the matching blog posts, possibly none.
+/// - `AppError` if an error occurs in the repository.
+///
+/// # Errors
+///
+/// This function will return an `AppError` if:
+/// - The repository fails.
+///
+/// # Example
+///
+/// ```text
+/// GET /api/v2/posts/search?term=rust
+/// ```
+#[utoipa::path(
+    get,
+    path = "/posts/search",
+    description = "Returns blog posts whose title, content or tags contain the search term, \
+        in a `data` envelope. An empty collection is returned when nothing matches.",
+    params(SearchQuery),
+    responses(
+        (status = 200, description = "Blog posts matching the search term", body = Collection<BlogPost>),
+    )
+)]
+pub async fn search_posts(
+    posts: State<DynPostRepository>,
+    query: Query<SearchQuery>,
+) -> Result<Json<Collection<BlogPost>>, AppError> {
+    match search::search_posts(posts, query).await {
+        Ok(Json(posts)) => Ok(Json(posts.into())),
+        Err(AppError::NotFound(_)) => Ok(Json(Vec::new().into())),
+        Err(err) => Err(err),
+    }
+}
@@ -0,0 +1,324 @@
+use crate::{
+    error::{AppError, ErrorBody},
+    model::webhook::{DeliveryFilter, Webhook, WebhookBody, WebhookDelivery},
+    webhook::Webhooks,
+};
+use axum::{
+    Json,
+    extract::{Path, Query, State},
+    http::StatusCode,
+};
+use axum_valid::Valid;
+
+/// Builds the error for a missing webhook.
+fn webhook_not_found() -> AppError {
+    AppError::NotFound("Webhook not found".to_string())
+}
+
+/// Creates a webhook.
+///
+/// # Arguments
+/// * `State(webhooks)`: The webhook repository.
+/// * `Valid(Json(payload))`: The URL, secret and events of the webhook.
+///
+/// From then on, each event it subscribes to is sent to the URL as a `POST` request
+/// with a JSON body, signed with the secret in the `Webhook-Signature` header.
+///
+/// # Returns
+/// Returns `201 Created` with the webhook, without its secret.
+///
+/// # Errors
+/// This function will return an `AppError` if:
+/// - The URL is not an HTTP or HTTPS URL, or the secret is too short (`400`).
+/// - The URL is of a loopback, link-local or private address, or its host does not
+///   resolve, unless private targets are allowed (`400`).
+/// - An event is unknown (`422`).
+/// - The repository fails.
+///
+/// # Example
+/// ```text
+/// POST /api/v1/webhooks
+/// {
+///     "url": "https://example.com/hooks/blog",
+///     "secret": "0123456789abcdef",
+///     "events": ["post.published", "post.deleted"]
+/// }
+/// ```
+#[utoipa::path(
+    post,
+    path = "/webhooks",
+    tag = "webhooks",
+    description = "Subscribes a URL to post lifecycle events.",
+    request_body = WebhookBody,
+    responses(
+        (status = 201, description = "The webhook was created", body = Webhook),
+        (status = 400, description = "The request body failed validation, or the URL is not of a public address", body = String),
+        (status = 422, description = "The request body is not a valid webhook", body = String),
+    )
+)]
+pub async fn create_webhook(
+    State(webhooks): State<Webhooks>,
+    Valid(Json(payload)): Valid<Json<WebhookBody>>,
+) -> Result<(StatusCode, Json<Webhook>), AppError> {
+    webhooks.check_target(&payload.url).await?;
+    let webhook = webhooks.repository.create(&payload).await?;
+    Ok((StatusCode::CREATED, Json(webhook)))
+}
+
+/// Lists the webhooks.
+///
+/// # Arguments
+/// * `State(webhooks)`: The webhook repository.
+///
+/// # Returns
+/// Returns every webhook, without secrets, ordered by ID.
+///
+/// # Errors
+/// This function will return an `AppError` if the repository fails.
+///
+/// # Example
+/// ```text
+/// GET /api/v1/webhooks
+/// ```
+#[utoipa::path(
+    get,
+    path = "/webhooks",
+    tag = "webhooks",
+    description = "Lists the webhooks, ordered by ID.",
+    responses(
+        (status = 200, description = "Every webhook", body = Vec<Webhook>),
+    )
+)]
+pub async fn list_webhooks(
+    State(webhooks): State<Webhooks>,
+) -> Result<Json<Vec<Webhook>>, AppError> {
+    Ok(Json(webhooks.repository.list().await?))
+}
+
+/// Retrieves a webhook by its ID.
+///
+/// # Arguments
+/// * `State(webhooks)`: The webhook repository.
+/// * `Path(id)`: The ID of the webhook.
+///
+/// # Returns
+/// Returns the webhook, without its secret.
+///
+/// # Errors
+/// This function will return an `AppError` if:
+/// - The repository fails.
+/// - The specified webhook does not exist.
+///
+/// # Example
+/// ```text
+/// GET /api/v1/webhooks/1
+/// ```
+#[utoipa::path(
+    get,
+    path = "/webhooks/{id}",
+    tag = "webhooks",
+    description = "Returns a webhook.",
+    params(("id" = i32, Path, description = "ID of the webhook")),
+    responses(
+        (status = 200, description = "The webhook", body = Webhook),
+        (status = 404, description = "No webhook has this ID", body = ErrorBody),
+    )
+)]
+pub async fn get_webhook(
+    State(webhooks): State<Webhooks>,
+    Path(id): Path<i32>,
+) -> Result<Json<Webhook>, AppError> {
+    webhooks
+        .repository
+        .get(id)
+        .await?
+        .map(Json)
+        .ok_or_else(webhook_not_found)
+}
+
+/// Replaces the URL, secret and events of a webhook.
+///
+/// # Arguments
+/// * `State(webhooks)`: The webhook repository.
+/// * `Path(id)`: The ID of the webhook.
+/// * `Valid(Json(payload))`: The new URL, secret and events of the webhook.
+///
+/// Deliveries still pending are sent to the new URL and signed with the new secret.
+///
+/// # Returns
+/// Returns the updated webhook, without its secret.
+///
+/// # Errors
+/// This function will return an `AppError` if:
+/// - The request body fails validation (`400`) or names an unknown event (`422`).
+/// - The URL is of a loopback, link-local or private address, or its host does not
+///   resolve, unless private targets are allowed (`400`).
+/// - The repository fails.
+/// - The specified webhook does not exist.
+///
+/// # Example
+/// ```text
+/// PUT /api/v1/webhooks/1
+/// {
+///     "url": "https://example.com/hooks/blog",
+///     "secret": "fedcba9876543210",
+///     "events": []
+/// }
+/// ```
+#[utoipa::path(
+    put,
+    path = "/webhooks/{id}",
+    tag = "webhooks",
+    description = "Replaces the URL, secret and events of a webhook.",
+    params(("id" = i32, Path, description = "ID of the webhook to update")),
+    request_body = WebhookBody,
+    responses(
+        (status = 200, description = "The updated webhook", body = Webhook),
+        (status = 400, description = "The request body failed validation, or the URL is not of a public address", body = String),
+        (status = 404, description = "No webhook has this ID", body = ErrorBody),
+        (status = 422, description = "The request body is not a valid webhook", body = String),
+    )
+)]
+pub async fn update_webhook(
+    State(webhooks): State<Webhooks>,
+    Path(id): Path<i32>,
+    Valid(Json(payload)): Valid<Json<WebhookBody>>,
+) -> Result<Json<Webhook>, AppError> {
+    webhooks.check_target(&payload.url).await?;
+    webhooks
+        .repository
+        .update(id, &payload)
+        .await?
+        .map(Json)
+        .ok_or_else(webhook_not_found)
+}
+
+/// Deletes a webhook with its delivery log.
+///
+/// # Arguments
+/// * `State(webhooks)`: The webhook repository.
+/// * `Path(id)`: The ID of the webhook.
+///
+/// # Returns
+/// Returns `204 No Content`. Pending deliveries are not sent.
+///
+/// # Errors
+/// This function will return an `AppError` if:
+/// - The repository fails.
+/// - The specified webhook does not exist.
+///
+/// # Example
+/// ```text
+/// DELETE /api/v1/webhooks/1
+/// ```
+#[utoipa::path(
+    delete,
+    path = "/webhooks/{id}",
+    tag = "webhooks",
+    description = "Permanently deletes a webhook and its deliveries.",
+    params(("id" = i32, Path, description = "ID of the webhook to delete")),
+    responses(
+        (status = 204, description = "The webhook was deleted"),
+        (status = 404, description = "No webhook has this ID", body = ErrorBody),
+    )
+)]
+pub async fn delete_webhook(
+    State(webhooks): State<Webhooks>,
+    Path(id): Path<i32>,
+) -> Result<StatusCode, AppError> {
+    if !webhooks.repository.delete(id).await? {
+        return Err(webhook_not_found());
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Lists the deliveries of events to a webhook.
+///
+/// # Arguments
+/// * `State(webhooks)`: The webhook repository.
+/// * `Path(id)`: The ID of the webhook.
+/// * `Query(filter)`: The optional `status` filter.
+///
+/// # Returns
+/// Returns the deliveries with their payloads and the outcome of their last attempt,
+/// newest first.
+///
+/// # Errors
+/// This function will return an `AppError` if:
+/// - The repository fails.
+/// - The specified webhook does not exist.
+///
+/// # Example
+/// ```text
+/// GET /api/v1/webhooks/1/deliveries?status=failed
+/// ```
+#[utoipa::path(
+    get,
+    path = "/webhooks/{id}/deliveries",
+    tag = "webhooks",
+    description = "Lists the deliveries of events to a webhook, newest first.",
+    params(("id" = i32, Path, description = "ID of the webhook"), DeliveryFilter),
+    responses(
+        (status = 200, description = "Deliveries passing the filter", body = Vec<WebhookDelivery>),
+        (status = 400, description = "The query string is invalid", body = String),
+        (status = 404, description = "No webhook has this ID", body = ErrorBody),
+    )
+)]
+pub async fn list_deliveries(
+    State(webhooks): State<Webhooks>,
+    Path(id): Path<i32>,
+    Query(filter): Query<DeliveryFilter>,
+) -> Result<Json<Vec<WebhookDelivery>>, AppError> {
+    if webhooks.repository.get(id).await?.is_none() {
+        return Err(webhook_not_found());
+    }
+    Ok(Json(webhooks.repository.deliveries(id, &filter).await?))
+}
+
+/// Sends the event of a delivery to its webhook again.
+///
+/// # Arguments
+/// * `State(webhooks)`: The webhook repository and the job queue sending deliveries.
+/// * `Path((id, delivery_id))`: The IDs of the webhook and of the delivery to repeat.
+///
+/// The event is recorded as a new delivery with the same payload, which is sent right
+/// away and retried like any other. The original delivery is left as it was.
+///
+/// # Returns
+/// Returns `202 Accepted` with the new delivery.
+///
+/// # Errors
+/// This function will return an `AppError` if:
+/// - The repository fails, or the job sending the delivery cannot be enqueued.
+/// - The specified webhook or delivery does not exist.
+///
+/// # Example
+/// ```text
+/// POST /api/v1/webhooks/1/deliveries/12/redeliver
+/// ```
+#[utoipa::path(
+    post,
+    path = "/webhooks/{id}/deliveries/{delivery_id}/redeliver",
+    tag = "webhooks",
+    description = "Sends the event of a delivery again, as a new delivery.",
+    params(
+        ("id" = i32, Path, description = "ID of the webhook"),
+        ("delivery_id" = i32, Path, description = "ID of the delivery to repeat"),
+    ),
+    responses(
+        (status = 202, description = "The new delivery, to be sent", body = WebhookDelivery),
+        (status = 404, description = "No delivery of this webhook has this ID", body = ErrorBody),
+    )
+)]
+pub async fn redeliver(
+    State(webhooks): State<Webhooks>,
+    Path((id, delivery_id)): Path<(i32, i32)>,
+) -> Result<(StatusCode, Json<WebhookDelivery>), AppError> {
+    let delivery = webhooks
+        .repository
+        .redeliver(id, delivery_id)
+        .await?
+        .ok_or_else(|| AppError::NotFound("Webhook delivery not found".to_string()))?;
+    webhooks.send(delivery.id).await?;
+    Ok((StatusCode::ACCEPTED, Json(delivery)))
+}
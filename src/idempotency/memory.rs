@@ -0,0 +1,111 @@
+use super::{Claim, IdempotencyStore, StoredResponse};
+use crate::error::AppError;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use std::{
+    collections::HashMap,
+    sync::{Mutex, MutexGuard, PoisonError},
+};
+
+/// A key and the request that claimed it.
+#[derive(Debug)]
+struct Entry {
+    /// Fingerprint of the request that claimed the key.
+    fingerprint: String,
+    /// The stored response, once the request has completed.
+    response: Option<StoredResponse>,
+    /// When the key may be claimed again.
+    expires_at: DateTime<Utc>,
+}
+
+/// [`IdempotencyStore`] keeping keys in memory. Keys are lost when the store is dropped.
+#[derive(Debug, Default)]
+pub struct InMemoryIdempotencyStore {
+    /// Entries keyed by scope and key.
+    entries: Mutex<HashMap<(String, String), Entry>>,
+}
+
+impl InMemoryIdempotencyStore {
+    /// Creates an empty store.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Locks the entries, recovering them if a previous holder panicked.
+    fn entries(&self) -> MutexGuard<'_, HashMap<(String, String), Entry>> {
+        self.entries.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+#[async_trait]
+impl IdempotencyStore for InMemoryIdempotencyStore {
+    async fn claim(
+        &self,
+        scope: &str,
+        key: &str,
+        fingerprint: &str,
+        locked_until: DateTime<Utc>,
+    ) -> Result<Claim, AppError> {
+        let mut entries = self.entries();
+        let id = (scope.to_string(), key.to_string());
+        if let Some(entry) = entries
+            .get(&id)
+            .filter(|entry| entry.expires_at > Utc::now())
+        {
+            let fingerprint = entry.fingerprint.clone();
+            return Ok(match &entry.response {
+                Some(response) => Claim::Completed {
+                    fingerprint,
+                    response: response.clone(),
+                },
+                None => Claim::InProgress { fingerprint },
+            });
+        }
+        entries.insert(
+            id,
+            Entry {
+                fingerprint: fingerprint.to_string(),
+                response: None,
+                expires_at: locked_until,
+            },
+        );
+        Ok(Claim::Acquired)
+    }
+
+    async fn complete(
+        &self,
+        scope: &str,
+        key: &str,
+        response: &StoredResponse,
+        expires_at: DateTime<Utc>,
+    ) -> Result<(), AppError> {
+        if let Some(entry) = self
+            .entries()
+            .get_mut(&(scope.to_string(), key.to_string()))
+        {
+            entry.response = Some(response.clone());
+            entry.expires_at = expires_at;
+        }
+        Ok(())
+    }
+
+    async fn release(&self, scope: &str, key: &str) -> Result<(), AppError> {
+        let mut entries = self.entries();
+        let id = (scope.to_string(), key.to_string());
+        if entries
+            .get(&id)
+            .is_some_and(|entry| entry.response.is_none())
+        {
+            entries.remove(&id);
+        }
+        Ok(())
+    }
+
+    async fn purge_expired(&self) -> Result<u64, AppError> {
+        let mut entries = self.entries();
+        let before = entries.len();
+        let now = Utc::now();
+        entries.retain(|_, entry| entry.expires_at > now);
+        Ok(u64::try_from(before - entries.len()).unwrap_or(u64::MAX))
+    }
+}
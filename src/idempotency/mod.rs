@@ -0,0 +1,175 @@
+use crate::{config, error::AppError};
+use anyhow::Result;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use std::{sync::Arc, time::Duration};
+
+/// In-memory storage, for tests and local development without a database.
+pub mod memory;
+
+/// PostgreSQL storage.
+pub mod postgres;
+
+/// SQLite storage.
+pub mod sqlite;
+
+/// Idempotency configuration read from the environment.
+///
+/// | Variable                          | Default   | Description                                                  |
+/// |-----------------------------------|-----------|--------------------------------------------------------------|
+/// | `IDEMPOTENCY_TTL_SECS`            | `86400`   | How long a key and its response are kept for replay.         |
+/// | `IDEMPOTENCY_LOCK_SECS`           | `60`      | How long a key stays locked by a request that never finished.|
+/// | `IDEMPOTENCY_PURGE_INTERVAL_SECS` | `3600`    | Interval between deletions of expired keys.                  |
+/// | `IDEMPOTENCY_MAX_RESPONSE_BYTES`  | `1048576` | Largest response body replayed; larger ones replay no body.  |
+#[derive(Debug, Clone)]
+pub struct IdempotencyConfig {
+    /// How long a completed request's response is replayed.
+    pub ttl: Duration,
+    /// How long a key is held by a request that is still in progress.
+    pub lock_timeout: Duration,
+    /// Interval between purges of expired keys.
+    pub purge_interval: Duration,
+    /// Largest response body stored; larger responses are replayed without their body.
+    pub max_response_bytes: usize,
+}
+
+impl IdempotencyConfig {
+    /// Builds the configuration from environment variables.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if any of the variables holds an invalid value.
+    pub fn from_env() -> Result<Self> {
+        let secs = |key: &str, default: u64| -> Result<Duration> {
+            Ok(Duration::from_secs(config::parse_or(key, default)?))
+        };
+        Ok(Self {
+            ttl: secs("IDEMPOTENCY_TTL_SECS", 24 * 60 * 60)?,
+            lock_timeout: secs("IDEMPOTENCY_LOCK_SECS", 60)?,
+            purge_interval: secs("IDEMPOTENCY_PURGE_INTERVAL_SECS", 60 * 60)?,
+            max_response_bytes: config::parse_or("IDEMPOTENCY_MAX_RESPONSE_BYTES", 1024 * 1024)?,
+        })
+    }
+}
+
+/// A response stored for replay.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StoredResponse {
+    /// HTTP status code.
+    pub status: u16,
+    /// Response headers as `(name, value)` pairs, without `Content-Length`.
+    pub headers: Vec<(String, String)>,
+    /// Response body.
+    pub body: Vec<u8>,
+}
+
+/// Outcome of [`IdempotencyStore::claim`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Claim {
+    /// The key was unused or had expired, and is now held by the caller, who must
+    /// [`complete`](IdempotencyStore::complete) or [`release`](IdempotencyStore::release) it.
+    Acquired,
+    /// Another request holding the key is still in progress.
+    InProgress {
+        /// Fingerprint of the request holding the key.
+        fingerprint: String,
+    },
+    /// A request with the key has completed.
+    Completed {
+        /// Fingerprint of the completed request.
+        fingerprint: String,
+        /// The response to replay.
+        response: StoredResponse,
+    },
+}
+
+/// Storage for idempotency keys and the responses of the requests that used them.
+///
+/// Keys are scoped, so that clients cannot see each other's responses. A key is held
+/// from [`claim`](Self::claim) until it is completed, released or its lock expires,
+/// and a completed key is kept until it expires.
+#[async_trait]
+pub trait IdempotencyStore: Send + Sync {
+    /// Claims a key for a request with the given fingerprint, unless the key is held
+    /// or completed and has not expired.
+    ///
+    /// A newly claimed key expires at `locked_until` unless it is completed first.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `AppError` if the storage fails.
+    async fn claim(
+        &self,
+        scope: &str,
+        key: &str,
+        fingerprint: &str,
+        locked_until: DateTime<Utc>,
+    ) -> Result<Claim, AppError>;
+
+    /// Stores the response of the request holding a key, to be replayed until
+    /// `expires_at`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `AppError` if the storage fails.
+    async fn complete(
+        &self,
+        scope: &str,
+        key: &str,
+        response: &StoredResponse,
+        expires_at: DateTime<Utc>,
+    ) -> Result<(), AppError>;
+
+    /// Releases a key that has not been completed, so that the request can be retried.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `AppError` if the storage fails.
+    async fn release(&self, scope: &str, key: &str) -> Result<(), AppError>;
+
+    /// Deletes expired keys and returns how many were deleted.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `AppError` if the storage fails.
+    async fn purge_expired(&self) -> Result<u64, AppError>;
+}
+
+/// A shared, type-erased [`IdempotencyStore`].
+pub type DynIdempotencyStore = Arc<dyn IdempotencyStore>;
+
+/// Idempotency key storage together with its configuration, as stored in the
+/// application state.
+#[derive(Clone)]
+pub struct Idempotency {
+    /// Where keys and responses are stored.
+    pub store: DynIdempotencyStore,
+    /// Expiry and size limits.
+    pub config: Arc<IdempotencyConfig>,
+}
+
+impl Idempotency {
+    /// Bundles a store with its configuration.
+    pub fn new(store: DynIdempotencyStore, config: IdempotencyConfig) -> Self {
+        Self {
+            store,
+            config: Arc::new(config),
+        }
+    }
+
+    /// Purges expired keys at the configured interval, for as long as the process runs.
+    pub fn spawn_purge(&self) {
+        let idempotency = self.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(idempotency.config.purge_interval);
+            loop {
+                interval.tick().await;
+                match idempotency.store.purge_expired().await {
+                    Ok(0) => {}
+                    Ok(purged) => tracing::debug!(purged, "purged expired idempotency keys"),
+                    Err(err) => tracing::warn!("failed to purge idempotency keys: {err}"),
+                }
+            }
+        });
+    }
+}
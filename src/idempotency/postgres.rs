@@ -0,0 +1,152 @@
+use super::{Claim, IdempotencyStore, StoredResponse};
+use crate::{error::AppError, telemetry::span};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use sqlx::{PgPool, types::Json};
+use tracing::Instrument;
+
+/// Table reported on query spans.
+const TABLE: &str = "idempotency_keys";
+
+/// Database system reported on query spans.
+const SYSTEM: &str = "postgresql";
+
+/// [`IdempotencyStore`] backed by the `idempotency_keys` table.
+#[derive(Debug, Clone)]
+pub struct PgIdempotencyStore {
+    /// Pool of the primary database.
+    pool: PgPool,
+}
+
+impl PgIdempotencyStore {
+    /// Creates a store running its queries on `pool`.
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl IdempotencyStore for PgIdempotencyStore {
+    async fn claim(
+        &self,
+        scope: &str,
+        key: &str,
+        fingerprint: &str,
+        locked_until: DateTime<Utc>,
+    ) -> Result<Claim, AppError> {
+        // A key released between the two queries can be claimed on the next attempt.
+        loop {
+            let claimed = sqlx::query_scalar!(
+                r#"
+                INSERT INTO idempotency_keys (scope, key, fingerprint, expires_at)
+                VALUES ($1, $2, $3, $4)
+                ON CONFLICT (scope, key) DO UPDATE
+                SET fingerprint = EXCLUDED.fingerprint,
+                    status = NULL,
+                    headers = NULL,
+                    body = NULL,
+                    created_at = NOW(),
+                    expires_at = EXCLUDED.expires_at
+                WHERE idempotency_keys.expires_at <= NOW()
+                RETURNING key;
+                "#,
+                scope,
+                key,
+                fingerprint,
+                locked_until
+            )
+            .fetch_optional(&self.pool)
+            .instrument(span::db_query_on(SYSTEM, "INSERT", TABLE))
+            .await?;
+            if claimed.is_some() {
+                return Ok(Claim::Acquired);
+            }
+
+            let existing = sqlx::query!(
+                r#"
+                SELECT
+                    fingerprint,
+                    status,
+                    headers AS "headers: Json<Vec<(String, String)>>",
+                    body
+                FROM idempotency_keys
+                WHERE scope = $1 AND key = $2;
+                "#,
+                scope,
+                key
+            )
+            .fetch_optional(&self.pool)
+            .instrument(span::db_query_on(SYSTEM, "SELECT", TABLE))
+            .await?;
+            let Some(existing) = existing else {
+                continue;
+            };
+
+            let fingerprint = existing.fingerprint;
+            return Ok(match existing.status.and_then(|s| u16::try_from(s).ok()) {
+                Some(status) => Claim::Completed {
+                    fingerprint,
+                    response: StoredResponse {
+                        status,
+                        headers: existing
+                            .headers
+                            .map(|headers| headers.0)
+                            .unwrap_or_default(),
+                        body: existing.body.unwrap_or_default(),
+                    },
+                },
+                None => Claim::InProgress { fingerprint },
+            });
+        }
+    }
+
+    async fn complete(
+        &self,
+        scope: &str,
+        key: &str,
+        response: &StoredResponse,
+        expires_at: DateTime<Utc>,
+    ) -> Result<(), AppError> {
+        let status = i16::try_from(response.status).map_err(|_| AppError::InternalServerError)?;
+        sqlx::query!(
+            r#"
+            UPDATE idempotency_keys
+            SET status = $3,
+                headers = $4,
+                body = $5,
+                expires_at = $6
+            WHERE scope = $1 AND key = $2;
+            "#,
+            scope,
+            key,
+            status,
+            Json(&response.headers) as _,
+            &response.body,
+            expires_at
+        )
+        .execute(&self.pool)
+        .instrument(span::db_query_on(SYSTEM, "UPDATE", TABLE))
+        .await?;
+        Ok(())
+    }
+
+    async fn release(&self, scope: &str, key: &str) -> Result<(), AppError> {
+        sqlx::query!(
+            "DELETE FROM idempotency_keys WHERE scope = $1 AND key = $2 AND status IS NULL",
+            scope,
+            key
+        )
+        .execute(&self.pool)
+        .instrument(span::db_query_on(SYSTEM, "DELETE", TABLE))
+        .await?;
+        Ok(())
+    }
+
+    async fn purge_expired(&self) -> Result<u64, AppError> {
+        let result = sqlx::query!("DELETE FROM idempotency_keys WHERE expires_at <= NOW()")
+            .execute(&self.pool)
+            .instrument(span::db_query_on(SYSTEM, "DELETE", TABLE))
+            .await?;
+        Ok(result.rows_affected())
+    }
+}
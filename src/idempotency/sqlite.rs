@@ -0,0 +1,165 @@
+use super::{Claim, IdempotencyStore, StoredResponse};
+use crate::{error::AppError, telemetry::span};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use sqlx::{FromRow, SqlitePool, types::Json};
+use tracing::Instrument;
+
+/// Table reported on query spans.
+const TABLE: &str = "idempotency_keys";
+
+/// Database system reported on query spans.
+const SYSTEM: &str = "sqlite";
+
+/// An `idempotency_keys` row, without its scope, key and timestamps.
+#[derive(Debug, FromRow)]
+struct KeyRow {
+    /// Fingerprint of the request that claimed the key.
+    fingerprint: String,
+    /// Status of the stored response, or `None` while the request is in progress.
+    status: Option<u16>,
+    /// Headers of the stored response.
+    headers: Option<Json<Vec<(String, String)>>>,
+    /// Body of the stored response.
+    body: Option<Vec<u8>>,
+}
+
+/// [`IdempotencyStore`] backed by a SQLite database migrated from `migrations/sqlite`.
+///
+/// Expiry times are stored as Unix milliseconds.
+#[derive(Debug, Clone)]
+pub struct SqliteIdempotencyStore {
+    /// Pool the queries run on.
+    pool: SqlitePool,
+}
+
+impl SqliteIdempotencyStore {
+    /// Creates a store running its queries on `pool`.
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl IdempotencyStore for SqliteIdempotencyStore {
+    async fn claim(
+        &self,
+        scope: &str,
+        key: &str,
+        fingerprint: &str,
+        locked_until: DateTime<Utc>,
+    ) -> Result<Claim, AppError> {
+        // A key released between the two queries can be claimed on the next attempt.
+        loop {
+            let now = Utc::now().timestamp_millis();
+            let claimed: Option<String> = sqlx::query_scalar(
+                r#"
+                INSERT INTO idempotency_keys (scope, key, fingerprint, expires_at)
+                VALUES (?1, ?2, ?3, ?4)
+                ON CONFLICT (scope, key) DO UPDATE
+                SET fingerprint = excluded.fingerprint,
+                    status = NULL,
+                    headers = NULL,
+                    body = NULL,
+                    created_at = strftime('%Y-%m-%dT%H:%M:%fZ', 'now'),
+                    expires_at = excluded.expires_at
+                WHERE idempotency_keys.expires_at <= ?5
+                RETURNING key;
+                "#,
+            )
+            .bind(scope)
+            .bind(key)
+            .bind(fingerprint)
+            .bind(locked_until.timestamp_millis())
+            .bind(now)
+            .fetch_optional(&self.pool)
+            .instrument(span::db_query_on(SYSTEM, "INSERT", TABLE))
+            .await?;
+            if claimed.is_some() {
+                return Ok(Claim::Acquired);
+            }
+
+            let existing: Option<KeyRow> = sqlx::query_as(
+                r#"
+                SELECT fingerprint, status, headers, body
+                FROM idempotency_keys
+                WHERE scope = ?1 AND key = ?2;
+                "#,
+            )
+            .bind(scope)
+            .bind(key)
+            .fetch_optional(&self.pool)
+            .instrument(span::db_query_on(SYSTEM, "SELECT", TABLE))
+            .await?;
+            let Some(existing) = existing else {
+                continue;
+            };
+
+            let fingerprint = existing.fingerprint;
+            return Ok(match existing.status {
+                Some(status) => Claim::Completed {
+                    fingerprint,
+                    response: StoredResponse {
+                        status,
+                        headers: existing
+                            .headers
+                            .map(|headers| headers.0)
+                            .unwrap_or_default(),
+                        body: existing.body.unwrap_or_default(),
+                    },
+                },
+                None => Claim::InProgress { fingerprint },
+            });
+        }
+    }
+
+    async fn complete(
+        &self,
+        scope: &str,
+        key: &str,
+        response: &StoredResponse,
+        expires_at: DateTime<Utc>,
+    ) -> Result<(), AppError> {
+        sqlx::query(
+            r#"
+            UPDATE idempotency_keys
+            SET status = ?3,
+                headers = ?4,
+                body = ?5,
+                expires_at = ?6
+            WHERE scope = ?1 AND key = ?2;
+            "#,
+        )
+        .bind(scope)
+        .bind(key)
+        .bind(response.status)
+        .bind(Json(&response.headers))
+        .bind(&response.body)
+        .bind(expires_at.timestamp_millis())
+        .execute(&self.pool)
+        .instrument(span::db_query_on(SYSTEM, "UPDATE", TABLE))
+        .await?;
+        Ok(())
+    }
+
+    async fn release(&self, scope: &str, key: &str) -> Result<(), AppError> {
+        sqlx::query(
+            "DELETE FROM idempotency_keys WHERE scope = ?1 AND key = ?2 AND status IS NULL",
+        )
+        .bind(scope)
+        .bind(key)
+        .execute(&self.pool)
+        .instrument(span::db_query_on(SYSTEM, "DELETE", TABLE))
+        .await?;
+        Ok(())
+    }
+
+    async fn purge_expired(&self) -> Result<u64, AppError> {
+        let result = sqlx::query("DELETE FROM idempotency_keys WHERE expires_at <= ?1")
+            .bind(Utc::now().timestamp_millis())
+            .execute(&self.pool)
+            .instrument(span::db_query_on(SYSTEM, "DELETE", TABLE))
+            .await?;
+        Ok(result.rows_affected())
+    }
+}
@@ -0,0 +1,202 @@
+use super::JobRepository;
+use crate::{
+    error::AppError,
+    model::job::{Job, JobFilter, JobOutcome, JobStatus, NewJob},
+};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use std::{
+    collections::BTreeMap,
+    sync::{Mutex, MutexGuard, PoisonError},
+};
+
+/// The jobs of an [`InMemoryJobRepository`].
+#[derive(Debug, Default)]
+struct Store {
+    /// Jobs by ID.
+    jobs: BTreeMap<i64, Job>,
+    /// ID of the most recently enqueued job.
+    last_id: i64,
+}
+
+/// [`JobRepository`] keeping jobs in memory. They are lost when the repository is
+/// dropped.
+#[derive(Debug, Default)]
+pub struct InMemoryJobRepository {
+    /// The stored jobs.
+    inner: Mutex<Store>,
+}
+
+impl InMemoryJobRepository {
+    /// Creates an empty repository.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Locks the store, recovering it if a previous holder panicked.
+    fn store(&self) -> MutexGuard<'_, Store> {
+        self.inner.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// Returns whether a job is running in its `attempt`.
+fn in_attempt(job: &Job, attempt: i32) -> bool {
+    job.status == JobStatus::Running && job.attempts == attempt
+}
+
+#[async_trait]
+impl JobRepository for InMemoryJobRepository {
+    async fn enqueue(&self, job: &NewJob) -> Result<Option<Job>, AppError> {
+        let mut store = self.store();
+        if job.unique_key.is_some()
+            && store
+                .jobs
+                .values()
+                .any(|stored| stored.unique_key == job.unique_key)
+        {
+            return Ok(None);
+        }
+        let id = store
+            .last_id
+            .checked_add(1)
+            .ok_or(AppError::InternalServerError)?;
+        let enqueued = Job {
+            id,
+            queue: job.queue.clone(),
+            kind: job.kind.clone(),
+            payload: job.payload.clone(),
+            status: JobStatus::Queued,
+            attempts: 0,
+            max_attempts: job.max_attempts,
+            run_at: job.run_at,
+            locked_until: None,
+            last_error: None,
+            unique_key: job.unique_key.clone(),
+            created_at: Utc::now(),
+            finished_at: None,
+        };
+        store.last_id = id;
+        store.jobs.insert(id, enqueued.clone());
+        Ok(Some(enqueued))
+    }
+
+    async fn get(&self, id: i64) -> Result<Option<Job>, AppError> {
+        Ok(self.store().jobs.get(&id).cloned())
+    }
+
+    async fn list(&self, filter: &JobFilter) -> Result<Vec<Job>, AppError> {
+        Ok(self
+            .store()
+            .jobs
+            .values()
+            .rev()
+            .filter(|job| {
+                filter
+                    .queue
+                    .as_ref()
+                    .is_none_or(|queue| job.queue == *queue)
+            })
+            .filter(|job| filter.kind.as_ref().is_none_or(|kind| job.kind == *kind))
+            .filter(|job| filter.status.is_none_or(|status| job.status == status))
+            .take(usize::try_from(filter.limit()).unwrap_or(usize::MAX))
+            .cloned()
+            .collect())
+    }
+
+    async fn claim(
+        &self,
+        queue: &str,
+        limit: u32,
+        locked_until: DateTime<Utc>,
+    ) -> Result<Vec<Job>, AppError> {
+        let mut store = self.store();
+        let now = Utc::now();
+        let mut due: Vec<(DateTime<Utc>, i64)> = store
+            .jobs
+            .values()
+            .filter(|job| job.queue == queue)
+            .filter(|job| match job.status {
+                JobStatus::Queued => job.run_at <= now,
+                JobStatus::Running => job.locked_until.is_some_and(|until| until <= now),
+                JobStatus::Succeeded | JobStatus::Dead => false,
+            })
+            .map(|job| (job.run_at, job.id))
+            .collect();
+        due.sort_unstable();
+        due.truncate(usize::try_from(limit).unwrap_or(usize::MAX));
+
+        let mut claimed = Vec::with_capacity(due.len());
+        for (_, id) in due {
+            if let Some(job) = store.jobs.get_mut(&id) {
+                job.status = JobStatus::Running;
+                job.attempts = job.attempts.saturating_add(1);
+                job.locked_until = Some(locked_until);
+                claimed.push(job.clone());
+            }
+        }
+        Ok(claimed)
+    }
+
+    async fn heartbeat(
+        &self,
+        id: i64,
+        attempt: i32,
+        locked_until: DateTime<Utc>,
+    ) -> Result<bool, AppError> {
+        let mut store = self.store();
+        match store.jobs.get_mut(&id) {
+            Some(job) if in_attempt(job, attempt) => {
+                job.locked_until = Some(locked_until);
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    async fn finish(&self, id: i64, attempt: i32, outcome: &JobOutcome) -> Result<bool, AppError> {
+        let mut store = self.store();
+        let Some(job) = store
+            .jobs
+            .get_mut(&id)
+            .filter(|job| in_attempt(job, attempt))
+        else {
+            return Ok(false);
+        };
+        job.status = outcome.status;
+        if let Some(run_at) = outcome.run_at {
+            job.run_at = run_at;
+        }
+        job.locked_until = None;
+        job.last_error = outcome.error.clone();
+        if matches!(outcome.status, JobStatus::Succeeded | JobStatus::Dead) {
+            job.finished_at = Some(Utc::now());
+        }
+        Ok(true)
+    }
+
+    async fn retry(&self, id: i64) -> Result<Option<Job>, AppError> {
+        let mut store = self.store();
+        let Some(job) = store
+            .jobs
+            .get_mut(&id)
+            .filter(|job| matches!(job.status, JobStatus::Queued | JobStatus::Dead))
+        else {
+            return Ok(None);
+        };
+        job.status = JobStatus::Queued;
+        job.attempts = 0;
+        job.run_at = Utc::now();
+        job.finished_at = None;
+        Ok(Some(job.clone()))
+    }
+
+    async fn purge(&self, finished_before: DateTime<Utc>) -> Result<u64, AppError> {
+        let mut store = self.store();
+        let before = store.jobs.len();
+        store.jobs.retain(|_, job| {
+            job.finished_at
+                .is_none_or(|finished| finished >= finished_before)
+        });
+        u64::try_from(before - store.jobs.len()).map_err(|_| AppError::InternalServerError)
+    }
+}
@@ -0,0 +1,722 @@
+use crate::{
+    config,
+    error::AppError,
+    model::job::{Job, JobFilter, JobOutcome, JobStatus, NewJob},
+};
+use anyhow::{Context, Result, bail};
+use async_trait::async_trait;
+use chrono::{DateTime, SecondsFormat, Timelike, Utc};
+use croner::Cron;
+use serde::{Serialize, de::DeserializeOwned};
+use std::{
+    collections::{BTreeMap, HashMap},
+    future::Future,
+    pin::pin,
+    sync::Arc,
+    time::Duration,
+};
+use tokio::sync::{Notify, Semaphore};
+use tracing::Instrument;
+
+/// In-memory storage, for tests and local development without a database.
+pub mod memory;
+
+/// PostgreSQL storage.
+pub mod postgres;
+
+/// SQLite storage.
+pub mod sqlite;
+
+/// Queue of the jobs whose handler does not name one.
+pub const DEFAULT_QUEUE: &str = "default";
+
+/// Queue of the housekeeping jobs, such as [`PurgeJobs`].
+pub const MAINTENANCE_QUEUE: &str = "maintenance";
+
+/// Background job configuration read from the environment.
+///
+/// | Variable                 | Default     | Description                                                          |
+/// |--------------------------|-------------|----------------------------------------------------------------------|
+/// | `JOBS_CONCURRENCY`       | `4`         | Jobs of a queue each server runs at once.                            |
+/// | `JOBS_QUEUE_CONCURRENCY` | unset       | Comma-separated `queue=limit` pairs overriding `JOBS_CONCURRENCY`.   |
+/// | `JOBS_MAX_ATTEMPTS`      | `10`        | Attempts to run a job before it is dead.                             |
+/// | `JOBS_RETRY_BASE_SECS`   | `10`        | Delay before the first retry, doubled after each failure.            |
+/// | `JOBS_RETRY_MAX_SECS`    | `3600`      | Longest delay between two attempts.                                  |
+/// | `JOBS_LEASE_SECS`        | `60`        | How long a running job is reserved for a worker that stops extending it. |
+/// | `JOBS_POLL_INTERVAL_MS`  | `1000`      | Interval between checks for due jobs.                                |
+/// | `JOBS_RETENTION_HOURS`   | `168`       | How long succeeded and dead jobs are kept.                           |
+/// | `JOBS_PURGE_SCHEDULE`    | `0 * * * *` | Cron schedule, in UTC, of the purge of finished jobs.                |
+/// | `JOBS_PUBLISH_SCHEDULE`  | `* * * * *` | Cron schedule, in UTC, of the announcement of scheduled posts.       |
+#[derive(Debug, Clone)]
+pub struct JobsConfig {
+    /// Jobs of a queue each server runs at once, unless overridden for the queue.
+    pub concurrency: u32,
+    /// Jobs each server runs at once, by queue.
+    pub queue_concurrency: HashMap<String, u32>,
+    /// Attempts to run a job before it is dead.
+    pub max_attempts: u32,
+    /// Delay before the first retry.
+    pub retry_base: Duration,
+    /// Longest delay between two attempts.
+    pub retry_max: Duration,
+    /// How long a claimed job is reserved for its worker, which extends the lease while
+    /// the job runs.
+    pub lease: Duration,
+    /// Interval between checks for due jobs.
+    pub poll_interval: Duration,
+    /// How long finished jobs are kept.
+    pub retention: Duration,
+    /// When finished jobs are purged.
+    pub purge_schedule: Cron,
+    /// When posts whose scheduled publication date has passed are announced.
+    pub publish_schedule: Cron,
+}
+
+impl JobsConfig {
+    /// Builds the configuration from environment variables.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if any of the variables holds an invalid value, a concurrency
+    /// limit, the number of attempts or the lease is zero, or a schedule is not a cron
+    /// expression.
+    pub fn from_env() -> Result<Self> {
+        let secs = |key: &str, default: u64| -> Result<Duration> {
+            Ok(Duration::from_secs(config::parse_or(key, default)?))
+        };
+        let queue_concurrency = config::var_opt("JOBS_QUEUE_CONCURRENCY")
+            .map(|pairs| parse_queue_concurrency(&pairs))
+            .transpose()
+            .context("Invalid value for JOBS_QUEUE_CONCURRENCY")?
+            .unwrap_or_default();
+        let purge_schedule = config::var_or("JOBS_PURGE_SCHEDULE", "0 * * * *")
+            .parse()
+            .map_err(|err| anyhow::anyhow!("{err}"))
+            .context("Invalid value for JOBS_PURGE_SCHEDULE")?;
+        let publish_schedule = config::var_or("JOBS_PUBLISH_SCHEDULE", "* * * * *")
+            .parse()
+            .map_err(|err| anyhow::anyhow!("{err}"))
+            .context("Invalid value for JOBS_PUBLISH_SCHEDULE")?;
+        let config = Self {
+            concurrency: config::parse_or("JOBS_CONCURRENCY", 4)?,
+            queue_concurrency,
+            max_attempts: config::parse_or("JOBS_MAX_ATTEMPTS", 10)?,
+            retry_base: secs("JOBS_RETRY_BASE_SECS", 10)?,
+            retry_max: secs("JOBS_RETRY_MAX_SECS", 60 * 60)?,
+            lease: secs("JOBS_LEASE_SECS", 60)?,
+            poll_interval: Duration::from_millis(config::parse_or("JOBS_POLL_INTERVAL_MS", 1000)?),
+            retention: Duration::from_secs(
+                config::parse_or::<u64>("JOBS_RETENTION_HOURS", 7 * 24)?.saturating_mul(60 * 60),
+            ),
+            purge_schedule,
+            publish_schedule,
+        };
+        if config.concurrency == 0 {
+            bail!("Invalid value for JOBS_CONCURRENCY: must be at least 1");
+        }
+        if config.max_attempts == 0 {
+            bail!("Invalid value for JOBS_MAX_ATTEMPTS: must be at least 1");
+        }
+        if config.lease.is_zero() {
+            bail!("Invalid value for JOBS_LEASE_SECS: must be at least 1");
+        }
+        Ok(config)
+    }
+
+    /// Returns how many jobs of a queue each server runs at once.
+    pub fn concurrency_of(&self, queue: &str) -> u32 {
+        self.queue_concurrency
+            .get(queue)
+            .copied()
+            .unwrap_or(self.concurrency)
+    }
+
+    /// Returns how long to wait before running a job again after its `attempts`-th
+    /// failed attempt.
+    pub fn retry_delay(&self, attempts: u32) -> Duration {
+        let factor = 2u32.saturating_pow(attempts.saturating_sub(1));
+        self.retry_base.saturating_mul(factor).min(self.retry_max)
+    }
+}
+
+/// Parses comma-separated `queue=limit` pairs, each limit being at least 1.
+fn parse_queue_concurrency(pairs: &str) -> Result<HashMap<String, u32>> {
+    pairs
+        .split(',')
+        .map(str::trim)
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (queue, limit) = pair
+                .split_once('=')
+                .with_context(|| format!("{pair:?} is not a queue=limit pair"))?;
+            let limit: u32 = limit
+                .trim()
+                .parse()
+                .with_context(|| format!("Invalid limit for queue {queue:?}"))?;
+            if limit == 0 {
+                bail!("The limit of queue {queue:?} must be at least 1");
+            }
+            Ok((queue.trim().to_string(), limit))
+        })
+        .collect()
+}
+
+/// Storage of the jobs run by [`Jobs`].
+#[async_trait]
+pub trait JobRepository: Send + Sync {
+    /// Stores a queued job and returns it with its assigned ID, or `None` if a job with
+    /// the same unique key was enqueued already.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `AppError` if the storage fails.
+    async fn enqueue(&self, job: &NewJob) -> Result<Option<Job>, AppError>;
+
+    /// Returns the job with the given ID, if any.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `AppError` if the storage fails.
+    async fn get(&self, id: i64) -> Result<Option<Job>, AppError>;
+
+    /// Returns the jobs passing the filter, newest first, up to its limit.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `AppError` if the storage fails.
+    async fn list(&self, filter: &JobFilter) -> Result<Vec<Job>, AppError>;
+
+    /// Claims up to `limit` jobs of a queue, oldest due first: queued jobs that are due,
+    /// and running jobs whose lease expired. Each claimed job is counted as a new
+    /// attempt, marked as running and reserved until `locked_until`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `AppError` if the storage fails.
+    async fn claim(
+        &self,
+        queue: &str,
+        limit: u32,
+        locked_until: DateTime<Utc>,
+    ) -> Result<Vec<Job>, AppError>;
+
+    /// Extends the lease of a running job to `locked_until`, returning whether the job
+    /// is still in its `attempt`, that is, was not claimed again meanwhile.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `AppError` if the storage fails.
+    async fn heartbeat(
+        &self,
+        id: i64,
+        attempt: i32,
+        locked_until: DateTime<Utc>,
+    ) -> Result<bool, AppError>;
+
+    /// Records the outcome of an attempt, returning whether the job was still in that
+    /// `attempt`. The outcome of an attempt whose job was claimed again is ignored.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `AppError` if the storage fails.
+    async fn finish(&self, id: i64, attempt: i32, outcome: &JobOutcome) -> Result<bool, AppError>;
+
+    /// Queues a dead or queued job again, due now and with its attempts reset, and
+    /// returns it. Returns `None` if no such job is dead or queued.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `AppError` if the storage fails.
+    async fn retry(&self, id: i64) -> Result<Option<Job>, AppError>;
+
+    /// Deletes the succeeded and dead jobs that finished before `finished_before`, and
+    /// returns how many were deleted.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `AppError` if the storage fails.
+    async fn purge(&self, finished_before: DateTime<Utc>) -> Result<u64, AppError>;
+}
+
+/// A shared, type-erased [`JobRepository`].
+pub type DynJobRepository = Arc<dyn JobRepository>;
+
+/// Runs the jobs of one kind, registered with [`Jobs::with_handler`] at startup.
+///
+/// Jobs are run at least once: a job is run again if its handler fails, or if the
+/// server stops while running it. Handlers should therefore tolerate running a job
+/// twice.
+#[async_trait]
+pub trait JobHandler: Send + Sync + 'static {
+    /// Name of the kind of jobs, stored with each job.
+    const KIND: &'static str;
+
+    /// Queue the jobs run on, sharing its concurrency limit.
+    const QUEUE: &'static str = DEFAULT_QUEUE;
+
+    /// Input of a job, stored as JSON.
+    type Payload: Serialize + DeserializeOwned + Send + Sync + 'static;
+
+    /// Attempts to run a job before it is dead, or `None` for `JOBS_MAX_ATTEMPTS`.
+    fn max_attempts(&self) -> Option<u32> {
+        None
+    }
+
+    /// How long to wait before running a job again after its `attempts`-th failed
+    /// attempt, or `None` for the backoff of `JOBS_RETRY_BASE_SECS`.
+    fn retry_delay(&self, _attempts: u32) -> Option<Duration> {
+        None
+    }
+
+    /// Runs a job.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `AppError` if the job failed and should be attempted again later.
+    async fn run(&self, payload: Self::Payload) -> Result<(), AppError>;
+}
+
+/// Why an attempt to run a job failed.
+#[derive(Debug)]
+enum Failure {
+    /// The job may succeed if attempted again.
+    Retry(String),
+    /// The job cannot succeed, and is dead right away.
+    Discard(String),
+}
+
+/// A [`JobHandler`] taking its payload as JSON, so that handlers of different payload
+/// types can be registered together.
+#[async_trait]
+trait ErasedHandler: Send + Sync {
+    /// Reads the payload of a job and runs it.
+    async fn run(&self, payload: serde_json::Value) -> Result<(), Failure>;
+
+    /// See [`JobHandler::max_attempts`].
+    fn max_attempts(&self) -> Option<u32>;
+
+    /// See [`JobHandler::retry_delay`].
+    fn retry_delay(&self, attempts: u32) -> Option<Duration>;
+}
+
+/// Type-erasing wrapper of a [`JobHandler`].
+struct Typed<H>(H);
+
+#[async_trait]
+impl<H: JobHandler> ErasedHandler for Typed<H> {
+    async fn run(&self, payload: serde_json::Value) -> Result<(), Failure> {
+        let payload = serde_json::from_value(payload)
+            .map_err(|err| Failure::Discard(format!("Invalid payload: {err}")))?;
+        self.0
+            .run(payload)
+            .await
+            .map_err(|err| Failure::Retry(err.to_string()))
+    }
+
+    fn max_attempts(&self) -> Option<u32> {
+        self.0.max_attempts()
+    }
+
+    fn retry_delay(&self, attempts: u32) -> Option<Duration> {
+        self.0.retry_delay(attempts)
+    }
+}
+
+/// A job enqueued on a cron schedule.
+#[derive(Clone)]
+struct Recurring {
+    /// When the job is enqueued.
+    schedule: Cron,
+    /// The job enqueued, without its due time and unique key.
+    job: NewJob,
+}
+
+/// The background job queue: the stored jobs, the handlers running them and the
+/// recurring jobs, as stored in the application state.
+///
+/// Each queue is worked by every server, which runs up to the concurrency limit of the
+/// queue at once. Jobs are claimed with `FOR UPDATE SKIP LOCKED` on PostgreSQL, so that
+/// each due job is run by a single server; see [`JobRepository::claim`].
+#[derive(Clone)]
+pub struct Jobs {
+    /// Where jobs are stored.
+    pub repository: DynJobRepository,
+    /// Concurrency, retries and leases.
+    pub config: Arc<JobsConfig>,
+    /// Handlers by kind of job.
+    handlers: HashMap<&'static str, Arc<dyn ErasedHandler>>,
+    /// Queues with a registered handler, with the signal waking their worker when a job
+    /// is enqueued.
+    queues: BTreeMap<&'static str, Arc<Notify>>,
+    /// Jobs enqueued on a schedule.
+    recurring: Vec<Recurring>,
+}
+
+impl Jobs {
+    /// Creates a job queue storing its jobs in `repository`, without handlers yet.
+    pub fn new(repository: DynJobRepository, config: JobsConfig) -> Self {
+        Self {
+            repository,
+            config: Arc::new(config),
+            handlers: HashMap::new(),
+            queues: BTreeMap::new(),
+            recurring: Vec::new(),
+        }
+    }
+
+    /// Registers the handler of the jobs of kind `H::KIND`, replacing any previous one,
+    /// and makes this server work its queue. Jobs of that kind enqueued through this
+    /// queue, or through clones made after the registration, take their attempts from
+    /// the handler.
+    #[must_use]
+    pub fn with_handler<H: JobHandler>(mut self, handler: H) -> Self {
+        self.handlers.insert(H::KIND, Arc::new(Typed(handler)));
+        self.queues.entry(H::QUEUE).or_default();
+        self
+    }
+
+    /// Enqueues a job of kind `H::KIND` with `payload` at each time of `schedule`, in UTC.
+    ///
+    /// Every server runs the schedule, but each time is enqueued once, under a unique
+    /// key made of the kind and the time. Times missed while no server was running are
+    /// not caught up.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the payload cannot be serialized.
+    pub fn with_recurring<H: JobHandler>(
+        mut self,
+        schedule: Cron,
+        payload: &H::Payload,
+    ) -> Result<Self> {
+        let job = self
+            .new_job::<H>(payload, Utc::now())
+            .map_err(|err| anyhow::anyhow!("{err}"))
+            .with_context(|| format!("Failed to serialize the payload of {}", H::KIND))?;
+        self.recurring.push(Recurring { schedule, job });
+        Ok(self)
+    }
+
+    /// Builds a job of kind `H::KIND`, due at `run_at`, with the attempts of its
+    /// registered handler.
+    fn new_job<H: JobHandler>(
+        &self,
+        payload: &H::Payload,
+        run_at: DateTime<Utc>,
+    ) -> Result<NewJob, AppError> {
+        let payload = serde_json::to_value(payload).map_err(|err| {
+            tracing::error!(kind = H::KIND, "failed to serialize a job payload: {err}");
+            AppError::InternalServerError
+        })?;
+        let max_attempts = self
+            .handlers
+            .get(H::KIND)
+            .and_then(|handler| handler.max_attempts())
+            .unwrap_or(self.config.max_attempts);
+        Ok(NewJob {
+            queue: H::QUEUE.to_string(),
+            kind: H::KIND.to_string(),
+            payload,
+            max_attempts: i32::try_from(max_attempts).unwrap_or(i32::MAX),
+            run_at,
+            unique_key: None,
+        })
+    }
+
+    /// Enqueues a job of kind `H::KIND`, due now.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `AppError` if the payload cannot be serialized or the storage fails.
+    pub async fn enqueue<H: JobHandler>(&self, payload: &H::Payload) -> Result<Job, AppError> {
+        self.enqueue_at::<H>(payload, Utc::now()).await
+    }
+
+    /// Enqueues a job of kind `H::KIND`, due at `run_at`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `AppError` if the payload cannot be serialized or the storage fails.
+    pub async fn enqueue_at<H: JobHandler>(
+        &self,
+        payload: &H::Payload,
+        run_at: DateTime<Utc>,
+    ) -> Result<Job, AppError> {
+        let job = self
+            .repository
+            .enqueue(&self.new_job::<H>(payload, run_at)?)
+            .await?
+            // Only jobs with a unique key may be skipped.
+            .ok_or(AppError::InternalServerError)?;
+        self.wake(&job.queue);
+        Ok(job)
+    }
+
+    /// Enqueues a job of kind `H::KIND`, due now, unless a job was enqueued under the
+    /// same unique key before and not purged yet. Returns the job if it was enqueued.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `AppError` if the payload cannot be serialized or the storage fails.
+    pub async fn enqueue_once<H: JobHandler>(
+        &self,
+        payload: &H::Payload,
+        unique_key: String,
+    ) -> Result<Option<Job>, AppError> {
+        let job = NewJob {
+            unique_key: Some(unique_key),
+            ..self.new_job::<H>(payload, Utc::now())?
+        };
+        let job = self.repository.enqueue(&job).await?;
+        if let Some(job) = &job {
+            self.wake(&job.queue);
+        }
+        Ok(job)
+    }
+
+    /// Wakes the worker of a queue on this server, such as after a job was enqueued or
+    /// retried.
+    pub fn wake(&self, queue: &str) {
+        if let Some(enqueued) = self.queues.get(queue) {
+            enqueued.notify_one();
+        }
+    }
+
+    /// Works every queue with a registered handler and enqueues the recurring jobs, in
+    /// the background for as long as the process runs.
+    pub fn spawn(&self) {
+        for (&queue, enqueued) in &self.queues {
+            let jobs = self.clone();
+            let enqueued = enqueued.clone();
+            tokio::spawn(async move { jobs.work(queue, enqueued).await });
+        }
+        for recurring in &self.recurring {
+            let jobs = self.clone();
+            let recurring = recurring.clone();
+            tokio::spawn(async move { jobs.schedule(recurring).await });
+        }
+    }
+
+    /// Claims and runs the due jobs of a queue, running up to its concurrency limit at
+    /// once, and checks for more at the configured interval and whenever a job is
+    /// enqueued.
+    async fn work(self, queue: &'static str, enqueued: Arc<Notify>) {
+        let limit = usize::try_from(self.config.concurrency_of(queue)).unwrap_or(usize::MAX);
+        let slots = Arc::new(Semaphore::new(limit.min(Semaphore::MAX_PERMITS)));
+        loop {
+            // Wait for a free slot, then claim a job for every free slot.
+            let Ok(slot) = slots.clone().acquire_owned().await else {
+                return;
+            };
+            let mut free = vec![slot];
+            while let Ok(slot) = slots.clone().try_acquire_owned() {
+                free.push(slot);
+            }
+            let claimed = match self.claim(queue, free.len()).await {
+                Ok(claimed) => claimed,
+                Err(err) => {
+                    tracing::warn!(queue, "failed to claim jobs: {err}");
+                    Vec::new()
+                }
+            };
+            let count = claimed.len();
+            for (job, slot) in claimed.into_iter().zip(free) {
+                let jobs = self.clone();
+                let span = tracing::info_span!(
+                    "job",
+                    job_id = job.id,
+                    queue,
+                    kind = %job.kind,
+                    attempt = job.attempts
+                );
+                tokio::spawn(
+                    async move {
+                        jobs.run(job).await;
+                        drop(slot);
+                    }
+                    .instrument(span),
+                );
+            }
+            // More jobs may be due than were claimed.
+            if count > 0 {
+                continue;
+            }
+            tokio::select! {
+                () = enqueued.notified() => {}
+                () = tokio::time::sleep(self.config.poll_interval) => {}
+            }
+        }
+    }
+
+    /// Claims up to `limit` due jobs of a queue, reserving them for the configured lease.
+    async fn claim(&self, queue: &str, limit: usize) -> Result<Vec<Job>, AppError> {
+        let limit = u32::try_from(limit).unwrap_or(u32::MAX);
+        self.repository.claim(queue, limit, self.lease_end()?).await
+    }
+
+    /// Returns when a lease taken now ends.
+    fn lease_end(&self) -> Result<DateTime<Utc>, AppError> {
+        let lease = chrono::Duration::from_std(self.config.lease)
+            .map_err(|_| AppError::InternalServerError)?;
+        Ok(Utc::now() + lease)
+    }
+
+    /// Runs a claimed job and records the outcome.
+    async fn run(&self, job: Job) {
+        let result = if job.attempts > job.max_attempts {
+            // The job was claimed again after its last attempt was abandoned.
+            Err(Failure::Discard(
+                "The last attempt did not finish before its lease expired".to_string(),
+            ))
+        } else {
+            match self.handlers.get(job.kind.as_str()) {
+                Some(handler) => {
+                    let payload = job.payload.clone();
+                    self.heartbeat_while(&job, handler.run(payload)).await
+                }
+                None => Err(Failure::Retry(format!(
+                    "No handler is registered for jobs of kind {:?}",
+                    job.kind
+                ))),
+            }
+        };
+
+        let attempts = u32::try_from(job.attempts).unwrap_or_default();
+        let retry_delay = self
+            .handlers
+            .get(job.kind.as_str())
+            .and_then(|handler| handler.retry_delay(attempts))
+            .unwrap_or_else(|| self.config.retry_delay(attempts));
+        let outcome = match result {
+            Ok(()) => JobOutcome {
+                status: JobStatus::Succeeded,
+                run_at: None,
+                error: None,
+            },
+            Err(Failure::Retry(error)) if job.attempts < job.max_attempts => {
+                tracing::info!("job failed: {error}");
+                let delay =
+                    chrono::Duration::from_std(retry_delay).unwrap_or(chrono::Duration::MAX);
+                JobOutcome {
+                    status: JobStatus::Queued,
+                    run_at: Utc::now().checked_add_signed(delay),
+                    error: Some(error),
+                }
+            }
+            Err(Failure::Retry(error) | Failure::Discard(error)) => {
+                tracing::warn!("job is dead: {error}");
+                JobOutcome {
+                    status: JobStatus::Dead,
+                    run_at: None,
+                    error: Some(error),
+                }
+            }
+        };
+        match self.repository.finish(job.id, job.attempts, &outcome).await {
+            Ok(true) => {}
+            Ok(false) => tracing::warn!("job was claimed again before its attempt finished"),
+            Err(err) => tracing::warn!("failed to record the outcome of a job: {err}"),
+        }
+    }
+
+    /// Awaits `run`, extending the lease of the job every third of it meanwhile.
+    async fn heartbeat_while(
+        &self,
+        job: &Job,
+        run: impl Future<Output = Result<(), Failure>>,
+    ) -> Result<(), Failure> {
+        let mut run = pin!(run);
+        let period = (self.config.lease / 3).max(Duration::from_millis(1));
+        let mut heartbeats = tokio::time::interval_at(tokio::time::Instant::now() + period, period);
+        loop {
+            tokio::select! {
+                result = &mut run => return result,
+                _ = heartbeats.tick() => {
+                    let extended = match self.lease_end() {
+                        Ok(locked_until) => {
+                            self.repository.heartbeat(job.id, job.attempts, locked_until).await
+                        }
+                        Err(err) => Err(err),
+                    };
+                    match extended {
+                        Ok(true) => {}
+                        Ok(false) => tracing::warn!("job was claimed again while running"),
+                        Err(err) => tracing::warn!("failed to extend the lease of a job: {err}"),
+                    }
+                }
+            }
+        }
+    }
+
+    /// Enqueues a recurring job at each time of its schedule, for as long as the
+    /// process runs.
+    async fn schedule(self, recurring: Recurring) {
+        let kind = recurring.job.kind.clone();
+        // Schedules have a precision of a second, which the next times would otherwise
+        // be offset from.
+        let mut after = Utc::now().with_nanosecond(0).unwrap_or_else(Utc::now);
+        loop {
+            let next = match recurring.schedule.find_next_occurrence(&after, false) {
+                Ok(next) => next,
+                Err(err) => {
+                    tracing::warn!(kind, "recurring job has no next run: {err}");
+                    return;
+                }
+            };
+            tokio::time::sleep((next - Utc::now()).to_std().unwrap_or_default()).await;
+            let job = NewJob {
+                run_at: next,
+                unique_key: Some(format!(
+                    "{kind}@{}",
+                    next.to_rfc3339_opts(SecondsFormat::Secs, true)
+                )),
+                ..recurring.job.clone()
+            };
+            match self.repository.enqueue(&job).await {
+                Ok(Some(job)) => self.wake(&job.queue),
+                // Another server enqueued this run.
+                Ok(None) => {}
+                Err(err) => tracing::warn!(kind, "failed to enqueue a recurring job: {err}"),
+            }
+            after = next;
+        }
+    }
+}
+
+/// Deletes the jobs that succeeded or died longer ago than the retention period. It is
+/// run on the schedule of `JOBS_PURGE_SCHEDULE`.
+pub struct PurgeJobs {
+    /// Where jobs are stored.
+    repository: DynJobRepository,
+    /// How long finished jobs are kept.
+    retention: Duration,
+}
+
+impl PurgeJobs {
+    /// Creates the handler purging `repository`.
+    pub fn new(repository: DynJobRepository, retention: Duration) -> Self {
+        Self {
+            repository,
+            retention,
+        }
+    }
+}
+
+#[async_trait]
+impl JobHandler for PurgeJobs {
+    const KIND: &'static str = "jobs.purge";
+    const QUEUE: &'static str = MAINTENANCE_QUEUE;
+    type Payload = ();
+
+    async fn run(&self, (): ()) -> Result<(), AppError> {
+        let retention = chrono::Duration::from_std(self.retention).unwrap_or(chrono::Duration::MAX);
+        let finished_before = Utc::now()
+            .checked_sub_signed(retention)
+            .unwrap_or(DateTime::<Utc>::MIN_UTC);
+        let purged = self.repository.purge(finished_before).await?;
+        if purged > 0 {
+            tracing::info!(purged, "purged finished jobs");
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,265 @@
+use super::JobRepository;
+use crate::{
+    error::AppError,
+    model::job::{Job, JobFilter, JobOutcome, JobStatus, NewJob},
+    telemetry::span,
+};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use tracing::Instrument;
+
+/// Table of jobs, reported on query spans.
+const TABLE: &str = "jobs";
+
+/// Database system reported on query spans.
+const SYSTEM: &str = "postgresql";
+
+/// A row of the `jobs` table.
+struct JobRow {
+    /// Unique identifier of the job.
+    id: i64,
+    /// Queue whose workers run the job.
+    queue: String,
+    /// Kind of the job.
+    kind: String,
+    /// Input of the handler.
+    payload: serde_json::Value,
+    /// Status of the job, as stored.
+    status: String,
+    /// Attempts started.
+    attempts: i32,
+    /// Attempts allowed.
+    max_attempts: i32,
+    /// When the job is due.
+    run_at: DateTime<Utc>,
+    /// When the lease of a running job ends.
+    locked_until: Option<DateTime<Utc>>,
+    /// Why the last failed attempt failed.
+    last_error: Option<String>,
+    /// Key the job was enqueued under.
+    unique_key: Option<String>,
+    /// When the job was enqueued.
+    created_at: DateTime<Utc>,
+    /// When the job succeeded or died.
+    finished_at: Option<DateTime<Utc>>,
+}
+
+impl TryFrom<JobRow> for Job {
+    type Error = AppError;
+
+    fn try_from(row: JobRow) -> Result<Self, Self::Error> {
+        let status = JobStatus::try_from(row.status).map_err(|err| {
+            tracing::error!(job_id = row.id, "{err}");
+            AppError::InternalServerError
+        })?;
+        Ok(Self {
+            id: row.id,
+            queue: row.queue,
+            kind: row.kind,
+            payload: row.payload,
+            status,
+            attempts: row.attempts,
+            max_attempts: row.max_attempts,
+            run_at: row.run_at,
+            locked_until: row.locked_until,
+            last_error: row.last_error,
+            unique_key: row.unique_key,
+            created_at: row.created_at,
+            finished_at: row.finished_at,
+        })
+    }
+}
+
+/// [`JobRepository`] backed by the `jobs` table.
+///
+/// Jobs are claimed with `FOR UPDATE SKIP LOCKED`, so that the workers of several
+/// servers can share a queue without claiming the same job.
+#[derive(Debug, Clone)]
+pub struct PgJobRepository {
+    /// Pool of the primary database.
+    pool: PgPool,
+}
+
+impl PgJobRepository {
+    /// Creates a repository running its queries on `pool`.
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl JobRepository for PgJobRepository {
+    async fn enqueue(&self, job: &NewJob) -> Result<Option<Job>, AppError> {
+        sqlx::query_as!(
+            JobRow,
+            r#"
+            INSERT INTO jobs (queue, kind, payload, max_attempts, run_at, unique_key)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            ON CONFLICT (unique_key) DO NOTHING
+            RETURNING *;
+            "#,
+            job.queue,
+            job.kind,
+            job.payload,
+            job.max_attempts,
+            job.run_at,
+            job.unique_key
+        )
+        .fetch_optional(&self.pool)
+        .instrument(span::db_query_on(SYSTEM, "INSERT", TABLE))
+        .await?
+        .map(Job::try_from)
+        .transpose()
+    }
+
+    async fn get(&self, id: i64) -> Result<Option<Job>, AppError> {
+        sqlx::query_as!(JobRow, "SELECT * FROM jobs WHERE id = $1;", id)
+            .fetch_optional(&self.pool)
+            .instrument(span::db_query_on(SYSTEM, "SELECT", TABLE))
+            .await?
+            .map(Job::try_from)
+            .transpose()
+    }
+
+    async fn list(&self, filter: &JobFilter) -> Result<Vec<Job>, AppError> {
+        sqlx::query_as!(
+            JobRow,
+            r#"
+            SELECT * FROM jobs
+            WHERE ($1::TEXT IS NULL OR queue = $1)
+              AND ($2::TEXT IS NULL OR kind = $2)
+              AND ($3::TEXT IS NULL OR status = $3)
+            ORDER BY id DESC
+            LIMIT $4;
+            "#,
+            filter.queue,
+            filter.kind,
+            filter.status.map(JobStatus::as_str),
+            i64::from(filter.limit())
+        )
+        .fetch_all(&self.pool)
+        .instrument(span::db_query_on(SYSTEM, "SELECT", TABLE))
+        .await?
+        .into_iter()
+        .map(Job::try_from)
+        .collect()
+    }
+
+    async fn claim(
+        &self,
+        queue: &str,
+        limit: u32,
+        locked_until: DateTime<Utc>,
+    ) -> Result<Vec<Job>, AppError> {
+        let rows = sqlx::query_as!(
+            JobRow,
+            r#"
+            WITH due AS (
+                SELECT id FROM jobs
+                WHERE queue = $1
+                  AND (
+                      (status = 'queued' AND run_at <= NOW())
+                      OR (status = 'running' AND locked_until <= NOW())
+                  )
+                ORDER BY run_at, id
+                LIMIT $2
+                FOR UPDATE SKIP LOCKED
+            )
+            UPDATE jobs
+            SET status = 'running', attempts = jobs.attempts + 1, locked_until = $3
+            FROM due
+            WHERE jobs.id = due.id
+            RETURNING jobs.*;
+            "#,
+            queue,
+            i64::from(limit),
+            locked_until
+        )
+        .fetch_all(&self.pool)
+        .instrument(span::db_query_on(SYSTEM, "UPDATE", TABLE))
+        .await?;
+        let mut claimed = rows
+            .into_iter()
+            .map(Job::try_from)
+            .collect::<Result<Vec<_>, _>>()?;
+        claimed.sort_unstable_by_key(|job| (job.run_at, job.id));
+        Ok(claimed)
+    }
+
+    async fn heartbeat(
+        &self,
+        id: i64,
+        attempt: i32,
+        locked_until: DateTime<Utc>,
+    ) -> Result<bool, AppError> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE jobs SET locked_until = $3
+            WHERE id = $1 AND attempts = $2 AND status = 'running';
+            "#,
+            id,
+            attempt,
+            locked_until
+        )
+        .execute(&self.pool)
+        .instrument(span::db_query_on(SYSTEM, "UPDATE", TABLE))
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn finish(&self, id: i64, attempt: i32, outcome: &JobOutcome) -> Result<bool, AppError> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE jobs
+            SET status = $3,
+                run_at = COALESCE($4, run_at),
+                locked_until = NULL,
+                last_error = $5,
+                finished_at = CASE WHEN $3 IN ('succeeded', 'dead') THEN NOW() END
+            WHERE id = $1 AND attempts = $2 AND status = 'running';
+            "#,
+            id,
+            attempt,
+            outcome.status.as_str(),
+            outcome.run_at,
+            outcome.error
+        )
+        .execute(&self.pool)
+        .instrument(span::db_query_on(SYSTEM, "UPDATE", TABLE))
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn retry(&self, id: i64) -> Result<Option<Job>, AppError> {
+        sqlx::query_as!(
+            JobRow,
+            r#"
+            UPDATE jobs
+            SET status = 'queued', attempts = 0, run_at = NOW(), finished_at = NULL
+            WHERE id = $1 AND status IN ('queued', 'dead')
+            RETURNING *;
+            "#,
+            id
+        )
+        .fetch_optional(&self.pool)
+        .instrument(span::db_query_on(SYSTEM, "UPDATE", TABLE))
+        .await?
+        .map(Job::try_from)
+        .transpose()
+    }
+
+    async fn purge(&self, finished_before: DateTime<Utc>) -> Result<u64, AppError> {
+        let result = sqlx::query!(
+            r#"
+            DELETE FROM jobs
+            WHERE status IN ('succeeded', 'dead') AND finished_at < $1;
+            "#,
+            finished_before
+        )
+        .execute(&self.pool)
+        .instrument(span::db_query_on(SYSTEM, "DELETE", TABLE))
+        .await?;
+        Ok(result.rows_affected())
+    }
+}
@@ -0,0 +1,271 @@
+use super::JobRepository;
+use crate::{
+    error::AppError,
+    model::job::{Job, JobFilter, JobOutcome, JobStatus, NewJob},
+    telemetry::span,
+};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use sqlx::{FromRow, SqlitePool, types::Json};
+use tracing::Instrument;
+
+/// Table of jobs, reported on query spans.
+const TABLE: &str = "jobs";
+
+/// Database system reported on query spans.
+const SYSTEM: &str = "sqlite";
+
+/// Converts Unix milliseconds, as `run_at` and `locked_until` are stored, to a time.
+fn from_millis(id: i64, millis: i64) -> Result<DateTime<Utc>, AppError> {
+    DateTime::from_timestamp_millis(millis).ok_or_else(|| {
+        tracing::error!(job_id = id, "invalid job time {millis}");
+        AppError::InternalServerError
+    })
+}
+
+/// A row of the `jobs` table.
+#[derive(Debug, FromRow)]
+struct JobRow {
+    /// Unique identifier of the job.
+    id: i64,
+    /// Queue whose workers run the job.
+    queue: String,
+    /// Kind of the job.
+    kind: String,
+    /// Input of the handler.
+    payload: Json<serde_json::Value>,
+    /// Status of the job, as stored.
+    status: String,
+    /// Attempts started.
+    attempts: i32,
+    /// Attempts allowed.
+    max_attempts: i32,
+    /// When the job is due, in Unix milliseconds.
+    run_at: i64,
+    /// When the lease of a running job ends, in Unix milliseconds.
+    locked_until: Option<i64>,
+    /// Why the last failed attempt failed.
+    last_error: Option<String>,
+    /// Key the job was enqueued under.
+    unique_key: Option<String>,
+    /// When the job was enqueued.
+    created_at: DateTime<Utc>,
+    /// When the job succeeded or died.
+    finished_at: Option<DateTime<Utc>>,
+}
+
+impl TryFrom<JobRow> for Job {
+    type Error = AppError;
+
+    fn try_from(row: JobRow) -> Result<Self, Self::Error> {
+        let status = JobStatus::try_from(row.status).map_err(|err| {
+            tracing::error!(job_id = row.id, "{err}");
+            AppError::InternalServerError
+        })?;
+        Ok(Self {
+            id: row.id,
+            queue: row.queue,
+            kind: row.kind,
+            payload: row.payload.0,
+            status,
+            attempts: row.attempts,
+            max_attempts: row.max_attempts,
+            run_at: from_millis(row.id, row.run_at)?,
+            locked_until: row
+                .locked_until
+                .map(|millis| from_millis(row.id, millis))
+                .transpose()?,
+            last_error: row.last_error,
+            unique_key: row.unique_key,
+            created_at: row.created_at,
+            finished_at: row.finished_at,
+        })
+    }
+}
+
+/// [`JobRepository`] backed by a SQLite database migrated from `migrations/sqlite`.
+///
+/// The times jobs are due and leases end are stored as Unix milliseconds. SQLite runs
+/// one write at a time, so a job is never claimed twice.
+#[derive(Debug, Clone)]
+pub struct SqliteJobRepository {
+    /// Pool the queries run on.
+    pool: SqlitePool,
+}
+
+impl SqliteJobRepository {
+    /// Creates a repository running its queries on `pool`.
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl JobRepository for SqliteJobRepository {
+    async fn enqueue(&self, job: &NewJob) -> Result<Option<Job>, AppError> {
+        let row: Option<JobRow> = sqlx::query_as(
+            r#"
+            INSERT INTO jobs (queue, kind, payload, max_attempts, run_at, unique_key)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+            ON CONFLICT (unique_key) DO NOTHING
+            RETURNING *;
+            "#,
+        )
+        .bind(&job.queue)
+        .bind(&job.kind)
+        .bind(Json(&job.payload))
+        .bind(job.max_attempts)
+        .bind(job.run_at.timestamp_millis())
+        .bind(&job.unique_key)
+        .fetch_optional(&self.pool)
+        .instrument(span::db_query_on(SYSTEM, "INSERT", TABLE))
+        .await?;
+        row.map(Job::try_from).transpose()
+    }
+
+    async fn get(&self, id: i64) -> Result<Option<Job>, AppError> {
+        let row: Option<JobRow> = sqlx::query_as("SELECT * FROM jobs WHERE id = ?1;")
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .instrument(span::db_query_on(SYSTEM, "SELECT", TABLE))
+            .await?;
+        row.map(Job::try_from).transpose()
+    }
+
+    async fn list(&self, filter: &JobFilter) -> Result<Vec<Job>, AppError> {
+        let rows: Vec<JobRow> = sqlx::query_as(
+            r#"
+            SELECT * FROM jobs
+            WHERE (?1 IS NULL OR queue = ?1)
+              AND (?2 IS NULL OR kind = ?2)
+              AND (?3 IS NULL OR status = ?3)
+            ORDER BY id DESC
+            LIMIT ?4;
+            "#,
+        )
+        .bind(&filter.queue)
+        .bind(&filter.kind)
+        .bind(filter.status.map(JobStatus::as_str))
+        .bind(filter.limit())
+        .fetch_all(&self.pool)
+        .instrument(span::db_query_on(SYSTEM, "SELECT", TABLE))
+        .await?;
+        rows.into_iter().map(Job::try_from).collect()
+    }
+
+    async fn claim(
+        &self,
+        queue: &str,
+        limit: u32,
+        locked_until: DateTime<Utc>,
+    ) -> Result<Vec<Job>, AppError> {
+        let rows: Vec<JobRow> = sqlx::query_as(
+            r#"
+            UPDATE jobs
+            SET status = 'running', attempts = attempts + 1, locked_until = ?4
+            WHERE id IN (
+                SELECT id FROM jobs
+                WHERE queue = ?1
+                  AND (
+                      (status = 'queued' AND run_at <= ?2)
+                      OR (status = 'running' AND locked_until <= ?2)
+                  )
+                ORDER BY run_at, id
+                LIMIT ?3
+            )
+            RETURNING *;
+            "#,
+        )
+        .bind(queue)
+        .bind(Utc::now().timestamp_millis())
+        .bind(limit)
+        .bind(locked_until.timestamp_millis())
+        .fetch_all(&self.pool)
+        .instrument(span::db_query_on(SYSTEM, "UPDATE", TABLE))
+        .await?;
+        let mut claimed = rows
+            .into_iter()
+            .map(Job::try_from)
+            .collect::<Result<Vec<_>, _>>()?;
+        claimed.sort_unstable_by_key(|job| (job.run_at, job.id));
+        Ok(claimed)
+    }
+
+    async fn heartbeat(
+        &self,
+        id: i64,
+        attempt: i32,
+        locked_until: DateTime<Utc>,
+    ) -> Result<bool, AppError> {
+        let result = sqlx::query(
+            r#"
+            UPDATE jobs SET locked_until = ?3
+            WHERE id = ?1 AND attempts = ?2 AND status = 'running';
+            "#,
+        )
+        .bind(id)
+        .bind(attempt)
+        .bind(locked_until.timestamp_millis())
+        .execute(&self.pool)
+        .instrument(span::db_query_on(SYSTEM, "UPDATE", TABLE))
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn finish(&self, id: i64, attempt: i32, outcome: &JobOutcome) -> Result<bool, AppError> {
+        let result = sqlx::query(
+            r#"
+            UPDATE jobs
+            SET status = ?3,
+                run_at = COALESCE(?4, run_at),
+                locked_until = NULL,
+                last_error = ?5,
+                finished_at = CASE
+                    WHEN ?3 IN ('succeeded', 'dead') THEN strftime('%Y-%m-%dT%H:%M:%fZ', 'now')
+                END
+            WHERE id = ?1 AND attempts = ?2 AND status = 'running';
+            "#,
+        )
+        .bind(id)
+        .bind(attempt)
+        .bind(outcome.status.as_str())
+        .bind(outcome.run_at.map(|time| time.timestamp_millis()))
+        .bind(&outcome.error)
+        .execute(&self.pool)
+        .instrument(span::db_query_on(SYSTEM, "UPDATE", TABLE))
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn retry(&self, id: i64) -> Result<Option<Job>, AppError> {
+        let row: Option<JobRow> = sqlx::query_as(
+            r#"
+            UPDATE jobs
+            SET status = 'queued', attempts = 0, run_at = ?2, finished_at = NULL
+            WHERE id = ?1 AND status IN ('queued', 'dead')
+            RETURNING *;
+            "#,
+        )
+        .bind(id)
+        .bind(Utc::now().timestamp_millis())
+        .fetch_optional(&self.pool)
+        .instrument(span::db_query_on(SYSTEM, "UPDATE", TABLE))
+        .await?;
+        row.map(Job::try_from).transpose()
+    }
+
+    async fn purge(&self, finished_before: DateTime<Utc>) -> Result<u64, AppError> {
+        let result = sqlx::query(
+            r#"
+            DELETE FROM jobs
+            WHERE status IN ('succeeded', 'dead')
+              AND finished_at < strftime('%Y-%m-%dT%H:%M:%fZ', ?1);
+            "#,
+        )
+        .bind(finished_before)
+        .execute(&self.pool)
+        .instrument(span::db_query_on(SYSTEM, "DELETE", TABLE))
+        .await?;
+        Ok(result.rows_affected())
+    }
+}
@@ -24,15 +24,41 @@
 #![deny(clippy::cast_precision_loss)] // Prevents loss of precision during casts.
 #![deny(clippy::cast_sign_loss)] // Disallows sign loss during casting.
 
+/// Module for coordinating the people viewing and editing the same blog post.
+pub mod collab;
+/// Module for reading configuration from the environment.
+pub mod config;
+/// Module for converting posts to and from content files such as Markdown.
+pub mod content;
 /// Module for handling database operations.
 pub mod database;
 /// Module for handling errors within the application.
 pub mod error;
+/// Module for pushing changes to blog posts to live subscribers.
+pub mod events;
 /// Module for defining routes and request handlers.
 pub mod handler;
+/// Module for storing idempotency keys and the responses they replay.
+pub mod idempotency;
+/// Module for running background jobs on durable queues.
+pub mod jobs;
+/// Module for storing uploaded media files and attaching them to posts.
+pub mod media;
 /// Module for defining application models.
 pub mod model;
+/// Module for generating the OpenAPI specification.
+pub mod openapi;
+/// Module for recording domain events in an outbox and dispatching them to consumers.
+pub mod outbox;
+/// Module for storing and querying blog posts.
+pub mod repository;
 /// Module for handling server logic.
 pub mod server;
+/// Module for rendering published posts as a static website.
+pub mod site;
 /// Module for maintaining application state.
 pub mod state;
+/// Module for logging, tracing and request correlation.
+pub mod telemetry;
+/// Module for notifying webhooks of changes to blog posts.
+pub mod webhook;
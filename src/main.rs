@@ -1,12 +1,10 @@
 use anyhow::Context;
-use blog_api::server;
+use blog_api::{server, telemetry};
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
     dotenv::dotenv().context("Failed to load .env file")?;
-    tracing_subscriber::fmt()
-        .with_max_level(tracing::Level::DEBUG)
-        .init();
+    let _telemetry = telemetry::init_tracing(&telemetry::TelemetryConfig::from_env()?)?;
 
     server::setup_server().await
 }
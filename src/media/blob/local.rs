@@ -0,0 +1,100 @@
+use super::{BlobStore, BlobStream, storage_error};
+use crate::error::AppError;
+use async_trait::async_trait;
+use axum::body::Bytes;
+use futures_util::StreamExt;
+use std::{
+    io::{self, SeekFrom},
+    ops::Range,
+    path::PathBuf,
+    process,
+    sync::atomic::{AtomicU64, Ordering},
+};
+use tokio::{
+    fs::{self, File},
+    io::{AsyncReadExt, AsyncSeekExt},
+};
+use tokio_util::io::ReaderStream;
+
+/// Counter making the names of temporary files unique within the process.
+static TEMP_FILES: AtomicU64 = AtomicU64::new(0);
+
+/// [`BlobStore`] keeping each blob in a file under a root directory.
+///
+/// A blob with key `9f86d0…` is stored as `<root>/9f/86/9f86d0…`, so that no
+/// directory grows too large. Blobs are written to a temporary file first and then
+/// renamed, so readers never see a partial blob.
+#[derive(Debug, Clone)]
+pub struct LocalBlobStore {
+    /// Directory the blobs are stored under.
+    root: PathBuf,
+}
+
+impl LocalBlobStore {
+    /// Creates a store keeping its blobs under `root`, which is created when the first
+    /// blob is stored.
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    /// Returns the path of the blob stored under `key`.
+    fn path(&self, key: &str) -> Result<PathBuf, AppError> {
+        if key.len() < 4 || !key.bytes().all(|byte| byte.is_ascii_hexdigit()) {
+            return Err(storage_error(format!("invalid blob key {key:?}")));
+        }
+        Ok(self.root.join(&key[..2]).join(&key[2..4]).join(key))
+    }
+}
+
+#[async_trait]
+impl BlobStore for LocalBlobStore {
+    async fn put(&self, key: &str, data: Bytes) -> Result<(), AppError> {
+        let path = self.path(key)?;
+        if fs::try_exists(&path).await.map_err(storage_error)? {
+            return Ok(());
+        }
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).await.map_err(storage_error)?;
+        }
+        let temp = path.with_extension(format!(
+            "{}.{}.tmp",
+            process::id(),
+            TEMP_FILES.fetch_add(1, Ordering::Relaxed)
+        ));
+        if let Err(err) = fs::write(&temp, &data).await {
+            let _ = fs::remove_file(&temp).await;
+            return Err(storage_error(err));
+        }
+        fs::rename(&temp, &path).await.map_err(storage_error)
+    }
+
+    async fn get(&self, key: &str, range: Range<u64>) -> Result<Option<BlobStream>, AppError> {
+        let mut file = match File::open(self.path(key)?).await {
+            Ok(file) => file,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(storage_error(err)),
+        };
+        file.seek(SeekFrom::Start(range.start))
+            .await
+            .map_err(storage_error)?;
+        let length = range.end.saturating_sub(range.start);
+        Ok(Some(ReaderStream::new(file.take(length)).boxed()))
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), AppError> {
+        let path = self.path(key)?;
+        match fs::remove_file(&path).await {
+            Ok(()) => {}
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(()),
+            Err(err) => return Err(storage_error(err)),
+        }
+        // Remove the two levels of directories if this was their last blob; removing a
+        // directory that still holds files fails, which ends the cleanup.
+        for dir in path.ancestors().skip(1).take(2) {
+            if fs::remove_dir(dir).await.is_err() {
+                break;
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,56 @@
+use super::{BlobStore, BlobStream};
+use crate::error::AppError;
+use async_trait::async_trait;
+use axum::body::Bytes;
+use futures_util::{StreamExt, stream};
+use std::{
+    collections::HashMap,
+    ops::Range,
+    sync::{Mutex, MutexGuard, PoisonError},
+};
+
+/// [`BlobStore`] keeping blobs in memory. Blobs are lost when the store is dropped.
+#[derive(Debug, Default)]
+pub struct InMemoryBlobStore {
+    /// Blobs by key.
+    blobs: Mutex<HashMap<String, Bytes>>,
+}
+
+impl InMemoryBlobStore {
+    /// Creates an empty store.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Locks the blobs, recovering them if a previous holder panicked.
+    fn blobs(&self) -> MutexGuard<'_, HashMap<String, Bytes>> {
+        self.blobs.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+#[async_trait]
+impl BlobStore for InMemoryBlobStore {
+    async fn put(&self, key: &str, data: Bytes) -> Result<(), AppError> {
+        self.blobs().entry(key.to_string()).or_insert(data);
+        Ok(())
+    }
+
+    async fn get(&self, key: &str, range: Range<u64>) -> Result<Option<BlobStream>, AppError> {
+        let Some(blob) = self.blobs().get(key).cloned() else {
+            return Ok(None);
+        };
+        let start = usize::try_from(range.start).map_err(super::storage_error)?;
+        let end = usize::try_from(range.end).map_err(super::storage_error)?;
+        if start > end || end > blob.len() {
+            return Err(super::storage_error("range outside of the blob"));
+        }
+        Ok(Some(
+            stream::once(async move { Ok(blob.slice(start..end)) }).boxed(),
+        ))
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), AppError> {
+        self.blobs().remove(key);
+        Ok(())
+    }
+}
@@ -0,0 +1,75 @@
+use crate::error::AppError;
+use async_trait::async_trait;
+use axum::body::Bytes;
+use futures_util::stream::BoxStream;
+use std::{io, ops::Range, sync::Arc};
+
+/// Storage on the local file system.
+pub mod local;
+
+/// In-memory storage, for tests.
+pub mod memory;
+
+/// Storage in a bucket of an S3-compatible service.
+pub mod s3;
+
+/// Signing of S3 requests with AWS Signature Version 4.
+pub mod sigv4;
+
+/// A stream of the bytes of a stored blob.
+pub type BlobStream = BoxStream<'static, io::Result<Bytes>>;
+
+/// Storage for the content of media files, addressed by key.
+///
+/// Keys are the hex-encoded SHA-256 hashes of the content, so a key always refers to the
+/// same bytes: storing content under a key that is already used is a no-op.
+#[async_trait]
+pub trait BlobStore: Send + Sync {
+    /// Stores `data` under `key`, unless a blob is stored under it already.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `AppError` if the storage fails.
+    async fn put(&self, key: &str, data: Bytes) -> Result<(), AppError>;
+
+    /// Streams the bytes of the blob stored under `key` within `range`, which must lie
+    /// within the blob, or returns `None` if no blob is stored under it.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `AppError` if the storage fails.
+    async fn get(&self, key: &str, range: Range<u64>) -> Result<Option<BlobStream>, AppError>;
+
+    /// Deletes the blob stored under `key`, if any.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `AppError` if the storage fails.
+    async fn delete(&self, key: &str) -> Result<(), AppError>;
+
+    /// Returns a temporary URL that clients can download the blob stored under `key` from
+    /// without going through the API, if the store offers one. Responses to the URL
+    /// carry the given `Content-Type` and `Content-Disposition` headers.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `AppError` if the URL cannot be built.
+    fn download_url(
+        &self,
+        key: &str,
+        content_type: &str,
+        content_disposition: &str,
+    ) -> Result<Option<String>, AppError> {
+        let _ = (key, content_type, content_disposition);
+        Ok(None)
+    }
+}
+
+/// A shared, type-erased [`BlobStore`].
+pub type DynBlobStore = Arc<dyn BlobStore>;
+
+/// Logs a storage failure and converts it into an `AppError`.
+pub(crate) fn storage_error(err: impl std::fmt::Display) -> AppError {
+    tracing::error!("blob storage failed: {err}");
+    AppError::InternalServerError
+}
@@ -0,0 +1,454 @@
+use super::{
+    BlobStore, BlobStream,
+    sigv4::{self, Signer},
+    storage_error,
+};
+use crate::{config, error::AppError};
+use anyhow::{Context, Result, bail};
+use async_trait::async_trait;
+use axum::{
+    body::Bytes,
+    http::{Method, StatusCode, header},
+};
+use chrono::Utc;
+use futures_util::{StreamExt, TryStreamExt, stream};
+use quick_xml::escape::unescape;
+use reqwest::{Client, Response, Url};
+use std::{fmt, io, ops::Range, time::Duration};
+
+/// Smallest part of a multipart upload that S3 accepts, except for the last part.
+const MIN_PART_SIZE: usize = 5 * 1024 * 1024;
+
+/// S3 configuration read from the environment, used when `MEDIA_STORAGE` is `s3`.
+///
+/// | Variable                            | Default     | Description                                          |
+/// |-------------------------------------|-------------|------------------------------------------------------|
+/// | `MEDIA_S3_ENDPOINT`                 | required    | URL of the S3-compatible service, e.g. `http://localhost:9000`. |
+/// | `MEDIA_S3_BUCKET`                   | required    | Bucket the files are stored in.                      |
+/// | `MEDIA_S3_REGION`                   | `us-east-1` | Region requests are signed for.                      |
+/// | `MEDIA_S3_ACCESS_KEY_ID`            | required    | ID of the access key.                                |
+/// | `MEDIA_S3_SECRET_ACCESS_KEY`        | required    | Secret of the access key.                            |
+/// | `MEDIA_S3_MULTIPART_THRESHOLD_BYTES`| `8388608`   | Files larger than this are uploaded in parts.        |
+/// | `MEDIA_S3_PART_SIZE_BYTES`          | `8388608`   | Size of the parts of multipart uploads, at least 5 MiB. |
+/// | `MEDIA_S3_PRESIGNED_DOWNLOADS`      | `false`     | Redirect downloads to presigned URLs.                |
+/// | `MEDIA_S3_PRESIGN_EXPIRY_SECS`      | `900`       | How long presigned URLs are valid, at most 7 days.   |
+#[derive(Clone)]
+pub struct S3Config {
+    /// URL of the service. Objects are addressed path-style, as `<endpoint>/<bucket>/<key>`.
+    pub endpoint: Url,
+    /// Bucket the blobs are stored in.
+    pub bucket: String,
+    /// Region requests are signed for.
+    pub region: String,
+    /// ID of the access key.
+    pub access_key: String,
+    /// Secret of the access key.
+    pub secret_key: String,
+    /// Size above which blobs are uploaded in parts.
+    pub multipart_threshold: usize,
+    /// Size of each part of a multipart upload, but the last.
+    pub part_size: usize,
+    /// Whether downloads are redirected to presigned URLs rather than streamed through
+    /// the API.
+    pub presigned_downloads: bool,
+    /// How long presigned URLs are valid.
+    pub presign_expiry: Duration,
+}
+
+impl fmt::Debug for S3Config {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("S3Config")
+            .field("endpoint", &self.endpoint.as_str())
+            .field("bucket", &self.bucket)
+            .field("region", &self.region)
+            .field("access_key", &self.access_key)
+            .field("multipart_threshold", &self.multipart_threshold)
+            .field("part_size", &self.part_size)
+            .field("presigned_downloads", &self.presigned_downloads)
+            .field("presign_expiry", &self.presign_expiry)
+            .finish_non_exhaustive()
+    }
+}
+
+impl S3Config {
+    /// Builds the configuration from environment variables.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a required variable is unset or any of the variables holds an
+    /// invalid value.
+    pub fn from_env() -> Result<Self> {
+        let required = |key: &str| {
+            config::var_opt(key).with_context(|| format!("{key} is required with MEDIA_STORAGE=s3"))
+        };
+        let endpoint = required("MEDIA_S3_ENDPOINT")?;
+        let endpoint = Url::parse(&endpoint)
+            .ok()
+            .filter(|url| matches!(url.scheme(), "http" | "https") && url.has_host())
+            .with_context(|| {
+                format!("Invalid value for MEDIA_S3_ENDPOINT: {endpoint:?} is not an http(s) URL")
+            })?;
+        let part_size = config::parse_or("MEDIA_S3_PART_SIZE_BYTES", 8 * 1024 * 1024)?;
+        if part_size < MIN_PART_SIZE {
+            bail!("Invalid value for MEDIA_S3_PART_SIZE_BYTES: must be at least {MIN_PART_SIZE}");
+        }
+        let presign_expiry =
+            Duration::from_secs(config::parse_or("MEDIA_S3_PRESIGN_EXPIRY_SECS", 900)?);
+        if presign_expiry.is_zero() || presign_expiry > sigv4::MAX_PRESIGN_EXPIRY {
+            bail!(
+                "Invalid value for MEDIA_S3_PRESIGN_EXPIRY_SECS: must be between 1 and {}",
+                sigv4::MAX_PRESIGN_EXPIRY.as_secs()
+            );
+        }
+
+        Ok(Self {
+            endpoint,
+            bucket: required("MEDIA_S3_BUCKET")?,
+            region: config::var_or("MEDIA_S3_REGION", "us-east-1"),
+            access_key: required("MEDIA_S3_ACCESS_KEY_ID")?,
+            secret_key: required("MEDIA_S3_SECRET_ACCESS_KEY")?,
+            multipart_threshold: config::parse_or(
+                "MEDIA_S3_MULTIPART_THRESHOLD_BYTES",
+                8 * 1024 * 1024,
+            )?,
+            part_size,
+            presigned_downloads: config::parse_or("MEDIA_S3_PRESIGNED_DOWNLOADS", false)?,
+            presign_expiry,
+        })
+    }
+}
+
+/// [`BlobStore`] keeping each blob as an object in a bucket of an S3-compatible service,
+/// such as AWS S3 or MinIO, so that several API instances can share the blobs.
+///
+/// Requests are signed with AWS Signature Version 4 and address objects path-style.
+/// Blobs larger than [`S3Config::multipart_threshold`] are uploaded in parts; an upload
+/// that fails is aborted, so that the service discards its parts.
+///
+/// Blobs are not streamed: [`BlobStore::put`] receives the whole blob, and media uploads
+/// are buffered in memory up to `HTTP_MAX_UPLOAD_BYTES` (10 MiB by default) before they
+/// are stored. Parts only spare the service one large request; with the default
+/// settings only blobs between 8 and 10 MiB are uploaded in parts.
+#[derive(Debug, Clone)]
+pub struct S3BlobStore {
+    /// HTTP client, whose connections are reused across requests.
+    client: Client,
+    /// Signs every request with the configured access key.
+    signer: Signer,
+    /// Endpoint, bucket, upload and presigning settings.
+    config: S3Config,
+}
+
+/// Query parameters of a request, not yet percent-encoded.
+type Query = Vec<(String, String)>;
+
+impl S3BlobStore {
+    /// Creates a store for the configured bucket, which must exist.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the HTTP client cannot be created.
+    pub fn new(config: S3Config) -> Result<Self> {
+        let client = Client::builder()
+            .build()
+            .context("Failed to create the S3 HTTP client")?;
+        let signer = Signer::new(&config.access_key, &config.secret_key, &config.region);
+        Ok(Self {
+            client,
+            signer,
+            config,
+        })
+    }
+
+    /// Returns the path of the object stored under `key`, below the path of the endpoint.
+    fn path(&self, key: &str) -> String {
+        format!(
+            "{}/{}/{key}",
+            self.config.endpoint.path().trim_end_matches('/'),
+            self.config.bucket
+        )
+    }
+
+    /// Returns the `Host` header of requests to the endpoint.
+    fn host(&self) -> String {
+        let host = self.config.endpoint.host_str().unwrap_or_default();
+        match self.config.endpoint.port() {
+            Some(port) => format!("{host}:{port}"),
+            None => host.to_string(),
+        }
+    }
+
+    /// Returns the URL of `path` with the given, already encoded, query string.
+    fn url(&self, path: &str, query: &str) -> Url {
+        let mut url = self.config.endpoint.clone();
+        url.set_path(&sigv4::uri_encode(path, true));
+        url.set_query(Some(query).filter(|query| !query.is_empty()));
+        url
+    }
+
+    /// Signs and sends a request for the object stored under `key`.
+    async fn send(
+        &self,
+        method: Method,
+        key: &str,
+        query: Query,
+        headers: Vec<(String, String)>,
+        body: Bytes,
+    ) -> Result<Response, AppError> {
+        let path = self.path(key);
+        let now = Utc::now();
+        let payload_hash = sigv4::payload_hash(&body);
+        let mut headers = headers;
+        headers.extend([
+            ("host".to_string(), self.host()),
+            ("x-amz-content-sha256".to_string(), payload_hash.clone()),
+            ("x-amz-date".to_string(), sigv4::amz_date(now)),
+        ]);
+        let authorization = self.signer.authorization(
+            &sigv4::Request {
+                method: method.as_str(),
+                path: &path,
+                query: &query,
+                headers: &headers,
+                payload_hash: &payload_hash,
+            },
+            now,
+        );
+
+        let url = self.url(&path, &sigv4::canonical_query(&query));
+        let mut request = self
+            .client
+            .request(method.clone(), url)
+            .header(header::AUTHORIZATION, authorization)
+            .body(body);
+        for (name, value) in headers.into_iter().filter(|(name, _)| name != "host") {
+            request = request.header(name, value);
+        }
+        request
+            .send()
+            .await
+            .map_err(|err| storage_error(format!("S3 {method} {key} failed: {err}")))
+    }
+
+    /// Returns the response if its status is successful, and an error describing the
+    /// failure otherwise.
+    async fn check(response: Response, action: &str) -> Result<Response, AppError> {
+        let status = response.status();
+        if status.is_success() {
+            return Ok(response);
+        }
+        let body = response.text().await.unwrap_or_default();
+        let code = element(&body, "Code").unwrap_or_default();
+        Err(storage_error(format!(
+            "S3 {action} failed with {status} {code}"
+        )))
+    }
+
+    /// Returns whether an object is stored under `key`.
+    async fn exists(&self, key: &str) -> Result<bool, AppError> {
+        let response = self
+            .send(Method::HEAD, key, Vec::new(), Vec::new(), Bytes::new())
+            .await?;
+        if response.status() == StatusCode::NOT_FOUND {
+            return Ok(false);
+        }
+        Self::check(response, "HEAD").await.map(|_| true)
+    }
+
+    /// Uploads a blob in parts of [`S3Config::part_size`], aborting the upload if any
+    /// part fails. The blob is already in memory; each part is a slice of it.
+    async fn put_multipart(&self, key: &str, data: Bytes) -> Result<(), AppError> {
+        let response = self
+            .send(
+                Method::POST,
+                key,
+                vec![("uploads".to_string(), String::new())],
+                Vec::new(),
+                Bytes::new(),
+            )
+            .await?;
+        let body = Self::check(response, "CreateMultipartUpload")
+            .await?
+            .text()
+            .await
+            .map_err(storage_error)?;
+        let upload_id = element(&body, "UploadId")
+            .ok_or_else(|| storage_error("S3 CreateMultipartUpload returned no UploadId"))?;
+
+        let result = self.upload_parts(key, &upload_id, data).await;
+        if result.is_err() {
+            let aborted = self
+                .send(
+                    Method::DELETE,
+                    key,
+                    vec![("uploadId".to_string(), upload_id)],
+                    Vec::new(),
+                    Bytes::new(),
+                )
+                .await;
+            if let Err(err) = aborted {
+                tracing::warn!("failed to abort S3 multipart upload of {key}: {err}");
+            }
+        }
+        result
+    }
+
+    /// Uploads the parts of a multipart upload and completes it.
+    async fn upload_parts(&self, key: &str, upload_id: &str, data: Bytes) -> Result<(), AppError> {
+        let mut completion = String::from("<CompleteMultipartUpload>");
+        let mut start = 0;
+        let mut number = 1;
+        while start < data.len() {
+            let end = data.len().min(start.saturating_add(self.config.part_size));
+            let query = vec![
+                ("partNumber".to_string(), number.to_string()),
+                ("uploadId".to_string(), upload_id.to_string()),
+            ];
+            let response = self
+                .send(Method::PUT, key, query, Vec::new(), data.slice(start..end))
+                .await?;
+            let response = Self::check(response, "UploadPart").await?;
+            let etag = response
+                .headers()
+                .get(header::ETAG)
+                .and_then(|etag| etag.to_str().ok())
+                .ok_or_else(|| storage_error("S3 UploadPart returned no ETag"))?;
+            completion.push_str(&format!(
+                "<Part><PartNumber>{number}</PartNumber><ETag>{}</ETag></Part>",
+                quick_xml::escape::escape(etag)
+            ));
+            start = end;
+            number += 1;
+        }
+        completion.push_str("</CompleteMultipartUpload>");
+
+        let response = self
+            .send(
+                Method::POST,
+                key,
+                vec![("uploadId".to_string(), upload_id.to_string())],
+                vec![("content-type".to_string(), "application/xml".to_string())],
+                Bytes::from(completion),
+            )
+            .await?;
+        let body = Self::check(response, "CompleteMultipartUpload")
+            .await?
+            .text()
+            .await
+            .map_err(storage_error)?;
+        // The service reports failures that happen after it started to respond in the body.
+        if body.contains("<Error>") {
+            let code = element(&body, "Code").unwrap_or_default();
+            return Err(storage_error(format!(
+                "S3 CompleteMultipartUpload failed with {code}"
+            )));
+        }
+        Ok(())
+    }
+
+    /// Returns a presigned URL for a request for the object stored under `key`, which
+    /// anyone holding it can send without credentials until it expires.
+    ///
+    /// A presigned `GET` lets clients download a blob straight from the service, and a
+    /// presigned `PUT` lets them upload one. `query` may set response headers of a
+    /// `GET`, such as `response-content-disposition`.
+    ///
+    pub fn presigned_url(
+        &self,
+        method: &Method,
+        key: &str,
+        query: Query,
+        expires_in: Duration,
+    ) -> Url {
+        let path = self.path(key);
+        let headers = [("host".to_string(), self.host())];
+        let query = self.signer.presign(
+            &sigv4::Request {
+                method: method.as_str(),
+                path: &path,
+                query: &query,
+                headers: &headers,
+                payload_hash: sigv4::UNSIGNED_PAYLOAD,
+            },
+            Utc::now(),
+            expires_in,
+        );
+        self.url(&path, &query)
+    }
+}
+
+/// Returns the unescaped text of the first `<name>` element of an XML document.
+fn element(xml: &str, name: &str) -> Option<String> {
+    let (_, rest) = xml.split_once(&format!("<{name}>"))?;
+    let (text, _) = rest.split_once(&format!("</{name}>"))?;
+    unescape(text).ok().map(|text| text.into_owned())
+}
+
+#[async_trait]
+impl BlobStore for S3BlobStore {
+    async fn put(&self, key: &str, data: Bytes) -> Result<(), AppError> {
+        if self.exists(key).await? {
+            return Ok(());
+        }
+        if data.len() > self.config.multipart_threshold {
+            return self.put_multipart(key, data).await;
+        }
+        let response = self
+            .send(Method::PUT, key, Vec::new(), Vec::new(), data)
+            .await?;
+        Self::check(response, "PutObject").await.map(|_| ())
+    }
+
+    async fn get(&self, key: &str, range: Range<u64>) -> Result<Option<BlobStream>, AppError> {
+        if range.is_empty() {
+            return Ok(self.exists(key).await?.then(|| stream::empty().boxed()));
+        }
+        let headers = vec![(
+            "range".to_string(),
+            format!("bytes={}-{}", range.start, range.end - 1),
+        )];
+        let response = self
+            .send(Method::GET, key, Vec::new(), headers, Bytes::new())
+            .await?;
+        if response.status() == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        let response = Self::check(response, "GetObject").await?;
+        Ok(Some(
+            response.bytes_stream().map_err(io::Error::other).boxed(),
+        ))
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), AppError> {
+        let response = self
+            .send(Method::DELETE, key, Vec::new(), Vec::new(), Bytes::new())
+            .await?;
+        if response.status() == StatusCode::NOT_FOUND {
+            return Ok(());
+        }
+        Self::check(response, "DeleteObject").await.map(|_| ())
+    }
+
+    fn download_url(
+        &self,
+        key: &str,
+        content_type: &str,
+        content_disposition: &str,
+    ) -> Result<Option<String>, AppError> {
+        if !self.config.presigned_downloads {
+            return Ok(None);
+        }
+        let query = vec![
+            (
+                "response-content-type".to_string(),
+                content_type.to_string(),
+            ),
+            (
+                "response-content-disposition".to_string(),
+                content_disposition.to_string(),
+            ),
+        ];
+        let url = self.presigned_url(&Method::GET, key, query, self.config.presign_expiry);
+        Ok(Some(url.into()))
+    }
+}
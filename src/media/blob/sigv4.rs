@@ -0,0 +1,230 @@
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use sha2::{Digest, Sha256};
+use std::{fmt::Write, time::Duration};
+
+/// Name of the signing algorithm, as it appears in signatures.
+const ALGORITHM: &str = "AWS4-HMAC-SHA256";
+
+/// Payload hash of presigned requests, whose body is unknown when they are signed.
+pub const UNSIGNED_PAYLOAD: &str = "UNSIGNED-PAYLOAD";
+
+/// Longest validity of a presigned URL that S3 accepts: seven days.
+pub const MAX_PRESIGN_EXPIRY: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+/// Returns the hex-encoded SHA-256 hash of a request payload.
+pub fn payload_hash(payload: &[u8]) -> String {
+    hex::encode(Sha256::digest(payload))
+}
+
+/// Formats a time as the `X-Amz-Date` of a request, e.g. `20130524T000000Z`.
+pub fn amz_date(time: DateTime<Utc>) -> String {
+    time.format("%Y%m%dT%H%M%SZ").to_string()
+}
+
+/// Percent-encodes a path or query component as SigV4 requires: every byte except
+/// letters, digits and `-._~` is encoded, and so is `/` unless `keep_slash` is set.
+pub fn uri_encode(value: &str, keep_slash: bool) -> String {
+    let mut encoded = String::with_capacity(value.len());
+    for byte in value.bytes() {
+        if byte.is_ascii_alphanumeric() || b"-._~".contains(&byte) || (keep_slash && byte == b'/') {
+            encoded.push(char::from(byte));
+        } else {
+            let _ = write!(encoded, "%{byte:02X}");
+        }
+    }
+    encoded
+}
+
+/// Builds the canonical query string: the encoded parameters sorted by name, then value.
+pub fn canonical_query(query: &[(String, String)]) -> String {
+    let mut pairs: Vec<(String, String)> = query
+        .iter()
+        .map(|(name, value)| (uri_encode(name, false), uri_encode(value, false)))
+        .collect();
+    pairs.sort();
+    pairs
+        .iter()
+        .map(|(name, value)| format!("{name}={value}"))
+        .collect::<Vec<_>>()
+        .join("&")
+}
+
+/// An HTTP request to sign.
+#[derive(Debug, Clone, Copy)]
+pub struct Request<'a> {
+    /// HTTP method, e.g. `GET`.
+    pub method: &'a str,
+    /// Path of the request, not yet percent-encoded.
+    pub path: &'a str,
+    /// Query parameters, not yet percent-encoded.
+    pub query: &'a [(String, String)],
+    /// Headers to sign, which must include `host`. Names may be in any case.
+    pub headers: &'a [(String, String)],
+    /// Hex-encoded SHA-256 hash of the body, or [`UNSIGNED_PAYLOAD`].
+    pub payload_hash: &'a str,
+}
+
+impl Request<'_> {
+    /// Returns the names of the signed headers, lowercase, sorted and joined by `;`, and
+    /// the canonical request.
+    fn canonicalize(&self) -> (String, String) {
+        let mut headers: Vec<(String, String)> = self
+            .headers
+            .iter()
+            .map(|(name, value)| {
+                let value = value.split_whitespace().collect::<Vec<_>>().join(" ");
+                (name.to_ascii_lowercase(), value)
+            })
+            .collect();
+        headers.sort();
+        let signed_headers = headers
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .collect::<Vec<_>>()
+            .join(";");
+        let canonical_headers: String = headers
+            .iter()
+            .map(|(name, value)| format!("{name}:{value}\n"))
+            .collect();
+        let canonical_request = format!(
+            "{}\n{}\n{}\n{canonical_headers}\n{signed_headers}\n{}",
+            self.method,
+            uri_encode(self.path, true),
+            canonical_query(self.query),
+            self.payload_hash
+        );
+        (signed_headers, canonical_request)
+    }
+}
+
+/// Signs requests to S3 with AWS Signature Version 4.
+#[derive(Clone)]
+pub struct Signer {
+    /// ID of the access key.
+    access_key: String,
+    /// Secret of the access key.
+    secret_key: String,
+    /// Region the requests are sent to, e.g. `us-east-1`.
+    region: String,
+}
+
+impl std::fmt::Debug for Signer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Signer")
+            .field("access_key", &self.access_key)
+            .field("region", &self.region)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Signer {
+    /// Creates a signer for the given access key and region.
+    pub fn new(
+        access_key: impl Into<String>,
+        secret_key: impl Into<String>,
+        region: impl Into<String>,
+    ) -> Self {
+        Self {
+            access_key: access_key.into(),
+            secret_key: secret_key.into(),
+            region: region.into(),
+        }
+    }
+
+    /// Returns the `Authorization` header of a request sent at `time`.
+    ///
+    /// The request headers must include `x-amz-date` set to [`amz_date`] of `time`, and
+    /// `x-amz-content-sha256` set to its payload hash.
+    pub fn authorization(&self, request: &Request<'_>, time: DateTime<Utc>) -> String {
+        let (signed_headers, canonical_request) = request.canonicalize();
+        let signature = self.signature(&canonical_request, time);
+        format!(
+            "{ALGORITHM} Credential={}/{}, SignedHeaders={signed_headers}, Signature={signature}",
+            self.access_key,
+            self.scope(time)
+        )
+    }
+
+    /// Returns the query string of a presigned URL for a request, valid for `expires_in`
+    /// from `time`: the query of the request plus the signing parameters.
+    ///
+    /// Only the headers of the request are signed, so clients must send them as given;
+    /// usually that is just `host`. The payload is not signed.
+    pub fn presign(
+        &self,
+        request: &Request<'_>,
+        time: DateTime<Utc>,
+        expires_in: Duration,
+    ) -> String {
+        let signed_headers = {
+            let mut names: Vec<String> = request
+                .headers
+                .iter()
+                .map(|(name, _)| name.to_ascii_lowercase())
+                .collect();
+            names.sort();
+            names.join(";")
+        };
+        let mut query = request.query.to_vec();
+        query.extend([
+            ("X-Amz-Algorithm".to_string(), ALGORITHM.to_string()),
+            (
+                "X-Amz-Credential".to_string(),
+                format!("{}/{}", self.access_key, self.scope(time)),
+            ),
+            ("X-Amz-Date".to_string(), amz_date(time)),
+            (
+                "X-Amz-Expires".to_string(),
+                expires_in.min(MAX_PRESIGN_EXPIRY).as_secs().to_string(),
+            ),
+            ("X-Amz-SignedHeaders".to_string(), signed_headers),
+        ]);
+        let (_, canonical_request) = Request {
+            query: &query,
+            payload_hash: UNSIGNED_PAYLOAD,
+            ..*request
+        }
+        .canonicalize();
+        let signature = self.signature(&canonical_request, time);
+        query.push(("X-Amz-Signature".to_string(), signature));
+        canonical_query(&query)
+    }
+
+    /// Returns the credential scope of requests signed at `time`.
+    fn scope(&self, time: DateTime<Utc>) -> String {
+        format!("{}/{}/s3/aws4_request", time.format("%Y%m%d"), self.region)
+    }
+
+    /// Signs a canonical request with the key derived for the day of `time`.
+    fn signature(&self, canonical_request: &str, time: DateTime<Utc>) -> String {
+        let string_to_sign = format!(
+            "{ALGORITHM}\n{}\n{}\n{}",
+            amz_date(time),
+            self.scope(time),
+            payload_hash(canonical_request.as_bytes())
+        );
+        let key = [
+            time.format("%Y%m%d").to_string().as_str(),
+            &self.region,
+            "s3",
+            "aws4_request",
+        ]
+        .into_iter()
+        .fold(
+            format!("AWS4{}", self.secret_key).into_bytes(),
+            |key, part| hmac(&key, part.as_bytes()),
+        );
+        hex::encode(hmac(&key, string_to_sign.as_bytes()))
+    }
+}
+
+/// Computes the HMAC-SHA256 of `data` with `key`.
+fn hmac(key: &[u8], data: &[u8]) -> Vec<u8> {
+    // HMAC accepts keys of any length, so this never falls back.
+    let Ok(mut mac) = Hmac::<Sha256>::new_from_slice(key) else {
+        return Vec::new();
+    };
+    mac.update(data);
+    mac.finalize().into_bytes().to_vec()
+}
@@ -0,0 +1,211 @@
+use super::{MediaRepository, with_variants};
+use crate::{
+    error::AppError,
+    model::media::{
+        MediaFile, MediaFilter, MediaVariant, NewMediaFile, NewMediaVariant, VariantsStatus,
+    },
+};
+use async_trait::async_trait;
+use chrono::Utc;
+use std::{
+    collections::BTreeMap,
+    sync::{Mutex, MutexGuard, PoisonError},
+};
+
+/// The files, variants and attachments of an [`InMemoryMediaRepository`].
+#[derive(Debug, Default)]
+struct Store {
+    /// Files by ID, without their variants.
+    files: BTreeMap<i32, MediaFile>,
+    /// Variants of the files, in the order they were stored.
+    variants: Vec<MediaVariant>,
+    /// Attachments as `(post ID, media ID)` pairs, in the order they were made.
+    attachments: Vec<(i32, i32)>,
+    /// ID of the most recently created file.
+    last_id: i32,
+    /// ID of the most recently stored variant.
+    last_variant_id: i32,
+}
+
+impl Store {
+    /// Returns copies of the given files with their variants.
+    fn with_variants(&self, files: Vec<MediaFile>) -> Vec<MediaFile> {
+        let variants = self
+            .variants
+            .iter()
+            .filter(|variant| files.iter().any(|file| file.id == variant.media_id))
+            .cloned()
+            .collect();
+        with_variants(files, variants)
+    }
+}
+
+/// [`MediaRepository`] keeping records in memory. Records are lost when the repository
+/// is dropped.
+///
+/// The repository does not know about posts: attachments to a post are kept after the
+/// post is deleted, so callers check that posts exist.
+#[derive(Debug, Default)]
+pub struct InMemoryMediaRepository {
+    /// The stored files and attachments.
+    inner: Mutex<Store>,
+}
+
+impl InMemoryMediaRepository {
+    /// Creates an empty repository.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Locks the store, recovering it if a previous holder panicked.
+    fn store(&self) -> MutexGuard<'_, Store> {
+        self.inner.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+#[async_trait]
+impl MediaRepository for InMemoryMediaRepository {
+    async fn create(&self, file: &NewMediaFile) -> Result<MediaFile, AppError> {
+        let mut store = self.store();
+        let id = store
+            .last_id
+            .checked_add(1)
+            .ok_or(AppError::InternalServerError)?;
+        let created = MediaFile {
+            id,
+            owner: file.owner.clone(),
+            filename: file.filename.clone(),
+            content_type: file.content_type.clone(),
+            size: file.size,
+            content_hash: file.content_hash.clone(),
+            width: file.width,
+            height: file.height,
+            created_at: Utc::now(),
+            variants_status: file.variants_status,
+            variants: Vec::new(),
+            srcset: Vec::new(),
+        };
+        store.last_id = id;
+        store.files.insert(id, created.clone());
+        Ok(created)
+    }
+
+    async fn get(&self, id: i32) -> Result<Option<MediaFile>, AppError> {
+        let store = self.store();
+        let file = store.files.get(&id).cloned();
+        Ok(store.with_variants(file.into_iter().collect()).pop())
+    }
+
+    async fn list(&self, filter: &MediaFilter) -> Result<Vec<MediaFile>, AppError> {
+        let store = self.store();
+        let files = store
+            .files
+            .values()
+            .filter(|file| filter.owner.is_none() || file.owner == filter.owner)
+            .filter(|file| {
+                filter
+                    .variants_status
+                    .is_none_or(|status| file.variants_status == status)
+            })
+            .cloned()
+            .collect();
+        Ok(store.with_variants(files))
+    }
+
+    async fn delete(&self, id: i32) -> Result<Option<MediaFile>, AppError> {
+        let mut store = self.store();
+        let Some(file) = store.files.remove(&id) else {
+            return Ok(None);
+        };
+        let deleted = store.with_variants(vec![file]).pop();
+        store.variants.retain(|variant| variant.media_id != id);
+        store.attachments.retain(|&(_, media_id)| media_id != id);
+        Ok(deleted)
+    }
+
+    async fn has_content(&self, content_hash: &str) -> Result<bool, AppError> {
+        let store = self.store();
+        Ok(store
+            .files
+            .values()
+            .any(|file| file.content_hash == content_hash)
+            || store
+                .variants
+                .iter()
+                .any(|variant| variant.content_hash == content_hash))
+    }
+
+    async fn attach(&self, post_id: i32, media_id: i32) -> Result<(), AppError> {
+        let mut store = self.store();
+        if !store.files.contains_key(&media_id) {
+            return Err(AppError::NotFound("Media file not found".to_string()));
+        }
+        if !store.attachments.contains(&(post_id, media_id)) {
+            store.attachments.push((post_id, media_id));
+        }
+        Ok(())
+    }
+
+    async fn detach(&self, post_id: i32, media_id: i32) -> Result<bool, AppError> {
+        let mut store = self.store();
+        let before = store.attachments.len();
+        store
+            .attachments
+            .retain(|&attachment| attachment != (post_id, media_id));
+        Ok(store.attachments.len() < before)
+    }
+
+    async fn list_for_post(&self, post_id: i32) -> Result<Vec<MediaFile>, AppError> {
+        let store = self.store();
+        let files = store
+            .attachments
+            .iter()
+            .filter(|&&(post, _)| post == post_id)
+            .filter_map(|(_, media_id)| store.files.get(media_id).cloned())
+            .collect();
+        Ok(store.with_variants(files))
+    }
+
+    async fn add_variant(&self, variant: &NewMediaVariant) -> Result<MediaVariant, AppError> {
+        let mut store = self.store();
+        if !store.files.contains_key(&variant.media_id) {
+            return Err(AppError::NotFound("Media file not found".to_string()));
+        }
+        let mut added = MediaVariant {
+            id: 0,
+            media_id: variant.media_id,
+            name: variant.name.clone(),
+            content_type: variant.content_type.clone(),
+            size: variant.size,
+            content_hash: variant.content_hash.clone(),
+            width: variant.width,
+            height: variant.height,
+            created_at: Utc::now(),
+        };
+        if let Some(stored) = store
+            .variants
+            .iter_mut()
+            .find(|stored| stored.media_id == variant.media_id && stored.name == variant.name)
+        {
+            added.id = stored.id;
+            *stored = added.clone();
+            return Ok(added);
+        }
+        added.id = store
+            .last_variant_id
+            .checked_add(1)
+            .ok_or(AppError::InternalServerError)?;
+        store.last_variant_id = added.id;
+        store.variants.push(added.clone());
+        Ok(added)
+    }
+
+    async fn set_variants_status(&self, id: i32, status: VariantsStatus) -> Result<bool, AppError> {
+        Ok(self
+            .store()
+            .files
+            .get_mut(&id)
+            .map(|file| file.variants_status = status)
+            .is_some())
+    }
+}
@@ -0,0 +1,669 @@
+use crate::{
+    config,
+    error::AppError,
+    jobs::{JobHandler, Jobs},
+    model::{
+        job::Job,
+        media::{
+            MediaFile, MediaFilter, MediaVariant, NewMediaFile, NewMediaVariant, SourceSet,
+            VariantsStatus,
+        },
+    },
+    server::middleware::versioning::ApiVersion,
+};
+use anyhow::{Result, bail};
+use async_trait::async_trait;
+use axum::body::Bytes;
+use blob::{
+    DynBlobStore,
+    local::LocalBlobStore,
+    s3::{S3BlobStore, S3Config},
+    storage_error,
+};
+use futures_util::TryStreamExt;
+use sha2::{Digest, Sha256};
+use std::{path::PathBuf, sync::Arc, time::Duration};
+use tokio::sync::Semaphore;
+use variants::VariantConfig;
+
+/// Storage for the content of media files.
+pub mod blob;
+
+/// In-memory storage of media file records, for tests and local development.
+pub mod memory;
+
+/// PostgreSQL storage of media file records.
+pub mod postgres;
+
+/// Parsing of `Range` headers.
+pub mod range;
+
+/// Recognition of file types and image dimensions from file content.
+pub mod sniff;
+
+/// SQLite storage of media file records.
+pub mod sqlite;
+
+/// Removal of identifying metadata from uploaded images.
+pub mod strip;
+
+/// Generation of resized and re-encoded variants of uploaded images.
+pub mod variants;
+
+/// Queue of the jobs generating variants, whose concurrency limit is set in
+/// `JOBS_QUEUE_CONCURRENCY`.
+pub const VARIANTS_QUEUE: &str = "media";
+
+/// Longest stored file name or owner, in characters.
+const MAX_NAME_LENGTH: usize = 255;
+
+/// Where the content of media files is stored.
+#[derive(Debug, Clone)]
+pub enum MediaStorage {
+    /// In a directory on the local file system.
+    Local(PathBuf),
+    /// In a bucket of an S3-compatible service.
+    S3(S3Config),
+}
+
+impl MediaStorage {
+    /// Creates the blob store for this storage.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the store cannot be created.
+    pub fn blob_store(&self) -> Result<DynBlobStore> {
+        Ok(match self {
+            Self::Local(dir) => Arc::new(LocalBlobStore::new(dir.clone())),
+            Self::S3(config) => Arc::new(S3BlobStore::new(config.clone())?),
+        })
+    }
+}
+
+/// Media configuration read from the environment.
+///
+/// | Variable                   | Default                                     | Description                                      |
+/// |----------------------------|---------------------------------------------|--------------------------------------------------|
+/// | `MEDIA_STORAGE`            | `local`                                     | `local` or `s3`; see [`S3Config`] for the latter. |
+/// | `MEDIA_DIR`                | `media`                                     | Directory media files are stored in locally.     |
+/// | `MEDIA_ALLOWED_TYPES`      | `image/png,image/jpeg,image/gif,image/webp` | Comma-separated MIME types accepted for upload.  |
+/// | `MEDIA_CACHE_MAX_AGE_SECS` | `31536000`                                  | How long clients may cache downloaded files.     |
+///
+/// The size of uploads is limited by `HTTP_MAX_UPLOAD_BYTES`; see
+/// [`HttpConfig`](crate::server::middleware::http::HttpConfig). The variants generated
+/// from images are configured as described for [`VariantConfig`].
+#[derive(Debug, Clone)]
+pub struct MediaConfig {
+    /// Where the content of files is stored.
+    pub storage: MediaStorage,
+    /// MIME types accepted for upload, each recognized by [`sniff::content_type`].
+    pub allowed_types: Vec<String>,
+    /// `max-age` of the `Cache-Control` header of downloads.
+    pub cache_max_age: Duration,
+    /// Variants generated from uploaded images.
+    pub variants: VariantConfig,
+}
+
+impl MediaConfig {
+    /// Builds the configuration from environment variables.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if any of the variables holds an invalid value, such as a MIME
+    /// type that uploads cannot be recognized as, or a variable required by the storage
+    /// is unset.
+    pub fn from_env() -> Result<Self> {
+        let allowed_types: Vec<String> = config::var_or(
+            "MEDIA_ALLOWED_TYPES",
+            "image/png,image/jpeg,image/gif,image/webp",
+        )
+        .split(',')
+        .map(|content_type| content_type.trim().to_ascii_lowercase())
+        .filter(|content_type| !content_type.is_empty())
+        .collect();
+        for content_type in &allowed_types {
+            if !sniff::CONTENT_TYPES.contains(&content_type.as_str()) {
+                bail!(
+                    "Invalid value for MEDIA_ALLOWED_TYPES: {content_type:?} is not one of {}",
+                    sniff::CONTENT_TYPES.join(", ")
+                );
+            }
+        }
+        let storage = match config::var_or("MEDIA_STORAGE", "local").trim() {
+            "local" => MediaStorage::Local(PathBuf::from(config::var_or("MEDIA_DIR", "media"))),
+            "s3" => MediaStorage::S3(S3Config::from_env()?),
+            other => bail!("Invalid value for MEDIA_STORAGE: {other:?} is not `local` or `s3`"),
+        };
+        Ok(Self {
+            storage,
+            allowed_types,
+            cache_max_age: Duration::from_secs(config::parse_or(
+                "MEDIA_CACHE_MAX_AGE_SECS",
+                365 * 24 * 60 * 60,
+            )?),
+            variants: VariantConfig::from_env()?,
+        })
+    }
+}
+
+/// Storage for the records of media files and their attachment to posts.
+///
+/// The content of the files is kept separately, in a [`blob::BlobStore`].
+#[async_trait]
+pub trait MediaRepository: Send + Sync {
+    /// Stores the record of a new file and returns it with its assigned ID.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `AppError` if the storage fails.
+    async fn create(&self, file: &NewMediaFile) -> Result<MediaFile, AppError>;
+
+    /// Returns the file with the given ID, if any.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `AppError` if the storage fails.
+    async fn get(&self, id: i32) -> Result<Option<MediaFile>, AppError>;
+
+    /// Returns every file passing the filter, ordered by ID.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `AppError` if the storage fails.
+    async fn list(&self, filter: &MediaFilter) -> Result<Vec<MediaFile>, AppError>;
+
+    /// Deletes a file, detaching it from every post, and returns it if it existed.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `AppError` if the storage fails.
+    async fn delete(&self, id: i32) -> Result<Option<MediaFile>, AppError>;
+
+    /// Returns whether any file has the given content hash.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `AppError` if the storage fails.
+    async fn has_content(&self, content_hash: &str) -> Result<bool, AppError>;
+
+    /// Attaches a file to a post; attaching it again changes nothing.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `AppError::NotFound` if the post or the file does not exist, or an
+    /// `AppError` if the storage fails.
+    async fn attach(&self, post_id: i32, media_id: i32) -> Result<(), AppError>;
+
+    /// Detaches a file from a post, returning whether it was attached.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `AppError` if the storage fails.
+    async fn detach(&self, post_id: i32, media_id: i32) -> Result<bool, AppError>;
+
+    /// Returns the files attached to a post, in the order they were attached.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `AppError` if the storage fails.
+    async fn list_for_post(&self, post_id: i32) -> Result<Vec<MediaFile>, AppError>;
+
+    /// Stores a variant of a file, replacing any variant of the file with the same name.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `AppError::NotFound` if the file does not exist, or an `AppError` if
+    /// the storage fails.
+    async fn add_variant(&self, variant: &NewMediaVariant) -> Result<MediaVariant, AppError>;
+
+    /// Sets the status of the variants of a file, returning whether the file exists.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `AppError` if the storage fails.
+    async fn set_variants_status(&self, id: i32, status: VariantsStatus) -> Result<bool, AppError>;
+}
+
+/// A shared, type-erased [`MediaRepository`].
+pub type DynMediaRepository = Arc<dyn MediaRepository>;
+
+/// Checks that the file to feature on a post exists and is an image.
+///
+/// # Errors
+///
+/// Returns an `AppError::UnprocessableEntity` if no file has the ID or it is not an
+/// image, or an `AppError` if the repository fails.
+pub async fn check_featured_image(
+    repository: &dyn MediaRepository,
+    id: i32,
+) -> Result<(), AppError> {
+    match repository.get(id).await? {
+        Some(file) if file.content_type.starts_with("image/") => Ok(()),
+        Some(_) => Err(AppError::UnprocessableEntity(
+            "Featured image must be an image".to_string(),
+        )),
+        None => Err(AppError::UnprocessableEntity(
+            "Featured image not found".to_string(),
+        )),
+    }
+}
+
+/// Maps the foreign key violation of an attachment to a missing post or file to
+/// `AppError::NotFound`, like the in-memory repository reports a missing file.
+fn missing_reference(err: sqlx::Error) -> AppError {
+    if err
+        .as_database_error()
+        .is_some_and(|err| err.is_foreign_key_violation())
+    {
+        AppError::NotFound("Blog post or media file not found".to_string())
+    } else {
+        err.into()
+    }
+}
+
+/// Returns the path of the content of a media file or of one of its variants, under the
+/// newest API version since every version serves media the same way.
+pub fn content_path(media_id: i32, variant: Option<&str>) -> String {
+    let prefix = ApiVersion::LATEST.prefix();
+    match variant {
+        Some(name) => format!("{prefix}/media/{media_id}/variants/{name}"),
+        None => format!("{prefix}/media/{media_id}/content"),
+    }
+}
+
+/// Sets the variants of each file and the source sets built from them, given the
+/// variants of all the files in the order they were generated.
+pub(crate) fn with_variants(
+    mut files: Vec<MediaFile>,
+    variants: Vec<MediaVariant>,
+) -> Vec<MediaFile> {
+    for variant in variants {
+        if let Some(file) = files.iter_mut().find(|file| file.id == variant.media_id) {
+            file.variants.push(variant);
+        }
+    }
+    for file in &mut files {
+        file.srcset = source_sets(file);
+    }
+    files
+}
+
+/// Groups the variants of a file by type, leaving out thumbnails, in the order the types
+/// were generated in. The type of the file itself comes last, with the file as its
+/// widest candidate, as the fallback `srcset` of an `<img>`.
+fn source_sets(file: &MediaFile) -> Vec<SourceSet> {
+    let mut sets: Vec<(String, Vec<String>)> = Vec::new();
+    for variant in file
+        .variants
+        .iter()
+        .filter(|variant| !variant.name.starts_with(variants::THUMBNAIL))
+    {
+        let candidate = format!(
+            "{} {}w",
+            content_path(file.id, Some(&variant.name)),
+            variant.width
+        );
+        match sets
+            .iter_mut()
+            .find(|(content_type, _)| *content_type == variant.content_type)
+        {
+            Some((_, candidates)) => candidates.push(candidate),
+            None => sets.push((variant.content_type.clone(), vec![candidate])),
+        }
+    }
+    if sets.is_empty() {
+        return Vec::new();
+    }
+    let mut own = match sets
+        .iter()
+        .position(|(content_type, _)| *content_type == file.content_type)
+    {
+        Some(index) => sets.remove(index),
+        None => (file.content_type.clone(), Vec::new()),
+    };
+    if let Some(width) = file.width {
+        own.1
+            .push(format!("{} {width}w", content_path(file.id, None)));
+    }
+    sets.push(own);
+    sets.into_iter()
+        .map(|(content_type, candidates)| SourceSet {
+            content_type,
+            srcset: candidates.join(", "),
+        })
+        .collect()
+}
+
+/// Media file records and content together with their configuration, as stored in the
+/// application state.
+#[derive(Clone)]
+pub struct Media {
+    /// Where the records of files are stored.
+    pub repository: DynMediaRepository,
+    /// Where the content of files is stored.
+    pub blobs: DynBlobStore,
+    /// Accepted types, caching and variants.
+    pub config: Arc<MediaConfig>,
+    /// Permits to generate variants, limiting how many images are processed at once.
+    processing: Arc<Semaphore>,
+}
+
+/// Returns the last path component of an uploaded file name, without control
+/// characters, so that it is safe to echo in a `Content-Disposition` header.
+fn clean_filename(filename: Option<&str>) -> String {
+    let name: String = filename
+        .unwrap_or_default()
+        .rsplit(['/', '\\'])
+        .next()
+        .unwrap_or_default()
+        .chars()
+        .filter(|c| !c.is_control())
+        .take(MAX_NAME_LENGTH)
+        .collect();
+    match name.trim() {
+        "" | "." | ".." => "upload".to_string(),
+        name => name.to_string(),
+    }
+}
+
+impl Media {
+    /// Bundles a repository and blob store with their configuration.
+    pub fn new(repository: DynMediaRepository, blobs: DynBlobStore, config: MediaConfig) -> Self {
+        Self {
+            repository,
+            blobs,
+            processing: Arc::new(Semaphore::new(config.variants.concurrency)),
+            config: Arc::new(config),
+        }
+    }
+
+    /// Stores an uploaded file and returns its record.
+    ///
+    /// The type of the file is recognized from its content. The type it was uploaded as,
+    /// if any other than `application/octet-stream`, must match. Images are stored
+    /// without identifying metadata and rotated upright, and their variants are generated
+    /// by a job enqueued on `jobs`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `AppError::BadRequest` if the file is empty or the owner is invalid,
+    /// an `AppError::UnsupportedMediaType` if its type is not recognized, not accepted
+    /// or does not match the declared type, or an `AppError` if the storage fails.
+    pub async fn upload(
+        &self,
+        jobs: &Jobs,
+        owner: Option<String>,
+        filename: Option<&str>,
+        declared_type: Option<&str>,
+        data: Bytes,
+    ) -> Result<MediaFile, AppError> {
+        if data.is_empty() {
+            return Err(AppError::BadRequest("The file is empty".to_string()));
+        }
+        let owner = owner
+            .map(|owner| owner.trim().to_string())
+            .filter(|owner| !owner.is_empty());
+        if owner
+            .as_ref()
+            .is_some_and(|owner| owner.chars().count() > MAX_NAME_LENGTH)
+        {
+            return Err(AppError::BadRequest(format!(
+                "The owner must be at most {MAX_NAME_LENGTH} characters long"
+            )));
+        }
+
+        let content_type = sniff::content_type(&data).ok_or_else(|| {
+            AppError::UnsupportedMediaType("The file type is not recognized".to_string())
+        })?;
+        if !self
+            .config
+            .allowed_types
+            .iter()
+            .any(|allowed| allowed == content_type)
+        {
+            return Err(AppError::UnsupportedMediaType(format!(
+                "Files of type {content_type} are not accepted"
+            )));
+        }
+        let declared = declared_type
+            .and_then(|declared| declared.split(';').next())
+            .map(|declared| declared.trim().to_ascii_lowercase())
+            .filter(|declared| declared != "application/octet-stream");
+        if let Some(declared) = declared
+            && declared != content_type
+        {
+            return Err(AppError::UnsupportedMediaType(format!(
+                "The file was uploaded as {declared} but its content is {content_type}"
+            )));
+        }
+
+        let data = if variants::SOURCE_TYPES.contains(&content_type) {
+            let upload = data.clone();
+            tokio::task::spawn_blocking(move || variants::prepare_original(content_type, &upload))
+                .await
+                .map_err(|err| storage_error(format!("preparing an image failed: {err}")))?
+                .map_or(data, Bytes::from)
+        } else {
+            data
+        };
+        let dimensions = sniff::dimensions(content_type, &data).and_then(|(width, height)| {
+            Some((i32::try_from(width).ok()?, i32::try_from(height).ok()?))
+        });
+        let variants_status = if self.config.variants.applies_to(content_type) {
+            VariantsStatus::Pending
+        } else {
+            VariantsStatus::Ready
+        };
+        let file = NewMediaFile {
+            owner,
+            filename: clean_filename(filename),
+            content_type: content_type.to_string(),
+            size: i64::try_from(data.len()).map_err(|_| AppError::InternalServerError)?,
+            content_hash: hex::encode(Sha256::digest(&data)),
+            width: dimensions.map(|(width, _)| width),
+            height: dimensions.map(|(_, height)| height),
+            variants_status,
+        };
+        self.blobs.put(&file.content_hash, data).await?;
+        let file = self.repository.create(&file).await?;
+        if file.variants_status == VariantsStatus::Pending
+            && let Err(err) = enqueue_variants(jobs, file.id).await
+        {
+            // The file is stored either way; its variants are enqueued again at startup.
+            tracing::warn!(
+                media_id = file.id,
+                "failed to enqueue media variants: {err}"
+            );
+        }
+        Ok(file)
+    }
+
+    /// Generates and stores the variants of a file, one image at a time per permit.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `AppError` if the image cannot be processed or the storage fails.
+    async fn generate_variants(&self, file: &MediaFile, data: Bytes) -> Result<(), AppError> {
+        let _permit = self
+            .processing
+            .acquire()
+            .await
+            .map_err(|_| AppError::InternalServerError)?;
+        let config = self.config.variants.clone();
+        let content_type = file.content_type.clone();
+        let generated =
+            tokio::task::spawn_blocking(move || variants::generate(&config, &content_type, &data))
+                .await
+                .map_err(|err| storage_error(format!("generating variants failed: {err}")))?
+                .map_err(|err| storage_error(format!("generating variants failed: {err:#}")))?;
+
+        for variant in generated {
+            let content_hash = hex::encode(Sha256::digest(&variant.data));
+            let new = NewMediaVariant {
+                media_id: file.id,
+                name: variant.name,
+                content_type: variant.content_type.to_string(),
+                size: i64::try_from(variant.data.len())
+                    .map_err(|_| AppError::InternalServerError)?,
+                content_hash,
+                width: i32::try_from(variant.width).map_err(|_| AppError::InternalServerError)?,
+                height: i32::try_from(variant.height).map_err(|_| AppError::InternalServerError)?,
+            };
+            self.blobs
+                .put(&new.content_hash, Bytes::from(variant.data))
+                .await?;
+            if let Err(err) = self.repository.add_variant(&new).await {
+                // The file was deleted meanwhile; so is the content only its variant had.
+                self.delete_unused_content(&new.content_hash).await?;
+                return Err(err);
+            }
+        }
+        Ok(())
+    }
+
+    /// Enqueues the jobs generating the variants of the files left pending that have
+    /// none, such as files uploaded before a failure to enqueue their job, and returns
+    /// how many were enqueued.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `AppError` if the storage fails.
+    pub async fn resume_variants(&self, jobs: &Jobs) -> Result<usize, AppError> {
+        let pending = self
+            .repository
+            .list(&MediaFilter {
+                variants_status: Some(VariantsStatus::Pending),
+                ..MediaFilter::default()
+            })
+            .await?;
+        let mut enqueued = 0;
+        for file in pending {
+            if enqueue_variants(jobs, file.id).await?.is_some() {
+                enqueued += 1;
+            }
+        }
+        Ok(enqueued)
+    }
+
+    /// Reads the whole content of a file, if it is stored.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `AppError` if the blob store fails.
+    async fn read_content(&self, file: &MediaFile) -> Result<Option<Bytes>, AppError> {
+        let size = u64::try_from(file.size).map_err(|_| AppError::InternalServerError)?;
+        let Some(stream) = self.blobs.get(&file.content_hash, 0..size).await? else {
+            return Ok(None);
+        };
+        let chunks: Vec<Bytes> = stream.try_collect().await.map_err(storage_error)?;
+        Ok(Some(Bytes::from(chunks.concat())))
+    }
+
+    /// Deletes the content stored under a hash unless a file or variant still has it.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `AppError` if the repository fails.
+    async fn delete_unused_content(&self, content_hash: &str) -> Result<(), AppError> {
+        if !self.repository.has_content(content_hash).await? {
+            // The records are gone either way; a blob left behind only takes up space.
+            if let Err(err) = self.blobs.delete(content_hash).await {
+                tracing::warn!("failed to delete media content: {err}");
+            }
+        }
+        Ok(())
+    }
+
+    /// Deletes a file with its variants, returning whether it existed. Their content is
+    /// deleted too, unless another file or variant has the same content.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `AppError` if the storage fails.
+    pub async fn delete(&self, id: i32) -> Result<bool, AppError> {
+        let Some(file) = self.repository.delete(id).await? else {
+            return Ok(false);
+        };
+        self.delete_unused_content(&file.content_hash).await?;
+        for variant in &file.variants {
+            self.delete_unused_content(&variant.content_hash).await?;
+        }
+        Ok(true)
+    }
+
+    /// Checks that the file to feature on a post exists and is an image, see
+    /// [`check_featured_image`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an `AppError::UnprocessableEntity` if no file has the ID or it is not an
+    /// image, or an `AppError` if the repository fails.
+    pub async fn check_featured_image(&self, id: i32) -> Result<(), AppError> {
+        check_featured_image(self.repository.as_ref(), id).await
+    }
+}
+
+/// Enqueues the job generating the variants of a file, unless it was enqueued before.
+///
+/// # Errors
+///
+/// Returns an `AppError` if the job cannot be enqueued.
+async fn enqueue_variants(jobs: &Jobs, media_id: i32) -> Result<Option<Job>, AppError> {
+    let unique_key = format!("{}:{media_id}", GenerateVariants::KIND);
+    jobs.enqueue_once::<GenerateVariants>(&media_id, unique_key)
+        .await
+}
+
+/// Generates the variants of a pending file, as the job of kind `media.variants`
+/// enqueued for it by [`Media::upload`], then sets its status to ready, or to failed if
+/// any variant could not be generated or its content is missing.
+///
+/// Jobs of files that are no longer pending, such as deleted files, succeed without
+/// generating anything. A job is only attempted again if the file or its content cannot
+/// be read.
+pub struct GenerateVariants {
+    /// Where the files and their variants are stored.
+    media: Media,
+}
+
+impl GenerateVariants {
+    /// Creates the handler generating the variants of the files of `media`.
+    pub fn new(media: Media) -> Self {
+        Self { media }
+    }
+}
+
+#[async_trait]
+impl JobHandler for GenerateVariants {
+    const KIND: &'static str = "media.variants";
+    const QUEUE: &'static str = VARIANTS_QUEUE;
+    type Payload = i32;
+
+    async fn run(&self, media_id: i32) -> Result<(), AppError> {
+        let media = &self.media;
+        let Some(file) = media.repository.get(media_id).await? else {
+            return Ok(());
+        };
+        if file.variants_status != VariantsStatus::Pending {
+            return Ok(());
+        }
+        let status = match media.read_content(&file).await? {
+            Some(data) => match media.generate_variants(&file, data).await {
+                Ok(()) => VariantsStatus::Ready,
+                Err(err) => {
+                    tracing::warn!(media_id, "failed to generate media variants: {err}");
+                    VariantsStatus::Failed
+                }
+            },
+            None => {
+                tracing::warn!(media_id, "content of media file is missing");
+                VariantsStatus::Failed
+            }
+        };
+        media
+            .repository
+            .set_variants_status(file.id, status)
+            .await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,279 @@
+use super::{MediaRepository, missing_reference, with_variants};
+use crate::{
+    error::AppError,
+    model::media::{
+        MediaFile, MediaFilter, MediaVariant, NewMediaFile, NewMediaVariant, VariantsStatus,
+    },
+    telemetry::span,
+};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use tracing::Instrument;
+
+/// Table of media files, reported on query spans.
+const TABLE: &str = "media";
+
+/// Table of attachments of media files to posts, reported on query spans.
+const ATTACHMENTS: &str = "post_media";
+
+/// Table of variants of media files, reported on query spans.
+const VARIANTS: &str = "media_variants";
+
+/// Database system reported on query spans.
+const SYSTEM: &str = "postgresql";
+
+/// A row of the `media` table, which a [`MediaFile`] completes with its variants.
+struct MediaRow {
+    /// Unique identifier of the file.
+    id: i32,
+    /// Who uploaded the file.
+    owner: Option<String>,
+    /// Name of the uploaded file.
+    filename: String,
+    /// MIME type recognized from the content.
+    content_type: String,
+    /// Size of the file in bytes.
+    size: i64,
+    /// Hex-encoded SHA-256 hash of the content.
+    content_hash: String,
+    /// Width of an image in pixels.
+    width: Option<i32>,
+    /// Height of an image in pixels.
+    height: Option<i32>,
+    /// When the file was uploaded.
+    created_at: DateTime<Utc>,
+    /// Status of the variants, as stored.
+    variants_status: String,
+}
+
+impl TryFrom<MediaRow> for MediaFile {
+    type Error = AppError;
+
+    fn try_from(row: MediaRow) -> Result<Self, Self::Error> {
+        let variants_status = VariantsStatus::try_from(row.variants_status).map_err(|err| {
+            tracing::error!(media_id = row.id, "{err}");
+            AppError::InternalServerError
+        })?;
+        Ok(Self {
+            id: row.id,
+            owner: row.owner,
+            filename: row.filename,
+            content_type: row.content_type,
+            size: row.size,
+            content_hash: row.content_hash,
+            width: row.width,
+            height: row.height,
+            created_at: row.created_at,
+            variants_status,
+            variants: Vec::new(),
+            srcset: Vec::new(),
+        })
+    }
+}
+
+/// [`MediaRepository`] backed by the `media`, `media_variants` and `post_media` tables.
+#[derive(Debug, Clone)]
+pub struct PgMediaRepository {
+    /// Pool of the primary database.
+    pool: PgPool,
+}
+
+impl PgMediaRepository {
+    /// Creates a repository running its queries on `pool`.
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    /// Converts rows into files and loads their variants.
+    async fn with_variants(&self, rows: Vec<MediaRow>) -> Result<Vec<MediaFile>, AppError> {
+        let files = rows
+            .into_iter()
+            .map(MediaFile::try_from)
+            .collect::<Result<Vec<_>, _>>()?;
+        if files.is_empty() {
+            return Ok(files);
+        }
+        let ids: Vec<i32> = files.iter().map(|file| file.id).collect();
+        let variants = sqlx::query_as!(
+            MediaVariant,
+            "SELECT * FROM media_variants WHERE media_id = ANY($1) ORDER BY id",
+            &ids
+        )
+        .fetch_all(&self.pool)
+        .instrument(span::db_query_on(SYSTEM, "SELECT", VARIANTS))
+        .await?;
+        Ok(with_variants(files, variants))
+    }
+}
+
+#[async_trait]
+impl MediaRepository for PgMediaRepository {
+    async fn create(&self, file: &NewMediaFile) -> Result<MediaFile, AppError> {
+        let created = sqlx::query_as!(
+            MediaRow,
+            r#"
+            INSERT INTO media (
+                owner, filename, content_type, size, content_hash, width, height, variants_status
+            )
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+            RETURNING *;
+            "#,
+            file.owner,
+            file.filename,
+            file.content_type,
+            file.size,
+            file.content_hash,
+            file.width,
+            file.height,
+            file.variants_status.as_str()
+        )
+        .fetch_one(&self.pool)
+        .instrument(span::db_query_on(SYSTEM, "INSERT", TABLE))
+        .await?;
+        created.try_into()
+    }
+
+    async fn get(&self, id: i32) -> Result<Option<MediaFile>, AppError> {
+        let row = sqlx::query_as!(MediaRow, "SELECT * FROM media WHERE id = $1", id)
+            .fetch_optional(&self.pool)
+            .instrument(span::db_query_on(SYSTEM, "SELECT", TABLE))
+            .await?;
+        Ok(self.with_variants(row.into_iter().collect()).await?.pop())
+    }
+
+    async fn list(&self, filter: &MediaFilter) -> Result<Vec<MediaFile>, AppError> {
+        let rows = sqlx::query_as!(
+            MediaRow,
+            r#"
+            SELECT * FROM media
+            WHERE ($1::TEXT IS NULL OR owner = $1)
+                AND ($2::TEXT IS NULL OR variants_status = $2)
+            ORDER BY id;
+            "#,
+            filter.owner,
+            filter.variants_status.map(VariantsStatus::as_str)
+        )
+        .fetch_all(&self.pool)
+        .instrument(span::db_query_on(SYSTEM, "SELECT", TABLE))
+        .await?;
+        self.with_variants(rows).await
+    }
+
+    async fn delete(&self, id: i32) -> Result<Option<MediaFile>, AppError> {
+        // The variants go with the file, so they are loaded first.
+        let Some(file) = self.get(id).await? else {
+            return Ok(None);
+        };
+        let result = sqlx::query!("DELETE FROM media WHERE id = $1", id)
+            .execute(&self.pool)
+            .instrument(span::db_query_on(SYSTEM, "DELETE", TABLE))
+            .await?;
+        Ok((result.rows_affected() > 0).then_some(file))
+    }
+
+    async fn has_content(&self, content_hash: &str) -> Result<bool, AppError> {
+        let exists = sqlx::query_scalar!(
+            r#"
+            SELECT EXISTS (SELECT 1 FROM media WHERE content_hash = $1)
+                OR EXISTS (SELECT 1 FROM media_variants WHERE content_hash = $1) AS "exists!";
+            "#,
+            content_hash
+        )
+        .fetch_one(&self.pool)
+        .instrument(span::db_query_on(SYSTEM, "SELECT", TABLE))
+        .await?;
+        Ok(exists)
+    }
+
+    async fn attach(&self, post_id: i32, media_id: i32) -> Result<(), AppError> {
+        sqlx::query!(
+            r#"
+            INSERT INTO post_media (post_id, media_id)
+            VALUES ($1, $2)
+            ON CONFLICT (post_id, media_id) DO NOTHING;
+            "#,
+            post_id,
+            media_id
+        )
+        .execute(&self.pool)
+        .instrument(span::db_query_on(SYSTEM, "INSERT", ATTACHMENTS))
+        .await
+        .map_err(missing_reference)?;
+        Ok(())
+    }
+
+    async fn detach(&self, post_id: i32, media_id: i32) -> Result<bool, AppError> {
+        let result = sqlx::query!(
+            "DELETE FROM post_media WHERE post_id = $1 AND media_id = $2",
+            post_id,
+            media_id
+        )
+        .execute(&self.pool)
+        .instrument(span::db_query_on(SYSTEM, "DELETE", ATTACHMENTS))
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn list_for_post(&self, post_id: i32) -> Result<Vec<MediaFile>, AppError> {
+        let rows = sqlx::query_as!(
+            MediaRow,
+            r#"
+            SELECT media.*
+            FROM post_media
+            JOIN media ON media.id = post_media.media_id
+            WHERE post_media.post_id = $1
+            ORDER BY post_media.created_at, media.id;
+            "#,
+            post_id
+        )
+        .fetch_all(&self.pool)
+        .instrument(span::db_query_on(SYSTEM, "SELECT", ATTACHMENTS))
+        .await?;
+        self.with_variants(rows).await
+    }
+
+    async fn add_variant(&self, variant: &NewMediaVariant) -> Result<MediaVariant, AppError> {
+        let added = sqlx::query_as!(
+            MediaVariant,
+            r#"
+            INSERT INTO media_variants (
+                media_id, name, content_type, size, content_hash, width, height
+            )
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            ON CONFLICT (media_id, name) DO UPDATE SET
+                content_type = EXCLUDED.content_type,
+                size = EXCLUDED.size,
+                content_hash = EXCLUDED.content_hash,
+                width = EXCLUDED.width,
+                height = EXCLUDED.height,
+                created_at = EXCLUDED.created_at
+            RETURNING *;
+            "#,
+            variant.media_id,
+            variant.name,
+            variant.content_type,
+            variant.size,
+            variant.content_hash,
+            variant.width,
+            variant.height
+        )
+        .fetch_one(&self.pool)
+        .instrument(span::db_query_on(SYSTEM, "INSERT", VARIANTS))
+        .await
+        .map_err(missing_reference)?;
+        Ok(added)
+    }
+
+    async fn set_variants_status(&self, id: i32, status: VariantsStatus) -> Result<bool, AppError> {
+        let result = sqlx::query!(
+            "UPDATE media SET variants_status = $2 WHERE id = $1",
+            id,
+            status.as_str()
+        )
+        .execute(&self.pool)
+        .instrument(span::db_query_on(SYSTEM, "UPDATE", TABLE))
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+}
@@ -0,0 +1,54 @@
+use std::ops::Range;
+
+/// What to send in response to a `Range` header.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ByteRange {
+    /// The whole content (`200 OK`).
+    Full,
+    /// The given bytes of the content (`206 Partial Content`).
+    Partial(Range<u64>),
+    /// Nothing, since the range starts past the end of the content
+    /// (`416 Range Not Satisfiable`).
+    Unsatisfiable,
+}
+
+impl ByteRange {
+    /// Interprets a `Range` header for content of `size` bytes.
+    ///
+    /// Only single byte ranges are served: `bytes=<first>-<last>`, `bytes=<first>-` and
+    /// `bytes=-<suffix length>`. A header asking for several ranges, or that is not a
+    /// valid byte range, is ignored, as HTTP allows, and the whole content is sent.
+    pub fn parse(header: &str, size: u64) -> Self {
+        let Some(spec) = header.trim().strip_prefix("bytes=") else {
+            return Self::Full;
+        };
+        let Some((first, last)) = spec.trim().split_once('-') else {
+            return Self::Full;
+        };
+        let number = |value: &str| -> Option<u64> {
+            if value.is_empty() || !value.bytes().all(|byte| byte.is_ascii_digit()) {
+                return None;
+            }
+            value.parse().ok()
+        };
+        match (first.trim(), last.trim()) {
+            ("", suffix) => match number(suffix) {
+                Some(0) => Self::Unsatisfiable,
+                Some(_) if size == 0 => Self::Unsatisfiable,
+                Some(length) => Self::Partial(size.saturating_sub(length)..size),
+                None => Self::Full,
+            },
+            (first, "") => match number(first) {
+                Some(first) if first >= size => Self::Unsatisfiable,
+                Some(first) => Self::Partial(first..size),
+                None => Self::Full,
+            },
+            (first, last) => match (number(first), number(last)) {
+                (Some(first), Some(last)) if first > last => Self::Full,
+                (Some(first), Some(_)) if first >= size => Self::Unsatisfiable,
+                (Some(first), Some(last)) => Self::Partial(first..last.saturating_add(1).min(size)),
+                _ => Self::Full,
+            },
+        }
+    }
+}
@@ -0,0 +1,118 @@
+/// MIME types that files can be recognized as.
+pub const CONTENT_TYPES: [&str; 5] = [
+    "image/png",
+    "image/jpeg",
+    "image/gif",
+    "image/webp",
+    "application/pdf",
+];
+
+/// Signatures of the recognized file types: the MIME type, the offset of the magic
+/// bytes and the magic bytes themselves.
+const SIGNATURES: [(&str, usize, &[u8]); 6] = [
+    ("image/png", 0, b"\x89PNG\r\n\x1a\n"),
+    ("image/jpeg", 0, b"\xff\xd8\xff"),
+    ("image/gif", 0, b"GIF87a"),
+    ("image/gif", 0, b"GIF89a"),
+    ("image/webp", 8, b"WEBP"),
+    ("application/pdf", 0, b"%PDF-"),
+];
+
+/// Returns the MIME type of a file recognized from its leading bytes, regardless of its
+/// name or the type it was uploaded as.
+pub fn content_type(data: &[u8]) -> Option<&'static str> {
+    SIGNATURES
+        .into_iter()
+        .find(|(content_type, offset, magic)| {
+            data.get(*offset..offset + magic.len()) == Some(*magic)
+                && (*content_type != "image/webp" || data.starts_with(b"RIFF"))
+        })
+        .map(|(content_type, _, _)| content_type)
+}
+
+/// Returns the width and height in pixels of an image, read from its header.
+pub fn dimensions(content_type: &str, data: &[u8]) -> Option<(u32, u32)> {
+    match content_type {
+        "image/png" => png_dimensions(data),
+        "image/jpeg" => jpeg_dimensions(data),
+        "image/gif" => Some((u32::from(u16_le(data, 6)?), u32::from(u16_le(data, 8)?))),
+        "image/webp" => webp_dimensions(data),
+        _ => None,
+    }
+}
+
+/// Reads a little-endian `u16` at `offset`.
+fn u16_le(data: &[u8], offset: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(
+        data.get(offset..offset + 2)?.try_into().ok()?,
+    ))
+}
+
+/// Reads a big-endian `u16` at `offset`.
+fn u16_be(data: &[u8], offset: usize) -> Option<u16> {
+    Some(u16::from_be_bytes(
+        data.get(offset..offset + 2)?.try_into().ok()?,
+    ))
+}
+
+/// Reads a little-endian 24-bit integer at `offset`.
+fn u24_le(data: &[u8], offset: usize) -> Option<u32> {
+    let bytes = data.get(offset..offset + 3)?;
+    Some(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], 0]))
+}
+
+/// Reads the dimensions from the `IHDR` chunk, which PNG requires to come first.
+fn png_dimensions(data: &[u8]) -> Option<(u32, u32)> {
+    if data.get(12..16)? != b"IHDR" {
+        return None;
+    }
+    let width = u32::from_be_bytes(data.get(16..20)?.try_into().ok()?);
+    let height = u32::from_be_bytes(data.get(20..24)?.try_into().ok()?);
+    Some((width, height))
+}
+
+/// Reads the dimensions from the first start-of-frame segment of a JPEG, skipping the
+/// segments before it.
+fn jpeg_dimensions(data: &[u8]) -> Option<(u32, u32)> {
+    let mut offset = 2;
+    loop {
+        if *data.get(offset)? != 0xff {
+            return None;
+        }
+        let marker = *data.get(offset + 1)?;
+        match marker {
+            // Fill bytes before a marker.
+            0xff => offset += 1,
+            // Markers without a segment.
+            0x01 | 0xd0..=0xd7 => offset += 2,
+            // Start of frame, except DHT (0xc4), JPG (0xc8) and DAC (0xcc).
+            0xc0..=0xcf if !matches!(marker, 0xc4 | 0xc8 | 0xcc) => {
+                let height = u16_be(data, offset + 5)?;
+                let width = u16_be(data, offset + 7)?;
+                return Some((u32::from(width), u32::from(height)));
+            }
+            // Start of scan or end of image before any frame.
+            0xda | 0xd9 => return None,
+            _ => offset += 2 + usize::from(u16_be(data, offset + 2)?),
+        }
+    }
+}
+
+/// Reads the dimensions from the first chunk of a WebP, in any of its three formats.
+fn webp_dimensions(data: &[u8]) -> Option<(u32, u32)> {
+    match data.get(12..16)? {
+        // Lossy: 14-bit dimensions after the frame tag and start code.
+        b"VP8 " => Some((
+            u32::from(u16_le(data, 26)? & 0x3fff),
+            u32::from(u16_le(data, 28)? & 0x3fff),
+        )),
+        // Lossless: 14-bit dimensions minus one, packed after the signature byte.
+        b"VP8L" => {
+            let bits = u32::from_le_bytes(data.get(21..25)?.try_into().ok()?);
+            Some(((bits & 0x3fff) + 1, ((bits >> 14) & 0x3fff) + 1))
+        }
+        // Extended: 24-bit dimensions minus one.
+        b"VP8X" => Some((u24_le(data, 24)? + 1, u24_le(data, 27)? + 1)),
+        _ => None,
+    }
+}
@@ -0,0 +1,225 @@
+use super::{MediaRepository, missing_reference, with_variants};
+use crate::{
+    error::AppError,
+    model::media::{
+        MediaFile, MediaFilter, MediaVariant, NewMediaFile, NewMediaVariant, VariantsStatus,
+    },
+    telemetry::span,
+};
+use async_trait::async_trait;
+use sqlx::SqlitePool;
+use tracing::Instrument;
+
+/// Table of media files, reported on query spans.
+const TABLE: &str = "media";
+
+/// Table of attachments of media files to posts, reported on query spans.
+const ATTACHMENTS: &str = "post_media";
+
+/// Table of variants of media files, reported on query spans.
+const VARIANTS: &str = "media_variants";
+
+/// Database system reported on query spans.
+const SYSTEM: &str = "sqlite";
+
+/// [`MediaRepository`] backed by a SQLite database migrated from `migrations/sqlite`.
+///
+/// Attachments rely on foreign keys, which SQLx enables on every connection.
+#[derive(Debug, Clone)]
+pub struct SqliteMediaRepository {
+    /// Pool the queries run on.
+    pool: SqlitePool,
+}
+
+impl SqliteMediaRepository {
+    /// Creates a repository running its queries on `pool`.
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool }
+    }
+
+    /// Loads the variants of the given files.
+    async fn with_variants(&self, files: Vec<MediaFile>) -> Result<Vec<MediaFile>, AppError> {
+        if files.is_empty() {
+            return Ok(files);
+        }
+        let ids: Vec<i32> = files.iter().map(|file| file.id).collect();
+        let variants = sqlx::query_as(
+            r#"
+            SELECT * FROM media_variants
+            WHERE media_id IN (SELECT value FROM json_each(?1))
+            ORDER BY id;
+            "#,
+        )
+        .bind(serde_json::to_string(&ids).map_err(|_| AppError::InternalServerError)?)
+        .fetch_all(&self.pool)
+        .instrument(span::db_query_on(SYSTEM, "SELECT", VARIANTS))
+        .await?;
+        Ok(with_variants(files, variants))
+    }
+}
+
+#[async_trait]
+impl MediaRepository for SqliteMediaRepository {
+    async fn create(&self, file: &NewMediaFile) -> Result<MediaFile, AppError> {
+        let created = sqlx::query_as(
+            r#"
+            INSERT INTO media (
+                owner, filename, content_type, size, content_hash, width, height, variants_status
+            )
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)
+            RETURNING *;
+            "#,
+        )
+        .bind(&file.owner)
+        .bind(&file.filename)
+        .bind(&file.content_type)
+        .bind(file.size)
+        .bind(&file.content_hash)
+        .bind(file.width)
+        .bind(file.height)
+        .bind(file.variants_status.as_str())
+        .fetch_one(&self.pool)
+        .instrument(span::db_query_on(SYSTEM, "INSERT", TABLE))
+        .await?;
+        Ok(created)
+    }
+
+    async fn get(&self, id: i32) -> Result<Option<MediaFile>, AppError> {
+        let file = sqlx::query_as("SELECT * FROM media WHERE id = ?1")
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .instrument(span::db_query_on(SYSTEM, "SELECT", TABLE))
+            .await?;
+        Ok(self.with_variants(file.into_iter().collect()).await?.pop())
+    }
+
+    async fn list(&self, filter: &MediaFilter) -> Result<Vec<MediaFile>, AppError> {
+        let files = sqlx::query_as(
+            r#"
+            SELECT * FROM media
+            WHERE (?1 IS NULL OR owner = ?1)
+                AND (?2 IS NULL OR variants_status = ?2)
+            ORDER BY id;
+            "#,
+        )
+        .bind(&filter.owner)
+        .bind(filter.variants_status.map(VariantsStatus::as_str))
+        .fetch_all(&self.pool)
+        .instrument(span::db_query_on(SYSTEM, "SELECT", TABLE))
+        .await?;
+        self.with_variants(files).await
+    }
+
+    async fn delete(&self, id: i32) -> Result<Option<MediaFile>, AppError> {
+        // The variants go with the file, so they are loaded first.
+        let Some(file) = self.get(id).await? else {
+            return Ok(None);
+        };
+        let result = sqlx::query("DELETE FROM media WHERE id = ?1")
+            .bind(id)
+            .execute(&self.pool)
+            .instrument(span::db_query_on(SYSTEM, "DELETE", TABLE))
+            .await?;
+        Ok((result.rows_affected() > 0).then_some(file))
+    }
+
+    async fn has_content(&self, content_hash: &str) -> Result<bool, AppError> {
+        let exists = sqlx::query_scalar(
+            r#"
+            SELECT EXISTS (SELECT 1 FROM media WHERE content_hash = ?1)
+                OR EXISTS (SELECT 1 FROM media_variants WHERE content_hash = ?1);
+            "#,
+        )
+        .bind(content_hash)
+        .fetch_one(&self.pool)
+        .instrument(span::db_query_on(SYSTEM, "SELECT", TABLE))
+        .await?;
+        Ok(exists)
+    }
+
+    async fn attach(&self, post_id: i32, media_id: i32) -> Result<(), AppError> {
+        sqlx::query(
+            r#"
+            INSERT INTO post_media (post_id, media_id)
+            VALUES (?1, ?2)
+            ON CONFLICT (post_id, media_id) DO NOTHING;
+            "#,
+        )
+        .bind(post_id)
+        .bind(media_id)
+        .execute(&self.pool)
+        .instrument(span::db_query_on(SYSTEM, "INSERT", ATTACHMENTS))
+        .await
+        .map_err(missing_reference)?;
+        Ok(())
+    }
+
+    async fn detach(&self, post_id: i32, media_id: i32) -> Result<bool, AppError> {
+        let result = sqlx::query("DELETE FROM post_media WHERE post_id = ?1 AND media_id = ?2")
+            .bind(post_id)
+            .bind(media_id)
+            .execute(&self.pool)
+            .instrument(span::db_query_on(SYSTEM, "DELETE", ATTACHMENTS))
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn list_for_post(&self, post_id: i32) -> Result<Vec<MediaFile>, AppError> {
+        // Timestamps have millisecond precision, so the rowid keeps ties in attach order.
+        let files = sqlx::query_as(
+            r#"
+            SELECT media.*
+            FROM post_media
+            JOIN media ON media.id = post_media.media_id
+            WHERE post_media.post_id = ?1
+            ORDER BY post_media.created_at, post_media.rowid;
+            "#,
+        )
+        .bind(post_id)
+        .fetch_all(&self.pool)
+        .instrument(span::db_query_on(SYSTEM, "SELECT", ATTACHMENTS))
+        .await?;
+        self.with_variants(files).await
+    }
+
+    async fn add_variant(&self, variant: &NewMediaVariant) -> Result<MediaVariant, AppError> {
+        let added = sqlx::query_as(
+            r#"
+            INSERT INTO media_variants (
+                media_id, name, content_type, size, content_hash, width, height
+            )
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+            ON CONFLICT (media_id, name) DO UPDATE SET
+                content_type = excluded.content_type,
+                size = excluded.size,
+                content_hash = excluded.content_hash,
+                width = excluded.width,
+                height = excluded.height,
+                created_at = excluded.created_at
+            RETURNING *;
+            "#,
+        )
+        .bind(variant.media_id)
+        .bind(&variant.name)
+        .bind(&variant.content_type)
+        .bind(variant.size)
+        .bind(&variant.content_hash)
+        .bind(variant.width)
+        .bind(variant.height)
+        .fetch_one(&self.pool)
+        .instrument(span::db_query_on(SYSTEM, "INSERT", VARIANTS))
+        .await
+        .map_err(missing_reference)?;
+        Ok(added)
+    }
+
+    async fn set_variants_status(&self, id: i32, status: VariantsStatus) -> Result<bool, AppError> {
+        let result = sqlx::query("UPDATE media SET variants_status = ?2 WHERE id = ?1")
+            .bind(id)
+            .bind(status.as_str())
+            .execute(&self.pool)
+            .instrument(span::db_query_on(SYSTEM, "UPDATE", TABLE))
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+}
@@ -0,0 +1,141 @@
+/// JPEG markers of segments that carry metadata: `APP1` (EXIF and XMP), `APP13` (IPTC)
+/// and `COM` (comments).
+const JPEG_METADATA: [u8; 3] = [0xe1, 0xed, 0xfe];
+
+/// PNG chunks that carry metadata: EXIF, text of any kind and the modification time.
+const PNG_METADATA: [&[u8; 4]; 5] = [b"eXIf", b"tEXt", b"zTXt", b"iTXt", b"tIME"];
+
+/// WebP chunks that carry metadata.
+const WEBP_METADATA: [&[u8; 4]; 2] = [b"EXIF", b"XMP "];
+
+/// Flags of the `VP8X` chunk announcing EXIF and XMP chunks.
+const VP8X_METADATA_FLAGS: u8 = 0x08 | 0x04;
+
+/// Returns a copy of an image without the metadata that may tell where, when or with
+/// what it was taken, such as EXIF with its GPS coordinates, XMP, IPTC and comments.
+/// The pixel data, color profiles and any other parts are copied unchanged.
+///
+/// Returns `None` for other types than PNG, JPEG and WebP, and for images whose
+/// structure cannot be followed.
+pub fn strip_metadata(content_type: &str, data: &[u8]) -> Option<Vec<u8>> {
+    match content_type {
+        "image/jpeg" => strip_jpeg(data),
+        "image/png" => strip_png(data),
+        "image/webp" => strip_webp(data),
+        _ => None,
+    }
+}
+
+/// Reads a big-endian `u16` at `offset`.
+fn u16_be(data: &[u8], offset: usize) -> Option<u16> {
+    Some(u16::from_be_bytes(
+        data.get(offset..offset + 2)?.try_into().ok()?,
+    ))
+}
+
+/// Reads a big-endian `u32` at `offset` as a length.
+fn u32_be(data: &[u8], offset: usize) -> Option<usize> {
+    usize::try_from(u32::from_be_bytes(
+        data.get(offset..offset + 4)?.try_into().ok()?,
+    ))
+    .ok()
+}
+
+/// Reads a little-endian `u32` at `offset` as a length.
+fn u32_le(data: &[u8], offset: usize) -> Option<usize> {
+    usize::try_from(u32::from_le_bytes(
+        data.get(offset..offset + 4)?.try_into().ok()?,
+    ))
+    .ok()
+}
+
+/// Drops the metadata segments before the start of the scan, where cameras and editors
+/// write them; the scan and everything after it is copied as is.
+fn strip_jpeg(data: &[u8]) -> Option<Vec<u8>> {
+    let mut stripped = Vec::with_capacity(data.len());
+    stripped.extend_from_slice(data.get(..2)?);
+    let mut offset = 2;
+    loop {
+        if *data.get(offset)? != 0xff {
+            return None;
+        }
+        let marker = *data.get(offset + 1)?;
+        match marker {
+            // Fill bytes before a marker.
+            0xff => offset += 1,
+            // Markers without a length: TEM, RST0-7 and EOI.
+            0x01 | 0xd0..=0xd7 | 0xd9 => {
+                stripped.extend_from_slice(data.get(offset..offset + 2)?);
+                if marker == 0xd9 {
+                    return Some(stripped);
+                }
+                offset += 2;
+            }
+            // Start of scan.
+            0xda => {
+                stripped.extend_from_slice(data.get(offset..)?);
+                return Some(stripped);
+            }
+            _ => {
+                let length = usize::from(u16_be(data, offset + 2)?);
+                if length < 2 {
+                    return None;
+                }
+                let segment = data.get(offset..offset + 2 + length)?;
+                if !JPEG_METADATA.contains(&marker) {
+                    stripped.extend_from_slice(segment);
+                }
+                offset += segment.len();
+            }
+        }
+    }
+}
+
+/// Drops the metadata chunks, copying the others up to `IEND`.
+fn strip_png(data: &[u8]) -> Option<Vec<u8>> {
+    let mut stripped = Vec::with_capacity(data.len());
+    stripped.extend_from_slice(data.get(..8)?);
+    let mut offset = 8;
+    loop {
+        let length = u32_be(data, offset)?;
+        // Length, type, data and CRC.
+        let chunk = data.get(offset..offset.checked_add(12)?.checked_add(length)?)?;
+        let kind = chunk.get(4..8)?;
+        if !PNG_METADATA.iter().any(|metadata| kind == *metadata) {
+            stripped.extend_from_slice(chunk);
+        }
+        offset += chunk.len();
+        if kind == b"IEND" {
+            return Some(stripped);
+        }
+    }
+}
+
+/// Drops the metadata chunks and their flags in the `VP8X` chunk, then updates the size
+/// in the RIFF header.
+fn strip_webp(data: &[u8]) -> Option<Vec<u8>> {
+    let end = u32_le(data, 4)?.checked_add(8)?.min(data.len());
+    let mut stripped = Vec::with_capacity(end);
+    stripped.extend_from_slice(data.get(..12)?);
+    let mut offset = 12;
+    while offset < end {
+        let length = u32_le(data, offset + 4)?;
+        // Chunks are padded to an even length.
+        let padded = length.checked_add(length % 2)?;
+        let chunk = data.get(offset..offset.checked_add(8)?.checked_add(padded)?)?;
+        let kind = chunk.get(..4)?;
+        if kind == b"VP8X" {
+            let start = stripped.len();
+            stripped.extend_from_slice(chunk);
+            if let Some(flags) = stripped.get_mut(start + 8) {
+                *flags &= !VP8X_METADATA_FLAGS;
+            }
+        } else if !WEBP_METADATA.iter().any(|metadata| kind == *metadata) {
+            stripped.extend_from_slice(chunk);
+        }
+        offset += chunk.len();
+    }
+    let size = u32::try_from(stripped.len() - 8).ok()?;
+    stripped.get_mut(4..8)?.copy_from_slice(&size.to_le_bytes());
+    Some(stripped)
+}
@@ -0,0 +1,327 @@
+use super::strip;
+use crate::config;
+use anyhow::{Context, Result, bail};
+use image::{
+    DynamicImage, ImageDecoder, ImageReader,
+    codecs::{avif::AvifEncoder, jpeg::JpegEncoder, png::PngEncoder},
+    imageops::FilterType,
+    metadata::Orientation,
+};
+use std::io::Cursor;
+
+/// MIME types of the images variants are generated from. GIFs are left alone, since
+/// their variants would lose the animation.
+pub const SOURCE_TYPES: [&str; 3] = ["image/png", "image/jpeg", "image/webp"];
+
+/// Name of thumbnails, followed by the extension of their format.
+pub const THUMBNAIL: &str = "thumbnail";
+
+/// Quality of uploaded images that have to be re-encoded to be rotated upright.
+const ORIGINAL_QUALITY: u8 = 90;
+
+/// Speed of the AVIF encoder, from 1 (slowest, smallest) to 10.
+const AVIF_SPEED: u8 = 8;
+
+/// A format variants are encoded in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VariantFormat {
+    /// The format of the uploaded image.
+    Original,
+    /// JPEG, without transparency.
+    Jpeg,
+    /// Lossless PNG.
+    Png,
+    /// Lossy WebP.
+    Webp,
+    /// Lossy AVIF.
+    Avif,
+}
+
+impl VariantFormat {
+    /// Returns the format of an image of the given MIME type, if variants can be
+    /// encoded in it.
+    fn of(content_type: &str) -> Option<Self> {
+        match content_type {
+            "image/jpeg" => Some(Self::Jpeg),
+            "image/png" => Some(Self::Png),
+            "image/webp" => Some(Self::Webp),
+            "image/avif" => Some(Self::Avif),
+            _ => None,
+        }
+    }
+
+    /// Replaces [`Self::Original`] with the format of an image of the given MIME type.
+    fn resolve(self, content_type: &str) -> Option<Self> {
+        match self {
+            Self::Original => Self::of(content_type),
+            format => Some(format),
+        }
+    }
+
+    /// Returns the MIME type of the format, or `None` for [`Self::Original`].
+    pub fn content_type(self) -> Option<&'static str> {
+        match self {
+            Self::Original => None,
+            Self::Jpeg => Some("image/jpeg"),
+            Self::Png => Some("image/png"),
+            Self::Webp => Some("image/webp"),
+            Self::Avif => Some("image/avif"),
+        }
+    }
+
+    /// Returns the file extension of the format, or `None` for [`Self::Original`].
+    fn extension(self) -> Option<&'static str> {
+        match self {
+            Self::Original => None,
+            Self::Jpeg => Some("jpg"),
+            Self::Png => Some("png"),
+            Self::Webp => Some("webp"),
+            Self::Avif => Some("avif"),
+        }
+    }
+}
+
+impl std::str::FromStr for VariantFormat {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "original" => Ok(Self::Original),
+            "jpeg" | "jpg" => Ok(Self::Jpeg),
+            "png" => Ok(Self::Png),
+            "webp" => Ok(Self::Webp),
+            "avif" => Ok(Self::Avif),
+            _ => Err(format!(
+                "{value:?} is not one of original, jpeg, png, webp, avif"
+            )),
+        }
+    }
+}
+
+/// Configuration of the variants generated from uploaded images, read from the
+/// environment.
+///
+/// | Variable                    | Default             | Description                                          |
+/// |-----------------------------|---------------------|------------------------------------------------------|
+/// | `MEDIA_VARIANT_WIDTHS`      | `320,640,1024,1920` | Comma-separated widths in pixels images are resized to. |
+/// | `MEDIA_VARIANT_FORMATS`     | `webp,original`     | Comma-separated formats of the variants: `original`, `jpeg`, `png`, `webp` or `avif`. Empty disables variants. |
+/// | `MEDIA_THUMBNAIL_SIZE`      | `200`               | Side of square thumbnails in pixels; `0` disables them. |
+/// | `MEDIA_VARIANT_QUALITY`     | `80`                | Quality of JPEG, WebP and AVIF variants, from 1 to 100. |
+/// | `MEDIA_VARIANT_CONCURRENCY` | `2`                 | How many images are processed at the same time.     |
+#[derive(Debug, Clone)]
+pub struct VariantConfig {
+    /// Widths images are resized to, ascending. Widths not below the width of an image
+    /// are skipped for it.
+    pub widths: Vec<u32>,
+    /// Formats each size is encoded in.
+    pub formats: Vec<VariantFormat>,
+    /// Side of square thumbnails, or `0` for none.
+    pub thumbnail_size: u32,
+    /// Quality of lossy formats, from 1 to 100.
+    pub quality: u8,
+    /// How many images are processed at the same time.
+    pub concurrency: usize,
+}
+
+impl VariantConfig {
+    /// Builds the configuration from environment variables.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if any of the variables holds an invalid value.
+    pub fn from_env() -> Result<Self> {
+        let mut widths = list("MEDIA_VARIANT_WIDTHS", "320,640,1024,1920")?;
+        if widths.contains(&0) {
+            bail!("Invalid value for MEDIA_VARIANT_WIDTHS: widths must be positive");
+        }
+        widths.sort_unstable();
+        widths.dedup();
+        let quality = config::parse_or("MEDIA_VARIANT_QUALITY", 80)?;
+        if !(1..=100).contains(&quality) {
+            bail!("Invalid value for MEDIA_VARIANT_QUALITY: must be between 1 and 100");
+        }
+        let concurrency = config::parse_or("MEDIA_VARIANT_CONCURRENCY", 2)?;
+        if concurrency == 0 {
+            bail!("Invalid value for MEDIA_VARIANT_CONCURRENCY: must be positive");
+        }
+        Ok(Self {
+            widths,
+            formats: list("MEDIA_VARIANT_FORMATS", "webp,original")?,
+            thumbnail_size: config::parse_or("MEDIA_THUMBNAIL_SIZE", 200)?,
+            quality,
+            concurrency,
+        })
+    }
+
+    /// Returns whether variants are generated for images of the given MIME type.
+    pub fn applies_to(&self, content_type: &str) -> bool {
+        !self.formats.is_empty() && SOURCE_TYPES.contains(&content_type)
+    }
+}
+
+/// Reads a comma-separated list from an environment variable; an empty value is an
+/// empty list.
+fn list<T>(key: &str, default: &str) -> Result<Vec<T>>
+where
+    T: std::str::FromStr,
+    T::Err: std::fmt::Display,
+{
+    config::var_or(key, default)
+        .split(',')
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+        .map(|item| {
+            item.to_ascii_lowercase()
+                .parse()
+                .map_err(|err| anyhow::anyhow!("Invalid value for {key}: {err}"))
+        })
+        .collect()
+}
+
+/// A variant generated from an image.
+#[derive(Debug, Clone)]
+pub struct Variant {
+    /// Name of the variant, such as `640w.webp` or `thumbnail.jpg`.
+    pub name: String,
+    /// MIME type of the variant.
+    pub content_type: &'static str,
+    /// Width in pixels.
+    pub width: u32,
+    /// Height in pixels.
+    pub height: u32,
+    /// Encoded content.
+    pub data: Vec<u8>,
+}
+
+/// Decodes an image and reads the orientation recorded in its EXIF metadata.
+fn decode(data: &[u8]) -> Result<(DynamicImage, Orientation)> {
+    let mut decoder = ImageReader::new(Cursor::new(data))
+        .with_guessed_format()?
+        .into_decoder()?;
+    let orientation = decoder.orientation()?;
+    Ok((DynamicImage::from_decoder(decoder)?, orientation))
+}
+
+/// Encodes an image in a format other than [`VariantFormat::Original`].
+fn encode(image: &DynamicImage, format: VariantFormat, quality: u8) -> Result<Vec<u8>> {
+    let mut data = Vec::new();
+    let has_alpha = image.color().has_alpha();
+    match format {
+        VariantFormat::Original => bail!("the original format must be resolved first"),
+        VariantFormat::Jpeg => image
+            .to_rgb8()
+            .write_with_encoder(JpegEncoder::new_with_quality(&mut data, quality))?,
+        VariantFormat::Png => image.write_with_encoder(PngEncoder::new(&mut data))?,
+        VariantFormat::Webp => {
+            let (width, height) = (image.width(), image.height());
+            let encoded = if has_alpha {
+                webp::Encoder::from_rgba(&image.to_rgba8(), width, height)
+                    .encode(f32::from(quality))
+            } else {
+                webp::Encoder::from_rgb(&image.to_rgb8(), width, height).encode(f32::from(quality))
+            };
+            data.extend_from_slice(&encoded);
+        }
+        VariantFormat::Avif => {
+            let encoder = AvifEncoder::new_with_speed_quality(&mut data, AVIF_SPEED, quality);
+            if has_alpha {
+                image.to_rgba8().write_with_encoder(encoder)?;
+            } else {
+                image.to_rgb8().write_with_encoder(encoder)?;
+            }
+        }
+    }
+    Ok(data)
+}
+
+/// Returns the content to store for an uploaded image: rotated upright if its EXIF
+/// orientation asks for it, and without metadata such as EXIF and its GPS coordinates.
+///
+/// Only images that have to be rotated are re-encoded; the others keep their pixel data
+/// as is. Returns `None` to store the upload unchanged, because it is not a PNG, JPEG
+/// or WebP image or its structure cannot be followed.
+pub fn prepare_original(content_type: &str, data: &[u8]) -> Option<Vec<u8>> {
+    let rotated = ImageReader::new(Cursor::new(data))
+        .with_guessed_format()
+        .ok()
+        .and_then(|reader| reader.into_decoder().ok())
+        .and_then(|mut decoder| decoder.orientation().ok())
+        .is_some_and(|orientation| orientation != Orientation::NoTransforms);
+    if rotated {
+        let upright = decode(data).and_then(|(mut image, orientation)| {
+            image.apply_orientation(orientation);
+            let format = VariantFormat::of(content_type).context("unsupported type")?;
+            encode(&image, format, ORIGINAL_QUALITY)
+        });
+        match upright {
+            Ok(upright) => return Some(upright),
+            Err(err) => tracing::debug!("failed to rotate an uploaded image: {err}"),
+        }
+    }
+    strip::strip_metadata(content_type, data)
+}
+
+/// Generates the variants of an image of the given MIME type, as configured.
+///
+/// Each width below the width of the image, and the width of the image itself unless
+/// the format is that of the image, is encoded in every format, from the smallest width
+/// up. Thumbnails are cropped to a square no larger than the image and come last.
+///
+/// # Errors
+///
+/// Returns an error if the image cannot be decoded or a variant cannot be encoded.
+pub fn generate(config: &VariantConfig, content_type: &str, data: &[u8]) -> Result<Vec<Variant>> {
+    let (mut image, orientation) = decode(data)?;
+    image.apply_orientation(orientation);
+    let (width, height) = (image.width(), image.height());
+    let mut formats: Vec<VariantFormat> = Vec::new();
+    for format in config
+        .formats
+        .iter()
+        .filter_map(|format| format.resolve(content_type))
+    {
+        if !formats.contains(&format) {
+            formats.push(format);
+        }
+    }
+
+    let mut sizes: Vec<(String, DynamicImage, Vec<VariantFormat>)> = Vec::new();
+    for &target in config.widths.iter().filter(|&&target| target < width) {
+        let scaled = u64::from(height) * u64::from(target) / u64::from(width);
+        let target_height = u32::try_from(scaled.max(1))?;
+        let resized = image.resize_exact(target, target_height, FilterType::Lanczos3);
+        sizes.push((format!("{target}w"), resized, formats.clone()));
+    }
+    // The image itself serves its full width in its own format.
+    let other_formats: Vec<VariantFormat> = formats
+        .iter()
+        .copied()
+        .filter(|format| format.content_type() != Some(content_type))
+        .collect();
+    if !other_formats.is_empty() {
+        sizes.push((format!("{width}w"), image.clone(), other_formats));
+    }
+    if config.thumbnail_size > 0 {
+        let side = config.thumbnail_size.min(width).min(height);
+        let thumbnail = image.resize_to_fill(side, side, FilterType::Lanczos3);
+        sizes.push((THUMBNAIL.to_string(), thumbnail, formats.clone()));
+    }
+
+    let mut variants = Vec::new();
+    for (label, image, formats) in sizes {
+        for format in formats {
+            let (Some(content_type), Some(extension)) = (format.content_type(), format.extension())
+            else {
+                continue;
+            };
+            variants.push(Variant {
+                name: format!("{label}.{extension}"),
+                content_type,
+                width: image.width(),
+                height: image.height(),
+                data: encode(&image, format, config.quality)?,
+            });
+        }
+    }
+    Ok(variants)
+}
@@ -1,28 +1,304 @@
 use chrono::{DateTime, Utc};
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize};
 use sqlx::FromRow;
+use utoipa::ToSchema;
+use validator::{Validate, ValidationError};
+
+/// Longest accepted slug, in bytes.
+pub const MAX_SLUG_LENGTH: usize = 100;
+
+/// Returns whether `slug` is a valid slug: lowercase ASCII letters and digits in
+/// groups separated by single hyphens, at most [`MAX_SLUG_LENGTH`] bytes long.
+pub fn is_valid_slug(slug: &str) -> bool {
+    slug.len() <= MAX_SLUG_LENGTH
+        && slug.split('-').all(|part| {
+            !part.is_empty()
+                && part
+                    .bytes()
+                    .all(|byte| byte.is_ascii_lowercase() || byte.is_ascii_digit())
+        })
+}
+
+/// Turns arbitrary text, such as a title or file name, into a valid slug.
+///
+/// ASCII letters are lowercased, other characters become hyphens, and the result is
+/// truncated to [`MAX_SLUG_LENGTH`]. Returns `None` if no letter or digit remains.
+pub fn slugify(text: &str) -> Option<String> {
+    let mut slug = String::new();
+    for part in text
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|part| !part.is_empty())
+    {
+        if slug.len() + 1 + part.len() > MAX_SLUG_LENGTH {
+            break;
+        }
+        if !slug.is_empty() {
+            slug.push('-');
+        }
+        slug.push_str(&part.to_ascii_lowercase());
+    }
+    (!slug.is_empty()).then_some(slug)
+}
+
+/// Deserializes an optional field of a [`BlogPostBody`] that an update may clear.
+///
+/// With `#[serde(default)]`, a field left out is `None`, a field sent as `null` is
+/// `Some(None)` and a field sent with a value is `Some(Some(value))`.
+fn double_option<'de, T, D>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+where
+    T: Deserialize<'de>,
+    D: Deserializer<'de>,
+{
+    Option::<T>::deserialize(deserializer).map(Some)
+}
+
+/// Validates the slug of a [`BlogPostBody`].
+fn validate_slug(slug: &str) -> Result<(), ValidationError> {
+    if is_valid_slug(slug) {
+        Ok(())
+    } else {
+        Err(ValidationError::new("slug").with_message(
+            "Slug must be lowercase letters and digits separated by single hyphens".into(),
+        ))
+    }
+}
 
 /// Represents a blog post stored in the database.
-#[derive(Debug, Serialize, Deserialize, FromRow)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, FromRow, ToSchema)]
 pub struct BlogPost {
     /// Unique identifier for the blog post.
+    #[schema(example = 1)]
     pub id: i32,
 
     /// Title of the blog post.
+    #[schema(example = "My First Post")]
     pub title: String,
 
     /// Content of the blog post.
     pub content: String,
 
     /// Category to which the blog post belongs.
+    #[schema(example = "Rust")]
     pub category: String,
 
     /// List of tags associated with the blog post.
+    #[schema(example = json!(["rust", "async"]))]
     pub tags: Vec<String>,
 
+    /// Unique, URL-friendly name of the blog post, if it has one.
+    #[schema(example = "my-first-post")]
+    pub slug: Option<String>,
+
+    /// Name of the author of the blog post, if known.
+    #[schema(example = "Jane Doe")]
+    pub author: Option<String>,
+
+    /// Whether the blog post is a draft, not yet meant to be published.
+    pub draft: bool,
+
+    /// When the blog post was, or is to be, published.
+    pub published_at: Option<DateTime<Utc>>,
+
+    /// ID of the media file shown as the cover of the blog post, if it has one.
+    #[schema(example = 3)]
+    pub featured_image_id: Option<i32>,
+
+    /// Title shown by search engines and shared links instead of the title.
+    #[schema(example = "My First Post: Getting Started with Rust")]
+    pub meta_title: Option<String>,
+
+    /// Summary shown by search engines and shared links.
+    pub meta_description: Option<String>,
+
+    /// Preferred URL of the blog post, if it differs from its URL on the site.
+    #[schema(example = "https://example.com/my-first-post")]
+    pub canonical_url: Option<String>,
+
+    /// Whether search engines are asked not to index the blog post.
+    pub noindex: bool,
+
     /// Timestamp when the blog post was created.
     pub created_at: Option<DateTime<Utc>>,
 
     /// Timestamp when the blog post was last updated.
     pub updated_at: Option<DateTime<Utc>>,
 }
+
+impl BlogPost {
+    /// Returns whether the post is published at `now`: it is not a draft, and its
+    /// publication date, if it has one, is not in the future.
+    pub fn is_published_at(&self, now: DateTime<Utc>) -> bool {
+        !self.draft
+            && self
+                .published_at
+                .is_none_or(|published_at| published_at <= now)
+    }
+}
+
+/// Represents the request body for creating or updating a blog post.
+///
+/// `slug`, `author`, `draft`, `published_at` and the featured image and SEO fields are
+/// optional: a new post without them has no slug, author, featured image or SEO fields,
+/// is not a draft, has no publication date and may be indexed, and an update without
+/// them keeps the stored values. An update sending `null` clears the field, except for
+/// `draft` and `noindex`, which keep their stored values.
+#[derive(Debug, Default, Serialize, Deserialize, Validate, ToSchema)]
+pub struct BlogPostBody {
+    /// Title of the blog post.
+    #[validate(length(min = 1, message = "Title cannot be empty"))]
+    pub title: String,
+
+    /// Content of the blog post.
+    #[validate(length(min = 1, message = "Content cannot be empty"))]
+    pub content: String,
+
+    /// Category to which the blog post belongs.
+    #[validate(length(min = 1, message = "Category cannot be empty"))]
+    pub category: String,
+
+    /// List of tags associated with the blog post.
+    #[validate(length(min = 1, message = "At least one tag is required"))]
+    pub tags: Vec<String>,
+
+    /// Unique, URL-friendly name of the blog post.
+    #[serde(
+        default,
+        deserialize_with = "double_option",
+        skip_serializing_if = "Option::is_none"
+    )]
+    #[validate(custom(function = "validate_slug"))]
+    #[schema(example = "my-first-post")]
+    pub slug: Option<Option<String>>,
+
+    /// Name of the author of the blog post.
+    #[serde(
+        default,
+        deserialize_with = "double_option",
+        skip_serializing_if = "Option::is_none"
+    )]
+    #[validate(length(min = 1, message = "Author cannot be empty"))]
+    #[schema(example = "Jane Doe")]
+    pub author: Option<Option<String>>,
+
+    /// Whether the blog post is a draft.
+    #[serde(default)]
+    pub draft: Option<bool>,
+
+    /// When the blog post was, or is to be, published.
+    #[serde(
+        default,
+        deserialize_with = "double_option",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub published_at: Option<Option<DateTime<Utc>>>,
+
+    /// ID of the media file shown as the cover of the blog post; must be an image.
+    #[serde(
+        default,
+        deserialize_with = "double_option",
+        skip_serializing_if = "Option::is_none"
+    )]
+    #[schema(example = 3)]
+    pub featured_image_id: Option<Option<i32>>,
+
+    /// Title shown by search engines and shared links instead of the title.
+    #[serde(
+        default,
+        deserialize_with = "double_option",
+        skip_serializing_if = "Option::is_none"
+    )]
+    #[validate(length(min = 1, message = "Meta title cannot be empty"))]
+    pub meta_title: Option<Option<String>>,
+
+    /// Summary shown by search engines and shared links.
+    #[serde(
+        default,
+        deserialize_with = "double_option",
+        skip_serializing_if = "Option::is_none"
+    )]
+    #[validate(length(min = 1, message = "Meta description cannot be empty"))]
+    pub meta_description: Option<Option<String>>,
+
+    /// Preferred URL of the blog post, if it differs from its URL on the site.
+    #[serde(
+        default,
+        deserialize_with = "double_option",
+        skip_serializing_if = "Option::is_none"
+    )]
+    #[validate(url(message = "Canonical URL must be an absolute URL"))]
+    #[schema(example = "https://example.com/my-first-post")]
+    pub canonical_url: Option<Option<String>>,
+
+    /// Whether search engines are asked not to index the blog post.
+    #[serde(default)]
+    pub noindex: Option<bool>,
+}
+
+impl BlogPostBody {
+    /// Returns the slug sent, if any.
+    pub fn slug(&self) -> Option<&str> {
+        self.slug.as_ref().and_then(Option::as_deref)
+    }
+
+    /// Returns the author sent, if any.
+    pub fn author(&self) -> Option<&str> {
+        self.author.as_ref().and_then(Option::as_deref)
+    }
+
+    /// Returns the publication date sent, if any.
+    pub fn published_at(&self) -> Option<DateTime<Utc>> {
+        self.published_at.flatten()
+    }
+
+    /// Returns the ID of the featured image sent, if any.
+    pub fn featured_image_id(&self) -> Option<i32> {
+        self.featured_image_id.flatten()
+    }
+
+    /// Returns the meta title sent, if any.
+    pub fn meta_title(&self) -> Option<&str> {
+        self.meta_title.as_ref().and_then(Option::as_deref)
+    }
+
+    /// Returns the meta description sent, if any.
+    pub fn meta_description(&self) -> Option<&str> {
+        self.meta_description.as_ref().and_then(Option::as_deref)
+    }
+
+    /// Returns the canonical URL sent, if any.
+    pub fn canonical_url(&self) -> Option<&str> {
+        self.canonical_url.as_ref().and_then(Option::as_deref)
+    }
+
+    /// Returns `post` as this body updates it, with its timestamps unchanged.
+    ///
+    /// The title, content, category and tags are replaced, and the optional fields sent
+    /// are set or, when sent as `null`, cleared; the others keep their values.
+    pub fn apply_to(&self, post: &BlogPost) -> BlogPost {
+        BlogPost {
+            title: self.title.clone(),
+            content: self.content.clone(),
+            category: self.category.clone(),
+            tags: self.tags.clone(),
+            slug: self.slug.clone().unwrap_or_else(|| post.slug.clone()),
+            author: self.author.clone().unwrap_or_else(|| post.author.clone()),
+            draft: self.draft.unwrap_or(post.draft),
+            published_at: self.published_at.unwrap_or(post.published_at),
+            featured_image_id: self.featured_image_id.unwrap_or(post.featured_image_id),
+            meta_title: self
+                .meta_title
+                .clone()
+                .unwrap_or_else(|| post.meta_title.clone()),
+            meta_description: self
+                .meta_description
+                .clone()
+                .unwrap_or_else(|| post.meta_description.clone()),
+            canonical_url: self
+                .canonical_url
+                .clone()
+                .unwrap_or_else(|| post.canonical_url.clone()),
+            noindex: self.noindex.unwrap_or(post.noindex),
+            ..post.clone()
+        }
+    }
+}
@@ -0,0 +1,143 @@
+use crate::{
+    model::blog::BlogPost,
+    repository::{PostFilter, Reassignment},
+};
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+
+/// How a bulk create treats posts that fail validation.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum BulkMode {
+    /// Store nothing unless every post is valid.
+    #[default]
+    AllOrNothing,
+    /// Store the valid posts one by one and report the ones that are invalid or that
+    /// the storage refuses.
+    BestEffort,
+}
+
+/// Query parameters of a bulk create.
+#[derive(Debug, Default, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct BulkCreateParams {
+    /// How posts that fail validation are treated; defaults to `all_or_nothing`.
+    #[serde(default)]
+    #[param(inline)]
+    pub mode: BulkMode,
+}
+
+/// Outcome of one post of a bulk create.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum BulkItemStatus {
+    /// The post was stored.
+    Created,
+    /// The post failed validation and was not stored.
+    Invalid,
+    /// The post was valid but not stored, because another post was invalid.
+    Skipped,
+    /// The post was valid but the storage refused it, e.g. because another post has
+    /// its slug, which a bulk create only reports in `best_effort` mode; or the post is
+    /// locked for editing, and a bulk update or delete left it unchanged.
+    Rejected,
+}
+
+/// Result of one post of a bulk create, in the order of the request.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct BulkItemResult {
+    /// Position of the post in the request, starting at `0`.
+    #[schema(example = 0)]
+    pub index: usize,
+
+    /// What happened to the post.
+    pub status: BulkItemStatus,
+
+    /// The stored post, if it was created.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub post: Option<BlogPost>,
+
+    /// Why the post is invalid or was refused, if it was.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(example = "title: Title cannot be empty")]
+    pub error: Option<String>,
+}
+
+/// Response of a bulk create.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct BulkCreateResponse {
+    /// Number of posts stored.
+    #[schema(example = 1)]
+    pub created: usize,
+
+    /// Number of posts that failed validation or were refused by the storage.
+    #[schema(example = 0)]
+    pub failed: usize,
+
+    /// One result per post of the request, in order.
+    pub results: Vec<BulkItemResult>,
+}
+
+/// A post a bulk update or delete left unchanged because it is locked for editing.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct BulkRejection {
+    /// ID of the post.
+    #[schema(example = 2)]
+    pub id: i32,
+
+    /// What happened to the post; always `rejected`.
+    pub status: BulkItemStatus,
+
+    /// Why the post was left unchanged.
+    #[schema(example = "Blog post 2 is being edited by ann until 2026-10-19T12:00:00+00:00")]
+    pub error: String,
+}
+
+/// Request body of a bulk update: which posts to change and how.
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct BulkUpdateBody {
+    /// Posts to update; at least one of `category` and `tag` is required.
+    pub filter: PostFilter,
+
+    /// Changes applied to every matching post.
+    #[serde(flatten)]
+    pub changes: Reassignment,
+}
+
+/// Response of a bulk update.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct BulkUpdateResponse {
+    /// Number of posts updated.
+    #[schema(example = 2)]
+    pub updated: usize,
+
+    /// IDs of the updated posts, in ascending order.
+    #[schema(example = json!([1, 3]))]
+    pub ids: Vec<i32>,
+
+    /// Matching posts left unchanged because they are locked, in ascending order of ID.
+    pub rejected: Vec<BulkRejection>,
+}
+
+/// Request body of a bulk delete.
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct BulkDeleteBody {
+    /// IDs of the posts to delete.
+    #[schema(example = json!([1, 2, 3]))]
+    pub ids: Vec<i32>,
+}
+
+/// Response of a bulk delete.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct BulkDeleteResponse {
+    /// IDs of the deleted posts, in ascending order.
+    #[schema(example = json!([1, 3]))]
+    pub deleted: Vec<i32>,
+
+    /// Requested IDs that no post has.
+    #[schema(example = json!([2]))]
+    pub not_found: Vec<i32>,
+
+    /// Posts left in place because they are locked, in ascending order of ID.
+    pub rejected: Vec<BulkRejection>,
+}
@@ -0,0 +1,110 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+
+/// Longest name of a participant, in characters.
+pub const MAX_NAME_LENGTH: usize = 64;
+
+/// Query parameters of a connection to the room of a post.
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct CollaborateParams {
+    /// Name shown to the other participants, and as the holder of the edit lock.
+    #[param(example = "alice", min_length = 1, max_length = 64)]
+    pub name: String,
+}
+
+/// An advisory lock on editing a blog post.
+///
+/// While the lock is held, updates of the post must carry its token in the
+/// [`EDIT_LOCK_HEADER`](crate::collab::EDIT_LOCK_HEADER) header. The token is only sent to the holder.
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+pub struct EditLock {
+    /// ID of the locked blog post.
+    #[schema(example = 1)]
+    pub post_id: i32,
+
+    /// Name of the participant holding the lock.
+    #[schema(example = "alice")]
+    pub holder: String,
+
+    /// Secret identifying the holder.
+    #[serde(skip)]
+    pub token: String,
+
+    /// When the holder acquired the lock.
+    pub acquired_at: DateTime<Utc>,
+
+    /// When the lock expires unless renewed by a heartbeat.
+    pub expires_at: DateTime<Utc>,
+}
+
+impl EditLock {
+    /// Returns whether the lock is still held at `now`.
+    pub fn is_held_at(&self, now: DateTime<Utc>) -> bool {
+        self.expires_at > now
+    }
+}
+
+/// Someone connected to the room of a post.
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+pub struct Participant {
+    /// ID of the participant's session, unique on the server.
+    #[schema(example = 7)]
+    pub id: u64,
+
+    /// Name the participant connected with.
+    #[schema(example = "alice")]
+    pub name: String,
+
+    /// Whether the participant holds the edit lock, rather than viewing the post.
+    pub editing: bool,
+
+    /// When the participant connected.
+    pub joined_at: DateTime<Utc>,
+}
+
+/// A message sent by a participant.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, ToSchema)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ClientMessage {
+    /// Asks for the edit lock, or renews it if already held.
+    Lock,
+    /// Renews the edit lock held by the participant.
+    Heartbeat,
+    /// Releases the edit lock held by the participant.
+    Unlock,
+}
+
+/// A message sent to a participant.
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ServerMessage {
+    /// Who is in the room and who holds the edit lock, sent on joining and whenever
+    /// either changes.
+    Presence {
+        /// Participants connected through this server, in the order they joined.
+        participants: Vec<Participant>,
+        /// The edit lock of the post, if held.
+        lock: Option<EditLock>,
+    },
+    /// The edit lock was granted or renewed.
+    LockGranted {
+        /// Token to send in the `Edit-Lock` header of updates.
+        token: String,
+        /// The lock.
+        lock: EditLock,
+    },
+    /// The edit lock is held by someone else.
+    LockDenied {
+        /// The lock.
+        lock: EditLock,
+    },
+    /// The edit lock of the participant expired and was taken over, or was never held.
+    LockLost,
+    /// A message could not be handled.
+    Error {
+        /// What went wrong.
+        message: String,
+    },
+}
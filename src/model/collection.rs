@@ -0,0 +1,22 @@
+use serde::Serialize;
+use utoipa::ToSchema;
+
+/// A list of resources wrapped in an envelope, as returned by version 2 of the API.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct Collection<T> {
+    /// The resources in the collection.
+    pub data: Vec<T>,
+
+    /// Number of resources in `data`.
+    #[schema(example = 1)]
+    pub count: usize,
+}
+
+impl<T> From<Vec<T>> for Collection<T> {
+    fn from(data: Vec<T>) -> Self {
+        Self {
+            count: data.len(),
+            data,
+        }
+    }
+}
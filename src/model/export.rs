@@ -0,0 +1,178 @@
+use crate::{
+    content::{archive, markdown},
+    model::blog::BlogPost,
+};
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde::Deserialize;
+use std::{borrow::Cow, fmt::Write, io};
+use utoipa::{IntoParams, ToSchema};
+
+/// Separator between the tags of a post in CSV exports.
+///
+/// A separator or backslash within a tag is escaped with a backslash, so `a|b` and
+/// `c\d` are written as `a\|b` and `c\\d`.
+pub const CSV_TAG_SEPARATOR: char = '|';
+
+/// Columns of CSV exports, in order.
+const CSV_COLUMNS: [&str; 16] = [
+    "id",
+    "title",
+    "content",
+    "category",
+    "tags",
+    "slug",
+    "author",
+    "draft",
+    "published_at",
+    "featured_image_id",
+    "meta_title",
+    "meta_description",
+    "canonical_url",
+    "noindex",
+    "created_at",
+    "updated_at",
+];
+
+/// File format of a post export.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    /// One JSON object per line.
+    #[default]
+    Ndjson,
+    /// Comma-separated values with a header row; tags are joined by
+    /// [`CSV_TAG_SEPARATOR`], which is escaped within tags.
+    Csv,
+    /// A tar archive of Markdown files with YAML front matter, one per post, which
+    /// can be imported again.
+    Markdown,
+}
+
+/// Query parameters of an export, besides the listing filters.
+#[derive(Debug, Default, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ExportParams {
+    /// File format; defaults to `ndjson`.
+    #[serde(default)]
+    #[param(inline)]
+    pub format: ExportFormat,
+}
+
+/// Quotes a CSV field if it contains a delimiter, quote or line break (RFC 4180).
+fn csv_field(value: &str) -> Cow<'_, str> {
+    if value.contains([',', '"', '\n', '\r']) {
+        Cow::Owned(format!("\"{}\"", value.replace('"', "\"\"")))
+    } else {
+        Cow::Borrowed(value)
+    }
+}
+
+/// Joins tags with [`CSV_TAG_SEPARATOR`], escaping the separator and backslashes
+/// within them.
+fn csv_tags(tags: &[String]) -> String {
+    let mut joined = String::new();
+    for (index, tag) in tags.iter().enumerate() {
+        if index > 0 {
+            joined.push(CSV_TAG_SEPARATOR);
+        }
+        for c in tag.chars() {
+            if c == CSV_TAG_SEPARATOR || c == '\\' {
+                joined.push('\\');
+            }
+            joined.push(c);
+        }
+    }
+    joined
+}
+
+impl ExportFormat {
+    /// Returns the media type of the format.
+    pub fn content_type(self) -> &'static str {
+        match self {
+            Self::Ndjson => "application/x-ndjson",
+            Self::Csv => "text/csv; charset=utf-8",
+            Self::Markdown => "application/x-tar",
+        }
+    }
+
+    /// Returns the file extension of the format.
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Ndjson => "ndjson",
+            Self::Csv => "csv",
+            Self::Markdown => "tar",
+        }
+    }
+
+    /// Returns the bytes written before the first post, if any.
+    pub fn header(self) -> Option<Vec<u8>> {
+        match self {
+            Self::Ndjson | Self::Markdown => None,
+            Self::Csv => Some(format!("{}\r\n", CSV_COLUMNS.join(",")).into_bytes()),
+        }
+    }
+
+    /// Returns the bytes written after the last post, if any.
+    pub fn footer(self) -> Option<Vec<u8>> {
+        match self {
+            Self::Ndjson | Self::Csv => None,
+            Self::Markdown => Some(archive::TAR_END.to_vec()),
+        }
+    }
+
+    /// Encodes one post in the format: a line, including its terminator, or an archive
+    /// member.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the post cannot be serialized.
+    pub fn encode(self, post: &BlogPost) -> io::Result<Vec<u8>> {
+        match self {
+            Self::Ndjson => {
+                let mut line = serde_json::to_vec(post)?;
+                line.push(b'\n');
+                Ok(line)
+            }
+            Self::Markdown => {
+                let text = markdown::render(post).map_err(io::Error::other)?;
+                let mtime = post
+                    .updated_at
+                    .and_then(|time| u64::try_from(time.timestamp()).ok())
+                    .unwrap_or_default();
+                archive::tar_entry(&markdown::file_name(post), text.as_bytes(), mtime)
+            }
+            Self::Csv => {
+                let timestamp = |time: Option<DateTime<Utc>>| {
+                    time.map(|time| time.to_rfc3339_opts(SecondsFormat::AutoSi, true))
+                        .unwrap_or_default()
+                };
+                let tags = csv_tags(&post.tags);
+                let mut line = String::new();
+                // Writing to a `String` cannot fail.
+                let _ = write!(
+                    line,
+                    "{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{}\r\n",
+                    post.id,
+                    csv_field(&post.title),
+                    csv_field(&post.content),
+                    csv_field(&post.category),
+                    csv_field(&tags),
+                    csv_field(post.slug.as_deref().unwrap_or_default()),
+                    csv_field(post.author.as_deref().unwrap_or_default()),
+                    post.draft,
+                    timestamp(post.published_at),
+                    post.featured_image_id
+                        .map(|id| id.to_string())
+                        .unwrap_or_default(),
+                    csv_field(post.meta_title.as_deref().unwrap_or_default()),
+                    csv_field(post.meta_description.as_deref().unwrap_or_default()),
+                    csv_field(post.canonical_url.as_deref().unwrap_or_default()),
+                    post.noindex,
+                    timestamp(post.created_at),
+                    timestamp(post.updated_at),
+                );
+                Ok(line.into_bytes())
+            }
+        }
+    }
+}
@@ -0,0 +1,160 @@
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+
+/// Query parameters of a Markdown import.
+#[derive(Debug, Default, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ImportParams {
+    /// Name of the uploaded file when it is a single Markdown file rather than an
+    /// archive; the slug of a post without one in its front matter is derived from it.
+    /// Defaults to `post.md`.
+    #[param(example = "hello-world.md")]
+    pub filename: Option<String>,
+}
+
+/// Outcome of one file of an import.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ImportItemStatus {
+    /// No post had the slug of the file, so one was created.
+    Created,
+    /// The post with the slug of the file was updated.
+    Updated,
+    /// The file could not be read or failed validation, and was not imported.
+    Invalid,
+}
+
+/// Result of one file of an import, in the order of the upload.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ImportItemResult {
+    /// Path of the file within the upload.
+    #[schema(example = "posts/hello-world.md")]
+    pub file: String,
+
+    /// What happened to the file.
+    pub status: ImportItemStatus,
+
+    /// ID of the created or updated post.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(example = 1)]
+    pub id: Option<i32>,
+
+    /// Slug the file was matched on.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(example = "hello-world")]
+    pub slug: Option<String>,
+
+    /// Why the file was not imported, if it was not.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(example = "front matter: missing field `category`")]
+    pub error: Option<String>,
+}
+
+/// Response of an import.
+#[derive(Debug, Default, Serialize, ToSchema)]
+pub struct ImportResponse {
+    /// Number of posts created.
+    #[schema(example = 1)]
+    pub created: usize,
+
+    /// Number of posts updated.
+    #[schema(example = 0)]
+    pub updated: usize,
+
+    /// Number of files not imported.
+    #[schema(example = 0)]
+    pub failed: usize,
+
+    /// One result per file of the upload, in order.
+    pub results: Vec<ImportItemResult>,
+}
+
+/// Query parameters of a WordPress import.
+#[derive(Debug, Default, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct WordPressImportParams {
+    /// Report what the import would do without storing anything.
+    #[serde(default)]
+    #[param(example = true)]
+    pub dry_run: bool,
+}
+
+/// Outcome of one item of a WordPress import.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum WordPressItemStatus {
+    /// No post had the slug of the item, so one was (or, in a dry run, would be)
+    /// created.
+    Created,
+    /// The post with the slug of the item was (or would be) updated.
+    Updated,
+    /// The item is not a post, e.g. a page or an attachment, or is trashed.
+    Skipped,
+    /// The item failed validation and was not imported.
+    Invalid,
+}
+
+/// Result of one item of a WordPress import, in the order of the export.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct WordPressItemResult {
+    /// Title of the item.
+    #[schema(example = "Hello, world")]
+    pub title: String,
+
+    /// Original permalink of the item, from which to redirect to the imported post.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(example = "https://example.com/2025/03/01/hello-world/")]
+    pub link: Option<String>,
+
+    /// Slug of the item in WordPress (`wp:post_name`), if it had one.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(example = "hello-world")]
+    pub original_slug: Option<String>,
+
+    /// Slug of the imported post; differs from `original_slug` only if that is not a
+    /// valid slug here, e.g. a percent-encoded non-ASCII one, or is missing.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(example = "hello-world")]
+    pub slug: Option<String>,
+
+    /// What happened, or in a dry run would happen, to the item.
+    pub status: WordPressItemStatus,
+
+    /// ID of the created or updated post; in a dry run, only of a post that would be
+    /// updated.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(example = 1)]
+    pub id: Option<i32>,
+
+    /// Why the item was skipped or not imported.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(example = "post type `page` is not imported")]
+    pub reason: Option<String>,
+}
+
+/// Response of a WordPress import.
+#[derive(Debug, Default, Serialize, ToSchema)]
+pub struct WordPressImportResponse {
+    /// Whether this was a dry run, in which case nothing was stored and the counts are
+    /// of what the import would do.
+    pub dry_run: bool,
+
+    /// Number of posts created.
+    #[schema(example = 1)]
+    pub created: usize,
+
+    /// Number of posts updated.
+    #[schema(example = 0)]
+    pub updated: usize,
+
+    /// Number of items skipped.
+    #[schema(example = 3)]
+    pub skipped: usize,
+
+    /// Number of items that failed validation.
+    #[schema(example = 0)]
+    pub failed: usize,
+
+    /// One result per item of the export, in order.
+    pub results: Vec<WordPressItemResult>,
+}
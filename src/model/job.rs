@@ -0,0 +1,156 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+
+/// Number of jobs listed when the filter sets no limit.
+pub const DEFAULT_LIST_LIMIT: u32 = 100;
+
+/// Largest number of jobs listed at once.
+pub const MAX_LIST_LIMIT: u32 = 1000;
+
+/// Progress of a background job.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum JobStatus {
+    /// The job waits for its `run_at` time or for a free worker, including between
+    /// retries.
+    Queued,
+    /// A worker is running the job.
+    Running,
+    /// The job finished.
+    Succeeded,
+    /// Every attempt failed, or the job cannot succeed; it is kept until retried or
+    /// purged.
+    Dead,
+}
+
+impl JobStatus {
+    /// Returns the status as stored in the database.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Queued => "queued",
+            Self::Running => "running",
+            Self::Succeeded => "succeeded",
+            Self::Dead => "dead",
+        }
+    }
+}
+
+impl TryFrom<String> for JobStatus {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        match value.as_str() {
+            "queued" => Ok(Self::Queued),
+            "running" => Ok(Self::Running),
+            "succeeded" => Ok(Self::Succeeded),
+            "dead" => Ok(Self::Dead),
+            _ => Err(format!("unknown job status {value:?}")),
+        }
+    }
+}
+
+/// A unit of background work, with the outcome of its last attempt.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct Job {
+    /// Unique identifier of the job.
+    #[schema(example = 42)]
+    pub id: i64,
+
+    /// Queue whose workers run the job.
+    #[schema(example = "maintenance")]
+    pub queue: String,
+
+    /// Kind of the job, naming the handler that runs it.
+    #[schema(example = "jobs.purge")]
+    pub kind: String,
+
+    /// Input of the handler.
+    #[schema(value_type = Object)]
+    pub payload: serde_json::Value,
+
+    /// Whether the job is waiting, running or finished.
+    pub status: JobStatus,
+
+    /// Attempts started so far.
+    #[schema(example = 1)]
+    pub attempts: i32,
+
+    /// Attempts allowed before the job is dead.
+    #[schema(example = 10)]
+    pub max_attempts: i32,
+
+    /// When the job is due, or was last due.
+    pub run_at: DateTime<Utc>,
+
+    /// While the job runs, when its worker is considered gone and the job is claimed
+    /// again.
+    pub locked_until: Option<DateTime<Utc>>,
+
+    /// Why the last failed attempt failed.
+    #[schema(example = "Database error")]
+    pub last_error: Option<String>,
+
+    /// Key the job was enqueued under, if it must only be enqueued once.
+    #[schema(example = "jobs.purge@2026-10-19T21:00:00Z")]
+    pub unique_key: Option<String>,
+
+    /// When the job was enqueued.
+    pub created_at: DateTime<Utc>,
+
+    /// When the job succeeded or died.
+    pub finished_at: Option<DateTime<Utc>>,
+}
+
+/// A job to enqueue.
+#[derive(Debug, Clone)]
+pub struct NewJob {
+    /// Queue whose workers run the job.
+    pub queue: String,
+    /// Kind of the job.
+    pub kind: String,
+    /// Input of the handler.
+    pub payload: serde_json::Value,
+    /// Attempts allowed before the job is dead.
+    pub max_attempts: i32,
+    /// When the job is due.
+    pub run_at: DateTime<Utc>,
+    /// Key under which the job is enqueued at most once.
+    pub unique_key: Option<String>,
+}
+
+/// The outcome of an attempt to run a job.
+#[derive(Debug, Clone)]
+pub struct JobOutcome {
+    /// The status of the job after the attempt: succeeded, queued again or dead.
+    pub status: JobStatus,
+    /// When the job is due again, if it is queued again.
+    pub run_at: Option<DateTime<Utc>>,
+    /// Why the attempt failed.
+    pub error: Option<String>,
+}
+
+/// Filters applied when listing jobs. Unset filters match every job.
+#[derive(Debug, Clone, Default, Deserialize, IntoParams, ToSchema)]
+#[into_params(parameter_in = Query)]
+pub struct JobFilter {
+    /// Only list jobs of this queue.
+    pub queue: Option<String>,
+
+    /// Only list jobs of this kind.
+    pub kind: Option<String>,
+
+    /// Only list jobs in this state.
+    pub status: Option<JobStatus>,
+
+    /// Largest number of jobs listed, at most 1000; 100 if unset.
+    #[param(maximum = 1000)]
+    pub limit: Option<u32>,
+}
+
+impl JobFilter {
+    /// Returns the number of jobs to list.
+    pub fn limit(&self) -> u32 {
+        self.limit.unwrap_or(DEFAULT_LIST_LIMIT).min(MAX_LIST_LIMIT)
+    }
+}
@@ -0,0 +1,203 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use utoipa::{IntoParams, ToSchema};
+
+/// An uploaded media file, such as an image to show in a post.
+///
+/// Files are stored by the SHA-256 hash of their content, so uploading the same
+/// content twice stores it once.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, FromRow, ToSchema)]
+pub struct MediaFile {
+    /// Unique identifier of the file.
+    #[schema(example = 1)]
+    pub id: i32,
+
+    /// Who uploaded the file, as given with the upload.
+    #[schema(example = "ann")]
+    pub owner: Option<String>,
+
+    /// Name of the uploaded file, without any directories.
+    #[schema(example = "diagram.png")]
+    pub filename: String,
+
+    /// MIME type of the file, as recognized from its content.
+    #[schema(example = "image/png")]
+    pub content_type: String,
+
+    /// Size of the file in bytes.
+    #[schema(example = 48213)]
+    pub size: i64,
+
+    /// Hex-encoded SHA-256 hash of the content, also used as its `ETag`.
+    #[schema(example = "9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08")]
+    pub content_hash: String,
+
+    /// Width of an image in pixels.
+    #[schema(example = 800)]
+    pub width: Option<i32>,
+
+    /// Height of an image in pixels.
+    #[schema(example = 600)]
+    pub height: Option<i32>,
+
+    /// When the file was uploaded.
+    pub created_at: DateTime<Utc>,
+
+    /// Whether the variants of the file have been generated.
+    #[sqlx(try_from = "String")]
+    pub variants_status: VariantsStatus,
+
+    /// Resized and re-encoded copies of an image, served at
+    /// `/media/{id}/variants/{name}`.
+    #[sqlx(skip)]
+    #[serde(default)]
+    pub variants: Vec<MediaVariant>,
+
+    /// The file and its variants grouped by MIME type, ready for the `srcset` attribute of
+    /// `<source>` and `<img>` elements. The set in the type of the file comes last.
+    #[sqlx(skip)]
+    #[serde(default)]
+    pub srcset: Vec<SourceSet>,
+}
+
+/// Progress of the generation of the variants of a media file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum VariantsStatus {
+    /// The variants are being generated.
+    Pending,
+    /// Every variant is generated, or the file gets none.
+    Ready,
+    /// Generating the variants failed, e.g. because the image could not be decoded.
+    Failed,
+}
+
+impl VariantsStatus {
+    /// Returns the status as stored in the database.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Pending => "pending",
+            Self::Ready => "ready",
+            Self::Failed => "failed",
+        }
+    }
+}
+
+impl TryFrom<String> for VariantsStatus {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        match value.as_str() {
+            "pending" => Ok(Self::Pending),
+            "ready" => Ok(Self::Ready),
+            "failed" => Ok(Self::Failed),
+            _ => Err(format!("unknown variants status {value:?}")),
+        }
+    }
+}
+
+/// A resized or re-encoded copy of an uploaded image, such as a WebP thumbnail.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, FromRow, ToSchema)]
+pub struct MediaVariant {
+    /// Unique identifier of the variant.
+    #[schema(example = 7)]
+    pub id: i32,
+
+    /// ID of the media file the variant was generated from.
+    #[schema(example = 1)]
+    pub media_id: i32,
+
+    /// Name of the variant, unique per media file: its width followed by `w`, or
+    /// `thumbnail`, then the extension of its format.
+    #[schema(example = "640w.webp")]
+    pub name: String,
+
+    /// MIME type of the variant.
+    #[schema(example = "image/webp")]
+    pub content_type: String,
+
+    /// Size of the variant in bytes.
+    #[schema(example = 21034)]
+    pub size: i64,
+
+    /// Hex-encoded SHA-256 hash of the content, also used as its `ETag`.
+    #[schema(example = "2c26b46b68ffc68ff99b453c1d30413413422d706483bfa0f98a5e886266e7ae")]
+    pub content_hash: String,
+
+    /// Width in pixels.
+    #[schema(example = 640)]
+    pub width: i32,
+
+    /// Height in pixels.
+    #[schema(example = 480)]
+    pub height: i32,
+
+    /// When the variant was generated.
+    pub created_at: DateTime<Utc>,
+}
+
+/// The URLs of an image at several widths in one format.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct SourceSet {
+    /// MIME type of the images, for the `type` attribute of a `<source>` element.
+    #[schema(example = "image/webp")]
+    pub content_type: String,
+
+    /// Comma-separated URLs with their widths, for a `srcset` attribute.
+    #[schema(
+        example = "/api/v2/media/1/variants/320w.webp 320w, /api/v2/media/1/variants/640w.webp 640w"
+    )]
+    pub srcset: String,
+}
+
+/// A media file to store, described from its upload.
+#[derive(Debug, Clone)]
+pub struct NewMediaFile {
+    /// Who uploaded the file.
+    pub owner: Option<String>,
+    /// Name of the uploaded file.
+    pub filename: String,
+    /// MIME type recognized from the content.
+    pub content_type: String,
+    /// Size of the file in bytes.
+    pub size: i64,
+    /// Hex-encoded SHA-256 hash of the content.
+    pub content_hash: String,
+    /// Width of an image in pixels.
+    pub width: Option<i32>,
+    /// Height of an image in pixels.
+    pub height: Option<i32>,
+    /// Whether variants are to be generated.
+    pub variants_status: VariantsStatus,
+}
+
+/// A variant of a media file to store, as generated from its content.
+#[derive(Debug, Clone)]
+pub struct NewMediaVariant {
+    /// ID of the media file the variant was generated from.
+    pub media_id: i32,
+    /// Name of the variant, unique per media file.
+    pub name: String,
+    /// MIME type of the variant.
+    pub content_type: String,
+    /// Size of the variant in bytes.
+    pub size: i64,
+    /// Hex-encoded SHA-256 hash of the content.
+    pub content_hash: String,
+    /// Width in pixels.
+    pub width: i32,
+    /// Height in pixels.
+    pub height: i32,
+}
+
+/// Filters applied when listing media files. Unset filters match every file.
+#[derive(Debug, Clone, Default, Deserialize, IntoParams, ToSchema)]
+#[into_params(parameter_in = Query)]
+pub struct MediaFilter {
+    /// Only list files uploaded by this owner.
+    pub owner: Option<String>,
+
+    /// Only list files whose variants are in this state.
+    pub variants_status: Option<VariantsStatus>,
+}
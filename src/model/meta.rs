@@ -0,0 +1,67 @@
+use super::media::MediaFile;
+use serde::Serialize;
+use utoipa::ToSchema;
+
+/// A `<meta>` tag of a page head.
+///
+/// Open Graph tags are written with a `property` attribute and Twitter Card tags with a
+/// `name` attribute, both taking `name` here.
+#[derive(Debug, Clone, PartialEq, Serialize, ToSchema)]
+pub struct MetaTag {
+    /// Value of the `property` or `name` attribute.
+    #[schema(example = "og:title")]
+    pub name: String,
+
+    /// Value of the `content` attribute.
+    #[schema(example = "My First Post")]
+    pub content: String,
+}
+
+impl MetaTag {
+    /// Creates a tag.
+    pub fn new(name: &str, content: impl Into<String>) -> Self {
+        Self {
+            name: name.to_string(),
+            content: content.into(),
+        }
+    }
+}
+
+/// The metadata of a blog post for search engines and link previews, derived from the
+/// post, its author and its featured image.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct PostMeta {
+    /// Title of the page: the meta title of the post, or else its title.
+    #[schema(example = "My First Post")]
+    pub title: String,
+
+    /// Description of the page: the meta description of the post, or else the start of
+    /// its content as plain text.
+    pub description: String,
+
+    /// Canonical URL of the post: its own, or else its URL on the site.
+    #[schema(example = "https://example.com/posts/my-first-post/")]
+    pub canonical_url: String,
+
+    /// Value of the `robots` meta tag, set when search engines are asked not to index
+    /// the post.
+    #[schema(example = "noindex")]
+    pub robots: Option<String>,
+
+    /// Open Graph tags, such as `og:title` and `article:published_time`.
+    pub open_graph: Vec<MetaTag>,
+
+    /// Twitter Card tags, such as `twitter:card`.
+    pub twitter: Vec<MetaTag>,
+
+    /// Schema.org `BlogPosting` structured data, for a
+    /// `<script type="application/ld+json">` element.
+    #[schema(value_type = Object)]
+    pub json_ld: serde_json::Value,
+
+    /// All of the above as HTML, ready to be placed in the page head.
+    pub html: String,
+
+    /// The featured image of the post, with its variants.
+    pub featured_image: Option<MediaFile>,
+}
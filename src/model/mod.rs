@@ -1 +1,10 @@
 pub mod blog;
+pub mod bulk;
+pub mod collab;
+pub mod collection;
+pub mod export;
+pub mod import;
+pub mod job;
+pub mod media;
+pub mod meta;
+pub mod webhook;
@@ -0,0 +1,229 @@
+use super::blog::BlogPost;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+use validator::{Validate, ValidationError};
+
+/// Something that happened to a blog post, which webhooks can subscribe to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub enum WebhookEvent {
+    /// A post was created.
+    #[serde(rename = "post.created")]
+    PostCreated,
+    /// A post was updated.
+    #[serde(rename = "post.updated")]
+    PostUpdated,
+    /// A post was deleted.
+    #[serde(rename = "post.deleted")]
+    PostDeleted,
+    /// A post was created or updated so that it is published, or its scheduled
+    /// publication date passed.
+    #[serde(rename = "post.published")]
+    PostPublished,
+    /// A published post was updated so that it is no longer published.
+    #[serde(rename = "post.unpublished")]
+    PostUnpublished,
+}
+
+impl WebhookEvent {
+    /// Returns the name of the event, as sent and stored.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::PostCreated => "post.created",
+            Self::PostUpdated => "post.updated",
+            Self::PostDeleted => "post.deleted",
+            Self::PostPublished => "post.published",
+            Self::PostUnpublished => "post.unpublished",
+        }
+    }
+}
+
+impl TryFrom<String> for WebhookEvent {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        match value.as_str() {
+            "post.created" => Ok(Self::PostCreated),
+            "post.updated" => Ok(Self::PostUpdated),
+            "post.deleted" => Ok(Self::PostDeleted),
+            "post.published" => Ok(Self::PostPublished),
+            "post.unpublished" => Ok(Self::PostUnpublished),
+            _ => Err(format!("unknown webhook event {value:?}")),
+        }
+    }
+}
+
+/// A subscription to post lifecycle events, delivered as signed `POST` requests to its
+/// URL. The secret signing the requests is never returned.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct Webhook {
+    /// Unique identifier of the webhook.
+    #[schema(example = 1)]
+    pub id: i32,
+
+    /// URL the events are sent to.
+    #[schema(example = "https://example.com/hooks/blog")]
+    pub url: String,
+
+    /// Events sent to the URL; every event if empty.
+    #[schema(example = json!(["post.published", "post.deleted"]))]
+    pub events: Vec<WebhookEvent>,
+
+    /// When the webhook was created.
+    pub created_at: DateTime<Utc>,
+}
+
+impl Webhook {
+    /// Returns whether the webhook subscribes to an event.
+    pub fn subscribes_to(&self, event: WebhookEvent) -> bool {
+        self.events.is_empty() || self.events.contains(&event)
+    }
+}
+
+/// Validates the URL of a [`WebhookBody`], which must be an HTTP or HTTPS URL.
+fn validate_http_url(url: &str) -> Result<(), ValidationError> {
+    if url.starts_with("http://") || url.starts_with("https://") {
+        Ok(())
+    } else {
+        Err(ValidationError::new("url").with_message("URL must use http or https".into()))
+    }
+}
+
+/// Represents the request body for creating or replacing a webhook.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, Validate, ToSchema)]
+pub struct WebhookBody {
+    /// URL the events are sent to.
+    #[validate(
+        url(message = "URL must be an absolute URL"),
+        custom(function = "validate_http_url")
+    )]
+    #[schema(example = "https://example.com/hooks/blog")]
+    pub url: String,
+
+    /// Key of the HMAC-SHA256 signature of each request, shared with the receiver.
+    #[validate(length(min = 16, message = "Secret must be at least 16 characters long"))]
+    #[schema(example = "0123456789abcdef")]
+    pub secret: String,
+
+    /// Events sent to the URL; every event if empty or unset.
+    #[serde(default)]
+    #[schema(example = json!(["post.published", "post.deleted"]))]
+    pub events: Vec<WebhookEvent>,
+}
+
+/// The JSON body of a webhook request.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct WebhookPayload {
+    /// What happened to the post.
+    pub event: WebhookEvent,
+
+    /// When it happened.
+    pub occurred_at: DateTime<Utc>,
+
+    /// The post after the event, or as it was before it was deleted.
+    pub post: BlogPost,
+}
+
+/// Progress of the delivery of an event to a webhook.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum DeliveryStatus {
+    /// The event has not been delivered yet and will be attempted again.
+    Pending,
+    /// The receiver answered with a `2xx` status.
+    Succeeded,
+    /// Every attempt failed; the event will not be attempted again.
+    Failed,
+}
+
+impl DeliveryStatus {
+    /// Returns the status as stored in the database.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Pending => "pending",
+            Self::Succeeded => "succeeded",
+            Self::Failed => "failed",
+        }
+    }
+}
+
+impl TryFrom<String> for DeliveryStatus {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        match value.as_str() {
+            "pending" => Ok(Self::Pending),
+            "succeeded" => Ok(Self::Succeeded),
+            "failed" => Ok(Self::Failed),
+            _ => Err(format!("unknown delivery status {value:?}")),
+        }
+    }
+}
+
+/// An event sent, or to be sent, to a webhook, with the outcome of its last attempt.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+pub struct WebhookDelivery {
+    /// Unique identifier of the delivery, sent in the `Webhook-Delivery` header.
+    #[schema(example = 12)]
+    pub id: i32,
+
+    /// ID of the webhook the event is sent to.
+    #[schema(example = 1)]
+    pub webhook_id: i32,
+
+    /// The event sent.
+    pub event: WebhookEvent,
+
+    /// The body sent, a [`WebhookPayload`].
+    #[schema(value_type = Object)]
+    pub payload: serde_json::Value,
+
+    /// Whether the event was delivered.
+    pub status: DeliveryStatus,
+
+    /// How many times sending the event was attempted.
+    #[schema(example = 1)]
+    pub attempts: i32,
+
+    /// When the event is to be sent next, while pending.
+    pub next_attempt_at: Option<DateTime<Utc>>,
+
+    /// When sending the event was last attempted.
+    pub last_attempt_at: Option<DateTime<Utc>>,
+
+    /// HTTP status the receiver answered the last attempt with, if it answered.
+    #[schema(example = 200)]
+    pub response_status: Option<i32>,
+
+    /// Why the last attempt failed.
+    #[schema(example = "HTTP 503 Service Unavailable")]
+    pub error: Option<String>,
+
+    /// When the event was recorded for delivery.
+    pub created_at: DateTime<Utc>,
+
+    /// When the receiver accepted the event.
+    pub delivered_at: Option<DateTime<Utc>>,
+}
+
+/// The outcome of an attempt to send an event to a webhook.
+#[derive(Debug, Clone)]
+pub struct DeliveryAttempt {
+    /// The status of the delivery after the attempt.
+    pub status: DeliveryStatus,
+    /// When the event is to be sent again, if the attempt failed and may be retried.
+    pub next_attempt_at: Option<DateTime<Utc>>,
+    /// HTTP status the receiver answered with, if it answered.
+    pub response_status: Option<i32>,
+    /// Why the attempt failed.
+    pub error: Option<String>,
+}
+
+/// Filters applied when listing the deliveries of a webhook. Unset filters match every
+/// delivery.
+#[derive(Debug, Clone, Default, Deserialize, IntoParams, ToSchema)]
+#[into_params(parameter_in = Query)]
+pub struct DeliveryFilter {
+    /// Only list deliveries in this state.
+    pub status: Option<DeliveryStatus>,
+}
@@ -0,0 +1,336 @@
+use crate::{
+    error::ErrorBody,
+    events::Reset,
+    handler::{
+        bulk, collab, create, delete, events, export, import, job, list, media, meta, read, search,
+        update, v2, webhook,
+    },
+    model::{
+        blog::BlogPost,
+        collab::{ClientMessage, ServerMessage},
+        collection::Collection,
+        webhook::WebhookPayload,
+    },
+    server::middleware::versioning::ApiVersion,
+};
+use utoipa::{
+    Modify, OpenApi,
+    openapi::{
+        ObjectBuilder, OpenApi as OpenApiDoc, RefOr, Required, Response, ResponseBuilder, Type,
+        content::Content,
+        path::{ParameterBuilder, ParameterIn},
+        security::{HttpAuthScheme, HttpBuilder, SecurityRequirement, SecurityScheme},
+    },
+};
+
+/// Responses any operation can produce, regardless of the handler.
+///
+/// | Status | Cause                                                   |
+/// |--------|---------------------------------------------------------|
+/// | `408`  | The client did not send the request body in time.       |
+/// | `429`  | The client exceeded its rate limit.                     |
+/// | `500`  | A database or other internal error occurred.            |
+/// | `503`  | The handler did not complete within its timeout.        |
+const COMMON_RESPONSES: [(&str, &str); 4] = [
+    ("408", "The request body was not received in time"),
+    (
+        "429",
+        "The client exceeded its rate limit; see `Retry-After`",
+    ),
+    ("500", "An internal or database error occurred"),
+    ("503", "The request took too long to process"),
+];
+
+/// Adds the [`COMMON_RESPONSES`] to every operation in the document.
+struct CommonResponses;
+
+impl Modify for CommonResponses {
+    fn modify(&self, openapi: &mut OpenApiDoc) {
+        for item in openapi.paths.paths.values_mut() {
+            let operations = [
+                &mut item.get,
+                &mut item.put,
+                &mut item.post,
+                &mut item.delete,
+                &mut item.patch,
+            ];
+            for operation in operations.into_iter().flatten() {
+                for (status, description) in COMMON_RESPONSES {
+                    operation
+                        .responses
+                        .responses
+                        .entry(status.to_string())
+                        .or_insert_with(|| error_response(description));
+                }
+            }
+        }
+    }
+}
+
+/// Documents the optional `Idempotency-Key` header on every `POST`, `PATCH` and
+/// `DELETE` operation, and the responses it can cause.
+struct IdempotencyKeyHeader;
+
+impl Modify for IdempotencyKeyHeader {
+    fn modify(&self, openapi: &mut OpenApiDoc) {
+        for item in openapi.paths.paths.values_mut() {
+            let operations = [&mut item.post, &mut item.patch, &mut item.delete];
+            for operation in operations.into_iter().flatten() {
+                let parameter = ParameterBuilder::new()
+                    .name("Idempotency-Key")
+                    .parameter_in(ParameterIn::Header)
+                    .required(Required::False)
+                    .description(Some(
+                        "Unique key of this request. Repeats with the same key and body \
+                         replay the first response, marked with `Idempotent-Replayed: true`.",
+                    ))
+                    .schema(Some(
+                        ObjectBuilder::new()
+                            .schema_type(Type::String)
+                            .min_length(Some(1))
+                            .max_length(Some(255)),
+                    ))
+                    .build();
+                operation
+                    .parameters
+                    .get_or_insert_with(Vec::new)
+                    .push(parameter);
+                let responses = &mut operation.responses.responses;
+                responses.entry("409".to_string()).or_insert_with(|| {
+                    error_response("A request with the same `Idempotency-Key` is in progress")
+                });
+                responses.entry("422".to_string()).or_insert_with(|| {
+                    error_response("The `Idempotency-Key` was used for a different request")
+                });
+            }
+        }
+    }
+}
+
+/// Tags of the operations that require the `ADMIN_TOKEN`.
+const ADMIN_TAGS: [&str; 2] = ["webhooks", "jobs"];
+
+/// Name of the security scheme of the administration endpoints.
+const ADMIN_SCHEME: &str = "admin_token";
+
+/// Declares the bearer token of the administration endpoints, and requires it on every
+/// operation tagged with one of the [`ADMIN_TAGS`], which may then answer `401`.
+struct AdminAuthentication;
+
+impl Modify for AdminAuthentication {
+    fn modify(&self, openapi: &mut OpenApiDoc) {
+        openapi
+            .components
+            .get_or_insert_with(Default::default)
+            .add_security_scheme(
+                ADMIN_SCHEME,
+                SecurityScheme::Http(
+                    HttpBuilder::new()
+                        .scheme(HttpAuthScheme::Bearer)
+                        .description(Some("The `ADMIN_TOKEN` of the server"))
+                        .build(),
+                ),
+            );
+        for item in openapi.paths.paths.values_mut() {
+            let operations = [
+                &mut item.get,
+                &mut item.put,
+                &mut item.post,
+                &mut item.delete,
+                &mut item.patch,
+            ];
+            for operation in operations.into_iter().flatten() {
+                let admin = operation
+                    .tags
+                    .iter()
+                    .flatten()
+                    .any(|tag| ADMIN_TAGS.contains(&tag.as_str()));
+                if !admin {
+                    continue;
+                }
+                operation
+                    .security
+                    .get_or_insert_with(Vec::new)
+                    .push(SecurityRequirement::new(ADMIN_SCHEME, Vec::<String>::new()));
+                operation
+                    .responses
+                    .responses
+                    .entry("401".to_string())
+                    .or_insert_with(|| error_response("The admin token is missing or wrong"));
+            }
+        }
+    }
+}
+
+/// Prefixes the operation IDs of a version's document with the version, e.g. `v2_find_all`.
+///
+/// Versions share handlers, so without the prefix the merged document would
+/// contain duplicate operation IDs.
+struct VersionOperationIds(ApiVersion);
+
+/// Operation ID prefix for version 1.
+const V1_OPERATION_IDS: VersionOperationIds = VersionOperationIds(ApiVersion::V1);
+
+/// Operation ID prefix for version 2.
+const V2_OPERATION_IDS: VersionOperationIds = VersionOperationIds(ApiVersion::V2);
+
+impl Modify for VersionOperationIds {
+    fn modify(&self, openapi: &mut OpenApiDoc) {
+        for item in openapi.paths.paths.values_mut() {
+            let operations = [
+                &mut item.get,
+                &mut item.put,
+                &mut item.post,
+                &mut item.delete,
+                &mut item.patch,
+            ];
+            for operation in operations.into_iter().flatten() {
+                if let Some(id) = &operation.operation_id {
+                    operation.operation_id = Some(format!("{}_{id}", self.0));
+                }
+            }
+        }
+    }
+}
+
+/// Removes the empty license that is derived from the crate manifest.
+struct ClearLicense;
+
+impl Modify for ClearLicense {
+    fn modify(&self, openapi: &mut OpenApiDoc) {
+        openapi.info.license = None;
+    }
+}
+
+/// Builds a response whose body is an [`ErrorBody`].
+fn error_response(description: &str) -> RefOr<Response> {
+    ResponseBuilder::new()
+        .description(description)
+        .content(
+            "application/json",
+            Content::new(Some(RefOr::Ref(utoipa::openapi::Ref::from_schema_name(
+                "ErrorBody",
+            )))),
+        )
+        .into()
+}
+
+/// Operations of version 1 of the API, relative to `/api/v1`.
+#[derive(OpenApi)]
+#[openapi(
+    paths(
+        create::create_post,
+        list::find_all,
+        search::search_posts,
+        read::find_by_id,
+        meta::find_meta_by_id,
+        collab::collaborate,
+        update::update_by_id,
+        delete::delete_by_id,
+        bulk::create_posts,
+        bulk::update_posts,
+        bulk::delete_posts,
+        export::export_posts,
+        events::stream_events,
+        import::import_posts,
+        import::import_wordpress,
+        media::upload_media,
+        media::list_media,
+        media::get_media,
+        media::download_media,
+        media::download_media_variant,
+        media::delete_media,
+        media::list_post_media,
+        media::attach_media,
+        media::detach_media,
+        webhook::create_webhook,
+        webhook::list_webhooks,
+        webhook::get_webhook,
+        webhook::update_webhook,
+        webhook::delete_webhook,
+        webhook::list_deliveries,
+        webhook::redeliver,
+        job::list_jobs,
+        job::get_job,
+        job::retry_job,
+    ),
+    modifiers(&V1_OPERATION_IDS)
+)]
+struct V1Api;
+
+/// Operations of version 2 of the API, relative to `/api/v2`.
+#[derive(OpenApi)]
+#[openapi(
+    paths(
+        create::create_post,
+        v2::list::find_all,
+        v2::search::search_posts,
+        read::find_by_id,
+        meta::find_meta_by_id,
+        collab::collaborate,
+        update::update_by_id,
+        delete::delete_by_id,
+        bulk::create_posts,
+        bulk::update_posts,
+        bulk::delete_posts,
+        export::export_posts,
+        events::stream_events,
+        import::import_posts,
+        import::import_wordpress,
+        media::upload_media,
+        media::list_media,
+        media::get_media,
+        media::download_media,
+        media::download_media_variant,
+        media::delete_media,
+        media::list_post_media,
+        media::attach_media,
+        media::detach_media,
+        webhook::create_webhook,
+        webhook::list_webhooks,
+        webhook::get_webhook,
+        webhook::update_webhook,
+        webhook::delete_webhook,
+        webhook::list_deliveries,
+        webhook::redeliver,
+        job::list_jobs,
+        job::get_job,
+        job::retry_job,
+    ),
+    components(schemas(Collection<BlogPost>)),
+    modifiers(&V2_OPERATION_IDS)
+)]
+struct V2Api;
+
+/// The OpenAPI 3.1 description of the blog API.
+///
+/// Paths and schemas are generated from the `#[utoipa::path]` annotations on the
+/// handlers and the `ToSchema` derives on the request and response types. Each
+/// API version is documented under its `/api/v{N}` prefix.
+#[derive(OpenApi)]
+#[openapi(
+    info(
+        title = "Blog Platform API",
+        description = "Create, read, update, delete and search blog posts.\n\n\
+            Unversioned paths such as `/posts` are served by the version requested in the \
+            `Accept` header (`application/vnd.blog.v2+json`), or by the default version."
+    ),
+    nest(
+        (path = "/api/v1", api = V1Api),
+        (path = "/api/v2", api = V2Api),
+    ),
+    components(schemas(ErrorBody, WebhookPayload, Reset, ClientMessage, ServerMessage)),
+    tags(
+        (name = "posts", description = "Blog post management"),
+        (name = "media", description = "Media file uploads and their attachment to blog posts"),
+        (name = "webhooks", description = "Notification of other services when blog posts change"),
+        (name = "jobs", description = "Inspection and retry of background jobs"),
+    ),
+    modifiers(
+        &CommonResponses,
+        &IdempotencyKeyHeader,
+        &AdminAuthentication,
+        &ClearLicense
+    )
+)]
+pub struct ApiDoc;
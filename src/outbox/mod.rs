@@ -0,0 +1,339 @@
+use crate::{config, error::AppError, model::blog::BlogPost};
+use anyhow::{Result, bail};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use futures_util::future::join_all;
+use serde::{Deserialize, Serialize};
+use std::{sync::Arc, time::Duration};
+use tracing::Instrument;
+
+/// PostgreSQL storage.
+pub mod postgres;
+
+/// SQLite storage.
+pub mod sqlite;
+
+/// Something that happened to a blog post.
+///
+/// Events are recorded in the outbox by the same transaction as the change they
+/// describe, so an event is recorded if and only if the change is committed. The
+/// [`Dispatcher`] then hands them to the registered [`EventConsumer`]s.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", content = "post")]
+pub enum DomainEvent {
+    /// A post was created.
+    #[serde(rename = "post.created")]
+    PostCreated(BlogPost),
+    /// A post was updated.
+    #[serde(rename = "post.updated")]
+    PostUpdated(BlogPost),
+    /// A post was deleted; the post is as it was before.
+    #[serde(rename = "post.deleted")]
+    PostDeleted(BlogPost),
+    /// A post was created or updated so that it is published, or its scheduled
+    /// publication date passed.
+    #[serde(rename = "post.published")]
+    PostPublished(BlogPost),
+    /// A published post was updated so that it is no longer published.
+    #[serde(rename = "post.unpublished")]
+    PostUnpublished(BlogPost),
+}
+
+impl DomainEvent {
+    /// Returns the events of a post being saved: [`PostCreated`](Self::PostCreated) if
+    /// there was no post `before`, else [`PostUpdated`](Self::PostUpdated), followed by
+    /// [`PostPublished`](Self::PostPublished) or
+    /// [`PostUnpublished`](Self::PostUnpublished) if the write changed whether the post
+    /// is published.
+    pub fn saved(before: Option<&BlogPost>, after: &BlogPost) -> Vec<Self> {
+        let now = Utc::now();
+        let was_published = before.is_some_and(|post| post.is_published_at(now));
+        let is_published = after.is_published_at(now);
+        let mut events = vec![if before.is_some() {
+            Self::PostUpdated(after.clone())
+        } else {
+            Self::PostCreated(after.clone())
+        }];
+        if is_published && !was_published {
+            events.push(Self::PostPublished(after.clone()));
+        } else if was_published && !is_published {
+            events.push(Self::PostUnpublished(after.clone()));
+        }
+        events
+    }
+
+    /// Returns the name of the event, as stored.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::PostCreated(_) => "post.created",
+            Self::PostUpdated(_) => "post.updated",
+            Self::PostDeleted(_) => "post.deleted",
+            Self::PostPublished(_) => "post.published",
+            Self::PostUnpublished(_) => "post.unpublished",
+        }
+    }
+
+    /// Returns the post the event is about.
+    pub fn post(&self) -> &BlogPost {
+        match self {
+            Self::PostCreated(post)
+            | Self::PostUpdated(post)
+            | Self::PostDeleted(post)
+            | Self::PostPublished(post)
+            | Self::PostUnpublished(post) => post,
+        }
+    }
+
+    /// Returns the ID of the aggregate the event belongs to, which is the ID of the post.
+    /// The events of an aggregate are dispatched in the order they were recorded.
+    pub fn aggregate_id(&self) -> i32 {
+        self.post().id
+    }
+}
+
+/// An event read from the outbox.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OutboxEvent {
+    /// Position of the event in the outbox, increasing with the time it was recorded.
+    pub id: i64,
+    /// The event.
+    pub event: DomainEvent,
+    /// When the event was recorded.
+    pub occurred_at: DateTime<Utc>,
+    /// Attempts made to dispatch the event before this one.
+    pub attempts: i32,
+}
+
+/// Outbox dispatch configuration read from the environment.
+///
+/// | Variable                  | Default | Description                                                      |
+/// |---------------------------|---------|------------------------------------------------------------------|
+/// | `OUTBOX_BATCH_SIZE`       | `64`    | Largest number of events dispatched at once.                     |
+/// | `OUTBOX_POLL_INTERVAL_MS` | `1000`  | Interval between checks for new events.                          |
+/// | `OUTBOX_RETRY_BASE_SECS`  | `1`     | Delay before the first retry, doubled after each failure.        |
+/// | `OUTBOX_RETRY_MAX_SECS`   | `300`   | Longest delay between two attempts.                              |
+/// | `OUTBOX_LEASE_SECS`       | `60`    | How long an event being dispatched is hidden from other servers. |
+#[derive(Debug, Clone)]
+pub struct OutboxConfig {
+    /// Largest number of events claimed at once.
+    pub batch_size: u32,
+    /// Interval between checks for new events.
+    pub poll_interval: Duration,
+    /// Delay before the first retry.
+    pub retry_base: Duration,
+    /// Longest delay between two attempts.
+    pub retry_max: Duration,
+    /// How long a claimed event is reserved for its dispatcher.
+    pub lease: Duration,
+}
+
+impl OutboxConfig {
+    /// Builds the configuration from environment variables.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if any of the variables holds an invalid value, or the batch size
+    /// or lease is zero.
+    pub fn from_env() -> Result<Self> {
+        let secs = |key: &str, default: u64| -> Result<Duration> {
+            Ok(Duration::from_secs(config::parse_or(key, default)?))
+        };
+        let config = Self {
+            batch_size: config::parse_or("OUTBOX_BATCH_SIZE", 64)?,
+            poll_interval: Duration::from_millis(config::parse_or(
+                "OUTBOX_POLL_INTERVAL_MS",
+                1000,
+            )?),
+            retry_base: secs("OUTBOX_RETRY_BASE_SECS", 1)?,
+            retry_max: secs("OUTBOX_RETRY_MAX_SECS", 5 * 60)?,
+            lease: secs("OUTBOX_LEASE_SECS", 60)?,
+        };
+        if config.batch_size == 0 {
+            bail!("Invalid value for OUTBOX_BATCH_SIZE: must be at least 1");
+        }
+        if config.lease.is_zero() {
+            bail!("Invalid value for OUTBOX_LEASE_SECS: must be at least 1");
+        }
+        Ok(config)
+    }
+
+    /// Returns how long to wait before dispatching an event again after its
+    /// `attempts`-th failed attempt.
+    pub fn retry_delay(&self, attempts: u32) -> Duration {
+        let factor = 2u32.saturating_pow(attempts.saturating_sub(1));
+        self.retry_base.saturating_mul(factor).min(self.retry_max)
+    }
+}
+
+/// Storage of the outbox the [`Dispatcher`] drains.
+///
+/// Events are recorded by the [`PostRepository`](crate::repository::PostRepository)
+/// writing the posts, within its transaction.
+#[async_trait]
+pub trait OutboxRepository: Send + Sync {
+    /// Claims, in ID order, up to `limit` due events that are the oldest undispatched
+    /// event of their aggregate, and hides them from other claims until `locked_until`.
+    ///
+    /// Later events of an aggregate cannot be claimed before the earlier ones are
+    /// dispatched, which keeps the events of each aggregate in order even across
+    /// servers.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `AppError` if the storage fails.
+    async fn claim(
+        &self,
+        limit: u32,
+        locked_until: DateTime<Utc>,
+    ) -> Result<Vec<OutboxEvent>, AppError>;
+
+    /// Marks a claimed event as dispatched.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `AppError` if the storage fails.
+    async fn complete(&self, id: i64) -> Result<(), AppError>;
+
+    /// Records a failed attempt to dispatch a claimed event, which is due again at
+    /// `next_attempt_at`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `AppError` if the storage fails.
+    async fn retry(
+        &self,
+        id: i64,
+        next_attempt_at: DateTime<Utc>,
+        error: &str,
+    ) -> Result<(), AppError>;
+}
+
+/// A shared, type-erased [`OutboxRepository`].
+pub type DynOutboxRepository = Arc<dyn OutboxRepository>;
+
+/// Something reacting to domain events, registered with the [`Dispatcher`].
+///
+/// Events are handed over at least once: an event is dispatched again, to every
+/// consumer, if any consumer fails or the server stops before the event is marked as
+/// dispatched. Consumers should therefore tolerate seeing an event twice.
+#[async_trait]
+pub trait EventConsumer: Send + Sync {
+    /// Returns the name of the consumer, as logged and recorded with failures.
+    fn name(&self) -> &'static str;
+
+    /// Handles an event.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `AppError` if the event could not be handled and should be dispatched
+    /// again later.
+    async fn consume(&self, event: &OutboxEvent) -> Result<(), AppError>;
+}
+
+/// Drains the outbox in the background, handing each event to every registered
+/// consumer.
+///
+/// Events of different aggregates are dispatched concurrently; the events of one
+/// aggregate are dispatched one at a time, in order, and a failing event holds back the
+/// later events of its aggregate until it is dispatched.
+#[derive(Clone)]
+pub struct Dispatcher {
+    /// Where the events are read from.
+    outbox: DynOutboxRepository,
+    /// Batch size, polling and retries.
+    config: Arc<OutboxConfig>,
+    /// Consumers, in the order they are called.
+    consumers: Vec<Arc<dyn EventConsumer>>,
+}
+
+impl Dispatcher {
+    /// Creates a dispatcher draining `outbox`, without consumers yet.
+    pub fn new(outbox: DynOutboxRepository, config: OutboxConfig) -> Self {
+        Self {
+            outbox,
+            config: Arc::new(config),
+            consumers: Vec::new(),
+        }
+    }
+
+    /// Registers a consumer, called after the consumers registered before it.
+    #[must_use]
+    pub fn with_consumer(mut self, consumer: Arc<dyn EventConsumer>) -> Self {
+        self.consumers.push(consumer);
+        self
+    }
+
+    /// Dispatches every event that is due and first in line for its aggregate, up to the
+    /// configured batch size, and returns how many were attempted.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `AppError` if the events cannot be claimed.
+    pub async fn dispatch_due(&self) -> Result<usize, AppError> {
+        // Claimed events are dispatched again after the lease if the process stops
+        // meanwhile.
+        let lease = chrono::Duration::from_std(self.config.lease)
+            .map_err(|_| AppError::InternalServerError)?;
+        let claimed = self
+            .outbox
+            .claim(self.config.batch_size, Utc::now() + lease)
+            .await?;
+        let count = claimed.len();
+        join_all(claimed.iter().map(|event| {
+            let span = tracing::info_span!(
+                "domain_event",
+                event_id = event.id,
+                event = event.event.name(),
+                post_id = event.event.aggregate_id()
+            );
+            self.dispatch(event).instrument(span)
+        }))
+        .await;
+        Ok(count)
+    }
+
+    /// Hands a claimed event to every consumer and records the outcome.
+    async fn dispatch(&self, event: &OutboxEvent) {
+        let mut failure = None;
+        for consumer in &self.consumers {
+            if let Err(err) = consumer.consume(event).await {
+                failure = Some(format!("{}: {err}", consumer.name()));
+                break;
+            }
+        }
+        let recorded = match failure {
+            None => self.outbox.complete(event.id).await,
+            Some(error) => {
+                let attempts = u32::try_from(event.attempts)
+                    .unwrap_or_default()
+                    .saturating_add(1);
+                tracing::warn!(attempts, "failed to dispatch a domain event: {error}");
+                let delay = chrono::Duration::from_std(self.config.retry_delay(attempts))
+                    .unwrap_or(chrono::Duration::MAX);
+                let next_attempt_at = Utc::now()
+                    .checked_add_signed(delay)
+                    .unwrap_or(DateTime::<Utc>::MAX_UTC);
+                self.outbox.retry(event.id, next_attempt_at, &error).await
+            }
+        };
+        if let Err(err) = recorded {
+            tracing::warn!("failed to record the dispatch of a domain event: {err}");
+        }
+    }
+
+    /// Dispatches events in the background for as long as the process runs, checking at
+    /// the configured interval.
+    pub fn spawn(self) {
+        tokio::spawn(async move {
+            loop {
+                match self.dispatch_due().await {
+                    // Dispatching an event may have made the next one of its aggregate due.
+                    Ok(count) if count > 0 => continue,
+                    Ok(_) => {}
+                    Err(err) => tracing::warn!("failed to claim domain events: {err}"),
+                }
+                tokio::time::sleep(self.config.poll_interval).await;
+            }
+        });
+    }
+}
@@ -0,0 +1,179 @@
+use super::{DomainEvent, OutboxEvent, OutboxRepository};
+use crate::{error::AppError, telemetry::span};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use sqlx::{PgConnection, PgPool};
+use tracing::Instrument;
+
+/// Table of the outbox, reported on query spans.
+const TABLE: &str = "domain_events";
+
+/// Database system reported on query spans.
+const SYSTEM: &str = "postgresql";
+
+/// Records events in the outbox, in order, on a connection that is in the transaction
+/// making the changes they describe.
+pub(crate) async fn record(
+    conn: &mut PgConnection,
+    events: &[DomainEvent],
+) -> Result<(), AppError> {
+    if events.is_empty() {
+        return Ok(());
+    }
+    let aggregates: Vec<i32> = events.iter().map(DomainEvent::aggregate_id).collect();
+    let names: Vec<&str> = events.iter().map(DomainEvent::name).collect();
+    let payloads = events
+        .iter()
+        .map(serde_json::to_value)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|err| {
+            tracing::error!("failed to serialize a domain event: {err}");
+            AppError::InternalServerError
+        })?;
+    sqlx::query!(
+        r#"
+        INSERT INTO domain_events (aggregate_id, event_type, payload)
+        SELECT event.aggregate_id, event.event_type, event.payload
+        FROM UNNEST($1::INTEGER[], $2::TEXT[], $3::JSONB[]) WITH ORDINALITY
+            AS event(aggregate_id, event_type, payload, position)
+        ORDER BY event.position;
+        "#,
+        &aggregates,
+        &names as &[&str],
+        &payloads
+    )
+    .execute(conn)
+    .instrument(span::db_query_on(SYSTEM, "INSERT", TABLE))
+    .await?;
+    Ok(())
+}
+
+/// Converts a stored event, logging and failing on one that cannot be read.
+fn event(
+    id: i64,
+    payload: serde_json::Value,
+    occurred_at: DateTime<Utc>,
+    attempts: i32,
+) -> Result<OutboxEvent, AppError> {
+    let event = serde_json::from_value(payload).map_err(|err| {
+        tracing::error!(event_id = id, "invalid domain event: {err}");
+        AppError::InternalServerError
+    })?;
+    Ok(OutboxEvent {
+        id,
+        event,
+        occurred_at,
+        attempts,
+    })
+}
+
+/// [`OutboxRepository`] backed by the `domain_events` table.
+///
+/// Events are claimed with `FOR UPDATE SKIP LOCKED`, so that several servers can drain
+/// the same outbox.
+#[derive(Debug, Clone)]
+pub struct PgOutboxRepository {
+    /// Pool of the primary database.
+    pool: PgPool,
+}
+
+impl PgOutboxRepository {
+    /// Creates a repository running its queries on `pool`.
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    /// Returns an event of the outbox by ID, dispatched or not, if it exists.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `AppError` if the query fails or the event cannot be read.
+    pub async fn find(&self, id: i64) -> Result<Option<OutboxEvent>, AppError> {
+        let row = sqlx::query!(
+            "SELECT id, payload, occurred_at, attempts FROM domain_events WHERE id = $1;",
+            id
+        )
+        .fetch_optional(&self.pool)
+        .instrument(span::db_query_on(SYSTEM, "SELECT", TABLE))
+        .await?;
+        row.map(|row| event(row.id, row.payload, row.occurred_at, row.attempts))
+            .transpose()
+    }
+}
+
+#[async_trait]
+impl OutboxRepository for PgOutboxRepository {
+    async fn claim(
+        &self,
+        limit: u32,
+        locked_until: DateTime<Utc>,
+    ) -> Result<Vec<OutboxEvent>, AppError> {
+        let rows = sqlx::query!(
+            r#"
+            WITH due AS (
+                SELECT id FROM domain_events AS e
+                WHERE dispatched_at IS NULL
+                  AND next_attempt_at <= NOW()
+                  AND NOT EXISTS (
+                      SELECT 1 FROM domain_events AS earlier
+                      WHERE earlier.aggregate_id = e.aggregate_id
+                        AND earlier.dispatched_at IS NULL
+                        AND earlier.id < e.id
+                  )
+                ORDER BY id
+                LIMIT $1
+                FOR UPDATE SKIP LOCKED
+            )
+            UPDATE domain_events AS e
+            SET next_attempt_at = $2
+            FROM due
+            WHERE e.id = due.id
+            RETURNING e.id, e.payload, e.occurred_at, e.attempts;
+            "#,
+            i64::from(limit),
+            locked_until
+        )
+        .fetch_all(&self.pool)
+        .instrument(span::db_query_on(SYSTEM, "UPDATE", TABLE))
+        .await?;
+        let mut claimed = rows
+            .into_iter()
+            .map(|row| event(row.id, row.payload, row.occurred_at, row.attempts))
+            .collect::<Result<Vec<_>, _>>()?;
+        claimed.sort_unstable_by_key(|event| event.id);
+        Ok(claimed)
+    }
+
+    async fn complete(&self, id: i64) -> Result<(), AppError> {
+        sqlx::query!(
+            "UPDATE domain_events SET dispatched_at = NOW(), last_error = NULL WHERE id = $1;",
+            id
+        )
+        .execute(&self.pool)
+        .instrument(span::db_query_on(SYSTEM, "UPDATE", TABLE))
+        .await?;
+        Ok(())
+    }
+
+    async fn retry(
+        &self,
+        id: i64,
+        next_attempt_at: DateTime<Utc>,
+        error: &str,
+    ) -> Result<(), AppError> {
+        sqlx::query!(
+            r#"
+            UPDATE domain_events
+            SET attempts = attempts + 1, next_attempt_at = $2, last_error = $3
+            WHERE id = $1;
+            "#,
+            id,
+            next_attempt_at,
+            error
+        )
+        .execute(&self.pool)
+        .instrument(span::db_query_on(SYSTEM, "UPDATE", TABLE))
+        .await?;
+        Ok(())
+    }
+}
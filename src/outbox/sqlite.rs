@@ -0,0 +1,149 @@
+use super::{DomainEvent, OutboxEvent, OutboxRepository};
+use crate::{error::AppError, telemetry::span};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use sqlx::{SqliteConnection, SqlitePool, types::Json};
+use tracing::Instrument;
+
+/// Table of the outbox, reported on query spans.
+const TABLE: &str = "domain_events";
+
+/// Database system reported on query spans.
+const SYSTEM: &str = "sqlite";
+
+/// Records events in the outbox, in order, on a connection that is in the transaction
+/// making the changes they describe.
+pub(crate) async fn record(
+    conn: &mut SqliteConnection,
+    events: &[DomainEvent],
+) -> Result<(), AppError> {
+    if events.is_empty() {
+        return Ok(());
+    }
+    // The events are bound as one JSON array and unpacked with json_each, so a single
+    // statement records them all.
+    let batch: Vec<serde_json::Value> = events
+        .iter()
+        .map(|event| {
+            serde_json::json!({
+                "aggregate_id": event.aggregate_id(),
+                "event_type": event.name(),
+                "payload": event,
+            })
+        })
+        .collect();
+    sqlx::query(
+        r#"
+        INSERT INTO domain_events (aggregate_id, event_type, payload, next_attempt_at)
+        SELECT value ->> 'aggregate_id', value ->> 'event_type', value -> 'payload', ?2
+        FROM json_each(?1)
+        ORDER BY key;
+        "#,
+    )
+    .bind(Json(batch))
+    .bind(Utc::now().timestamp_millis())
+    .execute(conn)
+    .instrument(span::db_query_on(SYSTEM, "INSERT", TABLE))
+    .await?;
+    Ok(())
+}
+
+/// [`OutboxRepository`] backed by a SQLite database migrated from `migrations/sqlite`.
+///
+/// The times events are due are stored as Unix milliseconds.
+#[derive(Debug, Clone)]
+pub struct SqliteOutboxRepository {
+    /// Pool the queries run on.
+    pool: SqlitePool,
+}
+
+impl SqliteOutboxRepository {
+    /// Creates a repository running its queries on `pool`.
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl OutboxRepository for SqliteOutboxRepository {
+    async fn claim(
+        &self,
+        limit: u32,
+        locked_until: DateTime<Utc>,
+    ) -> Result<Vec<OutboxEvent>, AppError> {
+        let rows: Vec<(i64, Json<DomainEvent>, DateTime<Utc>, i32)> = sqlx::query_as(
+            r#"
+            UPDATE domain_events
+            SET next_attempt_at = ?2
+            WHERE id IN (
+                SELECT id FROM domain_events AS e
+                WHERE dispatched_at IS NULL
+                  AND next_attempt_at <= ?1
+                  AND NOT EXISTS (
+                      SELECT 1 FROM domain_events AS earlier
+                      WHERE earlier.aggregate_id = e.aggregate_id
+                        AND earlier.dispatched_at IS NULL
+                        AND earlier.id < e.id
+                  )
+                ORDER BY id
+                LIMIT ?3
+            )
+            RETURNING id, payload, occurred_at, attempts;
+            "#,
+        )
+        .bind(Utc::now().timestamp_millis())
+        .bind(locked_until.timestamp_millis())
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .instrument(span::db_query_on(SYSTEM, "UPDATE", TABLE))
+        .await?;
+        let mut claimed: Vec<OutboxEvent> = rows
+            .into_iter()
+            .map(|(id, event, occurred_at, attempts)| OutboxEvent {
+                id,
+                event: event.0,
+                occurred_at,
+                attempts,
+            })
+            .collect();
+        claimed.sort_unstable_by_key(|event| event.id);
+        Ok(claimed)
+    }
+
+    async fn complete(&self, id: i64) -> Result<(), AppError> {
+        sqlx::query(
+            r#"
+            UPDATE domain_events
+            SET dispatched_at = strftime('%Y-%m-%dT%H:%M:%fZ', 'now'), last_error = NULL
+            WHERE id = ?1;
+            "#,
+        )
+        .bind(id)
+        .execute(&self.pool)
+        .instrument(span::db_query_on(SYSTEM, "UPDATE", TABLE))
+        .await?;
+        Ok(())
+    }
+
+    async fn retry(
+        &self,
+        id: i64,
+        next_attempt_at: DateTime<Utc>,
+        error: &str,
+    ) -> Result<(), AppError> {
+        sqlx::query(
+            r#"
+            UPDATE domain_events
+            SET attempts = attempts + 1, next_attempt_at = ?2, last_error = ?3
+            WHERE id = ?1;
+            "#,
+        )
+        .bind(id)
+        .bind(next_attempt_at.timestamp_millis())
+        .bind(error)
+        .execute(&self.pool)
+        .instrument(span::db_query_on(SYSTEM, "UPDATE", TABLE))
+        .await?;
+        Ok(())
+    }
+}
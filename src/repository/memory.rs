@@ -0,0 +1,406 @@
+use super::{PostFilter, PostRepository, Reassignment, Upserted, scheduled_and_due, upsert_slug};
+use crate::{
+    error::AppError,
+    model::blog::{BlogPost, BlogPostBody},
+    outbox::{DomainEvent, OutboxEvent, OutboxRepository},
+};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use futures_util::stream::{self, BoxStream, StreamExt};
+use std::{
+    collections::{BTreeMap, HashSet},
+    sync::{Mutex, MutexGuard},
+};
+
+/// [`PostRepository`] keeping posts in memory.
+///
+/// Behaves like [`PgPostRepository`](super::postgres::PgPostRepository): IDs start at
+/// `1` and are never reused, and search is a case-insensitive substring match. Posts
+/// are lost when the repository is dropped.
+///
+/// The repository is also the [`OutboxRepository`] its writes record their
+/// [`DomainEvent`]s in, under the same lock as the posts.
+#[derive(Debug, Default)]
+pub struct InMemoryPostRepository {
+    /// Stored posts and the last assigned ID.
+    inner: Mutex<Store>,
+}
+
+/// The mutable state of an [`InMemoryPostRepository`].
+#[derive(Debug, Default)]
+struct Store {
+    /// Posts keyed by ID, so iteration is ordered by ID.
+    posts: BTreeMap<i32, BlogPost>,
+    /// The ID assigned to the most recently created post.
+    last_id: i32,
+    /// The outbox, ordered by ID.
+    events: Vec<StoredEvent>,
+}
+
+/// An event in the outbox of an [`InMemoryPostRepository`].
+#[derive(Debug)]
+struct StoredEvent {
+    /// The event and the attempts made to dispatch it.
+    event: OutboxEvent,
+    /// When the event is due, pushed back while it is claimed and after failures.
+    next_attempt_at: DateTime<Utc>,
+    /// Whether the event was dispatched.
+    dispatched: bool,
+}
+
+impl Store {
+    /// Appends events to the outbox.
+    fn record(&mut self, events: Vec<DomainEvent>) {
+        let now = Utc::now();
+        for event in events {
+            let id = self.events.last().map_or(1, |last| last.event.id + 1);
+            self.events.push(StoredEvent {
+                event: OutboxEvent {
+                    id,
+                    event,
+                    occurred_at: now,
+                    attempts: 0,
+                },
+                next_attempt_at: now,
+                dispatched: false,
+            });
+        }
+    }
+
+    /// Returns the undispatched event with the ID, if any.
+    fn pending_event(&mut self, id: i64) -> Option<&mut StoredEvent> {
+        self.events
+            .iter_mut()
+            .find(|stored| stored.event.id == id && !stored.dispatched)
+    }
+
+    /// Returns whether the last `post.published` or `post.unpublished` event of a post
+    /// is `post.published`.
+    fn announced(&self, id: i32) -> bool {
+        self.events
+            .iter()
+            .rev()
+            .map(|stored| &stored.event.event)
+            .find(|event| {
+                event.aggregate_id() == id
+                    && matches!(
+                        event,
+                        DomainEvent::PostPublished(_) | DomainEvent::PostUnpublished(_)
+                    )
+            })
+            .is_some_and(|event| matches!(event, DomainEvent::PostPublished(_)))
+    }
+
+    /// Returns the ID of the post with the slug, if any.
+    fn find_slug(&self, slug: &str) -> Option<i32> {
+        self.posts
+            .values()
+            .find(|post| post.slug.as_deref() == Some(slug))
+            .map(|post| post.id)
+    }
+
+    /// Rejects a slug already used by a post other than `id`, like the unique index of
+    /// the database backends.
+    fn check_slug(&self, slug: Option<&str>, id: Option<i32>) -> Result<(), AppError> {
+        match slug.and_then(|slug| self.find_slug(slug)) {
+            Some(owner) if Some(owner) != id => Err(AppError::Conflict(
+                "A blog post with this slug already exists".to_string(),
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// Stores a new post under the next ID.
+    fn insert(&mut self, post: &BlogPostBody) -> Result<BlogPost, AppError> {
+        self.check_slug(post.slug(), None)?;
+        let id = self
+            .last_id
+            .checked_add(1)
+            .ok_or(AppError::InternalServerError)?;
+        let now = Some(Utc::now());
+        let created = BlogPost {
+            id,
+            title: post.title.clone(),
+            content: post.content.clone(),
+            category: post.category.clone(),
+            tags: post.tags.clone(),
+            slug: post.slug().map(str::to_string),
+            author: post.author().map(str::to_string),
+            draft: post.draft.unwrap_or_default(),
+            published_at: post.published_at(),
+            featured_image_id: post.featured_image_id(),
+            meta_title: post.meta_title().map(str::to_string),
+            meta_description: post.meta_description().map(str::to_string),
+            canonical_url: post.canonical_url().map(str::to_string),
+            noindex: post.noindex.unwrap_or_default(),
+            created_at: now,
+            updated_at: now,
+        };
+        self.last_id = id;
+        self.posts.insert(id, created.clone());
+        Ok(created)
+    }
+
+    /// Updates a stored post with `post`; see [`BlogPostBody::apply_to`].
+    fn update(&mut self, id: i32, post: &BlogPostBody) -> Result<Option<BlogPost>, AppError> {
+        self.check_slug(post.slug(), Some(id))?;
+        Ok(self.posts.get_mut(&id).map(|stored| {
+            let mut updated = post.apply_to(stored);
+            if updated != *stored {
+                updated.updated_at = Some(Utc::now());
+            }
+            stored.clone_from(&updated);
+            updated
+        }))
+    }
+}
+
+impl InMemoryPostRepository {
+    /// Creates an empty repository.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Locks the store, recovering it if a previous holder panicked.
+    fn store(&self) -> MutexGuard<'_, Store> {
+        self.inner
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+}
+
+#[async_trait]
+impl PostRepository for InMemoryPostRepository {
+    async fn create(&self, post: &BlogPostBody) -> Result<BlogPost, AppError> {
+        let mut store = self.store();
+        let created = store.insert(post)?;
+        store.record(DomainEvent::saved(None, &created));
+        Ok(created)
+    }
+
+    async fn create_many(&self, posts: &[BlogPostBody]) -> Result<Vec<BlogPost>, AppError> {
+        let mut store = self.store();
+        // Check for ID exhaustion and slug conflicts up front, so that either every post
+        // is stored or none is.
+        i32::try_from(posts.len())
+            .ok()
+            .and_then(|count| store.last_id.checked_add(count))
+            .ok_or(AppError::InternalServerError)?;
+        let mut slugs = HashSet::new();
+        for slug in posts.iter().filter_map(BlogPostBody::slug) {
+            if !slugs.insert(slug) {
+                return Err(AppError::Conflict(
+                    "A blog post with this slug already exists".to_string(),
+                ));
+            }
+            store.check_slug(Some(slug), None)?;
+        }
+        let created = posts
+            .iter()
+            .map(|post| store.insert(post))
+            .collect::<Result<Vec<_>, _>>()?;
+        store.record(
+            created
+                .iter()
+                .flat_map(|post| DomainEvent::saved(None, post))
+                .collect(),
+        );
+        Ok(created)
+    }
+
+    async fn get(&self, id: i32) -> Result<Option<BlogPost>, AppError> {
+        Ok(self.store().posts.get(&id).cloned())
+    }
+
+    async fn get_by_slug(&self, slug: &str) -> Result<Option<BlogPost>, AppError> {
+        let store = self.store();
+        Ok(store
+            .find_slug(slug)
+            .and_then(|id| store.posts.get(&id))
+            .cloned())
+    }
+
+    async fn list(&self, filter: &PostFilter) -> Result<Vec<BlogPost>, AppError> {
+        Ok(self
+            .store()
+            .posts
+            .values()
+            .filter(|post| filter.matches(post))
+            .cloned()
+            .collect())
+    }
+
+    fn stream(&self, filter: &PostFilter) -> BoxStream<'static, Result<BlogPost, AppError>> {
+        // The posts are already in memory; copying the matching ones releases the lock
+        // before the stream is consumed.
+        let posts: Vec<BlogPost> = self
+            .store()
+            .posts
+            .values()
+            .filter(|post| filter.matches(post))
+            .cloned()
+            .collect();
+        stream::iter(posts.into_iter().map(Ok)).boxed()
+    }
+
+    async fn update(&self, id: i32, post: &BlogPostBody) -> Result<Option<BlogPost>, AppError> {
+        let mut store = self.store();
+        let Some(before) = store.posts.get(&id).cloned() else {
+            return Ok(None);
+        };
+        let updated = store.update(id, post)?;
+        if let Some(updated) = &updated {
+            store.record(DomainEvent::saved(Some(&before), updated));
+        }
+        Ok(updated)
+    }
+
+    async fn upsert_by_slug(&self, post: &BlogPostBody) -> Result<Upserted, AppError> {
+        let slug = upsert_slug(post)?;
+        let mut store = self.store();
+        let before = store
+            .find_slug(slug)
+            .and_then(|id| store.posts.get(&id))
+            .cloned();
+        let upserted = match &before {
+            Some(before) => Upserted::Updated(
+                store
+                    .update(before.id, post)?
+                    .ok_or(AppError::InternalServerError)?,
+            ),
+            None => Upserted::Created(store.insert(post)?),
+        };
+        store.record(DomainEvent::saved(before.as_ref(), upserted.post()));
+        Ok(upserted)
+    }
+
+    async fn delete(&self, id: i32) -> Result<bool, AppError> {
+        let mut store = self.store();
+        let Some(deleted) = store.posts.remove(&id) else {
+            return Ok(false);
+        };
+        store.record(vec![DomainEvent::PostDeleted(deleted)]);
+        Ok(true)
+    }
+
+    async fn reassign(
+        &self,
+        filter: &PostFilter,
+        changes: &Reassignment,
+        except: &[i32],
+    ) -> Result<Vec<i32>, AppError> {
+        let mut store = self.store();
+        let now = Some(Utc::now());
+        let mut updated = Vec::new();
+        let mut events = Vec::new();
+        for post in store
+            .posts
+            .values_mut()
+            .filter(|post| filter.matches(post) && !except.contains(&post.id))
+        {
+            if let Some((category, tags)) = changes.apply(&post.category, &post.tags) {
+                if category != post.category || tags != post.tags {
+                    post.category = category;
+                    post.tags = tags;
+                    post.updated_at = now;
+                    events.push(DomainEvent::PostUpdated(post.clone()));
+                }
+                updated.push(post.id);
+            }
+        }
+        store.record(events);
+        Ok(updated)
+    }
+
+    async fn delete_many(&self, ids: &[i32]) -> Result<Vec<i32>, AppError> {
+        let mut store = self.store();
+        let mut deleted: Vec<BlogPost> =
+            ids.iter().filter_map(|id| store.posts.remove(id)).collect();
+        deleted.sort_unstable_by_key(|post| post.id);
+        let ids = deleted.iter().map(|post| post.id).collect();
+        store.record(deleted.into_iter().map(DomainEvent::PostDeleted).collect());
+        Ok(ids)
+    }
+
+    async fn search(&self, term: &str) -> Result<Vec<BlogPost>, AppError> {
+        let term = term.to_lowercase();
+        let contains = |text: &str| text.to_lowercase().contains(&term);
+        Ok(self
+            .store()
+            .posts
+            .values()
+            .filter(|post| {
+                contains(&post.title) || contains(&post.content) || contains(&post.tags.join(","))
+            })
+            .cloned()
+            .collect())
+    }
+
+    async fn announce_scheduled(&self, now: DateTime<Utc>) -> Result<Vec<BlogPost>, AppError> {
+        let mut store = self.store();
+        let due: Vec<BlogPost> = store
+            .posts
+            .values()
+            .filter(|post| scheduled_and_due(post, now) && !store.announced(post.id))
+            .cloned()
+            .collect();
+        store.record(
+            due.iter()
+                .cloned()
+                .map(DomainEvent::PostPublished)
+                .collect(),
+        );
+        Ok(due)
+    }
+
+    async fn rebuild_search_index(&self) -> Result<(), AppError> {
+        // Searches scan the posts directly; there is no index to rebuild.
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl OutboxRepository for InMemoryPostRepository {
+    async fn claim(
+        &self,
+        limit: u32,
+        locked_until: DateTime<Utc>,
+    ) -> Result<Vec<OutboxEvent>, AppError> {
+        let limit = usize::try_from(limit).unwrap_or(usize::MAX);
+        let now = Utc::now();
+        let mut store = self.store();
+        // Only the oldest undispatched event of each post may be claimed.
+        let mut seen = HashSet::new();
+        let mut claimed = Vec::new();
+        for stored in store.events.iter_mut().filter(|stored| !stored.dispatched) {
+            if claimed.len() >= limit {
+                break;
+            }
+            if seen.insert(stored.event.event.aggregate_id()) && stored.next_attempt_at <= now {
+                stored.next_attempt_at = locked_until;
+                claimed.push(stored.event.clone());
+            }
+        }
+        Ok(claimed)
+    }
+
+    async fn complete(&self, id: i64) -> Result<(), AppError> {
+        if let Some(stored) = self.store().pending_event(id) {
+            stored.dispatched = true;
+        }
+        Ok(())
+    }
+
+    async fn retry(
+        &self,
+        id: i64,
+        next_attempt_at: DateTime<Utc>,
+        _error: &str,
+    ) -> Result<(), AppError> {
+        if let Some(stored) = self.store().pending_event(id) {
+            stored.event.attempts = stored.event.attempts.saturating_add(1);
+            stored.next_attempt_at = next_attempt_at;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,321 @@
+use crate::{
+    error::AppError,
+    jobs::JobHandler,
+    model::blog::{BlogPost, BlogPostBody},
+};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use futures_util::stream::BoxStream;
+use serde::Deserialize;
+use std::sync::Arc;
+use utoipa::{IntoParams, ToSchema};
+
+/// In-memory storage, for tests and local development without a database.
+pub mod memory;
+
+/// PostgreSQL storage.
+pub mod postgres;
+
+/// SQLite storage, with full-text search backed by FTS5.
+pub mod sqlite;
+
+/// Outcome of [`PostRepository::upsert_by_slug`].
+#[derive(Debug, Clone)]
+pub enum Upserted {
+    /// No post had the slug, so a new one was created.
+    Created(BlogPost),
+    /// The post with the slug was updated.
+    Updated(BlogPost),
+}
+
+impl Upserted {
+    /// Returns the created or updated post.
+    pub fn post(&self) -> &BlogPost {
+        match self {
+            Self::Created(post) | Self::Updated(post) => post,
+        }
+    }
+}
+
+/// Maps a unique violation, which can only be on the slug, to `409 Conflict`, and a
+/// foreign key violation, which can only be on the featured image, to
+/// `422 Unprocessable Entity`.
+fn write_error(err: sqlx::Error) -> AppError {
+    match err.as_database_error() {
+        Some(db_err) if db_err.is_unique_violation() => {
+            AppError::Conflict("A blog post with this slug already exists".to_string())
+        }
+        Some(db_err) if db_err.is_foreign_key_violation() => {
+            AppError::UnprocessableEntity("Featured image not found".to_string())
+        }
+        _ => err.into(),
+    }
+}
+
+/// Returns whether a post was scheduled, being written with a publication date later
+/// than its creation, and that date has passed by `now`; see
+/// [`PostRepository::announce_scheduled`].
+fn scheduled_and_due(post: &BlogPost, now: DateTime<Utc>) -> bool {
+    !post.draft
+        && post.published_at.is_some_and(|published_at| {
+            published_at <= now
+                && post
+                    .created_at
+                    .is_some_and(|created_at| published_at > created_at)
+        })
+}
+
+/// Returns the slug [`PostRepository::upsert_by_slug`] matches on.
+fn upsert_slug(post: &BlogPostBody) -> Result<&str, AppError> {
+    post.slug()
+        .ok_or_else(|| AppError::BadRequest("A slug is required to upsert a blog post".to_string()))
+}
+
+/// Filters applied when listing blog posts. Unset filters match every post.
+#[derive(Debug, Clone, Default, Deserialize, IntoParams, ToSchema)]
+#[into_params(parameter_in = Query)]
+pub struct PostFilter {
+    /// Only return posts in this category (exact match).
+    #[schema(example = "Rust")]
+    pub category: Option<String>,
+
+    /// Only return posts carrying this tag (exact match).
+    #[schema(example = "async")]
+    pub tag: Option<String>,
+}
+
+impl PostFilter {
+    /// Returns whether a post passes the filter.
+    pub fn matches(&self, post: &BlogPost) -> bool {
+        self.category
+            .as_ref()
+            .is_none_or(|category| post.category == *category)
+            && self.tag.as_ref().is_none_or(|tag| post.tags.contains(tag))
+    }
+
+    /// Returns whether the filter matches every post.
+    pub fn is_empty(&self) -> bool {
+        self.category.is_none() && self.tag.is_none()
+    }
+}
+
+/// Changes applied to every post matched by a bulk update.
+#[derive(Debug, Clone, Default, Deserialize, ToSchema)]
+pub struct Reassignment {
+    /// Move the posts to this category.
+    #[schema(example = "Programming")]
+    pub category: Option<String>,
+
+    /// Tags to add to the posts that do not carry them yet.
+    #[serde(default)]
+    #[schema(example = json!(["rust"]))]
+    pub add_tags: Vec<String>,
+
+    /// Tags to remove from the posts.
+    #[serde(default)]
+    #[schema(example = json!(["draft"]))]
+    pub remove_tags: Vec<String>,
+}
+
+impl Reassignment {
+    /// Returns whether the reassignment changes nothing.
+    pub fn is_empty(&self) -> bool {
+        self.category.is_none() && self.add_tags.is_empty() && self.remove_tags.is_empty()
+    }
+
+    /// Returns the category and tags of a post after the reassignment, or `None` if
+    /// the post would be left without tags.
+    ///
+    /// Tags keep their order, added tags go last and duplicates are dropped.
+    pub fn apply(&self, category: &str, tags: &[String]) -> Option<(String, Vec<String>)> {
+        let mut reassigned: Vec<String> = Vec::new();
+        for tag in tags.iter().chain(&self.add_tags) {
+            if !self.remove_tags.contains(tag) && !reassigned.contains(tag) {
+                reassigned.push(tag.clone());
+            }
+        }
+        if reassigned.is_empty() {
+            return None;
+        }
+        let category = self.category.as_deref().unwrap_or(category).to_string();
+        Some((category, reassigned))
+    }
+}
+
+/// Storage for blog posts.
+///
+/// Handlers depend on this trait through [`AppState`](crate::state::AppState) rather
+/// than on a database connection, so the storage can be swapped, e.g. for
+/// [`memory::InMemoryPostRepository`] in tests.
+///
+/// Lookups by ID return `None` (or `false` for [`delete`](Self::delete)) when no post
+/// has the ID; mapping that to `404 Not Found` is left to the caller.
+#[async_trait]
+pub trait PostRepository: Send + Sync {
+    /// Stores a new post and returns it with its assigned ID and timestamps.
+    ///
+    /// Not every storage checks that the featured image exists, so callers should.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `AppError::Conflict` if another post has the slug, an
+    /// `AppError::UnprocessableEntity` if the storage finds no featured image, or an
+    /// `AppError` if the storage fails.
+    async fn create(&self, post: &BlogPostBody) -> Result<BlogPost, AppError>;
+
+    /// Returns the post with the given ID.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `AppError` if the storage fails.
+    async fn get(&self, id: i32) -> Result<Option<BlogPost>, AppError>;
+
+    /// Returns the post with the given slug.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `AppError` if the storage fails.
+    async fn get_by_slug(&self, slug: &str) -> Result<Option<BlogPost>, AppError>;
+
+    /// Returns every post passing the filter, ordered by ID.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `AppError` if the storage fails.
+    async fn list(&self, filter: &PostFilter) -> Result<Vec<BlogPost>, AppError>;
+
+    /// Stores several new posts at once and returns them in the same order.
+    ///
+    /// Either every post is stored or, if the storage fails, none is.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `AppError::Conflict` if two posts, or a post and a stored post, have
+    /// the same slug, or an `AppError` if the storage fails.
+    async fn create_many(&self, posts: &[BlogPostBody]) -> Result<Vec<BlogPost>, AppError>;
+
+    /// Streams every post passing the filter, ordered by ID, as it is read from the
+    /// storage rather than collecting the posts first.
+    ///
+    /// The stream owns everything it needs, so it can outlive the repository borrow,
+    /// e.g. as a response body.
+    fn stream(&self, filter: &PostFilter) -> BoxStream<'static, Result<BlogPost, AppError>>;
+
+    /// Replaces the title, content, category and tags of a post and returns it.
+    ///
+    /// The slug, author, draft flag, publication date, featured image and SEO fields are
+    /// replaced only if set in `post`, and cleared if sent as `null`; see
+    /// [`BlogPostBody::apply_to`]. The update time is set to the current time if anything
+    /// changed.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `AppError::Conflict` if another post has the slug, an
+    /// `AppError::UnprocessableEntity` if the storage finds no featured image, or an
+    /// `AppError` if the storage fails.
+    async fn update(&self, id: i32, post: &BlogPostBody) -> Result<Option<BlogPost>, AppError>;
+
+    /// Updates the post with the slug of `post` like [`update`](Self::update), or creates
+    /// it if no post has the slug.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `AppError::BadRequest` if `post` has no slug, or an `AppError` if the
+    /// storage fails.
+    async fn upsert_by_slug(&self, post: &BlogPostBody) -> Result<Upserted, AppError>;
+
+    /// Deletes a post, returning whether it existed.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `AppError` if the storage fails.
+    async fn delete(&self, id: i32) -> Result<bool, AppError>;
+
+    /// Applies a reassignment to every post passing the filter, except the posts whose
+    /// IDs are in `except`, and returns the IDs of the updated posts, in ascending order.
+    ///
+    /// Posts the reassignment would leave without tags are not updated, and the update
+    /// time is only set for posts it changes. Either every matching post is updated or,
+    /// if the storage fails, none is.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `AppError` if the storage fails.
+    async fn reassign(
+        &self,
+        filter: &PostFilter,
+        changes: &Reassignment,
+        except: &[i32],
+    ) -> Result<Vec<i32>, AppError>;
+
+    /// Deletes several posts at once and returns the IDs of the posts that existed, in
+    /// ascending order.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `AppError` if the storage fails.
+    async fn delete_many(&self, ids: &[i32]) -> Result<Vec<i32>, AppError>;
+
+    /// Returns the posts whose title, content or tags contain `term`, ignoring case.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `AppError` if the storage fails.
+    async fn search(&self, term: &str) -> Result<Vec<BlogPost>, AppError>;
+
+    /// Records a `post.published` event for every post whose scheduled publication date
+    /// has passed by `now` but that has not been announced yet, and returns those posts,
+    /// ordered by ID.
+    ///
+    /// A post is scheduled if it is not a draft and its `published_at` is later than its
+    /// creation. It has been announced if the last `post.published` or
+    /// `post.unpublished` event recorded for it is `post.published`, whether a write or
+    /// an earlier call recorded it.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `AppError` if the storage fails.
+    async fn announce_scheduled(&self, now: DateTime<Utc>) -> Result<Vec<BlogPost>, AppError>;
+
+    /// Rebuilds the indexes used by [`search`](Self::search) from the stored posts.
+    ///
+    /// Storages that search without an index of their own, such as PostgreSQL and the
+    /// in-memory storage, have nothing to rebuild.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `AppError` if the storage fails.
+    async fn rebuild_search_index(&self) -> Result<(), AppError>;
+}
+
+/// A shared, type-erased [`PostRepository`], as stored in the application state.
+pub type DynPostRepository = Arc<dyn PostRepository>;
+
+/// Announces the posts whose scheduled publication date has passed, as the job of kind
+/// `posts.announce_scheduled` run on the schedule of `JOBS_PUBLISH_SCHEDULE`; see
+/// [`PostRepository::announce_scheduled`].
+pub struct AnnounceScheduledPosts {
+    /// Where posts and their events are stored.
+    posts: DynPostRepository,
+}
+
+impl AnnounceScheduledPosts {
+    /// Creates the handler announcing the posts of `posts`.
+    pub fn new(posts: DynPostRepository) -> Self {
+        Self { posts }
+    }
+}
+
+#[async_trait]
+impl JobHandler for AnnounceScheduledPosts {
+    const KIND: &'static str = "posts.announce_scheduled";
+    type Payload = ();
+
+    async fn run(&self, (): ()) -> Result<(), AppError> {
+        let announced = self.posts.announce_scheduled(Utc::now()).await?;
+        if !announced.is_empty() {
+            tracing::info!(announced = announced.len(), "announced scheduled posts");
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,557 @@
+use super::{PostFilter, PostRepository, Reassignment, Upserted, upsert_slug, write_error};
+use crate::{
+    database::replica::Replica,
+    error::AppError,
+    model::blog::{BlogPost, BlogPostBody},
+    outbox::{self, DomainEvent},
+    telemetry::span,
+};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use futures_util::{StreamExt, TryStreamExt, future, stream::BoxStream};
+use sqlx::{Executor, FromRow, PgConnection, PgPool};
+use tracing::Instrument;
+
+/// Database system reported on query spans.
+const SYSTEM: &str = "postgresql";
+
+/// Number of posts inserted per statement by [`PgPostRepository::create_many`].
+const INSERT_BATCH_SIZE: usize = 500;
+
+/// [`PostRepository`] backed by the `blog_posts` table.
+///
+/// Writes always run on the primary, in a transaction recording their
+/// [`DomainEvent`]s in the `domain_events` outbox. Reads run on the replica, if one is
+/// attached and [`Replica::reader`] allows it.
+#[derive(Debug, Clone)]
+pub struct PgPostRepository {
+    /// Pool of the primary database.
+    pool: PgPool,
+    /// Replica serving reads, if any.
+    replica: Option<Replica>,
+}
+
+impl PgPostRepository {
+    /// Creates a repository running its queries on `pool`.
+    pub fn new(pool: PgPool) -> Self {
+        Self {
+            pool,
+            replica: None,
+        }
+    }
+
+    /// Sends reads to `replica` when it is healthy.
+    #[must_use]
+    pub fn with_replica(mut self, replica: Option<Replica>) -> Self {
+        self.replica = replica;
+        self
+    }
+
+    /// Returns the pool read-only queries should run on.
+    fn reader(&self) -> &PgPool {
+        self.replica
+            .as_ref()
+            .map_or(&self.pool, |replica| replica.reader(&self.pool))
+    }
+}
+
+#[async_trait]
+impl PostRepository for PgPostRepository {
+    async fn create(&self, post: &BlogPostBody) -> Result<BlogPost, AppError> {
+        let mut tx = self.pool.begin().await?;
+        let created = create_in(&mut tx, post).await?;
+        tx.commit().await?;
+        Ok(created)
+    }
+
+    async fn create_many(&self, posts: &[BlogPostBody]) -> Result<Vec<BlogPost>, AppError> {
+        let mut tx = self.pool.begin().await?;
+        let mut created = Vec::with_capacity(posts.len());
+        for batch in posts.chunks(INSERT_BATCH_SIZE) {
+            let titles: Vec<&str> = batch.iter().map(|post| post.title.as_str()).collect();
+            let contents: Vec<&str> = batch.iter().map(|post| post.content.as_str()).collect();
+            let categories: Vec<&str> = batch.iter().map(|post| post.category.as_str()).collect();
+            // Arrays of arrays must be rectangular in Postgres, so each post's tags
+            // travel as a JSON array and are turned back into TEXT[] on insert.
+            let tags: Vec<serde_json::Value> = batch
+                .iter()
+                .map(|post| serde_json::json!(post.tags))
+                .collect();
+            let slugs: Vec<Option<&str>> = batch.iter().map(BlogPostBody::slug).collect();
+            let authors: Vec<Option<&str>> = batch.iter().map(BlogPostBody::author).collect();
+            let drafts: Vec<bool> = batch
+                .iter()
+                .map(|post| post.draft.unwrap_or_default())
+                .collect();
+            let published: Vec<Option<DateTime<Utc>>> =
+                batch.iter().map(BlogPostBody::published_at).collect();
+            let featured: Vec<Option<i32>> =
+                batch.iter().map(BlogPostBody::featured_image_id).collect();
+            let meta_titles: Vec<Option<&str>> =
+                batch.iter().map(BlogPostBody::meta_title).collect();
+            let meta_descriptions: Vec<Option<&str>> =
+                batch.iter().map(BlogPostBody::meta_description).collect();
+            let canonical_urls: Vec<Option<&str>> =
+                batch.iter().map(BlogPostBody::canonical_url).collect();
+            let noindex: Vec<bool> = batch
+                .iter()
+                .map(|post| post.noindex.unwrap_or_default())
+                .collect();
+            let mut inserted = sqlx::query_as!(
+                BlogPost,
+                r#"
+                INSERT INTO blog_posts (
+                    title, content, category, tags, slug, author, draft, published_at,
+                    featured_image_id, meta_title, meta_description, canonical_url, noindex
+                )
+                SELECT
+                    batch.title,
+                    batch.content,
+                    batch.category,
+                    ARRAY(SELECT jsonb_array_elements_text(batch.tags)),
+                    batch.slug,
+                    batch.author,
+                    batch.draft,
+                    batch.published_at,
+                    batch.featured_image_id,
+                    batch.meta_title,
+                    batch.meta_description,
+                    batch.canonical_url,
+                    batch.noindex
+                FROM UNNEST(
+                    $1::TEXT[], $2::TEXT[], $3::TEXT[], $4::JSONB[],
+                    $5::TEXT[], $6::TEXT[], $7::BOOLEAN[], $8::TIMESTAMPTZ[],
+                    $9::INTEGER[], $10::TEXT[], $11::TEXT[], $12::TEXT[], $13::BOOLEAN[]
+                ) WITH ORDINALITY AS batch(
+                    title, content, category, tags, slug, author, draft, published_at,
+                    featured_image_id, meta_title, meta_description, canonical_url, noindex,
+                    position
+                )
+                ORDER BY batch.position
+                RETURNING *;
+                "#,
+                &titles as &[&str],
+                &contents as &[&str],
+                &categories as &[&str],
+                &tags,
+                &slugs as &[Option<&str>],
+                &authors as &[Option<&str>],
+                &drafts,
+                &published as &[Option<DateTime<Utc>>],
+                &featured as &[Option<i32>],
+                &meta_titles as &[Option<&str>],
+                &meta_descriptions as &[Option<&str>],
+                &canonical_urls as &[Option<&str>],
+                &noindex
+            )
+            .fetch_all(&mut *tx)
+            .instrument(span::db_query(SYSTEM, "INSERT"))
+            .await
+            .map_err(write_error)?;
+            // IDs are assigned in insertion order, which is the order of the batch.
+            inserted.sort_unstable_by_key(|post| post.id);
+            created.append(&mut inserted);
+        }
+        let events: Vec<DomainEvent> = created
+            .iter()
+            .flat_map(|post| DomainEvent::saved(None, post))
+            .collect();
+        outbox::postgres::record(&mut tx, &events).await?;
+        tx.commit().await?;
+        Ok(created)
+    }
+
+    async fn get(&self, id: i32) -> Result<Option<BlogPost>, AppError> {
+        let post = sqlx::query_as!(BlogPost, "SELECT * FROM blog_posts WHERE id = $1", id)
+            .fetch_optional(self.reader())
+            .instrument(span::db_query(SYSTEM, "SELECT"))
+            .await?;
+        Ok(post)
+    }
+
+    async fn get_by_slug(&self, slug: &str) -> Result<Option<BlogPost>, AppError> {
+        let post = sqlx::query_as!(BlogPost, "SELECT * FROM blog_posts WHERE slug = $1", slug)
+            .fetch_optional(self.reader())
+            .instrument(span::db_query(SYSTEM, "SELECT"))
+            .await?;
+        Ok(post)
+    }
+
+    async fn list(&self, filter: &PostFilter) -> Result<Vec<BlogPost>, AppError> {
+        let posts = sqlx::query_as!(
+            BlogPost,
+            r#"
+            SELECT * FROM blog_posts
+            WHERE ($1::TEXT IS NULL OR category = $1)
+              AND ($2::TEXT IS NULL OR $2 = ANY(tags))
+            ORDER BY id;
+            "#,
+            filter.category,
+            filter.tag
+        )
+        .fetch_all(self.reader())
+        .instrument(span::db_query(SYSTEM, "SELECT"))
+        .await?;
+        Ok(posts)
+    }
+
+    fn stream(&self, filter: &PostFilter) -> BoxStream<'static, Result<BlogPost, AppError>> {
+        // The query macros' `fetch` borrows the pool for as long as the stream lives.
+        // `Executor::fetch` on a pool instead moves a handle to the pool into the
+        // stream, which acquires a connection of its own and holds it until dropped;
+        // rows are read from the cursor as the stream is polled.
+        let query = sqlx::query(
+            r#"
+            SELECT * FROM blog_posts
+            WHERE ($1::TEXT IS NULL OR category = $1)
+              AND ($2::TEXT IS NULL OR $2 = ANY(tags))
+            ORDER BY id;
+            "#,
+        )
+        .bind(filter.category.clone())
+        .bind(filter.tag.clone());
+        let rows = Executor::fetch(self.reader(), query)
+            .and_then(|row| future::ready(BlogPost::from_row(&row)))
+            .map_err(AppError::from)
+            .boxed();
+        span::instrument_stream(rows, span::db_query(SYSTEM, "SELECT"))
+    }
+
+    async fn update(&self, id: i32, post: &BlogPostBody) -> Result<Option<BlogPost>, AppError> {
+        let mut tx = self.pool.begin().await?;
+        let updated = update_in(&mut tx, id, post).await?;
+        tx.commit().await?;
+        Ok(updated)
+    }
+
+    async fn upsert_by_slug(&self, post: &BlogPostBody) -> Result<Upserted, AppError> {
+        let slug = upsert_slug(post)?;
+        let mut tx = self.pool.begin().await?;
+        let before = sqlx::query_as!(
+            BlogPost,
+            "SELECT * FROM blog_posts WHERE slug = $1 FOR UPDATE",
+            slug
+        )
+        .fetch_optional(&mut *tx)
+        .instrument(span::db_query(SYSTEM, "SELECT"))
+        .await?;
+        // A post inserted by another transaction after the lookup makes the insert fail
+        // with a conflict.
+        let upserted = match &before {
+            Some(before) => Upserted::Updated(update_row(&mut tx, &post.apply_to(before)).await?),
+            None => Upserted::Created(insert(&mut tx, post).await?),
+        };
+        outbox::postgres::record(
+            &mut tx,
+            &DomainEvent::saved(before.as_ref(), upserted.post()),
+        )
+        .await?;
+        tx.commit().await?;
+        Ok(upserted)
+    }
+
+    async fn delete(&self, id: i32) -> Result<bool, AppError> {
+        let mut tx = self.pool.begin().await?;
+        let deleted = sqlx::query_as!(
+            BlogPost,
+            "DELETE FROM blog_posts WHERE id = $1 RETURNING *",
+            id
+        )
+        .fetch_optional(&mut *tx)
+        .instrument(span::db_query(SYSTEM, "DELETE"))
+        .await?;
+        let Some(deleted) = deleted else {
+            return Ok(false);
+        };
+        outbox::postgres::record(&mut tx, &[DomainEvent::PostDeleted(deleted)]).await?;
+        tx.commit().await?;
+        Ok(true)
+    }
+
+    async fn reassign(
+        &self,
+        filter: &PostFilter,
+        changes: &Reassignment,
+        except: &[i32],
+    ) -> Result<Vec<i32>, AppError> {
+        let mut tx = self.pool.begin().await?;
+        let rows = sqlx::query!(
+            r#"
+            WITH reassigned AS (
+                SELECT
+                    id,
+                    category AS previous_category,
+                    tags AS previous_tags,
+                    COALESCE($3, category) AS category,
+                    ARRAY(
+                        SELECT tag
+                        FROM UNNEST(tags || $4::TEXT[]) WITH ORDINALITY AS t(tag, position)
+                        WHERE tag <> ALL($5::TEXT[])
+                        GROUP BY tag
+                        ORDER BY MIN(position)
+                    ) AS tags
+                FROM blog_posts
+                WHERE ($1::TEXT IS NULL OR category = $1)
+                  AND ($2::TEXT IS NULL OR $2 = ANY(tags))
+                  AND id <> ALL($6::INT4[])
+            )
+            UPDATE blog_posts
+            SET category = reassigned.category,
+                tags = reassigned.tags,
+                updated_at = CASE
+                    WHEN (blog_posts.category, blog_posts.tags)
+                        IS DISTINCT FROM (reassigned.category, reassigned.tags)
+                    THEN NOW()
+                    ELSE blog_posts.updated_at
+                END
+            FROM reassigned
+            WHERE blog_posts.id = reassigned.id
+              AND CARDINALITY(reassigned.tags) > 0
+            RETURNING
+                blog_posts.id,
+                (reassigned.previous_category, reassigned.previous_tags)
+                    IS DISTINCT FROM (reassigned.category, reassigned.tags) AS "changed!";
+            "#,
+            filter.category,
+            filter.tag,
+            changes.category,
+            &changes.add_tags,
+            &changes.remove_tags,
+            except
+        )
+        .fetch_all(&mut *tx)
+        .instrument(span::db_query(SYSTEM, "UPDATE"))
+        .await?;
+        // Only the posts the reassignment changed are reported as updated.
+        let changed: Vec<i32> = rows
+            .iter()
+            .filter(|row| row.changed)
+            .map(|row| row.id)
+            .collect();
+        let events: Vec<DomainEvent> = sqlx::query_as!(
+            BlogPost,
+            "SELECT * FROM blog_posts WHERE id = ANY($1) ORDER BY id",
+            &changed
+        )
+        .fetch_all(&mut *tx)
+        .instrument(span::db_query(SYSTEM, "SELECT"))
+        .await?
+        .into_iter()
+        .map(DomainEvent::PostUpdated)
+        .collect();
+        outbox::postgres::record(&mut tx, &events).await?;
+        tx.commit().await?;
+        let mut updated: Vec<i32> = rows.into_iter().map(|row| row.id).collect();
+        updated.sort_unstable();
+        Ok(updated)
+    }
+
+    async fn delete_many(&self, ids: &[i32]) -> Result<Vec<i32>, AppError> {
+        let mut tx = self.pool.begin().await?;
+        let mut deleted = sqlx::query_as!(
+            BlogPost,
+            "DELETE FROM blog_posts WHERE id = ANY($1) RETURNING *",
+            ids
+        )
+        .fetch_all(&mut *tx)
+        .instrument(span::db_query(SYSTEM, "DELETE"))
+        .await?;
+        deleted.sort_unstable_by_key(|post| post.id);
+        let ids = deleted.iter().map(|post| post.id).collect();
+        let events: Vec<DomainEvent> = deleted.into_iter().map(DomainEvent::PostDeleted).collect();
+        outbox::postgres::record(&mut tx, &events).await?;
+        tx.commit().await?;
+        Ok(ids)
+    }
+
+    async fn search(&self, term: &str) -> Result<Vec<BlogPost>, AppError> {
+        let posts = sqlx::query_as!(
+            BlogPost,
+            r#"
+            SELECT * FROM blog_posts
+            WHERE
+                title ILIKE '%' || $1 || '%' OR
+                content ILIKE '%' || $1 || '%' OR
+                array_to_string(tags, ',') ILIKE '%' || $1 || '%'
+            ORDER BY id;
+            "#,
+            term
+        )
+        .fetch_all(self.reader())
+        .instrument(span::db_query(SYSTEM, "SELECT"))
+        .await?;
+        Ok(posts)
+    }
+
+    async fn announce_scheduled(&self, now: DateTime<Utc>) -> Result<Vec<BlogPost>, AppError> {
+        let mut tx = self.pool.begin().await?;
+        // Posts being written are left for the next run rather than waited for.
+        let due = sqlx::query_as!(
+            BlogPost,
+            r#"
+            SELECT * FROM blog_posts
+            WHERE NOT draft
+                AND published_at <= $1
+                AND published_at > created_at
+                AND (
+                    SELECT event_type FROM domain_events
+                    WHERE aggregate_id = blog_posts.id
+                        AND event_type IN ('post.published', 'post.unpublished')
+                    ORDER BY id DESC
+                    LIMIT 1
+                ) IS DISTINCT FROM 'post.published'
+            ORDER BY id
+            FOR UPDATE SKIP LOCKED;
+            "#,
+            now
+        )
+        .fetch_all(&mut *tx)
+        .instrument(span::db_query(SYSTEM, "SELECT"))
+        .await?;
+        let events: Vec<DomainEvent> = due
+            .iter()
+            .cloned()
+            .map(DomainEvent::PostPublished)
+            .collect();
+        outbox::postgres::record(&mut tx, &events).await?;
+        tx.commit().await?;
+        Ok(due)
+    }
+
+    async fn rebuild_search_index(&self) -> Result<(), AppError> {
+        // Searches scan the posts with `ILIKE`; there is no index to rebuild.
+        Ok(())
+    }
+}
+
+/// Creates a post like [`PgPostRepository::create`], on a connection that is in a
+/// transaction the caller commits, such as a
+/// [`DatabaseTransaction`](crate::database::extractor::DatabaseTransaction).
+///
+/// # Errors
+///
+/// Returns an `AppError::Conflict` if another post has the slug, or an `AppError` if
+/// the database fails.
+pub async fn create_in(conn: &mut PgConnection, post: &BlogPostBody) -> Result<BlogPost, AppError> {
+    let created = insert(conn, post).await?;
+    outbox::postgres::record(conn, &DomainEvent::saved(None, &created)).await?;
+    Ok(created)
+}
+
+/// Updates a post like [`PgPostRepository::update`], on a connection that is in a
+/// transaction the caller commits.
+///
+/// # Errors
+///
+/// Returns an `AppError::Conflict` if another post has the slug, or an `AppError` if
+/// the database fails.
+pub async fn update_in(
+    conn: &mut PgConnection,
+    id: i32,
+    post: &BlogPostBody,
+) -> Result<Option<BlogPost>, AppError> {
+    // The row is locked until the transaction ends, so the events are computed from the
+    // post as the update found it.
+    let Some(before) = sqlx::query_as!(
+        BlogPost,
+        "SELECT * FROM blog_posts WHERE id = $1 FOR UPDATE",
+        id
+    )
+    .fetch_optional(&mut *conn)
+    .instrument(span::db_query(SYSTEM, "SELECT"))
+    .await?
+    else {
+        return Ok(None);
+    };
+    let updated = update_row(conn, &post.apply_to(&before)).await?;
+    outbox::postgres::record(conn, &DomainEvent::saved(Some(&before), &updated)).await?;
+    Ok(Some(updated))
+}
+
+/// Inserts a post on `conn` and returns it.
+async fn insert(conn: &mut PgConnection, post: &BlogPostBody) -> Result<BlogPost, AppError> {
+    sqlx::query_as!(
+        BlogPost,
+        r#"
+        INSERT INTO blog_posts (
+            title, content, category, tags, slug, author, draft, published_at,
+            featured_image_id, meta_title, meta_description, canonical_url, noindex
+        )
+        VALUES (
+            $1, $2, $3, $4, $5, $6, COALESCE($7, FALSE), $8, $9, $10, $11, $12,
+            COALESCE($13, FALSE)
+        )
+        RETURNING *;
+        "#,
+        post.title,
+        post.content,
+        post.category,
+        &post.tags,
+        post.slug(),
+        post.author(),
+        post.draft,
+        post.published_at(),
+        post.featured_image_id(),
+        post.meta_title(),
+        post.meta_description(),
+        post.canonical_url(),
+        post.noindex
+    )
+    .fetch_one(conn)
+    .instrument(span::db_query(SYSTEM, "INSERT"))
+    .await
+    .map_err(write_error)
+}
+
+/// Writes the fields of `post` to its row on `conn` and returns the stored post.
+///
+/// The update time is set to the current time if anything changed.
+async fn update_row(conn: &mut PgConnection, post: &BlogPost) -> Result<BlogPost, AppError> {
+    sqlx::query_as!(
+        BlogPost,
+        r#"
+        UPDATE blog_posts
+        SET title = $2,
+            content = $3,
+            category = $4,
+            tags = $5,
+            slug = $6,
+            author = $7,
+            draft = $8,
+            published_at = $9,
+            featured_image_id = $10,
+            meta_title = $11,
+            meta_description = $12,
+            canonical_url = $13,
+            noindex = $14,
+            updated_at = CASE
+                WHEN (
+                    title, content, category, tags, slug, author, draft, published_at,
+                    featured_image_id, meta_title, meta_description, canonical_url, noindex
+                ) IS DISTINCT FROM ($2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14)
+                THEN NOW()
+                ELSE updated_at
+            END
+        WHERE id = $1
+        RETURNING *;
+        "#,
+        post.id,
+        post.title,
+        post.content,
+        post.category,
+        &post.tags,
+        post.slug,
+        post.author,
+        post.draft,
+        post.published_at,
+        post.featured_image_id,
+        post.meta_title,
+        post.meta_description,
+        post.canonical_url,
+        post.noindex
+    )
+    .fetch_one(conn)
+    .instrument(span::db_query(SYSTEM, "UPDATE"))
+    .await
+    .map_err(write_error)
+}
@@ -0,0 +1,553 @@
+use super::{
+    PostFilter, PostRepository, Reassignment, Upserted, scheduled_and_due, upsert_slug, write_error,
+};
+use crate::{
+    error::AppError,
+    model::blog::{BlogPost, BlogPostBody},
+    outbox::{self, DomainEvent},
+    telemetry::span,
+};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use futures_util::{StreamExt, TryStreamExt, future, stream::BoxStream};
+use sqlx::{Executor, FromRow, SqliteConnection, SqlitePool, types::Json};
+use tracing::Instrument;
+
+/// Database system reported on query spans.
+const SYSTEM: &str = "sqlite";
+
+/// Number of posts inserted per statement by [`SqlitePostRepository::create_many`].
+const INSERT_BATCH_SIZE: usize = 500;
+
+/// Shortest term the trigram index can match; shorter terms fall back to `LIKE`.
+const MIN_FTS_TERM_CHARS: usize = 3;
+
+/// A `blog_posts` row as stored by SQLite, where tags are a JSON array.
+#[derive(Debug, FromRow)]
+struct PostRow {
+    /// Unique identifier for the blog post.
+    id: i32,
+    /// Title of the blog post.
+    title: String,
+    /// Content of the blog post.
+    content: String,
+    /// Category to which the blog post belongs.
+    category: String,
+    /// Tags, stored as a JSON array of strings.
+    tags: Json<Vec<String>>,
+    /// Unique, URL-friendly name of the blog post.
+    slug: Option<String>,
+    /// Name of the author of the blog post.
+    author: Option<String>,
+    /// Whether the blog post is a draft.
+    draft: bool,
+    /// When the blog post was, or is to be, published.
+    published_at: Option<DateTime<Utc>>,
+    /// ID of the media file shown as the cover of the blog post.
+    featured_image_id: Option<i32>,
+    /// Title shown by search engines and shared links.
+    meta_title: Option<String>,
+    /// Summary shown by search engines and shared links.
+    meta_description: Option<String>,
+    /// Preferred URL of the blog post.
+    canonical_url: Option<String>,
+    /// Whether search engines are asked not to index the blog post.
+    noindex: bool,
+    /// Timestamp when the blog post was created.
+    created_at: Option<DateTime<Utc>>,
+    /// Timestamp when the blog post was last updated.
+    updated_at: Option<DateTime<Utc>>,
+}
+
+impl From<PostRow> for BlogPost {
+    fn from(row: PostRow) -> Self {
+        Self {
+            id: row.id,
+            title: row.title,
+            content: row.content,
+            category: row.category,
+            tags: row.tags.0,
+            slug: row.slug,
+            author: row.author,
+            draft: row.draft,
+            published_at: row.published_at,
+            featured_image_id: row.featured_image_id,
+            meta_title: row.meta_title,
+            meta_description: row.meta_description,
+            canonical_url: row.canonical_url,
+            noindex: row.noindex,
+            created_at: row.created_at,
+            updated_at: row.updated_at,
+        }
+    }
+}
+
+/// Quotes a search term as an FTS5 string, so it is matched literally.
+fn fts_phrase(term: &str) -> String {
+    format!("\"{}\"", term.replace('"', "\"\""))
+}
+
+/// [`PostRepository`] backed by a SQLite database migrated from `migrations/sqlite`.
+///
+/// Queries are checked at runtime rather than at compile time, since the compile-time
+/// checks run against the Postgres schema. Writes run in a transaction recording their
+/// [`DomainEvent`]s in the `domain_events` outbox.
+#[derive(Debug, Clone)]
+pub struct SqlitePostRepository {
+    /// Pool the queries run on.
+    pool: SqlitePool,
+}
+
+impl SqlitePostRepository {
+    /// Creates a repository running its queries on `pool`.
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl PostRepository for SqlitePostRepository {
+    async fn create(&self, post: &BlogPostBody) -> Result<BlogPost, AppError> {
+        let mut tx = self.pool.begin().await?;
+        let created = insert(&mut tx, post).await?;
+        outbox::sqlite::record(&mut tx, &DomainEvent::saved(None, &created)).await?;
+        tx.commit().await?;
+        Ok(created)
+    }
+
+    async fn create_many(&self, posts: &[BlogPostBody]) -> Result<Vec<BlogPost>, AppError> {
+        let now = Utc::now();
+        let mut tx = self.pool.begin().await?;
+        let mut created = Vec::with_capacity(posts.len());
+        for batch in posts.chunks(INSERT_BATCH_SIZE) {
+            // The batch is bound as one JSON array and unpacked with json_each, so a
+            // single statement inserts the whole batch.
+            let rows: Vec<PostRow> = sqlx::query_as(
+                r#"
+                INSERT INTO blog_posts (
+                    title, content, category, tags, slug, author, draft, published_at,
+                    created_at, updated_at, featured_image_id, meta_title, meta_description,
+                    canonical_url, noindex
+                )
+                SELECT
+                    value ->> 'title',
+                    value ->> 'content',
+                    value ->> 'category',
+                    value -> 'tags',
+                    value ->> 'slug',
+                    value ->> 'author',
+                    COALESCE(value ->> 'draft', 0),
+                    value ->> 'published_at',
+                    ?2,
+                    ?2,
+                    value ->> 'featured_image_id',
+                    value ->> 'meta_title',
+                    value ->> 'meta_description',
+                    value ->> 'canonical_url',
+                    COALESCE(value ->> 'noindex', 0)
+                FROM json_each(?1)
+                ORDER BY key
+                RETURNING *;
+                "#,
+            )
+            .bind(Json(batch))
+            .bind(now)
+            .fetch_all(&mut *tx)
+            .instrument(span::db_query(SYSTEM, "INSERT"))
+            .await
+            .map_err(write_error)?;
+            let mut inserted: Vec<BlogPost> = rows.into_iter().map(Into::into).collect();
+            // IDs are assigned in insertion order, which is the order of the batch.
+            inserted.sort_unstable_by_key(|post| post.id);
+            created.append(&mut inserted);
+        }
+        let events: Vec<DomainEvent> = created
+            .iter()
+            .flat_map(|post| DomainEvent::saved(None, post))
+            .collect();
+        outbox::sqlite::record(&mut tx, &events).await?;
+        tx.commit().await?;
+        Ok(created)
+    }
+
+    async fn get(&self, id: i32) -> Result<Option<BlogPost>, AppError> {
+        let row: Option<PostRow> = sqlx::query_as("SELECT * FROM blog_posts WHERE id = ?1")
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .instrument(span::db_query(SYSTEM, "SELECT"))
+            .await?;
+        Ok(row.map(Into::into))
+    }
+
+    async fn get_by_slug(&self, slug: &str) -> Result<Option<BlogPost>, AppError> {
+        let row: Option<PostRow> = sqlx::query_as("SELECT * FROM blog_posts WHERE slug = ?1")
+            .bind(slug)
+            .fetch_optional(&self.pool)
+            .instrument(span::db_query(SYSTEM, "SELECT"))
+            .await?;
+        Ok(row.map(Into::into))
+    }
+
+    async fn list(&self, filter: &PostFilter) -> Result<Vec<BlogPost>, AppError> {
+        let rows: Vec<PostRow> = sqlx::query_as(
+            r#"
+            SELECT * FROM blog_posts
+            WHERE (?1 IS NULL OR category = ?1)
+              AND (?2 IS NULL OR EXISTS (SELECT 1 FROM json_each(tags) WHERE value = ?2))
+            ORDER BY id;
+            "#,
+        )
+        .bind(&filter.category)
+        .bind(&filter.tag)
+        .fetch_all(&self.pool)
+        .instrument(span::db_query(SYSTEM, "SELECT"))
+        .await?;
+        Ok(rows.into_iter().map(Into::into).collect())
+    }
+
+    fn stream(&self, filter: &PostFilter) -> BoxStream<'static, Result<BlogPost, AppError>> {
+        // `Executor::fetch` on a pool moves a handle to the pool into the stream, so the
+        // stream can outlive `self`; see `PgPostRepository::stream`.
+        let query = sqlx::query(
+            r#"
+            SELECT * FROM blog_posts
+            WHERE (?1 IS NULL OR category = ?1)
+              AND (?2 IS NULL OR EXISTS (SELECT 1 FROM json_each(tags) WHERE value = ?2))
+            ORDER BY id;
+            "#,
+        )
+        .bind(filter.category.clone())
+        .bind(filter.tag.clone());
+        let rows = Executor::fetch(&self.pool, query)
+            .and_then(|row| future::ready(PostRow::from_row(&row).map(BlogPost::from)))
+            .map_err(AppError::from)
+            .boxed();
+        span::instrument_stream(rows, span::db_query(SYSTEM, "SELECT"))
+    }
+
+    async fn update(&self, id: i32, post: &BlogPostBody) -> Result<Option<BlogPost>, AppError> {
+        // The events are computed from the post as the update found it; the transaction
+        // keeps the lookup and write together.
+        let mut tx = self.pool.begin().await?;
+        let before: Option<PostRow> = sqlx::query_as("SELECT * FROM blog_posts WHERE id = ?1")
+            .bind(id)
+            .fetch_optional(&mut *tx)
+            .instrument(span::db_query(SYSTEM, "SELECT"))
+            .await?;
+        let Some(before) = before.map(BlogPost::from) else {
+            return Ok(None);
+        };
+        let updated = update_row(&mut tx, &post.apply_to(&before)).await?;
+        outbox::sqlite::record(&mut tx, &DomainEvent::saved(Some(&before), &updated)).await?;
+        tx.commit().await?;
+        Ok(Some(updated))
+    }
+
+    async fn upsert_by_slug(&self, post: &BlogPostBody) -> Result<Upserted, AppError> {
+        let slug = upsert_slug(post)?;
+        // SQLite has no way to tell inserted rows from updated ones in RETURNING, so the
+        // post is looked up first; the transaction keeps the lookup and write together.
+        let mut tx = self.pool.begin().await?;
+        let existing: Option<PostRow> = sqlx::query_as("SELECT * FROM blog_posts WHERE slug = ?1")
+            .bind(slug)
+            .fetch_optional(&mut *tx)
+            .instrument(span::db_query(SYSTEM, "SELECT"))
+            .await?;
+        let before = existing.map(BlogPost::from);
+        let upserted = match &before {
+            Some(before) => Upserted::Updated(update_row(&mut tx, &post.apply_to(before)).await?),
+            None => Upserted::Created(insert(&mut tx, post).await?),
+        };
+        let events = DomainEvent::saved(before.as_ref(), upserted.post());
+        outbox::sqlite::record(&mut tx, &events).await?;
+        tx.commit().await?;
+        Ok(upserted)
+    }
+
+    async fn delete(&self, id: i32) -> Result<bool, AppError> {
+        let mut tx = self.pool.begin().await?;
+        let deleted: Option<PostRow> =
+            sqlx::query_as("DELETE FROM blog_posts WHERE id = ?1 RETURNING *")
+                .bind(id)
+                .fetch_optional(&mut *tx)
+                .instrument(span::db_query(SYSTEM, "DELETE"))
+                .await?;
+        let Some(deleted) = deleted else {
+            return Ok(false);
+        };
+        outbox::sqlite::record(&mut tx, &[DomainEvent::PostDeleted(deleted.into())]).await?;
+        tx.commit().await?;
+        Ok(true)
+    }
+
+    async fn reassign(
+        &self,
+        filter: &PostFilter,
+        changes: &Reassignment,
+        except: &[i32],
+    ) -> Result<Vec<i32>, AppError> {
+        let mut tx = self.pool.begin().await?;
+        let matching: Vec<PostRow> = sqlx::query_as(
+            r#"
+            SELECT * FROM blog_posts
+            WHERE (?1 IS NULL OR category = ?1)
+              AND (?2 IS NULL OR EXISTS (SELECT 1 FROM json_each(tags) WHERE value = ?2))
+            ORDER BY id;
+            "#,
+        )
+        .bind(&filter.category)
+        .bind(&filter.tag)
+        .fetch_all(&mut *tx)
+        .instrument(span::db_query(SYSTEM, "SELECT"))
+        .await?;
+
+        let now = Utc::now();
+        let mut updated = Vec::new();
+        let mut events = Vec::new();
+        for post in matching
+            .into_iter()
+            .map(BlogPost::from)
+            .filter(|post| !except.contains(&post.id))
+        {
+            let Some((category, tags)) = changes.apply(&post.category, &post.tags) else {
+                continue;
+            };
+            let changed = category != post.category || tags != post.tags;
+            sqlx::query(
+                r#"
+                UPDATE blog_posts
+                SET category = ?1, tags = ?2, updated_at = COALESCE(?3, updated_at)
+                WHERE id = ?4;
+                "#,
+            )
+            .bind(&category)
+            .bind(Json(&tags))
+            .bind(changed.then_some(now))
+            .bind(post.id)
+            .execute(&mut *tx)
+            .instrument(span::db_query(SYSTEM, "UPDATE"))
+            .await?;
+            updated.push(post.id);
+            // Only the posts the reassignment changed are reported as updated.
+            if changed {
+                events.push(DomainEvent::PostUpdated(BlogPost {
+                    category,
+                    tags,
+                    updated_at: Some(now),
+                    ..post
+                }));
+            }
+        }
+        outbox::sqlite::record(&mut tx, &events).await?;
+        tx.commit().await?;
+        Ok(updated)
+    }
+
+    async fn delete_many(&self, ids: &[i32]) -> Result<Vec<i32>, AppError> {
+        let mut tx = self.pool.begin().await?;
+        let rows: Vec<PostRow> = sqlx::query_as(
+            "DELETE FROM blog_posts WHERE id IN (SELECT value FROM json_each(?1)) RETURNING *",
+        )
+        .bind(Json(ids))
+        .fetch_all(&mut *tx)
+        .instrument(span::db_query(SYSTEM, "DELETE"))
+        .await?;
+        let mut deleted: Vec<BlogPost> = rows.into_iter().map(Into::into).collect();
+        deleted.sort_unstable_by_key(|post| post.id);
+        let ids = deleted.iter().map(|post| post.id).collect();
+        let events: Vec<DomainEvent> = deleted.into_iter().map(DomainEvent::PostDeleted).collect();
+        outbox::sqlite::record(&mut tx, &events).await?;
+        tx.commit().await?;
+        Ok(ids)
+    }
+
+    async fn search(&self, term: &str) -> Result<Vec<BlogPost>, AppError> {
+        // The trigram index only matches terms of at least three characters; shorter
+        // terms scan the index table with LIKE, which is case-insensitive for ASCII.
+        let query = if term.chars().count() >= MIN_FTS_TERM_CHARS {
+            sqlx::query_as(
+                r#"
+                SELECT blog_posts.* FROM blog_posts
+                JOIN blog_posts_fts ON blog_posts_fts.rowid = blog_posts.id
+                WHERE blog_posts_fts MATCH ?1
+                ORDER BY blog_posts.id;
+                "#,
+            )
+            .bind(fts_phrase(term))
+        } else {
+            sqlx::query_as(
+                r#"
+                SELECT blog_posts.* FROM blog_posts
+                JOIN blog_posts_fts ON blog_posts_fts.rowid = blog_posts.id
+                WHERE
+                    blog_posts_fts.title LIKE '%' || ?1 || '%' OR
+                    blog_posts_fts.content LIKE '%' || ?1 || '%' OR
+                    blog_posts_fts.tags LIKE '%' || ?1 || '%'
+                ORDER BY blog_posts.id;
+                "#,
+            )
+            .bind(term.to_string())
+        };
+        let rows: Vec<PostRow> = query
+            .fetch_all(&self.pool)
+            .instrument(span::db_query(SYSTEM, "SELECT"))
+            .await?;
+        Ok(rows.into_iter().map(Into::into).collect())
+    }
+
+    async fn announce_scheduled(&self, now: DateTime<Utc>) -> Result<Vec<BlogPost>, AppError> {
+        let mut tx = self.pool.begin().await?;
+        // Dates are compared here rather than in SQL, where they are text.
+        let rows: Vec<PostRow> = sqlx::query_as(
+            r#"
+            SELECT * FROM blog_posts
+            WHERE NOT draft
+                AND published_at IS NOT NULL
+                AND (
+                    SELECT event_type FROM domain_events
+                    WHERE aggregate_id = blog_posts.id
+                        AND event_type IN ('post.published', 'post.unpublished')
+                    ORDER BY id DESC
+                    LIMIT 1
+                ) IS NOT 'post.published'
+            ORDER BY id;
+            "#,
+        )
+        .fetch_all(&mut *tx)
+        .instrument(span::db_query(SYSTEM, "SELECT"))
+        .await?;
+        let due: Vec<BlogPost> = rows
+            .into_iter()
+            .map(BlogPost::from)
+            .filter(|post| scheduled_and_due(post, now))
+            .collect();
+        let events: Vec<DomainEvent> = due
+            .iter()
+            .cloned()
+            .map(DomainEvent::PostPublished)
+            .collect();
+        outbox::sqlite::record(&mut tx, &events).await?;
+        tx.commit().await?;
+        Ok(due)
+    }
+
+    async fn rebuild_search_index(&self) -> Result<(), AppError> {
+        let mut tx = self.pool.begin().await?;
+        for statement in [
+            "DELETE FROM blog_posts_fts",
+            r#"
+            INSERT INTO blog_posts_fts (rowid, title, content, tags)
+            SELECT id, title, content, (SELECT group_concat(value, ',') FROM json_each(tags))
+            FROM blog_posts
+            "#,
+            "INSERT INTO blog_posts_fts (blog_posts_fts) VALUES ('optimize')",
+        ] {
+            sqlx::query(statement)
+                .execute(&mut *tx)
+                .instrument(span::db_query(SYSTEM, "REINDEX"))
+                .await?;
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+}
+
+/// Inserts a post on `conn` and returns it.
+async fn insert(conn: &mut SqliteConnection, post: &BlogPostBody) -> Result<BlogPost, AppError> {
+    let row: PostRow = sqlx::query_as(
+        r#"
+        INSERT INTO blog_posts (
+            title, content, category, tags, slug, author, draft, published_at,
+            created_at, updated_at, featured_image_id, meta_title, meta_description,
+            canonical_url, noindex
+        )
+        VALUES (
+            ?1, ?2, ?3, ?4, ?5, ?6, COALESCE(?7, 0), ?8, ?9, ?9, ?10, ?11, ?12, ?13,
+            COALESCE(?14, 0)
+        )
+        RETURNING *;
+        "#,
+    )
+    .bind(&post.title)
+    .bind(&post.content)
+    .bind(&post.category)
+    .bind(Json(&post.tags))
+    .bind(post.slug())
+    .bind(post.author())
+    .bind(post.draft)
+    .bind(post.published_at())
+    .bind(Utc::now())
+    .bind(post.featured_image_id())
+    .bind(post.meta_title())
+    .bind(post.meta_description())
+    .bind(post.canonical_url())
+    .bind(post.noindex)
+    .fetch_one(conn)
+    .instrument(span::db_query(SYSTEM, "INSERT"))
+    .await
+    .map_err(write_error)?;
+    Ok(row.into())
+}
+
+/// Writes the fields of `post` to its row on `conn` and returns the stored post.
+///
+/// The update time is set to the current time if anything changed.
+async fn update_row(conn: &mut SqliteConnection, post: &BlogPost) -> Result<BlogPost, AppError> {
+    let row: PostRow = sqlx::query_as(
+        r#"
+        UPDATE blog_posts
+        SET title = ?2,
+            content = ?3,
+            category = ?4,
+            tags = ?5,
+            slug = ?6,
+            author = ?7,
+            draft = ?8,
+            published_at = ?9,
+            featured_image_id = ?10,
+            meta_title = ?11,
+            meta_description = ?12,
+            canonical_url = ?13,
+            noindex = ?14,
+            updated_at = CASE
+                WHEN title IS NOT ?2
+                    OR content IS NOT ?3
+                    OR category IS NOT ?4
+                    OR tags IS NOT ?5
+                    OR slug IS NOT ?6
+                    OR author IS NOT ?7
+                    OR draft IS NOT ?8
+                    OR published_at IS NOT ?9
+                    OR featured_image_id IS NOT ?10
+                    OR meta_title IS NOT ?11
+                    OR meta_description IS NOT ?12
+                    OR canonical_url IS NOT ?13
+                    OR noindex IS NOT ?14
+                THEN ?15
+                ELSE updated_at
+            END
+        WHERE id = ?1
+        RETURNING *;
+        "#,
+    )
+    .bind(post.id)
+    .bind(&post.title)
+    .bind(&post.content)
+    .bind(&post.category)
+    .bind(Json(&post.tags))
+    .bind(&post.slug)
+    .bind(&post.author)
+    .bind(post.draft)
+    .bind(post.published_at)
+    .bind(post.featured_image_id)
+    .bind(&post.meta_title)
+    .bind(&post.meta_description)
+    .bind(&post.canonical_url)
+    .bind(post.noindex)
+    .bind(Utc::now())
+    .fetch_one(conn)
+    .instrument(span::db_query(SYSTEM, "UPDATE"))
+    .await
+    .map_err(write_error)?;
+    Ok(row.into())
+}
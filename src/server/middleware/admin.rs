@@ -0,0 +1,99 @@
+use crate::{config, error::AppError};
+use anyhow::{Result, bail};
+use axum::{
+    extract::{Request, State},
+    http::header,
+    middleware::Next,
+    response::Response,
+};
+use std::sync::Arc;
+
+/// Shortest token accepted in `ADMIN_TOKEN`.
+const MIN_TOKEN_LEN: usize = 16;
+
+/// Authentication of the administration endpoints, read from the environment.
+///
+/// | Variable      | Default | Description                                                        |
+/// |---------------|---------|--------------------------------------------------------------------|
+/// | `ADMIN_TOKEN` | unset   | Bearer token of the administration endpoints, disabled while unset |
+///
+/// The administration endpoints manage webhooks and background jobs; see
+/// [`require_token`].
+#[derive(Debug, Clone, Default)]
+pub struct AdminConfig {
+    /// Token clients send as `Authorization: Bearer <token>`, or `None` to refuse every
+    /// request.
+    pub token: Option<String>,
+}
+
+impl AdminConfig {
+    /// Builds the configuration from environment variables.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `ADMIN_TOKEN` is shorter than 16 characters.
+    pub fn from_env() -> Result<Self> {
+        let token = config::var_opt("ADMIN_TOKEN");
+        if token
+            .as_ref()
+            .is_some_and(|token| token.chars().count() < MIN_TOKEN_LEN)
+        {
+            bail!("Invalid value for ADMIN_TOKEN: must be at least {MIN_TOKEN_LEN} characters");
+        }
+        Ok(Self { token })
+    }
+
+    /// Returns whether the value of an `Authorization` header carries the token.
+    ///
+    /// Tokens are compared in constant time, so their content cannot be guessed from how
+    /// long a refusal takes.
+    fn accepts(&self, authorization: &str) -> bool {
+        let Some(token) = &self.token else {
+            return false;
+        };
+        let Some(sent) = authorization
+            .split_once(' ')
+            .filter(|(scheme, _)| scheme.eq_ignore_ascii_case("bearer"))
+            .map(|(_, sent)| sent.trim())
+        else {
+            return false;
+        };
+        sent.len() == token.len()
+            && sent
+                .bytes()
+                .zip(token.bytes())
+                .fold(0, |diff, (a, b)| diff | (a ^ b))
+                == 0
+    }
+}
+
+/// Middleware admitting only requests carrying the `ADMIN_TOKEN` as a bearer token.
+///
+/// Every request is refused while no token is configured.
+///
+/// # Errors
+///
+/// Returns an `AppError::Unauthorized` if the request has no `Authorization` header, or
+/// one that does not carry the token.
+pub async fn require_token(
+    State(config): State<Arc<AdminConfig>>,
+    request: Request,
+    next: Next,
+) -> Result<Response, AppError> {
+    if config.token.is_none() {
+        return Err(AppError::Unauthorized(
+            "The admin API is disabled; set ADMIN_TOKEN to enable it".to_string(),
+        ));
+    }
+    let authorized = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| config.accepts(value));
+    if !authorized {
+        return Err(AppError::Unauthorized(
+            "A valid admin token is required".to_string(),
+        ));
+    }
+    Ok(next.run(request).await)
+}
@@ -0,0 +1,254 @@
+use crate::{config, server::middleware::rate_limit::RouteClass};
+use anyhow::{Context, Result};
+use axum::{
+    extract::State,
+    http::{HeaderName, HeaderValue, Method, header},
+    response::Response,
+};
+use std::{str::FromStr, sync::Arc, time::Duration};
+use tower_http::{
+    compression::CompressionLayer,
+    cors::{AllowOrigin, Any, CorsLayer},
+};
+
+/// Request headers browsers may send on cross-origin requests.
+const ALLOWED_HEADERS: [HeaderName; 6] = [
+    header::CONTENT_TYPE,
+    header::AUTHORIZATION,
+    header::ACCEPT,
+    HeaderName::from_static("x-request-id"),
+    HeaderName::from_static("idempotency-key"),
+    HeaderName::from_static("edit-lock"),
+];
+
+/// Response headers exposed to cross-origin scripts.
+const EXPOSED_HEADERS: [HeaderName; 11] = [
+    HeaderName::from_static("x-request-id"),
+    HeaderName::from_static("ratelimit-limit"),
+    HeaderName::from_static("ratelimit-remaining"),
+    HeaderName::from_static("ratelimit-reset"),
+    HeaderName::from_static("ratelimit-policy"),
+    header::RETRY_AFTER,
+    HeaderName::from_static("api-version"),
+    HeaderName::from_static("deprecation"),
+    HeaderName::from_static("sunset"),
+    header::LINK,
+    HeaderName::from_static("idempotent-replayed"),
+];
+
+/// Security headers added to every response that does not already set them.
+pub const SECURITY_HEADERS: [(HeaderName, &str); 5] = [
+    (header::X_CONTENT_TYPE_OPTIONS, "nosniff"),
+    (header::X_FRAME_OPTIONS, "DENY"),
+    (header::REFERRER_POLICY, "no-referrer"),
+    (
+        header::CONTENT_SECURITY_POLICY,
+        "default-src 'none'; frame-ancestors 'none'",
+    ),
+    (
+        HeaderName::from_static("cross-origin-resource-policy"),
+        "same-origin",
+    ),
+];
+
+/// Origins allowed to make cross-origin requests.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CorsOrigins {
+    /// Any origin (`*`).
+    Any,
+    /// Only the listed origins.
+    List(Vec<HeaderValue>),
+}
+
+impl FromStr for CorsOrigins {
+    type Err = String;
+
+    /// Parses `*` or a comma-separated list of origins.
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        if value.trim() == "*" {
+            return Ok(Self::Any);
+        }
+        value
+            .split(',')
+            .map(str::trim)
+            .filter(|origin| !origin.is_empty())
+            .map(|origin| {
+                HeaderValue::from_str(origin)
+                    .map_err(|err| format!("invalid origin `{origin}`: {err}"))
+            })
+            .collect::<Result<_, _>>()
+            .map(Self::List)
+    }
+}
+
+/// Timeout applied to handlers of each class of route.
+#[derive(Debug, Clone, Copy)]
+pub struct RouteTimeouts {
+    /// Timeout for read routes.
+    pub read: Duration,
+    /// Timeout for write routes.
+    pub write: Duration,
+    /// Timeout for search routes.
+    pub search: Duration,
+}
+
+impl RouteTimeouts {
+    /// Returns the timeout for a class of routes.
+    pub fn get(&self, class: RouteClass) -> Duration {
+        match class {
+            RouteClass::Read => self.read,
+            RouteClass::Write => self.write,
+            RouteClass::Search => self.search,
+        }
+    }
+}
+
+/// HTTP middleware configuration read from the environment.
+///
+/// | Variable                 | Default                     | Description                                         |
+/// |--------------------------|-----------------------------|-----------------------------------------------------|
+/// | `CORS_ALLOWED_ORIGINS`   | unset (no CORS)             | `*` or a comma-separated list of origins.           |
+/// | `CORS_ALLOWED_METHODS`   | `GET,POST,PUT,PATCH,DELETE` | Comma-separated list of methods.                    |
+/// | `HTTP_COMPRESSION`       | `true`                      | Compress responses with zstd, brotli or gzip.       |
+/// | `HTTP_MAX_BODY_BYTES`    | `1048576`                   | Maximum size of a (decompressed) request body.      |
+/// | `HTTP_MAX_UPLOAD_BYTES`  | `10485760`                  | Maximum size of a media upload request body.        |
+/// | `HTTP_BODY_TIMEOUT_SECS` | `10`                        | Time allowed to receive a request body (`408`).     |
+/// | `HTTP_READ_TIMEOUT_SECS` | `10`                        | Handler timeout for read routes (`503`).            |
+/// | `HTTP_WRITE_TIMEOUT_SECS`| `15`                        | Handler timeout for write routes (`503`).           |
+/// | `HTTP_SEARCH_TIMEOUT_SECS`| `5`                        | Handler timeout for search routes (`503`).          |
+/// | `HTTP_SECURITY_HEADERS`  | `true`                      | Add `nosniff`, frame, referrer and CSP headers.     |
+/// | `HTTP_HSTS_MAX_AGE_SECS` | unset                       | Send `Strict-Transport-Security` with this max age. |
+#[derive(Debug, Clone)]
+pub struct HttpConfig {
+    /// Allowed CORS origins, or `None` to disable CORS.
+    pub cors_origins: Option<CorsOrigins>,
+    /// Methods allowed on cross-origin requests.
+    pub cors_methods: Vec<Method>,
+    /// Whether responses are compressed.
+    pub compression: bool,
+    /// Maximum size of a request body after decompression.
+    pub max_body_bytes: usize,
+    /// Maximum size of a media upload request body, which replaces `max_body_bytes`
+    /// on upload routes.
+    pub max_upload_bytes: usize,
+    /// Time allowed for a client to send its request body.
+    pub body_timeout: Duration,
+    /// Handler timeouts per class of route.
+    pub timeouts: RouteTimeouts,
+    /// Whether security headers are added to responses.
+    pub security_headers: bool,
+    /// `max-age` of the `Strict-Transport-Security` header, if enabled.
+    pub hsts_max_age: Option<u64>,
+}
+
+impl HttpConfig {
+    /// Builds the configuration from environment variables.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if any of the variables holds an invalid value.
+    pub fn from_env() -> Result<Self> {
+        let cors_origins = config::var_opt("CORS_ALLOWED_ORIGINS")
+            .map(|origins| origins.parse::<CorsOrigins>())
+            .transpose()
+            .map_err(|err| anyhow::anyhow!(err))
+            .context("Invalid value for CORS_ALLOWED_ORIGINS")?;
+        let cors_methods = config::var_or("CORS_ALLOWED_METHODS", "GET,POST,PUT,PATCH,DELETE")
+            .split(',')
+            .map(str::trim)
+            .filter(|method| !method.is_empty())
+            .map(|method| Method::from_str(&method.to_ascii_uppercase()))
+            .collect::<Result<_, _>>()
+            .context("Invalid value for CORS_ALLOWED_METHODS")?;
+        let secs = |key, default| config::parse_or(key, default).map(Duration::from_secs);
+
+        Ok(Self {
+            cors_origins,
+            cors_methods,
+            compression: config::parse_or("HTTP_COMPRESSION", true)?,
+            max_body_bytes: config::parse_or("HTTP_MAX_BODY_BYTES", 1024 * 1024)?,
+            max_upload_bytes: config::parse_or("HTTP_MAX_UPLOAD_BYTES", 10 * 1024 * 1024)?,
+            body_timeout: secs("HTTP_BODY_TIMEOUT_SECS", 10)?,
+            timeouts: RouteTimeouts {
+                read: secs("HTTP_READ_TIMEOUT_SECS", 10)?,
+                write: secs("HTTP_WRITE_TIMEOUT_SECS", 15)?,
+                search: secs("HTTP_SEARCH_TIMEOUT_SECS", 5)?,
+            },
+            security_headers: config::parse_or("HTTP_SECURITY_HEADERS", true)?,
+            hsts_max_age: config::var_opt("HTTP_HSTS_MAX_AGE_SECS")
+                .map(|_| config::parse_or("HTTP_HSTS_MAX_AGE_SECS", 0))
+                .transpose()?,
+        })
+    }
+
+    /// Returns the maximum size of the body of a request: `max_upload_bytes` for media
+    /// uploads (`POST …/media`) and `max_body_bytes` for everything else.
+    pub fn body_limit(&self, method: &Method, path: &str) -> usize {
+        if method == Method::POST && path.trim_end_matches('/').ends_with("/media") {
+            self.max_upload_bytes
+        } else {
+            self.max_body_bytes
+        }
+    }
+
+    /// Builds the CORS layer.
+    ///
+    /// When no origins are configured the layer allows none, so browsers keep
+    /// blocking cross-origin requests.
+    pub fn cors(&self) -> CorsLayer {
+        let layer = CorsLayer::new()
+            .allow_methods(self.cors_methods.clone())
+            .allow_headers(ALLOWED_HEADERS)
+            .expose_headers(EXPOSED_HEADERS)
+            .max_age(Duration::from_secs(3600));
+
+        match &self.cors_origins {
+            Some(CorsOrigins::Any) => layer.allow_origin(Any),
+            Some(CorsOrigins::List(list)) => layer.allow_origin(AllowOrigin::list(list.clone())),
+            None => layer,
+        }
+    }
+
+    /// Builds the response compression layer, negotiating zstd, brotli or gzip
+    /// from the `Accept-Encoding` header.
+    pub fn compression(&self) -> CompressionLayer {
+        CompressionLayer::new()
+            .zstd(self.compression)
+            .br(self.compression)
+            .gzip(self.compression)
+    }
+
+    /// Returns the security headers to add to responses.
+    pub fn security_headers(&self) -> Vec<(HeaderName, HeaderValue)> {
+        if !self.security_headers {
+            return Vec::new();
+        }
+        let mut headers: Vec<_> = SECURITY_HEADERS
+            .iter()
+            .map(|(name, value)| (name.clone(), HeaderValue::from_static(value)))
+            .collect();
+        let hsts = self
+            .hsts_max_age
+            .map(|max_age| format!("max-age={max_age}; includeSubDomains"))
+            .and_then(|value| HeaderValue::from_str(&value).ok());
+        if let Some(value) = hsts {
+            headers.push((header::STRICT_TRANSPORT_SECURITY, value));
+        }
+        headers
+    }
+}
+
+/// Middleware adding the configured security headers to a response.
+///
+/// Headers already set by a handler are left untouched, so individual routes can
+/// relax the defaults (for example a looser `Content-Security-Policy`).
+pub async fn set_security_headers(
+    State(config): State<Arc<HttpConfig>>,
+    mut response: Response,
+) -> Response {
+    let headers = response.headers_mut();
+    for (name, value) in config.security_headers() {
+        headers.entry(name).or_insert(value);
+    }
+    response
+}
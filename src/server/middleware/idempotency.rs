@@ -0,0 +1,209 @@
+use crate::{
+    error::AppError,
+    idempotency::{Claim, DynIdempotencyStore, Idempotency, StoredResponse},
+    server::middleware::rate_limit::{self, RateLimiter},
+};
+use axum::{
+    body::{self, Body},
+    extract::{Request, State},
+    http::{HeaderName, HeaderValue, Method, StatusCode, header, request::Parts},
+    middleware::Next,
+    response::Response,
+};
+use chrono::Utc;
+use sha2::{Digest, Sha256};
+use std::mem;
+
+/// Request header carrying the client's idempotency key.
+pub const IDEMPOTENCY_KEY: HeaderName = HeaderName::from_static("idempotency-key");
+
+/// Response header marking a replayed response.
+pub const IDEMPOTENT_REPLAYED: HeaderName = HeaderName::from_static("idempotent-replayed");
+
+/// Longest accepted idempotency key.
+const MAX_KEY_LENGTH: usize = 255;
+
+/// A claimed key, released when dropped unless its response was stored.
+///
+/// Dropping covers handlers that time out or panic: the key is released in the
+/// background, so the client can retry instead of waiting for the lock to expire.
+struct Lease {
+    /// Where the key is stored.
+    store: DynIdempotencyStore,
+    /// Scope of the key.
+    scope: String,
+    /// The key itself.
+    key: String,
+    /// Whether the key was completed or released already.
+    settled: bool,
+}
+
+impl Lease {
+    /// Stores the response of the request holding the key.
+    async fn complete(mut self, response: &StoredResponse, idempotency: &Idempotency) {
+        self.settled = true;
+        let expires_at = Utc::now() + idempotency.config.ttl;
+        if let Err(err) = self
+            .store
+            .complete(&self.scope, &self.key, response, expires_at)
+            .await
+        {
+            tracing::warn!("failed to store idempotent response: {err}");
+        }
+    }
+}
+
+impl Drop for Lease {
+    fn drop(&mut self) {
+        if self.settled {
+            return;
+        }
+        let store = self.store.clone();
+        let (scope, key) = (mem::take(&mut self.scope), mem::take(&mut self.key));
+        tokio::spawn(async move {
+            if let Err(err) = store.release(&scope, &key).await {
+                tracing::warn!("failed to release idempotency key: {err}");
+            }
+        });
+    }
+}
+
+/// Fingerprints a request by its method, path, query and body.
+fn fingerprint(parts: &Parts, body: &[u8]) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(parts.method.as_str());
+    hasher.update(b" ");
+    hasher.update(parts.uri.to_string());
+    hasher.update(b"\n");
+    hasher.update(body);
+    hex::encode(hasher.finalize())
+}
+
+/// Rebuilds a stored response, marked as replayed.
+fn replay(stored: StoredResponse) -> Response {
+    let mut response = Response::new(Body::from(stored.body));
+    *response.status_mut() = StatusCode::from_u16(stored.status).unwrap_or(StatusCode::OK);
+    let headers = response.headers_mut();
+    for (name, value) in stored.headers {
+        if let (Ok(name), Ok(value)) = (HeaderName::try_from(name), HeaderValue::try_from(value)) {
+            headers.append(name, value);
+        }
+    }
+    headers.insert(IDEMPOTENT_REPLAYED, HeaderValue::from_static("true"));
+    response
+}
+
+/// Middleware honoring the `Idempotency-Key` header on `POST`, `PATCH` and `DELETE`
+/// requests.
+///
+/// The first request with a key runs normally and its response is stored, unless it
+/// is a server error, in which case the key is released so the request can be retried.
+/// Repeats of the request get the stored response, marked with
+/// `Idempotent-Replayed: true`, until the key expires. Of a response larger than
+/// `IDEMPOTENCY_MAX_RESPONSE_BYTES`, only the status and headers are replayed. Reusing a key for a different
+/// request (method, path, query or body) is rejected with `422 Unprocessable Entity`,
+/// and repeating a request that is still in progress with `409 Conflict`.
+///
+/// Keys are scoped to the client, identified like the rate limiter identifies clients.
+/// Requests without the header are not affected.
+///
+/// # Errors
+///
+/// Returns an `AppError` if the key is invalid, conflicts with another request, or
+/// cannot be claimed because the storage fails.
+pub async fn enforce(
+    State(idempotency): State<Idempotency>,
+    State(limiter): State<RateLimiter>,
+    request: Request,
+    next: Next,
+) -> Result<Response, AppError> {
+    if !matches!(
+        *request.method(),
+        Method::POST | Method::PATCH | Method::DELETE
+    ) {
+        return Ok(next.run(request).await);
+    }
+    let Some(key) = request.headers().get(IDEMPOTENCY_KEY) else {
+        return Ok(next.run(request).await);
+    };
+    let key = key
+        .to_str()
+        .ok()
+        .filter(|key| !key.is_empty() && key.len() <= MAX_KEY_LENGTH)
+        .ok_or_else(|| {
+            AppError::BadRequest(format!(
+                "Idempotency-Key must be 1 to {MAX_KEY_LENGTH} visible ASCII characters"
+            ))
+        })?
+        .to_string();
+    let scope = rate_limit::client_key(&request, limiter.config());
+
+    let (parts, body) = request.into_parts();
+    let bytes = body::to_bytes(body, usize::MAX)
+        .await
+        .map_err(|_| AppError::BadRequest("Failed to read the request body".to_string()))?;
+    let fingerprint = fingerprint(&parts, &bytes);
+
+    let locked_until = Utc::now() + idempotency.config.lock_timeout;
+    match idempotency
+        .store
+        .claim(&scope, &key, &fingerprint, locked_until)
+        .await?
+    {
+        Claim::Acquired => {}
+        Claim::InProgress { fingerprint: held }
+        | Claim::Completed {
+            fingerprint: held, ..
+        } if held != fingerprint => {
+            return Err(AppError::UnprocessableEntity(
+                "Idempotency-Key was already used for a different request".to_string(),
+            ));
+        }
+        Claim::InProgress { .. } => {
+            return Err(AppError::Conflict(
+                "A request with this Idempotency-Key is still in progress".to_string(),
+            ));
+        }
+        Claim::Completed { response, .. } => return Ok(replay(response)),
+    }
+    let lease = Lease {
+        store: idempotency.store.clone(),
+        scope,
+        key,
+        settled: false,
+    };
+
+    let response = next
+        .run(Request::from_parts(parts, Body::from(bytes)))
+        .await;
+    if response.status().is_server_error() {
+        return Ok(response);
+    }
+
+    let (mut parts, body) = response.into_parts();
+    let bytes = body::to_bytes(body, usize::MAX)
+        .await
+        .map_err(|_| AppError::InternalServerError)?;
+    parts.headers.remove(header::CONTENT_LENGTH);
+    // A response too large to store is replayed without its body, rather than releasing
+    // the key and letting a retry run the request again.
+    let fits = bytes.len() <= idempotency.config.max_response_bytes;
+    if !fits {
+        tracing::warn!(
+            bytes = bytes.len(),
+            "response too large to store for idempotent replay, storing its status only"
+        );
+    }
+    let stored = StoredResponse {
+        status: parts.status.as_u16(),
+        headers: parts
+            .headers
+            .iter()
+            .filter(|(name, _)| fits || *name != header::CONTENT_TYPE)
+            .filter_map(|(name, value)| Some((name.to_string(), value.to_str().ok()?.to_string())))
+            .collect(),
+        body: if fits { bytes.to_vec() } else { Vec::new() },
+    };
+    lease.complete(&stored, &idempotency).await;
+    Ok(Response::from_parts(parts, Body::from(bytes)))
+}
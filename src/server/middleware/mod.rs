@@ -0,0 +1,21 @@
+/// Bearer token authentication of the administration endpoints.
+pub mod admin;
+
+/// HTTP middleware configuration: CORS, compression, body limits, timeouts and
+/// security headers.
+pub mod http;
+
+/// `Idempotency-Key` handling for write requests.
+pub mod idempotency;
+
+/// Per-client token-bucket rate limiting.
+///
+/// This module defines the rate limiter stored in the application state and the
+/// middleware that enforces separate budgets for read, write and search routes.
+pub mod rate_limit;
+
+/// Body size limits and per-route timeouts.
+pub mod timeout;
+
+/// API version negotiation and deprecation headers.
+pub mod versioning;
@@ -0,0 +1,535 @@
+use crate::{config, error::AppError};
+use anyhow::{Context, Result};
+use axum::{
+    extract::{ConnectInfo, Request, State},
+    http::{HeaderMap, HeaderName, HeaderValue, Method},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use std::{
+    collections::HashMap,
+    net::SocketAddr,
+    str::FromStr,
+    sync::{Arc, Mutex, PoisonError},
+    time::{Duration, Instant},
+};
+
+/// Tokens are tracked in thousandths so refills stay exact without floating point.
+const SCALE: u128 = 1000;
+
+/// Number of checks between sweeps of idle buckets.
+const PRUNE_EVERY: u64 = 1024;
+
+/// Header advertising the request budget of the current window.
+const RATE_LIMIT_LIMIT: HeaderName = HeaderName::from_static("ratelimit-limit");
+/// Header advertising how many requests are left in the current window.
+const RATE_LIMIT_REMAINING: HeaderName = HeaderName::from_static("ratelimit-remaining");
+/// Header advertising the seconds until the budget is fully restored.
+const RATE_LIMIT_RESET: HeaderName = HeaderName::from_static("ratelimit-reset");
+/// Header describing the quota policy, e.g. `30;w=60`.
+const RATE_LIMIT_POLICY: HeaderName = HeaderName::from_static("ratelimit-policy");
+
+/// A request budget: `capacity` requests per `window`, refilled continuously.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Budget {
+    /// Maximum burst size and number of requests allowed per window.
+    pub capacity: u32,
+    /// Time it takes for an empty bucket to refill completely.
+    pub window: Duration,
+}
+
+impl Budget {
+    /// Creates a budget of `capacity` requests per `window`.
+    pub const fn new(capacity: u32, window: Duration) -> Self {
+        Self { capacity, window }
+    }
+
+    /// The bucket size in thousandths of a token.
+    fn full(&self) -> u128 {
+        u128::from(self.capacity) * SCALE
+    }
+
+    /// The thousandths of a token earned over `elapsed`.
+    fn refill(&self, elapsed: Duration) -> u128 {
+        elapsed.as_nanos() * self.full() / self.window.as_nanos().max(1)
+    }
+
+    /// The time needed to earn `milli` thousandths of a token.
+    fn time_for(&self, milli: u128) -> Duration {
+        let nanos = milli * self.window.as_nanos() / self.full().max(1);
+        Duration::from_nanos(u64::try_from(nanos).unwrap_or(u64::MAX))
+    }
+}
+
+impl FromStr for Budget {
+    type Err = String;
+
+    /// Parses a budget written as `<requests>/<seconds>`, e.g. `30/60`.
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let (capacity, seconds) = value
+            .split_once('/')
+            .ok_or_else(|| format!("expected `<requests>/<seconds>`, got `{value}`"))?;
+        let capacity = capacity
+            .trim()
+            .parse()
+            .map_err(|err| format!("invalid request count: {err}"))?;
+        let seconds: u64 = seconds
+            .trim()
+            .parse()
+            .map_err(|err| format!("invalid window: {err}"))?;
+        if seconds == 0 {
+            return Err("window must be at least one second".to_string());
+        }
+        Ok(Self::new(capacity, Duration::from_secs(seconds)))
+    }
+}
+
+/// The class of route a request belongs to, each with its own budget.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RouteClass {
+    /// Safe requests (`GET`, `HEAD`, `OPTIONS`) outside of search.
+    Read,
+    /// Requests that modify data.
+    Write,
+    /// Full-text search requests, which are the most expensive reads.
+    Search,
+}
+
+impl RouteClass {
+    /// Classifies a request by its method and path.
+    pub fn of(method: &Method, path: &str) -> Self {
+        if path.trim_end_matches('/').ends_with("/search") {
+            Self::Search
+        } else if method.is_safe() {
+            Self::Read
+        } else {
+            Self::Write
+        }
+    }
+}
+
+/// Rate limiting configuration read from the environment.
+///
+/// | Variable                      | Default  | Description                                          |
+/// |-------------------------------|----------|------------------------------------------------------|
+/// | `RATE_LIMIT_ENABLED`          | `true`   | Set to `false` to disable rate limiting.             |
+/// | `RATE_LIMIT_READ`             | `300/60` | Budget for read routes, as `<requests>/<seconds>`.   |
+/// | `RATE_LIMIT_WRITE`            | `30/60`  | Budget for write routes.                             |
+/// | `RATE_LIMIT_SEARCH`           | `60/60`  | Budget for search routes.                            |
+/// | `RATE_LIMIT_TRUSTED_HEADER`   | unset    | Proxy header holding the client IP, e.g. `x-forwarded-for`. |
+#[derive(Debug, Clone)]
+pub struct RateLimitConfig {
+    /// Whether requests are limited at all.
+    pub enabled: bool,
+    /// Budget applied to read routes.
+    pub read: Budget,
+    /// Budget applied to write routes.
+    pub write: Budget,
+    /// Budget applied to search routes.
+    pub search: Budget,
+    /// Header set by a trusted reverse proxy that carries the client IP.
+    ///
+    /// Only configure this when the API is reachable exclusively through that
+    /// proxy, since clients can otherwise forge the header.
+    pub trusted_header: Option<HeaderName>,
+}
+
+impl RateLimitConfig {
+    /// Builds the configuration from environment variables.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if any of the variables holds an invalid value.
+    pub fn from_env() -> Result<Self> {
+        let window = Duration::from_secs(60);
+        let trusted_header = config::var_opt("RATE_LIMIT_TRUSTED_HEADER")
+            .map(|name| HeaderName::from_str(name.trim()))
+            .transpose()
+            .context("Invalid value for RATE_LIMIT_TRUSTED_HEADER")?;
+
+        Ok(Self {
+            enabled: config::parse_or("RATE_LIMIT_ENABLED", true)?,
+            read: config::parse_or("RATE_LIMIT_READ", Budget::new(300, window))?,
+            write: config::parse_or("RATE_LIMIT_WRITE", Budget::new(30, window))?,
+            search: config::parse_or("RATE_LIMIT_SEARCH", Budget::new(60, window))?,
+            trusted_header,
+        })
+    }
+
+    /// Returns the budget for a class of routes.
+    pub fn budget(&self, class: RouteClass) -> Budget {
+        match class {
+            RouteClass::Read => self.read,
+            RouteClass::Write => self.write,
+            RouteClass::Search => self.search,
+        }
+    }
+}
+
+/// Identity of an authenticated caller.
+///
+/// Authentication middleware can insert this into the request extensions so that
+/// the caller is rate limited by identity rather than by IP address.
+#[derive(Debug, Clone)]
+pub struct ClientIdentity(pub String);
+
+/// A token bucket for one client and route class.
+#[derive(Debug)]
+struct Bucket {
+    /// Available thousandths of a token.
+    milli: u128,
+    /// When `milli` was last brought up to date.
+    updated: Instant,
+}
+
+/// The outcome of checking a request against its budget.
+#[derive(Debug, Clone, Copy)]
+pub struct Decision {
+    /// Whether the request may proceed.
+    pub allowed: bool,
+    /// The budget the request was checked against.
+    pub budget: Budget,
+    /// Whole requests left in the bucket after this one.
+    pub remaining: u32,
+    /// Time until the bucket is full again.
+    pub reset: Duration,
+    /// Time until the next request would be allowed.
+    pub retry_after: Duration,
+}
+
+/// Mutable limiter state shared between requests.
+#[derive(Debug, Default)]
+struct Buckets {
+    /// Buckets keyed by route class and client key.
+    entries: HashMap<(RouteClass, String), Bucket>,
+    /// Checks performed since the last sweep.
+    checks: u64,
+}
+
+/// A per-client token-bucket rate limiter.
+///
+/// Cloning is cheap; all clones share the same buckets.
+#[derive(Debug, Clone)]
+pub struct RateLimiter {
+    /// Budgets and client identification settings.
+    config: Arc<RateLimitConfig>,
+    /// Token buckets for every client seen recently.
+    buckets: Arc<Mutex<Buckets>>,
+}
+
+impl RateLimiter {
+    /// Creates a limiter with no recorded clients.
+    pub fn new(config: RateLimitConfig) -> Self {
+        Self {
+            config: Arc::new(config),
+            buckets: Arc::default(),
+        }
+    }
+
+    /// Returns the limiter's configuration.
+    pub fn config(&self) -> &RateLimitConfig {
+        &self.config
+    }
+
+    /// Takes one token from the client's bucket for the given route class.
+    pub fn check(&self, class: RouteClass, client: &str, now: Instant) -> Decision {
+        let budget = self.config.budget(class);
+        let mut buckets = self.buckets.lock().unwrap_or_else(PoisonError::into_inner);
+
+        buckets.checks += 1;
+        if buckets.checks.is_multiple_of(PRUNE_EVERY) {
+            buckets.entries.retain(|(class, _), bucket| {
+                now - bucket.updated < self.config.budget(*class).window
+            });
+        }
+
+        let bucket = buckets
+            .entries
+            .entry((class, client.to_string()))
+            .or_insert_with(|| Bucket {
+                milli: budget.full(),
+                updated: now,
+            });
+        let elapsed = now.saturating_duration_since(bucket.updated);
+        bucket.milli = (bucket.milli + budget.refill(elapsed)).min(budget.full());
+        bucket.updated = now;
+
+        let allowed = bucket.milli >= SCALE;
+        if allowed {
+            bucket.milli -= SCALE;
+        }
+
+        Decision {
+            allowed,
+            budget,
+            remaining: u32::try_from(bucket.milli / SCALE).unwrap_or(u32::MAX),
+            reset: budget.time_for(budget.full() - bucket.milli),
+            retry_after: budget.time_for(SCALE.saturating_sub(bucket.milli)),
+        }
+    }
+}
+
+/// Rounds a duration up to whole seconds.
+fn ceil_secs(duration: Duration) -> u64 {
+    duration.as_secs() + u64::from(duration.subsec_nanos() > 0)
+}
+
+/// Determines the key a request is rate limited under.
+///
+/// Authenticated callers are keyed by [`ClientIdentity`]. Everyone else is keyed
+/// by IP address, taken from the trusted proxy header when configured (the last
+/// address in the list, which is the one the proxy itself observed) or from the
+/// socket peer address otherwise.
+pub(crate) fn client_key(request: &Request, config: &RateLimitConfig) -> String {
+    if let Some(ClientIdentity(identity)) = request.extensions().get::<ClientIdentity>() {
+        return format!("user:{identity}");
+    }
+
+    let forwarded = config
+        .trusted_header
+        .as_ref()
+        .and_then(|header| request.headers().get(header))
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.rsplit(',').map(str::trim).find(|ip| !ip.is_empty()));
+    if let Some(ip) = forwarded {
+        return format!("ip:{ip}");
+    }
+
+    request
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map_or_else(
+            || "ip:unknown".to_string(),
+            |ConnectInfo(addr)| format!("ip:{}", addr.ip()),
+        )
+}
+
+/// Adds `RateLimit-*` headers describing a decision to a response.
+fn set_headers(headers: &mut HeaderMap, decision: &Decision) {
+    let budget = decision.budget;
+    let values = [
+        (RATE_LIMIT_LIMIT, budget.capacity.to_string()),
+        (RATE_LIMIT_REMAINING, decision.remaining.to_string()),
+        (RATE_LIMIT_RESET, ceil_secs(decision.reset).to_string()),
+        (
+            RATE_LIMIT_POLICY,
+            format!("{};w={}", budget.capacity, budget.window.as_secs()),
+        ),
+    ];
+    for (name, value) in values {
+        if let Ok(value) = HeaderValue::from_str(&value) {
+            headers.insert(name, value);
+        }
+    }
+}
+
+/// Middleware enforcing the per-client budgets.
+///
+/// Requests over budget are rejected with `AppError::TooManyRequests` (`429`),
+/// which carries a `Retry-After` header. Every response, rejected or not, is
+/// annotated with `RateLimit-Limit`, `RateLimit-Remaining`, `RateLimit-Reset`
+/// and `RateLimit-Policy` headers.
+pub async fn limit(State(limiter): State<RateLimiter>, request: Request, next: Next) -> Response {
+    if !limiter.config().enabled {
+        return next.run(request).await;
+    }
+
+    let class = RouteClass::of(request.method(), request.uri().path());
+    let client = client_key(&request, limiter.config());
+    let decision = limiter.check(class, &client, Instant::now());
+
+    let mut response = if decision.allowed {
+        next.run(request).await
+    } else {
+        tracing::warn!(?class, client, "rate limit exceeded");
+        AppError::TooManyRequests {
+            retry_after: ceil_secs(decision.retry_after),
+        }
+        .into_response()
+    };
+    set_headers(response.headers_mut(), &decision);
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{
+        Router,
+        body::Body,
+        http::{StatusCode, header},
+        middleware::from_fn_with_state,
+        routing::post,
+    };
+    use std::net::{Ipv4Addr, SocketAddr};
+    use tower::ServiceExt;
+
+    /// Returns a configuration allowing 2 writes, 4 reads and 1 search every 10 seconds,
+    /// with clients identified by `trusted_header` if given.
+    fn config(trusted_header: Option<&'static str>) -> RateLimitConfig {
+        let window = Duration::from_secs(10);
+        RateLimitConfig {
+            enabled: true,
+            read: Budget::new(4, window),
+            write: Budget::new(2, window),
+            search: Budget::new(1, window),
+            trusted_header: trusted_header.map(HeaderName::from_static),
+        }
+    }
+
+    /// Builds a request from the peer `10.0.0.1`, with the given headers.
+    fn request(method: Method, uri: &str, headers: &[(&str, &str)]) -> Result<Request> {
+        let mut request = Request::builder().method(method).uri(uri);
+        for (name, value) in headers {
+            request = request.header(*name, *value);
+        }
+        Ok(request
+            .extension(ConnectInfo(SocketAddr::from((
+                Ipv4Addr::new(10, 0, 0, 1),
+                4000,
+            ))))
+            .body(Body::empty())?)
+    }
+
+    #[test]
+    fn budgets_are_parsed_as_requests_per_seconds() {
+        assert_eq!(
+            "30/60".parse(),
+            Ok(Budget::new(30, Duration::from_secs(60)))
+        );
+        assert_eq!(
+            " 5 / 1 ".parse(),
+            Ok(Budget::new(5, Duration::from_secs(1)))
+        );
+        for invalid in ["30", "x/60", "30/x", "30/0", "-1/60"] {
+            assert!(invalid.parse::<Budget>().is_err(), "{invalid}");
+        }
+    }
+
+    #[test]
+    fn routes_are_classified_by_method_and_path() {
+        assert_eq!(
+            RouteClass::of(&Method::GET, "/api/v1/posts"),
+            RouteClass::Read
+        );
+        assert_eq!(
+            RouteClass::of(&Method::HEAD, "/api/v1/posts/1"),
+            RouteClass::Read
+        );
+        assert_eq!(
+            RouteClass::of(&Method::POST, "/api/v1/posts"),
+            RouteClass::Write
+        );
+        assert_eq!(
+            RouteClass::of(&Method::DELETE, "/api/v1/posts/1"),
+            RouteClass::Write
+        );
+        assert_eq!(
+            RouteClass::of(&Method::GET, "/api/v1/posts/search/"),
+            RouteClass::Search
+        );
+    }
+
+    #[test]
+    fn tokens_refill_continuously() {
+        let limiter = RateLimiter::new(config(None));
+        let start = Instant::now();
+
+        let first = limiter.check(RouteClass::Write, "ip:a", start);
+        assert!(first.allowed);
+        assert_eq!(first.remaining, 1);
+        assert_eq!(first.reset, Duration::from_secs(5));
+        assert!(limiter.check(RouteClass::Write, "ip:a", start).allowed);
+        let denied = limiter.check(RouteClass::Write, "ip:a", start);
+        assert!(!denied.allowed);
+        assert_eq!(denied.remaining, 0);
+        assert_eq!(denied.retry_after, Duration::from_secs(5));
+        assert_eq!(denied.reset, Duration::from_secs(10));
+
+        // A token is earned every 5 seconds, and never more than the capacity.
+        let later = start + Duration::from_secs(4);
+        let early = limiter.check(RouteClass::Write, "ip:a", later);
+        assert!(!early.allowed);
+        assert_eq!(early.retry_after, Duration::from_secs(1));
+        assert!(
+            limiter
+                .check(RouteClass::Write, "ip:a", start + Duration::from_secs(5))
+                .allowed
+        );
+        let idle = limiter.check(RouteClass::Write, "ip:a", start + Duration::from_secs(60));
+        assert!(idle.allowed);
+        assert_eq!(idle.remaining, 1);
+    }
+
+    #[test]
+    fn each_route_class_and_client_has_its_own_budget() {
+        let limiter = RateLimiter::new(config(None));
+        let now = Instant::now();
+        for _ in 0..2 {
+            assert!(limiter.check(RouteClass::Write, "ip:a", now).allowed);
+        }
+        assert!(!limiter.check(RouteClass::Write, "ip:a", now).allowed);
+
+        assert!(limiter.check(RouteClass::Write, "ip:b", now).allowed);
+        let read = limiter.check(RouteClass::Read, "ip:a", now);
+        assert!(read.allowed);
+        assert_eq!(read.budget, Budget::new(4, Duration::from_secs(10)));
+        assert_eq!(read.remaining, 3);
+        assert!(limiter.check(RouteClass::Search, "ip:a", now).allowed);
+        assert!(!limiter.check(RouteClass::Search, "ip:a", now).allowed);
+    }
+
+    #[tokio::test]
+    async fn exhausted_clients_are_rejected_with_retry_after() -> Result<()> {
+        let limiter = RateLimiter::new(config(None));
+        let app = Router::new()
+            .route("/api/v1/posts", post(|| async { StatusCode::CREATED }))
+            .layer(from_fn_with_state(limiter, limit));
+
+        for remaining in ["1", "0"] {
+            let response = app
+                .clone()
+                .oneshot(request(Method::POST, "/api/v1/posts", &[])?)
+                .await?;
+            assert_eq!(response.status(), StatusCode::CREATED);
+            assert_eq!(response.headers()[RATE_LIMIT_REMAINING], remaining);
+            assert_eq!(response.headers()[RATE_LIMIT_LIMIT], "2");
+            assert_eq!(response.headers()[RATE_LIMIT_POLICY], "2;w=10");
+        }
+        let response = app
+            .oneshot(request(Method::POST, "/api/v1/posts", &[])?)
+            .await?;
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(response.headers()[header::RETRY_AFTER], "5");
+        assert_eq!(response.headers()[RATE_LIMIT_REMAINING], "0");
+        assert_eq!(response.headers()[RATE_LIMIT_RESET], "10");
+        Ok(())
+    }
+
+    #[test]
+    fn clients_are_keyed_by_the_trusted_header_or_their_peer_address() -> Result<()> {
+        let forwarded = [("x-forwarded-for", "203.0.113.9, 198.51.100.7 ")];
+
+        let untrusted = config(None);
+        let request_from_proxy = request(Method::GET, "/", &forwarded)?;
+        assert_eq!(client_key(&request_from_proxy, &untrusted), "ip:10.0.0.1");
+
+        let trusted = config(Some("x-forwarded-for"));
+        assert_eq!(client_key(&request_from_proxy, &trusted), "ip:198.51.100.7");
+        let direct = request(Method::GET, "/", &[])?;
+        assert_eq!(client_key(&direct, &trusted), "ip:10.0.0.1");
+        let blank = request(Method::GET, "/", &[("x-forwarded-for", " , ")])?;
+        assert_eq!(client_key(&blank, &trusted), "ip:10.0.0.1");
+
+        let mut signed_in = request(Method::GET, "/", &forwarded)?;
+        signed_in
+            .extensions_mut()
+            .insert(ClientIdentity("ann".to_string()));
+        assert_eq!(client_key(&signed_in, &trusted), "user:ann");
+
+        let mut unknown = request(Method::GET, "/", &[])?;
+        unknown.extensions_mut().remove::<ConnectInfo<SocketAddr>>();
+        assert_eq!(client_key(&unknown, &trusted), "ip:unknown");
+        Ok(())
+    }
+}
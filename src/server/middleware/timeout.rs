@@ -0,0 +1,56 @@
+use crate::{
+    error::AppError,
+    server::middleware::{http::HttpConfig, rate_limit::RouteClass},
+};
+use axum::{
+    body::{self, Body},
+    extract::{Request, State},
+    middleware::Next,
+    response::Response,
+};
+use http_body_util::LengthLimitError;
+use std::sync::Arc;
+use tokio::time;
+
+/// Middleware enforcing body size limits and timeouts.
+///
+/// The request body is buffered first: a client that does not finish sending it
+/// within the body timeout receives `408 Request Timeout`, and a body larger than
+/// the configured limit receives `413 Payload Too Large`; media uploads have a limit
+/// of their own, see [`HttpConfig::body_limit`]. The handler then runs under the
+/// timeout for its class of route and yields `503 Service Unavailable` if it does not
+/// complete in time.
+///
+/// # Errors
+///
+/// Returns an `AppError` if the body is too large or arrives too slowly, or if
+/// the handler times out.
+pub async fn enforce(
+    State(config): State<Arc<HttpConfig>>,
+    request: Request,
+    next: Next,
+) -> Result<Response, AppError> {
+    let class = RouteClass::of(request.method(), request.uri().path());
+    let limit = config.body_limit(request.method(), request.uri().path());
+    let (parts, body) = request.into_parts();
+
+    let bytes = time::timeout(config.body_timeout, body::to_bytes(body, limit))
+        .await
+        .map_err(|_| AppError::RequestTimeout)?
+        .map_err(|err| {
+            if err.into_inner().is::<LengthLimitError>() {
+                AppError::PayloadTooLarge(format!(
+                    "Request body exceeds the limit of {limit} bytes"
+                ))
+            } else {
+                AppError::BadRequest("Failed to read the request body".to_string())
+            }
+        })?;
+    let request = Request::from_parts(parts, Body::from(bytes));
+
+    time::timeout(config.timeouts.get(class), next.run(request))
+        .await
+        .map_err(|_| {
+            AppError::ServiceUnavailable("The request took too long to process".to_string())
+        })
+}
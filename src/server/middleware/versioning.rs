@@ -0,0 +1,280 @@
+use crate::{config, error::AppError};
+use anyhow::{Context, Result};
+use axum::{
+    Router,
+    extract::{Request, State},
+    http::{HeaderMap, HeaderName, HeaderValue, Uri, header},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use chrono::{DateTime, Utc};
+use std::{convert::Infallible, fmt, str::FromStr, sync::Arc};
+use tower::{Service, ServiceExt, service_fn};
+
+/// Response header naming the API version that produced the response.
+const API_VERSION: HeaderName = HeaderName::from_static("api-version");
+/// Response header announcing that a route is deprecated (RFC 9745).
+const DEPRECATION: HeaderName = HeaderName::from_static("deprecation");
+/// Response header announcing when a route will be removed (RFC 8594).
+const SUNSET: HeaderName = HeaderName::from_static("sunset");
+
+/// A version of the public API, mounted under `/api/v{N}`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum ApiVersion {
+    /// The original API, returning bare JSON arrays from collection routes.
+    V1,
+    /// Collection routes return a `{ "data": [...], "count": n }` envelope.
+    V2,
+}
+
+impl ApiVersion {
+    /// Every version currently served, oldest first.
+    pub const ALL: [Self; 2] = [Self::V1, Self::V2];
+
+    /// The newest version.
+    pub const LATEST: Self = Self::V2;
+
+    /// The version number, e.g. `2`.
+    pub fn number(self) -> u8 {
+        match self {
+            Self::V1 => 1,
+            Self::V2 => 2,
+        }
+    }
+
+    /// The path prefix the version is mounted under, e.g. `/api/v2`.
+    pub fn prefix(self) -> &'static str {
+        match self {
+            Self::V1 => "/api/v1",
+            Self::V2 => "/api/v2",
+        }
+    }
+
+    /// Reads the version requested through the `Accept` header.
+    ///
+    /// Both vendor media types (`application/vnd.blog.v2+json`) and a `version`
+    /// parameter (`application/json; version=2`) are understood. Returns `None`
+    /// when no version is requested.
+    ///
+    /// # Errors
+    ///
+    /// Returns `AppError::NotAcceptable` if the requested version does not exist.
+    pub fn from_accept(headers: &HeaderMap) -> Result<Option<Self>, AppError> {
+        let requested = headers
+            .get_all(header::ACCEPT)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .find_map(requested_version);
+
+        requested
+            .map(|number| {
+                number.parse().map_err(|_| {
+                    AppError::NotAcceptable(format!("API version {number} is not supported"))
+                })
+            })
+            .transpose()
+    }
+}
+
+/// Extracts the version number from a single `Accept` media range.
+fn requested_version(media_range: &str) -> Option<&str> {
+    let media_range = media_range.trim();
+    let (media_type, params) = media_range
+        .split_once(';')
+        .map_or((media_range, ""), |(media_type, params)| {
+            (media_type, params)
+        });
+
+    if let Some(rest) = media_type.trim().strip_prefix("application/vnd.blog.v") {
+        return rest.strip_suffix("+json");
+    }
+    params
+        .split(';')
+        .filter_map(|param| param.split_once('='))
+        .find(|(name, _)| name.trim().eq_ignore_ascii_case("version"))
+        .map(|(_, value)| value.trim())
+}
+
+impl fmt::Display for ApiVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "v{}", self.number())
+    }
+}
+
+impl FromStr for ApiVersion {
+    type Err = String;
+
+    /// Parses `1`, `2`, `v1` or `v2`.
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let number = value.trim().trim_start_matches(['v', 'V']);
+        Self::ALL
+            .into_iter()
+            .find(|version| version.number().to_string() == number)
+            .ok_or_else(|| format!("unknown API version `{value}`"))
+    }
+}
+
+/// Deprecation notice attached to the responses of routes we plan to retire.
+#[derive(Debug, Clone)]
+pub struct Deprecation {
+    /// When the routes were (or will be) deprecated.
+    pub deprecated_at: DateTime<Utc>,
+    /// When the routes will stop being served, if decided.
+    pub sunset: Option<DateTime<Utc>>,
+    /// Path of the replacement, advertised as `rel="successor-version"`.
+    pub successor: Option<&'static str>,
+}
+
+impl Deprecation {
+    /// Adds `Deprecation`, `Sunset` and `Link` headers to a response.
+    fn apply(&self, headers: &mut HeaderMap) {
+        let mut values = vec![(DEPRECATION, format!("@{}", self.deprecated_at.timestamp()))];
+        if let Some(sunset) = self.sunset {
+            values.push((
+                SUNSET,
+                sunset.format("%a, %d %b %Y %H:%M:%S GMT").to_string(),
+            ));
+        }
+        if let Some(successor) = self.successor {
+            values.push((
+                header::LINK,
+                format!("<{successor}>; rel=\"successor-version\""),
+            ));
+        }
+        for (name, value) in values {
+            if let Ok(value) = HeaderValue::from_str(&value) {
+                headers.append(name, value);
+            }
+        }
+    }
+}
+
+/// API versioning configuration read from the environment.
+///
+/// | Variable                  | Default | Description                                           |
+/// |---------------------------|---------|-------------------------------------------------------|
+/// | `API_DEFAULT_VERSION`     | `1`     | Version served on unversioned paths such as `/posts`. |
+/// | `API_V{N}_DEPRECATED_AT`  | unset   | RFC 3339 date from which version `N` is deprecated.   |
+/// | `API_V{N}_SUNSET`         | unset   | RFC 3339 date on which version `N` will be removed.   |
+#[derive(Debug, Clone)]
+pub struct VersioningConfig {
+    /// Version used when a request names none.
+    pub default_version: ApiVersion,
+    /// Deprecation notices per version.
+    pub deprecations: Vec<(ApiVersion, Deprecation)>,
+}
+
+impl VersioningConfig {
+    /// Builds the configuration from environment variables.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if any of the variables holds an invalid value.
+    pub fn from_env() -> Result<Self> {
+        let date = |key: String| {
+            config::var_opt(&key)
+                .map(|value| {
+                    DateTime::parse_from_rfc3339(value.trim())
+                        .map(|date| date.with_timezone(&Utc))
+                        .with_context(|| format!("Invalid value for {key}: {value:?}"))
+                })
+                .transpose()
+        };
+
+        let mut deprecations = Vec::new();
+        for version in ApiVersion::ALL {
+            let number = version.number();
+            let deprecated_at = date(format!("API_V{number}_DEPRECATED_AT"))?;
+            let sunset = date(format!("API_V{number}_SUNSET"))?;
+            if let Some(deprecated_at) = deprecated_at.or(sunset.map(|_| Utc::now())) {
+                let successor =
+                    (version != ApiVersion::LATEST).then(|| ApiVersion::LATEST.prefix());
+                deprecations.push((
+                    version,
+                    Deprecation {
+                        deprecated_at,
+                        sunset,
+                        successor,
+                    },
+                ));
+            }
+        }
+
+        Ok(Self {
+            default_version: config::parse_or("API_DEFAULT_VERSION", ApiVersion::V1)?,
+            deprecations,
+        })
+    }
+
+    /// Returns the deprecation notice for a version, if it is deprecated.
+    pub fn deprecation(&self, version: ApiVersion) -> Option<Arc<Deprecation>> {
+        self.deprecations
+            .iter()
+            .find(|(deprecated, _)| *deprecated == version)
+            .map(|(_, deprecation)| Arc::new(deprecation.clone()))
+    }
+}
+
+/// Middleware marking every response of the wrapped routes as deprecated.
+///
+/// Apply it with `route_layer` to individual routes or with `layer` to a whole
+/// version's router.
+pub async fn mark_deprecated(
+    State(deprecation): State<Arc<Deprecation>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let mut response = next.run(request).await;
+    deprecation.apply(response.headers_mut());
+    response
+}
+
+/// Middleware adding an `Api-Version` header to every response of a version's routes.
+pub async fn set_version_header(
+    State(version): State<ApiVersion>,
+    mut response: Response,
+) -> Response {
+    response
+        .headers_mut()
+        .insert(API_VERSION, HeaderValue::from(u16::from(version.number())));
+    response
+}
+
+/// Prefixes a URI's path with the version's mount point.
+fn versioned_uri(uri: &Uri, version: ApiVersion) -> Option<Uri> {
+    let path_and_query = uri.path_and_query().map_or("/", |pq| pq.as_str());
+    format!("{}{}", version.prefix(), path_and_query)
+        .parse()
+        .ok()
+}
+
+/// Builds the fallback service serving unversioned paths such as `/posts`.
+///
+/// The version is negotiated from the `Accept` header (see
+/// [`ApiVersion::from_accept`]), falling back to `default`. The request is then
+/// forwarded to the matching `/api/v{N}` route of `versioned`, and the response
+/// is marked with `Vary: Accept`.
+pub fn negotiate(
+    versioned: Router,
+    default: ApiVersion,
+) -> impl Service<Request, Response = Response, Error = Infallible, Future: Send> + Clone {
+    service_fn(move |mut request: Request| {
+        let versioned = versioned.clone();
+        async move {
+            let version = match ApiVersion::from_accept(request.headers()) {
+                Ok(version) => version.unwrap_or(default),
+                Err(err) => return Ok(err.into_response()),
+            };
+            if let Some(uri) = versioned_uri(request.uri(), version) {
+                *request.uri_mut() = uri;
+            }
+
+            let mut response = versioned.oneshot(request).await?;
+            response
+                .headers_mut()
+                .append(header::VARY, HeaderValue::from_static("accept"));
+            Ok(response)
+        }
+    })
+}
@@ -1,16 +1,74 @@
-use crate::{database::connection::db_connect, state::AppState};
+use crate::{
+    collab::{
+        CollabConfig, Collaboration, DynEditLockRepository, postgres::PgEditLockRepository,
+        sqlite::SqliteEditLockRepository,
+    },
+    database::{
+        connection::{Database, db_connect},
+        replica::{Replica, ReplicaConfig},
+    },
+    events::{
+        EventsConfig, PostEvents,
+        postgres::{self as live, PgEventNotifier},
+    },
+    idempotency::{
+        DynIdempotencyStore, Idempotency, IdempotencyConfig, postgres::PgIdempotencyStore,
+        sqlite::SqliteIdempotencyStore,
+    },
+    jobs::{
+        DynJobRepository, Jobs, JobsConfig, PurgeJobs, postgres::PgJobRepository,
+        sqlite::SqliteJobRepository,
+    },
+    media::{
+        DynMediaRepository, GenerateVariants, Media, MediaConfig, postgres::PgMediaRepository,
+        sqlite::SqliteMediaRepository,
+    },
+    outbox::{
+        Dispatcher, DynOutboxRepository, EventConsumer, OutboxConfig, postgres::PgOutboxRepository,
+        sqlite::SqliteOutboxRepository,
+    },
+    repository::{
+        AnnounceScheduledPosts, DynPostRepository, postgres::PgPostRepository,
+        sqlite::SqlitePostRepository,
+    },
+    site::SiteConfig,
+    state::AppState,
+    webhook::{
+        DeliverWebhook, DynWebhookRepository, WebhookConfig, Webhooks,
+        postgres::PgWebhookRepository, sqlite::SqliteWebhookRepository,
+    },
+};
 use anyhow::Context;
+use middleware::{
+    admin::AdminConfig,
+    http::HttpConfig,
+    rate_limit::{RateLimitConfig, RateLimiter},
+    versioning::VersioningConfig,
+};
+use std::{net::SocketAddr, sync::Arc};
 use tokio::net::TcpListener;
 
+/// Middleware applied to the application routes.
+pub mod middleware;
 /// Routes module for the Axum server.
-mod routes;
+pub mod routes;
 
 /// Initializes and runs the Axum server.
 ///
 /// This function performs the following steps:
 /// 1. Binds a TCP listener to `localhost` on port `3000`.
-/// 2. Establishes a connection to the PostgreSQL database.
-/// 3. Constructs the application state with the database connection pool.
+/// 2. Establishes a connection to the PostgreSQL or SQLite database named by `DATABASE_URL`,
+///    and to the PostgreSQL read replica named by `DATABASE_REPLICA_URL`, if set.
+/// 3. Constructs the application state with the database connection pool, a blog post
+///    repository, media file repository, idempotency key store, webhook repository, edit
+///    lock repository and job queue for that database, the local directory or S3 bucket
+///    holding the content of media files, rate limiter, HTTP middleware, API versioning
+///    and site settings. The domain events recorded in the outbox are dispatched in the
+///    background, and the queued jobs run there, among them the webhook deliveries the
+///    events lead to and the generation of image variants. Pending deliveries and variants
+///    without a job, such as those recorded by an earlier version, are enqueued. With
+///    PostgreSQL, the server listens for the post events notified by every server, to
+///    stream them to its live subscribers.
 /// 4. Sets up the application routes.
 /// 5. Starts the Axum server with the configured routes and state.
 ///
@@ -18,12 +76,16 @@ mod routes;
 ///
 /// Returns an error if:
 /// - Binding to the specified address and port fails.
-/// - Establishing a database connection fails.
+/// - Establishing a database connection fails, or listening for post events on it.
+/// - The replica, media, idempotency, webhook, live event, collaboration, outbox, job,
+///   rate limit, HTTP middleware, API versioning or site configuration is invalid.
 /// - Starting the server encounters an issue.
 ///
 /// # Example
 ///
 /// ```rust,no_run
+/// use blog_api::server::setup_server;
+///
 /// #[tokio::main]
 /// async fn main() -> anyhow::Result<()> {
 ///     setup_server().await
@@ -37,11 +99,146 @@ pub async fn setup_server() -> anyhow::Result<()> {
         .await
         .context("Failed to bind to port 3000")?;
 
-    // Establish a connection to the PostgreSQL database
-    let pool = db_connect().await?;
+    // Establish a connection to the database and pick the matching post storage
+    let replica = Replica::from_config(ReplicaConfig::from_env()?)?;
+    let (pool, replica, posts, events, files, keys, hooks, locks, queue): (
+        _,
+        _,
+        DynPostRepository,
+        DynOutboxRepository,
+        DynMediaRepository,
+        DynIdempotencyStore,
+        DynWebhookRepository,
+        DynEditLockRepository,
+        DynJobRepository,
+    ) = match db_connect().await? {
+        Database::Postgres(pool) => {
+            let posts = PgPostRepository::new(pool.clone()).with_replica(replica.clone());
+            let events = PgOutboxRepository::new(pool.clone());
+            let files = PgMediaRepository::new(pool.clone());
+            let keys = PgIdempotencyStore::new(pool.clone());
+            let hooks = PgWebhookRepository::new(pool.clone());
+            let locks = PgEditLockRepository::new(pool.clone());
+            let queue = PgJobRepository::new(pool.clone());
+            (
+                Some(pool),
+                replica,
+                Arc::new(posts),
+                Arc::new(events),
+                Arc::new(files),
+                Arc::new(keys),
+                Arc::new(hooks),
+                Arc::new(locks),
+                Arc::new(queue),
+            )
+        }
+        Database::Sqlite(pool) => {
+            tracing::info!("Using SQLite storage");
+            if replica.is_some() {
+                tracing::warn!("DATABASE_REPLICA_URL is ignored with SQLite storage");
+            }
+            let events = SqliteOutboxRepository::new(pool.clone());
+            let files = SqliteMediaRepository::new(pool.clone());
+            let keys = SqliteIdempotencyStore::new(pool.clone());
+            let hooks = SqliteWebhookRepository::new(pool.clone());
+            let locks = SqliteEditLockRepository::new(pool.clone());
+            let queue = SqliteJobRepository::new(pool.clone());
+            (
+                None,
+                None,
+                Arc::new(SqlitePostRepository::new(pool)),
+                Arc::new(events),
+                Arc::new(files),
+                Arc::new(keys),
+                Arc::new(hooks),
+                Arc::new(locks),
+                Arc::new(queue),
+            )
+        }
+    };
+    if let Some(replica) = &replica {
+        replica.check_health().await;
+        replica.spawn_health_checks();
+    }
+
+    let media_config = MediaConfig::from_env()?;
+    let blobs = media_config.storage.blob_store()?;
+    let media = Media::new(files, blobs, media_config);
+
+    let idempotency = Idempotency::new(keys, IdempotencyConfig::from_env()?);
+    idempotency.spawn_purge();
+
+    // Run background jobs: webhook deliveries, image variants, and on schedule the purge
+    // of finished jobs and the announcement of scheduled posts
+    let jobs_config = JobsConfig::from_env()?;
+    let webhook_config = WebhookConfig::from_env()?;
+    let purge = PurgeJobs::new(queue.clone(), jobs_config.retention);
+    let purge_schedule = jobs_config.purge_schedule.clone();
+    let publish_schedule = jobs_config.publish_schedule.clone();
+    let jobs = Jobs::new(queue, jobs_config)
+        .with_handler(purge)
+        .with_handler(DeliverWebhook::new(hooks.clone(), webhook_config.clone())?)
+        .with_handler(GenerateVariants::new(media.clone()))
+        .with_handler(AnnounceScheduledPosts::new(posts.clone()))
+        .with_recurring::<PurgeJobs>(purge_schedule, &())?
+        .with_recurring::<AnnounceScheduledPosts>(publish_schedule, &())?;
+    jobs.spawn();
 
-    // Construct the application state with the database connection pool
-    let state = AppState { pool };
+    // Enqueue the jobs of work left pending without one, such as by an earlier version
+    let webhooks = Webhooks::new(hooks, webhook_config, jobs.clone());
+    match webhooks.resume().await {
+        Ok(0) => {}
+        Ok(resumed) => tracing::info!(resumed, "resumed sending webhook deliveries"),
+        Err(err) => tracing::warn!("failed to resume sending webhook deliveries: {err}"),
+    }
+    match media.resume_variants(&jobs).await {
+        Ok(0) => {}
+        Ok(resumed) => tracing::info!(resumed, "resumed generating media variants"),
+        Err(err) => tracing::warn!("failed to resume generating media variants: {err}"),
+    }
+
+    // Relay post changes to live subscribers, through PostgreSQL so that every server
+    // sees the changes made through the others
+    let live_events = PostEvents::new(EventsConfig::from_env()?);
+    let notifier: Arc<dyn EventConsumer> = match &pool {
+        Some(pool) => {
+            live::listen(pool.clone(), live_events.clone())
+                .await
+                .context("Failed to listen for post events")?;
+            Arc::new(PgEventNotifier::new(pool.clone()))
+        }
+        None => Arc::new(live_events.clone()),
+    };
+
+    // Hand the domain events recorded with each write to the webhooks and live subscribers
+    Dispatcher::new(events, OutboxConfig::from_env()?)
+        .with_consumer(Arc::new(webhooks.clone()))
+        .with_consumer(notifier)
+        .spawn();
+
+    // Construct the application state with the database connection pool and middleware settings
+    let rate_limiter = RateLimiter::new(RateLimitConfig::from_env()?);
+    let http = Arc::new(HttpConfig::from_env()?);
+    let versioning = Arc::new(VersioningConfig::from_env()?);
+    let site = Arc::new(SiteConfig::from_env()?);
+    let admin = Arc::new(AdminConfig::from_env()?);
+    let collab = Collaboration::new(locks, CollabConfig::from_env()?);
+    let state = AppState {
+        pool,
+        replica,
+        posts,
+        media,
+        idempotency,
+        webhooks,
+        events: live_events,
+        collab,
+        jobs,
+        rate_limiter,
+        http,
+        versioning,
+        site,
+        admin,
+    };
 
     // Log the server's listening address
     tracing::info!("Listening on http://localhost:3000");
@@ -49,8 +246,12 @@ pub async fn setup_server() -> anyhow::Result<()> {
     // Set up the application routes with the provided state
 
     // Start the Axum server with the configured routes and state
-    axum::serve(listener, routes::setup_routes(state))
-        .await
-        .context("Failed to start server")?;
+    // Peer addresses are recorded so that clients can be rate limited by IP
+    axum::serve(
+        listener,
+        routes::setup_routes(state).into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .await
+    .context("Failed to start server")?;
     Ok(())
 }
@@ -1,15 +1,145 @@
 use crate::{
+    database::{extractor::finish_transaction, replica},
     handler::{
-        create::create_post, delete::delete_by_id, list::find_all, read::find_by_id,
-        search::search_posts, update::update_by_id,
+        bulk, collab,
+        create::create_post,
+        delete::delete_by_id,
+        docs::{openapi_json, redoc},
+        events::stream_events,
+        export::export_posts,
+        import::{import_posts, import_wordpress},
+        job,
+        list::find_all,
+        media,
+        meta::find_meta_by_id,
+        read::find_by_id,
+        search::search_posts,
+        update::update_by_id,
+        v2, webhook,
+    },
+    server::middleware::{
+        admin, http, idempotency, rate_limit, timeout,
+        versioning::{self, ApiVersion},
     },
     state::AppState,
+    telemetry::{request_id, span},
 };
 use axum::{
-    routing::{get, post},
     Router,
+    body::Body,
+    extract::DefaultBodyLimit,
+    middleware,
+    routing::{get, post, put},
+};
+use tower::ServiceBuilder;
+use tower_http::{
+    decompression::RequestDecompressionLayer,
+    map_request_body::MapRequestBodyLayer,
+    request_id::{MakeRequestUuid, PropagateRequestIdLayer, SetRequestIdLayer},
+    trace::TraceLayer,
 };
-use tower_http::trace::TraceLayer;
+
+/// Builds the routes of one version of the API, relative to its `/api/v{N}` prefix.
+///
+/// Version 2 starts from the version 1 routes and overrides the handlers whose response
+/// shape changed, so unchanged endpoints are shared. Responses carry an `Api-Version`
+/// header, and deprecation headers when the version is configured as deprecated.
+fn api_routes(version: ApiVersion, state: &AppState) -> Router<AppState> {
+    let router = match version {
+        ApiVersion::V1 => Router::new()
+            .route("/posts", post(create_post).get(find_all))
+            .route("/posts/search", get(search_posts)),
+        ApiVersion::V2 => Router::new()
+            .route("/posts", post(create_post).get(v2::list::find_all))
+            .route("/posts/search", get(v2::search::search_posts)),
+    }
+    .route(
+        "/posts/bulk",
+        post(bulk::create_posts)
+            .patch(bulk::update_posts)
+            .delete(bulk::delete_posts),
+    )
+    .route("/posts/export", get(export_posts))
+    .route("/events", get(stream_events))
+    .route("/posts/import", post(import_posts))
+    .route("/posts/import/wordpress", post(import_wordpress))
+    .route(
+        "/posts/{id}",
+        get(find_by_id).put(update_by_id).delete(delete_by_id),
+    )
+    .route("/posts/{id}/meta", get(find_meta_by_id))
+    .route("/posts/{id}/collaborate", get(collab::collaborate))
+    .route("/posts/{id}/media", get(media::list_post_media))
+    .route(
+        "/posts/{id}/media/{media_id}",
+        put(media::attach_media).delete(media::detach_media),
+    )
+    // Upload bodies are limited by `timeout::enforce`, with a limit of their own.
+    .route(
+        "/media",
+        post(media::upload_media)
+            .get(media::list_media)
+            .layer(DefaultBodyLimit::disable()),
+    )
+    .route(
+        "/media/{id}",
+        get(media::get_media).delete(media::delete_media),
+    )
+    .route("/media/{id}/content", get(media::download_media))
+    .route(
+        "/media/{id}/variants/{name}",
+        get(media::download_media_variant),
+    )
+    .merge(admin_routes(state))
+    .layer(middleware::map_response_with_state(
+        version,
+        versioning::set_version_header,
+    ));
+
+    match state.versioning.deprecation(version) {
+        Some(deprecation) => router.layer(middleware::from_fn_with_state(
+            deprecation,
+            versioning::mark_deprecated,
+        )),
+        None => router,
+    }
+}
+
+/// Builds the administration routes of a version of the API, which require the
+/// `ADMIN_TOKEN`; see [`admin::require_token`].
+fn admin_routes(state: &AppState) -> Router<AppState> {
+    // Layered on each route rather than the router, so that methods a route does not
+    // serve still answer `405 Method Not Allowed`.
+    let guard = || middleware::from_fn_with_state(state.admin.clone(), admin::require_token);
+    Router::new()
+        .route(
+            "/webhooks",
+            post(webhook::create_webhook)
+                .get(webhook::list_webhooks)
+                .route_layer(guard()),
+        )
+        .route(
+            "/webhooks/{id}",
+            get(webhook::get_webhook)
+                .put(webhook::update_webhook)
+                .delete(webhook::delete_webhook)
+                .route_layer(guard()),
+        )
+        .route(
+            "/webhooks/{id}/deliveries",
+            get(webhook::list_deliveries).route_layer(guard()),
+        )
+        .route(
+            "/webhooks/{id}/deliveries/{delivery_id}/redeliver",
+            post(webhook::redeliver).route_layer(guard()),
+        )
+        .route("/jobs", get(job::list_jobs).route_layer(guard()))
+        .route("/jobs/{id}", get(job::get_job).route_layer(guard()))
+        .route(
+            "/jobs/{id}/retry",
+            post(job::retry_job).route_layer(guard()),
+        )
+}
 
 /// Configures the application's routes and middleware.
 ///
@@ -27,7 +157,7 @@ use tower_http::trace::TraceLayer;
 ///
 /// # Example
 ///
-/// ```rust
+/// ```rust,ignore
 /// use axum::{Router, routing::get};
 /// use tower_http::trace::TraceLayer;
 /// use crate::state::AppState;
@@ -38,18 +168,78 @@ use tower_http::trace::TraceLayer;
 ///
 /// # Notes
 ///
+/// - The blog API is served under `/api/v1` and `/api/v2`; see `api_routes`. Unversioned
+///   paths such as `/posts` are forwarded to the version negotiated from the `Accept` header,
+///   or to the configured default version; see [`versioning::negotiate`].
+/// - Every request is assigned an `X-Request-Id` (an incoming one is kept as-is), which is
+///   echoed on the response, recorded on the request span and included in error bodies.
 /// - The `TraceLayer` from `tower_http` is used to log high-level information about incoming
 ///   HTTP requests and responses. This is useful for monitoring and debugging purposes.
+///   Its span continues any W3C `traceparent` sent by the caller.
+/// - Each client is rate limited with separate token buckets for read, write and search
+///   routes; see [`rate_limit::limit`].
+/// - CORS, response compression and security headers are configured through
+///   [`http::HttpConfig`]; gzip, brotli and zstd request bodies are decompressed.
+/// - Request bodies are size limited and every handler runs under a per-route timeout;
+///   see [`timeout::enforce`].
+/// - Reads go to the read replica, if configured, except for clients that wrote recently;
+///   see [`replica::read_your_writes`].
+/// - The administration routes, which manage webhooks and background jobs, require the `ADMIN_TOKEN` as a
+///   bearer token; see [`admin::require_token`].
+/// - `POST`, `PATCH` and `DELETE` requests carrying an `Idempotency-Key` are executed
+///   once per key and their responses replayed for repeats; see [`idempotency::enforce`].
+/// - Transactions begun by a `DatabaseTransaction` extractor are committed or rolled back
+///   once the handler's response is ready; see [`finish_transaction`].
 /// - The application state is shared across all routes using Axum's state management.
 pub fn setup_routes(state: AppState) -> Router {
+    let http = state.http.clone();
+    let versioned = ApiVersion::ALL
+        .into_iter()
+        .fold(Router::new(), |router, version| {
+            router.nest(version.prefix(), api_routes(version, &state))
+        })
+        .with_state(state.clone());
+
     Router::new()
         .route("/", get(|| async { "Hello, World!" }))
-        .route("/posts", post(create_post).get(find_all))
-        .route("/posts/search", get(search_posts))
-        .route(
-            "/posts/{id}",
-            get(find_by_id).put(update_by_id).delete(delete_by_id),
+        .route("/openapi.json", get(openapi_json))
+        .route("/docs", get(redoc))
+        .merge(versioned.clone())
+        .fallback_service(versioning::negotiate(
+            versioned,
+            state.versioning.default_version,
+        ))
+        .layer(
+            ServiceBuilder::new()
+                .layer(SetRequestIdLayer::x_request_id(MakeRequestUuid))
+                .layer(PropagateRequestIdLayer::x_request_id())
+                .layer(
+                    TraceLayer::new_for_http()
+                        .make_span_with(span::make_http_span)
+                        .on_response(span::on_http_response),
+                )
+                .layer(http.cors())
+                .layer(http.compression())
+                .layer(RequestDecompressionLayer::new())
+                .layer(MapRequestBodyLayer::new(Body::new))
+                .layer(middleware::from_fn(request_id::scope))
+                .layer(middleware::map_response_with_state(
+                    http.clone(),
+                    http::set_security_headers,
+                ))
+                .layer(middleware::from_fn_with_state(
+                    state.clone(),
+                    rate_limit::limit,
+                ))
+                .layer(middleware::from_fn_with_state(
+                    state.clone(),
+                    replica::read_your_writes,
+                ))
+                .layer(middleware::from_fn_with_state(http, timeout::enforce))
+                .layer(middleware::from_fn_with_state(
+                    state.clone(),
+                    idempotency::enforce,
+                ))
+                .layer(middleware::from_fn(finish_transaction)),
         )
-        .layer(TraceLayer::new_for_http())
-        .with_state(state)
 }
@@ -0,0 +1,135 @@
+use super::{
+    SiteConfig,
+    render::{post_date, post_dir},
+};
+use crate::model::blog::BlogPost;
+use chrono::{DateTime, SecondsFormat, Utc};
+use quick_xml::escape::escape;
+use std::fmt::Write;
+
+/// Returns the time a set of posts last changed, for the `updated` dates of feeds.
+fn last_updated(posts: &[(&BlogPost, String)]) -> DateTime<Utc> {
+    posts
+        .iter()
+        .filter_map(|(post, _)| post.updated_at.or(post.created_at))
+        .max()
+        .unwrap_or(DateTime::UNIX_EPOCH)
+}
+
+/// Renders an Atom feed of posts, each given with its content rendered to HTML.
+pub fn atom(config: &SiteConfig, posts: &[(&BlogPost, String)]) -> String {
+    let rfc3339 = |date: DateTime<Utc>| date.to_rfc3339_opts(SecondsFormat::Secs, true);
+    let base_url = escape(&config.base_url);
+    let mut feed = String::new();
+    // Writing to a `String` cannot fail.
+    let _ = write!(
+        feed,
+        "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n\
+         <feed xmlns=\"http://www.w3.org/2005/Atom\">\n\
+         <title>{}</title>\n",
+        escape(&config.title)
+    );
+    if let Some(description) = &config.description {
+        let _ = writeln!(feed, "<subtitle>{}</subtitle>", escape(description));
+    }
+    let _ = write!(
+        feed,
+        "<id>{base_url}</id>\n\
+         <link href=\"{base_url}\"/>\n\
+         <link rel=\"self\" href=\"{base_url}atom.xml\"/>\n\
+         <updated>{}</updated>\n",
+        rfc3339(last_updated(posts))
+    );
+    for (post, html) in posts {
+        let url = format!("{base_url}{}", post_dir(post));
+        let author = post.author.as_deref().unwrap_or(&config.title);
+        let updated = post.updated_at.or(post.created_at).unwrap_or_default();
+        let _ = write!(
+            feed,
+            "<entry>\n\
+             <title>{}</title>\n\
+             <id>{url}</id>\n\
+             <link href=\"{url}\"/>\n\
+             <updated>{}</updated>\n",
+            escape(&post.title),
+            rfc3339(updated)
+        );
+        if let Some(published) = post_date(post) {
+            let _ = writeln!(feed, "<published>{}</published>", rfc3339(published));
+        }
+        let _ = writeln!(feed, "<author><name>{}</name></author>", escape(author));
+        for term in std::iter::once(&post.category).chain(&post.tags) {
+            let _ = writeln!(feed, "<category term=\"{}\"/>", escape(term));
+        }
+        let _ = write!(
+            feed,
+            "<content type=\"html\">{}</content>\n</entry>\n",
+            escape(html)
+        );
+    }
+    feed.push_str("</feed>\n");
+    feed
+}
+
+/// Renders an RSS 2.0 feed of posts, each given with its content rendered to HTML.
+pub fn rss(config: &SiteConfig, posts: &[(&BlogPost, String)]) -> String {
+    let base_url = escape(&config.base_url);
+    let mut feed = String::new();
+    let _ = write!(
+        feed,
+        "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n\
+         <rss version=\"2.0\">\n<channel>\n\
+         <title>{}</title>\n\
+         <link>{base_url}</link>\n\
+         <description>{}</description>\n\
+         <lastBuildDate>{}</lastBuildDate>\n",
+        escape(&config.title),
+        escape(config.description.as_deref().unwrap_or(&config.title)),
+        last_updated(posts).to_rfc2822()
+    );
+    for (post, html) in posts {
+        let url = format!("{base_url}{}", post_dir(post));
+        let _ = write!(
+            feed,
+            "<item>\n\
+             <title>{}</title>\n\
+             <link>{url}</link>\n\
+             <guid isPermaLink=\"true\">{url}</guid>\n",
+            escape(&post.title)
+        );
+        if let Some(published) = post_date(post) {
+            let _ = writeln!(feed, "<pubDate>{}</pubDate>", published.to_rfc2822());
+        }
+        for term in std::iter::once(&post.category).chain(&post.tags) {
+            let _ = writeln!(feed, "<category>{}</category>", escape(term));
+        }
+        let _ = write!(
+            feed,
+            "<description>{}</description>\n</item>\n",
+            escape(html)
+        );
+    }
+    feed.push_str("</channel>\n</rss>\n");
+    feed
+}
+
+/// Renders a sitemap of pages, given by their URL and the time they last changed.
+pub fn sitemap(pages: &[(String, Option<DateTime<Utc>>)]) -> String {
+    let mut sitemap = String::from(
+        "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n\
+         <urlset xmlns=\"http://www.sitemaps.org/schemas/sitemap/0.9\">\n",
+    );
+    for (url, modified) in pages {
+        let _ = write!(sitemap, "<url><loc>{}</loc>", escape(url));
+        if let Some(modified) = modified {
+            let _ = write!(
+                sitemap,
+                "<lastmod>{}</lastmod>",
+                modified.to_rfc3339_opts(SecondsFormat::Secs, true)
+            );
+        }
+        sitemap.push_str("</url>\n");
+    }
+    sitemap.push_str("</urlset>\n");
+    sitemap
+}
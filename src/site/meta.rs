@@ -0,0 +1,239 @@
+use super::{
+    SiteConfig,
+    render::{post_date, post_dir},
+};
+use crate::{
+    media::content_path,
+    model::{
+        blog::BlogPost,
+        media::MediaFile,
+        meta::{MetaTag, PostMeta},
+    },
+};
+use chrono::{DateTime, SecondsFormat, Utc};
+use pulldown_cmark::{Event, Parser, TagEnd};
+use quick_xml::escape::escape;
+use serde_json::{Map, Value, json};
+use std::fmt::Write;
+
+/// Longest description derived from the content of a post, in characters; search
+/// engines cut longer ones off.
+pub const DESCRIPTION_LENGTH: usize = 160;
+
+/// Returns the start of Markdown content as plain text of at most `max_chars`
+/// characters, cut at a word boundary and ending with `…` when shortened.
+pub fn excerpt(markdown: &str, max_chars: usize) -> String {
+    let mut text = String::new();
+    for event in Parser::new(markdown) {
+        match event {
+            Event::Text(part) | Event::Code(part) => text.push_str(&part),
+            Event::SoftBreak
+            | Event::HardBreak
+            | Event::End(TagEnd::Paragraph | TagEnd::Heading(_) | TagEnd::Item) => {
+                text.push(' ');
+            }
+            _ => {}
+        }
+    }
+    let words: Vec<&str> = text.split_whitespace().collect();
+    let mut excerpt = String::new();
+    for (index, word) in words.iter().enumerate() {
+        let separator = usize::from(!excerpt.is_empty());
+        // Leave room for the ellipsis unless this is the last word.
+        let reserved = usize::from(index + 1 < words.len());
+        if excerpt.chars().count() + separator + word.chars().count() + reserved > max_chars {
+            excerpt.push('…');
+            break;
+        }
+        if separator > 0 {
+            excerpt.push(' ');
+        }
+        excerpt.push_str(word);
+    }
+    excerpt
+}
+
+/// Formats a time as in Open Graph and schema.org dates, e.g. `2025-03-01T12:00:00Z`.
+fn timestamp(time: DateTime<Utc>) -> String {
+    time.to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+/// Builds the metadata of a post for search engines and link previews.
+///
+/// The canonical URL defaults to the URL of the post on the site, and the featured
+/// image, if any, is linked on the API at `config.api_url`.
+pub fn post_meta(
+    post: &BlogPost,
+    featured_image: Option<MediaFile>,
+    config: &SiteConfig,
+) -> PostMeta {
+    let title = post
+        .meta_title
+        .clone()
+        .unwrap_or_else(|| post.title.clone());
+    let description = post
+        .meta_description
+        .clone()
+        .unwrap_or_else(|| excerpt(&post.content, DESCRIPTION_LENGTH));
+    let canonical_url = post
+        .canonical_url
+        .clone()
+        .unwrap_or_else(|| format!("{}{}", config.base_url, post_dir(post)));
+    let image_url = featured_image.as_ref().map(|image| {
+        format!(
+            "{}{}",
+            config.api_url,
+            content_path(image.id, None).trim_start_matches('/')
+        )
+    });
+    let published = post_date(post).map(timestamp);
+    let modified = post.updated_at.or(post.created_at).map(timestamp);
+
+    let mut open_graph = vec![
+        MetaTag::new("og:type", "article"),
+        MetaTag::new("og:site_name", config.title.clone()),
+        MetaTag::new("og:title", title.clone()),
+        MetaTag::new("og:description", description.clone()),
+        MetaTag::new("og:url", canonical_url.clone()),
+    ];
+    if let (Some(image), Some(url)) = (&featured_image, &image_url) {
+        open_graph.push(MetaTag::new("og:image", url.clone()));
+        open_graph.push(MetaTag::new("og:image:type", image.content_type.clone()));
+        if let (Some(width), Some(height)) = (image.width, image.height) {
+            open_graph.push(MetaTag::new("og:image:width", width.to_string()));
+            open_graph.push(MetaTag::new("og:image:height", height.to_string()));
+        }
+    }
+    if let Some(published) = &published {
+        open_graph.push(MetaTag::new("article:published_time", published.clone()));
+    }
+    if let Some(modified) = &modified {
+        open_graph.push(MetaTag::new("article:modified_time", modified.clone()));
+    }
+    if let Some(author) = &post.author {
+        open_graph.push(MetaTag::new("article:author", author.clone()));
+    }
+    open_graph.push(MetaTag::new("article:section", post.category.clone()));
+    for tag in &post.tags {
+        open_graph.push(MetaTag::new("article:tag", tag.clone()));
+    }
+
+    let card = if image_url.is_some() {
+        "summary_large_image"
+    } else {
+        "summary"
+    };
+    let mut twitter = vec![MetaTag::new("twitter:card", card)];
+    if let Some(handle) = &config.twitter_handle {
+        twitter.push(MetaTag::new("twitter:site", handle.clone()));
+    }
+    twitter.push(MetaTag::new("twitter:title", title.clone()));
+    twitter.push(MetaTag::new("twitter:description", description.clone()));
+    if let Some(url) = &image_url {
+        twitter.push(MetaTag::new("twitter:image", url.clone()));
+    }
+
+    let mut json_ld = Map::new();
+    json_ld.insert("@context".into(), json!("https://schema.org"));
+    json_ld.insert("@type".into(), json!("BlogPosting"));
+    json_ld.insert("headline".into(), json!(title));
+    json_ld.insert("description".into(), json!(description));
+    json_ld.insert("url".into(), json!(canonical_url));
+    json_ld.insert(
+        "mainEntityOfPage".into(),
+        json!({ "@type": "WebPage", "@id": canonical_url }),
+    );
+    if let Some(url) = &image_url {
+        json_ld.insert("image".into(), json!([url]));
+    }
+    if let Some(published) = &published {
+        json_ld.insert("datePublished".into(), json!(published));
+    }
+    if let Some(modified) = &modified {
+        json_ld.insert("dateModified".into(), json!(modified));
+    }
+    if let Some(author) = &post.author {
+        json_ld.insert(
+            "author".into(),
+            json!({ "@type": "Person", "name": author }),
+        );
+    }
+    json_ld.insert(
+        "publisher".into(),
+        json!({ "@type": "Organization", "name": config.title, "url": config.base_url }),
+    );
+    json_ld.insert("articleSection".into(), json!(post.category));
+    json_ld.insert("keywords".into(), json!(post.tags.join(", ")));
+    let json_ld = Value::Object(json_ld);
+
+    let robots = post.noindex.then(|| "noindex".to_string());
+    let html = head_html(
+        &title,
+        &description,
+        &canonical_url,
+        robots.as_deref(),
+        &open_graph,
+        &twitter,
+        &json_ld,
+    );
+    PostMeta {
+        title,
+        description,
+        canonical_url,
+        robots,
+        open_graph,
+        twitter,
+        json_ld,
+        html,
+        featured_image,
+    }
+}
+
+/// Renders the metadata of a post as the elements of a page head, one per line.
+fn head_html(
+    title: &str,
+    description: &str,
+    canonical_url: &str,
+    robots: Option<&str>,
+    open_graph: &[MetaTag],
+    twitter: &[MetaTag],
+    json_ld: &Value,
+) -> String {
+    // Writing to a `String` cannot fail.
+    let mut html = String::new();
+    let _ = writeln!(html, "<title>{}</title>", escape(title));
+    let _ = writeln!(
+        html,
+        "<meta name=\"description\" content=\"{}\">",
+        escape(description)
+    );
+    let _ = writeln!(
+        html,
+        "<link rel=\"canonical\" href=\"{}\">",
+        escape(canonical_url)
+    );
+    if let Some(robots) = robots {
+        let _ = writeln!(
+            html,
+            "<meta name=\"robots\" content=\"{}\">",
+            escape(robots)
+        );
+    }
+    for (attribute, tags) in [("property", open_graph), ("name", twitter)] {
+        for tag in tags {
+            let _ = writeln!(
+                html,
+                "<meta {attribute}=\"{}\" content=\"{}\">",
+                escape(&tag.name),
+                escape(&tag.content)
+            );
+        }
+    }
+    // `<` is escaped so that no string in the data can close the script element.
+    let _ = writeln!(
+        html,
+        "<script type=\"application/ld+json\">{}</script>",
+        json_ld.to_string().replace('<', "\\u003c")
+    );
+    html
+}
@@ -0,0 +1,439 @@
+use crate::{config, model::blog::BlogPost};
+use anyhow::{Context, Result, bail};
+use chrono::{DateTime, Utc};
+use minijinja::{Value, context};
+use render::{Pagination, PostView, SiteView, Templates};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{
+    collections::{BTreeMap, HashSet},
+    fmt::Write,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+/// Atom and RSS feeds, and the sitemap.
+pub mod feed;
+
+/// Metadata of posts for search engines and link previews.
+pub mod meta;
+
+/// Templates and the values they are rendered with.
+pub mod render;
+
+/// File in the output directory recording what each generated file was built from.
+pub const MANIFEST_FILE: &str = ".site-manifest.json";
+
+/// Number of posts in the feeds.
+const FEED_SIZE: usize = 20;
+
+/// Static site configuration read from the environment.
+///
+/// The API uses it too, for the metadata of posts.
+///
+/// | Variable              | Default                  | Description                                           |
+/// |-----------------------|--------------------------|-------------------------------------------------------|
+/// | `SITE_TITLE`          | `Blog`                   | Title of the site.                                    |
+/// | `SITE_DESCRIPTION`    | unset                    | Description of the site, for page metadata and feeds. |
+/// | `SITE_BASE_URL`       | `http://localhost:8000/` | Absolute URL the site is served from.                 |
+/// | `SITE_API_URL`        | `http://localhost:3000/` | Absolute URL the API is served from, for media URLs.  |
+/// | `SITE_TWITTER_HANDLE` | unset                    | Twitter account of the site, e.g. `@blog`.            |
+/// | `SITE_PAGE_SIZE`      | `10`                     | Posts per index, category and tag page.               |
+/// | `SITE_TEMPLATES_DIR`  | unset                    | Directory of templates replacing the built-in ones.   |
+#[derive(Debug, Clone)]
+pub struct SiteConfig {
+    /// Title of the site.
+    pub title: String,
+    /// Description of the site.
+    pub description: Option<String>,
+    /// Absolute URL the site is served from, ending with `/`.
+    pub base_url: String,
+    /// Absolute URL the API is served from, ending with `/`.
+    pub api_url: String,
+    /// Twitter account of the site, if any.
+    pub twitter_handle: Option<String>,
+    /// Posts per index, category and tag page; at least 1.
+    pub page_size: usize,
+    /// Directory of templates replacing the built-in ones, if any.
+    pub templates_dir: Option<PathBuf>,
+}
+
+impl SiteConfig {
+    /// Builds the configuration from environment variables.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if any of the variables holds an invalid value.
+    pub fn from_env() -> Result<Self> {
+        let config = Self {
+            title: config::var_or("SITE_TITLE", "Blog"),
+            description: config::var_opt("SITE_DESCRIPTION"),
+            base_url: config::var_or("SITE_BASE_URL", "http://localhost:8000/"),
+            api_url: config::var_or("SITE_API_URL", "http://localhost:3000/"),
+            twitter_handle: config::var_opt("SITE_TWITTER_HANDLE"),
+            page_size: config::parse_or("SITE_PAGE_SIZE", 10)?,
+            templates_dir: config::var_opt("SITE_TEMPLATES_DIR").map(PathBuf::from),
+        };
+        config.validated()
+    }
+
+    /// Checks the configuration, adding the trailing `/` the base and API URLs may lack.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the base or API URL is not an absolute HTTP(S) URL or the
+    /// page size is zero.
+    pub fn validated(mut self) -> Result<Self> {
+        for (name, url) in [("base", &mut self.base_url), ("API", &mut self.api_url)] {
+            if !url.starts_with("http://") && !url.starts_with("https://") {
+                bail!("The site {name} URL must start with http:// or https://: {url:?}");
+            }
+            if !url.ends_with('/') {
+                url.push('/');
+            }
+        }
+        if self.page_size == 0 {
+            bail!("The site page size must be at least 1");
+        }
+        Ok(self)
+    }
+}
+
+/// What a build did.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct BuildReport {
+    /// Number of files written because they were new or their sources changed.
+    pub written: usize,
+    /// Number of files left as they were.
+    pub unchanged: usize,
+    /// Number of files of the previous build removed because nothing produces them
+    /// anymore, e.g. for unpublished posts.
+    pub removed: usize,
+}
+
+/// The fingerprint of the sources of each file of a build, by path relative to the
+/// output directory.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Manifest {
+    /// Fingerprints by path.
+    files: BTreeMap<String, String>,
+}
+
+impl Manifest {
+    /// Reads the manifest of the previous build, or an empty one if there is none.
+    fn read(path: &Path) -> Result<Self> {
+        match fs::read(path) {
+            Ok(bytes) => serde_json::from_slice(&bytes)
+                .with_context(|| format!("Invalid site manifest {}", path.display())),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(err) => Err(err).with_context(|| format!("Failed to read {}", path.display())),
+        }
+    }
+}
+
+/// Returns the part of a fingerprint contributed by posts: their IDs and update times.
+fn post_sources<'a>(posts: impl IntoIterator<Item = &'a BlogPost>) -> String {
+    let mut sources = String::new();
+    for post in posts {
+        let updated_at = post.updated_at.or(post.created_at).unwrap_or_default();
+        // Writing to a `String` cannot fail.
+        let _ = writeln!(sources, "{} {}", post.id, updated_at.timestamp_micros());
+    }
+    sources
+}
+
+/// Writes the files of one build, skipping those whose sources did not change.
+struct Builder<'a> {
+    /// Directory the site is written to.
+    output: &'a Path,
+    /// Configuration of the site.
+    config: &'a SiteConfig,
+    /// Templates of the site.
+    templates: Templates,
+    /// Hash of everything every file depends on: the configuration and templates.
+    build_key: String,
+    /// Manifest of the previous build.
+    previous: Manifest,
+    /// Manifest of this build.
+    next: Manifest,
+    /// What the build did so far.
+    report: BuildReport,
+}
+
+impl Builder<'_> {
+    /// Writes the file at `path`, relative to the output directory, unless the previous
+    /// build wrote it from the same `sources` and it is still there.
+    fn file(
+        &mut self,
+        path: &str,
+        sources: &str,
+        render: impl FnOnce(&Self) -> Result<String>,
+    ) -> Result<()> {
+        let fingerprint = hex::encode(Sha256::digest(format!(
+            "{}\n{path}\n{sources}",
+            self.build_key
+        )));
+        let target = self.output.join(path);
+        if self.previous.files.get(path) == Some(&fingerprint) && target.is_file() {
+            self.report.unchanged += 1;
+        } else {
+            let contents = render(self)?;
+            if let Some(parent) = target.parent() {
+                fs::create_dir_all(parent)
+                    .with_context(|| format!("Failed to create {}", parent.display()))?;
+            }
+            fs::write(&target, contents)
+                .with_context(|| format!("Failed to write {}", target.display()))?;
+            self.report.written += 1;
+        }
+        self.next.files.insert(path.to_string(), fingerprint);
+        Ok(())
+    }
+
+    /// Writes the paginated list of `posts` under `dir`, relative to the site root,
+    /// and returns the URL of its first page.
+    fn list(
+        &mut self,
+        kind: &str,
+        dir: &str,
+        heading: Option<&str>,
+        posts: &[&BlogPost],
+    ) -> Result<String> {
+        let page_url = |page: usize| match page {
+            1 => format!("{}{dir}", self.config.base_url),
+            _ => format!("{}{dir}page/{page}/", self.config.base_url),
+        };
+        let pages: Vec<&[&BlogPost]> = if posts.is_empty() {
+            vec![&[]]
+        } else {
+            posts.chunks(self.config.page_size).collect()
+        };
+        let count = pages.len();
+        let first_url = page_url(1);
+        for (index, page_posts) in pages.into_iter().enumerate() {
+            let page = index + 1;
+            let path = match page {
+                1 => format!("{dir}index.html"),
+                _ => format!("{dir}page/{page}/index.html"),
+            };
+            let pagination = Pagination {
+                page,
+                pages: count,
+                prev_url: (page > 1).then(|| page_url(page - 1)),
+                next_url: (page < count).then(|| page_url(page + 1)),
+            };
+            let sources = format!(
+                "{kind}\n{heading:?}\n{page}/{count}\n{}",
+                post_sources(page_posts.iter().copied())
+            );
+            self.file(&path, &sources, |builder| {
+                let posts: Vec<PostView> = page_posts
+                    .iter()
+                    .map(|post| PostView::new(post, builder.config))
+                    .collect();
+                builder.templates.render(
+                    "list.html",
+                    context! {
+                        site => SiteView::from(builder.config),
+                        kind,
+                        heading,
+                        posts,
+                        pagination,
+                    },
+                )
+            })?;
+        }
+        Ok(first_url)
+    }
+
+    /// Removes the files of the previous build this build did not write, and the
+    /// directories left empty, then records this build.
+    fn finish(mut self) -> Result<BuildReport> {
+        for path in self.previous.files.keys() {
+            if self.next.files.contains_key(path) {
+                continue;
+            }
+            let target = self.output.join(path);
+            match fs::remove_file(&target) {
+                Ok(()) => self.report.removed += 1,
+                Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+                Err(err) => {
+                    return Err(err)
+                        .with_context(|| format!("Failed to remove {}", target.display()));
+                }
+            }
+            // Only empty directories can be removed, so this stops at the first
+            // directory still holding files.
+            for dir in target.ancestors().skip(1) {
+                if dir == self.output || fs::remove_dir(dir).is_err() {
+                    break;
+                }
+            }
+        }
+        let manifest = serde_json::to_vec_pretty(&self.next)?;
+        let path = self.output.join(MANIFEST_FILE);
+        fs::write(&path, manifest)
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+        Ok(self.report)
+    }
+}
+
+/// Groups posts by the directory names of their categories or tags, keeping the
+/// first name seen for each, so names that slugify alike share a page.
+fn group_by_term<'a>(
+    posts: &[&'a BlogPost],
+    terms: impl Fn(&'a BlogPost) -> Vec<&'a String>,
+) -> BTreeMap<String, (&'a str, Vec<&'a BlogPost>)> {
+    let mut groups: BTreeMap<String, (&str, Vec<&BlogPost>)> = BTreeMap::new();
+    for &post in posts {
+        let mut seen = HashSet::new();
+        for term in terms(post) {
+            let slug = render::term_slug(term);
+            if seen.insert(slug.clone()) {
+                groups
+                    .entry(slug)
+                    .or_insert((term, Vec::new()))
+                    .1
+                    .push(post);
+            }
+        }
+    }
+    groups
+}
+
+/// Renders the posts published at `now` as a static website in `output`.
+///
+/// Writes a page per post under `posts/<slug>/`, the paginated index at the root and
+/// under `page/<n>/`, paginated category and tag pages under `categories/<name>/` and
+/// `tags/<name>/`, Atom and RSS feeds of the newest posts (`atom.xml`, `rss.xml`) and
+/// a `sitemap.xml`. Posts are listed newest first.
+///
+/// A manifest of what each file was built from, the update times of its posts plus the
+/// configuration and templates, is kept in the output directory. Files whose sources
+/// did not change since the last build are not rewritten, unless `force` is set, and
+/// files of the last build that are no longer produced are removed. Other files in the
+/// output directory, such as stylesheets, are left alone.
+///
+/// # Errors
+///
+/// Returns an error if a template fails or a file cannot be read or written.
+pub fn build(
+    posts: &[BlogPost],
+    config: &SiteConfig,
+    output: &Path,
+    now: DateTime<Utc>,
+    force: bool,
+) -> Result<BuildReport> {
+    let templates = Templates::load(config.templates_dir.as_deref())?;
+    let build_key = hex::encode(Sha256::digest(format!(
+        "{}\n{}\n{:?}\n{}\n{}\n{}",
+        env!("CARGO_PKG_VERSION"),
+        config.title,
+        config.description,
+        config.base_url,
+        config.page_size,
+        templates.fingerprint
+    )));
+    let previous = if force {
+        Manifest::default()
+    } else {
+        Manifest::read(&output.join(MANIFEST_FILE))?
+    };
+    fs::create_dir_all(output).with_context(|| format!("Failed to create {}", output.display()))?;
+
+    let mut published: Vec<&BlogPost> = posts
+        .iter()
+        .filter(|post| post.is_published_at(now))
+        .collect();
+    published.sort_by(|a, b| {
+        render::post_date(b)
+            .cmp(&render::post_date(a))
+            .then(b.id.cmp(&a.id))
+    });
+
+    let mut builder = Builder {
+        output,
+        config,
+        templates,
+        build_key,
+        previous,
+        next: Manifest::default(),
+        report: BuildReport::default(),
+    };
+    let mut sitemap = Vec::new();
+
+    for &post in &published {
+        let dir = render::post_dir(post);
+        builder.file(
+            &format!("{dir}index.html"),
+            &post_sources([post]),
+            |builder| {
+                let content = render::markdown_to_html(&post.content);
+                builder.templates.render(
+                    "post.html",
+                    context! {
+                        site => SiteView::from(builder.config),
+                        post => PostView::new(post, builder.config),
+                        content => Value::from_safe_string(content),
+                    },
+                )
+            },
+        )?;
+        sitemap.push((
+            format!("{}{dir}", config.base_url),
+            post.updated_at.or(post.created_at),
+        ));
+    }
+
+    let last_modified = published
+        .iter()
+        .filter_map(|post| post.updated_at.or(post.created_at))
+        .max();
+    let url = builder.list("index", "", None, &published)?;
+    sitemap.push((url, last_modified));
+    for (kind, dir, terms) in [
+        (
+            "category",
+            "categories",
+            group_by_term(&published, |post| vec![&post.category]),
+        ),
+        (
+            "tag",
+            "tags",
+            group_by_term(&published, |post| post.tags.iter().collect()),
+        ),
+    ] {
+        for (slug, (name, posts)) in terms {
+            let url = builder.list(kind, &format!("{dir}/{slug}/"), Some(name), &posts)?;
+            let last_modified = posts
+                .iter()
+                .filter_map(|post| post.updated_at.or(post.created_at))
+                .max();
+            sitemap.push((url, last_modified));
+        }
+    }
+
+    let newest = &published[..published.len().min(FEED_SIZE)];
+    let feed_sources = post_sources(newest.iter().copied());
+    let feed_posts = || -> Vec<(&BlogPost, String)> {
+        newest
+            .iter()
+            .map(|&post| (post, render::markdown_to_html(&post.content)))
+            .collect()
+    };
+    builder.file("atom.xml", &feed_sources, |builder| {
+        Ok(feed::atom(builder.config, &feed_posts()))
+    })?;
+    builder.file("rss.xml", &feed_sources, |builder| {
+        Ok(feed::rss(builder.config, &feed_posts()))
+    })?;
+    let sitemap_sources = sitemap
+        .iter()
+        .map(|(url, modified)| format!("{url} {modified:?}\n"))
+        .collect::<String>();
+    builder.file("sitemap.xml", &sitemap_sources, |_| {
+        Ok(feed::sitemap(&sitemap))
+    })?;
+
+    builder.finish()
+}
@@ -0,0 +1,199 @@
+use super::SiteConfig;
+use crate::model::blog::{BlogPost, slugify};
+use anyhow::{Context, Result};
+use chrono::{DateTime, SecondsFormat, Utc};
+use minijinja::{AutoEscape, Environment, Error, Output, State, Value, escape_formatter};
+use pulldown_cmark::{Options, Parser, html};
+use quick_xml::escape::escape;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::{fs, path::Path};
+
+/// Names of the templates, with their built-in sources.
+///
+/// `post.html` renders a post and `list.html` a page of the index, a category or a tag;
+/// both extend `base.html`.
+const TEMPLATES: [(&str, &str); 3] = [
+    ("base.html", include_str!("../../templates/site/base.html")),
+    ("post.html", include_str!("../../templates/site/post.html")),
+    ("list.html", include_str!("../../templates/site/list.html")),
+];
+
+/// The templates of a site.
+pub struct Templates {
+    /// The compiled templates.
+    env: Environment<'static>,
+    /// Hash of the template sources, so that pages are rebuilt when a template changes.
+    pub fingerprint: String,
+}
+
+impl Templates {
+    /// Loads the templates, reading each one from `dir` if it holds a file of that name
+    /// and using the built-in template otherwise.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a template file cannot be read or has a syntax error.
+    pub fn load(dir: Option<&Path>) -> Result<Self> {
+        let mut env = Environment::new();
+        env.set_keep_trailing_newline(true);
+        env.set_formatter(format_value);
+        let mut hasher = Sha256::new();
+        for (name, builtin) in TEMPLATES {
+            let path = dir.map(|dir| dir.join(name)).filter(|path| path.is_file());
+            let source = match path {
+                Some(path) => fs::read_to_string(&path)
+                    .with_context(|| format!("Failed to read template {}", path.display()))?,
+                None => builtin.to_string(),
+            };
+            hasher.update(name.as_bytes());
+            hasher.update(source.as_bytes());
+            env.add_template_owned(name, source)
+                .with_context(|| format!("Invalid template {name}"))?;
+        }
+        Ok(Self {
+            env,
+            fingerprint: hex::encode(hasher.finalize()),
+        })
+    }
+
+    /// Renders a template.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the template fails, e.g. on an undefined filter.
+    pub fn render(&self, name: &str, context: Value) -> Result<String> {
+        self.env
+            .get_template(name)
+            .and_then(|template| template.render(context))
+            .with_context(|| format!("Failed to render {name}"))
+    }
+}
+
+/// Writes a value into a template, escaping strings like minijinja does by default
+/// except for `/`, so that URLs stay readable in the generated HTML.
+fn format_value(out: &mut Output<'_>, state: &State<'_, '_>, value: &Value) -> Result<(), Error> {
+    match value.as_str() {
+        Some(text) if state.auto_escape() == AutoEscape::Html && !value.is_safe() => {
+            out.write_str(&escape(text)).map_err(Error::from)
+        }
+        _ => escape_formatter(out, state, value),
+    }
+}
+
+/// Renders Markdown content to HTML.
+pub fn markdown_to_html(markdown: &str) -> String {
+    let options = Options::ENABLE_TABLES
+        | Options::ENABLE_FOOTNOTES
+        | Options::ENABLE_STRIKETHROUGH
+        | Options::ENABLE_TASKLISTS;
+    let mut rendered = String::new();
+    html::push_html(&mut rendered, Parser::new_ext(markdown, options));
+    rendered
+}
+
+/// Returns the directory name of a category or tag: its slug, or the hex encoding of
+/// its name if it has no ASCII letters or digits.
+pub fn term_slug(name: &str) -> String {
+    slugify(name).unwrap_or_else(|| hex::encode(name))
+}
+
+/// Returns the directory a post is written to, relative to the site root.
+pub fn post_dir(post: &BlogPost) -> String {
+    match &post.slug {
+        Some(slug) => format!("posts/{slug}/"),
+        None => format!("posts/post-{}/", post.id),
+    }
+}
+
+/// Returns the date a post is shown with: its publication date, or else its creation
+/// date.
+pub fn post_date(post: &BlogPost) -> Option<DateTime<Utc>> {
+    post.published_at.or(post.created_at)
+}
+
+/// The site, as seen by templates.
+#[derive(Debug, Serialize)]
+pub struct SiteView<'a> {
+    /// Title of the site.
+    pub title: &'a str,
+    /// Description of the site.
+    pub description: Option<&'a str>,
+    /// Absolute URL of the site root, ending with `/`.
+    pub base_url: &'a str,
+}
+
+impl<'a> From<&'a SiteConfig> for SiteView<'a> {
+    fn from(config: &'a SiteConfig) -> Self {
+        Self {
+            title: &config.title,
+            description: config.description.as_deref(),
+            base_url: &config.base_url,
+        }
+    }
+}
+
+/// A category or tag, as seen by templates.
+#[derive(Debug, Serialize)]
+pub struct TermView {
+    /// Name of the category or tag.
+    pub name: String,
+    /// Absolute URL of its first page.
+    pub url: String,
+}
+
+/// A post, as seen by templates; the rendered content is passed separately.
+#[derive(Debug, Serialize)]
+pub struct PostView {
+    /// Title of the post.
+    pub title: String,
+    /// Absolute URL of the post.
+    pub url: String,
+    /// Author of the post.
+    pub author: Option<String>,
+    /// Category of the post.
+    pub category: TermView,
+    /// Tags of the post.
+    pub tags: Vec<TermView>,
+    /// Date of the post, in RFC 3339.
+    pub date: String,
+    /// Date of the post, for display, e.g. `March 1, 2025`.
+    pub date_display: String,
+}
+
+impl PostView {
+    /// Builds the view of a post.
+    pub fn new(post: &BlogPost, config: &SiteConfig) -> Self {
+        let term = |kind: &str, name: &str| TermView {
+            name: name.to_string(),
+            url: format!("{}{kind}/{}/", config.base_url, term_slug(name)),
+        };
+        let date = post_date(post);
+        Self {
+            title: post.title.clone(),
+            url: format!("{}{}", config.base_url, post_dir(post)),
+            author: post.author.clone(),
+            category: term("categories", &post.category),
+            tags: post.tags.iter().map(|tag| term("tags", tag)).collect(),
+            date: date
+                .map(|date| date.to_rfc3339_opts(SecondsFormat::Secs, true))
+                .unwrap_or_default(),
+            date_display: date
+                .map(|date| date.format("%B %-d, %Y").to_string())
+                .unwrap_or_default(),
+        }
+    }
+}
+
+/// Links between the pages of a paginated list, as seen by templates.
+#[derive(Debug, Serialize)]
+pub struct Pagination {
+    /// Number of this page, starting at 1.
+    pub page: usize,
+    /// Number of pages.
+    pub pages: usize,
+    /// Absolute URL of the previous page, with newer posts.
+    pub prev_url: Option<String>,
+    /// Absolute URL of the next page, with older posts.
+    pub next_url: Option<String>,
+}
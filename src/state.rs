@@ -1,11 +1,68 @@
+use crate::{
+    collab::Collaboration,
+    database::replica::Replica,
+    events::PostEvents,
+    idempotency::Idempotency,
+    jobs::Jobs,
+    media::Media,
+    repository::DynPostRepository,
+    server::middleware::{
+        admin::AdminConfig, http::HttpConfig, rate_limit::RateLimiter, versioning::VersioningConfig,
+    },
+    site::SiteConfig,
+    webhook::Webhooks,
+};
 use axum::extract::FromRef;
+use std::sync::Arc;
 
 /// Represents the shared application state.
 ///
 /// This struct is used to manage and share resources across the application,
-/// such as the database connection pool.
+/// such as the database connection pools, the blog post repository, uploaded media,
+/// idempotency keys, webhooks, live events, collaboration on posts, background jobs, the rate limiter, HTTP, API versioning, site and admin settings.
 #[derive(Clone, FromRef)]
 pub struct AppState {
-    /// A connection pool for interacting with the PostgreSQL database.
-    pub pool: sqlx::PgPool,
+    /// A connection pool for interacting with the PostgreSQL database, or `None` when
+    /// another storage backend is in use.
+    pub pool: Option<sqlx::PgPool>,
+
+    /// Read replica of the PostgreSQL database, if one is configured.
+    pub replica: Option<Replica>,
+
+    /// Storage for blog posts, used by the post handlers.
+    pub posts: DynPostRepository,
+
+    /// Records and content of uploaded media files, used by the media handlers.
+    pub media: Media,
+
+    /// Storage for idempotency keys, used by the idempotency middleware.
+    pub idempotency: Idempotency,
+
+    /// Webhooks notified of changes to blog posts, used by the post and webhook handlers.
+    pub webhooks: Webhooks,
+
+    /// Live stream of changes to blog posts, used by the event stream handler.
+    pub events: PostEvents,
+
+    /// Presence and edit locks of the posts being edited, used by the collaboration and
+    /// update handlers.
+    pub collab: Collaboration,
+
+    /// Queue of background jobs, used by the job handlers.
+    pub jobs: Jobs,
+
+    /// Per-client rate limiter shared by all routes.
+    pub rate_limiter: RateLimiter,
+
+    /// CORS, compression, body limit and timeout settings.
+    pub http: Arc<HttpConfig>,
+
+    /// Default API version and deprecation notices.
+    pub versioning: Arc<VersioningConfig>,
+
+    /// Title and URLs of the site, used in the metadata of posts.
+    pub site: Arc<SiteConfig>,
+
+    /// Token of the administration endpoints, checked by the admin middleware.
+    pub admin: Arc<AdminConfig>,
 }
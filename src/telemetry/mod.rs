@@ -0,0 +1,149 @@
+use crate::config;
+use anyhow::{Context, Result, bail};
+use opentelemetry::{global, trace::TracerProvider as _};
+use opentelemetry_sdk::{Resource, propagation::TraceContextPropagator, trace::SdkTracerProvider};
+use std::str::FromStr;
+use tracing_subscriber::{
+    EnvFilter, fmt::format::FmtSpan, layer::SubscriberExt, util::SubscriberInitExt,
+};
+
+/// Request ID generation, propagation and task-local access.
+pub mod request_id;
+
+/// Span construction for HTTP requests and database queries.
+pub mod span;
+
+/// Output format for log lines written to stdout.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogFormat {
+    /// Human-readable, single-line text output.
+    Text,
+    /// One JSON object per line, suitable for log pipelines.
+    Json,
+}
+
+impl FromStr for LogFormat {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.to_ascii_lowercase().as_str() {
+            "text" | "pretty" => Ok(Self::Text),
+            "json" => Ok(Self::Json),
+            other => Err(format!(
+                "unknown log format `{other}`, expected `text` or `json`"
+            )),
+        }
+    }
+}
+
+/// Tracing configuration read from the environment.
+///
+/// | Variable                      | Default    | Description                                   |
+/// |-------------------------------|------------|-----------------------------------------------|
+/// | `RUST_LOG`                    | `debug`    | Log filter directives.                        |
+/// | `LOG_FORMAT`                  | `text`     | `text` or `json`.                             |
+/// | `OTEL_SERVICE_NAME`           | `blog-api` | Service name reported to the collector.       |
+/// | `OTEL_EXPORTER_OTLP_ENDPOINT` | unset      | Enables the OTLP/HTTP exporter when set.      |
+#[derive(Debug, Clone)]
+pub struct TelemetryConfig {
+    /// Format used for log lines.
+    pub log_format: LogFormat,
+    /// Service name attached to exported spans.
+    pub service_name: String,
+    /// Whether spans are exported over OTLP.
+    pub otlp_enabled: bool,
+}
+
+impl TelemetryConfig {
+    /// Builds the configuration from environment variables.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `LOG_FORMAT` holds an unknown value.
+    pub fn from_env() -> Result<Self> {
+        Ok(Self {
+            log_format: config::parse_or("LOG_FORMAT", LogFormat::Text)?,
+            service_name: config::var_or("OTEL_SERVICE_NAME", "blog-api"),
+            otlp_enabled: config::var_opt("OTEL_EXPORTER_OTLP_ENDPOINT").is_some()
+                || config::var_opt("OTEL_EXPORTER_OTLP_TRACES_ENDPOINT").is_some(),
+        })
+    }
+}
+
+/// Keeps the tracer provider alive and flushes pending spans when dropped.
+///
+/// Hold on to this value for the lifetime of the process.
+pub struct TelemetryGuard {
+    /// The tracer provider backing the OpenTelemetry layer.
+    provider: SdkTracerProvider,
+}
+
+impl Drop for TelemetryGuard {
+    fn drop(&mut self) {
+        if let Err(err) = self.provider.shutdown() {
+            tracing::warn!("Failed to shut down tracer provider: {err}");
+        }
+    }
+}
+
+/// Installs the global tracing subscriber.
+///
+/// The subscriber writes logs in the configured format, bridges spans into
+/// OpenTelemetry, and registers the W3C `traceparent` propagator so incoming
+/// trace contexts are continued. When an OTLP endpoint is configured, spans are
+/// batched and exported over OTLP/HTTP.
+///
+/// # Errors
+///
+/// Returns an error if the configuration is invalid, the OTLP exporter cannot
+/// be built, or a global subscriber is already installed.
+pub fn init_tracing(config: &TelemetryConfig) -> Result<TelemetryGuard> {
+    global::set_text_map_propagator(TraceContextPropagator::new());
+
+    let mut provider = SdkTracerProvider::builder().with_resource(
+        Resource::builder()
+            .with_service_name(config.service_name.clone())
+            .build(),
+    );
+    if config.otlp_enabled {
+        let exporter = opentelemetry_otlp::SpanExporter::builder()
+            .with_http()
+            .build()
+            .context("Failed to build OTLP span exporter")?;
+        provider = provider.with_batch_exporter(exporter);
+    }
+    let provider = provider.build();
+    global::set_tracer_provider(provider.clone());
+
+    let filter = EnvFilter::try_from_default_env().or_else(|_| EnvFilter::try_new("debug"))?;
+    let otel = tracing_opentelemetry::layer().with_tracer(provider.tracer("blog-api"));
+    let (text, json) = match config.log_format {
+        LogFormat::Text => (
+            Some(tracing_subscriber::fmt::layer().with_span_events(FmtSpan::CLOSE)),
+            None,
+        ),
+        LogFormat::Json => (
+            None,
+            Some(
+                tracing_subscriber::fmt::layer()
+                    .json()
+                    .with_current_span(true)
+                    .with_span_list(false)
+                    .with_span_events(FmtSpan::CLOSE),
+            ),
+        ),
+    };
+
+    if tracing_subscriber::registry()
+        .with(filter)
+        .with(text)
+        .with(json)
+        .with(otel)
+        .try_init()
+        .is_err()
+    {
+        bail!("A global tracing subscriber is already installed");
+    }
+
+    Ok(TelemetryGuard { provider })
+}
@@ -0,0 +1,36 @@
+use axum::{
+    extract::Request,
+    http::{HeaderName, HeaderValue},
+    middleware::Next,
+    response::Response,
+};
+
+/// Header used to carry the request ID in both directions.
+pub const X_REQUEST_ID: HeaderName = HeaderName::from_static("x-request-id");
+
+tokio::task_local! {
+    /// The ID of the request currently being handled by this task.
+    static REQUEST_ID: String;
+}
+
+/// Returns the ID of the request being handled by the current task, if any.
+pub fn current() -> Option<String> {
+    REQUEST_ID.try_with(Clone::clone).ok()
+}
+
+/// Reads the request ID from a header value, ignoring non-ASCII values.
+pub fn from_header(value: Option<&HeaderValue>) -> &str {
+    value
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default()
+}
+
+/// Middleware that exposes the request's `X-Request-Id` to the rest of the task.
+///
+/// Must run inside `SetRequestIdLayer` so the header is always present. Code
+/// that has no access to the request, such as `AppError::into_response`, can
+/// then read it through [`current`].
+pub async fn scope(request: Request, next: Next) -> Response {
+    let id = from_header(request.headers().get(X_REQUEST_ID)).to_string();
+    REQUEST_ID.scope(id, next.run(request)).await
+}
@@ -0,0 +1,114 @@
+use super::request_id::{self, X_REQUEST_ID};
+use axum::{extract::MatchedPath, http::Request, response::Response};
+use futures_util::{Stream, StreamExt, stream::BoxStream};
+use opentelemetry::global;
+use opentelemetry_http::HeaderExtractor;
+use std::{
+    pin::Pin,
+    task::{Context, Poll},
+    time::Duration,
+};
+use tracing::{Span, field::Empty};
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+/// Creates the root span for an incoming HTTP request.
+///
+/// The span records the request ID and continues the trace described by an
+/// incoming W3C `traceparent` header, if one is present.
+pub fn make_http_span<B>(request: &Request<B>) -> Span {
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map_or_else(|| request.uri().path(), MatchedPath::as_str);
+    let span = tracing::info_span!(
+        "http.request",
+        otel.name = %format!("{} {}", request.method(), route),
+        otel.kind = "server",
+        http.request.method = %request.method(),
+        http.route = %route,
+        url.path = %request.uri().path(),
+        request_id = %request_id::from_header(request.headers().get(X_REQUEST_ID)),
+        http.response.status_code = Empty,
+        otel.status_code = Empty,
+    );
+
+    let parent = global::get_text_map_propagator(|propagator| {
+        propagator.extract(&HeaderExtractor(request.headers()))
+    });
+    if let Err(err) = span.set_parent(parent) {
+        tracing::debug!("Failed to attach trace parent: {err}");
+    }
+    span
+}
+
+/// Records the response status on the request span and logs completion.
+pub fn on_http_response<B>(response: &Response<B>, latency: Duration, span: &Span) {
+    let status = response.status();
+    span.record("http.response.status_code", status.as_u16());
+    if status.is_server_error() {
+        span.record("otel.status_code", "ERROR");
+    }
+    tracing::info!(
+        status = status.as_u16(),
+        latency_ms = latency.as_millis(),
+        "finished processing request"
+    );
+}
+
+/// Creates a client span for a single database query.
+///
+/// `system` names the database, e.g. `postgresql` or `sqlite`. Wrap query futures
+/// with this span using `tracing::Instrument` so that the query's duration is timed
+/// and SQLx's statement logs are nested beneath it.
+pub fn db_query(system: &'static str, operation: &'static str) -> Span {
+    db_query_on(system, operation, "blog_posts")
+}
+
+/// Creates a client span for a single database query on `collection`, for queries on
+/// tables other than `blog_posts`; see [`db_query`].
+pub fn db_query_on(
+    system: &'static str,
+    operation: &'static str,
+    collection: &'static str,
+) -> Span {
+    tracing::info_span!(
+        "db.query",
+        otel.name = %format!("{operation} {collection}"),
+        otel.kind = "client",
+        db.system = system,
+        db.operation.name = operation,
+        db.collection.name = collection,
+    )
+}
+
+/// A stream polled inside a span; see [`instrument_stream`].
+struct InstrumentedStream<T> {
+    /// The wrapped stream.
+    inner: BoxStream<'static, T>,
+    /// Span entered while the stream is polled, closed when the stream is dropped.
+    span: Span,
+}
+
+impl<T> Stream for InstrumentedStream<T> {
+    type Item = T;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<T>> {
+        let this = &mut *self;
+        let _entered = this.span.enter();
+        this.inner.poll_next_unpin(cx)
+    }
+}
+
+/// Runs every poll of a stream inside `span`, e.g. a [`db_query`] span around a query
+/// whose rows are streamed. The span lasts until the stream is dropped, so it times
+/// the whole stream.
+pub fn instrument_stream<T: Send + 'static>(
+    stream: BoxStream<'static, T>,
+    span: Span,
+) -> BoxStream<'static, T> {
+    InstrumentedStream {
+        inner: stream,
+        span,
+    }
+    .boxed()
+}
@@ -0,0 +1,257 @@
+use super::{DueDelivery, WebhookRepository};
+use crate::{
+    error::AppError,
+    model::webhook::{
+        DeliveryAttempt, DeliveryFilter, DeliveryStatus, Webhook, WebhookBody, WebhookDelivery,
+        WebhookEvent,
+    },
+};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use std::{
+    collections::BTreeMap,
+    sync::{Mutex, MutexGuard, PoisonError},
+};
+
+/// A webhook and the secret signing its requests.
+#[derive(Debug)]
+struct Subscription {
+    /// The webhook as returned to clients.
+    webhook: Webhook,
+    /// Secret signing the requests.
+    secret: String,
+}
+
+/// The webhooks and deliveries of an [`InMemoryWebhookRepository`].
+#[derive(Debug, Default)]
+struct Store {
+    /// Webhooks by ID.
+    webhooks: BTreeMap<i32, Subscription>,
+    /// Deliveries by ID.
+    deliveries: BTreeMap<i32, WebhookDelivery>,
+    /// ID of the most recently created webhook.
+    last_id: i32,
+    /// ID of the most recently recorded delivery.
+    last_delivery_id: i32,
+}
+
+impl Store {
+    /// Records a pending delivery, due now, and returns it.
+    fn add_delivery(
+        &mut self,
+        webhook_id: i32,
+        event: WebhookEvent,
+        payload: serde_json::Value,
+        now: DateTime<Utc>,
+    ) -> Result<WebhookDelivery, AppError> {
+        let id = self
+            .last_delivery_id
+            .checked_add(1)
+            .ok_or(AppError::InternalServerError)?;
+        let delivery = WebhookDelivery {
+            id,
+            webhook_id,
+            event,
+            payload,
+            status: DeliveryStatus::Pending,
+            attempts: 0,
+            next_attempt_at: Some(now),
+            last_attempt_at: None,
+            response_status: None,
+            error: None,
+            created_at: now,
+            delivered_at: None,
+        };
+        self.last_delivery_id = id;
+        self.deliveries.insert(id, delivery.clone());
+        Ok(delivery)
+    }
+}
+
+/// [`WebhookRepository`] keeping webhooks and deliveries in memory. They are lost when
+/// the repository is dropped.
+#[derive(Debug, Default)]
+pub struct InMemoryWebhookRepository {
+    /// The stored webhooks and deliveries.
+    inner: Mutex<Store>,
+}
+
+impl InMemoryWebhookRepository {
+    /// Creates an empty repository.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Locks the store, recovering it if a previous holder panicked.
+    fn store(&self) -> MutexGuard<'_, Store> {
+        self.inner.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+#[async_trait]
+impl WebhookRepository for InMemoryWebhookRepository {
+    async fn create(&self, webhook: &WebhookBody) -> Result<Webhook, AppError> {
+        let mut store = self.store();
+        let id = store
+            .last_id
+            .checked_add(1)
+            .ok_or(AppError::InternalServerError)?;
+        let created = Webhook {
+            id,
+            url: webhook.url.clone(),
+            events: webhook.events.clone(),
+            created_at: Utc::now(),
+        };
+        store.last_id = id;
+        store.webhooks.insert(
+            id,
+            Subscription {
+                webhook: created.clone(),
+                secret: webhook.secret.clone(),
+            },
+        );
+        Ok(created)
+    }
+
+    async fn get(&self, id: i32) -> Result<Option<Webhook>, AppError> {
+        Ok(self
+            .store()
+            .webhooks
+            .get(&id)
+            .map(|subscription| subscription.webhook.clone()))
+    }
+
+    async fn list(&self) -> Result<Vec<Webhook>, AppError> {
+        Ok(self
+            .store()
+            .webhooks
+            .values()
+            .map(|subscription| subscription.webhook.clone())
+            .collect())
+    }
+
+    async fn update(&self, id: i32, webhook: &WebhookBody) -> Result<Option<Webhook>, AppError> {
+        let mut store = self.store();
+        Ok(store.webhooks.get_mut(&id).map(|subscription| {
+            subscription.webhook.url = webhook.url.clone();
+            subscription.webhook.events = webhook.events.clone();
+            subscription.secret = webhook.secret.clone();
+            subscription.webhook.clone()
+        }))
+    }
+
+    async fn delete(&self, id: i32) -> Result<bool, AppError> {
+        let mut store = self.store();
+        if store.webhooks.remove(&id).is_none() {
+            return Ok(false);
+        }
+        store
+            .deliveries
+            .retain(|_, delivery| delivery.webhook_id != id);
+        Ok(true)
+    }
+
+    async fn enqueue(
+        &self,
+        event: WebhookEvent,
+        payload: &serde_json::Value,
+    ) -> Result<Vec<i32>, AppError> {
+        let mut store = self.store();
+        let subscribed: Vec<i32> = store
+            .webhooks
+            .values()
+            .filter(|subscription| subscription.webhook.subscribes_to(event))
+            .map(|subscription| subscription.webhook.id)
+            .collect();
+        let now = Utc::now();
+        subscribed
+            .into_iter()
+            .map(|webhook_id| {
+                store
+                    .add_delivery(webhook_id, event, payload.clone(), now)
+                    .map(|delivery| delivery.id)
+            })
+            .collect()
+    }
+
+    async fn deliveries(
+        &self,
+        webhook_id: i32,
+        filter: &DeliveryFilter,
+    ) -> Result<Vec<WebhookDelivery>, AppError> {
+        Ok(self
+            .store()
+            .deliveries
+            .values()
+            .rev()
+            .filter(|delivery| delivery.webhook_id == webhook_id)
+            .filter(|delivery| filter.status.is_none_or(|status| delivery.status == status))
+            .cloned()
+            .collect())
+    }
+
+    async fn redeliver(
+        &self,
+        webhook_id: i32,
+        delivery_id: i32,
+    ) -> Result<Option<WebhookDelivery>, AppError> {
+        let mut store = self.store();
+        let Some(delivery) = store
+            .deliveries
+            .get(&delivery_id)
+            .filter(|delivery| delivery.webhook_id == webhook_id)
+        else {
+            return Ok(None);
+        };
+        let (event, payload) = (delivery.event, delivery.payload.clone());
+        store
+            .add_delivery(webhook_id, event, payload, Utc::now())
+            .map(Some)
+    }
+
+    async fn pending_delivery(&self, id: i32) -> Result<Option<DueDelivery>, AppError> {
+        let store = self.store();
+        let Some(delivery) = store
+            .deliveries
+            .get(&id)
+            .filter(|delivery| delivery.status == DeliveryStatus::Pending)
+        else {
+            return Ok(None);
+        };
+        Ok(store
+            .webhooks
+            .get(&delivery.webhook_id)
+            .map(|subscription| DueDelivery {
+                delivery: delivery.clone(),
+                url: subscription.webhook.url.clone(),
+                secret: subscription.secret.clone(),
+            }))
+    }
+
+    async fn pending_deliveries(&self) -> Result<Vec<i32>, AppError> {
+        Ok(self
+            .store()
+            .deliveries
+            .values()
+            .filter(|delivery| delivery.status == DeliveryStatus::Pending)
+            .map(|delivery| delivery.id)
+            .collect())
+    }
+
+    async fn record_attempt(&self, id: i32, attempt: &DeliveryAttempt) -> Result<(), AppError> {
+        let mut store = self.store();
+        if let Some(delivery) = store.deliveries.get_mut(&id) {
+            let now = Utc::now();
+            delivery.status = attempt.status;
+            delivery.attempts = delivery.attempts.saturating_add(1);
+            delivery.next_attempt_at = attempt.next_attempt_at;
+            delivery.last_attempt_at = Some(now);
+            delivery.response_status = attempt.response_status;
+            delivery.error = attempt.error.clone();
+            if attempt.status == DeliveryStatus::Succeeded {
+                delivery.delivered_at = Some(now);
+            }
+        }
+        Ok(())
+    }
+}
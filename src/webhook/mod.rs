@@ -0,0 +1,591 @@
+use crate::{
+    config,
+    error::AppError,
+    jobs::{JobHandler, Jobs},
+    model::webhook::{
+        DeliveryAttempt, DeliveryFilter, DeliveryStatus, Webhook, WebhookBody, WebhookDelivery,
+        WebhookEvent, WebhookPayload,
+    },
+    outbox::{DomainEvent, EventConsumer, OutboxEvent},
+};
+use anyhow::{Context, Result, bail};
+use async_trait::async_trait;
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use reqwest::{
+    Client, Url,
+    dns::{Addrs, Name, Resolve, Resolving},
+    header::CONTENT_TYPE,
+    redirect::Policy,
+};
+use sha2::Sha256;
+use std::{
+    net::{IpAddr, SocketAddr},
+    sync::Arc,
+    time::Duration,
+};
+use tokio::net::lookup_host;
+use tracing::Instrument;
+
+/// In-memory storage, for tests and local development without a database.
+pub mod memory;
+
+/// PostgreSQL storage.
+pub mod postgres;
+
+/// SQLite storage.
+pub mod sqlite;
+
+/// Header carrying the name of the event sent.
+pub const EVENT_HEADER: &str = "Webhook-Event";
+
+/// Header carrying the ID of the delivery, which stays the same across retries.
+pub const DELIVERY_HEADER: &str = "Webhook-Delivery";
+
+/// Header carrying the time and signature of a request, as `t={timestamp},v1={signature}`;
+/// see [`signature`].
+pub const SIGNATURE_HEADER: &str = "Webhook-Signature";
+
+/// Queue of the jobs sending deliveries, whose concurrency limit is set in
+/// `JOBS_QUEUE_CONCURRENCY`.
+pub const DELIVERY_QUEUE: &str = "webhooks";
+
+/// Webhook delivery configuration read from the environment.
+///
+/// | Variable                        | Default | Description                                                |
+/// |---------------------------------|---------|------------------------------------------------------------|
+/// | `WEBHOOK_MAX_ATTEMPTS`          | `8`     | Attempts to deliver an event before it is marked failed.   |
+/// | `WEBHOOK_RETRY_BASE_SECS`       | `30`    | Delay before the first retry, doubled after each failure.  |
+/// | `WEBHOOK_RETRY_MAX_SECS`        | `3600`  | Longest delay between two attempts.                        |
+/// | `WEBHOOK_TIMEOUT_SECS`          | `10`    | How long a receiver has to answer.                         |
+/// | `WEBHOOK_ALLOW_PRIVATE_TARGETS` | `false` | Allow URLs of loopback, link-local and private addresses.  |
+///
+/// Unless private targets are allowed, webhooks may only be sent to public addresses;
+/// see [`check_target`]. Deliveries are sent by background jobs on the [`DELIVERY_QUEUE`], so how many are
+/// sent at once is the concurrency limit of that queue.
+#[derive(Debug, Clone)]
+pub struct WebhookConfig {
+    /// Attempts to deliver an event before giving up.
+    pub max_attempts: u32,
+    /// Delay before the first retry.
+    pub retry_base: Duration,
+    /// Longest delay between two attempts.
+    pub retry_max: Duration,
+    /// Timeout of each request to a receiver.
+    pub timeout: Duration,
+    /// Whether webhooks may be sent to loopback, link-local and private addresses, such
+    /// as receivers on the same host in development.
+    pub allow_private_targets: bool,
+}
+
+impl WebhookConfig {
+    /// Builds the configuration from environment variables.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if any of the variables holds an invalid value, or the number of
+    /// attempts is zero.
+    pub fn from_env() -> Result<Self> {
+        let secs = |key: &str, default: u64| -> Result<Duration> {
+            Ok(Duration::from_secs(config::parse_or(key, default)?))
+        };
+        let config = Self {
+            max_attempts: config::parse_or("WEBHOOK_MAX_ATTEMPTS", 8)?,
+            retry_base: secs("WEBHOOK_RETRY_BASE_SECS", 30)?,
+            retry_max: secs("WEBHOOK_RETRY_MAX_SECS", 60 * 60)?,
+            timeout: secs("WEBHOOK_TIMEOUT_SECS", 10)?,
+            allow_private_targets: config::parse_or("WEBHOOK_ALLOW_PRIVATE_TARGETS", false)?,
+        };
+        if config.max_attempts == 0 {
+            bail!("Invalid value for WEBHOOK_MAX_ATTEMPTS: must be at least 1");
+        }
+        Ok(config)
+    }
+
+    /// Returns how long to wait before retrying a delivery after its `attempts`-th
+    /// failed attempt.
+    pub fn retry_delay(&self, attempts: u32) -> Duration {
+        let factor = 2u32.saturating_pow(attempts.saturating_sub(1));
+        self.retry_base.saturating_mul(factor).min(self.retry_max)
+    }
+}
+
+/// A pending delivery, with where to send it.
+#[derive(Debug, Clone)]
+pub struct DueDelivery {
+    /// The delivery, counting the attempts made before this one.
+    pub delivery: WebhookDelivery,
+    /// URL of the webhook.
+    pub url: String,
+    /// Secret of the webhook, signing the request.
+    pub secret: String,
+}
+
+/// Storage for webhooks and the deliveries of events to them.
+#[async_trait]
+pub trait WebhookRepository: Send + Sync {
+    /// Stores a new webhook and returns it with its assigned ID.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `AppError` if the storage fails.
+    async fn create(&self, webhook: &WebhookBody) -> Result<Webhook, AppError>;
+
+    /// Returns the webhook with the given ID, if any.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `AppError` if the storage fails.
+    async fn get(&self, id: i32) -> Result<Option<Webhook>, AppError>;
+
+    /// Returns every webhook, ordered by ID.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `AppError` if the storage fails.
+    async fn list(&self) -> Result<Vec<Webhook>, AppError>;
+
+    /// Replaces the URL, secret and events of a webhook, returning it if it exists.
+    /// Pending deliveries are sent to the new URL, signed with the new secret.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `AppError` if the storage fails.
+    async fn update(&self, id: i32, webhook: &WebhookBody) -> Result<Option<Webhook>, AppError>;
+
+    /// Deletes a webhook with its deliveries, returning whether it existed.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `AppError` if the storage fails.
+    async fn delete(&self, id: i32) -> Result<bool, AppError>;
+
+    /// Records a pending delivery of an event to every webhook subscribed to it, due
+    /// now, and returns their IDs.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `AppError` if the storage fails.
+    async fn enqueue(
+        &self,
+        event: WebhookEvent,
+        payload: &serde_json::Value,
+    ) -> Result<Vec<i32>, AppError>;
+
+    /// Returns the deliveries of a webhook passing the filter, newest first.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `AppError` if the storage fails.
+    async fn deliveries(
+        &self,
+        webhook_id: i32,
+        filter: &DeliveryFilter,
+    ) -> Result<Vec<WebhookDelivery>, AppError>;
+
+    /// Records a new pending delivery of the event and payload of a delivery to its
+    /// webhook, due now, and returns it if the delivery exists.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `AppError` if the storage fails.
+    async fn redeliver(
+        &self,
+        webhook_id: i32,
+        delivery_id: i32,
+    ) -> Result<Option<WebhookDelivery>, AppError>;
+
+    /// Returns the delivery with the given ID with where to send it, if it is pending.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `AppError` if the storage fails.
+    async fn pending_delivery(&self, id: i32) -> Result<Option<DueDelivery>, AppError>;
+
+    /// Returns the IDs of the pending deliveries, in order.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `AppError` if the storage fails.
+    async fn pending_deliveries(&self) -> Result<Vec<i32>, AppError>;
+
+    /// Records the outcome of an attempt, counting it.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `AppError` if the storage fails.
+    async fn record_attempt(&self, id: i32, attempt: &DeliveryAttempt) -> Result<(), AppError>;
+}
+
+/// A shared, type-erased [`WebhookRepository`].
+pub type DynWebhookRepository = Arc<dyn WebhookRepository>;
+
+/// Returns the hex-encoded HMAC-SHA256 of `{timestamp}.{body}` keyed with the secret of a
+/// webhook, as sent in the [`SIGNATURE_HEADER`].
+///
+/// Receivers verify a request by computing the signature of its body with the timestamp
+/// of the header, and may reject old timestamps to guard against replays.
+pub fn signature(secret: &str, timestamp: i64, body: &[u8]) -> String {
+    // HMAC accepts keys of any length, so this never falls back.
+    let Ok(mut mac) = Hmac::<Sha256>::new_from_slice(secret.as_bytes()) else {
+        return String::new();
+    };
+    mac.update(timestamp.to_string().as_bytes());
+    mac.update(b".");
+    mac.update(body);
+    hex::encode(mac.finalize().into_bytes())
+}
+
+/// Returns whether an address is reachable from the internet at large, as opposed to
+/// loopback, link-local (such as cloud metadata services), private, shared, unique-local,
+/// multicast and other special addresses.
+pub fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [first, second, ..] = ip.octets();
+            // `0.0.0.0/8` is "this network"; `100.64.0.0/10` is shared by carriers.
+            let special = first == 0 || (first == 100 && second & 0xc0 == 64);
+            !(special
+                || ip.is_private()
+                || ip.is_loopback()
+                || ip.is_link_local()
+                || ip.is_broadcast()
+                || ip.is_multicast()
+                || ip.is_documentation())
+        }
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => is_public(IpAddr::V4(ip)),
+            None => {
+                !(ip.is_unspecified()
+                    || ip.is_loopback()
+                    || ip.is_multicast()
+                    || ip.is_unique_local()
+                    || ip.is_unicast_link_local())
+            }
+        },
+    }
+}
+
+/// Checks that webhooks may be sent to a URL: unless `allow_private` is set, its host
+/// must resolve, and only to [public](is_public) addresses.
+///
+/// # Errors
+///
+/// Returns why the URL is refused.
+pub async fn check_target(url: &str, allow_private: bool) -> Result<(), String> {
+    if allow_private {
+        return Ok(());
+    }
+    let url = Url::parse(url).map_err(|_| "The URL is not valid".to_string())?;
+    let port = url.port_or_known_default().unwrap_or(80);
+    let host = url
+        .host_str()
+        .ok_or_else(|| "The URL has no host".to_string())?;
+    // IPv6 hosts are written in brackets.
+    let literal = host.trim_start_matches('[').trim_end_matches(']');
+    let addresses: Vec<IpAddr> = match literal.parse() {
+        Ok(ip) => vec![ip],
+        Err(_) => lookup_host((host, port))
+            .await
+            .map_err(|_| format!("The host {host} cannot be resolved"))?
+            .map(|address| address.ip())
+            .collect(),
+    };
+    if addresses.is_empty() {
+        return Err(format!("The host {host} cannot be resolved"));
+    }
+    match addresses.into_iter().find(|ip| !is_public(*ip)) {
+        Some(ip) => Err(format!(
+            "The URL resolves to {ip}, which is not a public address"
+        )),
+        None => Ok(()),
+    }
+}
+
+/// Resolves the hosts of webhook URLs to their [public](is_public) addresses only, so a
+/// host cannot pass [`check_target`] and then resolve to a private address when the
+/// request is sent.
+struct PublicResolver;
+
+impl Resolve for PublicResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        Box::pin(async move {
+            let host = name.as_str().to_string();
+            let addresses: Vec<SocketAddr> = lookup_host((host.as_str(), 0))
+                .await?
+                .filter(|address| is_public(address.ip()))
+                .collect();
+            if addresses.is_empty() {
+                return Err(format!("{host} does not resolve to a public address").into());
+            }
+            let addresses: Addrs = Box::new(addresses.into_iter());
+            Ok(addresses)
+        })
+    }
+}
+
+/// Webhooks together with the job queue delivering events to them, as stored in the
+/// application state.
+#[derive(Clone)]
+pub struct Webhooks {
+    /// Where webhooks and deliveries are stored.
+    pub repository: DynWebhookRepository,
+    /// Retries and timeouts.
+    pub config: Arc<WebhookConfig>,
+    /// Queue of the jobs sending deliveries, with [`DeliverWebhook`] registered.
+    jobs: Jobs,
+}
+
+impl Webhooks {
+    /// Bundles a repository with its configuration and the job queue sending its
+    /// deliveries.
+    pub fn new(repository: DynWebhookRepository, config: WebhookConfig, jobs: Jobs) -> Self {
+        Self {
+            repository,
+            config: Arc::new(config),
+            jobs,
+        }
+    }
+
+    /// Checks that webhooks may be sent to a URL; see [`check_target`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an `AppError::BadRequest` if the URL is refused.
+    pub async fn check_target(&self, url: &str) -> Result<(), AppError> {
+        check_target(url, self.config.allow_private_targets)
+            .await
+            .map_err(AppError::BadRequest)
+    }
+
+    /// Enqueues the job sending a recorded delivery, unless it was enqueued before.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `AppError` if the job cannot be enqueued.
+    pub async fn send(&self, delivery_id: i32) -> Result<(), AppError> {
+        let unique_key = format!("{}:{delivery_id}", DeliverWebhook::KIND);
+        self.jobs
+            .enqueue_once::<DeliverWebhook>(&delivery_id, unique_key)
+            .await?;
+        Ok(())
+    }
+
+    /// Enqueues the jobs sending the pending deliveries that have none, such as
+    /// deliveries recorded by a server that stopped before enqueuing their jobs, and
+    /// returns how many were enqueued.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `AppError` if the storage fails.
+    pub async fn resume(&self) -> Result<usize, AppError> {
+        let mut enqueued = 0;
+        for delivery_id in self.repository.pending_deliveries().await? {
+            let unique_key = format!("{}:{delivery_id}", DeliverWebhook::KIND);
+            if self
+                .jobs
+                .enqueue_once::<DeliverWebhook>(&delivery_id, unique_key)
+                .await?
+                .is_some()
+            {
+                enqueued += 1;
+            }
+        }
+        Ok(enqueued)
+    }
+}
+
+/// Sends a pending delivery, as the job of kind `webhooks.deliver` enqueued for it by
+/// [`Webhooks::send`].
+///
+/// The job is attempted up to `WEBHOOK_MAX_ATTEMPTS` times, with the backoff of
+/// `WEBHOOK_RETRY_BASE_SECS`, and each attempt is recorded with the delivery. Jobs of
+/// deliveries that are no longer pending, such as those of deleted webhooks, succeed
+/// without sending anything.
+pub struct DeliverWebhook {
+    /// Where webhooks and deliveries are stored.
+    repository: DynWebhookRepository,
+    /// Retries and timeouts.
+    config: WebhookConfig,
+    /// Client sending the requests, without following redirects, and only to public
+    /// addresses unless private targets are allowed.
+    client: Client,
+}
+
+impl DeliverWebhook {
+    /// Creates the handler sending the deliveries of `repository`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the HTTP client cannot be created.
+    pub fn new(repository: DynWebhookRepository, config: WebhookConfig) -> Result<Self> {
+        let mut client = Client::builder()
+            .timeout(config.timeout)
+            .redirect(Policy::none())
+            .user_agent(concat!("blog-api/", env!("CARGO_PKG_VERSION")));
+        if !config.allow_private_targets {
+            client = client.dns_resolver(Arc::new(PublicResolver));
+        }
+        let client = client
+            .build()
+            .context("Failed to create the webhook HTTP client")?;
+        Ok(Self {
+            repository,
+            config,
+            client,
+        })
+    }
+
+    /// Sends the request of a delivery, returning the status of the response, if any, and
+    /// why the attempt failed, if it did.
+    async fn send(
+        &self,
+        url: &str,
+        secret: &str,
+        delivery: &WebhookDelivery,
+        body: Vec<u8>,
+    ) -> (Option<u16>, Option<String>) {
+        let timestamp = Utc::now().timestamp();
+        let signature = signature(secret, timestamp, &body);
+        let sent = self
+            .client
+            .post(url)
+            .header(CONTENT_TYPE, "application/json")
+            .header(EVENT_HEADER, delivery.event.as_str())
+            .header(DELIVERY_HEADER, delivery.id.to_string())
+            .header(SIGNATURE_HEADER, format!("t={timestamp},v1={signature}"))
+            .body(body)
+            .send()
+            .await;
+        match sent {
+            Ok(response) if response.status().is_success() => {
+                (Some(response.status().as_u16()), None)
+            }
+            Ok(response) => (
+                Some(response.status().as_u16()),
+                Some(format!("HTTP {}", response.status())),
+            ),
+            Err(err) => (None, Some(err.without_url().to_string())),
+        }
+    }
+
+    /// Sends a pending delivery and records the outcome, returning why the attempt
+    /// failed, if it did.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `AppError` if the payload cannot be serialized or the outcome cannot
+    /// be recorded.
+    async fn deliver(&self, due: DueDelivery) -> Result<Option<String>, AppError> {
+        let delivery = due.delivery;
+        let body = serde_json::to_vec(&delivery.payload).map_err(|err| {
+            tracing::error!("failed to serialize a webhook payload: {err}");
+            AppError::InternalServerError
+        })?;
+        let (response_status, error) =
+            match check_target(&due.url, self.config.allow_private_targets).await {
+                Ok(()) => self.send(&due.url, &due.secret, &delivery, body).await,
+                Err(refused) => (None, Some(refused)),
+            };
+
+        let attempts = u32::try_from(delivery.attempts)
+            .unwrap_or_default()
+            .saturating_add(1);
+        let (status, next_attempt_at) = if error.is_none() {
+            (DeliveryStatus::Succeeded, None)
+        } else if attempts >= self.config.max_attempts {
+            (DeliveryStatus::Failed, None)
+        } else {
+            let delay = chrono::Duration::from_std(self.config.retry_delay(attempts))
+                .unwrap_or(chrono::Duration::MAX);
+            (
+                DeliveryStatus::Pending,
+                Utc::now().checked_add_signed(delay),
+            )
+        };
+        let attempt = DeliveryAttempt {
+            status,
+            next_attempt_at,
+            response_status: response_status.map(i32::from),
+            error: error.clone(),
+        };
+        self.repository
+            .record_attempt(delivery.id, &attempt)
+            .await?;
+        Ok(error)
+    }
+}
+
+#[async_trait]
+impl JobHandler for DeliverWebhook {
+    const KIND: &'static str = "webhooks.deliver";
+    const QUEUE: &'static str = DELIVERY_QUEUE;
+    type Payload = i32;
+
+    fn max_attempts(&self) -> Option<u32> {
+        Some(self.config.max_attempts)
+    }
+
+    fn retry_delay(&self, attempts: u32) -> Option<Duration> {
+        Some(self.config.retry_delay(attempts))
+    }
+
+    async fn run(&self, delivery_id: i32) -> Result<(), AppError> {
+        let Some(due) = self.repository.pending_delivery(delivery_id).await? else {
+            return Ok(());
+        };
+        let span = tracing::info_span!(
+            "webhook_delivery",
+            webhook_id = due.delivery.webhook_id,
+            delivery_id
+        );
+        match self.deliver(due).instrument(span).await? {
+            Some(error) => Err(AppError::ServiceUnavailable(error)),
+            None => Ok(()),
+        }
+    }
+}
+
+impl From<&DomainEvent> for WebhookEvent {
+    fn from(event: &DomainEvent) -> Self {
+        match event {
+            DomainEvent::PostCreated(_) => Self::PostCreated,
+            DomainEvent::PostUpdated(_) => Self::PostUpdated,
+            DomainEvent::PostDeleted(_) => Self::PostDeleted,
+            DomainEvent::PostPublished(_) => Self::PostPublished,
+            DomainEvent::PostUnpublished(_) => Self::PostUnpublished,
+        }
+    }
+}
+
+impl From<&OutboxEvent> for WebhookPayload {
+    fn from(event: &OutboxEvent) -> Self {
+        Self {
+            event: WebhookEvent::from(&event.event),
+            occurred_at: event.occurred_at,
+            post: event.event.post().clone(),
+        }
+    }
+}
+
+/// Webhooks consume the domain events of the outbox: each event is recorded as a
+/// delivery to every webhook subscribed to it, sent by a job. An event dispatched again
+/// is delivered again, so receivers may see an event twice.
+#[async_trait]
+impl EventConsumer for Webhooks {
+    fn name(&self) -> &'static str {
+        "webhooks"
+    }
+
+    async fn consume(&self, event: &OutboxEvent) -> Result<(), AppError> {
+        let webhook_event = WebhookEvent::from(&event.event);
+        let payload = serde_json::to_value(WebhookPayload::from(event)).map_err(|err| {
+            tracing::error!("failed to serialize a webhook payload: {err}");
+            AppError::InternalServerError
+        })?;
+        for delivery_id in self.repository.enqueue(webhook_event, &payload).await? {
+            self.send(delivery_id).await?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,357 @@
+use super::{DueDelivery, WebhookRepository};
+use crate::{
+    error::AppError,
+    model::webhook::{
+        DeliveryAttempt, DeliveryFilter, DeliveryStatus, Webhook, WebhookBody, WebhookDelivery,
+        WebhookEvent,
+    },
+    telemetry::span,
+};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use tracing::Instrument;
+
+/// Table of webhooks, reported on query spans.
+const TABLE: &str = "webhooks";
+
+/// Table of deliveries of events to webhooks, reported on query spans.
+const DELIVERIES: &str = "webhook_deliveries";
+
+/// Database system reported on query spans.
+const SYSTEM: &str = "postgresql";
+
+/// Converts stored event names, logging and failing on unknown ones.
+fn events(names: Vec<String>) -> Result<Vec<WebhookEvent>, AppError> {
+    names.into_iter().map(event).collect()
+}
+
+/// Converts a stored event name, logging and failing on an unknown one.
+fn event(name: String) -> Result<WebhookEvent, AppError> {
+    WebhookEvent::try_from(name).map_err(|err| {
+        tracing::error!("{err}");
+        AppError::InternalServerError
+    })
+}
+
+/// Returns the stored names of events.
+fn event_names(events: &[WebhookEvent]) -> Vec<String> {
+    events
+        .iter()
+        .map(|event| event.as_str().to_string())
+        .collect()
+}
+
+/// A row of the `webhooks` table, without its secret.
+struct WebhookRow {
+    /// Unique identifier of the webhook.
+    id: i32,
+    /// URL the events are sent to.
+    url: String,
+    /// Names of the events sent.
+    events: Vec<String>,
+    /// When the webhook was created.
+    created_at: DateTime<Utc>,
+}
+
+impl TryFrom<WebhookRow> for Webhook {
+    type Error = AppError;
+
+    fn try_from(row: WebhookRow) -> Result<Self, Self::Error> {
+        Ok(Self {
+            id: row.id,
+            url: row.url,
+            events: events(row.events)?,
+            created_at: row.created_at,
+        })
+    }
+}
+
+/// A row of the `webhook_deliveries` table.
+struct DeliveryRow {
+    /// Unique identifier of the delivery.
+    id: i32,
+    /// ID of the webhook the event is sent to.
+    webhook_id: i32,
+    /// Name of the event.
+    event: String,
+    /// Body sent.
+    payload: serde_json::Value,
+    /// Status of the delivery, as stored.
+    status: String,
+    /// Attempts made.
+    attempts: i32,
+    /// When the event is to be sent next.
+    next_attempt_at: Option<DateTime<Utc>>,
+    /// When sending the event was last attempted.
+    last_attempt_at: Option<DateTime<Utc>>,
+    /// HTTP status of the last answer.
+    response_status: Option<i32>,
+    /// Why the last attempt failed.
+    error: Option<String>,
+    /// When the event was recorded.
+    created_at: DateTime<Utc>,
+    /// When the receiver accepted the event.
+    delivered_at: Option<DateTime<Utc>>,
+}
+
+impl TryFrom<DeliveryRow> for WebhookDelivery {
+    type Error = AppError;
+
+    fn try_from(row: DeliveryRow) -> Result<Self, Self::Error> {
+        let status = DeliveryStatus::try_from(row.status).map_err(|err| {
+            tracing::error!(delivery_id = row.id, "{err}");
+            AppError::InternalServerError
+        })?;
+        Ok(Self {
+            id: row.id,
+            webhook_id: row.webhook_id,
+            event: event(row.event)?,
+            payload: row.payload,
+            status,
+            attempts: row.attempts,
+            next_attempt_at: row.next_attempt_at,
+            last_attempt_at: row.last_attempt_at,
+            response_status: row.response_status,
+            error: row.error,
+            created_at: row.created_at,
+            delivered_at: row.delivered_at,
+        })
+    }
+}
+
+/// [`WebhookRepository`] backed by the `webhooks` and `webhook_deliveries` tables.
+#[derive(Debug, Clone)]
+pub struct PgWebhookRepository {
+    /// Pool of the primary database.
+    pool: PgPool,
+}
+
+impl PgWebhookRepository {
+    /// Creates a repository running its queries on `pool`.
+    pub fn new(pool: PgPool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl WebhookRepository for PgWebhookRepository {
+    async fn create(&self, webhook: &WebhookBody) -> Result<Webhook, AppError> {
+        sqlx::query_as!(
+            WebhookRow,
+            r#"
+            INSERT INTO webhooks (url, secret, events)
+            VALUES ($1, $2, $3)
+            RETURNING id, url, events, created_at;
+            "#,
+            webhook.url,
+            webhook.secret,
+            &event_names(&webhook.events)
+        )
+        .fetch_one(&self.pool)
+        .instrument(span::db_query_on(SYSTEM, "INSERT", TABLE))
+        .await?
+        .try_into()
+    }
+
+    async fn get(&self, id: i32) -> Result<Option<Webhook>, AppError> {
+        sqlx::query_as!(
+            WebhookRow,
+            "SELECT id, url, events, created_at FROM webhooks WHERE id = $1;",
+            id
+        )
+        .fetch_optional(&self.pool)
+        .instrument(span::db_query_on(SYSTEM, "SELECT", TABLE))
+        .await?
+        .map(Webhook::try_from)
+        .transpose()
+    }
+
+    async fn list(&self) -> Result<Vec<Webhook>, AppError> {
+        sqlx::query_as!(
+            WebhookRow,
+            "SELECT id, url, events, created_at FROM webhooks ORDER BY id;"
+        )
+        .fetch_all(&self.pool)
+        .instrument(span::db_query_on(SYSTEM, "SELECT", TABLE))
+        .await?
+        .into_iter()
+        .map(Webhook::try_from)
+        .collect()
+    }
+
+    async fn update(&self, id: i32, webhook: &WebhookBody) -> Result<Option<Webhook>, AppError> {
+        sqlx::query_as!(
+            WebhookRow,
+            r#"
+            UPDATE webhooks
+            SET url = $2, secret = $3, events = $4
+            WHERE id = $1
+            RETURNING id, url, events, created_at;
+            "#,
+            id,
+            webhook.url,
+            webhook.secret,
+            &event_names(&webhook.events)
+        )
+        .fetch_optional(&self.pool)
+        .instrument(span::db_query_on(SYSTEM, "UPDATE", TABLE))
+        .await?
+        .map(Webhook::try_from)
+        .transpose()
+    }
+
+    async fn delete(&self, id: i32) -> Result<bool, AppError> {
+        let result = sqlx::query!("DELETE FROM webhooks WHERE id = $1;", id)
+            .execute(&self.pool)
+            .instrument(span::db_query_on(SYSTEM, "DELETE", TABLE))
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn enqueue(
+        &self,
+        event: WebhookEvent,
+        payload: &serde_json::Value,
+    ) -> Result<Vec<i32>, AppError> {
+        let ids = sqlx::query_scalar!(
+            r#"
+            INSERT INTO webhook_deliveries (webhook_id, event, payload)
+            SELECT id, $1, $2
+            FROM webhooks
+            WHERE cardinality(events) = 0 OR $1 = ANY(events)
+            ORDER BY id
+            RETURNING id;
+            "#,
+            event.as_str(),
+            payload
+        )
+        .fetch_all(&self.pool)
+        .instrument(span::db_query_on(SYSTEM, "INSERT", DELIVERIES))
+        .await?;
+        Ok(ids)
+    }
+
+    async fn deliveries(
+        &self,
+        webhook_id: i32,
+        filter: &DeliveryFilter,
+    ) -> Result<Vec<WebhookDelivery>, AppError> {
+        sqlx::query_as!(
+            DeliveryRow,
+            r#"
+            SELECT * FROM webhook_deliveries
+            WHERE webhook_id = $1 AND ($2::TEXT IS NULL OR status = $2)
+            ORDER BY id DESC;
+            "#,
+            webhook_id,
+            filter.status.map(DeliveryStatus::as_str)
+        )
+        .fetch_all(&self.pool)
+        .instrument(span::db_query_on(SYSTEM, "SELECT", DELIVERIES))
+        .await?
+        .into_iter()
+        .map(WebhookDelivery::try_from)
+        .collect()
+    }
+
+    async fn redeliver(
+        &self,
+        webhook_id: i32,
+        delivery_id: i32,
+    ) -> Result<Option<WebhookDelivery>, AppError> {
+        sqlx::query_as!(
+            DeliveryRow,
+            r#"
+            INSERT INTO webhook_deliveries (webhook_id, event, payload)
+            SELECT webhook_id, event, payload
+            FROM webhook_deliveries
+            WHERE webhook_id = $1 AND id = $2
+            RETURNING *;
+            "#,
+            webhook_id,
+            delivery_id
+        )
+        .fetch_optional(&self.pool)
+        .instrument(span::db_query_on(SYSTEM, "INSERT", DELIVERIES))
+        .await?
+        .map(WebhookDelivery::try_from)
+        .transpose()
+    }
+
+    async fn pending_delivery(&self, id: i32) -> Result<Option<DueDelivery>, AppError> {
+        let row = sqlx::query!(
+            r#"
+            SELECT d.*, w.url, w.secret
+            FROM webhook_deliveries AS d
+            JOIN webhooks AS w ON w.id = d.webhook_id
+            WHERE d.id = $1 AND d.status = 'pending';
+            "#,
+            id
+        )
+        .fetch_optional(&self.pool)
+        .instrument(span::db_query_on(SYSTEM, "SELECT", DELIVERIES))
+        .await?;
+        row.map(|row| {
+            let delivery = WebhookDelivery::try_from(DeliveryRow {
+                id: row.id,
+                webhook_id: row.webhook_id,
+                event: row.event,
+                payload: row.payload,
+                status: row.status,
+                attempts: row.attempts,
+                next_attempt_at: row.next_attempt_at,
+                last_attempt_at: row.last_attempt_at,
+                response_status: row.response_status,
+                error: row.error,
+                created_at: row.created_at,
+                delivered_at: row.delivered_at,
+            })?;
+            Ok(DueDelivery {
+                delivery,
+                url: row.url,
+                secret: row.secret,
+            })
+        })
+        .transpose()
+    }
+
+    async fn pending_deliveries(&self) -> Result<Vec<i32>, AppError> {
+        let ids = sqlx::query_scalar!(
+            r#"
+            SELECT id FROM webhook_deliveries
+            WHERE status = 'pending'
+            ORDER BY id;
+            "#
+        )
+        .fetch_all(&self.pool)
+        .instrument(span::db_query_on(SYSTEM, "SELECT", DELIVERIES))
+        .await?;
+        Ok(ids)
+    }
+
+    async fn record_attempt(&self, id: i32, attempt: &DeliveryAttempt) -> Result<(), AppError> {
+        sqlx::query!(
+            r#"
+            UPDATE webhook_deliveries
+            SET status = $2,
+                attempts = attempts + 1,
+                next_attempt_at = $3,
+                last_attempt_at = NOW(),
+                response_status = $4,
+                error = $5,
+                delivered_at = CASE WHEN $2 = 'succeeded' THEN NOW() END
+            WHERE id = $1;
+            "#,
+            id,
+            attempt.status.as_str(),
+            attempt.next_attempt_at,
+            attempt.response_status,
+            attempt.error
+        )
+        .execute(&self.pool)
+        .instrument(span::db_query_on(SYSTEM, "UPDATE", DELIVERIES))
+        .await?;
+        Ok(())
+    }
+}
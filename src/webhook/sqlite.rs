@@ -0,0 +1,341 @@
+use super::{DueDelivery, WebhookRepository};
+use crate::{
+    error::AppError,
+    model::webhook::{
+        DeliveryAttempt, DeliveryFilter, DeliveryStatus, Webhook, WebhookBody, WebhookDelivery,
+        WebhookEvent,
+    },
+    telemetry::span,
+};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use sqlx::{FromRow, SqlitePool, types::Json};
+use tracing::Instrument;
+
+/// Table of webhooks, reported on query spans.
+const TABLE: &str = "webhooks";
+
+/// Table of deliveries of events to webhooks, reported on query spans.
+const DELIVERIES: &str = "webhook_deliveries";
+
+/// Database system reported on query spans.
+const SYSTEM: &str = "sqlite";
+
+/// Converts a stored event name, logging and failing on an unknown one.
+fn event(name: String) -> Result<WebhookEvent, AppError> {
+    WebhookEvent::try_from(name).map_err(|err| {
+        tracing::error!("{err}");
+        AppError::InternalServerError
+    })
+}
+
+/// Returns the stored JSON array of the names of events.
+fn event_names(events: &[WebhookEvent]) -> Result<String, AppError> {
+    let names: Vec<&str> = events.iter().map(|event| event.as_str()).collect();
+    serde_json::to_string(&names).map_err(|_| AppError::InternalServerError)
+}
+
+/// Converts Unix milliseconds, as `next_attempt_at` is stored, to a time.
+fn from_millis(millis: Option<i64>) -> Option<DateTime<Utc>> {
+    millis.and_then(DateTime::from_timestamp_millis)
+}
+
+/// A row of the `webhooks` table, without its secret.
+#[derive(Debug, FromRow)]
+struct WebhookRow {
+    /// Unique identifier of the webhook.
+    id: i32,
+    /// URL the events are sent to.
+    url: String,
+    /// Names of the events sent.
+    events: Json<Vec<String>>,
+    /// When the webhook was created.
+    created_at: DateTime<Utc>,
+}
+
+impl TryFrom<WebhookRow> for Webhook {
+    type Error = AppError;
+
+    fn try_from(row: WebhookRow) -> Result<Self, Self::Error> {
+        Ok(Self {
+            id: row.id,
+            url: row.url,
+            events: row
+                .events
+                .0
+                .into_iter()
+                .map(event)
+                .collect::<Result<_, _>>()?,
+            created_at: row.created_at,
+        })
+    }
+}
+
+/// A row of the `webhook_deliveries` table.
+#[derive(Debug, FromRow)]
+struct DeliveryRow {
+    /// Unique identifier of the delivery.
+    id: i32,
+    /// ID of the webhook the event is sent to.
+    webhook_id: i32,
+    /// Name of the event.
+    event: String,
+    /// Body sent.
+    payload: Json<serde_json::Value>,
+    /// Status of the delivery, as stored.
+    status: String,
+    /// Attempts made.
+    attempts: i32,
+    /// When the event is to be sent next, in Unix milliseconds.
+    next_attempt_at: Option<i64>,
+    /// When sending the event was last attempted.
+    last_attempt_at: Option<DateTime<Utc>>,
+    /// HTTP status of the last answer.
+    response_status: Option<i32>,
+    /// Why the last attempt failed.
+    error: Option<String>,
+    /// When the event was recorded.
+    created_at: DateTime<Utc>,
+    /// When the receiver accepted the event.
+    delivered_at: Option<DateTime<Utc>>,
+}
+
+impl TryFrom<DeliveryRow> for WebhookDelivery {
+    type Error = AppError;
+
+    fn try_from(row: DeliveryRow) -> Result<Self, Self::Error> {
+        let status = DeliveryStatus::try_from(row.status).map_err(|err| {
+            tracing::error!(delivery_id = row.id, "{err}");
+            AppError::InternalServerError
+        })?;
+        Ok(Self {
+            id: row.id,
+            webhook_id: row.webhook_id,
+            event: event(row.event)?,
+            payload: row.payload.0,
+            status,
+            attempts: row.attempts,
+            next_attempt_at: from_millis(row.next_attempt_at),
+            last_attempt_at: row.last_attempt_at,
+            response_status: row.response_status,
+            error: row.error,
+            created_at: row.created_at,
+            delivered_at: row.delivered_at,
+        })
+    }
+}
+
+/// [`WebhookRepository`] backed by a SQLite database migrated from `migrations/sqlite`.
+///
+/// The times deliveries are due are stored as Unix milliseconds.
+#[derive(Debug, Clone)]
+pub struct SqliteWebhookRepository {
+    /// Pool the queries run on.
+    pool: SqlitePool,
+}
+
+impl SqliteWebhookRepository {
+    /// Creates a repository running its queries on `pool`.
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool }
+    }
+}
+
+#[async_trait]
+impl WebhookRepository for SqliteWebhookRepository {
+    async fn create(&self, webhook: &WebhookBody) -> Result<Webhook, AppError> {
+        let created: WebhookRow = sqlx::query_as(
+            r#"
+            INSERT INTO webhooks (url, secret, events)
+            VALUES (?1, ?2, ?3)
+            RETURNING id, url, events, created_at;
+            "#,
+        )
+        .bind(&webhook.url)
+        .bind(&webhook.secret)
+        .bind(event_names(&webhook.events)?)
+        .fetch_one(&self.pool)
+        .instrument(span::db_query_on(SYSTEM, "INSERT", TABLE))
+        .await?;
+        created.try_into()
+    }
+
+    async fn get(&self, id: i32) -> Result<Option<Webhook>, AppError> {
+        let row: Option<WebhookRow> =
+            sqlx::query_as("SELECT id, url, events, created_at FROM webhooks WHERE id = ?1;")
+                .bind(id)
+                .fetch_optional(&self.pool)
+                .instrument(span::db_query_on(SYSTEM, "SELECT", TABLE))
+                .await?;
+        row.map(Webhook::try_from).transpose()
+    }
+
+    async fn list(&self) -> Result<Vec<Webhook>, AppError> {
+        let rows: Vec<WebhookRow> =
+            sqlx::query_as("SELECT id, url, events, created_at FROM webhooks ORDER BY id;")
+                .fetch_all(&self.pool)
+                .instrument(span::db_query_on(SYSTEM, "SELECT", TABLE))
+                .await?;
+        rows.into_iter().map(Webhook::try_from).collect()
+    }
+
+    async fn update(&self, id: i32, webhook: &WebhookBody) -> Result<Option<Webhook>, AppError> {
+        let row: Option<WebhookRow> = sqlx::query_as(
+            r#"
+            UPDATE webhooks
+            SET url = ?2, secret = ?3, events = ?4
+            WHERE id = ?1
+            RETURNING id, url, events, created_at;
+            "#,
+        )
+        .bind(id)
+        .bind(&webhook.url)
+        .bind(&webhook.secret)
+        .bind(event_names(&webhook.events)?)
+        .fetch_optional(&self.pool)
+        .instrument(span::db_query_on(SYSTEM, "UPDATE", TABLE))
+        .await?;
+        row.map(Webhook::try_from).transpose()
+    }
+
+    async fn delete(&self, id: i32) -> Result<bool, AppError> {
+        let result = sqlx::query("DELETE FROM webhooks WHERE id = ?1;")
+            .bind(id)
+            .execute(&self.pool)
+            .instrument(span::db_query_on(SYSTEM, "DELETE", TABLE))
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn enqueue(
+        &self,
+        event: WebhookEvent,
+        payload: &serde_json::Value,
+    ) -> Result<Vec<i32>, AppError> {
+        let ids: Vec<i32> = sqlx::query_scalar(
+            r#"
+            INSERT INTO webhook_deliveries (webhook_id, event, payload, next_attempt_at)
+            SELECT id, ?1, ?2, ?3
+            FROM webhooks
+            WHERE events = '[]' OR EXISTS (SELECT 1 FROM json_each(events) WHERE value = ?1)
+            ORDER BY id
+            RETURNING id;
+            "#,
+        )
+        .bind(event.as_str())
+        .bind(Json(payload))
+        .bind(Utc::now().timestamp_millis())
+        .fetch_all(&self.pool)
+        .instrument(span::db_query_on(SYSTEM, "INSERT", DELIVERIES))
+        .await?;
+        Ok(ids)
+    }
+
+    async fn deliveries(
+        &self,
+        webhook_id: i32,
+        filter: &DeliveryFilter,
+    ) -> Result<Vec<WebhookDelivery>, AppError> {
+        let rows: Vec<DeliveryRow> = sqlx::query_as(
+            r#"
+            SELECT * FROM webhook_deliveries
+            WHERE webhook_id = ?1 AND (?2 IS NULL OR status = ?2)
+            ORDER BY id DESC;
+            "#,
+        )
+        .bind(webhook_id)
+        .bind(filter.status.map(DeliveryStatus::as_str))
+        .fetch_all(&self.pool)
+        .instrument(span::db_query_on(SYSTEM, "SELECT", DELIVERIES))
+        .await?;
+        rows.into_iter().map(WebhookDelivery::try_from).collect()
+    }
+
+    async fn redeliver(
+        &self,
+        webhook_id: i32,
+        delivery_id: i32,
+    ) -> Result<Option<WebhookDelivery>, AppError> {
+        let row: Option<DeliveryRow> = sqlx::query_as(
+            r#"
+            INSERT INTO webhook_deliveries (webhook_id, event, payload, next_attempt_at)
+            SELECT webhook_id, event, payload, ?3
+            FROM webhook_deliveries
+            WHERE webhook_id = ?1 AND id = ?2
+            RETURNING *;
+            "#,
+        )
+        .bind(webhook_id)
+        .bind(delivery_id)
+        .bind(Utc::now().timestamp_millis())
+        .fetch_optional(&self.pool)
+        .instrument(span::db_query_on(SYSTEM, "INSERT", DELIVERIES))
+        .await?;
+        row.map(WebhookDelivery::try_from).transpose()
+    }
+
+    async fn pending_delivery(&self, id: i32) -> Result<Option<DueDelivery>, AppError> {
+        let row: Option<DeliveryRow> = sqlx::query_as(
+            "SELECT * FROM webhook_deliveries WHERE id = ?1 AND status = 'pending';",
+        )
+        .bind(id)
+        .fetch_optional(&self.pool)
+        .instrument(span::db_query_on(SYSTEM, "SELECT", DELIVERIES))
+        .await?;
+        let Some(row) = row else {
+            return Ok(None);
+        };
+        let webhook: Option<(String, String)> =
+            sqlx::query_as("SELECT url, secret FROM webhooks WHERE id = ?1;")
+                .bind(row.webhook_id)
+                .fetch_optional(&self.pool)
+                .instrument(span::db_query_on(SYSTEM, "SELECT", TABLE))
+                .await?;
+        // A webhook deleted meanwhile takes its deliveries with it.
+        let Some((url, secret)) = webhook else {
+            return Ok(None);
+        };
+        Ok(Some(DueDelivery {
+            delivery: row.try_into()?,
+            url,
+            secret,
+        }))
+    }
+
+    async fn pending_deliveries(&self) -> Result<Vec<i32>, AppError> {
+        let ids: Vec<i32> = sqlx::query_scalar(
+            "SELECT id FROM webhook_deliveries WHERE status = 'pending' ORDER BY id;",
+        )
+        .fetch_all(&self.pool)
+        .instrument(span::db_query_on(SYSTEM, "SELECT", DELIVERIES))
+        .await?;
+        Ok(ids)
+    }
+
+    async fn record_attempt(&self, id: i32, attempt: &DeliveryAttempt) -> Result<(), AppError> {
+        sqlx::query(
+            r#"
+            UPDATE webhook_deliveries
+            SET status = ?2,
+                attempts = attempts + 1,
+                next_attempt_at = ?3,
+                last_attempt_at = strftime('%Y-%m-%dT%H:%M:%fZ', 'now'),
+                response_status = ?4,
+                error = ?5,
+                delivered_at = CASE
+                    WHEN ?2 = 'succeeded' THEN strftime('%Y-%m-%dT%H:%M:%fZ', 'now')
+                END
+            WHERE id = ?1;
+            "#,
+        )
+        .bind(id)
+        .bind(attempt.status.as_str())
+        .bind(attempt.next_attempt_at.map(|time| time.timestamp_millis()))
+        .bind(attempt.response_status)
+        .bind(&attempt.error)
+        .execute(&self.pool)
+        .instrument(span::db_query_on(SYSTEM, "UPDATE", DELIVERIES))
+        .await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,383 @@
+mod common;
+
+use axum::{
+    Router,
+    body::Body,
+    http::{Method, Request, StatusCode, header},
+};
+use blog_api::{
+    collab::{CollabConfig, Collaboration},
+    repository::memory::InMemoryPostRepository,
+    server::{
+        middleware::http::{CorsOrigins, HttpConfig},
+        routes::setup_routes,
+    },
+};
+use common::{send, send_with};
+use futures_util::{SinkExt, StreamExt};
+use serde_json::{Value, json};
+use std::{net::SocketAddr, sync::Arc, time::Duration};
+use tokio::net::{TcpListener, TcpStream};
+use tokio_tungstenite::{
+    MaybeTlsStream, WebSocketStream, connect_async,
+    tungstenite::{Error as WsError, Message, http::StatusCode as WsStatus},
+};
+use tower::ServiceExt;
+
+/// A WebSocket connected to the room of a post.
+type Socket = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+/// Builds one application per backend, with edit locks expiring after `lock_ttl`.
+async fn apps_with_lock_ttl(lock_ttl: Duration) -> Vec<(&'static str, Router)> {
+    let config = CollabConfig { lock_ttl };
+    let mut apps = Vec::new();
+    for backend in common::backends().await {
+        let mut state = backend.state();
+        state.collab = Collaboration::new(backend.locks, config.clone());
+        apps.push((backend.name, setup_routes(state)));
+    }
+    apps
+}
+
+/// Serves an application on a local port and returns its address.
+async fn serve(app: Router) -> SocketAddr {
+    let listener = TcpListener::bind("127.0.0.1:0").await.expect("listener");
+    let address = listener.local_addr().expect("address");
+    tokio::spawn(async move {
+        axum::serve(
+            listener,
+            app.into_make_service_with_connect_info::<SocketAddr>(),
+        )
+        .await
+    });
+    address
+}
+
+/// A valid request body for creating or updating a post.
+fn post_body(title: &str) -> Value {
+    json!({
+        "title": title,
+        "content": "Content",
+        "category": "Rust",
+        "tags": ["rust"]
+    })
+}
+
+/// Creates a post and returns its ID.
+async fn create(app: &Router) -> i64 {
+    let (status, post) = send(app, Method::POST, "/api/v1/posts", Some(post_body("Draft"))).await;
+    assert_eq!(status, StatusCode::CREATED, "{post}");
+    post["id"].as_i64().expect("post ID")
+}
+
+/// Joins the room of a post as `name`.
+async fn join(address: SocketAddr, post_id: i64, name: &str) -> Socket {
+    let url = format!("ws://{address}/api/v1/posts/{post_id}/collaborate?name={name}");
+    let (socket, _) = connect_async(url).await.expect("WebSocket");
+    socket
+}
+
+/// Returns the next message received, failing if none arrives in time.
+async fn next(socket: &mut Socket) -> Value {
+    loop {
+        let message = tokio::time::timeout(Duration::from_secs(5), socket.next())
+            .await
+            .expect("a message in time")
+            .expect("an open socket")
+            .expect("a message");
+        if let Message::Text(text) = message {
+            return serde_json::from_str(&text).expect("JSON message");
+        }
+    }
+}
+
+/// Returns the next message passing `check`, skipping the others.
+async fn until(socket: &mut Socket, check: impl Fn(&Value) -> bool) -> Value {
+    loop {
+        let message = next(socket).await;
+        if check(&message) {
+            return message;
+        }
+    }
+}
+
+/// Returns the next presence message listing exactly `names`, in order.
+async fn presence_of(socket: &mut Socket, names: &[&str]) -> Value {
+    until(socket, |message| {
+        message["type"] == "presence"
+            && message["participants"]
+                .as_array()
+                .is_some_and(|participants| {
+                    participants
+                        .iter()
+                        .map(|participant| participant["name"].as_str().unwrap_or_default())
+                        .eq(names.iter().copied())
+                })
+    })
+    .await
+}
+
+/// Sends a message of the given type.
+async fn say(socket: &mut Socket, kind: &str) {
+    socket
+        .send(Message::text(json!({ "type": kind }).to_string()))
+        .await
+        .expect("send");
+}
+
+#[tokio::test]
+async fn participants_see_each_other_and_the_lock_holder() {
+    for (backend, app) in apps_with_lock_ttl(Duration::from_secs(30)).await {
+        let id = create(&app).await;
+        let address = serve(app).await;
+
+        let mut alice = join(address, id, "alice").await;
+        presence_of(&mut alice, &["alice"]).await;
+        let mut bob = join(address, id, "bob").await;
+        presence_of(&mut alice, &["alice", "bob"]).await;
+        let presence = presence_of(&mut bob, &["alice", "bob"]).await;
+        assert_eq!(presence["lock"], Value::Null, "{backend}");
+        assert_eq!(presence["participants"][0]["editing"], false, "{backend}");
+
+        say(&mut alice, "lock").await;
+        let granted = until(&mut alice, |message| message["type"] != "presence").await;
+        assert_eq!(granted["type"], "lock_granted", "{backend}: {granted}");
+        assert!(granted["token"].is_string(), "{backend}");
+        assert_eq!(granted["lock"]["holder"], "alice", "{backend}");
+        assert!(granted["lock"].get("token").is_none(), "{backend}");
+
+        // The others see who is editing, but not the token.
+        let presence = until(&mut bob, |message| !message["lock"].is_null()).await;
+        assert_eq!(presence["lock"]["holder"], "alice", "{backend}");
+        assert!(presence["lock"].get("token").is_none(), "{backend}");
+        assert_eq!(presence["participants"][0]["editing"], true, "{backend}");
+        assert_eq!(presence["participants"][1]["editing"], false, "{backend}");
+
+        say(&mut bob, "lock").await;
+        let denied = until(&mut bob, |message| message["type"] != "presence").await;
+        assert_eq!(denied["type"], "lock_denied", "{backend}: {denied}");
+        assert_eq!(denied["lock"]["holder"], "alice", "{backend}");
+        say(&mut bob, "heartbeat").await;
+        let lost = until(&mut bob, |message| message["type"] != "presence").await;
+        assert_eq!(lost["type"], "lock_lost", "{backend}: {lost}");
+
+        // Leaving releases the lock.
+        alice.close(None).await.expect("close");
+        let presence = presence_of(&mut bob, &["bob"]).await;
+        assert_eq!(presence["lock"], Value::Null, "{backend}");
+        say(&mut bob, "lock").await;
+        let granted = until(&mut bob, |message| message["type"] != "presence").await;
+        assert_eq!(granted["type"], "lock_granted", "{backend}: {granted}");
+
+        socket_error(&mut bob, "not json").await;
+    }
+}
+
+/// Sends an invalid message and checks that it is answered by an error.
+async fn socket_error(socket: &mut Socket, text: &str) {
+    socket.send(Message::text(text)).await.expect("send");
+    let error = until(socket, |message| message["type"] != "presence").await;
+    assert_eq!(error["type"], "error", "{error}");
+    assert!(error["message"].is_string(), "{error}");
+}
+
+#[tokio::test]
+async fn updates_of_a_locked_post_need_the_lock_token() {
+    for (backend, app) in apps_with_lock_ttl(Duration::from_secs(30)).await {
+        let id = create(&app).await;
+        let address = serve(app.clone()).await;
+        let uri = format!("/api/v1/posts/{id}");
+
+        let mut alice = join(address, id, "alice").await;
+        say(&mut alice, "lock").await;
+        let granted = until(&mut alice, |message| message["type"] == "lock_granted").await;
+        let token = granted["token"].as_str().expect("token").to_string();
+
+        let (status, error) = send(&app, Method::PUT, &uri, Some(post_body("Bob's edit"))).await;
+        assert_eq!(status, StatusCode::LOCKED, "{backend}: {error}");
+        let message = error["error"].as_str().expect("error message");
+        assert!(message.contains("alice"), "{backend}: {message}");
+        assert!(message.contains("Edit-Lock"), "{backend}: {message}");
+        let (status, _, _) = send_with(
+            &app,
+            Method::PUT,
+            &uri,
+            Some(post_body("Bob's edit")),
+            &[("Edit-Lock", "not-the-token")],
+        )
+        .await;
+        assert_eq!(status, StatusCode::LOCKED, "{backend}");
+
+        let (status, _, post) = send_with(
+            &app,
+            Method::PUT,
+            &uri,
+            Some(post_body("Alice's edit")),
+            &[("Edit-Lock", &token)],
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK, "{backend}: {post}");
+        assert_eq!(post["title"], "Alice's edit", "{backend}");
+
+        // Other posts are not locked.
+        let other = create(&app).await;
+        let (status, _) = send(
+            &app,
+            Method::PUT,
+            &format!("/api/v1/posts/{other}"),
+            Some(post_body("Bob's edit")),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK, "{backend}");
+
+        let (status, _) = send(&app, Method::DELETE, &uri, None).await;
+        assert_eq!(status, StatusCode::LOCKED, "{backend}");
+
+        // Bulk edits leave the locked post alone and report it.
+        let (status, updated) = send(
+            &app,
+            Method::PATCH,
+            "/api/v1/posts/bulk",
+            Some(json!({ "filter": { "category": "Rust" }, "add_tags": ["bulk"] })),
+        )
+        .await;
+        assert_eq!(status, StatusCode::MULTI_STATUS, "{backend}: {updated}");
+        assert_eq!(updated["ids"], json!([other]), "{backend}");
+        assert_eq!(updated["rejected"][0]["id"], id, "{backend}");
+        assert_eq!(updated["rejected"][0]["status"], "rejected", "{backend}");
+        let message = updated["rejected"][0]["error"]
+            .as_str()
+            .expect("error message");
+        assert!(message.contains("alice"), "{backend}: {message}");
+        let (_, post) = send(&app, Method::GET, &uri, None).await;
+        assert_eq!(post["tags"], json!(["rust"]), "{backend}");
+        let (status, deleted) = send(
+            &app,
+            Method::DELETE,
+            "/api/v1/posts/bulk",
+            Some(json!({ "ids": [id, other] })),
+        )
+        .await;
+        assert_eq!(status, StatusCode::MULTI_STATUS, "{backend}: {deleted}");
+        assert_eq!(deleted["deleted"], json!([other]), "{backend}");
+        assert_eq!(deleted["rejected"][0]["id"], id, "{backend}");
+        let (status, _) = send(&app, Method::GET, &uri, None).await;
+        assert_eq!(status, StatusCode::OK, "{backend}");
+
+        say(&mut alice, "unlock").await;
+        until(&mut alice, |message| {
+            message["type"] == "presence" && message["lock"].is_null()
+        })
+        .await;
+        let (status, _) = send(&app, Method::PUT, &uri, Some(post_body("Bob's edit"))).await;
+        assert_eq!(status, StatusCode::OK, "{backend}");
+        let (status, _) = send(&app, Method::DELETE, &uri, None).await;
+        assert_eq!(status, StatusCode::NO_CONTENT, "{backend}");
+    }
+}
+
+#[tokio::test]
+async fn browsers_may_send_the_lock_token_across_origins() {
+    let mut state = common::state_with(Arc::new(InMemoryPostRepository::new()));
+    let mut http = HttpConfig::from_env().expect("http config");
+    http.cors_origins = Some(CorsOrigins::Any);
+    state.http = Arc::new(http);
+    let app = setup_routes(state);
+
+    let request = Request::builder()
+        .method(Method::OPTIONS)
+        .uri("/api/v1/posts/1")
+        .header(header::ORIGIN, "https://editor.example")
+        .header(header::ACCESS_CONTROL_REQUEST_METHOD, "PUT")
+        .header(
+            header::ACCESS_CONTROL_REQUEST_HEADERS,
+            "content-type,edit-lock",
+        )
+        .body(Body::empty())
+        .expect("request");
+    let response = app.oneshot(request).await.expect("response");
+    let allowed = response
+        .headers()
+        .get(header::ACCESS_CONTROL_ALLOW_HEADERS)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+    assert!(allowed.contains("edit-lock"), "{allowed:?}");
+}
+
+#[tokio::test]
+async fn locks_expire_without_heartbeats() {
+    for (backend, app) in apps_with_lock_ttl(Duration::from_millis(500)).await {
+        let id = create(&app).await;
+        let address = serve(app.clone()).await;
+
+        let mut alice = join(address, id, "alice").await;
+        let mut bob = join(address, id, "bob").await;
+        say(&mut alice, "lock").await;
+        let granted = until(&mut alice, |message| message["type"] == "lock_granted").await;
+        let token = granted["token"].as_str().expect("token").to_string();
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        say(&mut alice, "heartbeat").await;
+        let renewed = until(&mut alice, |message| message["type"] == "lock_granted").await;
+        assert!(
+            renewed["lock"]["expires_at"].as_str() > granted["lock"]["expires_at"].as_str(),
+            "{backend}"
+        );
+        assert_eq!(
+            renewed["lock"]["acquired_at"], granted["lock"]["acquired_at"],
+            "{backend}"
+        );
+
+        // Without heartbeats, the lock expires and the room is told.
+        until(&mut bob, |message| !message["lock"].is_null()).await;
+        until(&mut bob, |message| {
+            message["type"] == "presence" && message["lock"].is_null()
+        })
+        .await;
+        let (status, _, _) = send_with(
+            &app,
+            Method::PUT,
+            &format!("/api/v1/posts/{id}"),
+            Some(post_body("Bob's edit")),
+            &[("Edit-Lock", &token)],
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK, "{backend}");
+
+        say(&mut bob, "lock").await;
+        let granted = until(&mut bob, |message| message["type"] == "lock_granted").await;
+        assert_eq!(granted["lock"]["holder"], "bob", "{backend}");
+        say(&mut alice, "heartbeat").await;
+        let lost = until(&mut alice, |message| message["type"] != "presence").await;
+        assert_eq!(lost["type"], "lock_lost", "{backend}: {lost}");
+    }
+}
+
+#[tokio::test]
+async fn joining_needs_a_post_and_a_name() {
+    for (backend, app) in apps_with_lock_ttl(Duration::from_secs(30)).await {
+        let id = create(&app).await;
+        let address = serve(app).await;
+        for (uri, status) in [
+            (
+                format!("/api/v1/posts/{}/collaborate?name=alice", id + 100),
+                WsStatus::NOT_FOUND,
+            ),
+            (
+                format!("/api/v1/posts/{id}/collaborate?name=%20"),
+                WsStatus::BAD_REQUEST,
+            ),
+            (
+                format!("/api/v1/posts/{id}/collaborate"),
+                WsStatus::BAD_REQUEST,
+            ),
+        ] {
+            match connect_async(format!("ws://{address}{uri}")).await {
+                Err(WsError::Http(response)) => {
+                    assert_eq!(response.status(), status, "{backend}: {uri}")
+                }
+                other => panic!("{backend}: {uri} was not rejected: {other:?}"),
+            }
+        }
+    }
+}
@@ -0,0 +1,323 @@
+// Each test binary uses some of these helpers only.
+#![allow(dead_code)]
+
+use std::sync::{
+    Arc,
+    atomic::{AtomicUsize, Ordering},
+};
+
+use axum::{
+    Router,
+    body::{Body, Bytes, to_bytes},
+    http::{HeaderMap, Method, Request, StatusCode, header},
+};
+use blog_api::{
+    collab::{
+        CollabConfig, Collaboration, DynEditLockRepository, memory::InMemoryEditLockRepository,
+        postgres::PgEditLockRepository, sqlite::SqliteEditLockRepository,
+    },
+    database::connection::{pg_connect, pg_pool, sqlite_connect},
+    events::{EventsConfig, PostEvents},
+    idempotency::{
+        DynIdempotencyStore, Idempotency, IdempotencyConfig, memory::InMemoryIdempotencyStore,
+        postgres::PgIdempotencyStore, sqlite::SqliteIdempotencyStore,
+    },
+    jobs::{
+        DynJobRepository, Jobs, JobsConfig, memory::InMemoryJobRepository,
+        postgres::PgJobRepository, sqlite::SqliteJobRepository,
+    },
+    media::{
+        DynMediaRepository, Media, MediaConfig, blob::memory::InMemoryBlobStore,
+        memory::InMemoryMediaRepository, postgres::PgMediaRepository,
+        sqlite::SqliteMediaRepository,
+    },
+    outbox::{DynOutboxRepository, postgres::PgOutboxRepository, sqlite::SqliteOutboxRepository},
+    repository::{
+        DynPostRepository, memory::InMemoryPostRepository, postgres::PgPostRepository,
+        sqlite::SqlitePostRepository,
+    },
+    server::{
+        middleware::{
+            admin::AdminConfig,
+            http::HttpConfig,
+            rate_limit::{RateLimitConfig, RateLimiter},
+            versioning::VersioningConfig,
+        },
+        routes::setup_routes,
+    },
+    site::SiteConfig,
+    state::AppState,
+    webhook::{
+        DynWebhookRepository, WebhookConfig, Webhooks, memory::InMemoryWebhookRepository,
+        postgres::PgWebhookRepository, sqlite::SqliteWebhookRepository,
+    },
+};
+use serde_json::Value;
+use sqlx::PgPool;
+use tower::ServiceExt;
+
+/// Token of the administration endpoints of the applications built here.
+pub const ADMIN_TOKEN: &str = "test-admin-token-0123456789";
+
+/// The repositories of one storage backend, all empty.
+pub struct Backend {
+    /// Name of the backend, for assertion messages.
+    pub name: &'static str,
+    /// Blog posts.
+    pub posts: DynPostRepository,
+    /// Domain events recorded with the post writes.
+    pub outbox: DynOutboxRepository,
+    /// Media file records.
+    pub media: DynMediaRepository,
+    /// Idempotency keys.
+    pub idempotency: DynIdempotencyStore,
+    /// Webhook subscriptions and deliveries.
+    pub webhooks: DynWebhookRepository,
+    /// Edit locks.
+    pub locks: DynEditLockRepository,
+    /// Queued jobs.
+    pub jobs: DynJobRepository,
+    /// The PostgreSQL pool handlers write posts through, if this backend is PostgreSQL.
+    pub pool: Option<PgPool>,
+}
+
+impl Backend {
+    /// Builds application state that stores everything in this backend, except the
+    /// content of media files, which is kept in memory.
+    ///
+    /// On PostgreSQL, handlers write posts in the transaction of the request. Like
+    /// [`state_with`], rate limiting is disabled, no variants are generated and no
+    /// jobs are run. No outbox dispatcher is started either, so tests observing the
+    /// domain events spawn one on [`Backend::outbox`].
+    pub fn state(&self) -> AppState {
+        let mut state = state_with(self.posts.clone());
+        state.pool = self.pool.clone();
+        let jobs = Jobs::new(
+            self.jobs.clone(),
+            JobsConfig::from_env().expect("jobs config"),
+        );
+        state.media = Media::new(
+            self.media.clone(),
+            Arc::new(InMemoryBlobStore::new()),
+            media_config(),
+        );
+        state.idempotency = Idempotency::new(
+            self.idempotency.clone(),
+            IdempotencyConfig::from_env().expect("idempotency config"),
+        );
+        state.webhooks = Webhooks::new(self.webhooks.clone(), webhook_config(), jobs.clone());
+        state.collab = Collaboration::new(
+            self.locks.clone(),
+            CollabConfig::from_env().expect("collab config"),
+        );
+        state.jobs = jobs;
+        state
+    }
+}
+
+/// Returns one empty backend per kind of storage.
+///
+/// The in-memory and SQLite backends are always included. PostgreSQL is included when
+/// `TEST_DATABASE_URL` is set, with a schema of its own so tests can run in parallel.
+pub async fn backends() -> Vec<Backend> {
+    let posts = Arc::new(InMemoryPostRepository::new());
+    let memory = Backend {
+        name: "memory",
+        posts: posts.clone(),
+        outbox: posts,
+        media: Arc::new(InMemoryMediaRepository::new()),
+        idempotency: Arc::new(InMemoryIdempotencyStore::new()),
+        webhooks: Arc::new(InMemoryWebhookRepository::new()),
+        locks: Arc::new(InMemoryEditLockRepository::new()),
+        jobs: Arc::new(InMemoryJobRepository::new()),
+        pool: None,
+    };
+
+    let pool = sqlite_connect("sqlite::memory:")
+        .await
+        .expect("SQLite database");
+    let sqlite = Backend {
+        name: "sqlite",
+        posts: Arc::new(SqlitePostRepository::new(pool.clone())),
+        outbox: Arc::new(SqliteOutboxRepository::new(pool.clone())),
+        media: Arc::new(SqliteMediaRepository::new(pool.clone())),
+        idempotency: Arc::new(SqliteIdempotencyStore::new(pool.clone())),
+        webhooks: Arc::new(SqliteWebhookRepository::new(pool.clone())),
+        locks: Arc::new(SqliteEditLockRepository::new(pool.clone())),
+        jobs: Arc::new(SqliteJobRepository::new(pool)),
+        pool: None,
+    };
+
+    let mut backends = vec![memory, sqlite];
+    if let Ok(url) = std::env::var("TEST_DATABASE_URL") {
+        let pool = pg_connect(&fresh_schema(&url).await)
+            .await
+            .expect("PostgreSQL database");
+        backends.push(Backend {
+            name: "postgres",
+            posts: Arc::new(PgPostRepository::new(pool.clone())),
+            outbox: Arc::new(PgOutboxRepository::new(pool.clone())),
+            media: Arc::new(PgMediaRepository::new(pool.clone())),
+            idempotency: Arc::new(PgIdempotencyStore::new(pool.clone())),
+            webhooks: Arc::new(PgWebhookRepository::new(pool.clone())),
+            locks: Arc::new(PgEditLockRepository::new(pool.clone())),
+            jobs: Arc::new(PgJobRepository::new(pool.clone())),
+            pool: Some(pool),
+        });
+    }
+    backends
+}
+
+/// Creates an empty schema in the PostgreSQL database at `url` and returns a URL whose
+/// connections use it.
+///
+/// Schemas are left behind for inspection; they are named `posts_test_*`.
+async fn fresh_schema(url: &str) -> String {
+    static SCHEMAS: AtomicUsize = AtomicUsize::new(0);
+    let schema = format!(
+        "posts_test_{}_{}_{}",
+        std::process::id(),
+        chrono::Utc::now().timestamp_micros(),
+        SCHEMAS.fetch_add(1, Ordering::Relaxed)
+    );
+    let pool = pg_pool(url).await.expect("PostgreSQL database");
+    sqlx::query(&format!("CREATE SCHEMA {schema}"))
+        .execute(&pool)
+        .await
+        .expect("schema");
+    pool.close().await;
+
+    let separator = if url.contains('?') { '&' } else { '?' };
+    format!("{url}{separator}options=-c%20search_path%3D{schema}%2Cpublic")
+}
+
+/// Builds one application per backend, each with empty storage.
+pub async fn apps() -> Vec<(&'static str, Router)> {
+    backends()
+        .await
+        .into_iter()
+        .map(|backend| (backend.name, setup_routes(backend.state())))
+        .collect()
+}
+
+/// Builds application state that stores posts in `posts`.
+///
+/// No PostgreSQL pool is configured, and media files, idempotency keys, webhooks, edit
+/// locks and jobs are kept in memory. The administration endpoints take the
+/// [`ADMIN_TOKEN`], and webhooks may be sent to any address. Rate limiting is disabled so tests can send as many
+/// requests as they like. No variants are generated from images, so media records do not
+/// change after upload, and no jobs are run, so webhook deliveries stay pending.
+pub fn state_with(posts: DynPostRepository) -> AppState {
+    let mut rate_limit = RateLimitConfig::from_env().expect("rate limit config");
+    rate_limit.enabled = false;
+    let jobs = Jobs::new(
+        Arc::new(InMemoryJobRepository::new()),
+        JobsConfig::from_env().expect("jobs config"),
+    );
+
+    AppState {
+        pool: None,
+        replica: None,
+        posts,
+        media: Media::new(
+            Arc::new(InMemoryMediaRepository::new()),
+            Arc::new(InMemoryBlobStore::new()),
+            media_config(),
+        ),
+        idempotency: Idempotency::new(
+            Arc::new(InMemoryIdempotencyStore::new()),
+            IdempotencyConfig::from_env().expect("idempotency config"),
+        ),
+        webhooks: Webhooks::new(
+            Arc::new(InMemoryWebhookRepository::new()),
+            webhook_config(),
+            jobs.clone(),
+        ),
+        events: PostEvents::new(EventsConfig::from_env().expect("events config")),
+        collab: Collaboration::new(
+            Arc::new(InMemoryEditLockRepository::new()),
+            CollabConfig::from_env().expect("collab config"),
+        ),
+        jobs,
+        rate_limiter: RateLimiter::new(rate_limit),
+        http: Arc::new(HttpConfig::from_env().expect("http config")),
+        versioning: Arc::new(VersioningConfig::from_env().expect("versioning config")),
+        site: Arc::new(SiteConfig::from_env().expect("site config")),
+        admin: Arc::new(AdminConfig {
+            token: Some(ADMIN_TOKEN.to_string()),
+        }),
+    }
+}
+
+/// Returns the webhook configuration of the environment, allowing webhooks to be sent to
+/// receivers on this host.
+pub fn webhook_config() -> WebhookConfig {
+    let mut config = WebhookConfig::from_env().expect("webhook config");
+    config.allow_private_targets = true;
+    config
+}
+
+/// Returns the media configuration of the environment, without variants.
+pub fn media_config() -> MediaConfig {
+    let mut config = MediaConfig::from_env().expect("media config");
+    config.variants.formats.clear();
+    config
+}
+
+/// Sends a request and returns the status, headers and body of the response.
+pub async fn send_request(app: &Router, request: Request<Body>) -> (StatusCode, HeaderMap, Bytes) {
+    let response = app.clone().oneshot(request).await.expect("response");
+    let (parts, body) = response.into_parts();
+    let body = to_bytes(body, usize::MAX).await.expect("body");
+    (parts.status, parts.headers, body)
+}
+
+/// Sends a request with an optional JSON body and the given headers, and returns the
+/// status, headers and JSON body of the response, or `null` if it is not JSON.
+pub async fn send_with(
+    app: &Router,
+    method: Method,
+    uri: &str,
+    body: Option<Value>,
+    headers: &[(&str, &str)],
+) -> (StatusCode, HeaderMap, Value) {
+    let mut request = Request::builder()
+        .method(method)
+        .uri(uri)
+        .header(header::CONTENT_TYPE, "application/json");
+    for (name, value) in headers {
+        request = request.header(*name, *value);
+    }
+    let request = request
+        .body(body.map_or_else(Body::empty, |body| Body::from(body.to_string())))
+        .expect("request");
+    let (status, headers, body) = send_request(app, request).await;
+    let json = serde_json::from_slice(&body).unwrap_or(Value::Null);
+    (status, headers, json)
+}
+
+/// Sends a request with an optional JSON body to an administration endpoint, with the
+/// [`ADMIN_TOKEN`], and returns the status and JSON body of the response.
+pub async fn send_admin(
+    app: &Router,
+    method: Method,
+    uri: &str,
+    body: Option<Value>,
+) -> (StatusCode, Value) {
+    let authorization = format!("Bearer {ADMIN_TOKEN}");
+    let headers = [(header::AUTHORIZATION.as_str(), authorization.as_str())];
+    let (status, _, body) = send_with(app, method, uri, body, &headers).await;
+    (status, body)
+}
+
+/// Sends a request with an optional JSON body, and returns the status and JSON body of
+/// the response, or `null` if it is not JSON.
+pub async fn send(
+    app: &Router,
+    method: Method,
+    uri: &str,
+    body: Option<Value>,
+) -> (StatusCode, Value) {
+    let (status, _, body) = send_with(app, method, uri, body, &[]).await;
+    (status, body)
+}
@@ -0,0 +1,534 @@
+mod common;
+
+use axum::{
+    Router,
+    body::Body,
+    http::{Method, Request, StatusCode, header},
+};
+use blog_api::content::{
+    archive::{self, MAX_FILE_BYTES, MAX_UNCOMPRESSED_BYTES},
+    markdown,
+};
+use common::{apps, send, send_request};
+use flate2::{Compression, write::GzEncoder};
+use serde_json::{Value, json};
+use std::io::{Cursor, Read, Write};
+use zip::{ZipArchive, ZipWriter, write::SimpleFileOptions};
+
+/// Boundary of the multipart bodies sent by the tests.
+const BOUNDARY: &str = "content-test-boundary";
+
+/// Uploads a file to the import endpoint and returns the status and JSON report.
+async fn import(app: &Router, uri: &str, body: Vec<u8>) -> (StatusCode, Value) {
+    let request = Request::post(uri).body(Body::from(body)).expect("request");
+    let (status, _, body) = send_request(app, request).await;
+    (status, serde_json::from_slice(&body).unwrap_or(Value::Null))
+}
+
+/// Reads the posts of an application.
+async fn list(app: &Router) -> Value {
+    let (status, posts) = send(app, Method::GET, "/api/v1/posts", None).await;
+    assert_eq!(status, StatusCode::OK, "{posts}");
+    posts
+}
+
+/// Uploads a PNG image and returns its ID.
+async fn upload_image(app: &Router) -> i64 {
+    let mut png = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR".to_vec();
+    png.extend(1u32.to_be_bytes());
+    png.extend(1u32.to_be_bytes());
+    png.extend(b"\x08\x06\0\0\0");
+    let mut body = format!(
+        "--{BOUNDARY}\r\nContent-Disposition: form-data; name=\"file\"; \
+         filename=\"cover.png\"\r\nContent-Type: image/png\r\n\r\n"
+    )
+    .into_bytes();
+    body.extend(png);
+    body.extend(format!("\r\n--{BOUNDARY}--\r\n").bytes());
+    let request = Request::post("/api/v1/media")
+        .header(
+            header::CONTENT_TYPE,
+            format!("multipart/form-data; boundary={BOUNDARY}"),
+        )
+        .body(Body::from(body))
+        .expect("request");
+    let (status, _, body) = send_request(app, request).await;
+    assert_eq!(status, StatusCode::CREATED);
+    let image: Value = serde_json::from_slice(&body).expect("media file");
+    image["id"].as_i64().expect("ID")
+}
+
+/// Returns the statuses of the results of an import report.
+fn statuses(report: &Value) -> Vec<&str> {
+    report["results"]
+        .as_array()
+        .expect("results")
+        .iter()
+        .map(|result| result["status"].as_str().expect("status"))
+        .collect()
+}
+
+/// A Markdown post without a slug, dated with a bare date.
+const HELLO: &str = "---
+title: Hello, world
+category: Rust
+tags: [rust, intro]
+date: 2025-03-01
+unknown: ignored
+---
+My first post.
+";
+
+#[tokio::test]
+async fn markdown_files_are_upserted_by_slug() {
+    for (backend, app) in apps().await {
+        let (status, report) = import(
+            &app,
+            "/api/v1/posts/import?filename=2025-03-01-hello-world.md",
+            HELLO.into(),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK, "{backend}: {report}");
+        assert_eq!(
+            report,
+            json!({
+                "created": 1,
+                "updated": 0,
+                "failed": 0,
+                "results": [{
+                    "file": "2025-03-01-hello-world.md",
+                    "status": "created",
+                    "id": 1,
+                    "slug": "hello-world"
+                }]
+            }),
+            "{backend}"
+        );
+        let posts = list(&app).await;
+        assert_eq!(posts[0]["title"], "Hello, world", "{backend}");
+        assert_eq!(posts[0]["content"], "My first post.\n", "{backend}");
+        assert_eq!(posts[0]["tags"], json!(["rust", "intro"]), "{backend}");
+        assert_eq!(posts[0]["draft"], false, "{backend}");
+        assert_eq!(
+            posts[0]["published_at"], "2025-03-01T00:00:00Z",
+            "{backend}"
+        );
+
+        let edited = "---\ntitle: Hello again\nslug: hello-world\ncategory: Rust\ntags: rust\ndraft: true\n---\nEdited.\n";
+        let mut archive = ZipWriter::new(Cursor::new(Vec::new()));
+        for (name, text) in [
+            ("posts/hello.md", edited),
+            (
+                "posts/broken.md",
+                "---\ntitle: No category\ntags: [a]\n---\nx\n",
+            ),
+            (
+                "posts/cover.md",
+                "---\ntitle: Cover\ncategory: Go\ntags: [go]\nfeatured_image_id: 99\n---\nx\n",
+            ),
+            ("posts/notes.txt", "not imported"),
+            ("__MACOSX/posts/._hello.md", "metadata"),
+            (
+                "posts/second.markdown",
+                "---\ntitle: Second\ncategory: Go\ntags: [go]\n---\nMore.\n",
+            ),
+        ] {
+            archive
+                .start_file(name, SimpleFileOptions::default())
+                .expect("zip entry");
+            archive.write_all(text.as_bytes()).expect("zip data");
+        }
+        let zip = archive.finish().expect("zip").into_inner();
+
+        let (status, report) = import(&app, "/api/v1/posts/import", zip).await;
+        assert_eq!(status, StatusCode::MULTI_STATUS, "{backend}: {report}");
+        assert_eq!(
+            statuses(&report),
+            ["updated", "invalid", "invalid", "created"],
+            "{backend}"
+        );
+        assert_eq!(report["results"][0]["id"], 1, "{backend}");
+        assert!(
+            report["results"][1]["error"]
+                .as_str()
+                .expect("error")
+                .contains("missing field `category`"),
+            "{backend}: {report}"
+        );
+        // Posts the storage refuses are reported, and the import goes on.
+        assert_eq!(report["results"][2]["file"], "posts/cover.md", "{backend}");
+        assert_eq!(
+            report["results"][2]["error"], "Featured image not found",
+            "{backend}"
+        );
+        assert_eq!(report["results"][3]["slug"], "second", "{backend}");
+
+        let posts = list(&app).await;
+        assert_eq!(posts.as_array().map(Vec::len), Some(2), "{backend}");
+        assert_eq!(posts[0]["title"], "Hello again", "{backend}");
+        assert_eq!(posts[0]["tags"], json!(["rust"]), "{backend}");
+        assert_eq!(posts[0]["draft"], true, "{backend}");
+        assert_eq!(
+            posts[0]["published_at"], "2025-03-01T00:00:00Z",
+            "{backend}: kept when the file has no date"
+        );
+
+        let (status, _) = import(&app, "/api/v1/posts/import", b"no front matter".to_vec()).await;
+        assert_eq!(status, StatusCode::MULTI_STATUS, "{backend}");
+        let (status, _) = import(&app, "/api/v1/posts/import", b"PK\x03\x04oops".to_vec()).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{backend}");
+    }
+}
+
+#[tokio::test]
+async fn markdown_uploads_are_checked_as_a_whole() {
+    for (backend, app) in apps().await {
+        let mut archive = ZipWriter::new(Cursor::new(Vec::new()));
+        for name in ["first/hello-world.md", "second/hello-world.md"] {
+            archive
+                .start_file(name, SimpleFileOptions::default())
+                .expect("zip entry");
+            archive.write_all(HELLO.as_bytes()).expect("zip data");
+        }
+        let zip = archive.finish().expect("zip").into_inner();
+
+        let (status, report) = import(&app, "/api/v1/posts/import", zip).await;
+        assert_eq!(status, StatusCode::MULTI_STATUS, "{backend}: {report}");
+        assert_eq!(statuses(&report), ["created", "invalid"], "{backend}");
+        assert_eq!(
+            report["results"][1]["error"],
+            "slug: `hello-world` is already taken by an earlier file of the upload",
+            "{backend}"
+        );
+        assert_eq!(
+            list(&app).await.as_array().map(Vec::len),
+            Some(1),
+            "{backend}"
+        );
+    }
+
+    // Files of the largest size, compressed once and copied, expand past the budget,
+    // whether or not they are text.
+    let size = usize::try_from(MAX_FILE_BYTES).expect("size");
+    for byte in [b'a', 0xff] {
+        let mut one = ZipWriter::new(Cursor::new(Vec::new()));
+        one.start_file("post.md", SimpleFileOptions::default())
+            .expect("zip entry");
+        one.write_all(&vec![byte; size]).expect("zip data");
+        let mut one = ZipArchive::new(one.finish().expect("zip")).expect("zip");
+        let mut archive = ZipWriter::new(Cursor::new(Vec::new()));
+        for index in 0..=MAX_UNCOMPRESSED_BYTES / MAX_FILE_BYTES {
+            let entry = one.by_index(0).expect("zip entry");
+            archive
+                .raw_copy_file_rename(entry, format!("post-{index}.md"))
+                .expect("zip copy");
+        }
+        let zip = archive.finish().expect("zip").into_inner();
+        assert!(zip.len() < 1024 * 1024, "{} bytes", zip.len());
+        let err = archive::read_files(&zip, "", markdown::is_markdown).expect_err("zip bomb");
+        assert!(err.to_string().contains("may add up to at most"), "{err}");
+    }
+
+    // A file larger than the limit is reported, not cut short.
+    let files =
+        archive::read_files(&vec![b'a'; size + 1], "big.md", markdown::is_markdown).expect("files");
+    assert_eq!(
+        files[0].text,
+        Err(format!("the file is larger than {MAX_FILE_BYTES} bytes"))
+    );
+}
+
+#[tokio::test]
+async fn markdown_exports_round_trip() {
+    for (backend, app) in apps().await {
+        let image_id = upload_image(&app).await;
+        let seo = format!(
+            "---\ntitle: Search\ncategory: Web\ntags: [seo]\nfeatured_image_id: {image_id}\n\
+             meta_title: Searching well\ndescription: How to be found.\n\
+             canonical_url: https://example.com/search\nnoindex: true\n---\nFound.\n"
+        );
+        let mut archive = tar::Builder::new(GzEncoder::new(Vec::new(), Compression::default()));
+        for (name, text) in [
+            ("hello-world.md", HELLO),
+            (
+                "quoted.md",
+                "---\ntitle: 'Colons: and \"quotes\"'\ncategory: Go\ntags: [go, 'a, b']\ndraft: true\n---\n\n# Heading\n\nBody with --- inside.\n",
+            ),
+            ("search.md", &seo),
+        ] {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(text.len() as u64);
+            header.set_mode(0o644);
+            archive
+                .append_data(&mut header, name, text.as_bytes())
+                .expect("tar entry");
+        }
+        let tar_gz = archive.into_inner().expect("tar").finish().expect("gzip");
+
+        let (status, report) = import(&app, "/api/v1/posts/import", tar_gz).await;
+        assert_eq!(status, StatusCode::OK, "{backend}: {report}");
+        assert_eq!(report["created"], 3, "{backend}");
+        let before = list(&app).await;
+        assert_eq!(before[2]["featured_image_id"], image_id, "{backend}");
+        assert_eq!(before[2]["meta_title"], "Searching well", "{backend}");
+        assert_eq!(
+            before[2]["meta_description"], "How to be found.",
+            "{backend}"
+        );
+        assert_eq!(
+            before[2]["canonical_url"], "https://example.com/search",
+            "{backend}"
+        );
+        assert_eq!(before[2]["noindex"], true, "{backend}");
+
+        let request = Request::get("/api/v1/posts/export?format=markdown")
+            .body(Body::empty())
+            .expect("request");
+        let (status, _, exported) = send_request(&app, request).await;
+        assert_eq!(status, StatusCode::OK, "{backend}");
+        let mut files = Vec::new();
+        for entry in tar::Archive::new(exported.as_ref())
+            .entries()
+            .expect("entries")
+        {
+            let mut entry = entry.expect("entry");
+            let name = entry.path().expect("path").display().to_string();
+            let mut text = String::new();
+            entry.read_to_string(&mut text).expect("text");
+            files.push((name, text));
+        }
+        assert_eq!(files.len(), 3, "{backend}");
+        assert_eq!(files[0].0, "hello-world.md", "{backend}");
+        assert!(
+            files[0]
+                .1
+                .starts_with("---\ntitle: Hello, world\nslug: hello-world\n"),
+            "{backend}: {}",
+            files[0].1
+        );
+        assert!(
+            files[1]
+                .1
+                .ends_with("---\n\n# Heading\n\nBody with --- inside.\n"),
+            "{backend}: {}",
+            files[1].1
+        );
+        assert!(
+            files[2].1.contains(&format!(
+                "featured_image_id: {image_id}\nmeta_title: Searching well\n\
+                 description: How to be found.\ncanonical_url: https://example.com/search\n\
+                 noindex: true\n"
+            )),
+            "{backend}: {}",
+            files[2].1
+        );
+
+        let (status, report) = import(&app, "/api/v1/posts/import", exported.to_vec()).await;
+        assert_eq!(status, StatusCode::OK, "{backend}: {report}");
+        assert_eq!(
+            statuses(&report),
+            ["updated", "updated", "updated"],
+            "{backend}"
+        );
+        assert_eq!(
+            list(&app).await,
+            before,
+            "{backend}: unchanged by the round trip"
+        );
+    }
+}
+
+/// A WordPress export with two posts, a draft, a post whose slug is not ASCII, a
+/// post without a category, a trashed post, a page and an attachment.
+const WXR: &str = r#"<?xml version="1.0" encoding="UTF-8" ?>
+<rss version="2.0"
+    xmlns:excerpt="http://wordpress.org/export/1.2/excerpt/"
+    xmlns:content="http://purl.org/rss/1.0/modules/content/"
+    xmlns:dc="http://purl.org/dc/elements/1.1/"
+    xmlns:wp="http://wordpress.org/export/1.2/">
+<channel>
+    <title>Old blog</title>
+    <wp:wxr_version>1.2</wp:wxr_version>
+    <wp:author>
+        <wp:author_id>1</wp:author_id>
+        <wp:author_login><![CDATA[jane]]></wp:author_login>
+        <wp:author_display_name><![CDATA[Jane Doe]]></wp:author_display_name>
+    </wp:author>
+    <item>
+        <title><![CDATA[Hello, world]]></title>
+        <link>https://old.example.com/2025/03/01/hello-world/</link>
+        <dc:creator><![CDATA[jane]]></dc:creator>
+        <content:encoded><![CDATA[<!-- wp:paragraph -->
+<p>Hello <strong>world</strong> &amp; <a href="https://example.com">friends</a>.</p>
+<!-- /wp:paragraph -->
+
+<!-- wp:list -->
+<ul><li>one</li><li>two</li></ul>
+<!-- /wp:list -->]]></content:encoded>
+        <wp:post_id>10</wp:post_id>
+        <wp:post_date_gmt><![CDATA[2025-03-01 09:30:00]]></wp:post_date_gmt>
+        <wp:post_name><![CDATA[hello-world]]></wp:post_name>
+        <wp:status><![CDATA[publish]]></wp:status>
+        <wp:post_type><![CDATA[post]]></wp:post_type>
+        <category domain="category" nicename="rust"><![CDATA[Rust]]></category>
+        <category domain="post_tag" nicename="intro"><![CDATA[intro]]></category>
+        <category domain="category" nicename="news"><![CDATA[News]]></category>
+        <wp:postmeta>
+            <wp:meta_key><![CDATA[_edit_last]]></wp:meta_key>
+            <wp:meta_value><![CDATA[1]]></wp:meta_value>
+        </wp:postmeta>
+        <wp:comment>
+            <wp:comment_content><![CDATA[Nice post]]></wp:comment_content>
+        </wp:comment>
+    </item>
+    <item>
+        <title>Draft ideas</title>
+        <dc:creator><![CDATA[bob]]></dc:creator>
+        <content:encoded><![CDATA[First line
+second line
+
+Second paragraph]]></content:encoded>
+        <wp:post_id>11</wp:post_id>
+        <wp:post_date_gmt><![CDATA[0000-00-00 00:00:00]]></wp:post_date_gmt>
+        <wp:post_name><![CDATA[]]></wp:post_name>
+        <wp:status><![CDATA[draft]]></wp:status>
+        <wp:post_type><![CDATA[post]]></wp:post_type>
+        <category domain="category" nicename="uncategorized"><![CDATA[Uncategorized]]></category>
+    </item>
+    <item>
+        <title>Japan trip</title>
+        <link>https://old.example.com/?p=12</link>
+        <content:encoded><![CDATA[<p>Tokyo</p>]]></content:encoded>
+        <wp:post_id>12</wp:post_id>
+        <wp:post_name><![CDATA[%e6%97%a5%e6%9c%ac]]></wp:post_name>
+        <wp:status><![CDATA[publish]]></wp:status>
+        <wp:post_type><![CDATA[post]]></wp:post_type>
+        <category domain="category" nicename="travel"><![CDATA[Travel]]></category>
+    </item>
+    <item>
+        <title>No category</title>
+        <content:encoded><![CDATA[<p>Lost</p>]]></content:encoded>
+        <wp:post_name><![CDATA[no-category]]></wp:post_name>
+        <wp:status><![CDATA[publish]]></wp:status>
+        <wp:post_type><![CDATA[post]]></wp:post_type>
+    </item>
+    <item>
+        <title>Deleted</title>
+        <wp:post_name><![CDATA[deleted]]></wp:post_name>
+        <wp:status><![CDATA[trash]]></wp:status>
+        <wp:post_type><![CDATA[post]]></wp:post_type>
+    </item>
+    <item>
+        <title>About</title>
+        <wp:status><![CDATA[publish]]></wp:status>
+        <wp:post_type><![CDATA[page]]></wp:post_type>
+    </item>
+    <item>
+        <title>photo.jpg</title>
+        <wp:status><![CDATA[inherit]]></wp:status>
+        <wp:post_type><![CDATA[attachment]]></wp:post_type>
+    </item>
+</channel>
+</rss>
+"#;
+
+#[tokio::test]
+async fn wordpress_exports_are_imported_after_a_dry_run() {
+    for (backend, app) in apps().await {
+        let (status, report) = import(
+            &app,
+            "/api/v1/posts/import/wordpress?dry_run=true",
+            WXR.into(),
+        )
+        .await;
+        assert_eq!(status, StatusCode::MULTI_STATUS, "{backend}: {report}");
+        assert_eq!(report["dry_run"], true, "{backend}");
+        assert_eq!(
+            statuses(&report),
+            [
+                "created", "created", "created", "invalid", "skipped", "skipped", "skipped"
+            ],
+            "{backend}"
+        );
+        assert_eq!(
+            report["results"][0],
+            json!({
+                "title": "Hello, world",
+                "link": "https://old.example.com/2025/03/01/hello-world/",
+                "original_slug": "hello-world",
+                "slug": "hello-world",
+                "status": "created"
+            }),
+            "{backend}"
+        );
+        assert_eq!(report["results"][1]["slug"], "draft-ideas", "{backend}");
+        assert_eq!(report["results"][2]["original_slug"], "%e6%97%a5%e6%9c%ac");
+        assert_eq!(report["results"][2]["slug"], "japan-trip", "{backend}");
+        assert!(
+            report["results"][3]["reason"]
+                .as_str()
+                .expect("reason")
+                .contains("Category cannot be empty"),
+            "{backend}: {report}"
+        );
+        assert_eq!(
+            report["results"][5]["reason"], "post type `page` is not imported",
+            "{backend}"
+        );
+        assert_eq!(list(&app).await, json!([]), "{backend}: nothing stored");
+
+        let (status, report) = import(&app, "/api/v1/posts/import/wordpress", WXR.into()).await;
+        assert_eq!(status, StatusCode::MULTI_STATUS, "{backend}: {report}");
+        assert_eq!(
+            (&report["created"], &report["skipped"], &report["failed"]),
+            (&json!(3), &json!(3), &json!(1)),
+            "{backend}"
+        );
+        let posts = list(&app).await;
+        assert_eq!(
+            posts[0]["content"],
+            "Hello **world** & [friends](https://example.com).\n\n- one\n- two",
+            "{backend}"
+        );
+        assert_eq!(posts[0]["category"], "Rust", "{backend}");
+        assert_eq!(posts[0]["tags"], json!(["intro", "News"]), "{backend}");
+        assert_eq!(posts[0]["author"], "Jane Doe", "{backend}");
+        assert_eq!(posts[0]["draft"], false, "{backend}");
+        assert_eq!(
+            posts[0]["published_at"], "2025-03-01T09:30:00Z",
+            "{backend}"
+        );
+        assert_eq!(
+            posts[1]["content"], "First line\\\nsecond line\n\nSecond paragraph",
+            "{backend}"
+        );
+        assert_eq!(posts[1]["tags"], json!(["Uncategorized"]), "{backend}");
+        assert_eq!(posts[1]["author"], "bob", "{backend}");
+        assert_eq!(posts[1]["draft"], true, "{backend}");
+        assert_eq!(posts[1]["published_at"], Value::Null, "{backend}");
+
+        let (_, report) = import(
+            &app,
+            "/api/v1/posts/import/wordpress?dry_run=true",
+            WXR.into(),
+        )
+        .await;
+        assert_eq!(report["updated"], 3, "{backend}: {report}");
+        assert_eq!(report["results"][0]["id"], posts[0]["id"], "{backend}");
+
+        let (status, _) = import(
+            &app,
+            "/api/v1/posts/import/wordpress",
+            b"<rss><channel></channel></rss>".to_vec(),
+        )
+        .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{backend}");
+        let (status, _) = import(
+            &app,
+            "/api/v1/posts/import/wordpress",
+            b"<rss><channel>".to_vec(),
+        )
+        .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{backend}");
+    }
+}
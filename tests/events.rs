@@ -0,0 +1,341 @@
+mod common;
+
+use axum::{
+    Router,
+    body::{Body, BodyDataStream},
+    http::{Method, Request, StatusCode, header},
+};
+use blog_api::{
+    events::{EventsConfig, Notice, PostEvent, PostEvents, ResetReason},
+    model::{
+        blog::{BlogPost, BlogPostBody},
+        webhook::{WebhookEvent, WebhookPayload},
+    },
+    outbox::{Dispatcher, OutboxConfig},
+    repository::{PostFilter, PostRepository, memory::InMemoryPostRepository},
+    server::routes::setup_routes,
+};
+use common::send;
+use futures_util::StreamExt;
+use serde_json::{Value, json};
+use std::{pin::pin, sync::Arc, time::Duration};
+use tower::ServiceExt;
+
+/// Builds one application per backend, with its outbox dispatcher publishing to the
+/// live events of the application.
+async fn live_apps() -> Vec<(&'static str, Router)> {
+    let mut apps = Vec::new();
+    for backend in common::backends().await {
+        let state = backend.state();
+        Dispatcher::new(backend.outbox, outbox_config())
+            .with_consumer(Arc::new(state.events.clone()))
+            .spawn();
+        apps.push((backend.name, setup_routes(state)));
+    }
+    apps
+}
+
+/// Returns an outbox configuration polling quickly.
+fn outbox_config() -> OutboxConfig {
+    let mut config = OutboxConfig::from_env().expect("outbox config");
+    config.poll_interval = Duration::from_millis(10);
+    config
+}
+
+/// A valid request body for creating a post.
+fn post_body(title: &str, category: &str, draft: bool) -> Value {
+    json!({
+        "title": title,
+        "content": "Content",
+        "category": category,
+        "tags": ["rust"],
+        "draft": draft
+    })
+}
+
+/// Creates a post and returns its ID.
+async fn create(app: &Router, body: Value) -> i64 {
+    let (status, post) = send(app, Method::POST, "/api/v1/posts", Some(body)).await;
+    assert_eq!(status, StatusCode::CREATED, "{post}");
+    post["id"].as_i64().expect("post ID")
+}
+
+/// An event read from a stream.
+#[derive(Debug)]
+struct SseEvent {
+    /// The `id` field, if any.
+    id: Option<i64>,
+    /// The `event` field.
+    event: String,
+    /// The `data` field, as JSON.
+    data: Value,
+}
+
+/// A Server-Sent Events stream read from a response.
+struct EventStream {
+    /// The body of the response.
+    body: BodyDataStream,
+    /// Text received but not parsed yet.
+    pending: String,
+}
+
+impl EventStream {
+    /// Opens the stream at `uri`, resuming after `last_event_id` if set.
+    async fn open(app: &Router, uri: &str, last_event_id: Option<&str>) -> Self {
+        let mut request = Request::builder().uri(uri);
+        if let Some(id) = last_event_id {
+            request = request.header("Last-Event-ID", id);
+        }
+        let response = app
+            .clone()
+            .oneshot(request.body(Body::empty()).expect("request"))
+            .await
+            .expect("response");
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.headers()[header::CONTENT_TYPE],
+            "text/event-stream"
+        );
+        Self {
+            body: response.into_body().into_data_stream(),
+            pending: String::new(),
+        }
+    }
+
+    /// Returns the next event, skipping comments, failing if none arrives in time.
+    async fn next(&mut self) -> SseEvent {
+        loop {
+            if let Some(end) = self.pending.find("\n\n") {
+                let block: String = self.pending.drain(..end + 2).collect();
+                let mut id = None;
+                let mut event = None;
+                let mut data = None;
+                for line in block.lines() {
+                    if let Some(value) = line.strip_prefix("id: ") {
+                        id = Some(value.parse().expect("event ID"));
+                    } else if let Some(value) = line.strip_prefix("event: ") {
+                        event = Some(value.to_string());
+                    } else if let Some(value) = line.strip_prefix("data: ") {
+                        data = Some(serde_json::from_str(value).expect("JSON data"));
+                    }
+                }
+                if let (Some(event), Some(data)) = (event, data) {
+                    return SseEvent { id, event, data };
+                }
+                continue;
+            }
+            let chunk = tokio::time::timeout(Duration::from_secs(5), self.body.next())
+                .await
+                .expect("an event in time")
+                .expect("an open stream")
+                .expect("a chunk");
+            self.pending
+                .push_str(std::str::from_utf8(&chunk).expect("UTF-8"));
+        }
+    }
+
+    /// Returns the next `count` events.
+    async fn take(&mut self, count: usize) -> Vec<SseEvent> {
+        let mut events = Vec::with_capacity(count);
+        for _ in 0..count {
+            events.push(self.next().await);
+        }
+        events
+    }
+}
+
+/// Returns the names and post IDs of events.
+fn summary(events: &[SseEvent]) -> Vec<(&str, i64)> {
+    events
+        .iter()
+        .map(|event| {
+            (
+                event.event.as_str(),
+                event.data["post"]["id"].as_i64().expect("post ID"),
+            )
+        })
+        .collect()
+}
+
+#[tokio::test]
+async fn changes_are_streamed_to_subscribers_passing_the_filter() {
+    for (backend, app) in live_apps().await {
+        let mut everything = EventStream::open(&app, "/api/v1/events", None).await;
+        let mut rust = EventStream::open(&app, "/api/v1/events?category=Rust", None).await;
+
+        let go = create(&app, post_body("Go", "Go", true)).await;
+        let id = create(&app, post_body("Rust", "Rust", true)).await;
+        let (status, _) = send(
+            &app,
+            Method::PUT,
+            &format!("/api/v1/posts/{id}"),
+            Some(post_body("Rust", "Rust", false)),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK, "{backend}");
+        let request = Request::builder()
+            .method(Method::DELETE)
+            .uri(format!("/api/v1/posts/{id}"))
+            .body(Body::empty())
+            .expect("request");
+        let response = app.clone().oneshot(request).await.expect("response");
+        assert_eq!(response.status(), StatusCode::NO_CONTENT, "{backend}");
+
+        let events = everything.take(5).await;
+        let mut received = summary(&events);
+        // Events of different posts may be dispatched in any order.
+        received.sort_by_key(|(_, post_id)| *post_id == id);
+        assert_eq!(
+            received,
+            vec![
+                ("post.created", go),
+                ("post.created", id),
+                ("post.updated", id),
+                ("post.published", id),
+                ("post.deleted", id),
+            ],
+            "{backend}"
+        );
+        let event = events
+            .iter()
+            .find(|event| event.event == "post.published")
+            .expect("published event");
+        assert!(event.id.is_some(), "{backend}");
+        assert_eq!(event.data["event"], "post.published", "{backend}");
+        assert_eq!(event.data["post"]["title"], "Rust", "{backend}");
+        assert!(event.data["occurred_at"].is_string(), "{backend}");
+
+        let events = rust.take(4).await;
+        assert_eq!(
+            summary(&events),
+            vec![
+                ("post.created", id),
+                ("post.updated", id),
+                ("post.published", id),
+                ("post.deleted", id),
+            ],
+            "{backend}"
+        );
+    }
+}
+
+#[tokio::test]
+async fn streams_resume_after_the_last_event_id() {
+    for (backend, app) in live_apps().await {
+        let mut stream = EventStream::open(&app, "/api/v1/events", None).await;
+        let id = create(&app, post_body("Rust", "Rust", true)).await;
+        let (status, _) = send(
+            &app,
+            Method::PUT,
+            &format!("/api/v1/posts/{id}"),
+            Some(post_body("Rust", "Rust", false)),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK, "{backend}");
+        let events = stream.take(3).await;
+        let first = events[0].id.expect("event ID").to_string();
+
+        let mut resumed = EventStream::open(&app, "/api/v1/events", Some(&first)).await;
+        let replayed = resumed.take(2).await;
+        assert_eq!(
+            replayed.iter().map(|event| event.id).collect::<Vec<_>>(),
+            events[1..].iter().map(|event| event.id).collect::<Vec<_>>(),
+            "{backend}"
+        );
+        // The stream goes on with new events after the replay.
+        let other = create(&app, post_body("Go", "Go", true)).await;
+        assert_eq!(
+            summary(&[resumed.next().await]),
+            vec![("post.created", other)],
+            "{backend}"
+        );
+
+        // A subscriber resuming from an event that is no longer kept starts over.
+        let mut expired = EventStream::open(&app, "/api/v1/events", Some("999999")).await;
+        let reset = expired.next().await;
+        assert_eq!(reset.event, "reset", "{backend}");
+        assert_eq!(reset.id, None, "{backend}");
+        assert_eq!(reset.data, json!({ "reason": "expired" }), "{backend}");
+
+        let request = Request::builder()
+            .uri("/api/v1/events")
+            .header("Last-Event-ID", "latest")
+            .body(Body::empty())
+            .expect("request");
+        let response = app.clone().oneshot(request).await.expect("response");
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST, "{backend}");
+    }
+}
+
+/// Returns an event about `post`, with the given outbox ID.
+fn event(id: i64, post: &BlogPost) -> PostEvent {
+    PostEvent {
+        id,
+        payload: WebhookPayload {
+            event: WebhookEvent::PostUpdated,
+            occurred_at: chrono::Utc::now(),
+            post: post.clone(),
+        },
+    }
+}
+
+/// Returns the outbox ID of an event notice, or the reason of a reset.
+fn notice_id(notice: &Notice) -> Result<i64, ResetReason> {
+    match notice {
+        Notice::Event(event) => Ok(event.id),
+        Notice::Reset(reason) => Err(*reason),
+    }
+}
+
+#[tokio::test]
+async fn subscribers_are_reset_when_they_may_have_missed_events() {
+    let mut config = EventsConfig::from_env().expect("events config");
+    config.channel_capacity = 2;
+    config.replay_capacity = 3;
+    let events = PostEvents::new(config);
+    let post = InMemoryPostRepository::new()
+        .create(&BlogPostBody {
+            title: "Rust".to_string(),
+            content: "Content".to_string(),
+            category: "Rust".to_string(),
+            ..BlogPostBody::default()
+        })
+        .await
+        .expect("create");
+
+    let mut slow = pin!(events.subscribe(None, PostFilter::default()));
+    for id in 1..=4 {
+        events.publish(event(id, &post));
+    }
+    // An event dispatched again is not sent twice.
+    events.publish(event(4, &post));
+    // The slow subscriber missed the oldest events, and is told so.
+    assert_eq!(
+        notice_id(&slow.next().await.expect("notice")),
+        Err(ResetReason::Lagged)
+    );
+    assert_eq!(notice_id(&slow.next().await.expect("notice")), Ok(3));
+    assert_eq!(notice_id(&slow.next().await.expect("notice")), Ok(4));
+
+    // Only the most recent events are kept for replay.
+    let mut resumed = pin!(events.subscribe(Some(2), PostFilter::default()));
+    assert_eq!(notice_id(&resumed.next().await.expect("notice")), Ok(3));
+    assert_eq!(notice_id(&resumed.next().await.expect("notice")), Ok(4));
+    let mut expired = pin!(events.subscribe(Some(1), PostFilter::default()));
+    assert_eq!(
+        notice_id(&expired.next().await.expect("notice")),
+        Err(ResetReason::Expired)
+    );
+
+    // After an interruption, every subscriber is reset and nothing can be replayed.
+    events.reset();
+    assert_eq!(
+        notice_id(&resumed.next().await.expect("notice")),
+        Err(ResetReason::Interrupted)
+    );
+    let mut after = pin!(events.subscribe(Some(4), PostFilter::default()));
+    assert_eq!(
+        notice_id(&after.next().await.expect("notice")),
+        Err(ResetReason::Expired)
+    );
+}
@@ -0,0 +1,421 @@
+mod common;
+
+use async_trait::async_trait;
+use axum::{
+    Router,
+    body::{Body, Bytes, to_bytes},
+    http::{HeaderMap, Request, StatusCode, header},
+};
+use blog_api::{
+    error::AppError,
+    model::blog::{BlogPost, BlogPostBody},
+    repository::{
+        PostFilter, PostRepository, Reassignment, Upserted, memory::InMemoryPostRepository,
+    },
+    server::{
+        middleware::http::{CorsOrigins, HttpConfig, SECURITY_HEADERS},
+        routes::setup_routes,
+    },
+};
+use chrono::{DateTime, Utc};
+use flate2::{Compression, read::GzDecoder, write::GzEncoder};
+use futures_util::{StreamExt, stream, stream::BoxStream};
+use serde_json::{Value, json};
+use std::{
+    io::{Read, Write},
+    sync::Arc,
+    time::Duration,
+};
+use tower::ServiceExt;
+
+/// How long [`SlowSearch`] takes to search, well above the search timeout of the tests.
+const SEARCH_DELAY: Duration = Duration::from_secs(2);
+
+/// Boundary of the multipart bodies sent by the tests.
+const BOUNDARY: &str = "http-test-boundary";
+
+/// Origin allowed by the CORS configuration of the tests.
+const ORIGIN: &str = "https://blog.example";
+
+/// Posts stored in memory, with a search that takes [`SEARCH_DELAY`].
+struct SlowSearch(InMemoryPostRepository);
+
+#[async_trait]
+impl PostRepository for SlowSearch {
+    async fn create(&self, post: &BlogPostBody) -> Result<BlogPost, AppError> {
+        self.0.create(post).await
+    }
+
+    async fn get(&self, id: i32) -> Result<Option<BlogPost>, AppError> {
+        self.0.get(id).await
+    }
+
+    async fn get_by_slug(&self, slug: &str) -> Result<Option<BlogPost>, AppError> {
+        self.0.get_by_slug(slug).await
+    }
+
+    async fn list(&self, filter: &PostFilter) -> Result<Vec<BlogPost>, AppError> {
+        self.0.list(filter).await
+    }
+
+    async fn create_many(&self, posts: &[BlogPostBody]) -> Result<Vec<BlogPost>, AppError> {
+        self.0.create_many(posts).await
+    }
+
+    fn stream(&self, filter: &PostFilter) -> BoxStream<'static, Result<BlogPost, AppError>> {
+        self.0.stream(filter)
+    }
+
+    async fn update(&self, id: i32, post: &BlogPostBody) -> Result<Option<BlogPost>, AppError> {
+        self.0.update(id, post).await
+    }
+
+    async fn upsert_by_slug(&self, post: &BlogPostBody) -> Result<Upserted, AppError> {
+        self.0.upsert_by_slug(post).await
+    }
+
+    async fn delete(&self, id: i32) -> Result<bool, AppError> {
+        self.0.delete(id).await
+    }
+
+    async fn reassign(
+        &self,
+        filter: &PostFilter,
+        changes: &Reassignment,
+        except: &[i32],
+    ) -> Result<Vec<i32>, AppError> {
+        self.0.reassign(filter, changes, except).await
+    }
+
+    async fn delete_many(&self, ids: &[i32]) -> Result<Vec<i32>, AppError> {
+        self.0.delete_many(ids).await
+    }
+
+    async fn search(&self, term: &str) -> Result<Vec<BlogPost>, AppError> {
+        tokio::time::sleep(SEARCH_DELAY).await;
+        self.0.search(term).await
+    }
+
+    async fn announce_scheduled(&self, now: DateTime<Utc>) -> Result<Vec<BlogPost>, AppError> {
+        self.0.announce_scheduled(now).await
+    }
+
+    async fn rebuild_search_index(&self) -> Result<(), AppError> {
+        self.0.rebuild_search_index().await
+    }
+}
+
+/// Builds an application with empty, slowly searched storage and the HTTP
+/// configuration of the environment as changed by `configure`.
+fn app_with(configure: impl FnOnce(&mut HttpConfig)) -> Router {
+    let mut state = common::state_with(Arc::new(SlowSearch(InMemoryPostRepository::new())));
+    let mut http = HttpConfig::from_env().expect("http config");
+    configure(&mut http);
+    state.http = Arc::new(http);
+    setup_routes(state)
+}
+
+/// Sends a request and returns the status, headers and body of the response.
+async fn send(app: &Router, request: Request<Body>) -> (StatusCode, HeaderMap, Bytes) {
+    let response = app.clone().oneshot(request).await.expect("response");
+    let status = response.status();
+    let headers = response.headers().clone();
+    let body = to_bytes(response.into_body(), usize::MAX)
+        .await
+        .expect("body");
+    (status, headers, body)
+}
+
+/// A request creating a post whose content is `content_len` bytes long.
+fn create_post(content_len: usize) -> Request<Body> {
+    let post = json!({
+        "title": "Padded",
+        "content": "x".repeat(content_len),
+        "category": "Rust",
+        "tags": ["padding"],
+    });
+    Request::post("/api/v1/posts")
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Body::from(post.to_string()))
+        .expect("request")
+}
+
+/// A request uploading `len` bytes of plain text as a media file.
+fn upload(len: usize) -> Request<Body> {
+    let mut body = format!(
+        "--{BOUNDARY}\r\nContent-Disposition: form-data; name=\"file\"; \
+         filename=\"notes.txt\"\r\nContent-Type: text/plain\r\n\r\n"
+    )
+    .into_bytes();
+    body.extend(vec![b'x'; len]);
+    body.extend(format!("\r\n--{BOUNDARY}--\r\n").bytes());
+    Request::post("/api/v1/media")
+        .header(
+            header::CONTENT_TYPE,
+            format!("multipart/form-data; boundary={BOUNDARY}"),
+        )
+        .body(Body::from(body))
+        .expect("request")
+}
+
+/// A `GET` request with the given headers.
+fn get(uri: &str, headers: &[(header::HeaderName, &str)]) -> Request<Body> {
+    let mut request = Request::get(uri);
+    for (name, value) in headers {
+        request = request.header(name, *value);
+    }
+    request.body(Body::empty()).expect("request")
+}
+
+/// Returns the `error` message of a JSON error body.
+fn error_message(body: &[u8]) -> String {
+    let json: Value = serde_json::from_slice(body).expect("JSON error body");
+    json["error"].as_str().expect("error message").to_string()
+}
+
+/// Returns a header of a response as a string, if set.
+fn header_value<'a>(headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+    headers.get(name).and_then(|value| value.to_str().ok())
+}
+
+#[tokio::test]
+async fn request_bodies_above_the_limit_are_rejected() {
+    let app = app_with(|http| {
+        http.max_body_bytes = 1024;
+        http.max_upload_bytes = 4096;
+    });
+
+    let (status, _, _) = send(&app, create_post(100)).await;
+    assert_eq!(status, StatusCode::CREATED);
+
+    let (status, _, body) = send(&app, create_post(2000)).await;
+    assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
+    assert_eq!(
+        error_message(&body),
+        "Request body exceeds the limit of 1024 bytes"
+    );
+
+    // The limit applies to the decompressed body, not to what is sent.
+    let post = json!({
+        "title": "Compressed",
+        "content": "x".repeat(2000),
+        "category": "Rust",
+        "tags": ["padding"],
+    });
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder
+        .write_all(post.to_string().as_bytes())
+        .expect("compress");
+    let compressed = encoder.finish().expect("compress");
+    assert!(compressed.len() < 1024);
+    let request = Request::post("/api/v1/posts")
+        .header(header::CONTENT_TYPE, "application/json")
+        .header(header::CONTENT_ENCODING, "gzip")
+        .body(Body::from(compressed))
+        .expect("request");
+    let (status, _, _) = send(&app, request).await;
+    assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
+
+    // Media uploads have a limit of their own.
+    let (status, _, _) = send(&app, upload(2000)).await;
+    assert_eq!(status, StatusCode::UNSUPPORTED_MEDIA_TYPE);
+    let (status, _, body) = send(&app, upload(5000)).await;
+    assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
+    assert_eq!(
+        error_message(&body),
+        "Request body exceeds the limit of 4096 bytes"
+    );
+}
+
+#[tokio::test]
+async fn bodies_sent_too_slowly_time_out() {
+    let app = app_with(|http| http.body_timeout = Duration::from_millis(100));
+
+    let chunks = stream::once(async { Ok::<_, std::io::Error>(Bytes::from_static(b"{\"title\"")) })
+        .chain(stream::pending());
+    let request = Request::post("/api/v1/posts")
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Body::from_stream(chunks))
+        .expect("request");
+    let (status, _, body) = send(&app, request).await;
+    assert_eq!(status, StatusCode::REQUEST_TIMEOUT);
+    assert_eq!(
+        error_message(&body),
+        "The request was not received in time."
+    );
+}
+
+#[tokio::test]
+async fn handlers_time_out_by_class_of_route() {
+    let app = app_with(|http| {
+        http.timeouts.read = Duration::from_secs(10);
+        http.timeouts.search = Duration::from_millis(100);
+    });
+
+    let (status, _, body) = send(&app, get("/api/v1/posts/search?term=rust", &[])).await;
+    assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(error_message(&body), "The request took too long to process");
+
+    let (status, _, _) = send(&app, get("/api/v1/posts", &[])).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let app = app_with(|http| http.timeouts.search = SEARCH_DELAY * 5);
+    send(&app, create_post(10)).await;
+    let (status, _, _) = send(&app, get("/api/v1/posts/search?term=padded", &[])).await;
+    assert_eq!(status, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn cors_preflights_follow_the_allowed_origins() {
+    let app = app_with(|http| {
+        http.cors_origins = Some(CorsOrigins::List(vec![ORIGIN.parse().expect("origin")]));
+    });
+    let preflight = |origin: &str| {
+        Request::options("/api/v1/posts/1")
+            .header(header::ORIGIN, origin)
+            .header(header::ACCESS_CONTROL_REQUEST_METHOD, "PUT")
+            .header(
+                header::ACCESS_CONTROL_REQUEST_HEADERS,
+                "content-type, idempotency-key, edit-lock",
+            )
+            .body(Body::empty())
+            .expect("request")
+    };
+
+    let (status, headers, _) = send(&app, preflight(ORIGIN)).await;
+    assert!(status.is_success(), "{status}");
+    assert_eq!(
+        header_value(&headers, "access-control-allow-origin"),
+        Some(ORIGIN)
+    );
+    let methods = header_value(&headers, "access-control-allow-methods").expect("methods");
+    assert!(methods.contains("PUT"), "{methods}");
+    let allowed = header_value(&headers, "access-control-allow-headers").expect("headers");
+    for name in ["content-type", "idempotency-key", "edit-lock"] {
+        assert!(allowed.contains(name), "{name} in {allowed}");
+    }
+    assert_eq!(
+        header_value(&headers, "access-control-max-age"),
+        Some("3600")
+    );
+
+    let (_, headers, _) = send(&app, preflight("https://elsewhere.example")).await;
+    assert_eq!(header_value(&headers, "access-control-allow-origin"), None);
+
+    let (status, headers, _) = send(&app, get("/api/v1/posts", &[(header::ORIGIN, ORIGIN)])).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(
+        header_value(&headers, "access-control-allow-origin"),
+        Some(ORIGIN)
+    );
+    let exposed = header_value(&headers, "access-control-expose-headers").expect("exposed");
+    for name in [
+        "x-request-id",
+        "retry-after",
+        "deprecation",
+        "idempotent-replayed",
+    ] {
+        assert!(exposed.contains(name), "{name} in {exposed}");
+    }
+
+    // Without configured origins, browsers keep blocking cross-origin requests.
+    let app = app_with(|http| http.cors_origins = None);
+    let (_, headers, _) = send(&app, preflight(ORIGIN)).await;
+    assert_eq!(header_value(&headers, "access-control-allow-origin"), None);
+}
+
+#[tokio::test]
+async fn responses_are_compressed_as_negotiated() {
+    let app = app_with(|http| http.compression = true);
+    for _ in 0..5 {
+        let (status, _, _) = send(&app, create_post(200)).await;
+        assert_eq!(status, StatusCode::CREATED);
+    }
+
+    for (accept, expected) in [
+        ("zstd", Some("zstd")),
+        ("br", Some("br")),
+        ("gzip", Some("gzip")),
+        ("gzip;q=0.5, br", Some("br")),
+        ("identity", None),
+    ] {
+        let (status, headers, _) = send(
+            &app,
+            get("/api/v1/posts", &[(header::ACCEPT_ENCODING, accept)]),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK, "{accept}");
+        assert_eq!(
+            header_value(&headers, "content-encoding"),
+            expected,
+            "{accept}"
+        );
+    }
+
+    let (_, headers, body) = send(
+        &app,
+        get("/api/v1/posts", &[(header::ACCEPT_ENCODING, "gzip")]),
+    )
+    .await;
+    let vary = header_value(&headers, "vary").expect("vary");
+    assert!(vary.contains("accept-encoding"), "{vary}");
+    let mut json = String::new();
+    GzDecoder::new(&body[..])
+        .read_to_string(&mut json)
+        .expect("gzip body");
+    let posts: Value = serde_json::from_str(&json).expect("JSON body");
+    assert_eq!(posts.as_array().map(Vec::len), Some(5));
+
+    let (_, headers, _) = send(&app, get("/api/v1/posts", &[])).await;
+    assert_eq!(header_value(&headers, "content-encoding"), None);
+
+    let app = app_with(|http| http.compression = false);
+    send(&app, create_post(2000)).await;
+    let (_, headers, _) = send(
+        &app,
+        get("/api/v1/posts", &[(header::ACCEPT_ENCODING, "gzip")]),
+    )
+    .await;
+    assert_eq!(header_value(&headers, "content-encoding"), None);
+}
+
+#[tokio::test]
+async fn security_headers_are_added_to_every_response() {
+    let app = app_with(|http| {
+        http.security_headers = true;
+        http.hsts_max_age = Some(600);
+    });
+    for uri in ["/api/v1/posts", "/api/v1/posts/999"] {
+        let (_, headers, _) = send(&app, get(uri, &[])).await;
+        for (name, value) in &SECURITY_HEADERS {
+            assert_eq!(
+                header_value(&headers, name.as_str()),
+                Some(*value),
+                "{uri}: {name}"
+            );
+        }
+        assert_eq!(
+            header_value(&headers, "strict-transport-security"),
+            Some("max-age=600; includeSubDomains"),
+            "{uri}"
+        );
+    }
+
+    let app = app_with(|http| {
+        http.security_headers = true;
+        http.hsts_max_age = None;
+    });
+    let (_, headers, _) = send(&app, get("/api/v1/posts", &[])).await;
+    assert!(headers.contains_key(header::X_CONTENT_TYPE_OPTIONS));
+    assert!(!headers.contains_key(header::STRICT_TRANSPORT_SECURITY));
+
+    let app = app_with(|http| {
+        http.security_headers = false;
+        http.hsts_max_age = Some(600);
+    });
+    let (_, headers, _) = send(&app, get("/api/v1/posts", &[])).await;
+    for (name, _) in &SECURITY_HEADERS {
+        assert!(!headers.contains_key(name), "{name}");
+    }
+    assert!(!headers.contains_key(header::STRICT_TRANSPORT_SECURITY));
+}
@@ -0,0 +1,217 @@
+mod common;
+
+use axum::{
+    Router,
+    http::{HeaderMap, Method, StatusCode, header},
+};
+use blog_api::{
+    idempotency::{Claim, Idempotency, IdempotencyConfig, StoredResponse},
+    server::routes::setup_routes,
+};
+use common::{Backend, send, send_with};
+use serde_json::{Value, json};
+use std::time::Duration;
+
+/// Builds one application per backend, each keeping idempotency keys for `ttl` and
+/// storing response bodies of up to `max_response_bytes`.
+async fn apps_with_keys(ttl: Duration, max_response_bytes: usize) -> Vec<(&'static str, Router)> {
+    let mut config = IdempotencyConfig::from_env().expect("idempotency config");
+    config.ttl = ttl;
+    config.max_response_bytes = max_response_bytes;
+
+    let mut apps = Vec::new();
+    for backend in common::backends().await {
+        let mut state = backend.state();
+        state.idempotency = Idempotency::new(backend.idempotency, config.clone());
+        apps.push((backend.name, setup_routes(state)));
+    }
+    apps
+}
+
+/// Creates a post titled `title`, with the idempotency key `key` if set, and returns
+/// the status, headers and JSON response.
+async fn create(app: &Router, key: Option<&str>, title: &str) -> (StatusCode, HeaderMap, Value) {
+    let headers: Vec<_> = key
+        .map(|key| ("idempotency-key", key))
+        .into_iter()
+        .collect();
+    send_with(
+        app,
+        Method::POST,
+        "/api/v1/posts",
+        Some(post(title)),
+        &headers,
+    )
+    .await
+}
+
+/// A valid request body for creating a post.
+fn post(title: &str) -> Value {
+    json!({ "title": title, "content": "Some content", "category": "Rust", "tags": ["rust"] })
+}
+
+#[tokio::test]
+async fn repeated_requests_replay_the_stored_response() {
+    for (backend, app) in apps_with_keys(Duration::from_secs(60), 1024 * 1024).await {
+        let (status, headers, first) = create(&app, Some("retry-1"), "Async Rust").await;
+        assert_eq!(status, StatusCode::CREATED, "{backend}");
+        assert!(!headers.contains_key("idempotent-replayed"), "{backend}");
+
+        let (status, headers, replayed) = create(&app, Some("retry-1"), "Async Rust").await;
+        assert_eq!(status, StatusCode::CREATED, "{backend}");
+        assert_eq!(replayed, first, "{backend}");
+        assert_eq!(headers["idempotent-replayed"], "true", "{backend}");
+        assert_eq!(
+            headers[header::CONTENT_TYPE],
+            "application/json",
+            "{backend}"
+        );
+
+        let (status, _, error) = create(&app, Some("retry-1"), "Something else").await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY, "{backend}");
+        assert!(
+            error["error"]
+                .as_str()
+                .expect("error")
+                .contains("different request")
+        );
+
+        let (_, _, other) = create(&app, Some("retry-2"), "Async Rust").await;
+        assert_eq!(other["id"], 2, "{backend}: keys are independent");
+        let (_, _, unkeyed) = create(&app, None, "Async Rust").await;
+        assert_eq!(
+            unkeyed["id"], 3,
+            "{backend}: requests without a key are not deduplicated"
+        );
+
+        let (_, all) = send(&app, Method::GET, "/api/v1/posts", None).await;
+        assert_eq!(all.as_array().expect("posts").len(), 3, "{backend}");
+
+        let (status, _, _) = send_with(
+            &app,
+            Method::DELETE,
+            "/api/v1/posts/1",
+            None,
+            &[("idempotency-key", "del-1")],
+        )
+        .await;
+        assert_eq!(status, StatusCode::NO_CONTENT, "{backend}");
+        let (status, headers, _) = send_with(
+            &app,
+            Method::DELETE,
+            "/api/v1/posts/1",
+            None,
+            &[("idempotency-key", "del-1")],
+        )
+        .await;
+        assert_eq!(status, StatusCode::NO_CONTENT, "{backend}: not 404");
+        assert_eq!(headers["idempotent-replayed"], "true", "{backend}");
+
+        let long_key = "k".repeat(256);
+        let (status, _, _) = create(&app, Some(&long_key), "Async Rust").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{backend}");
+    }
+}
+
+#[tokio::test]
+async fn responses_too_large_to_store_are_replayed_without_their_body() {
+    for (backend, app) in apps_with_keys(Duration::from_secs(60), 16).await {
+        let (status, headers, created) = create(&app, Some("large-1"), "Async Rust").await;
+        assert_eq!(status, StatusCode::CREATED, "{backend}");
+        assert_eq!(created["id"], 1, "{backend}");
+        assert!(!headers.contains_key("idempotent-replayed"), "{backend}");
+
+        let (status, headers, replayed) = create(&app, Some("large-1"), "Async Rust").await;
+        assert_eq!(status, StatusCode::CREATED, "{backend}");
+        assert_eq!(headers["idempotent-replayed"], "true", "{backend}");
+        assert!(!headers.contains_key(header::CONTENT_TYPE), "{backend}");
+        assert_eq!(replayed, Value::Null, "{backend}");
+
+        let (_, all) = send(&app, Method::GET, "/api/v1/posts", None).await;
+        assert_eq!(all.as_array().expect("posts").len(), 1, "{backend}");
+    }
+}
+
+#[tokio::test]
+async fn expired_keys_can_be_reused() {
+    for (backend, app) in apps_with_keys(Duration::ZERO, 1024 * 1024).await {
+        for (title, id) in [("Async Rust", 1), ("Ownership", 2)] {
+            let (status, headers, created) = create(&app, Some("retry-1"), title).await;
+            assert_eq!(status, StatusCode::CREATED, "{backend}");
+            assert_eq!(created["id"], id, "{backend}");
+            assert!(!headers.contains_key("idempotent-replayed"), "{backend}");
+        }
+    }
+}
+
+#[tokio::test]
+async fn stores_lock_keys_until_completed_or_released() {
+    let later = chrono::Utc::now() + chrono::Duration::minutes(1);
+    let earlier = chrono::Utc::now() - chrono::Duration::minutes(1);
+
+    for Backend {
+        name: backend,
+        idempotency: store,
+        ..
+    } in common::backends().await
+    {
+        let claim = |fingerprint| store.claim("ip:test", "key", fingerprint, later);
+        assert_eq!(
+            claim("a").await.expect("claim"),
+            Claim::Acquired,
+            "{backend}"
+        );
+        assert_eq!(
+            claim("a").await.expect("claim"),
+            Claim::InProgress {
+                fingerprint: "a".to_string()
+            },
+            "{backend}"
+        );
+
+        store.release("ip:test", "key").await.expect("release");
+        assert_eq!(
+            claim("b").await.expect("claim"),
+            Claim::Acquired,
+            "{backend}"
+        );
+
+        let response = StoredResponse {
+            status: 201,
+            headers: vec![("content-type".to_string(), "application/json".to_string())],
+            body: b"{}".to_vec(),
+        };
+        store
+            .complete("ip:test", "key", &response, later)
+            .await
+            .expect("complete");
+        store.release("ip:test", "key").await.expect("release");
+        assert_eq!(
+            claim("b").await.expect("claim"),
+            Claim::Completed {
+                fingerprint: "b".to_string(),
+                response: response.clone()
+            },
+            "{backend}: completed keys are not released"
+        );
+        assert_eq!(
+            store
+                .claim("ip:other", "key", "b", later)
+                .await
+                .expect("claim"),
+            Claim::Acquired,
+            "{backend}: keys are scoped"
+        );
+
+        store
+            .complete("ip:test", "key", &response, earlier)
+            .await
+            .expect("complete");
+        assert_eq!(store.purge_expired().await.expect("purge"), 1, "{backend}");
+        assert_eq!(
+            claim("c").await.expect("claim"),
+            Claim::Acquired,
+            "{backend}"
+        );
+    }
+}
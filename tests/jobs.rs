@@ -0,0 +1,427 @@
+mod common;
+
+use async_trait::async_trait;
+use axum::http::{Method, StatusCode};
+use blog_api::{
+    error::AppError,
+    jobs::{DynJobRepository, JobHandler, Jobs, JobsConfig},
+    model::job::{Job, JobOutcome, JobStatus, NewJob},
+    repository::memory::InMemoryPostRepository,
+    server::routes::setup_routes,
+};
+use chrono::{Duration as TimeDelta, Utc};
+use common::{send, send_admin};
+use croner::Cron;
+use serde_json::json;
+use std::{
+    collections::HashSet,
+    sync::{
+        Arc,
+        atomic::{AtomicU32, Ordering},
+    },
+    time::Duration,
+};
+
+/// Fails the first `payload` attempts of each job, then succeeds.
+#[derive(Clone, Default)]
+struct Flaky {
+    /// Attempts run so far, across jobs.
+    runs: Arc<AtomicU32>,
+}
+
+#[async_trait]
+impl JobHandler for Flaky {
+    const KIND: &'static str = "test.flaky";
+    type Payload = u32;
+
+    async fn run(&self, failures: u32) -> Result<(), AppError> {
+        if self.runs.fetch_add(1, Ordering::SeqCst) < failures {
+            return Err(AppError::Conflict("Not yet".to_string()));
+        }
+        Ok(())
+    }
+}
+
+/// Sleeps for a while, recording how many jobs run at once.
+#[derive(Clone, Default)]
+struct Slow {
+    /// Jobs running now.
+    running: Arc<AtomicU32>,
+    /// Most jobs seen running at once.
+    peak: Arc<AtomicU32>,
+}
+
+#[async_trait]
+impl JobHandler for Slow {
+    const KIND: &'static str = "test.slow";
+    const QUEUE: &'static str = "slow";
+    type Payload = ();
+
+    async fn run(&self, (): ()) -> Result<(), AppError> {
+        let running = self.running.fetch_add(1, Ordering::SeqCst) + 1;
+        self.peak.fetch_max(running, Ordering::SeqCst);
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        self.running.fetch_sub(1, Ordering::SeqCst);
+        Ok(())
+    }
+}
+
+/// Returns the job repository of each backend.
+async fn repositories() -> Vec<(&'static str, DynJobRepository)> {
+    common::backends()
+        .await
+        .into_iter()
+        .map(|backend| (backend.name, backend.jobs))
+        .collect()
+}
+
+/// Returns a configuration polling and retrying quickly, at most `max_attempts` times.
+fn config(max_attempts: u32) -> JobsConfig {
+    let mut config = JobsConfig::from_env().expect("jobs config");
+    config.max_attempts = max_attempts;
+    config.retry_base = Duration::from_millis(20);
+    config.retry_max = Duration::from_millis(50);
+    config.poll_interval = Duration::from_millis(10);
+    config
+}
+
+/// Waits until a job is in `status`, and returns it.
+async fn wait_for(repository: &DynJobRepository, id: i64, status: JobStatus) -> Job {
+    for _ in 0..200 {
+        let job = repository.get(id).await.expect("get").expect("job");
+        if job.status == status {
+            return job;
+        }
+        tokio::time::sleep(Duration::from_millis(25)).await;
+    }
+    panic!("job {id} is not {}", status.as_str());
+}
+
+/// Returns a job due now, with at most `max_attempts` attempts.
+fn new_job(max_attempts: i32) -> NewJob {
+    NewJob {
+        queue: "default".to_string(),
+        kind: "test.manual".to_string(),
+        payload: json!({}),
+        max_attempts,
+        run_at: Utc::now(),
+        unique_key: None,
+    }
+}
+
+#[tokio::test]
+async fn failed_jobs_are_retried_until_they_succeed() {
+    for (name, repository) in repositories().await {
+        let flaky = Flaky::default();
+        let jobs = Jobs::new(repository.clone(), config(3)).with_handler(flaky.clone());
+        jobs.spawn();
+
+        let job = jobs.enqueue::<Flaky>(&2).await.expect("enqueue");
+        assert_eq!(job.status, JobStatus::Queued, "{name}");
+        assert_eq!(job.payload, json!(2), "{name}");
+
+        let job = wait_for(&repository, job.id, JobStatus::Succeeded).await;
+        assert_eq!(job.attempts, 3, "{name}");
+        assert_eq!(job.last_error, None, "{name}");
+        assert_eq!(job.locked_until, None, "{name}");
+        assert!(job.finished_at.is_some(), "{name}");
+        assert_eq!(flaky.runs.load(Ordering::SeqCst), 3, "{name}");
+    }
+}
+
+#[tokio::test]
+async fn jobs_failing_every_attempt_are_dead() {
+    for (name, repository) in repositories().await {
+        let flaky = Flaky::default();
+        let jobs = Jobs::new(repository.clone(), config(3)).with_handler(flaky.clone());
+        jobs.spawn();
+
+        let job = jobs.enqueue::<Flaky>(&u32::MAX).await.expect("enqueue");
+        let job = wait_for(&repository, job.id, JobStatus::Dead).await;
+        assert_eq!(job.attempts, 3, "{name}");
+        assert_eq!(
+            job.last_error.as_deref(),
+            Some("Conflict: Not yet"),
+            "{name}"
+        );
+        assert!(job.finished_at.is_some(), "{name}");
+
+        // A job whose payload cannot be read is dead without being retried.
+        let mut invalid = new_job(3);
+        invalid.kind = Flaky::KIND.to_string();
+        invalid.payload = json!("three");
+        let invalid = repository
+            .enqueue(&invalid)
+            .await
+            .expect("enqueue")
+            .expect("job");
+        jobs.wake("default");
+        let invalid = wait_for(&repository, invalid.id, JobStatus::Dead).await;
+        assert_eq!(invalid.attempts, 1, "{name}");
+        assert!(
+            invalid
+                .last_error
+                .as_deref()
+                .is_some_and(|error| error.starts_with("Invalid payload")),
+            "{name}: {invalid:?}"
+        );
+        assert_eq!(flaky.runs.load(Ordering::SeqCst), 3, "{name}");
+    }
+}
+
+#[tokio::test]
+async fn jobs_are_listed_and_dead_jobs_retried_by_admins() {
+    for (name, repository) in repositories().await {
+        let flaky = Flaky::default();
+        let jobs = Jobs::new(repository.clone(), config(1)).with_handler(flaky.clone());
+        jobs.spawn();
+        let mut state = common::state_with(Arc::new(InMemoryPostRepository::new()));
+        state.jobs = jobs.clone();
+        let app = setup_routes(state);
+
+        let succeeded = jobs.enqueue::<Flaky>(&0).await.expect("enqueue");
+        wait_for(&repository, succeeded.id, JobStatus::Succeeded).await;
+        let dead = jobs.enqueue::<Flaky>(&2).await.expect("enqueue");
+        wait_for(&repository, dead.id, JobStatus::Dead).await;
+
+        // Without the admin token, jobs can be neither seen nor retried.
+        for (method, uri) in [
+            (Method::GET, "/api/v1/jobs".to_string()),
+            (Method::GET, format!("/api/v1/jobs/{}", dead.id)),
+            (Method::POST, format!("/api/v1/jobs/{}/retry", dead.id)),
+        ] {
+            let (status, body) = send(&app, method, &uri, None).await;
+            assert_eq!(status, StatusCode::UNAUTHORIZED, "{name}: {uri}: {body}");
+        }
+
+        let (status, listed) = send_admin(&app, Method::GET, "/api/v1/jobs", None).await;
+        assert_eq!(status, StatusCode::OK, "{name}: {listed}");
+        let ids: Vec<_> = listed
+            .as_array()
+            .expect("jobs")
+            .iter()
+            .map(|job| job["id"].as_i64())
+            .collect();
+        assert_eq!(ids, [Some(dead.id), Some(succeeded.id)], "{name}");
+
+        let (status, listed) =
+            send_admin(&app, Method::GET, "/api/v1/jobs?status=dead", None).await;
+        assert_eq!(status, StatusCode::OK, "{name}: {listed}");
+        assert_eq!(listed.as_array().map(Vec::len), Some(1), "{name}: {listed}");
+        assert_eq!(listed[0]["id"], dead.id, "{name}");
+        assert_eq!(listed[0]["last_error"], "Conflict: Not yet", "{name}");
+
+        let (status, listed) = send_admin(&app, Method::GET, "/api/v1/jobs?kind=other", None).await;
+        assert_eq!(status, StatusCode::OK, "{name}: {listed}");
+        assert_eq!(listed, json!([]), "{name}");
+
+        let (status, body) = send_admin(&app, Method::GET, "/api/v1/jobs?status=lost", None).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{name}: {body}");
+
+        let uri = format!("/api/v1/jobs/{}", succeeded.id);
+        let (status, job) = send_admin(&app, Method::GET, &uri, None).await;
+        assert_eq!(status, StatusCode::OK, "{name}: {job}");
+        assert_eq!(job["status"], "succeeded", "{name}");
+        assert_eq!(job["kind"], Flaky::KIND, "{name}");
+
+        let (status, body) = send_admin(&app, Method::GET, "/api/v1/jobs/999999", None).await;
+        assert_eq!(status, StatusCode::NOT_FOUND, "{name}: {body}");
+        let (status, body) =
+            send_admin(&app, Method::POST, "/api/v1/jobs/999999/retry", None).await;
+        assert_eq!(status, StatusCode::NOT_FOUND, "{name}: {body}");
+
+        let uri = format!("/api/v1/jobs/{}/retry", succeeded.id);
+        let (status, body) = send_admin(&app, Method::POST, &uri, None).await;
+        assert_eq!(status, StatusCode::CONFLICT, "{name}: {body}");
+
+        // The retried job starts over with its attempts reset, and now succeeds.
+        let uri = format!("/api/v1/jobs/{}/retry", dead.id);
+        let (status, job) = send_admin(&app, Method::POST, &uri, None).await;
+        assert_eq!(status, StatusCode::OK, "{name}: {job}");
+        assert_eq!(job["status"], "queued", "{name}");
+        assert_eq!(job["attempts"], 0, "{name}");
+        let job = wait_for(&repository, dead.id, JobStatus::Succeeded).await;
+        assert_eq!(job.attempts, 1, "{name}");
+        assert_eq!(flaky.runs.load(Ordering::SeqCst), 3, "{name}");
+    }
+}
+
+#[tokio::test]
+async fn each_server_runs_a_queue_up_to_its_concurrency_limit() {
+    for (name, repository) in repositories().await {
+        let slow = Slow::default();
+        let mut config = config(3);
+        config.queue_concurrency.insert("slow".to_string(), 2);
+        let jobs = Jobs::new(repository.clone(), config).with_handler(slow.clone());
+
+        let mut ids = Vec::new();
+        for _ in 0..6 {
+            ids.push(jobs.enqueue::<Slow>(&()).await.expect("enqueue").id);
+        }
+        jobs.spawn();
+        for id in ids {
+            wait_for(&repository, id, JobStatus::Succeeded).await;
+        }
+        assert_eq!(slow.peak.load(Ordering::SeqCst), 2, "{name}");
+    }
+}
+
+#[tokio::test]
+async fn recurring_jobs_are_enqueued_once_per_time_across_servers() {
+    for (name, repository) in repositories().await {
+        let flaky = Flaky::default();
+        let every_second: Cron = "* * * * * *".parse().expect("schedule");
+        for _ in 0..2 {
+            Jobs::new(repository.clone(), config(3))
+                .with_handler(flaky.clone())
+                .with_recurring::<Flaky>(every_second.clone(), &0)
+                .expect("recurring job")
+                .spawn();
+        }
+        tokio::time::sleep(Duration::from_millis(2500)).await;
+
+        let filter = serde_json::from_value(json!({ "kind": Flaky::KIND })).expect("filter");
+        let enqueued = repository.list(&filter).await.expect("list");
+        let keys: HashSet<_> = enqueued
+            .iter()
+            .map(|job| job.unique_key.clone().expect("unique key"))
+            .collect();
+        assert!(enqueued.len() >= 2, "{name}: {enqueued:?}");
+        assert_eq!(keys.len(), enqueued.len(), "{name}: {enqueued:?}");
+        for job in &enqueued {
+            let key = format!(
+                "{}@{}",
+                Flaky::KIND,
+                job.run_at.format("%Y-%m-%dT%H:%M:%SZ")
+            );
+            assert_eq!(job.unique_key.as_ref(), Some(&key), "{name}");
+            assert_eq!(job.run_at.timestamp_subsec_nanos(), 0, "{name}");
+        }
+    }
+}
+
+#[tokio::test]
+async fn jobs_with_an_expired_lease_are_claimed_again() {
+    for (name, repository) in repositories().await {
+        let job = repository
+            .enqueue(&new_job(3))
+            .await
+            .expect("enqueue")
+            .expect("job");
+        let later = Utc::now() + TimeDelta::seconds(60);
+        let expired = Utc::now() - TimeDelta::seconds(1);
+
+        let claimed = repository
+            .claim("default", 5, expired)
+            .await
+            .expect("claim");
+        assert_eq!(claimed.len(), 1, "{name}");
+        assert_eq!(claimed[0].status, JobStatus::Running, "{name}");
+        assert_eq!(claimed[0].attempts, 1, "{name}");
+        assert!(
+            repository
+                .claim("other", 5, later)
+                .await
+                .expect("claim")
+                .is_empty(),
+            "{name}"
+        );
+
+        // The worker of the first attempt is considered gone.
+        let claimed = repository.claim("default", 5, later).await.expect("claim");
+        assert_eq!(claimed.len(), 1, "{name}");
+        assert_eq!(claimed[0].attempts, 2, "{name}");
+        assert!(
+            repository
+                .claim("default", 5, later)
+                .await
+                .expect("claim")
+                .is_empty(),
+            "{name}"
+        );
+
+        // Only the latest attempt may extend its lease or record its outcome.
+        let succeeded = JobOutcome {
+            status: JobStatus::Succeeded,
+            run_at: None,
+            error: None,
+        };
+        assert!(
+            !repository
+                .heartbeat(job.id, 1, later)
+                .await
+                .expect("heartbeat"),
+            "{name}"
+        );
+        assert!(
+            !repository
+                .finish(job.id, 1, &succeeded)
+                .await
+                .expect("finish"),
+            "{name}"
+        );
+        assert!(
+            repository
+                .heartbeat(job.id, 2, later)
+                .await
+                .expect("heartbeat"),
+            "{name}"
+        );
+        assert!(
+            repository
+                .finish(job.id, 2, &succeeded)
+                .await
+                .expect("finish"),
+            "{name}"
+        );
+        assert!(
+            repository.retry(job.id).await.expect("retry").is_none(),
+            "{name}"
+        );
+        let job = repository.get(job.id).await.expect("get").expect("job");
+        assert_eq!(job.status, JobStatus::Succeeded, "{name}");
+        assert_eq!(job.locked_until, None, "{name}");
+    }
+}
+
+#[tokio::test]
+async fn finished_jobs_are_purged_after_the_retention_period() {
+    for (name, repository) in repositories().await {
+        let finished = repository
+            .enqueue(&new_job(1))
+            .await
+            .expect("enqueue")
+            .expect("job");
+        let later = Utc::now() + TimeDelta::seconds(60);
+        repository.claim("default", 1, later).await.expect("claim");
+        let dead = JobOutcome {
+            status: JobStatus::Dead,
+            run_at: None,
+            error: Some("Gone".to_string()),
+        };
+        assert!(
+            repository
+                .finish(finished.id, 1, &dead)
+                .await
+                .expect("finish"),
+            "{name}"
+        );
+        let queued = repository
+            .enqueue(&new_job(1))
+            .await
+            .expect("enqueue")
+            .expect("job");
+
+        let before = Utc::now() - TimeDelta::seconds(60);
+        assert_eq!(repository.purge(before).await.expect("purge"), 0, "{name}");
+        assert_eq!(repository.purge(later).await.expect("purge"), 1, "{name}");
+        assert!(
+            repository.get(finished.id).await.expect("get").is_none(),
+            "{name}"
+        );
+        assert!(
+            repository.get(queued.id).await.expect("get").is_some(),
+            "{name}"
+        );
+    }
+}
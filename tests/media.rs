@@ -0,0 +1,739 @@
+mod common;
+
+use axum::{
+    Router,
+    body::{Body, Bytes},
+    http::{HeaderMap, Method, Request, StatusCode, header},
+};
+use blog_api::{
+    jobs::{Jobs, JobsConfig},
+    media::{
+        GenerateVariants, Media, MediaConfig,
+        blob::local::LocalBlobStore,
+        variants::{VariantConfig, VariantFormat},
+    },
+    server::routes::setup_routes,
+};
+use common::{send, send_admin, send_request};
+use serde_json::{Value, json};
+use std::{fs, path::PathBuf, process, sync::Arc, time::Duration};
+
+/// Boundary of the multipart bodies sent by the tests.
+const BOUNDARY: &str = "media-test-boundary";
+
+/// Returns a fresh blob directory under the system temporary directory.
+fn blob_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("blog-media-{name}-{}", process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    dir
+}
+
+/// Builds one application per backend. Media files are kept in memory with the
+/// in-memory backend, and in a fresh local directory with the others.
+async fn apps_with_blobs(name: &str) -> Vec<(&'static str, Router)> {
+    let mut apps = Vec::new();
+    for backend in common::backends().await {
+        let mut state = backend.state();
+        if backend.name != "memory" {
+            state.media = Media::new(
+                backend.media,
+                Arc::new(LocalBlobStore::new(blob_dir(&format!(
+                    "{name}-{}",
+                    backend.name
+                )))),
+                common::media_config(),
+            );
+        }
+        apps.push((backend.name, setup_routes(state)));
+    }
+    apps
+}
+
+/// Uploads a file declared as `content_type`, with an optional owner.
+async fn upload(
+    app: &Router,
+    filename: &str,
+    content_type: &str,
+    data: &[u8],
+    owner: Option<&str>,
+) -> (StatusCode, Value) {
+    let mut body = Vec::new();
+    if let Some(owner) = owner {
+        body.extend(
+            format!(
+                "--{BOUNDARY}\r\nContent-Disposition: form-data; name=\"owner\"\r\n\r\n{owner}\r\n"
+            )
+            .bytes(),
+        );
+    }
+    body.extend(
+        format!(
+            "--{BOUNDARY}\r\nContent-Disposition: form-data; name=\"file\"; \
+             filename=\"{filename}\"\r\nContent-Type: {content_type}\r\n\r\n"
+        )
+        .bytes(),
+    );
+    body.extend(data);
+    body.extend(format!("\r\n--{BOUNDARY}--\r\n").bytes());
+
+    let request = Request::post("/api/v1/media")
+        .header(
+            header::CONTENT_TYPE,
+            format!("multipart/form-data; boundary={BOUNDARY}"),
+        )
+        .body(Body::from(body))
+        .expect("request");
+    let (status, _, body) = send_request(app, request).await;
+    (status, serde_json::from_slice(&body).unwrap_or(Value::Null))
+}
+
+/// Downloads the content of a media file with the given request headers.
+async fn download(
+    app: &Router,
+    id: i64,
+    headers: &[(header::HeaderName, &str)],
+) -> (StatusCode, HeaderMap, Bytes) {
+    let mut request = Request::get(format!("/api/v1/media/{id}/content"));
+    for (name, value) in headers {
+        request = request.header(name, *value);
+    }
+    send_request(app, request.body(Body::empty()).expect("request")).await
+}
+
+/// The header of a 3×2 PNG, which is all the API reads of it.
+fn png() -> Vec<u8> {
+    let mut png = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR".to_vec();
+    png.extend(3u32.to_be_bytes());
+    png.extend(2u32.to_be_bytes());
+    png.extend(b"\x08\x06\0\0\0");
+    png.extend(b"pixel data follows");
+    png
+}
+
+#[tokio::test]
+async fn media_files_are_uploaded_and_downloaded() {
+    for (backend, app) in apps_with_blobs("download").await {
+        let data = png();
+        let (status, file) = upload(&app, "../diagram.png", "image/png", &data, Some("ann")).await;
+        assert_eq!(status, StatusCode::CREATED, "{backend}: {file}");
+        assert_eq!(file["id"], 1, "{backend}");
+        assert_eq!(file["owner"], "ann", "{backend}");
+        assert_eq!(file["filename"], "diagram.png", "{backend}");
+        assert_eq!(file["content_type"], "image/png", "{backend}");
+        assert_eq!(file["size"], data.len(), "{backend}");
+        assert_eq!(file["width"], 3, "{backend}");
+        assert_eq!(file["height"], 2, "{backend}");
+        let (_, fetched) = send(&app, Method::GET, "/api/v1/media/1", None).await;
+        assert_eq!(fetched, file, "{backend}");
+
+        // The type is recognized from the content, whatever the file is called.
+        let (status, _) = upload(&app, "notes.png", "image/png", b"just text", None).await;
+        assert_eq!(status, StatusCode::UNSUPPORTED_MEDIA_TYPE, "{backend}");
+        let (status, _) = upload(&app, "image.gif", "image/gif", &data, None).await;
+        assert_eq!(status, StatusCode::UNSUPPORTED_MEDIA_TYPE, "{backend}");
+        let (status, _) = upload(&app, "doc.pdf", "application/pdf", b"%PDF-1.7", None).await;
+        assert_eq!(status, StatusCode::UNSUPPORTED_MEDIA_TYPE, "{backend}");
+        let (status, unnamed) = upload(&app, "", "application/octet-stream", &data, None).await;
+        assert_eq!(status, StatusCode::CREATED, "{backend}");
+        assert_eq!(unnamed["filename"], "upload", "{backend}");
+        assert_eq!(unnamed["content_hash"], file["content_hash"], "{backend}");
+
+        let (_, owned) = send(&app, Method::GET, "/api/v1/media?owner=ann", None).await;
+        assert_eq!(owned, json!([file]), "{backend}");
+
+        let (status, headers, body) = download(&app, 1, &[]).await;
+        assert_eq!(status, StatusCode::OK, "{backend}");
+        assert_eq!(body, data, "{backend}");
+        assert_eq!(headers[header::CONTENT_TYPE], "image/png", "{backend}");
+        assert_eq!(headers[header::ACCEPT_RANGES], "bytes", "{backend}");
+        assert_eq!(
+            headers[header::CONTENT_DISPOSITION],
+            "inline; filename=\"diagram.png\"; filename*=UTF-8''diagram.png",
+            "{backend}"
+        );
+        assert_eq!(
+            headers["cross-origin-resource-policy"], "cross-origin",
+            "{backend}"
+        );
+        assert!(
+            headers[header::CACHE_CONTROL]
+                .to_str()
+                .is_ok_and(|value| value.starts_with("public, max-age=")),
+            "{backend}"
+        );
+        let etag = headers[header::ETAG].to_str().expect("ETag").to_string();
+        assert_eq!(
+            etag,
+            format!("\"{}\"", file["content_hash"].as_str().expect("hash"))
+        );
+
+        let (status, _, body) = download(&app, 1, &[(header::IF_NONE_MATCH, &etag)]).await;
+        assert_eq!(status, StatusCode::NOT_MODIFIED, "{backend}");
+        assert!(body.is_empty(), "{backend}");
+
+        let (status, headers, body) = download(&app, 1, &[(header::RANGE, "bytes=8-15")]).await;
+        assert_eq!(status, StatusCode::PARTIAL_CONTENT, "{backend}");
+        assert_eq!(body, &data[8..16], "{backend}");
+        assert_eq!(
+            headers[header::CONTENT_RANGE],
+            format!("bytes 8-15/{}", data.len()),
+            "{backend}"
+        );
+        let (status, _, body) = download(&app, 1, &[(header::RANGE, "bytes=-4")]).await;
+        assert_eq!(status, StatusCode::PARTIAL_CONTENT, "{backend}");
+        assert_eq!(body, &data[data.len() - 4..], "{backend}");
+        let (status, headers, _) = download(&app, 1, &[(header::RANGE, "bytes=1000-")]).await;
+        assert_eq!(status, StatusCode::RANGE_NOT_SATISFIABLE, "{backend}");
+        assert_eq!(
+            headers[header::CONTENT_RANGE],
+            format!("bytes */{}", data.len()),
+            "{backend}"
+        );
+        // A stale If-Range gets the whole, current content.
+        let (status, _, body) = download(
+            &app,
+            1,
+            &[
+                (header::RANGE, "bytes=0-3"),
+                (header::IF_RANGE, "\"stale\""),
+            ],
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK, "{backend}");
+        assert_eq!(body, data, "{backend}");
+
+        // Content shared by another file outlives the deletion of one of them.
+        let (status, _) = send(&app, Method::DELETE, "/api/v1/media/1", None).await;
+        assert_eq!(status, StatusCode::NO_CONTENT, "{backend}");
+        let (status, _, _) = download(&app, 1, &[]).await;
+        assert_eq!(status, StatusCode::NOT_FOUND, "{backend}");
+        let id = unnamed["id"].as_i64().expect("ID");
+        let (status, _, body) = download(&app, id, &[]).await;
+        assert_eq!(status, StatusCode::OK, "{backend}");
+        assert_eq!(body, data, "{backend}");
+        let (status, _) = send(&app, Method::DELETE, "/api/v1/media/1", None).await;
+        assert_eq!(status, StatusCode::NOT_FOUND, "{backend}");
+    }
+}
+
+#[tokio::test]
+async fn media_files_are_attached_to_posts() {
+    for (backend, app) in apps_with_blobs("attach").await {
+        let request = Request::post("/api/v1/posts")
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from(
+                json!({ "title": "Diagrams", "content": "c", "category": "c", "tags": ["t"] })
+                    .to_string(),
+            ))
+            .expect("request");
+        let (status, _, _) = send_request(&app, request).await;
+        assert_eq!(status, StatusCode::CREATED, "{backend}");
+        let (_, first) = upload(&app, "a.png", "image/png", &png(), None).await;
+        let mut gif = b"GIF89a".to_vec();
+        gif.extend([5, 0, 7, 0]);
+        let (status, second) = upload(&app, "b.gif", "image/gif", &gif, None).await;
+        assert_eq!(status, StatusCode::CREATED, "{backend}");
+        assert_eq!(
+            (second["width"].clone(), second["height"].clone()),
+            (json!(5), json!(7))
+        );
+
+        for uri in [
+            "/api/v1/posts/1/media/2",
+            "/api/v1/posts/1/media/1",
+            "/api/v1/posts/1/media/2",
+        ] {
+            let (status, _) = send(&app, Method::PUT, uri, None).await;
+            assert_eq!(status, StatusCode::NO_CONTENT, "{backend}: {uri}");
+        }
+        let (status, _) = send(&app, Method::PUT, "/api/v1/posts/2/media/1", None).await;
+        assert_eq!(status, StatusCode::NOT_FOUND, "{backend}");
+        let (status, _) = send(&app, Method::PUT, "/api/v1/posts/1/media/9", None).await;
+        assert_eq!(status, StatusCode::NOT_FOUND, "{backend}");
+
+        let (status, attached) = send(&app, Method::GET, "/api/v1/posts/1/media", None).await;
+        assert_eq!(status, StatusCode::OK, "{backend}");
+        assert_eq!(attached, json!([second, first]), "{backend}");
+
+        let (status, _) = send(&app, Method::DELETE, "/api/v1/posts/1/media/2", None).await;
+        assert_eq!(status, StatusCode::NO_CONTENT, "{backend}");
+        let (status, _) = send(&app, Method::DELETE, "/api/v1/posts/1/media/2", None).await;
+        assert_eq!(status, StatusCode::NOT_FOUND, "{backend}");
+        // Deleting a file detaches it.
+        let (status, _) = send(&app, Method::DELETE, "/api/v1/media/1", None).await;
+        assert_eq!(status, StatusCode::NO_CONTENT, "{backend}");
+        let (_, attached) = send(&app, Method::GET, "/api/v1/posts/1/media", None).await;
+        assert_eq!(attached, json!([]), "{backend}");
+        let (status, _) = send(&app, Method::GET, "/api/v1/posts/2/media", None).await;
+        assert_eq!(status, StatusCode::NOT_FOUND, "{backend}");
+    }
+}
+
+#[tokio::test]
+async fn featured_images_appear_in_post_metadata() {
+    for (backend, app) in apps_with_blobs("meta").await {
+        let (_, image) = upload(&app, "cover.png", "image/png", &png(), None).await;
+        let mut post = json!({
+            "title": "Diagrams & <Charts>",
+            "content": "# Diagrams\n\nDrawing *diagrams* in `Rust`, one box at a time.",
+            "category": "Rust",
+            "tags": ["svg", "plotting"],
+            "slug": "diagrams",
+            "author": "Jane Doe",
+            "featured_image_id": 9,
+        });
+        let (status, _) = send(&app, Method::POST, "/api/v1/posts", Some(post.clone())).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY, "{backend}");
+        post["featured_image_id"] = image["id"].clone();
+        let (status, created) = send(&app, Method::POST, "/api/v1/posts", Some(post)).await;
+        assert_eq!(status, StatusCode::CREATED, "{backend}");
+        assert_eq!(created["featured_image_id"], image["id"], "{backend}");
+        assert_eq!(created["noindex"], false, "{backend}");
+
+        let (status, meta) = send(&app, Method::GET, "/api/v1/posts/1/meta", None).await;
+        assert_eq!(status, StatusCode::OK, "{backend}");
+        assert_eq!(meta["title"], "Diagrams & <Charts>", "{backend}");
+        assert_eq!(
+            meta["description"], "Diagrams Drawing diagrams in Rust, one box at a time.",
+            "{backend}"
+        );
+        assert_eq!(
+            meta["canonical_url"], "http://localhost:8000/posts/diagrams/",
+            "{backend}"
+        );
+        assert_eq!(meta["robots"], Value::Null, "{backend}");
+        assert_eq!(meta["featured_image"], image, "{backend}");
+        let tags: Vec<(String, String)> = meta["open_graph"]
+            .as_array()
+            .into_iter()
+            .chain(meta["twitter"].as_array())
+            .flatten()
+            .map(|tag| {
+                let field = |name: &str| tag[name].as_str().unwrap_or_default().to_string();
+                (field("name"), field("content"))
+            })
+            .collect();
+        let image_url = "http://localhost:3000/api/v2/media/1/content";
+        for expected in [
+            ("og:type", "article"),
+            ("og:image", image_url),
+            ("og:image:width", "3"),
+            ("article:author", "Jane Doe"),
+            ("article:tag", "plotting"),
+            ("twitter:card", "summary_large_image"),
+            ("twitter:image", image_url),
+        ] {
+            assert!(
+                tags.contains(&(expected.0.to_string(), expected.1.to_string())),
+                "{backend}: {expected:?} in {tags:?}"
+            );
+        }
+        assert_eq!(meta["json_ld"]["@type"], "BlogPosting", "{backend}");
+        assert_eq!(meta["json_ld"]["author"]["name"], "Jane Doe", "{backend}");
+        assert_eq!(meta["json_ld"]["image"], json!([image_url]), "{backend}");
+        let html = meta["html"].as_str().unwrap_or_default();
+        assert!(
+            html.starts_with("<title>Diagrams &amp; &lt;Charts&gt;</title>\n"),
+            "{backend}: {html}"
+        );
+        assert!(
+            html.contains("\"headline\":\"Diagrams & \\u003cCharts>\""),
+            "{backend}: {html}"
+        );
+
+        // SEO fields override the derived values, and updates without them keep them.
+        let (status, _) = send(
+            &app,
+            Method::PUT,
+            "/api/v1/posts/1",
+            Some(json!({
+                "title": "Diagrams",
+                "content": "c",
+                "category": "Rust",
+                "tags": ["svg"],
+                "meta_title": "Drawing diagrams",
+                "meta_description": "How to draw diagrams.",
+                "canonical_url": "https://example.com/diagrams",
+                "noindex": true,
+            })),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK, "{backend}");
+        let (_, updated) = send(
+            &app,
+            Method::PUT,
+            "/api/v1/posts/1",
+            Some(
+                json!({ "title": "Diagrams", "content": "c", "category": "Rust", "tags": ["svg"] }),
+            ),
+        )
+        .await;
+        assert_eq!(updated["meta_title"], "Drawing diagrams", "{backend}");
+        assert_eq!(updated["featured_image_id"], image["id"], "{backend}");
+        let (_, meta) = send(&app, Method::GET, "/api/v1/posts/1/meta", None).await;
+        assert_eq!(meta["title"], "Drawing diagrams", "{backend}");
+        assert_eq!(meta["description"], "How to draw diagrams.", "{backend}");
+        assert_eq!(
+            meta["canonical_url"], "https://example.com/diagrams",
+            "{backend}"
+        );
+        assert_eq!(meta["robots"], "noindex", "{backend}");
+
+        // Fields sent as `null` are cleared.
+        let (status, cleared) = send(
+            &app,
+            Method::PUT,
+            "/api/v1/posts/1",
+            Some(json!({
+                "title": "Diagrams",
+                "content": "c",
+                "category": "Rust",
+                "tags": ["svg"],
+                "featured_image_id": null,
+                "canonical_url": null,
+            })),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK, "{backend}");
+        assert_eq!(cleared["featured_image_id"], Value::Null, "{backend}");
+        assert_eq!(cleared["canonical_url"], Value::Null, "{backend}");
+        assert_eq!(cleared["meta_title"], "Drawing diagrams", "{backend}");
+        let (_, meta) = send(&app, Method::GET, "/api/v1/posts/1/meta", None).await;
+        assert_eq!(meta["featured_image"], Value::Null, "{backend}");
+        assert_eq!(
+            meta["canonical_url"], "http://localhost:8000/posts/diagrams/",
+            "{backend}"
+        );
+        let (status, _) = send(
+            &app,
+            Method::PUT,
+            "/api/v1/posts/1",
+            Some(json!({
+                "title": "Diagrams",
+                "content": "c",
+                "category": "Rust",
+                "tags": ["svg"],
+                "featured_image_id": image["id"],
+            })),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK, "{backend}");
+
+        // A deleted image is no longer featured.
+        let (status, _) = send(&app, Method::DELETE, "/api/v1/media/1", None).await;
+        assert_eq!(status, StatusCode::NO_CONTENT, "{backend}");
+        let (_, meta) = send(&app, Method::GET, "/api/v1/posts/1/meta", None).await;
+        assert_eq!(meta["featured_image"], Value::Null, "{backend}");
+        assert_eq!(meta["twitter"][0]["content"], "summary", "{backend}");
+        let (status, _) = send(&app, Method::GET, "/api/v1/posts/2/meta", None).await;
+        assert_eq!(status, StatusCode::NOT_FOUND, "{backend}");
+    }
+}
+
+#[tokio::test]
+async fn featured_images_of_bulk_posts_are_checked() {
+    for (backend, app) in apps_with_blobs("bulk").await {
+        let (_, image) = upload(&app, "cover.png", "image/png", &png(), None).await;
+        let featuring = |title: &str, image_id: &Value| {
+            json!({
+                "title": title,
+                "content": "c",
+                "category": "Rust",
+                "tags": ["svg"],
+                "featured_image_id": image_id,
+            })
+        };
+        let batch = json!([
+            featuring("Covered", &image["id"]),
+            featuring("Uncovered", &json!(99))
+        ]);
+
+        let (status, rejected) = send(
+            &app,
+            Method::POST,
+            "/api/v1/posts/bulk",
+            Some(batch.clone()),
+        )
+        .await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY, "{backend}");
+        assert_eq!(rejected["results"][0]["status"], "skipped", "{backend}");
+        assert_eq!(rejected["results"][1]["status"], "invalid", "{backend}");
+        assert_eq!(
+            rejected["results"][1]["error"], "Featured image not found",
+            "{backend}"
+        );
+
+        let (status, partial) = send(
+            &app,
+            Method::POST,
+            "/api/v1/posts/bulk?mode=best_effort",
+            Some(batch),
+        )
+        .await;
+        assert_eq!(status, StatusCode::MULTI_STATUS, "{backend}");
+        assert_eq!(
+            (&partial["created"], &partial["failed"]),
+            (&json!(1), &json!(1)),
+            "{backend}"
+        );
+        assert_eq!(
+            partial["results"][0]["post"]["featured_image_id"], image["id"],
+            "{backend}"
+        );
+        assert_eq!(partial["results"][1]["status"], "invalid", "{backend}");
+
+        // CSV exports keep the featured image.
+        let (status, _, csv) = send_request(
+            &app,
+            Request::get("/api/v1/posts/export?format=csv")
+                .body(Body::empty())
+                .expect("request"),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK, "{backend}");
+        let csv = String::from_utf8(csv.to_vec()).expect("UTF-8");
+        let mut lines = csv.lines();
+        let columns: Vec<&str> = lines.next().expect("header").split(',').collect();
+        let fields: Vec<&str> = lines.next().expect("row").split(',').collect();
+        let featured = columns
+            .iter()
+            .position(|column| *column == "featured_image_id")
+            .expect("featured_image_id column");
+        assert_eq!(fields[featured], image["id"].to_string(), "{backend}");
+    }
+}
+
+/// Builds one application per backend, with media files in a fresh local directory,
+/// whose jobs generate variants at widths of 16 and 48 pixels in WebP and the format of
+/// each image, with 8-pixel thumbnails.
+async fn apps_with_variants(name: &str) -> Vec<(&'static str, Router)> {
+    let mut config = MediaConfig::from_env().expect("media config");
+    config.variants = VariantConfig {
+        widths: vec![16, 48],
+        formats: vec![VariantFormat::Webp, VariantFormat::Original],
+        thumbnail_size: 8,
+        quality: 80,
+        concurrency: 1,
+    };
+    let mut apps = Vec::new();
+    for backend in common::backends().await {
+        let mut state = backend.state();
+        state.media = Media::new(
+            backend.media,
+            Arc::new(LocalBlobStore::new(blob_dir(&format!(
+                "{name}-{}",
+                backend.name
+            )))),
+            config.clone(),
+        );
+        let mut jobs_config = JobsConfig::from_env().expect("jobs config");
+        jobs_config.poll_interval = Duration::from_millis(25);
+        state.jobs = Jobs::new(backend.jobs, jobs_config)
+            .with_handler(GenerateVariants::new(state.media.clone()));
+        state.jobs.spawn();
+        apps.push((backend.name, setup_routes(state)));
+    }
+    apps
+}
+
+/// Encodes a 64×32 image with a horizontal and a vertical gradient.
+fn gradient() -> image::RgbImage {
+    image::RgbImage::from_fn(64, 32, |x, y| {
+        image::Rgb([(x * 4) as u8, (y * 8) as u8, 128])
+    })
+}
+
+/// Encodes [`gradient`] as a JPEG whose EXIF metadata asks for a rotation by 90° and
+/// records where it was taken.
+fn jpeg_with_exif() -> Vec<u8> {
+    let mut encoded = Vec::new();
+    gradient()
+        .write_with_encoder(image::codecs::jpeg::JpegEncoder::new(&mut encoded))
+        .expect("JPEG");
+    // A big-endian TIFF structure: IFD0 with the orientation and a pointer to the GPS
+    // IFD, which holds the latitude reference.
+    let mut tiff = b"MM\0\x2a\0\0\0\x08".to_vec();
+    tiff.extend([0, 2]);
+    tiff.extend([0x01, 0x12, 0, 3, 0, 0, 0, 1, 0, 6, 0, 0]);
+    tiff.extend([0x88, 0x25, 0, 4, 0, 0, 0, 1, 0, 0, 0, 38]);
+    tiff.extend([0, 0, 0, 0]);
+    tiff.extend([0, 1]);
+    tiff.extend([0x00, 0x01, 0, 2, 0, 0, 0, 2, b'N', 0, 0, 0]);
+    tiff.extend([0, 0, 0, 0]);
+    let mut app1 = b"Exif\0\0".to_vec();
+    app1.extend(tiff);
+
+    let mut jpeg = encoded[..2].to_vec();
+    jpeg.extend([0xff, 0xe1]);
+    jpeg.extend(u16::try_from(app1.len() + 2).expect("length").to_be_bytes());
+    jpeg.extend(app1);
+    jpeg.extend(&encoded[2..]);
+    jpeg
+}
+
+/// Encodes [`gradient`] as a PNG, and returns it alone and with a text chunk after its
+/// header.
+fn png_with_text() -> (Vec<u8>, Vec<u8>) {
+    let mut png = Vec::new();
+    gradient()
+        .write_with_encoder(image::codecs::png::PngEncoder::new(&mut png))
+        .expect("PNG");
+    let chunk_data = b"Location\0Under the old oak";
+    let mut crc = flate2::Crc::new();
+    crc.update(b"tEXt");
+    crc.update(chunk_data);
+    let mut with_text = png[..33].to_vec();
+    with_text.extend(
+        u32::try_from(chunk_data.len())
+            .expect("length")
+            .to_be_bytes(),
+    );
+    with_text.extend(b"tEXt");
+    with_text.extend(chunk_data);
+    with_text.extend(crc.sum().to_be_bytes());
+    with_text.extend(&png[33..]);
+    (png, with_text)
+}
+
+/// Waits until the variants of a media file are no longer pending, and returns its
+/// record.
+async fn processed(app: &Router, id: i64) -> Value {
+    for _ in 0..600 {
+        let (_, file) = send(app, Method::GET, &format!("/api/v1/media/{id}"), None).await;
+        if file["variants_status"] != "pending" {
+            return file;
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    panic!("the variants of media file {id} are still pending");
+}
+
+#[tokio::test]
+async fn images_are_stripped_and_get_variants() {
+    for (backend, app) in apps_with_variants("variants").await {
+        let (status, file) = upload(&app, "photo.jpg", "image/jpeg", &jpeg_with_exif(), None).await;
+        assert_eq!(status, StatusCode::CREATED, "{backend}: {file}");
+        assert_eq!(file["variants_status"], "pending", "{backend}");
+        // The image is rotated upright and re-encoded without its metadata.
+        assert_eq!(
+            (file["width"].clone(), file["height"].clone()),
+            (json!(32), json!(64))
+        );
+        let (_, _, stored) = download(&app, 1, &[]).await;
+        assert!(
+            !stored.windows(4).any(|window| window == b"Exif"),
+            "{backend}"
+        );
+
+        let file = processed(&app, 1).await;
+        assert_eq!(file["variants_status"], "ready", "{backend}: {file}");
+        let names: Vec<&str> = file["variants"]
+            .as_array()
+            .expect("variants")
+            .iter()
+            .filter_map(|variant| variant["name"].as_str())
+            .collect();
+        assert_eq!(
+            names,
+            [
+                "16w.webp",
+                "16w.jpg",
+                "32w.webp",
+                "thumbnail.webp",
+                "thumbnail.jpg"
+            ],
+            "{backend}"
+        );
+        assert_eq!(
+            file["srcset"],
+            json!([
+                {
+                    "content_type": "image/webp",
+                    "srcset": "/api/v2/media/1/variants/16w.webp 16w, /api/v2/media/1/variants/32w.webp 32w",
+                },
+                {
+                    "content_type": "image/jpeg",
+                    "srcset": "/api/v2/media/1/variants/16w.jpg 16w, /api/v2/media/1/content 32w",
+                },
+            ]),
+            "{backend}"
+        );
+        let (_, ready) = send(
+            &app,
+            Method::GET,
+            "/api/v1/media?variants_status=ready",
+            None,
+        )
+        .await;
+        assert_eq!(ready, json!([file]), "{backend}");
+
+        let request = Request::get("/api/v1/media/1/variants/16w.webp")
+            .body(Body::empty())
+            .expect("request");
+        let (status, headers, body) = send_request(&app, request).await;
+        assert_eq!(status, StatusCode::OK, "{backend}");
+        assert_eq!(headers[header::CONTENT_TYPE], "image/webp", "{backend}");
+        assert_eq!(
+            headers[header::CONTENT_DISPOSITION],
+            "inline; filename=\"photo-16w.webp\"; filename*=UTF-8''photo-16w.webp",
+            "{backend}"
+        );
+        let variant = image::load_from_memory(&body).expect("WebP variant");
+        assert_eq!((variant.width(), variant.height()), (16, 32), "{backend}");
+        let request = Request::get("/api/v1/media/1/variants/thumbnail.jpg")
+            .body(Body::empty())
+            .expect("request");
+        let (status, _, body) = send_request(&app, request).await;
+        assert_eq!(status, StatusCode::OK, "{backend}");
+        let thumbnail = image::load_from_memory(&body).expect("JPEG thumbnail");
+        assert_eq!((thumbnail.width(), thumbnail.height()), (8, 8), "{backend}");
+        let request = Request::get("/api/v1/media/1/variants/999w.webp")
+            .body(Body::empty())
+            .expect("request");
+        let (status, _, _) = send_request(&app, request).await;
+        assert_eq!(status, StatusCode::NOT_FOUND, "{backend}");
+
+        // Metadata is stripped from images that need no rotation without re-encoding them.
+        let (png, with_text) = png_with_text();
+        let (status, file) = upload(&app, "map.png", "image/png", &with_text, None).await;
+        assert_eq!(status, StatusCode::CREATED, "{backend}: {file}");
+        let (_, _, stored) = download(&app, 2, &[]).await;
+        assert_eq!(stored, png, "{backend}");
+        let file = processed(&app, 2).await;
+        assert_eq!(file["variants_status"], "ready", "{backend}: {file}");
+        assert_eq!(
+            file["variants"].as_array().map(Vec::len),
+            Some(7),
+            "{backend}"
+        );
+
+        // GIFs are left alone, and broken images fail.
+        let mut gif = b"GIF89a".to_vec();
+        gif.extend([5, 0, 7, 0]);
+        let (_, file) = upload(&app, "anim.gif", "image/gif", &gif, None).await;
+        assert_eq!(file["variants_status"], "ready", "{backend}");
+        assert_eq!(file["variants"], json!([]), "{backend}");
+        let (_, file) = upload(&app, "broken.png", "image/png", &png[..40], None).await;
+        let file = processed(&app, file["id"].as_i64().expect("ID")).await;
+        assert_eq!(file["variants_status"], "failed", "{backend}");
+
+        // The variants of each image but the GIF were generated by a job.
+        let (_, jobs) =
+            send_admin(&app, Method::GET, "/api/v1/jobs?kind=media.variants", None).await;
+        let payloads: Vec<&Value> = jobs
+            .as_array()
+            .expect("jobs")
+            .iter()
+            .map(|job| &job["payload"])
+            .collect();
+        assert_eq!(payloads, [&file["id"], &json!(2), &json!(1)], "{backend}");
+
+        let (status, _) = send(&app, Method::DELETE, "/api/v1/media/1", None).await;
+        assert_eq!(status, StatusCode::NO_CONTENT, "{backend}");
+        let request = Request::get("/api/v1/media/1/variants/16w.webp")
+            .body(Body::empty())
+            .expect("request");
+        let (status, _, _) = send_request(&app, request).await;
+        assert_eq!(status, StatusCode::NOT_FOUND, "{backend}");
+    }
+}
@@ -0,0 +1,294 @@
+mod common;
+
+use axum::{
+    Router,
+    body::{Body, to_bytes},
+    extract::MatchedPath,
+    http::{HeaderValue, Method, Request, StatusCode, header},
+    middleware::{self, Next},
+    response::Response,
+};
+use blog_api::{
+    openapi::ApiDoc,
+    repository::memory::InMemoryPostRepository,
+    server::{middleware::versioning::ApiVersion, routes::setup_routes},
+};
+use std::{collections::BTreeSet, sync::Arc};
+use tower::ServiceExt;
+use utoipa::OpenApi;
+
+/// Methods probed on every documented path.
+const METHODS: [Method; 5] = [
+    Method::GET,
+    Method::POST,
+    Method::PUT,
+    Method::PATCH,
+    Method::DELETE,
+];
+
+/// Every operation of a version of the API, relative to its `/api/v{N}` prefix.
+///
+/// Kept by hand so that a route added to the router or to the specification alone
+/// fails the tests below.
+const API_ROUTES: [(Method, &str); 34] = [
+    (Method::GET, "/posts"),
+    (Method::POST, "/posts"),
+    (Method::GET, "/posts/search"),
+    (Method::POST, "/posts/bulk"),
+    (Method::PATCH, "/posts/bulk"),
+    (Method::DELETE, "/posts/bulk"),
+    (Method::GET, "/posts/export"),
+    (Method::GET, "/events"),
+    (Method::POST, "/posts/import"),
+    (Method::POST, "/posts/import/wordpress"),
+    (Method::GET, "/posts/{id}"),
+    (Method::PUT, "/posts/{id}"),
+    (Method::DELETE, "/posts/{id}"),
+    (Method::GET, "/posts/{id}/meta"),
+    (Method::GET, "/posts/{id}/collaborate"),
+    (Method::GET, "/posts/{id}/media"),
+    (Method::PUT, "/posts/{id}/media/{media_id}"),
+    (Method::DELETE, "/posts/{id}/media/{media_id}"),
+    (Method::POST, "/media"),
+    (Method::GET, "/media"),
+    (Method::GET, "/media/{id}"),
+    (Method::DELETE, "/media/{id}"),
+    (Method::GET, "/media/{id}/content"),
+    (Method::GET, "/media/{id}/variants/{name}"),
+    (Method::POST, "/webhooks"),
+    (Method::GET, "/webhooks"),
+    (Method::GET, "/webhooks/{id}"),
+    (Method::PUT, "/webhooks/{id}"),
+    (Method::DELETE, "/webhooks/{id}"),
+    (Method::GET, "/webhooks/{id}/deliveries"),
+    (
+        Method::POST,
+        "/webhooks/{id}/deliveries/{delivery_id}/redeliver",
+    ),
+    (Method::GET, "/jobs"),
+    (Method::GET, "/jobs/{id}"),
+    (Method::POST, "/jobs/{id}/retry"),
+];
+
+/// Routes served outside the versioned API, which the specification leaves out.
+const UNDOCUMENTED_ROUTES: [(Method, &str); 3] = [
+    (Method::GET, "/"),
+    (Method::GET, "/openapi.json"),
+    (Method::GET, "/docs"),
+];
+
+/// Header the test router uses to report which route handled a request.
+const MATCHED_PATH: &str = "x-matched-path";
+
+/// Copies the route template that matched a request onto its response.
+async fn echo_matched_path(request: Request<Body>, next: Next) -> Response {
+    let matched = request
+        .extensions()
+        .get::<MatchedPath>()
+        .and_then(|path| HeaderValue::from_str(path.as_str()).ok());
+    let mut response = next.run(request).await;
+    if let Some(matched) = matched {
+        response.headers_mut().insert(MATCHED_PATH, matched);
+    }
+    response
+}
+
+/// Builds the application with in-memory storage.
+fn app() -> Router {
+    setup_routes(common::state_with(Arc::new(InMemoryPostRepository::new())))
+        .layer(middleware::from_fn(echo_matched_path))
+}
+
+/// Lists the methods documented for each path in the specification.
+fn documented_operations() -> Vec<(String, Vec<Method>)> {
+    let spec = ApiDoc::openapi();
+    spec.paths
+        .paths
+        .iter()
+        .map(|(path, item)| {
+            let methods = [
+                (Method::GET, item.get.is_some()),
+                (Method::POST, item.post.is_some()),
+                (Method::PUT, item.put.is_some()),
+                (Method::PATCH, item.patch.is_some()),
+                (Method::DELETE, item.delete.is_some()),
+            ]
+            .into_iter()
+            .filter_map(|(method, documented)| documented.then_some(method))
+            .collect();
+            (path.clone(), methods)
+        })
+        .collect()
+}
+
+/// Returns every versioned operation of the route table, with its full path.
+fn routed_operations() -> BTreeSet<(String, String)> {
+    ApiVersion::ALL
+        .into_iter()
+        .flat_map(|version| {
+            API_ROUTES.iter().map(move |(method, path)| {
+                (format!("{}{path}", version.prefix()), method.to_string())
+            })
+        })
+        .collect()
+}
+
+/// Turns a templated path into a concrete request URI.
+fn concrete_uri(path: &str) -> String {
+    let uri = path.replace("{id}", "1");
+    if uri.ends_with("/search") {
+        format!("{uri}?term=rust")
+    } else {
+        uri
+    }
+}
+
+/// Sends a request and returns its status and the route that handled it.
+async fn send(app: Router, method: Method, uri: &str) -> (StatusCode, Option<String>, Vec<u8>) {
+    let request = Request::builder()
+        .method(method)
+        .uri(uri)
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Body::from(
+            r#"{"title":"t","content":"c","category":"c","tags":["t"]}"#,
+        ))
+        .expect("request");
+    let response = app.oneshot(request).await.expect("response");
+    let status = response.status();
+    let matched = response
+        .headers()
+        .get(MATCHED_PATH)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_string);
+    // Event streams stay open, so their body is not read.
+    let streaming = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .is_some_and(|value| value == "text/event-stream");
+    if streaming {
+        return (status, matched, Vec::new());
+    }
+    let body = to_bytes(response.into_body(), usize::MAX)
+        .await
+        .expect("body");
+    (status, matched, body.to_vec())
+}
+
+#[tokio::test]
+async fn every_documented_operation_is_routed() {
+    for (path, methods) in documented_operations() {
+        let uri = concrete_uri(&path);
+        for method in methods {
+            let (status, matched, _) = send(app(), method.clone(), &uri).await;
+            assert_ne!(
+                status,
+                StatusCode::METHOD_NOT_ALLOWED,
+                "{method} {path} is documented but not routed"
+            );
+            assert_eq!(
+                matched.as_deref(),
+                Some(path.as_str()),
+                "{method} {path} is documented but handled by another route"
+            );
+        }
+    }
+}
+
+#[tokio::test]
+async fn every_routed_method_on_documented_paths_is_documented() {
+    for (path, documented) in documented_operations() {
+        let uri = concrete_uri(&path);
+        for method in METHODS.iter().filter(|method| !documented.contains(method)) {
+            let (status, _, _) = send(app(), method.clone(), &uri).await;
+            assert_eq!(
+                status,
+                StatusCode::METHOD_NOT_ALLOWED,
+                "{method} {path} is routed but missing from the OpenAPI document"
+            );
+        }
+    }
+}
+
+#[test]
+fn route_table_matches_the_documented_operations() {
+    let routed = routed_operations();
+    let documented: BTreeSet<(String, String)> = documented_operations()
+        .into_iter()
+        .flat_map(|(path, methods)| {
+            methods
+                .into_iter()
+                .map(move |method| (path.clone(), method.to_string()))
+        })
+        .collect();
+
+    let undocumented: Vec<_> = routed.difference(&documented).collect();
+    assert!(
+        undocumented.is_empty(),
+        "routed but missing from the OpenAPI document: {undocumented:?}"
+    );
+    let unrouted: Vec<_> = documented.difference(&routed).collect();
+    assert!(
+        unrouted.is_empty(),
+        "documented but missing from the route table: {unrouted:?}"
+    );
+}
+
+#[tokio::test]
+async fn router_serves_exactly_the_route_table() {
+    let versioned = ApiVersion::ALL.into_iter().flat_map(|version| {
+        API_ROUTES
+            .iter()
+            .map(move |(method, path)| (method.clone(), format!("{}{path}", version.prefix())))
+    });
+    let unversioned = UNDOCUMENTED_ROUTES
+        .iter()
+        .map(|(method, path)| (method.clone(), (*path).to_string()));
+    let routes: Vec<(Method, String)> = versioned.chain(unversioned).collect();
+
+    for (method, path) in &routes {
+        let (status, matched, _) = send(app(), method.clone(), &concrete_uri(path)).await;
+        assert_ne!(
+            status,
+            StatusCode::METHOD_NOT_ALLOWED,
+            "{method} {path} is in the route table but not routed"
+        );
+        assert_eq!(
+            matched.as_deref(),
+            Some(path.as_str()),
+            "{method} {path} is in the route table but handled by another route"
+        );
+    }
+
+    let paths: BTreeSet<&String> = routes.iter().map(|(_, path)| path).collect();
+    for path in paths {
+        for method in &METHODS {
+            if routes.contains(&(method.clone(), path.clone())) {
+                continue;
+            }
+            let (status, _, _) = send(app(), method.clone(), &concrete_uri(path)).await;
+            assert_eq!(
+                status,
+                StatusCode::METHOD_NOT_ALLOWED,
+                "{method} {path} is routed but missing from the route table"
+            );
+        }
+    }
+}
+
+#[tokio::test]
+async fn served_document_matches_generated_spec() {
+    let (status, _, body) = send(app(), Method::GET, "/openapi.json").await;
+    assert_eq!(status, StatusCode::OK);
+
+    let served: serde_json::Value = serde_json::from_slice(&body).expect("valid JSON");
+    let generated = serde_json::to_value(ApiDoc::openapi()).expect("serializable spec");
+    assert_eq!(served, generated);
+    assert!(
+        served["openapi"]
+            .as_str()
+            .is_some_and(|version| version.starts_with("3.1"))
+    );
+    assert!(served["components"]["schemas"]["ErrorBody"].is_object());
+    assert!(served["components"]["schemas"]["BlogPost"].is_object());
+    assert!(served["components"]["schemas"]["BlogPostBody"].is_object());
+}
@@ -0,0 +1,418 @@
+mod common;
+
+use async_trait::async_trait;
+use blog_api::{
+    error::AppError,
+    model::blog::BlogPostBody,
+    outbox::{
+        Dispatcher, DomainEvent, DynOutboxRepository, EventConsumer, OutboxConfig, OutboxEvent,
+    },
+    repository::{DynPostRepository, PostFilter, Reassignment},
+};
+use chrono::{DateTime, Utc};
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+/// Returns the post repository and matching outbox of each backend.
+async fn backends() -> Vec<(&'static str, DynPostRepository, DynOutboxRepository)> {
+    common::backends()
+        .await
+        .into_iter()
+        .map(|backend| (backend.name, backend.posts, backend.outbox))
+        .collect()
+}
+
+/// A consumer recording the events it handles, failing the events of chosen posts a
+/// given number of times first.
+#[derive(Default)]
+struct Recorder {
+    /// Name and post ID of the events handled, in order.
+    handled: Mutex<Vec<(String, i32)>>,
+    /// Calls made, successful or not.
+    calls: Mutex<Vec<(String, i32)>>,
+    /// Failures left per post ID.
+    failures: Mutex<HashMap<i32, usize>>,
+}
+
+impl Recorder {
+    /// Fails the next `count` events of a post.
+    fn fail(&self, post_id: i32, count: usize) {
+        self.failures
+            .lock()
+            .expect("failures")
+            .insert(post_id, count);
+    }
+
+    /// Returns the names of the events handled for a post, in order.
+    fn handled_for(&self, post_id: i32) -> Vec<String> {
+        self.handled
+            .lock()
+            .expect("handled")
+            .iter()
+            .filter(|(_, id)| *id == post_id)
+            .map(|(name, _)| name.clone())
+            .collect()
+    }
+
+    /// Returns every event handled, in order.
+    fn handled(&self) -> Vec<(String, i32)> {
+        self.handled.lock().expect("handled").clone()
+    }
+}
+
+#[async_trait]
+impl EventConsumer for Recorder {
+    fn name(&self) -> &'static str {
+        "recorder"
+    }
+
+    async fn consume(&self, event: &OutboxEvent) -> Result<(), AppError> {
+        let entry = (event.event.name().to_string(), event.event.aggregate_id());
+        self.calls.lock().expect("calls").push(entry.clone());
+        if let Some(left) = self
+            .failures
+            .lock()
+            .expect("failures")
+            .get_mut(&entry.1)
+            .filter(|left| **left > 0)
+        {
+            *left -= 1;
+            return Err(AppError::ServiceUnavailable("try again".to_string()));
+        }
+        self.handled.lock().expect("handled").push(entry);
+        Ok(())
+    }
+}
+
+/// Returns a dispatcher of `outbox` retrying failed events right away.
+fn dispatcher(outbox: DynOutboxRepository, consumers: Vec<Arc<Recorder>>) -> Dispatcher {
+    let mut config = OutboxConfig::from_env().expect("outbox config");
+    config.retry_base = Duration::ZERO;
+    consumers
+        .into_iter()
+        .fold(Dispatcher::new(outbox, config), |dispatcher, consumer| {
+            dispatcher.with_consumer(consumer)
+        })
+}
+
+/// Dispatches events until none is left, and returns the number of rounds it took.
+async fn drain(dispatcher: &Dispatcher) -> usize {
+    for round in 0..50 {
+        if dispatcher.dispatch_due().await.expect("dispatch") == 0 {
+            return round;
+        }
+    }
+    panic!("the outbox was not drained");
+}
+
+/// A post body, published unless `draft`.
+fn body(title: &str, slug: Option<&str>, draft: bool) -> BlogPostBody {
+    BlogPostBody {
+        title: title.to_string(),
+        content: "Content".to_string(),
+        category: "Rust".to_string(),
+        tags: vec!["rust".to_string()],
+        slug: slug.map(|slug| Some(slug.to_string())),
+        draft: Some(draft),
+        ..BlogPostBody::default()
+    }
+}
+
+/// Returns the names of events as owned strings.
+fn names(names: &[&str]) -> Vec<String> {
+    names.iter().map(|name| (*name).to_string()).collect()
+}
+
+#[tokio::test]
+async fn writes_record_their_events_in_order() {
+    for (backend, posts, outbox) in backends().await {
+        let recorder = Arc::new(Recorder::default());
+        let dispatcher = dispatcher(outbox, vec![recorder.clone()]);
+
+        let published = posts
+            .create(&body("Published", Some("published"), false))
+            .await
+            .expect("create");
+        let draft = posts
+            .create(&body("Draft", None, true))
+            .await
+            .expect("create");
+        // A write that fails records nothing.
+        assert!(
+            posts
+                .create(&body("Duplicate", Some("published"), false))
+                .await
+                .is_err(),
+            "{backend}"
+        );
+        posts
+            .update(published.id, &body("Unpublished", None, true))
+            .await
+            .expect("update");
+        posts
+            .update(draft.id, &body("Draft", None, false))
+            .await
+            .expect("update");
+        posts
+            .update(draft.id, &body("Draft, edited", None, false))
+            .await
+            .expect("update");
+        assert!(
+            posts.delete(published.id).await.expect("delete"),
+            "{backend}"
+        );
+
+        // Writes finding no post record nothing.
+        assert!(
+            posts
+                .update(9999, &body("Missing", None, false))
+                .await
+                .expect("update")
+                .is_none(),
+            "{backend}"
+        );
+        assert!(!posts.delete(9999).await.expect("delete"), "{backend}");
+
+        drain(&dispatcher).await;
+        assert_eq!(
+            recorder.handled_for(published.id),
+            names(&[
+                "post.created",
+                "post.published",
+                "post.updated",
+                "post.unpublished",
+                "post.deleted",
+            ]),
+            "{backend}"
+        );
+        assert_eq!(
+            recorder.handled_for(draft.id),
+            names(&[
+                "post.created",
+                "post.updated",
+                "post.published",
+                "post.updated",
+            ]),
+            "{backend}"
+        );
+        assert_eq!(recorder.handled().len(), 9, "{backend}");
+
+        // Dispatched events are not dispatched again.
+        assert_eq!(drain(&dispatcher).await, 0, "{backend}");
+        assert_eq!(recorder.handled().len(), 9, "{backend}");
+    }
+}
+
+#[tokio::test]
+async fn events_carry_the_post_as_written() {
+    for (backend, posts, outbox) in backends().await {
+        let dispatcher = dispatcher(outbox.clone(), Vec::new());
+        let created = posts
+            .create(&body("Before", Some("post"), true))
+            .await
+            .expect("create");
+        let updated = posts
+            .upsert_by_slug(&body("After", Some("post"), true))
+            .await
+            .expect("upsert");
+        posts.delete(created.id).await.expect("delete");
+
+        let claimed = outbox
+            .claim(10, chrono::Utc::now() + chrono::Duration::seconds(60))
+            .await
+            .expect("claim");
+        assert_eq!(claimed.len(), 1, "{backend}: one event per post at a time");
+        assert_eq!(claimed[0].event, DomainEvent::PostCreated(created.clone()));
+        assert_eq!(claimed[0].attempts, 0, "{backend}");
+        // A claimed event is not claimed again until its lease expires.
+        assert!(
+            outbox
+                .claim(10, chrono::Utc::now())
+                .await
+                .expect("claim")
+                .is_empty(),
+            "{backend}"
+        );
+        outbox.complete(claimed[0].id).await.expect("complete");
+
+        let claimed = outbox.claim(10, chrono::Utc::now()).await.expect("claim");
+        assert_eq!(
+            claimed[0].event,
+            DomainEvent::PostUpdated(updated.post().clone()),
+            "{backend}"
+        );
+        outbox.complete(claimed[0].id).await.expect("complete");
+
+        let claimed = outbox.claim(10, chrono::Utc::now()).await.expect("claim");
+        assert_eq!(
+            claimed[0].event,
+            DomainEvent::PostDeleted(updated.post().clone()),
+            "{backend}"
+        );
+        outbox.complete(claimed[0].id).await.expect("complete");
+        assert_eq!(drain(&dispatcher).await, 0, "{backend}");
+    }
+}
+
+#[tokio::test]
+async fn bulk_writes_record_an_event_per_post() {
+    for (backend, posts, outbox) in backends().await {
+        let recorder = Arc::new(Recorder::default());
+        let dispatcher = dispatcher(outbox, vec![recorder.clone()]);
+        let mut rust = body("Rust", None, true);
+        rust.category = "Programming".to_string();
+        let created = posts
+            .create_many(&[rust, body("Go", None, true), body("Zig", None, true)])
+            .await
+            .expect("create many");
+        let ids: Vec<i32> = created.iter().map(|post| post.id).collect();
+
+        // The first post is already in the category, so only the others change.
+        let reassigned = posts
+            .reassign(
+                &PostFilter::default(),
+                &Reassignment {
+                    category: Some("Programming".to_string()),
+                    ..Reassignment::default()
+                },
+                &[],
+            )
+            .await
+            .expect("reassign");
+        assert_eq!(reassigned, ids, "{backend}");
+        let deleted = posts
+            .delete_many(&[ids[2], ids[0], 9999])
+            .await
+            .expect("delete many");
+        assert_eq!(deleted, vec![ids[0], ids[2]], "{backend}");
+
+        drain(&dispatcher).await;
+        assert_eq!(
+            recorder.handled_for(ids[0]),
+            names(&["post.created", "post.deleted"]),
+            "{backend}"
+        );
+        assert_eq!(
+            recorder.handled_for(ids[1]),
+            names(&["post.created", "post.updated"]),
+            "{backend}"
+        );
+        assert_eq!(
+            recorder.handled_for(ids[2]),
+            names(&["post.created", "post.updated", "post.deleted"]),
+            "{backend}"
+        );
+    }
+}
+
+#[tokio::test]
+async fn scheduled_posts_are_announced_once_their_date_passes() {
+    for (backend, posts, outbox) in backends().await {
+        let recorder = Arc::new(Recorder::default());
+        let dispatcher = dispatcher(outbox, vec![recorder.clone()]);
+        let now = Utc::now();
+        let hour = chrono::Duration::hours(1);
+        let at = |published_at: DateTime<Utc>, draft: bool| BlogPostBody {
+            published_at: Some(Some(published_at)),
+            ..body("Scheduled", None, draft)
+        };
+
+        let scheduled = posts.create(&at(now + hour, false)).await.expect("create");
+        let draft = posts.create(&at(now + hour, true)).await.expect("create");
+        // A post created with a past date is published by its creation.
+        let backdated = posts.create(&at(now - hour, false)).await.expect("create");
+
+        let announced = |posts: &DynPostRepository, now: DateTime<Utc>| {
+            let posts = posts.clone();
+            async move {
+                posts
+                    .announce_scheduled(now)
+                    .await
+                    .expect("announce")
+                    .into_iter()
+                    .map(|post| post.id)
+                    .collect::<Vec<_>>()
+            }
+        };
+        assert!(announced(&posts, now).await.is_empty(), "{backend}");
+        let later = now + hour * 2;
+        assert_eq!(
+            announced(&posts, later).await,
+            vec![scheduled.id],
+            "{backend}"
+        );
+        assert!(announced(&posts, later).await.is_empty(), "{backend}");
+
+        // A draft is announced once it is no longer one.
+        posts
+            .update(draft.id, &at(now + hour, false))
+            .await
+            .expect("update");
+        assert_eq!(announced(&posts, later).await, vec![draft.id], "{backend}");
+
+        drain(&dispatcher).await;
+        for id in [scheduled.id, backdated.id] {
+            assert_eq!(
+                recorder.handled_for(id),
+                names(&["post.created", "post.published"]),
+                "{backend}"
+            );
+        }
+        assert_eq!(
+            recorder.handled_for(draft.id),
+            names(&["post.created", "post.updated", "post.published"]),
+            "{backend}"
+        );
+    }
+}
+
+#[tokio::test]
+async fn failed_events_are_retried_and_hold_back_their_post_only() {
+    for (backend, posts, outbox) in backends().await {
+        let first = Arc::new(Recorder::default());
+        let failing = Arc::new(Recorder::default());
+        let dispatcher = dispatcher(outbox, vec![first.clone(), failing.clone()]);
+
+        let stuck = posts
+            .create(&body("Stuck", None, true))
+            .await
+            .expect("create");
+        posts
+            .update(stuck.id, &body("Stuck, edited", None, true))
+            .await
+            .expect("update");
+        let other = posts
+            .create(&body("Other", None, true))
+            .await
+            .expect("create");
+        failing.fail(stuck.id, 2);
+
+        drain(&dispatcher).await;
+        // The events of the other post went through while the first event of the stuck
+        // post was failing, and the stuck post's update waited for its creation.
+        assert_eq!(
+            failing.handled(),
+            vec![
+                ("post.created".to_string(), other.id),
+                ("post.created".to_string(), stuck.id),
+                ("post.updated".to_string(), stuck.id),
+            ],
+            "{backend}"
+        );
+        // Every consumer sees a retried event again.
+        assert_eq!(
+            first.handled_for(stuck.id),
+            names(&[
+                "post.created",
+                "post.created",
+                "post.created",
+                "post.updated"
+            ]),
+            "{backend}"
+        );
+        assert_eq!(failing.calls.lock().expect("calls").len(), 5, "{backend}");
+    }
+}
@@ -0,0 +1,413 @@
+mod common;
+
+use axum::{
+    Router,
+    body::Body,
+    http::{Method, Request, StatusCode, header},
+};
+use common::{apps, send, send_request};
+use serde_json::{Value, json};
+
+/// A valid request body for creating or updating a post.
+fn post(title: &str, category: &str, tags: &[&str]) -> Value {
+    json!({ "title": title, "content": "Some content", "category": category, "tags": tags })
+}
+
+#[tokio::test]
+async fn posts_round_trip_through_the_api() {
+    for (backend, app) in apps().await {
+        let (status, created) = send(
+            &app,
+            Method::POST,
+            "/api/v1/posts",
+            Some(post("Async Rust", "Rust", &["async"])),
+        )
+        .await;
+        assert_eq!(status, StatusCode::CREATED, "{backend}");
+        assert_eq!(created["id"], 1, "{backend}");
+        assert_eq!(created["tags"], json!(["async"]), "{backend}");
+
+        let (status, fetched) = send(&app, Method::GET, "/api/v1/posts/1", None).await;
+        assert_eq!(status, StatusCode::OK, "{backend}");
+        assert_eq!(fetched, created, "{backend}");
+
+        let (status, updated) = send(
+            &app,
+            Method::PUT,
+            "/api/v1/posts/1",
+            Some(post("Async Rust, revised", "Rust", &["async", "tokio"])),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK, "{backend}");
+        assert_eq!(updated["title"], "Async Rust, revised", "{backend}");
+        assert_eq!(updated["tags"], json!(["async", "tokio"]), "{backend}");
+
+        let (status, _) = send(&app, Method::DELETE, "/api/v1/posts/1", None).await;
+        assert_eq!(status, StatusCode::NO_CONTENT, "{backend}");
+
+        for method in [Method::GET, Method::DELETE] {
+            let (status, _) = send(&app, method, "/api/v1/posts/1", None).await;
+            assert_eq!(status, StatusCode::NOT_FOUND, "{backend}");
+        }
+        let (status, _) = send(
+            &app,
+            Method::PUT,
+            "/api/v1/posts/1",
+            Some(post("Gone", "Rust", &["async"])),
+        )
+        .await;
+        assert_eq!(status, StatusCode::NOT_FOUND, "{backend}");
+
+        let (_, recreated) = send(
+            &app,
+            Method::POST,
+            "/api/v1/posts",
+            Some(post("Async Rust", "Rust", &["async"])),
+        )
+        .await;
+        assert_eq!(recreated["id"], 2, "{backend}: IDs must not be reused");
+    }
+}
+
+#[tokio::test]
+async fn posts_can_be_filtered_and_searched() {
+    let titles = |posts: &Value| -> Vec<String> {
+        posts
+            .as_array()
+            .expect("array")
+            .iter()
+            .map(|post| post["title"].as_str().expect("title").to_string())
+            .collect()
+    };
+
+    for (backend, app) in apps().await {
+        for body in [
+            post("Async Rust", "Rust", &["async"]),
+            post("Ownership", "Rust", &["memory"]),
+            post("Goroutines", "Go", &["async", "go"]),
+        ] {
+            let (status, _) = send(&app, Method::POST, "/api/v1/posts", Some(body)).await;
+            assert_eq!(status, StatusCode::CREATED, "{backend}");
+        }
+
+        let (_, all) = send(&app, Method::GET, "/api/v1/posts", None).await;
+        assert_eq!(
+            titles(&all),
+            ["Async Rust", "Ownership", "Goroutines"],
+            "{backend}"
+        );
+
+        let (_, rust) = send(&app, Method::GET, "/api/v1/posts?category=Rust", None).await;
+        assert_eq!(titles(&rust), ["Async Rust", "Ownership"], "{backend}");
+
+        let (_, async_rust) = send(
+            &app,
+            Method::GET,
+            "/api/v1/posts?category=Rust&tag=async",
+            None,
+        )
+        .await;
+        assert_eq!(titles(&async_rust), ["Async Rust"], "{backend}");
+
+        let searches: [(&str, &[&str]); 5] = [
+            ("ASYNC", &["Async Rust", "Goroutines"]),
+            ("wnershi", &["Ownership"]),
+            ("async,go", &["Goroutines"]),
+            ("go", &["Goroutines"]),
+            ("\"quoted\"", &[]),
+        ];
+        for (term, expected) in searches {
+            let uri = format!("/api/v2/posts/search?term={}", term.replace('"', "%22"));
+            let (status, found) = send(&app, Method::GET, &uri, None).await;
+            assert_eq!(status, StatusCode::OK, "{backend}: {term}");
+            assert_eq!(titles(&found["data"]), expected, "{backend}: {term}");
+        }
+
+        let (status, _) = send(&app, Method::GET, "/api/v1/posts/search?term=python", None).await;
+        assert_eq!(status, StatusCode::NOT_FOUND, "{backend}");
+    }
+}
+
+#[tokio::test]
+async fn posts_can_be_created_updated_and_deleted_in_bulk() {
+    for (backend, app) in apps().await {
+        let invalid = json!({ "title": "", "content": "x", "category": "Rust", "tags": ["a"] });
+        let batch = json!([
+            post("Async Rust", "Rust", &["async", "draft"]),
+            invalid,
+            post("Goroutines", "Go", &["draft"]),
+        ]);
+
+        let (status, rejected) = send(
+            &app,
+            Method::POST,
+            "/api/v1/posts/bulk",
+            Some(batch.clone()),
+        )
+        .await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY, "{backend}");
+        assert_eq!(rejected["created"], 0, "{backend}");
+        let statuses: Vec<&Value> = rejected["results"]
+            .as_array()
+            .expect("results")
+            .iter()
+            .map(|result| &result["status"])
+            .collect();
+        assert_eq!(statuses, ["skipped", "invalid", "skipped"], "{backend}");
+        let (_, all) = send(&app, Method::GET, "/api/v1/posts", None).await;
+        assert_eq!(all, json!([]), "{backend}: all or nothing");
+
+        let (status, partial) = send(
+            &app,
+            Method::POST,
+            "/api/v1/posts/bulk?mode=best_effort",
+            Some(batch),
+        )
+        .await;
+        assert_eq!(status, StatusCode::MULTI_STATUS, "{backend}");
+        assert_eq!(
+            (&partial["created"], &partial["failed"]),
+            (&json!(2), &json!(1))
+        );
+        assert_eq!(partial["results"][0]["post"]["id"], 1, "{backend}");
+        assert_eq!(
+            partial["results"][1]["error"],
+            "title: Title cannot be empty"
+        );
+        assert_eq!(partial["results"][2]["post"]["title"], "Goroutines");
+
+        let (status, _) = send(&app, Method::POST, "/api/v1/posts/bulk", Some(json!([]))).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{backend}");
+
+        let (status, updated) = send(
+            &app,
+            Method::PATCH,
+            "/api/v1/posts/bulk",
+            Some(json!({
+                "filter": { "tag": "draft" },
+                "category": "Programming",
+                "add_tags": ["published", "async"],
+                "remove_tags": ["draft"]
+            })),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK, "{backend}");
+        assert_eq!(
+            updated,
+            json!({ "updated": 2, "ids": [1, 2], "rejected": [] }),
+            "{backend}"
+        );
+        let (_, first) = send(&app, Method::GET, "/api/v1/posts/1", None).await;
+        assert_eq!(first["category"], "Programming", "{backend}");
+        assert_eq!(first["tags"], json!(["async", "published"]), "{backend}");
+
+        let (status, untagged) = send(
+            &app,
+            Method::PATCH,
+            "/api/v1/posts/bulk",
+            Some(json!({ "filter": { "tag": "async" }, "remove_tags": ["async"] })),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK, "{backend}");
+        assert_eq!(untagged["ids"], json!([1, 2]), "{backend}");
+        let (status, emptied) = send(
+            &app,
+            Method::PATCH,
+            "/api/v1/posts/bulk",
+            Some(json!({ "filter": { "category": "Programming" }, "remove_tags": ["published"] })),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK, "{backend}");
+        assert_eq!(
+            emptied["updated"], 0,
+            "{backend}: posts keep their last tag"
+        );
+
+        let (status, _) = send(
+            &app,
+            Method::PATCH,
+            "/api/v1/posts/bulk",
+            Some(json!({ "filter": {}, "category": "Everything" })),
+        )
+        .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{backend}");
+
+        let (status, deleted) = send(
+            &app,
+            Method::DELETE,
+            "/api/v1/posts/bulk",
+            Some(json!({ "ids": [2, 7, 1, 7] })),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK, "{backend}");
+        assert_eq!(
+            deleted,
+            json!({ "deleted": [1, 2], "not_found": [7], "rejected": [] }),
+            "{backend}"
+        );
+        let (_, all) = send(&app, Method::GET, "/api/v1/posts", None).await;
+        assert_eq!(all, json!([]), "{backend}");
+    }
+}
+
+/// Sends a `GET` request and returns the status, content type and body as text.
+async fn download(app: &Router, uri: &str) -> (StatusCode, String, String) {
+    let request = Request::builder()
+        .uri(uri)
+        .body(Body::empty())
+        .expect("request");
+    let (status, headers, body) = send_request(app, request).await;
+    let content_type = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default()
+        .to_string();
+    let text = String::from_utf8(body.to_vec()).expect("UTF-8 body");
+    (status, content_type, text)
+}
+
+#[tokio::test]
+async fn posts_can_be_exported_as_ndjson_and_csv() {
+    for (backend, app) in apps().await {
+        let quoted = json!({
+            "title": "Commas, \"quotes\"",
+            "content": "Two\nlines",
+            "category": "Rust",
+            "tags": ["rust", "csv", "a|b", "c\\d"],
+            "meta_title": "Meta, title",
+            "meta_description": "Description",
+            "canonical_url": "https://example.com/quotes",
+            "noindex": true
+        });
+        for body in [quoted, post("Goroutines", "Go", &["go"])] {
+            let (status, _) = send(&app, Method::POST, "/api/v1/posts", Some(body)).await;
+            assert_eq!(status, StatusCode::CREATED, "{backend}");
+        }
+
+        let (status, content_type, ndjson) = download(&app, "/api/v1/posts/export").await;
+        assert_eq!(status, StatusCode::OK, "{backend}");
+        assert_eq!(content_type, "application/x-ndjson", "{backend}");
+        let lines: Vec<Value> = ndjson
+            .lines()
+            .map(|line| serde_json::from_str(line).expect("JSON line"))
+            .collect();
+        assert_eq!(lines.len(), 2, "{backend}");
+        assert_eq!(lines[0]["title"], "Commas, \"quotes\"", "{backend}");
+        assert_eq!(lines[1]["tags"], json!(["go"]), "{backend}");
+
+        let (status, content_type, csv) =
+            download(&app, "/api/v2/posts/export?format=csv&tag=csv").await;
+        assert_eq!(status, StatusCode::OK, "{backend}");
+        assert_eq!(content_type, "text/csv; charset=utf-8", "{backend}");
+        let (header, row) = csv.split_once("\r\n").expect("header row");
+        assert_eq!(
+            header,
+            "id,title,content,category,tags,slug,author,draft,published_at,featured_image_id,\
+             meta_title,meta_description,canonical_url,noindex,created_at,updated_at",
+            "{backend}"
+        );
+        assert!(
+            row.starts_with(
+                "1,\"Commas, \"\"quotes\"\"\",\"Two\nlines\",Rust,rust|csv|a\\|b|c\\\\d,,,false,,,\
+                 \"Meta, title\",Description,https://example.com/quotes,true,"
+            ),
+            "{backend}: {row}"
+        );
+        assert_eq!(row.matches("\r\n").count(), 1, "{backend}: one post");
+
+        let (status, _, _) = download(&app, "/api/v1/posts/export?format=xml").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{backend}");
+    }
+}
+
+#[tokio::test]
+async fn slugs_are_unique_and_kept_when_updates_omit_them() {
+    for (backend, app) in apps().await {
+        let mut first = post("First", "Rust", &["rust"]);
+        first["slug"] = json!("first-post");
+        first["draft"] = json!(true);
+        let (status, created) =
+            send(&app, Method::POST, "/api/v1/posts", Some(first.clone())).await;
+        assert_eq!(status, StatusCode::CREATED, "{backend}");
+        assert_eq!(created["slug"], "first-post", "{backend}");
+        assert_eq!(created["draft"], true, "{backend}");
+
+        let (status, _) = send(&app, Method::POST, "/api/v1/posts", Some(first)).await;
+        assert_eq!(status, StatusCode::CONFLICT, "{backend}");
+        let (status, _) = send(&app, Method::POST, "/api/v1/posts/bulk", Some(json!([
+            post("A", "Rust", &["a"]),
+            { "title": "B", "content": "x", "category": "Rust", "tags": ["b"], "slug": "first-post" }
+        ])))
+        .await;
+        assert_eq!(status, StatusCode::CONFLICT, "{backend}");
+
+        let (status, updated) = send(
+            &app,
+            Method::PUT,
+            "/api/v1/posts/1",
+            Some(post("First, edited", "Rust", &["rust"])),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK, "{backend}");
+        assert_eq!(
+            (&updated["slug"], &updated["draft"]),
+            (&json!("first-post"), &json!(true)),
+            "{backend}"
+        );
+
+        let mut invalid = post("Second", "Rust", &["rust"]);
+        invalid["slug"] = json!("Not A Slug");
+        let (status, _) = send(&app, Method::POST, "/api/v1/posts", Some(invalid)).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{backend}");
+        let (_, all) = send(&app, Method::GET, "/api/v1/posts", None).await;
+        assert_eq!(all.as_array().map(Vec::len), Some(1), "{backend}");
+
+        // In best effort mode, posts the storage refuses are reported one by one.
+        let with_slug = |title: &str, slug: &str| {
+            let mut post = post(title, "Rust", &["rust"]);
+            post["slug"] = json!(slug);
+            post
+        };
+        let (status, partial) = send(
+            &app,
+            Method::POST,
+            "/api/v1/posts/bulk?mode=best_effort",
+            Some(json!([
+                post("A", "Rust", &["a"]),
+                with_slug("B", "first-post"),
+                with_slug("C", "third-post"),
+                with_slug("D", "third-post"),
+            ])),
+        )
+        .await;
+        assert_eq!(status, StatusCode::MULTI_STATUS, "{backend}");
+        assert_eq!(
+            (&partial["created"], &partial["failed"]),
+            (&json!(2), &json!(2)),
+            "{backend}"
+        );
+        let statuses: Vec<&Value> = partial["results"]
+            .as_array()
+            .expect("results")
+            .iter()
+            .map(|result| &result["status"])
+            .collect();
+        assert_eq!(
+            statuses,
+            ["created", "rejected", "created", "rejected"],
+            "{backend}"
+        );
+        for index in [1, 3] {
+            assert_eq!(
+                partial["results"][index]["error"], "A blog post with this slug already exists",
+                "{backend}"
+            );
+        }
+        assert_eq!(
+            partial["results"][2]["post"]["slug"], "third-post",
+            "{backend}"
+        );
+        let (_, all) = send(&app, Method::GET, "/api/v1/posts", None).await;
+        assert_eq!(all.as_array().map(Vec::len), Some(3), "{backend}");
+    }
+}
@@ -0,0 +1,116 @@
+mod common;
+
+use axum::{
+    Router,
+    body::{Body, to_bytes},
+    http::{HeaderMap, Request, StatusCode, header},
+};
+use blog_api::{
+    repository::memory::InMemoryPostRepository,
+    server::{middleware::http::HttpConfig, routes::setup_routes},
+};
+use serde_json::{Value, json};
+use std::sync::Arc;
+use tower::ServiceExt;
+
+/// Builds an application with empty storage and a request body limit of 256 bytes.
+fn app() -> Router {
+    let mut state = common::state_with(Arc::new(InMemoryPostRepository::new()));
+    let mut http = HttpConfig::from_env().expect("http config");
+    http.max_body_bytes = 256;
+    state.http = Arc::new(http);
+    setup_routes(state)
+}
+
+/// Sends a request, with `request_id` as its `X-Request-Id` if set, and returns the
+/// status, headers and JSON body of the response.
+async fn send(
+    app: &Router,
+    request: axum::http::request::Builder,
+    body: Body,
+    request_id: Option<&str>,
+) -> (StatusCode, HeaderMap, Value) {
+    let request = match request_id {
+        Some(id) => request.header("x-request-id", id),
+        None => request,
+    };
+    let request = request
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(body)
+        .expect("request");
+    let response = app.clone().oneshot(request).await.expect("response");
+    let status = response.status();
+    let headers = response.headers().clone();
+    let body = to_bytes(response.into_body(), usize::MAX)
+        .await
+        .expect("body");
+    let json = serde_json::from_slice(&body).unwrap_or(Value::Null);
+    (status, headers, json)
+}
+
+/// Returns the `X-Request-Id` header of a response.
+fn request_id(headers: &HeaderMap) -> &str {
+    headers
+        .get("x-request-id")
+        .and_then(|value| value.to_str().ok())
+        .expect("X-Request-Id")
+}
+
+#[tokio::test]
+async fn request_ids_are_generated_when_missing() {
+    let app = app();
+
+    let (status, headers, _) = send(&app, Request::get("/api/v1/posts"), Body::empty(), None).await;
+    assert_eq!(status, StatusCode::OK);
+    let first = request_id(&headers).to_string();
+    assert_eq!(first.len(), 36, "{first}");
+
+    let (status, headers, body) =
+        send(&app, Request::get("/api/v1/posts/1"), Body::empty(), None).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    let second = request_id(&headers);
+    assert_ne!(second, first);
+    assert_eq!(body["request_id"], second);
+}
+
+#[tokio::test]
+async fn request_ids_round_trip_into_responses_and_error_bodies() {
+    let app = app();
+
+    let (status, headers, body) = send(
+        &app,
+        Request::get("/api/v1/posts"),
+        Body::empty(),
+        Some("client-id-1"),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(request_id(&headers), "client-id-1");
+    assert_eq!(body, json!([]));
+
+    // Errors returned by handlers.
+    let (status, headers, body) = send(
+        &app,
+        Request::get("/api/v1/posts/1"),
+        Body::empty(),
+        Some("client-id-2"),
+    )
+    .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(request_id(&headers), "client-id-2");
+    assert_eq!(body["request_id"], "client-id-2");
+
+    // Errors returned by middleware.
+    let oversized =
+        json!({ "title": "t", "content": "x".repeat(500), "category": "c", "tags": ["t"] });
+    let (status, headers, body) = send(
+        &app,
+        Request::post("/api/v1/posts"),
+        Body::from(oversized.to_string()),
+        Some("client-id-4"),
+    )
+    .await;
+    assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
+    assert_eq!(request_id(&headers), "client-id-4");
+    assert_eq!(body["request_id"], "client-id-4");
+}
@@ -0,0 +1,211 @@
+use blog_api::{
+    model::blog::{BlogPost, BlogPostBody},
+    repository::{PostFilter, PostRepository, memory::InMemoryPostRepository},
+    site::{self, BuildReport, SiteConfig},
+};
+use chrono::{Duration, Utc};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process,
+};
+
+/// Returns a fresh output directory under the system temporary directory.
+fn output_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("blog-site-{name}-{}", process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    dir
+}
+
+/// A site of two posts per page, without custom templates.
+fn config() -> SiteConfig {
+    SiteConfig {
+        title: "Test <Blog>".to_string(),
+        description: None,
+        base_url: "https://blog.example.com".to_string(),
+        api_url: "https://api.example.com".to_string(),
+        twitter_handle: None,
+        page_size: 2,
+        templates_dir: None,
+    }
+    .validated()
+    .expect("valid configuration")
+}
+
+/// A published post in the `Rust` category.
+fn body(title: &str, slug: &str, tags: &[&str]) -> BlogPostBody {
+    BlogPostBody {
+        title: title.to_string(),
+        content: format!("Some *Markdown* about {title}."),
+        category: "Rust".to_string(),
+        tags: tags.iter().map(ToString::to_string).collect(),
+        slug: Some(Some(slug.to_string())),
+        ..BlogPostBody::default()
+    }
+}
+
+/// Builds the site of every stored post.
+async fn build(posts: &InMemoryPostRepository, output: &Path) -> BuildReport {
+    let listed: Vec<BlogPost> = posts
+        .list(&PostFilter::default())
+        .await
+        .expect("posts are listed");
+    site::build(&listed, &config(), output, Utc::now(), false).expect("site is built")
+}
+
+/// Reads a generated file.
+fn read(output: &Path, path: &str) -> String {
+    fs::read_to_string(output.join(path)).unwrap_or_else(|err| panic!("{path}: {err}"))
+}
+
+#[tokio::test]
+async fn sites_are_rebuilt_incrementally() {
+    let output = output_dir("incremental");
+    let posts = InMemoryPostRepository::new();
+    let first = posts
+        .create(&body("First", "first", &["axum"]))
+        .await
+        .expect("post is created");
+    posts
+        .create(&body("Second", "second", &["axum"]))
+        .await
+        .expect("post is created");
+    posts
+        .create(&body("Third", "third", &["sqlx"]))
+        .await
+        .expect("post is created");
+    posts
+        .create(&BlogPostBody {
+            draft: Some(true),
+            ..body("Draft", "draft", &["axum"])
+        })
+        .await
+        .expect("post is created");
+    posts
+        .create(&BlogPostBody {
+            published_at: Some(Some(Utc::now() + Duration::days(1))),
+            ..body("Scheduled", "scheduled", &["axum"])
+        })
+        .await
+        .expect("post is created");
+
+    // 3 posts, 2 index pages, 2 category pages, 1 + 1 tag pages, 2 feeds, a sitemap.
+    let report = build(&posts, &output).await;
+    assert_eq!(
+        report,
+        BuildReport {
+            written: 12,
+            unchanged: 0,
+            removed: 0
+        }
+    );
+    let page = read(&output, "posts/first/index.html");
+    assert!(
+        page.contains("<title>First · Test &lt;Blog&gt;</title>"),
+        "{page}"
+    );
+    assert!(
+        page.contains("<p>Some <em>Markdown</em> about First.</p>"),
+        "{page}"
+    );
+    assert!(
+        page.contains(r#"href="https://blog.example.com/tags/axum/""#),
+        "{page}"
+    );
+    assert!(!output.join("posts/draft").exists());
+    assert!(!output.join("posts/scheduled").exists());
+    let index = read(&output, "index.html");
+    assert!(index.find("Third") < index.find("Second"), "{index}");
+    assert!(!index.contains("First"), "{index}");
+    assert!(read(&output, "page/2/index.html").contains("First"));
+    assert!(read(&output, "tags/sqlx/index.html").contains("Third"));
+    assert!(read(&output, "atom.xml").contains("<id>https://blog.example.com/posts/first/</id>"));
+    assert!(read(&output, "rss.xml").contains("<title>Test &lt;Blog&gt;</title>"));
+    assert!(
+        read(&output, "sitemap.xml").contains("<loc>https://blog.example.com/tags/sqlx/</loc>")
+    );
+
+    let report = build(&posts, &output).await;
+    assert_eq!(
+        report,
+        BuildReport {
+            written: 0,
+            unchanged: 12,
+            removed: 0
+        }
+    );
+
+    // Updating the oldest post rewrites its page and the pages and files listing it.
+    posts
+        .update(first.id, &body("First, revised", "first", &["axum"]))
+        .await
+        .expect("post is updated")
+        .expect("post exists");
+    let report = build(&posts, &output).await;
+    assert_eq!(
+        report,
+        BuildReport {
+            written: 7,
+            unchanged: 5,
+            removed: 0
+        }
+    );
+    assert!(read(&output, "posts/first/index.html").contains("First, revised"));
+    assert!(read(&output, "page/2/index.html").contains("First, revised"));
+
+    // Files of posts that are no longer published are removed, along with their
+    // directories; files the build did not write are kept.
+    fs::write(output.join("style.css"), "body {}").expect("stylesheet is written");
+    posts.delete(first.id).await.expect("post is deleted");
+    let report = build(&posts, &output).await;
+    assert_eq!(report.removed, 3);
+    assert!(!output.join("posts/first").exists());
+    assert!(!output.join("page").exists());
+    assert!(!output.join("categories/rust/page").exists());
+    assert!(output.join("style.css").exists());
+
+    fs::remove_dir_all(&output).expect("output is removed");
+}
+
+#[tokio::test]
+async fn templates_can_be_replaced() {
+    let output = output_dir("templates");
+    let templates = output_dir("templates-source");
+    fs::create_dir_all(&templates).expect("template directory is created");
+    fs::write(
+        templates.join("post.html"),
+        "<h1>{{ post.title }}</h1>{{ content }}",
+    )
+    .expect("template is written");
+    let posts = InMemoryPostRepository::new();
+    posts
+        .create(&body("Only", "only", &["axum"]))
+        .await
+        .expect("post is created");
+    let listed = posts
+        .list(&PostFilter::default())
+        .await
+        .expect("posts are listed");
+    let config = SiteConfig {
+        templates_dir: Some(templates.clone()),
+        ..config()
+    };
+
+    site::build(&listed, &config, &output, Utc::now(), false).expect("site is built");
+    assert_eq!(
+        read(&output, "posts/only/index.html"),
+        "<h1>Only</h1><p>Some <em>Markdown</em> about Only.</p>\n"
+    );
+    // The list pages still use the built-in templates.
+    assert!(read(&output, "index.html").starts_with("<!doctype html>"));
+
+    // Changing a template rebuilds every page, even if no post changed.
+    fs::write(templates.join("post.html"), "{{ post.title }}").expect("template is written");
+    let report =
+        site::build(&listed, &config, &output, Utc::now(), false).expect("site is rebuilt");
+    assert_eq!(report.unchanged, 0);
+    assert_eq!(read(&output, "posts/only/index.html"), "Only");
+
+    fs::remove_dir_all(&output).expect("output is removed");
+    fs::remove_dir_all(&templates).expect("templates are removed");
+}
@@ -0,0 +1,100 @@
+mod common;
+
+use axum::{
+    Router,
+    body::{Body, Bytes, to_bytes},
+    extract::State,
+    http::{HeaderMap, Request, StatusCode, header},
+    routing::post,
+};
+use blog_api::{
+    repository::memory::InMemoryPostRepository,
+    server::routes::setup_routes,
+    telemetry::{TelemetryConfig, init_tracing},
+};
+use std::sync::{Arc, Mutex};
+use tokio::net::TcpListener;
+use tower::ServiceExt;
+
+/// Trace ID of the `traceparent` header sent with the request.
+const TRACE_ID: &str = "4bf92f3577b34da6a3ce929d0e0e4736";
+
+/// Requests received by the stub collector: their `Content-Type` and body.
+type Received = Arc<Mutex<Vec<(String, Bytes)>>>;
+
+/// Stub OTLP/HTTP collector endpoint, recording every export request.
+async fn collect(State(received): State<Received>, headers: HeaderMap, body: Bytes) -> StatusCode {
+    let content_type = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default()
+        .to_string();
+    received.lock().expect("lock").push((content_type, body));
+    StatusCode::OK
+}
+
+/// Returns whether `haystack` contains `needle`.
+fn contains(haystack: &[u8], needle: &[u8]) -> bool {
+    haystack
+        .windows(needle.len())
+        .any(|window| window == needle)
+}
+
+// Installing the tracing subscriber is global to the process, so this file holds a
+// single test.
+#[test]
+fn request_spans_are_exported_over_otlp() {
+    let runtime = tokio::runtime::Runtime::new().expect("runtime");
+    let received = Received::default();
+    let listener = runtime
+        .block_on(TcpListener::bind("127.0.0.1:0"))
+        .expect("listener");
+    let address = listener.local_addr().expect("address");
+    let collector = Router::new()
+        .route("/v1/traces", post(collect))
+        .with_state(received.clone());
+    runtime.spawn(async move { axum::serve(listener, collector).await });
+
+    // SAFETY: no other thread of this test binary reads or writes the environment yet.
+    unsafe {
+        std::env::set_var(
+            "OTEL_EXPORTER_OTLP_TRACES_ENDPOINT",
+            format!("http://{address}/v1/traces"),
+        );
+        std::env::set_var("OTEL_SERVICE_NAME", "blog-api-telemetry-test");
+        std::env::set_var("RUST_LOG", "info");
+    }
+    let config = TelemetryConfig::from_env().expect("telemetry config");
+    assert!(config.otlp_enabled);
+    let guard = init_tracing(&config).expect("tracing");
+
+    let app = setup_routes(common::state_with(Arc::new(InMemoryPostRepository::new())));
+    let status = runtime.block_on(async {
+        let request = Request::get("/api/v1/posts")
+            .header("traceparent", format!("00-{TRACE_ID}-00f067aa0ba902b7-01"))
+            .body(Body::empty())
+            .expect("request");
+        let response = app.oneshot(request).await.expect("response");
+        let status = response.status();
+        to_bytes(response.into_body(), usize::MAX)
+            .await
+            .expect("body");
+        status
+    });
+    assert_eq!(status, StatusCode::OK);
+
+    // Shutting down the tracer provider flushes the batched spans.
+    drop(guard);
+
+    let received = received.lock().expect("lock");
+    assert!(!received.is_empty(), "no spans were exported");
+    let (content_type, body) = received
+        .iter()
+        .find(|(_, body)| contains(body, b"GET /api/v1/posts"))
+        .expect("request span");
+    assert_eq!(content_type, "application/x-protobuf");
+    assert!(contains(body, b"blog-api-telemetry-test"));
+    // The span continues the trace of the incoming `traceparent` header.
+    let trace_id = hex::decode(TRACE_ID).expect("trace ID");
+    assert!(contains(body, &trace_id));
+}
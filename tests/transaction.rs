@@ -0,0 +1,164 @@
+//! These tests need a PostgreSQL database. Run them with
+//! `TEST_DATABASE_URL=postgres://... cargo test -- --ignored`.
+
+use axum::{
+    Router,
+    body::Body,
+    extract::Path,
+    http::{Request, StatusCode},
+    middleware,
+    routing::post,
+};
+use blog_api::{
+    database::{
+        connection::pg_connect,
+        extractor::{DatabaseTransaction, finish_transaction},
+    },
+    error::AppError,
+};
+use sqlx::PgPool;
+use tower::ServiceExt;
+
+/// Inserts a post with the given title.
+async fn insert(conn: &mut sqlx::PgConnection, title: &str) -> Result<(), AppError> {
+    sqlx::query(
+        "INSERT INTO blog_posts (title, content, category, tags) VALUES ($1, '', '', '{}')",
+    )
+    .bind(title)
+    .execute(conn)
+    .await?;
+    Ok(())
+}
+
+/// Inserts a post and succeeds.
+async fn insert_ok(
+    mut tx: DatabaseTransaction,
+    Path(title): Path<String>,
+) -> Result<StatusCode, AppError> {
+    insert(&mut tx, &title).await?;
+    Ok(StatusCode::CREATED)
+}
+
+/// Inserts a post and then fails.
+async fn insert_err(
+    mut tx: DatabaseTransaction,
+    Path(title): Path<String>,
+) -> Result<StatusCode, AppError> {
+    insert(&mut tx, &title).await?;
+    Err(AppError::BadRequest("rejected after insert".to_string()))
+}
+
+/// Inserts a post and then panics.
+async fn insert_panic(mut tx: DatabaseTransaction, Path(title): Path<String>) -> StatusCode {
+    insert(&mut tx, &title).await.expect("insert");
+    panic!("handler panicked after insert");
+}
+
+/// Inserts posts in the outer transaction and in two savepoints, one rolled back.
+async fn insert_nested(
+    mut tx: DatabaseTransaction,
+    Path(title): Path<String>,
+) -> Result<StatusCode, AppError> {
+    insert(&mut tx, &format!("{title}-outer")).await?;
+
+    let mut discarded = tx.savepoint().await?;
+    insert(&mut discarded, &format!("{title}-discarded")).await?;
+    discarded.rollback().await?;
+
+    let mut kept = tx.savepoint().await?;
+    insert(&mut kept, &format!("{title}-kept")).await?;
+    let mut innermost = sqlx::Connection::begin(&mut *kept).await?;
+    insert(&mut innermost, &format!("{title}-innermost")).await?;
+    drop(innermost);
+    kept.commit().await?;
+
+    Ok(StatusCode::CREATED)
+}
+
+/// Connects to the test database and builds a router around the handlers above.
+async fn app() -> (PgPool, Router) {
+    let url = std::env::var("TEST_DATABASE_URL").expect("TEST_DATABASE_URL is not set");
+    let pool = pg_connect(&url).await.expect("test database");
+    let app = Router::new()
+        .route("/ok/{title}", post(insert_ok))
+        .route("/err/{title}", post(insert_err))
+        .route("/panic/{title}", post(insert_panic))
+        .route("/nested/{title}", post(insert_nested))
+        .layer(middleware::from_fn(finish_transaction))
+        .with_state(Some(pool.clone()));
+    (pool, app)
+}
+
+/// Sends a `POST` request, returning `None` if the handler panicked.
+async fn send(app: Router, uri: String) -> Option<StatusCode> {
+    let request = Request::post(uri).body(Body::empty()).expect("request");
+    tokio::spawn(app.oneshot(request))
+        .await
+        .ok()
+        .map(|response| response.expect("response").status())
+}
+
+/// Returns whether a post with the given title exists.
+async fn exists(pool: &PgPool, title: &str) -> bool {
+    sqlx::query_scalar("SELECT EXISTS (SELECT 1 FROM blog_posts WHERE title = $1)")
+        .bind(title)
+        .fetch_one(pool)
+        .await
+        .expect("query")
+}
+
+/// Returns a title no other test run uses.
+fn unique(name: &str) -> String {
+    format!(
+        "tx-{name}-{}",
+        chrono::Utc::now().timestamp_nanos_opt().unwrap_or_default()
+    )
+}
+
+#[tokio::test]
+#[ignore = "requires TEST_DATABASE_URL"]
+async fn successful_handlers_commit() {
+    let (pool, app) = app().await;
+    let title = unique("ok");
+    assert_eq!(
+        send(app, format!("/ok/{title}")).await,
+        Some(StatusCode::CREATED)
+    );
+    assert!(exists(&pool, &title).await);
+}
+
+#[tokio::test]
+#[ignore = "requires TEST_DATABASE_URL"]
+async fn failing_handlers_roll_back() {
+    let (pool, app) = app().await;
+    let title = unique("err");
+    assert_eq!(
+        send(app, format!("/err/{title}")).await,
+        Some(StatusCode::BAD_REQUEST)
+    );
+    assert!(!exists(&pool, &title).await);
+}
+
+#[tokio::test]
+#[ignore = "requires TEST_DATABASE_URL"]
+async fn panicking_handlers_roll_back() {
+    let (pool, app) = app().await;
+    let title = unique("panic");
+    assert_eq!(send(app, format!("/panic/{title}")).await, None);
+    assert!(!exists(&pool, &title).await);
+}
+
+#[tokio::test]
+#[ignore = "requires TEST_DATABASE_URL"]
+async fn savepoints_scope_nested_rollbacks() {
+    let (pool, app) = app().await;
+    let title = unique("nested");
+    assert_eq!(
+        send(app, format!("/nested/{title}")).await,
+        Some(StatusCode::CREATED)
+    );
+    assert!(exists(&pool, &format!("{title}-outer")).await);
+    assert!(!exists(&pool, &format!("{title}-discarded")).await);
+    assert!(exists(&pool, &format!("{title}-kept")).await);
+    assert!(!exists(&pool, &format!("{title}-innermost")).await);
+}
@@ -0,0 +1,186 @@
+mod common;
+
+use axum::{
+    Router,
+    body::{Body, to_bytes},
+    http::{HeaderMap, Request, StatusCode, header},
+};
+use blog_api::{
+    repository::memory::InMemoryPostRepository,
+    server::{
+        middleware::versioning::{ApiVersion, Deprecation, VersioningConfig},
+        routes::setup_routes,
+    },
+};
+use chrono::{TimeZone, Utc};
+use serde_json::{Value, json};
+use std::sync::Arc;
+use tower::ServiceExt;
+
+/// Builds an application with empty storage and the given versioning configuration.
+fn versioned_app(
+    default_version: ApiVersion,
+    deprecations: Vec<(ApiVersion, Deprecation)>,
+) -> Router {
+    let mut state = common::state_with(Arc::new(InMemoryPostRepository::new()));
+    state.versioning = Arc::new(VersioningConfig {
+        default_version,
+        deprecations,
+    });
+    setup_routes(state)
+}
+
+/// Sends a `GET` request with an optional `Accept` header and returns the status,
+/// headers and JSON body of the response.
+async fn get(app: &Router, uri: &str, accept: Option<&str>) -> (StatusCode, HeaderMap, Value) {
+    let mut request = Request::get(uri);
+    if let Some(accept) = accept {
+        request = request.header(header::ACCEPT, accept);
+    }
+    let request = request.body(Body::empty()).expect("request");
+    let response = app.clone().oneshot(request).await.expect("response");
+    let status = response.status();
+    let headers = response.headers().clone();
+    let body = to_bytes(response.into_body(), usize::MAX)
+        .await
+        .expect("body");
+    let json = serde_json::from_slice(&body).unwrap_or(Value::Null);
+    (status, headers, json)
+}
+
+/// Returns a header of a response as a string, if set.
+fn header_value<'a>(headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+    headers.get(name).and_then(|value| value.to_str().ok())
+}
+
+/// Returns whether a response varies on the `Accept` header.
+fn varies_on_accept(headers: &HeaderMap) -> bool {
+    headers
+        .get_all(header::VARY)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .any(|name| name.trim().eq_ignore_ascii_case("accept"))
+}
+
+/// A deprecation notice for version 1, with the given sunset and successor.
+fn deprecation(sunset: bool, successor: bool) -> Vec<(ApiVersion, Deprecation)> {
+    vec![(
+        ApiVersion::V1,
+        Deprecation {
+            deprecated_at: Utc
+                .with_ymd_and_hms(2026, 1, 1, 0, 0, 0)
+                .single()
+                .expect("date"),
+            sunset: sunset.then(|| {
+                Utc.with_ymd_and_hms(2027, 7, 1, 12, 30, 0)
+                    .single()
+                    .expect("date")
+            }),
+            successor: successor.then_some("/api/v2"),
+        },
+    )]
+}
+
+#[tokio::test]
+async fn unversioned_paths_negotiate_the_version_from_accept() {
+    let app = versioned_app(ApiVersion::V1, Vec::new());
+
+    for (accept, version) in [
+        (None, "1"),
+        (Some("application/json"), "1"),
+        (Some("application/vnd.blog.v2+json"), "2"),
+        (Some("application/vnd.blog.v1+json"), "1"),
+        (Some("application/json; version=2"), "2"),
+        (Some("application/json;Version=1"), "1"),
+        (Some("text/html, application/vnd.blog.v2+json;q=0.9"), "2"),
+    ] {
+        let (status, headers, body) = get(&app, "/posts", accept).await;
+        assert_eq!(status, StatusCode::OK, "{accept:?}");
+        assert_eq!(
+            header_value(&headers, "api-version"),
+            Some(version),
+            "{accept:?}"
+        );
+        assert!(varies_on_accept(&headers), "{accept:?}");
+        let expected = match version {
+            "1" => json!([]),
+            _ => json!({ "data": [], "count": 0 }),
+        };
+        assert_eq!(body, expected, "{accept:?}");
+    }
+
+    // Versioned paths ignore the `Accept` header and do not vary on it.
+    let (status, headers, _) =
+        get(&app, "/api/v2/posts", Some("application/vnd.blog.v1+json")).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(header_value(&headers, "api-version"), Some("2"));
+    assert!(!varies_on_accept(&headers));
+
+    let app = versioned_app(ApiVersion::V2, Vec::new());
+    let (_, headers, _) = get(&app, "/posts", None).await;
+    assert_eq!(header_value(&headers, "api-version"), Some("2"));
+    let (_, headers, _) = get(&app, "/posts", Some("application/vnd.blog.v1+json")).await;
+    assert_eq!(header_value(&headers, "api-version"), Some("1"));
+}
+
+#[tokio::test]
+async fn unknown_versions_are_not_acceptable() {
+    let app = versioned_app(ApiVersion::V1, Vec::new());
+
+    for (accept, number) in [
+        ("application/vnd.blog.v3+json", "3"),
+        ("application/json; version=0", "0"),
+        ("application/vnd.blog.vnext+json", "next"),
+    ] {
+        let (status, headers, body) = get(&app, "/posts", Some(accept)).await;
+        assert_eq!(status, StatusCode::NOT_ACCEPTABLE, "{accept}");
+        assert_eq!(
+            body["error"],
+            format!("API version {number} is not supported"),
+            "{accept}"
+        );
+        assert_eq!(header_value(&headers, "api-version"), None, "{accept}");
+    }
+}
+
+#[tokio::test]
+async fn deprecated_versions_announce_their_sunset() {
+    let app = versioned_app(ApiVersion::V1, deprecation(true, true));
+
+    // Every response of the deprecated version carries the notice, errors included.
+    for uri in ["/api/v1/posts", "/api/v1/posts/1", "/posts"] {
+        let (_, headers, _) = get(&app, uri, None).await;
+        assert_eq!(
+            header_value(&headers, "deprecation"),
+            Some("@1767225600"),
+            "{uri}"
+        );
+        assert_eq!(
+            header_value(&headers, "sunset"),
+            Some("Thu, 01 Jul 2027 12:30:00 GMT"),
+            "{uri}"
+        );
+        assert_eq!(
+            header_value(&headers, "link"),
+            Some("</api/v2>; rel=\"successor-version\""),
+            "{uri}"
+        );
+    }
+
+    for (uri, accept) in [
+        ("/api/v2/posts", None),
+        ("/posts", Some("application/vnd.blog.v2+json")),
+    ] {
+        let (_, headers, _) = get(&app, uri, accept).await;
+        for name in ["deprecation", "sunset", "link"] {
+            assert_eq!(header_value(&headers, name), None, "{uri}: {name}");
+        }
+    }
+
+    let app = versioned_app(ApiVersion::V1, deprecation(false, false));
+    let (_, headers, _) = get(&app, "/api/v1/posts", None).await;
+    assert_eq!(header_value(&headers, "deprecation"), Some("@1767225600"));
+    assert_eq!(header_value(&headers, "sunset"), None);
+    assert_eq!(header_value(&headers, "link"), None);
+}
@@ -0,0 +1,625 @@
+mod common;
+
+use axum::{
+    Router,
+    body::Bytes,
+    extract::State,
+    http::{HeaderMap, Method, StatusCode, header},
+    routing::post,
+};
+use blog_api::{
+    jobs::{JobHandler, Jobs, JobsConfig},
+    model::webhook::{DeliveryFilter, DeliveryStatus, WebhookBody, WebhookEvent},
+    outbox::{Dispatcher, OutboxConfig},
+    repository::AnnounceScheduledPosts,
+    server::{middleware::admin::AdminConfig, routes::setup_routes},
+    webhook::{
+        DELIVERY_HEADER, DeliverWebhook, EVENT_HEADER, SIGNATURE_HEADER, WebhookConfig, Webhooks,
+        signature,
+    },
+};
+use chrono::Utc;
+use common::{ADMIN_TOKEN, send, send_admin, send_with};
+use serde_json::{Value, json};
+use std::{
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicUsize, Ordering},
+    },
+    time::Duration,
+};
+use tokio::net::TcpListener;
+
+/// Secret of the webhooks created by the tests.
+const SECRET: &str = "test-webhook-secret";
+
+/// A local HTTP server recording the webhook requests it receives.
+#[derive(Default)]
+struct Receiver {
+    /// Headers and bodies of the requests received, in order.
+    requests: Mutex<Vec<(HeaderMap, Bytes)>>,
+    /// How many of the next requests are answered with `503 Service Unavailable`
+    /// instead of `204 No Content`.
+    failures: AtomicUsize,
+}
+
+impl Receiver {
+    /// Starts a receiver and returns it with the URL webhooks are sent to.
+    async fn start() -> (Arc<Self>, String) {
+        let receiver = Arc::new(Self::default());
+        let listener = TcpListener::bind("127.0.0.1:0").await.expect("listener");
+        let url = format!("http://{}/hook", listener.local_addr().expect("address"));
+        let app = Router::new()
+            .route("/hook", post(receive))
+            .with_state(receiver.clone());
+        tokio::spawn(async move { axum::serve(listener, app).await });
+        (receiver, url)
+    }
+
+    /// Returns the requests received so far.
+    fn requests(&self) -> Vec<(HeaderMap, Bytes)> {
+        self.requests.lock().expect("requests").clone()
+    }
+
+    /// Waits until `count` requests have been received, and returns them.
+    async fn wait_for(&self, count: usize) -> Vec<(HeaderMap, Bytes)> {
+        for _ in 0..200 {
+            let requests = self.requests();
+            if requests.len() >= count {
+                return requests;
+            }
+            tokio::time::sleep(Duration::from_millis(25)).await;
+        }
+        panic!(
+            "received {} webhook requests, expected {count}",
+            self.requests().len()
+        );
+    }
+}
+
+/// Records a webhook request, failing it if failures are left.
+async fn receive(
+    State(receiver): State<Arc<Receiver>>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> StatusCode {
+    receiver
+        .requests
+        .lock()
+        .expect("requests")
+        .push((headers, body));
+    let failed = receiver
+        .failures
+        .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |left| {
+            left.checked_sub(1)
+        })
+        .is_ok();
+    if failed {
+        StatusCode::SERVICE_UNAVAILABLE
+    } else {
+        StatusCode::NO_CONTENT
+    }
+}
+
+/// Returns a configuration retrying quickly, at most `max_attempts` times.
+fn config(max_attempts: u32) -> WebhookConfig {
+    let mut config = common::webhook_config();
+    config.max_attempts = max_attempts;
+    config.retry_base = Duration::from_millis(50);
+    config.retry_max = Duration::from_millis(200);
+    config
+}
+
+/// Returns a job queue configuration polling quickly.
+fn jobs_config() -> JobsConfig {
+    let mut config = JobsConfig::from_env().expect("jobs config");
+    config.poll_interval = Duration::from_millis(25);
+    config
+}
+
+/// Returns an outbox configuration polling quickly.
+fn outbox_config() -> OutboxConfig {
+    let mut config = OutboxConfig::from_env().expect("outbox config");
+    config.poll_interval = Duration::from_millis(10);
+    config
+}
+
+/// Builds one application per backend, with its outbox dispatcher and delivery jobs
+/// running, delivering webhooks at most `max_attempts` times.
+async fn apps_delivering(max_attempts: u32) -> Vec<(&'static str, Router)> {
+    let mut apps = Vec::new();
+    for backend in common::backends().await {
+        let mut state = backend.state();
+        let deliver =
+            DeliverWebhook::new(backend.webhooks.clone(), config(max_attempts)).expect("handler");
+        state.jobs = Jobs::new(backend.jobs, jobs_config()).with_handler(deliver);
+        state.jobs.spawn();
+        state.webhooks = Webhooks::new(backend.webhooks, config(max_attempts), state.jobs.clone());
+        Dispatcher::new(backend.outbox, outbox_config())
+            .with_consumer(Arc::new(state.webhooks.clone()))
+            .spawn();
+        apps.push((backend.name, setup_routes(state)));
+    }
+    apps
+}
+
+/// A valid request body for creating a post.
+fn post_body(title: &str, draft: bool) -> Value {
+    json!({
+        "title": title,
+        "content": "Content",
+        "category": "Rust",
+        "tags": ["rust"],
+        "draft": draft
+    })
+}
+
+/// Creates a webhook sending `events` to `url`, and returns its ID.
+async fn subscribe(app: &Router, url: &str, events: &[&str]) -> i64 {
+    let (status, webhook) = send_admin(
+        app,
+        Method::POST,
+        "/api/v1/webhooks",
+        Some(json!({ "url": url, "secret": SECRET, "events": events })),
+    )
+    .await;
+    assert_eq!(status, StatusCode::CREATED, "{webhook}");
+    assert!(webhook.get("secret").is_none(), "{webhook}");
+    webhook["id"].as_i64().expect("webhook ID")
+}
+
+/// Returns the deliveries of a webhook, newest first, once none is pending.
+async fn settled_deliveries(app: &Router, webhook_id: i64) -> Vec<Value> {
+    let uri = format!("/api/v1/webhooks/{webhook_id}/deliveries");
+    for _ in 0..200 {
+        let (status, deliveries) = send_admin(app, Method::GET, &uri, None).await;
+        assert_eq!(status, StatusCode::OK, "{deliveries}");
+        let deliveries = deliveries.as_array().expect("deliveries").clone();
+        if deliveries
+            .iter()
+            .all(|delivery| delivery["status"] != "pending")
+        {
+            return deliveries;
+        }
+        tokio::time::sleep(Duration::from_millis(25)).await;
+    }
+    panic!("webhook deliveries are still pending");
+}
+
+/// Returns the jobs of a kind, newest first, once none is queued or running.
+async fn settled_jobs(app: &Router, kind: &str) -> Vec<Value> {
+    let uri = format!("/api/v1/jobs?kind={kind}");
+    for _ in 0..200 {
+        let (status, jobs) = send_admin(app, Method::GET, &uri, None).await;
+        assert_eq!(status, StatusCode::OK, "{jobs}");
+        let jobs = jobs.as_array().expect("jobs").clone();
+        if jobs
+            .iter()
+            .all(|job| job["status"] == "succeeded" || job["status"] == "dead")
+        {
+            return jobs;
+        }
+        tokio::time::sleep(Duration::from_millis(25)).await;
+    }
+    panic!("{kind} jobs are still queued or running");
+}
+
+/// Returns the value of a header of a received request.
+fn header_value<'a>(headers: &'a HeaderMap, name: &str) -> &'a str {
+    headers
+        .get(name)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default()
+}
+
+#[tokio::test]
+async fn events_are_signed_and_retried_until_delivered() {
+    for (name, app) in apps_delivering(5).await {
+        let (receiver, url) = Receiver::start().await;
+        let webhook_id = subscribe(&app, &url, &[]).await;
+
+        // The first attempt of one of the two events fails and is retried.
+        receiver.failures.store(1, Ordering::SeqCst);
+        let (status, post) = send(
+            &app,
+            Method::POST,
+            "/api/v1/posts",
+            Some(post_body("Hooked", false)),
+        )
+        .await;
+        assert_eq!(status, StatusCode::CREATED, "{name}: {post}");
+
+        let requests = receiver.wait_for(3).await;
+        for (headers, body) in &requests {
+            let signed = header_value(headers, SIGNATURE_HEADER);
+            let (timestamp, sent) = signed
+                .strip_prefix("t=")
+                .and_then(|rest| rest.split_once(",v1="))
+                .unwrap_or_else(|| panic!("{name}: malformed signature {signed:?}"));
+            let timestamp: i64 = timestamp.parse().expect("timestamp");
+            assert_eq!(sent, signature(SECRET, timestamp, body), "{name}");
+            assert_eq!(
+                header_value(headers, header::CONTENT_TYPE.as_str()),
+                "application/json",
+                "{name}"
+            );
+
+            let payload: Value = serde_json::from_slice(body).expect("payload");
+            assert_eq!(
+                payload["event"],
+                header_value(headers, EVENT_HEADER),
+                "{name}"
+            );
+            assert_eq!(payload["post"], post, "{name}");
+        }
+
+        let deliveries = settled_deliveries(&app, webhook_id).await;
+        let mut events: Vec<&str> = deliveries
+            .iter()
+            .map(|delivery| delivery["event"].as_str().unwrap_or_default())
+            .collect();
+        events.sort_unstable();
+        assert_eq!(events, ["post.created", "post.published"], "{name}");
+        for delivery in &deliveries {
+            assert_eq!(delivery["status"], "succeeded", "{name}: {delivery}");
+            assert_eq!(delivery["response_status"], 204, "{name}: {delivery}");
+            assert!(delivery["delivered_at"].is_string(), "{name}: {delivery}");
+        }
+        let retried: Vec<&Value> = deliveries
+            .iter()
+            .filter(|delivery| delivery["attempts"] == 2)
+            .collect();
+        assert_eq!(retried.len(), 1, "{name}: {deliveries:?}");
+
+        // Retries of a delivery carry the same delivery ID.
+        let retried_id = retried[0]["id"].to_string();
+        let sent_with_id = requests
+            .iter()
+            .filter(|(headers, _)| header_value(headers, DELIVERY_HEADER) == retried_id)
+            .count();
+        assert_eq!(sent_with_id, 2, "{name}");
+    }
+}
+
+#[tokio::test]
+async fn webhooks_receive_the_events_they_subscribe_to() {
+    for (name, app) in apps_delivering(3).await {
+        let (receiver, url) = Receiver::start().await;
+        let webhook_id = subscribe(
+            &app,
+            &url,
+            &["post.published", "post.unpublished", "post.deleted"],
+        )
+        .await;
+
+        let (status, post) = send(
+            &app,
+            Method::POST,
+            "/api/v1/posts",
+            Some(post_body("Draft", true)),
+        )
+        .await;
+        assert_eq!(status, StatusCode::CREATED, "{name}: {post}");
+        let uri = format!("/api/v1/posts/{}", post["id"]);
+
+        let mut expected = Vec::new();
+        for (draft, event) in [(false, "post.published"), (true, "post.unpublished")] {
+            let (status, _) = send(&app, Method::PUT, &uri, Some(post_body("Draft", draft))).await;
+            assert_eq!(status, StatusCode::OK, "{name}");
+            expected.push(event);
+            receiver.wait_for(expected.len()).await;
+        }
+        let (status, _) = send(&app, Method::DELETE, &uri, None).await;
+        assert_eq!(status, StatusCode::NO_CONTENT, "{name}");
+        expected.push("post.deleted");
+
+        let requests = receiver.wait_for(expected.len()).await;
+        let events: Vec<&str> = requests
+            .iter()
+            .map(|(headers, _)| header_value(headers, EVENT_HEADER))
+            .collect();
+        assert_eq!(events, expected, "{name}");
+
+        let deliveries = settled_deliveries(&app, webhook_id).await;
+        let logged: Vec<&str> = deliveries
+            .iter()
+            .rev()
+            .map(|delivery| delivery["event"].as_str().unwrap_or_default())
+            .collect();
+        assert_eq!(logged, expected, "{name}");
+    }
+}
+
+#[tokio::test]
+async fn scheduled_posts_are_published_once_their_date_passes() {
+    for backend in common::backends().await {
+        let name = backend.name;
+        let (receiver, url) = Receiver::start().await;
+        let mut state = backend.state();
+        let deliver = DeliverWebhook::new(backend.webhooks.clone(), config(3)).expect("handler");
+        state.jobs = Jobs::new(backend.jobs, jobs_config())
+            .with_handler(deliver)
+            .with_handler(AnnounceScheduledPosts::new(backend.posts.clone()));
+        state.jobs.spawn();
+        state.webhooks = Webhooks::new(backend.webhooks, config(3), state.jobs.clone());
+        Dispatcher::new(backend.outbox, outbox_config())
+            .with_consumer(Arc::new(state.webhooks.clone()))
+            .spawn();
+        let jobs = state.jobs.clone();
+        let app = setup_routes(state);
+        let webhook_id = subscribe(&app, &url, &["post.published"]).await;
+
+        let published_at = Utc::now() + chrono::Duration::seconds(1);
+        let mut body = post_body("Scheduled", false);
+        body["published_at"] = json!(published_at);
+        let (status, post) = send(&app, Method::POST, "/api/v1/posts", Some(body)).await;
+        assert_eq!(status, StatusCode::CREATED, "{name}: {post}");
+        assert!(
+            settled_deliveries(&app, webhook_id).await.is_empty(),
+            "{name}"
+        );
+
+        // The job finds the post once its date has passed, and announces it once.
+        for _ in 0..2 {
+            jobs.enqueue_at::<AnnounceScheduledPosts>(&(), published_at)
+                .await
+                .expect("enqueue");
+        }
+        let requests = receiver.wait_for(1).await;
+        assert_eq!(
+            header_value(&requests[0].0, EVENT_HEADER),
+            "post.published",
+            "{name}"
+        );
+        let delivered: Value = serde_json::from_slice(&requests[0].1).expect("body");
+        assert_eq!(delivered["post"]["id"], post["id"], "{name}");
+
+        let announcements = settled_jobs(&app, AnnounceScheduledPosts::KIND).await;
+        assert_eq!(announcements.len(), 2, "{name}");
+        let deliveries = settled_deliveries(&app, webhook_id).await;
+        assert_eq!(deliveries.len(), 1, "{name}: {deliveries:?}");
+        assert_eq!(receiver.requests().len(), 1, "{name}");
+    }
+}
+
+#[tokio::test]
+async fn failed_deliveries_can_be_redelivered() {
+    for (name, app) in apps_delivering(2).await {
+        let (receiver, url) = Receiver::start().await;
+        let webhook_id = subscribe(&app, &url, &["post.created"]).await;
+
+        receiver.failures.store(2, Ordering::SeqCst);
+        let (status, _) = send(
+            &app,
+            Method::POST,
+            "/api/v1/posts",
+            Some(post_body("Unlucky", false)),
+        )
+        .await;
+        assert_eq!(status, StatusCode::CREATED, "{name}");
+        receiver.wait_for(2).await;
+
+        let deliveries = settled_deliveries(&app, webhook_id).await;
+        assert_eq!(deliveries.len(), 1, "{name}: {deliveries:?}");
+        let failed = &deliveries[0];
+        assert_eq!(failed["status"], "failed", "{name}: {failed}");
+        assert_eq!(failed["attempts"], 2, "{name}: {failed}");
+        assert_eq!(failed["response_status"], 503, "{name}: {failed}");
+        assert!(failed["error"].is_string(), "{name}: {failed}");
+        assert!(failed["next_attempt_at"].is_null(), "{name}: {failed}");
+
+        let (status, filtered) = send_admin(
+            &app,
+            Method::GET,
+            &format!("/api/v1/webhooks/{webhook_id}/deliveries?status=succeeded"),
+            None,
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK, "{name}");
+        assert_eq!(filtered, json!([]), "{name}");
+
+        let (status, redelivery) = send_admin(
+            &app,
+            Method::POST,
+            &format!(
+                "/api/v1/webhooks/{webhook_id}/deliveries/{}/redeliver",
+                failed["id"]
+            ),
+            None,
+        )
+        .await;
+        assert_eq!(status, StatusCode::ACCEPTED, "{name}: {redelivery}");
+        assert_ne!(redelivery["id"], failed["id"], "{name}");
+        assert_eq!(redelivery["payload"], failed["payload"], "{name}");
+
+        let requests = receiver.wait_for(3).await;
+        assert_eq!(requests[2].1, requests[0].1, "{name}");
+        let deliveries = settled_deliveries(&app, webhook_id).await;
+        assert_eq!(deliveries[0]["id"], redelivery["id"], "{name}");
+        assert_eq!(deliveries[0]["status"], "succeeded", "{name}");
+        assert_eq!(deliveries[1]["status"], "failed", "{name}");
+
+        // Each delivery is sent by a job, which dies with it after its last attempt.
+        let jobs = settled_jobs(&app, DeliverWebhook::KIND).await;
+        assert_eq!(jobs.len(), 2, "{name}: {jobs:?}");
+        assert_eq!(jobs[0]["payload"], redelivery["id"], "{name}");
+        assert_eq!(jobs[0]["status"], "succeeded", "{name}");
+        assert_eq!(jobs[1]["payload"], failed["id"], "{name}");
+        assert_eq!(jobs[1]["status"], "dead", "{name}");
+        assert_eq!(jobs[1]["attempts"], 2, "{name}");
+        assert_eq!(jobs[1]["max_attempts"], 2, "{name}");
+
+        let (status, _) = send_admin(
+            &app,
+            Method::POST,
+            &format!("/api/v1/webhooks/{webhook_id}/deliveries/999/redeliver"),
+            None,
+        )
+        .await;
+        assert_eq!(status, StatusCode::NOT_FOUND, "{name}");
+    }
+}
+
+#[tokio::test]
+async fn webhooks_can_be_managed() {
+    for (name, app) in apps_delivering(1).await {
+        let (status, _) = send_admin(
+            &app,
+            Method::POST,
+            "/api/v1/webhooks",
+            Some(json!({ "url": "ftp://example.com/hook", "secret": SECRET })),
+        )
+        .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{name}");
+        let (status, _) = send_admin(
+            &app,
+            Method::POST,
+            "/api/v1/webhooks",
+            Some(json!({ "url": "https://example.com/hook", "secret": "short" })),
+        )
+        .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST, "{name}");
+        let (status, _) = send_admin(
+            &app,
+            Method::POST,
+            "/api/v1/webhooks",
+            Some(json!({
+                "url": "https://example.com/hook",
+                "secret": SECRET,
+                "events": ["post.renamed"]
+            })),
+        )
+        .await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY, "{name}");
+
+        let id = subscribe(&app, "https://example.com/hook", &["post.deleted"]).await;
+        let uri = format!("/api/v1/webhooks/{id}");
+        let (status, updated) = send_admin(
+            &app,
+            Method::PUT,
+            &uri,
+            Some(json!({ "url": "https://example.org/hook", "secret": SECRET })),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK, "{name}: {updated}");
+        assert_eq!(updated["url"], "https://example.org/hook", "{name}");
+        assert_eq!(updated["events"], json!([]), "{name}");
+
+        let (status, listed) = send_admin(&app, Method::GET, "/api/v1/webhooks", None).await;
+        assert_eq!(status, StatusCode::OK, "{name}");
+        assert_eq!(listed, json!([updated]), "{name}");
+
+        let (status, _) = send_admin(&app, Method::DELETE, &uri, None).await;
+        assert_eq!(status, StatusCode::NO_CONTENT, "{name}");
+        for method in [Method::GET, Method::DELETE] {
+            let (status, _) = send_admin(&app, method, &uri, None).await;
+            assert_eq!(status, StatusCode::NOT_FOUND, "{name}");
+        }
+        let (status, _) = send_admin(&app, Method::GET, &format!("{uri}/deliveries"), None).await;
+        assert_eq!(status, StatusCode::NOT_FOUND, "{name}");
+    }
+}
+
+#[tokio::test]
+async fn webhook_routes_require_the_admin_token() {
+    let backend = common::backends().await.remove(0);
+    let app = setup_routes(backend.state());
+
+    for authorization in [
+        None,
+        Some("Bearer wrong-token-0123456789"),
+        Some(ADMIN_TOKEN),
+    ] {
+        let headers: Vec<(&str, &str)> = authorization
+            .map(|value| ("authorization", value))
+            .into_iter()
+            .collect();
+        let (status, headers, body) =
+            send_with(&app, Method::GET, "/api/v1/webhooks", None, &headers).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED, "{authorization:?}");
+        assert_eq!(
+            header_value(&headers, header::WWW_AUTHENTICATE.as_str()),
+            "Bearer",
+            "{authorization:?}"
+        );
+        assert_eq!(body["error"], "A valid admin token is required");
+    }
+    let (status, listed) = send_admin(&app, Method::GET, "/api/v1/webhooks", None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(listed, json!([]));
+
+    // Without a configured token, the administration endpoints are disabled.
+    let mut state = backend.state();
+    state.admin = Arc::new(AdminConfig::default());
+    let app = setup_routes(state);
+    let (status, body) = send_admin(&app, Method::GET, "/api/v1/webhooks", None).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    assert_eq!(
+        body["error"],
+        "The admin API is disabled; set ADMIN_TOKEN to enable it"
+    );
+}
+
+#[tokio::test]
+async fn webhooks_are_only_sent_to_public_addresses() {
+    let mut public = config(1);
+    public.allow_private_targets = false;
+
+    for backend in common::backends().await {
+        let name = backend.name;
+        let mut state = backend.state();
+        state.webhooks =
+            Webhooks::new(backend.webhooks.clone(), public.clone(), state.jobs.clone());
+        let app = setup_routes(state);
+
+        for url in [
+            "http://127.0.0.1/hook",
+            "http://localhost:8080/hook",
+            "http://169.254.169.254/latest/meta-data",
+            "http://10.0.0.1/hook",
+            "http://192.168.1.1/hook",
+            "http://[::1]/hook",
+            "http://[fd00::1]/hook",
+            "http://[::ffff:127.0.0.1]/hook",
+        ] {
+            let (status, body) = send_admin(
+                &app,
+                Method::POST,
+                "/api/v1/webhooks",
+                Some(json!({ "url": url, "secret": SECRET })),
+            )
+            .await;
+            assert_eq!(status, StatusCode::BAD_REQUEST, "{name}: {url}: {body}");
+        }
+
+        // A webhook stored before its URL was refused is not sent to either.
+        let (receiver, url) = Receiver::start().await;
+        let webhook = backend
+            .webhooks
+            .create(&WebhookBody {
+                url,
+                secret: SECRET.to_string(),
+                events: Vec::new(),
+            })
+            .await
+            .expect("webhook");
+        let ids = backend
+            .webhooks
+            .enqueue(WebhookEvent::PostCreated, &json!({}))
+            .await
+            .expect("delivery");
+        let deliver =
+            DeliverWebhook::new(backend.webhooks.clone(), public.clone()).expect("handler");
+        assert!(deliver.run(ids[0]).await.is_err(), "{name}");
+        assert!(receiver.requests().is_empty(), "{name}");
+
+        let deliveries = backend
+            .webhooks
+            .deliveries(webhook.id, &DeliveryFilter::default())
+            .await
+            .expect("deliveries");
+        assert_eq!(deliveries[0].status, DeliveryStatus::Failed, "{name}");
+        let error = deliveries[0].error.as_deref().unwrap_or_default();
+        assert!(error.contains("not a public address"), "{name}: {error}");
+    }
+}